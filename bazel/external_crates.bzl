@@ -393,6 +393,10 @@ def external_crates_repository(name, static_openssl, cargo_lockfile, lockfile):
             "hashlink": crate.spec(
                 version = "^0.8.0",
             ),
+            "hdrhistogram": crate.spec(
+                version = "^7.5.2",
+                default_features = False,
+            ),
             "hex": crate.spec(
                 version = "^0.4.3",
                 features = [
@@ -0,0 +1,17 @@
+use candid::{CandidType, Deserialize};
+
+use crate::icrc1::account::Account;
+use crate::icrc1::transfer::NumTokens;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AllowanceArgs {
+    pub account: Account,
+    pub spender: Account,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Allowance {
+    pub allowance: NumTokens,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
@@ -0,0 +1,39 @@
+use candid::{CandidType, Deserialize, Nat};
+
+use crate::icrc1::account::{Account, Subaccount};
+use crate::icrc1::transfer::{BlockIndex, Memo, NumTokens};
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ApproveArgs {
+    #[serde(default)]
+    pub from_subaccount: Option<Subaccount>,
+    pub spender: Account,
+    pub amount: NumTokens,
+    #[serde(default)]
+    pub expected_allowance: Option<NumTokens>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub fee: Option<NumTokens>,
+    #[serde(default)]
+    pub memo: Option<Memo>,
+    #[serde(default)]
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ApproveError {
+    BadFee { expected_fee: NumTokens },
+    // The caller does not have enough funds to pay the approval fee.
+    InsufficientFunds { balance: NumTokens },
+    // The caller specified the [expected_allowance] field, and the current
+    // allowance did not match the caller's expectation.
+    AllowanceChanged { current_allowance: NumTokens },
+    // The approval request expired before the ledger had a chance to apply it.
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: BlockIndex },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
@@ -0,0 +1,3 @@
+pub mod allowance;
+pub mod approve;
+pub mod transfer_from;
@@ -0,0 +1,34 @@
+use candid::{CandidType, Deserialize, Nat};
+
+use crate::icrc1::account::{Account, Subaccount};
+use crate::icrc1::transfer::{BlockIndex, Memo, NumTokens};
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferFromArgs {
+    #[serde(default)]
+    pub spender_subaccount: Option<Subaccount>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: NumTokens,
+    #[serde(default)]
+    pub fee: Option<NumTokens>,
+    #[serde(default)]
+    pub memo: Option<Memo>,
+    #[serde(default)]
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TransferFromError {
+    BadFee { expected_fee: NumTokens },
+    BadBurn { min_burn_amount: NumTokens },
+    // The [from] account does not hold enough funds for the transfer.
+    InsufficientFunds { balance: NumTokens },
+    // The caller exceeded its allowance.
+    InsufficientAllowance { allowance: NumTokens },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: BlockIndex },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
@@ -74,8 +74,8 @@ impl<Input: CandidType, Output: CandidType> CandidType for QueryArchiveFn<Input,
     fn _ty() -> candid::types::Type {
         candid::types::Type::Func(candid::types::Function {
             modes: vec![candid::parser::types::FuncMode::Query],
-            args: vec![Input::_ty()],
-            rets: vec![Output::_ty()],
+            args: vec![Input::ty()],
+            rets: vec![Output::ty()],
         })
     }
 
@@ -93,5 +93,25 @@ pub struct ArchiveInfo {
     pub block_range_start: BlockIndex,
     pub block_range_end: BlockIndex,
 }
+
+/// The argument of the ICRC-3 `icrc3_get_archives` endpoint.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetArchivesArgs {
+    /// The last archive seen by the client. The ledger returns
+    /// archives coming after this one if set, otherwise it returns
+    /// the first archives.
+    pub from: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ICRC3ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
+
+/// The result of the ICRC-3 `icrc3_get_archives` endpoint.
+pub type GetArchivesResult = Vec<ICRC3ArchiveInfo>;
+
 pub type QueryBlockArchiveFn = QueryArchiveFn<GetBlocksRequest, BlockRange>;
 pub type QueryTxArchiveFn = QueryArchiveFn<GetTransactionsRequest, TransactionRange>;
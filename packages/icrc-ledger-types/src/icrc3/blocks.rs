@@ -1,5 +1,5 @@
 use crate::icrc3::archive::ArchivedRange;
-use crate::icrc3::archive::QueryBlockArchiveFn;
+use crate::icrc3::archive::{QueryArchiveFn, QueryBlockArchiveFn};
 use crate::{icrc::generic_value::Value, icrc1::transfer::BlockIndex};
 use candid::{CandidType, Deserialize, Nat};
 use serde_bytes::ByteBuf;
@@ -52,3 +52,39 @@ pub struct DataCertificate {
     pub certificate: Option<serde_bytes::ByteBuf>,
     pub hash_tree: serde_bytes::ByteBuf,
 }
+
+/// The certificate of the tip of the block log, as returned by the
+/// ICRC-3 `icrc3_get_tip_certificate` endpoint.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct ICRC3DataCertificate {
+    /// The certificate of the canister's certified data.
+    pub certificate: serde_bytes::ByteBuf,
+    /// The CBOR-encoded hash tree that contains the last block index
+    /// and the hash of the last block.
+    pub hash_tree: serde_bytes::ByteBuf,
+}
+
+/// The argument of the ICRC-3 `icrc3_get_blocks` endpoint.
+pub type GetBlocksArgs = Vec<GetBlocksRequest>;
+
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: GenericBlock,
+}
+
+/// A list of block ranges that the client needs to fetch from an archive.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct ArchivedBlocks {
+    pub args: GetBlocksArgs,
+    pub callback: QueryArchiveFn<GetBlocksArgs, GetBlocksResult>,
+}
+
+/// The result of the ICRC-3 `icrc3_get_blocks` endpoint.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct GetBlocksResult {
+    /// The total number of blocks in the log.
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
@@ -41,6 +41,31 @@ pub struct Transfer {
     pub created_at_time: Option<u64>,
 }
 
+/// A transfer executed by [spender] on behalf of [from] using
+/// `icrc2_transfer_from`.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferFrom {
+    pub amount: Nat,
+    pub from: Account,
+    pub to: Account,
+    pub spender: Account,
+    pub memo: Option<Memo>,
+    pub fee: Option<Nat>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Approve {
+    pub from: Account,
+    pub spender: Account,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
+    pub memo: Option<Memo>,
+    pub fee: Option<Nat>,
+    pub created_at_time: Option<u64>,
+}
+
 // Representation of a Transaction which supports the Icrc1 Standard functionalities
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Transaction {
@@ -48,6 +73,8 @@ pub struct Transaction {
     pub mint: Option<Mint>,
     pub burn: Option<Burn>,
     pub transfer: Option<Transfer>,
+    pub transfer_from: Option<TransferFrom>,
+    pub approve: Option<Approve>,
     pub timestamp: u64,
 }
 
@@ -1,27 +1,267 @@
-use anyhow::Error;
+use std::{collections::HashMap, net::SocketAddr};
+
+use anyhow::{anyhow, Context, Error};
 use arc_swap::ArcSwapOption;
 use async_trait::async_trait;
+use futures::future::join_all;
+use ic_types::{
+    messages::{HttpStatusResponse, ReplicaHealthStatus},
+    NodeId,
+};
+use tracing::{info, warn};
+
+use crate::{
+    persist::Persist,
+    snapshot::{Node, RoutingTable, Subnet},
+    Run,
+};
+
+pub struct CheckResult {
+    pub height: u64,
+}
+
+#[async_trait]
+pub trait Check: Send + Sync {
+    async fn check(&self, node: &Node) -> Result<CheckResult, Error>;
+}
+
+pub struct Checker {
+    http_client: reqwest::Client,
+}
+
+impl Checker {
+    pub fn new(http_client: reqwest::Client) -> Self {
+        Self { http_client }
+    }
+}
+
+#[async_trait]
+impl Check for Checker {
+    async fn check(&self, node: &Node) -> Result<CheckResult, Error> {
+        let addr = SocketAddr::new(node.addr, node.port);
 
-use crate::{persist::Persist, snapshot::RoutingTable, Run};
+        let response = self
+            .http_client
+            .get(format!("http://{addr}/api/v2/status"))
+            .send()
+            .await
+            .context("request failed")?;
 
-pub struct Runner<'a, P: Persist> {
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(anyhow!("request failed with status {}", response.status()));
+        }
+
+        let body = response
+            .bytes()
+            .await
+            .context("failed to get response bytes")?;
+
+        let HttpStatusResponse {
+            replica_health_status,
+            certified_height,
+            ..
+        } = serde_cbor::from_slice(&body).context("failed to parse cbor response")?;
+
+        if replica_health_status != Some(ReplicaHealthStatus::Healthy) {
+            return Err(anyhow!("replica reported unhealthy status"));
+        }
+
+        Ok(CheckResult {
+            height: certified_height.map_or(0, |v| v.get()),
+        })
+    }
+}
+
+/// The outcome of the health checks of a node
+struct NodeCheck {
+    node: Node,
+    /// Number of consecutive successful checks, capped at `min_ok_count`
+    ok_count: u8,
+    /// Certified height reported by the last check, if it succeeded
+    height: Option<u64>,
+}
+
+pub struct Runner<'a, P: Persist, C: Check> {
     published_routing_table: &'a ArcSwapOption<RoutingTable>,
     persist: P,
+    checker: C,
+    ok_counts: HashMap<NodeId, u8>,
+
+    // Configuration
+    min_ok_count: u8,
+    max_height_lag: u64,
 }
 
-impl<'a, P: Persist> Runner<'a, P> {
-    pub fn new(published_routing_table: &'a ArcSwapOption<RoutingTable>, persist: P) -> Self {
+impl<'a, P: Persist, C: Check> Runner<'a, P, C> {
+    pub fn new(
+        published_routing_table: &'a ArcSwapOption<RoutingTable>,
+        persist: P,
+        checker: C,
+        min_ok_count: u8,
+        max_height_lag: u64,
+    ) -> Self {
         Self {
             published_routing_table,
             persist,
+            checker,
+            ok_counts: HashMap::new(),
+            min_ok_count,
+            max_height_lag,
         }
     }
 }
 
+/// Returns the nodes that passed at least `min_ok_count` consecutive checks
+/// and whose certified height lags at most `max_height_lag` behind the median
+/// height of the subnet
+fn healthy_nodes(checks: Vec<NodeCheck>, min_ok_count: u8, max_height_lag: u64) -> Vec<Node> {
+    let mut heights = checks
+        .iter()
+        .filter_map(|check| check.height)
+        .collect::<Vec<_>>();
+
+    // We use the median because it's a good approximation of
+    // the "consensus" and keeps us resilient to malicious replicas
+    // sending an artificially high height to DOS the BNs
+    let min_height = if !heights.is_empty() {
+        heights.sort_unstable();
+        let median_height = (heights[(heights.len() - 1) / 2] + heights[heights.len() / 2]) / 2;
+        median_height.saturating_sub(max_height_lag)
+    } else {
+        0
+    };
+
+    checks
+        .into_iter()
+        .filter(|check| check.ok_count >= min_ok_count)
+        .filter(|check| check.height.map_or(false, |height| height >= min_height))
+        .map(|check| check.node)
+        .collect()
+}
+
 #[async_trait]
-impl<'a, P: Persist> Run for Runner<'a, P> {
+impl<'a, P: Persist, C: Check> Run for Runner<'a, P, C> {
     async fn run(&mut self) -> Result<(), Error> {
-        // TODO(BOUN-725): Implement health check, similar to rs/boundary_node/control_plane/src/check.rs
-        todo!()
+        let rt = self
+            .published_routing_table
+            .load_full()
+            .ok_or_else(|| anyhow!("routing table not available"))?;
+
+        // Check all nodes concurrently
+        let nodes = rt.subnets.iter().flat_map(|subnet| &subnet.nodes);
+        let results = join_all(nodes.clone().map(|node| self.checker.check(node))).await;
+
+        // Update the ok counts, dropping nodes that are no longer in the registry
+        let mut ok_counts = HashMap::new();
+        let mut heights = HashMap::new();
+        for (node, result) in nodes.zip(results) {
+            let ok_count = match result {
+                Ok(CheckResult { height }) => {
+                    heights.insert(node.id, height);
+                    let ok_count = self.ok_counts.get(&node.id).copied().unwrap_or_default();
+                    self.min_ok_count.min(ok_count.saturating_add(1))
+                }
+                Err(error) => {
+                    warn!(node_id = %node.id, ?error, "health check failed");
+                    0
+                }
+            };
+            ok_counts.insert(node.id, ok_count);
+        }
+        self.ok_counts = ok_counts;
+
+        let subnets = rt
+            .subnets
+            .iter()
+            .map(|subnet| {
+                let checks = subnet
+                    .nodes
+                    .iter()
+                    .map(|node| NodeCheck {
+                        node: node.clone(),
+                        ok_count: self.ok_counts[&node.id],
+                        height: heights.get(&node.id).copied(),
+                    })
+                    .collect();
+
+                Subnet {
+                    id: subnet.id,
+                    nodes: healthy_nodes(checks, self.min_ok_count, self.max_height_lag),
+                }
+            })
+            .collect();
+
+        let status = self
+            .persist
+            .persist(RoutingTable {
+                subnets,
+                ..RoutingTable::clone(&rt)
+            })
+            .await
+            .context("failed to persist routes")?;
+
+        info!(
+            registry_version = rt.registry_version,
+            ?status,
+            "persisted routes"
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use ic_types::PrincipalId;
+
+    use super::*;
+
+    fn node_check(id: u64, ok_count: u8, height: Option<u64>) -> NodeCheck {
+        NodeCheck {
+            node: Node {
+                id: NodeId::from(PrincipalId::new_node_test_id(id)),
+                addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, id as u8)),
+                port: 8080,
+            },
+            ok_count,
+            height,
+        }
+    }
+
+    fn ids(nodes: Vec<Node>) -> Vec<NodeId> {
+        nodes.into_iter().map(|node| node.id).collect()
+    }
+
+    #[test]
+    fn healthy_nodes_filters_failed_checks() {
+        let checks = vec![
+            node_check(1, 2, Some(10)),
+            node_check(2, 1, Some(10)),
+            node_check(3, 0, None),
+        ];
+
+        assert_eq!(
+            ids(healthy_nodes(checks, 2, 1000)),
+            vec![NodeId::from(PrincipalId::new_node_test_id(1))]
+        );
+    }
+
+    #[test]
+    fn healthy_nodes_filters_lagging_nodes() {
+        let checks = vec![
+            node_check(1, 1, Some(10)),
+            node_check(2, 1, Some(1011)),
+            node_check(3, 1, Some(1011)),
+        ];
+
+        assert_eq!(
+            ids(healthy_nodes(checks, 1, 1000)),
+            vec![
+                NodeId::from(PrincipalId::new_node_test_id(2)),
+                NodeId::from(PrincipalId::new_node_test_id(3)),
+            ]
+        );
     }
 }
@@ -0,0 +1,315 @@
+use std::{
+    collections::HashMap, hash::Hash, net::IpAddr, str::FromStr, sync::Mutex, time::Instant,
+};
+
+use anyhow::{anyhow, Context, Error};
+use ic_types::{PrincipalId, SubnetId};
+
+// Above this number of buckets, full buckets are dropped, as they
+// are equivalent to buckets that were never used
+const PRUNE_THRESHOLD: usize = 100_000;
+
+/// A rate limit of `rate` requests per second, allowing bursts of up to `burst` requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+/// Parses `<RATE>` or `<RATE>:<BURST>`, where the burst defaults to the rate (at least 1)
+impl FromStr for RateLimit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = match s.split_once(':') {
+            Some((rate, burst)) => (rate, Some(burst)),
+            None => (s, None),
+        };
+
+        let rate: f64 = rate.parse().context("failed to parse rate")?;
+        let burst: f64 = match burst {
+            Some(burst) => burst.parse().context("failed to parse burst")?,
+            None => rate.max(1.),
+        };
+
+        if !rate.is_finite() || rate <= 0. {
+            return Err(anyhow!("rate must be positive"));
+        }
+        if !burst.is_finite() || burst < 1. {
+            return Err(anyhow!("burst must be at least 1"));
+        }
+
+        Ok(Self { rate, burst })
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token buckets for a rate limit, one per key
+pub struct TokenBuckets<K> {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> TokenBuckets<K> {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of the given key, returning whether one was available
+    pub fn try_acquire(&self, key: K, now: Instant) -> bool {
+        let RateLimit { rate, burst } = self.limit;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= PRUNE_THRESHOLD && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.saturating_duration_since(bucket.last_refill);
+                bucket.tokens + elapsed.as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.last_refill = bucket.last_refill.max(now);
+
+        if bucket.tokens < 1. {
+            return false;
+        }
+
+        bucket.tokens -= 1.;
+        true
+    }
+
+    /// Returns a token taken by `try_acquire` to the bucket of the given key
+    pub fn refund(&self, key: &K) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(key) {
+            bucket.tokens = (bucket.tokens + 1.).min(self.limit.burst);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    Subnet,
+    Canister,
+    Method,
+    Ip,
+}
+
+/// The rate limits applied to API calls, each of them optional
+#[derive(Default)]
+pub struct RateLimits {
+    per_subnet: Option<TokenBuckets<SubnetId>>,
+    per_canister: Option<TokenBuckets<PrincipalId>>,
+    per_method: Option<TokenBuckets<(PrincipalId, String)>>,
+    per_ip: Option<TokenBuckets<IpAddr>>,
+}
+
+impl RateLimits {
+    pub fn new(
+        per_subnet: Option<RateLimit>,
+        per_canister: Option<RateLimit>,
+        per_method: Option<RateLimit>,
+        per_ip: Option<RateLimit>,
+    ) -> Self {
+        Self {
+            per_subnet: per_subnet.map(TokenBuckets::new),
+            per_canister: per_canister.map(TokenBuckets::new),
+            per_method: per_method.map(TokenBuckets::new),
+            per_ip: per_ip.map(TokenBuckets::new),
+        }
+    }
+
+    pub fn check_ip(&self, ip: IpAddr, now: Instant) -> Result<(), LimitKind> {
+        check(&self.per_ip, ip, now, LimitKind::Ip)
+    }
+
+    /// Checks the limits of the subnet, the canister and, if given, the method
+    /// called. A token is taken from each of them only if all of them have one
+    pub fn check_call(
+        &self,
+        subnet_id: SubnetId,
+        canister_id: PrincipalId,
+        method_name: Option<&str>,
+        now: Instant,
+    ) -> Result<(), LimitKind> {
+        check(&self.per_subnet, subnet_id, now, LimitKind::Subnet)?;
+
+        if let Err(kind) = check(&self.per_canister, canister_id, now, LimitKind::Canister) {
+            refund(&self.per_subnet, &subnet_id);
+            return Err(kind);
+        }
+
+        if let Some(method_name) = method_name {
+            let key = (canister_id, method_name.to_string());
+            if let Err(kind) = check(&self.per_method, key, now, LimitKind::Method) {
+                refund(&self.per_canister, &canister_id);
+                refund(&self.per_subnet, &subnet_id);
+                return Err(kind);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check<K: Hash + Eq>(
+    buckets: &Option<TokenBuckets<K>>,
+    key: K,
+    now: Instant,
+    kind: LimitKind,
+) -> Result<(), LimitKind> {
+    match buckets {
+        Some(buckets) if !buckets.try_acquire(key, now) => Err(kind),
+        _ => Ok(()),
+    }
+}
+
+fn refund<K: Hash + Eq>(buckets: &Option<TokenBuckets<K>>, key: &K) {
+    if let Some(buckets) = buckets {
+        buckets.refund(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn parse_rate_limit() {
+        assert_eq!(
+            "10".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                rate: 10.,
+                burst: 10.
+            }
+        );
+        assert_eq!(
+            "0.5:20".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                rate: 0.5,
+                burst: 20.
+            }
+        );
+        assert_eq!(
+            "0.5".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                rate: 0.5,
+                burst: 1.
+            }
+        );
+
+        for s in ["", "abc", "0", "-1", "10:0", "10:abc", "inf"] {
+            assert!(s.parse::<RateLimit>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn token_buckets() {
+        let buckets = TokenBuckets::new(RateLimit {
+            rate: 2.,
+            burst: 3.,
+        });
+        let now = Instant::now();
+
+        // The burst is available immediately
+        for _ in 0..3 {
+            assert!(buckets.try_acquire("a", now));
+        }
+        assert!(!buckets.try_acquire("a", now));
+
+        // Keys have independent buckets
+        assert!(buckets.try_acquire("b", now));
+
+        // Tokens are refilled at the given rate
+        let now = now + Duration::from_millis(500);
+        assert!(buckets.try_acquire("a", now));
+        assert!(!buckets.try_acquire("a", now));
+
+        // Up to the burst
+        let now = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(buckets.try_acquire("a", now));
+        }
+        assert!(!buckets.try_acquire("a", now));
+    }
+
+    #[test]
+    fn rate_limits() {
+        let limit = RateLimit {
+            rate: 1.,
+            burst: 1.,
+        };
+        let limits = RateLimits::new(None, None, Some(limit), None);
+
+        let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(1));
+        let canister_id = PrincipalId::new_user_test_id(1);
+        let now = Instant::now();
+
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("a"), now),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("a"), now),
+            Err(LimitKind::Method)
+        );
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("b"), now),
+            Ok(())
+        );
+        assert_eq!(limits.check_call(subnet_id, canister_id, None, now), Ok(()));
+    }
+
+    #[test]
+    fn rejected_calls_do_not_take_tokens() {
+        let limits = RateLimits::new(
+            Some(RateLimit {
+                rate: 1.,
+                burst: 2.,
+            }),
+            None,
+            Some(RateLimit {
+                rate: 1.,
+                burst: 1.,
+            }),
+            None,
+        );
+
+        let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(1));
+        let canister_id = PrincipalId::new_user_test_id(1);
+        let now = Instant::now();
+
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("a"), now),
+            Ok(())
+        );
+        // The subnet token taken before the method limit is hit is refunded
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("a"), now),
+            Err(LimitKind::Method)
+        );
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("b"), now),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check_call(subnet_id, canister_id, Some("c"), now),
+            Err(LimitKind::Subnet)
+        );
+    }
+}
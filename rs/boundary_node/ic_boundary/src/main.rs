@@ -1,26 +1,40 @@
-// TODO: remove
-#![allow(unused)]
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use std::{net::SocketAddr, path::PathBuf};
-
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, Context, Error};
 use arc_swap::ArcSwapOption;
 use async_scoped::TokioScope;
 use async_trait::async_trait;
 use axum::{routing::method_routing::get, Router};
 use clap::Parser;
 use futures::TryFutureExt;
+use ic_crypto_utils_threshold_sig_der::{parse_threshold_sig_key, public_key_to_der};
+use ic_logger::new_replica_logger_from_config;
+use ic_registry_client::client::RegistryClientImpl;
+use ic_registry_local_store::LocalStoreImpl;
+use ic_registry_replicator::RegistryReplicator;
 use lazy_static::lazy_static;
 use prometheus::{labels, Registry as MetricsRegistry};
 use tracing::{error, info};
+use url::Url;
 
 mod check;
+mod limit;
 mod metrics;
 mod persist;
+mod routes;
 mod snapshot;
+mod validate;
 
 const SERVICE_NAME: &str = "ic-boundary";
 
+const SECOND: Duration = Duration::from_secs(1);
+const MINUTE: Duration = Duration::from_secs(60);
+
 #[derive(Parser)]
 #[clap(name = SERVICE_NAME)]
 #[clap(author = "Boundary Node Team <boundary-nodes@dfinity.org>")]
@@ -37,10 +51,46 @@ struct Cli {
     #[clap(long)]
     pub local_store_path: PathBuf,
 
+    /// Delay in seconds between polls of the NNS for registry updates
+    #[clap(long, default_value = "5")]
+    pub registry_poll_delay_secs: u64,
+
     /// The socket used to export metrics.
     #[clap(long, default_value = "127.0.0.1:9090")]
     metrics_addr: SocketAddr,
 
+    /// The socket used to serve API calls
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    http_addr: SocketAddr,
+
+    /// Maximum number of nodes to try before failing an API call
+    #[clap(long, default_value = "3")]
+    max_attempts: usize,
+
+    /// Timeout in seconds for API calls forwarded to replicas
+    #[clap(long, default_value = "60")]
+    request_timeout_secs: u64,
+
+    /// Maximum size in bytes of the body of API calls
+    #[clap(long, default_value = "5242880")]
+    max_request_body_size: usize,
+
+    /// Rate limit per subnet, as <REQUESTS_PER_SECOND>[:<BURST>]
+    #[clap(long)]
+    rate_limit_per_subnet: Option<limit::RateLimit>,
+
+    /// Rate limit per canister, as <REQUESTS_PER_SECOND>[:<BURST>]
+    #[clap(long)]
+    rate_limit_per_canister: Option<limit::RateLimit>,
+
+    /// Rate limit per canister method, as <REQUESTS_PER_SECOND>[:<BURST>]
+    #[clap(long)]
+    rate_limit_per_method: Option<limit::RateLimit>,
+
+    /// Rate limit per client IP address, as <REQUESTS_PER_SECOND>[:<BURST>]
+    #[clap(long)]
+    rate_limit_per_ip: Option<limit::RateLimit>,
+
     /// The path to the nftables replica ruleset file to update
     #[clap(long, default_value = "/tmp/system_replicas.ruleset")]
     nftables_system_replicas_path: PathBuf,
@@ -97,18 +147,75 @@ async fn main() -> Result<(), Error> {
     info!(
         msg = format!("Starting {SERVICE_NAME}"),
         metrics_addr = cli.metrics_addr.to_string().as_str(),
+        http_addr = cli.http_addr.to_string().as_str(),
+    );
+
+    let nns_urls = cli
+        .nns_url
+        .split(',')
+        .map(|url| Url::parse(url.trim()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to parse nns urls")?;
+    let nns_pub_key =
+        parse_threshold_sig_key(&cli.nns_pub_key_pem).context("failed to parse nns public key")?;
+    let root_key = public_key_to_der(&nns_pub_key.into_bytes())
+        .map_err(|err| anyhow!("failed to encode nns public key: {err}"))?;
+
+    let local_store = Arc::new(LocalStoreImpl::new(&cli.local_store_path));
+    let registry_client = Arc::new(RegistryClientImpl::new(local_store.clone(), None));
+
+    // The replicator populates the local store from the NNS, and keeps polling
+    // it for updates until it is dropped at the end of main
+    let (replicator_logger, _async_log_guard) = new_replica_logger_from_config(&Default::default());
+    let registry_replicator = RegistryReplicator::new_with_clients(
+        replicator_logger,
+        local_store,
+        registry_client.clone(),
+        Duration::from_secs(cli.registry_poll_delay_secs),
     );
+    // Returns once the local store is initialized, without awaiting the polling task
+    registry_replicator
+        .start_polling(nns_urls, Some(nns_pub_key))
+        .await
+        .context("failed to start registry replicator")?;
 
-    // TODO(BOUN-721): add registry_replicator.start_polling (rs/orchestrator/registry_replicator/src/main.rs)
-    // await the result, but don't await the thread handle
+    registry_client
+        .fetch_and_start_polling()
+        .context("failed to start registry client")?;
 
-    #[allow(clippy::let_unit_value)]
-    let registry_client = None.expect("TODO(BOUN-722): create a local client");
-    let snapshot_runner = snapshot::Runner::new(&routing_table, registry_client);
+    let snapshot_runner =
+        snapshot::Runner::new(&routing_table, registry_client, cli.min_registry_version);
+    let snapshot_runner = WithThrottle(snapshot_runner, ThrottleParams::new(MINUTE));
 
     let persister = persist::Persister::new(&ROUTES);
-    let check_runner = check::Runner::new(&routing_table, persister);
-    let _ = ROUTES;
+    let checker = check::Checker::new(reqwest::Client::builder().timeout(10 * SECOND).build()?);
+    let check_runner = check::Runner::new(
+        &routing_table,
+        persister,
+        checker,
+        cli.min_ok_count,
+        cli.max_height_lag,
+    );
+    let check_runner = WithThrottle(check_runner, ThrottleParams::new(10 * SECOND));
+
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(cli.request_timeout_secs))
+        .build()?;
+    let limits = limit::RateLimits::new(
+        cli.rate_limit_per_subnet,
+        cli.rate_limit_per_canister,
+        cli.rate_limit_per_method,
+        cli.rate_limit_per_ip,
+    );
+    let api_router = routes::router(routes::ProxyState::new(
+        &ROUTES,
+        http_client,
+        Some(root_key),
+        cli.max_attempts,
+        cli.max_request_body_size,
+        limits,
+        metrics::ProxyMetrics::new(metrics),
+    ));
 
     TokioScope::scope_and_block(|s| {
         let metrics_handler = || metrics::handler(metrics);
@@ -135,11 +242,43 @@ async fn main() -> Result<(), Error> {
                 }
             }
         });
-
-        // TODO(BOUN-726): Setup axum server for api calls
-        // TODO(BOUN-727): Setup route for non-status calls
-        // TODO(BOUN-728): Setup route for status calls
+        s.spawn(
+            axum::Server::bind(&cli.http_addr)
+                .serve(api_router.into_make_service_with_connect_info::<SocketAddr>())
+                .map_err(|err| anyhow!("server failed: {:?}", err)),
+        );
     });
 
     Ok(())
 }
+
+struct ThrottleParams {
+    throttle_duration: Duration,
+    next_time: Option<Instant>,
+}
+
+impl ThrottleParams {
+    fn new(throttle_duration: Duration) -> Self {
+        Self {
+            throttle_duration,
+            next_time: None,
+        }
+    }
+}
+
+struct WithThrottle<T>(T, ThrottleParams);
+
+#[async_trait]
+impl<T: Run + Send + Sync> Run for WithThrottle<T> {
+    async fn run(&mut self) -> Result<(), Error> {
+        let current_time = Instant::now();
+        let next_time = self.1.next_time.unwrap_or(current_time);
+
+        if next_time > current_time {
+            tokio::time::sleep(next_time - current_time).await;
+        }
+        self.1.next_time = Some(Instant::now() + self.1.throttle_duration);
+
+        self.0.run().await
+    }
+}
@@ -2,7 +2,7 @@ use axum::{
     body::Body,
     http::{Response, StatusCode},
 };
-use prometheus::{Encoder, Registry, TextEncoder};
+use prometheus::{Encoder, IntCounterVec, Opts, Registry, TextEncoder};
 
 pub async fn handler(registry: &Registry) -> Response<Body> {
     let metric_families = registry.gather();
@@ -22,3 +22,34 @@ pub async fn handler(registry: &Registry) -> Response<Body> {
         .body(metrics_text.into())
         .unwrap()
 }
+
+#[derive(Clone)]
+pub struct ProxyMetrics {
+    rejections: IntCounterVec,
+}
+
+impl ProxyMetrics {
+    pub fn new(registry: &Registry) -> Self {
+        let rejections = IntCounterVec::new(
+            Opts::new(
+                "requests_rejected_total",
+                "Number of API calls rejected or failed, by reason",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(rejections.clone())).unwrap();
+
+        Self { rejections }
+    }
+
+    pub fn record_rejection(&self, reason: &str) {
+        self.rejections.with_label_values(&[reason]).inc();
+    }
+
+    #[cfg(test)]
+    pub fn rejections(&self, reason: &str) -> u64 {
+        self.rejections.with_label_values(&[reason]).get()
+    }
+}
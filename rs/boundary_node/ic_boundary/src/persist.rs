@@ -1,13 +1,59 @@
+use std::{collections::HashMap, sync::Arc};
+
 use anyhow::Error;
 use arc_swap::ArcSwapOption;
 use async_trait::async_trait;
+use ic_registry_routing_table::RoutingTable as CanisterRoutingTable;
+use ic_types::{PrincipalId, SubnetId};
 
-use crate::{snapshot::RoutingTable, Run};
+use crate::snapshot::{Node, RoutingTable};
 
+/// Routes canister IDs to subnets and subnets to their healthy nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Routes {
-    // TODO(BOUN-723): Implement Routes table mapping canister ranges to subnets and subnets to nodes
+    pub registry_version: u64,
+    pub nns_subnet_id: SubnetId,
+    canister_routes: CanisterRoutingTable,
+    subnets: HashMap<SubnetId, Vec<Node>>,
+}
+
+impl Routes {
+    /// Returns the subnet hosting the given effective canister ID (or the
+    /// subnet itself, for subnet IDs), along with the healthy nodes of the
+    /// subnet, which may be empty
+    pub fn lookup(&self, id: PrincipalId) -> Option<(SubnetId, &[Node])> {
+        let subnet_id = self.canister_routes.route(id)?;
+        let nodes = self
+            .subnets
+            .get(&subnet_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        Some((subnet_id, nodes))
+    }
+
+    /// Returns whether there is any healthy node to route to
+    pub fn is_empty(&self) -> bool {
+        self.subnets.values().all(Vec::is_empty)
+    }
+}
+
+impl From<RoutingTable> for Routes {
+    fn from(rt: RoutingTable) -> Self {
+        Self {
+            registry_version: rt.registry_version,
+            nns_subnet_id: rt.nns_subnet_id,
+            canister_routes: rt.canister_routes,
+            subnets: rt
+                .subnets
+                .into_iter()
+                .map(|subnet| (subnet.id, subnet.nodes))
+                .collect(),
+        }
+    }
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum PersistStatus {
     Completed,
     SkippedUnchanged,
@@ -32,6 +78,132 @@ impl<'a> Persister<'a> {
 #[async_trait]
 impl<'a> Persist for Persister<'a> {
     async fn persist(&self, rt: RoutingTable) -> Result<PersistStatus, Error> {
-        todo!("TODO(BOUN-724): Implement mapping of RoutingTable into Routes")
+        let routes = Routes::from(rt);
+
+        // Keep the previous routes rather than dropping all traffic
+        if routes.is_empty() {
+            return Ok(PersistStatus::SkippedEmpty);
+        }
+
+        if self.published_routes.load().as_deref() == Some(&routes) {
+            return Ok(PersistStatus::SkippedUnchanged);
+        }
+
+        self.published_routes.store(Some(Arc::new(routes)));
+
+        Ok(PersistStatus::Completed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use ic_registry_routing_table::CanisterIdRange;
+    use ic_types::{CanisterId, NodeId};
+
+    use super::*;
+    use crate::snapshot::Subnet;
+
+    fn subnet_id(id: u64) -> SubnetId {
+        SubnetId::from(PrincipalId::new_subnet_test_id(id))
+    }
+
+    fn node(id: u64) -> Node {
+        Node {
+            id: NodeId::from(PrincipalId::new_node_test_id(id)),
+            addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, id as u8)),
+            port: 8080,
+        }
+    }
+
+    fn routing_table(nodes: Vec<Node>) -> RoutingTable {
+        let mut canister_routes = CanisterRoutingTable::new();
+        canister_routes
+            .insert(
+                CanisterIdRange {
+                    start: CanisterId::from_u64(0),
+                    end: CanisterId::from_u64(0xff),
+                },
+                subnet_id(1),
+            )
+            .unwrap();
+        canister_routes
+            .insert(
+                CanisterIdRange {
+                    start: CanisterId::from_u64(0x100),
+                    end: CanisterId::from_u64(0x1ff),
+                },
+                subnet_id(2),
+            )
+            .unwrap();
+
+        RoutingTable {
+            registry_version: 1,
+            nns_subnet_id: subnet_id(1),
+            canister_routes,
+            subnets: vec![
+                Subnet {
+                    id: subnet_id(1),
+                    nodes,
+                },
+                Subnet {
+                    id: subnet_id(2),
+                    nodes: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let routes = Routes::from(routing_table(vec![node(1), node(2)]));
+
+        let canister_id = CanisterId::from_u64(0x10).get();
+        assert_eq!(
+            routes.lookup(canister_id),
+            Some((subnet_id(1), &[node(1), node(2)][..]))
+        );
+
+        let canister_id = CanisterId::from_u64(0x110).get();
+        assert_eq!(routes.lookup(canister_id), Some((subnet_id(2), &[][..])));
+
+        let canister_id = CanisterId::from_u64(0x210).get();
+        assert_eq!(routes.lookup(canister_id), None);
+
+        // Subnet IDs are routed to the subnet itself
+        assert_eq!(
+            routes.lookup(subnet_id(1).get()),
+            Some((subnet_id(1), &[node(1), node(2)][..]))
+        );
+    }
+
+    #[tokio::test]
+    async fn persist() {
+        let published_routes = ArcSwapOption::const_empty();
+        let persister = Persister::new(&published_routes);
+
+        let out = persister.persist(routing_table(vec![])).await.unwrap();
+        assert_eq!(out, PersistStatus::SkippedEmpty);
+        assert!(published_routes.load().is_none());
+
+        let out = persister
+            .persist(routing_table(vec![node(1)]))
+            .await
+            .unwrap();
+        assert_eq!(out, PersistStatus::Completed);
+        assert_eq!(
+            published_routes
+                .load_full()
+                .unwrap()
+                .lookup(subnet_id(1).get()),
+            Some((subnet_id(1), &[node(1)][..]))
+        );
+
+        let out = persister
+            .persist(routing_table(vec![node(1)]))
+            .await
+            .unwrap();
+        assert_eq!(out, PersistStatus::SkippedUnchanged);
     }
 }
@@ -0,0 +1,638 @@
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use arc_swap::ArcSwapOption;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{ConnectInfo, Path, State},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use ic_types::{
+    messages::{Blob, HttpStatusResponse, ReplicaHealthStatus, IC_API_VERSION},
+    PrincipalId,
+};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    limit::{LimitKind, RateLimits},
+    metrics::ProxyMetrics,
+    persist::Routes,
+    snapshot::Node,
+    validate::{validate, RequestType, ValidationError},
+};
+
+const CONTENT_TYPE_CBOR: &str = "application/cbor";
+
+pub struct ProxyState {
+    published_routes: &'static ArcSwapOption<Routes>,
+    http_client: reqwest::Client,
+    /// DER-encoded NNS public key reported on status requests
+    root_key: Option<Vec<u8>>,
+    max_attempts: usize,
+    max_body_size: usize,
+    limits: RateLimits,
+    metrics: ProxyMetrics,
+}
+
+impl ProxyState {
+    pub fn new(
+        published_routes: &'static ArcSwapOption<Routes>,
+        http_client: reqwest::Client,
+        root_key: Option<Vec<u8>>,
+        max_attempts: usize,
+        max_body_size: usize,
+        limits: RateLimits,
+        metrics: ProxyMetrics,
+    ) -> Self {
+        Self {
+            published_routes,
+            http_client,
+            root_key,
+            max_attempts,
+            max_body_size,
+            limits,
+            metrics,
+        }
+    }
+}
+
+pub fn router(state: ProxyState) -> Router {
+    Router::new()
+        .route("/api/v2/status", get(status))
+        .route("/api/v2/canister/:canister_id/:endpoint", post(proxy))
+        .with_state(Arc::new(state))
+}
+
+/// The reasons for rejecting or failing an API call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorCause {
+    UnknownEndpoint,
+    InvalidCanisterId,
+    BodyTooLarge,
+    BodyReadFailed,
+    InvalidRequest(ValidationError),
+    RateLimited(LimitKind),
+    RoutingTableUnavailable,
+    CanisterNotRoutable,
+    NoHealthyNodes,
+    ReplicaUnavailable,
+}
+
+impl ErrorCause {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownEndpoint | Self::CanisterNotRoutable => StatusCode::NOT_FOUND,
+            Self::InvalidCanisterId | Self::BodyReadFailed | Self::InvalidRequest(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::RoutingTableUnavailable | Self::NoHealthyNodes => StatusCode::SERVICE_UNAVAILABLE,
+            Self::ReplicaUnavailable => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// The label of the cause in the rejection metrics
+    fn reason(&self) -> &'static str {
+        match self {
+            Self::UnknownEndpoint => "unknown_endpoint",
+            Self::InvalidCanisterId => "invalid_canister_id",
+            Self::BodyTooLarge => "body_too_large",
+            Self::BodyReadFailed => "body_read_failed",
+            Self::InvalidRequest(ValidationError::Malformed) => "malformed_request",
+            Self::InvalidRequest(ValidationError::Expired) => "ingress_expired",
+            Self::InvalidRequest(ValidationError::ExpiryTooFar) => "ingress_expiry_too_far",
+            Self::InvalidRequest(ValidationError::InvalidSender) => "invalid_sender",
+            Self::RateLimited(LimitKind::Subnet) => "rate_limited_subnet",
+            Self::RateLimited(LimitKind::Canister) => "rate_limited_canister",
+            Self::RateLimited(LimitKind::Method) => "rate_limited_method",
+            Self::RateLimited(LimitKind::Ip) => "rate_limited_ip",
+            Self::RoutingTableUnavailable => "routing_table_unavailable",
+            Self::CanisterNotRoutable => "canister_not_routable",
+            Self::NoHealthyNodes => "no_healthy_nodes",
+            Self::ReplicaUnavailable => "replica_unavailable",
+        }
+    }
+
+    fn details(&self) -> &'static str {
+        match self {
+            Self::UnknownEndpoint => "unknown endpoint",
+            Self::InvalidCanisterId => "invalid canister id",
+            Self::BodyTooLarge => "request body too large",
+            Self::BodyReadFailed => "failed to read request body",
+            Self::InvalidRequest(ValidationError::Malformed) => "malformed request envelope",
+            Self::InvalidRequest(ValidationError::Expired) => "ingress expiry lies in the past",
+            Self::InvalidRequest(ValidationError::ExpiryTooFar) => {
+                "ingress expiry lies too far in the future"
+            }
+            Self::InvalidRequest(ValidationError::InvalidSender) => "invalid sender",
+            Self::RateLimited(_) => "rate limit exceeded",
+            Self::RoutingTableUnavailable => "routing table not available",
+            Self::CanisterNotRoutable => "canister id not routable",
+            Self::NoHealthyNodes => "no healthy nodes available",
+            Self::ReplicaUnavailable => "all attempts failed",
+        }
+    }
+}
+
+impl IntoResponse for ErrorCause {
+    fn into_response(self) -> Response {
+        error_response(self.status_code(), self.details())
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, format!("{message}\n")).into_response()
+}
+
+fn cbor_response(status: StatusCode, body: Vec<u8>) -> Response {
+    (status, [(CONTENT_TYPE, CONTENT_TYPE_CBOR)], body).into_response()
+}
+
+/// Answers status requests locally instead of forwarding them to a replica
+async fn status(State(state): State<Arc<ProxyState>>) -> Response {
+    let has_routes = state
+        .published_routes
+        .load()
+        .as_deref()
+        .map_or(false, |routes| !routes.is_empty());
+
+    let response = HttpStatusResponse {
+        ic_api_version: IC_API_VERSION.to_string(),
+        root_key: state.root_key.clone().map(Blob),
+        impl_version: None,
+        impl_hash: None,
+        replica_health_status: Some(if has_routes {
+            ReplicaHealthStatus::Healthy
+        } else {
+            ReplicaHealthStatus::Starting
+        }),
+        certified_height: None,
+    };
+
+    let mut ser = serde_cbor::Serializer::new(Vec::new());
+    ser.self_describe().expect("failed to write cbor magic tag");
+    match response.serialize(&mut ser) {
+        Ok(()) => cbor_response(StatusCode::OK, ser.into_inner()),
+        Err(err) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("failed to serialize status: {err}"),
+        ),
+    }
+}
+
+async fn proxy(
+    State(state): State<Arc<ProxyState>>,
+    Path((canister_id, endpoint)): Path<(String, String)>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let client_addr = connect_info.map(|ConnectInfo(addr)| addr);
+
+    match forward(&state, &canister_id, &endpoint, client_addr, headers, body).await {
+        Ok(response) => response,
+        Err(cause) => {
+            state.metrics.record_rejection(cause.reason());
+            cause.into_response()
+        }
+    }
+}
+
+/// Reads the request body, rejecting it as soon as it exceeds `max_size`
+async fn read_body(
+    headers: &HeaderMap,
+    mut body: Body,
+    max_size: usize,
+) -> Result<Bytes, ErrorCause> {
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+
+    if content_length.map_or(false, |length| length > max_size) {
+        return Err(ErrorCause::BodyTooLarge);
+    }
+
+    let mut buf = Vec::with_capacity(content_length.unwrap_or_default());
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| ErrorCause::BodyReadFailed)?;
+        if buf.len() + chunk.len() > max_size {
+            return Err(ErrorCause::BodyTooLarge);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+
+    Ok(Bytes::from(buf))
+}
+
+/// Validates a canister call and forwards it to a healthy node of the subnet
+/// hosting the effective canister ID, trying other nodes on failure
+async fn forward(
+    state: &ProxyState,
+    canister_id: &str,
+    endpoint: &str,
+    client_addr: Option<SocketAddr>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, ErrorCause> {
+    let request_type = RequestType::from_endpoint(endpoint).ok_or(ErrorCause::UnknownEndpoint)?;
+
+    let canister_id: PrincipalId = canister_id
+        .parse()
+        .map_err(|_| ErrorCause::InvalidCanisterId)?;
+
+    // Shed load from abusive clients before reading their requests
+    if let Some(client_addr) = client_addr {
+        state
+            .limits
+            .check_ip(client_addr.ip(), Instant::now())
+            .map_err(ErrorCause::RateLimited)?;
+    }
+
+    let body = read_body(&headers, body, state.max_body_size).await?;
+
+    let request =
+        validate(request_type, &body, SystemTime::now()).map_err(ErrorCause::InvalidRequest)?;
+
+    let routes = state
+        .published_routes
+        .load_full()
+        .ok_or(ErrorCause::RoutingTableUnavailable)?;
+
+    let (subnet_id, nodes) = routes
+        .lookup(canister_id)
+        .ok_or(ErrorCause::CanisterNotRoutable)?;
+
+    state
+        .limits
+        .check_call(
+            subnet_id,
+            canister_id,
+            request.method_name.as_deref(),
+            Instant::now(),
+        )
+        .map_err(ErrorCause::RateLimited)?;
+
+    if nodes.is_empty() {
+        return Err(ErrorCause::NoHealthyNodes);
+    }
+
+    let mut nodes: Vec<&Node> = nodes.iter().collect();
+    nodes.shuffle(&mut rand::thread_rng());
+
+    let content_type = headers.get(CONTENT_TYPE);
+
+    for node in nodes.iter().cycle().take(state.max_attempts) {
+        let addr = SocketAddr::new(node.addr, node.port);
+        let url = format!("http://{addr}/api/v2/canister/{canister_id}/{endpoint}");
+
+        let mut request = state.http_client.post(url).body(body.clone());
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(error) => {
+                warn!(node_id = %node.id, ?error, "request failed");
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_server_error() {
+            warn!(node_id = %node.id, %status, "request failed");
+            continue;
+        }
+
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
+            headers.insert(CONTENT_TYPE, content_type.clone());
+        }
+
+        return match response.bytes().await {
+            Ok(body) => Ok((status, headers, body).into_response()),
+            Err(error) => {
+                warn!(node_id = %node.id, ?error, "failed to read response body");
+                Err(ErrorCause::ReplicaUnavailable)
+            }
+        };
+    }
+
+    Err(ErrorCause::ReplicaUnavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use axum::http::Request;
+    use ic_registry_routing_table::{CanisterIdRange, RoutingTable as CanisterRoutingTable};
+    use ic_types::{
+        messages::{
+            HttpCallContent, HttpCanisterUpdate, HttpQueryContent, HttpRequestEnvelope,
+            HttpUserQuery,
+        },
+        CanisterId, NodeId, SubnetId,
+    };
+    use prometheus::Registry;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        limit::RateLimit,
+        snapshot::{RoutingTable, Subnet},
+    };
+
+    fn routes(nodes: Vec<Node>) -> Routes {
+        let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(1));
+
+        let mut canister_routes = CanisterRoutingTable::new();
+        canister_routes
+            .insert(
+                CanisterIdRange {
+                    start: CanisterId::from_u64(0),
+                    end: CanisterId::from_u64(0xff),
+                },
+                subnet_id,
+            )
+            .unwrap();
+
+        Routes::from(RoutingTable {
+            registry_version: 1,
+            nns_subnet_id: subnet_id,
+            canister_routes,
+            subnets: vec![Subnet {
+                id: subnet_id,
+                nodes,
+            }],
+        })
+    }
+
+    fn node(id: u64, addr: SocketAddr) -> Node {
+        Node {
+            id: NodeId::from(PrincipalId::new_node_test_id(id)),
+            addr: addr.ip(),
+            port: addr.port(),
+        }
+    }
+
+    fn state(published_routes: &'static ArcSwapOption<Routes>, limits: RateLimits) -> ProxyState {
+        ProxyState::new(
+            published_routes,
+            reqwest::Client::new(),
+            None,
+            3,
+            1024,
+            limits,
+            ProxyMetrics::new(&Registry::new()),
+        )
+    }
+
+    /// Returns an anonymous `call` or `query` envelope, depending on the endpoint
+    fn envelope(endpoint: &str) -> Vec<u8> {
+        let ingress_expiry = (SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+            + Duration::from_secs(60))
+        .as_nanos() as u64;
+        let canister_id = Blob(CanisterId::from_u64(0x10).get().to_vec());
+        let sender = Blob(PrincipalId::new_anonymous().to_vec());
+
+        if endpoint == "call" {
+            serde_cbor::to_vec(&HttpRequestEnvelope {
+                content: HttpCallContent::Call {
+                    update: HttpCanisterUpdate {
+                        canister_id,
+                        method_name: "greet".to_string(),
+                        arg: Blob(vec![]),
+                        sender,
+                        ingress_expiry,
+                        nonce: None,
+                    },
+                },
+                sender_pubkey: None,
+                sender_sig: None,
+                sender_delegation: None,
+            })
+            .unwrap()
+        } else {
+            serde_cbor::to_vec(&HttpRequestEnvelope {
+                content: HttpQueryContent::Query {
+                    query: HttpUserQuery {
+                        canister_id,
+                        method_name: "greet".to_string(),
+                        arg: Blob(vec![]),
+                        sender,
+                        ingress_expiry,
+                        nonce: None,
+                    },
+                },
+                sender_pubkey: None,
+                sender_sig: None,
+                sender_delegation: None,
+            })
+            .unwrap()
+        }
+    }
+
+    fn call_request(canister_id: &str, endpoint: &str, body: Vec<u8>) -> Request<Body> {
+        Request::post(format!("/api/v2/canister/{canister_id}/{endpoint}"))
+            .header(CONTENT_TYPE, CONTENT_TYPE_CBOR)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// Starts a fake replica answering canister calls with the request path
+    async fn replica() -> SocketAddr {
+        let router = Router::new().route(
+            "/api/v2/canister/:canister_id/:endpoint",
+            post(
+                |Path((canister_id, endpoint)): Path<(String, String)>| async move {
+                    format!("{canister_id}/{endpoint}")
+                },
+            ),
+        );
+
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn status() {
+        static ROUTES: ArcSwapOption<Routes> = ArcSwapOption::const_empty();
+        let router = router(state(&ROUTES, RateLimits::default()));
+
+        let request = || Request::get("/api/v2/status").body(Body::empty()).unwrap();
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let status: HttpStatusResponse = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(status.ic_api_version, IC_API_VERSION);
+        assert_eq!(
+            status.replica_health_status,
+            Some(ReplicaHealthStatus::Starting)
+        );
+
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+        ROUTES.store(Some(Arc::new(routes(vec![node(1, addr)]))));
+
+        let response = router.oneshot(request()).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let status: HttpStatusResponse = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(
+            status.replica_health_status,
+            Some(ReplicaHealthStatus::Healthy)
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_rejects_invalid_requests() {
+        static ROUTES: ArcSwapOption<Routes> = ArcSwapOption::const_empty();
+        let state = state(&ROUTES, RateLimits::default());
+        let metrics = state.metrics.clone();
+        let router = router(state);
+
+        let canister_id = CanisterId::from_u64(0x10).to_string();
+        let unroutable_canister_id = CanisterId::from_u64(0x100).to_string();
+
+        let response = router
+            .clone()
+            .oneshot(call_request(&canister_id, "call", envelope("call")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(metrics.rejections("routing_table_unavailable"), 1);
+
+        ROUTES.store(Some(Arc::new(routes(vec![]))));
+
+        for (canister_id, endpoint, body, status, reason) in [
+            (
+                canister_id.as_str(),
+                "call",
+                envelope("call"),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "no_healthy_nodes",
+            ),
+            (
+                canister_id.as_str(),
+                "status",
+                envelope("query"),
+                StatusCode::NOT_FOUND,
+                "unknown_endpoint",
+            ),
+            (
+                "not-a-principal",
+                "call",
+                envelope("call"),
+                StatusCode::BAD_REQUEST,
+                "invalid_canister_id",
+            ),
+            (
+                unroutable_canister_id.as_str(),
+                "query",
+                envelope("query"),
+                StatusCode::NOT_FOUND,
+                "canister_not_routable",
+            ),
+            (
+                canister_id.as_str(),
+                "call",
+                envelope("query"),
+                StatusCode::BAD_REQUEST,
+                "malformed_request",
+            ),
+            (
+                canister_id.as_str(),
+                "query",
+                vec![0; 2048],
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "body_too_large",
+            ),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(call_request(canister_id, endpoint, body))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{canister_id}/{endpoint}");
+            assert_eq!(metrics.rejections(reason), 1, "{reason}");
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_rate_limits_canisters() {
+        static ROUTES: ArcSwapOption<Routes> = ArcSwapOption::const_empty();
+        let limit = RateLimit {
+            rate: 0.001,
+            burst: 1.,
+        };
+        let state = state(&ROUTES, RateLimits::new(None, Some(limit), None, None));
+        let metrics = state.metrics.clone();
+        let router = router(state);
+
+        ROUTES.store(Some(Arc::new(routes(vec![node(1, replica().await)]))));
+
+        let canister_id = CanisterId::from_u64(0x10).to_string();
+        let request = || call_request(&canister_id, "query", envelope("query"));
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(metrics.rejections("rate_limited_canister"), 1);
+    }
+
+    #[tokio::test]
+    async fn proxy_retries_failed_nodes() {
+        static ROUTES: ArcSwapOption<Routes> = ArcSwapOption::const_empty();
+        let state = state(&ROUTES, RateLimits::default());
+        let metrics = state.metrics.clone();
+        let router = router(state);
+        let canister_id = CanisterId::from_u64(0x10).to_string();
+
+        // Nothing listens on port 1, so requests to the first node always fail
+        let unreachable = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+        ROUTES.store(Some(Arc::new(routes(vec![
+            node(1, unreachable),
+            node(2, replica().await),
+        ]))));
+
+        let response = router
+            .clone()
+            .oneshot(call_request(&canister_id, "query", envelope("query")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], format!("{canister_id}/query").as_bytes());
+
+        ROUTES.store(Some(Arc::new(routes(vec![node(1, unreachable)]))));
+
+        let response = router
+            .oneshot(call_request(&canister_id, "query", envelope("query")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(metrics.rejections("replica_unavailable"), 1);
+    }
+}
@@ -1,36 +1,169 @@
-use anyhow::Error;
+use std::{net::IpAddr, sync::Arc};
+
+use anyhow::{anyhow, Context, Error};
 use arc_swap::ArcSwapOption;
 use async_trait::async_trait;
+use ic_registry_client::client::RegistryClient;
+use ic_registry_client_helpers::{
+    node::NodeRegistry,
+    routing_table::RoutingTableRegistry,
+    subnet::{SubnetListRegistry, SubnetRegistry},
+};
+use ic_registry_routing_table::RoutingTable as CanisterRoutingTable;
+use ic_types::{NodeId, SubnetId};
+use tracing::info;
 
 use crate::Run;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub id: NodeId,
+    pub addr: IpAddr,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subnet {
+    pub id: SubnetId,
+    pub nodes: Vec<Node>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RoutingTable {
-    // TODO(BOUN-722): Implement Routing table
-    // similar to rs/boundary_node/control_plane/src/registry.rs, but without encoding things as `String`s
+    pub registry_version: u64,
+    pub nns_subnet_id: SubnetId,
+    /// Maps canister ID ranges to the subnets hosting them
+    pub canister_routes: CanisterRoutingTable,
+    pub subnets: Vec<Subnet>,
 }
 
 pub struct Runner<'a> {
     published_routing_table: &'a ArcSwapOption<RoutingTable>,
-    registry_client: (),
+    registry_client: Arc<dyn RegistryClient>,
+    min_registry_version: u64,
 }
 
 impl<'a> Runner<'a> {
     pub fn new(
         published_routing_table: &'a ArcSwapOption<RoutingTable>,
-        registry_client: (),
+        registry_client: Arc<dyn RegistryClient>,
+        min_registry_version: u64,
     ) -> Self {
         Self {
             published_routing_table,
             registry_client,
+            min_registry_version,
         }
     }
+
+    /// Reads the routing table from the latest registry version
+    fn snapshot(&self) -> Result<RoutingTable, Error> {
+        let version = self.registry_client.get_latest_version();
+
+        let nns_subnet_id = self
+            .registry_client
+            .get_root_subnet_id(version)
+            .context("failed to get root subnet id")? // Result
+            .context("root subnet id not available")?; // Option
+
+        let subnet_ids = self
+            .registry_client
+            .get_subnet_ids(version)
+            .context("failed to get subnet ids")? // Result
+            .context("subnet ids not available")?; // Option
+
+        let subnets = subnet_ids
+            .into_iter()
+            .map(|subnet_id| {
+                let node_ids = self
+                    .registry_client
+                    .get_node_ids_on_subnet(subnet_id, version)
+                    .context("failed to get node ids")? // Result
+                    .context("node ids not available")?; // Option
+
+                let nodes = node_ids
+                    .into_iter()
+                    .map(|node_id| {
+                        let transport_info = self
+                            .registry_client
+                            .get_transport_info(node_id, version)
+                            .context("failed to get transport info")? // Result
+                            .context("transport info not available")?; // Option
+
+                        let http_endpoint =
+                            transport_info.http.context("http endpoint not available")?;
+
+                        Ok(Node {
+                            id: node_id,
+                            // IPv6 addresses may be enclosed in brackets
+                            addr: http_endpoint
+                                .ip_addr
+                                .trim_start_matches('[')
+                                .trim_end_matches(']')
+                                .parse()
+                                .context("failed to parse node ip address")?,
+                            port: http_endpoint
+                                .port
+                                .try_into()
+                                .context("failed to convert node port")?,
+                        })
+                    })
+                    .collect::<Result<Vec<Node>, Error>>()
+                    .context("failed to get nodes")?;
+
+                Ok(Subnet {
+                    id: subnet_id,
+                    nodes,
+                })
+            })
+            .collect::<Result<Vec<Subnet>, Error>>()
+            .context("failed to get subnets")?;
+
+        let canister_routes = self
+            .registry_client
+            .get_routing_table(version)
+            .context("failed to get routing table")? // Result
+            .context("routing table not available")?; // Option
+
+        Ok(RoutingTable {
+            registry_version: version.get(),
+            nns_subnet_id,
+            canister_routes,
+            subnets,
+        })
+    }
 }
 
 #[async_trait]
 impl<'a> Run for Runner<'a> {
     async fn run(&mut self) -> Result<(), Error> {
-        // similar to rs/boundary_node/control_plane/src/registry.rs, but without encoding things as `String`s
-        todo!("TODO(BOUN-722): Implement Routing reading routing table")
+        let version = self.registry_client.get_latest_version().get();
+
+        if version < self.min_registry_version {
+            return Err(anyhow!(
+                "registry version {} below minimum allowed version {}",
+                version,
+                self.min_registry_version,
+            ));
+        }
+
+        // Skip snapshots of a version that is already published
+        if let Some(rt) = self.published_routing_table.load_full() {
+            if rt.registry_version == version {
+                return Ok(());
+            }
+        }
+
+        let rt = self
+            .snapshot()
+            .context("failed to obtain registry snapshot")?;
+        info!(
+            registry_version = rt.registry_version,
+            subnets = rt.subnets.len(),
+            "published routing table"
+        );
+        self.published_routing_table.store(Some(Arc::new(rt)));
+
+        Ok(())
     }
 }
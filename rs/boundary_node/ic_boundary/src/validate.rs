@@ -0,0 +1,268 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ic_constants::{MAX_INGRESS_TTL, PERMITTED_DRIFT};
+use ic_types::{
+    messages::{
+        Blob, HttpCallContent, HttpQueryContent, HttpReadStateContent, HttpRequestEnvelope,
+    },
+    PrincipalId,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestType {
+    Call,
+    Query,
+    ReadState,
+}
+
+impl RequestType {
+    /// Maps the last segment of `/api/v2/canister/{id}/{endpoint}` to a request type
+    pub fn from_endpoint(endpoint: &str) -> Option<Self> {
+        match endpoint {
+            "call" => Some(Self::Call),
+            "query" => Some(Self::Query),
+            "read_state" => Some(Self::ReadState),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The body is not a CBOR envelope of the expected request type
+    Malformed,
+    /// The ingress expiry lies in the past
+    Expired,
+    /// The ingress expiry lies too far in the future
+    ExpiryTooFar,
+    /// The sender is not a valid principal, or does not match the sender public key
+    InvalidSender,
+}
+
+/// The fields of a validated envelope needed for routing and rate limiting
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidatedRequest {
+    pub sender: PrincipalId,
+    /// The method called, for calls and queries
+    pub method_name: Option<String>,
+}
+
+/// Validates the envelope of a `call`, `query` or `read_state` request
+/// before it is forwarded, mirroring the checks performed by the replica
+pub fn validate(
+    request_type: RequestType,
+    body: &[u8],
+    now: SystemTime,
+) -> Result<ValidatedRequest, ValidationError> {
+    let (sender, ingress_expiry, method_name, sender_pubkey) = match request_type {
+        RequestType::Call => {
+            let envelope: HttpRequestEnvelope<HttpCallContent> =
+                serde_cbor::from_slice(body).map_err(|_| ValidationError::Malformed)?;
+            let HttpCallContent::Call { update } = envelope.content;
+            (
+                update.sender,
+                update.ingress_expiry,
+                Some(update.method_name),
+                envelope.sender_pubkey,
+            )
+        }
+        RequestType::Query => {
+            let envelope: HttpRequestEnvelope<HttpQueryContent> =
+                serde_cbor::from_slice(body).map_err(|_| ValidationError::Malformed)?;
+            let HttpQueryContent::Query { query } = envelope.content;
+            (
+                query.sender,
+                query.ingress_expiry,
+                Some(query.method_name),
+                envelope.sender_pubkey,
+            )
+        }
+        RequestType::ReadState => {
+            let envelope: HttpRequestEnvelope<HttpReadStateContent> =
+                serde_cbor::from_slice(body).map_err(|_| ValidationError::Malformed)?;
+            let HttpReadStateContent::ReadState { read_state } = envelope.content;
+            (
+                read_state.sender,
+                read_state.ingress_expiry,
+                None,
+                envelope.sender_pubkey,
+            )
+        }
+    };
+
+    validate_expiry(ingress_expiry, now)?;
+    let sender = validate_sender(sender, sender_pubkey)?;
+
+    Ok(ValidatedRequest {
+        sender,
+        method_name,
+    })
+}
+
+/// Accepts expiry times within the validity window of the replica,
+/// allowing for clock drift between the boundary node and the replicas
+fn validate_expiry(ingress_expiry: u64, now: SystemTime) -> Result<(), ValidationError> {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let ingress_expiry = Duration::from_nanos(ingress_expiry);
+
+    if ingress_expiry + PERMITTED_DRIFT < now {
+        return Err(ValidationError::Expired);
+    }
+    if ingress_expiry > now + MAX_INGRESS_TTL + PERMITTED_DRIFT {
+        return Err(ValidationError::ExpiryTooFar);
+    }
+
+    Ok(())
+}
+
+/// Signed requests must come from the self-authenticating principal of the
+/// sender public key, unsigned ones from the anonymous principal
+fn validate_sender(
+    sender: Blob,
+    sender_pubkey: Option<Blob>,
+) -> Result<PrincipalId, ValidationError> {
+    let sender =
+        PrincipalId::try_from(sender.0.as_slice()).map_err(|_| ValidationError::InvalidSender)?;
+
+    let expected_sender = match sender_pubkey {
+        Some(pubkey) => PrincipalId::new_self_authenticating(&pubkey.0),
+        None => PrincipalId::new_anonymous(),
+    };
+
+    if sender != expected_sender {
+        return Err(ValidationError::InvalidSender);
+    }
+
+    Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_types::messages::{HttpReadState, HttpUserQuery};
+
+    use super::*;
+
+    const NOW: Duration = Duration::from_secs(1_700_000_000);
+
+    fn expiry(offset: Duration, past: bool) -> u64 {
+        let expiry = if past { NOW - offset } else { NOW + offset };
+        expiry.as_nanos() as u64
+    }
+
+    fn query(sender: PrincipalId, sender_pubkey: Option<Vec<u8>>, ingress_expiry: u64) -> Vec<u8> {
+        let envelope = HttpRequestEnvelope {
+            content: HttpQueryContent::Query {
+                query: HttpUserQuery {
+                    canister_id: Blob(vec![0; 10]),
+                    method_name: "greet".to_string(),
+                    arg: Blob(vec![]),
+                    sender: Blob(sender.to_vec()),
+                    ingress_expiry,
+                    nonce: None,
+                },
+            },
+            sender_pubkey: sender_pubkey.map(Blob),
+            sender_sig: None,
+            sender_delegation: None,
+        };
+
+        serde_cbor::to_vec(&envelope).unwrap()
+    }
+
+    fn validate_query(body: &[u8]) -> Result<ValidatedRequest, ValidationError> {
+        validate(RequestType::Query, body, UNIX_EPOCH + NOW)
+    }
+
+    #[test]
+    fn valid_requests() {
+        let anonymous = PrincipalId::new_anonymous();
+        let body = query(anonymous, None, expiry(Duration::from_secs(60), false));
+        assert_eq!(
+            validate_query(&body),
+            Ok(ValidatedRequest {
+                sender: anonymous,
+                method_name: Some("greet".to_string()),
+            })
+        );
+
+        let pubkey = vec![1; 44];
+        let sender = PrincipalId::new_self_authenticating(&pubkey);
+        let body = query(sender, Some(pubkey), expiry(Duration::ZERO, false));
+        assert_eq!(validate_query(&body).unwrap().sender, sender);
+
+        let envelope = HttpRequestEnvelope {
+            content: HttpReadStateContent::ReadState {
+                read_state: HttpReadState {
+                    sender: Blob(anonymous.to_vec()),
+                    paths: vec![],
+                    nonce: None,
+                    ingress_expiry: expiry(Duration::from_secs(60), false),
+                },
+            },
+            sender_pubkey: None,
+            sender_sig: None,
+            sender_delegation: None,
+        };
+        let body = serde_cbor::to_vec(&envelope).unwrap();
+        assert_eq!(
+            validate(RequestType::ReadState, &body, UNIX_EPOCH + NOW),
+            Ok(ValidatedRequest {
+                sender: anonymous,
+                method_name: None,
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_requests() {
+        assert_eq!(validate_query(b"garbage"), Err(ValidationError::Malformed));
+
+        // A query envelope sent to the call endpoint
+        let body = query(PrincipalId::new_anonymous(), None, NOW.as_nanos() as u64);
+        assert_eq!(
+            validate(RequestType::Call, &body, UNIX_EPOCH + NOW),
+            Err(ValidationError::Malformed)
+        );
+    }
+
+    #[test]
+    fn expiry_window() {
+        let anonymous = PrincipalId::new_anonymous();
+
+        let body = query(anonymous, None, expiry(2 * PERMITTED_DRIFT, true));
+        assert_eq!(validate_query(&body), Err(ValidationError::Expired));
+
+        let body = query(anonymous, None, expiry(PERMITTED_DRIFT / 2, true));
+        assert!(validate_query(&body).is_ok());
+
+        let too_far = MAX_INGRESS_TTL + 2 * PERMITTED_DRIFT;
+        let body = query(anonymous, None, expiry(too_far, false));
+        assert_eq!(validate_query(&body), Err(ValidationError::ExpiryTooFar));
+    }
+
+    #[test]
+    fn sender_format() {
+        let ingress_expiry = expiry(Duration::from_secs(60), false);
+
+        // Signed requests from the anonymous principal
+        let body = query(
+            PrincipalId::new_anonymous(),
+            Some(vec![1; 44]),
+            ingress_expiry,
+        );
+        assert_eq!(validate_query(&body), Err(ValidationError::InvalidSender));
+
+        // Unsigned requests from a non-anonymous principal
+        let body = query(PrincipalId::new_user_test_id(1), None, ingress_expiry);
+        assert_eq!(validate_query(&body), Err(ValidationError::InvalidSender));
+
+        // Senders that are not principals
+        let mut envelope: HttpRequestEnvelope<HttpQueryContent> =
+            serde_cbor::from_slice(&query(PrincipalId::new_anonymous(), None, ingress_expiry))
+                .unwrap();
+        let HttpQueryContent::Query { query } = &mut envelope.content;
+        query.sender = Blob(vec![0; 30]);
+        let body = serde_cbor::to_vec(&envelope).unwrap();
+        assert_eq!(validate_query(&body), Err(ValidationError::InvalidSender));
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use clap::Args;
+use hyper::http::header::{AUTHORIZATION, CACHE_CONTROL, COOKIE, SET_COOKIE, VARY};
+use ic_agent::export::Principal;
+use tracing::warn;
+
+use crate::{http::request::HttpRequest, validate::MAX_CERT_TIME_OFFSET_NS};
+
+const BODY_FILE_EXTENSION: &str = "body";
+
+/// The options for the response cache
+#[derive(Args)]
+pub struct CacheOpts {
+    /// Maximum total size in bytes of the cached response bodies. Responses are not cached
+    /// unless this is set.
+    #[clap(long)]
+    cache_size: Option<u64>,
+
+    /// Maximum size in bytes of a single cached response body.
+    #[clap(long, default_value = "10485760")]
+    cache_max_entry_size: u64,
+
+    /// Directory to store the cached response bodies in. By default, they are kept in memory.
+    /// Files of previous runs in this directory are removed on startup.
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+pub fn setup(opts: CacheOpts) -> Result<Option<Arc<Cache>>, anyhow::Error> {
+    let max_size = match opts.cache_size {
+        Some(max_size) if max_size > 0 => max_size,
+        _ => return Ok(None),
+    };
+
+    if let Some(dir) = &opts.cache_dir {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |ext| ext == BODY_FILE_EXTENSION)
+            {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+            }
+        }
+    }
+
+    Ok(Some(Arc::new(Cache::new(
+        max_size,
+        opts.cache_max_entry_size,
+        opts.cache_dir,
+    ))))
+}
+
+/// Responses are cached per canister, path, query and encoding, as the asset canister serves
+/// different bodies depending on the `Accept-Encoding` header.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    canister_id: Principal,
+    path: String,
+    query: Option<String>,
+    accept_encoding: Option<String>,
+}
+
+impl CacheKey {
+    fn new(canister_id: &Principal, request: &HttpRequest) -> Self {
+        CacheKey {
+            canister_id: *canister_id,
+            path: request.uri.path().to_string(),
+            query: request.uri.query().map(String::from),
+            accept_encoding: header(&request.headers, "accept-encoding").map(String::from),
+        }
+    }
+}
+
+pub struct CachedResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+enum StoredBody {
+    Memory(Arc<Vec<u8>>),
+    Disk(PathBuf),
+}
+
+struct Entry {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    body: StoredBody,
+    size: u64,
+    /// The time of the certificate the response was verified with, in nanoseconds
+    certificate_time: u128,
+    expires_at: u128,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<CacheKey, Entry>,
+    /// The keys of the entries, least recently used first
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    size: u64,
+}
+
+impl State {
+    fn touch(&mut self, key: &CacheKey) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.lru.insert(tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<StoredBody> {
+        let entry = self.entries.remove(key)?;
+        self.lru.remove(&entry.last_used);
+        self.size -= entry.size;
+        Some(entry.body)
+    }
+
+    /// Evicts the least recently used entries until `size` more bytes fit into `max_size`
+    fn make_room(&mut self, size: u64, max_size: u64, removed: &mut Vec<StoredBody>) {
+        while self.size + size > max_size {
+            let key = match self.lru.values().next() {
+                Some(key) => key.clone(),
+                None => break,
+            };
+            removed.extend(self.remove(&key));
+        }
+    }
+}
+
+/// A cache of verified query responses, bounded in total size and evicting the least recently
+/// used responses first. Responses expire according to their `Cache-Control` header, counted
+/// from the time of their certificate.
+pub struct Cache {
+    max_size: u64,
+    max_entry_size: u64,
+    dir: Option<PathBuf>,
+    next_file_id: AtomicU64,
+    state: Mutex<State>,
+}
+
+impl Cache {
+    pub fn new(max_size: u64, max_entry_size: u64, dir: Option<PathBuf>) -> Self {
+        Cache {
+            max_size,
+            max_entry_size: max_entry_size.min(max_size),
+            dir,
+            next_file_id: AtomicU64::new(0),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the cached response to the request, if any. Requests that modify the resource
+    /// invalidate its cached responses.
+    pub async fn get(
+        &self,
+        canister_id: &Principal,
+        request: &HttpRequest,
+        now: SystemTime,
+    ) -> Option<CachedResponse> {
+        if !is_safe_method(&request.method) {
+            self.invalidate(canister_id, request.uri.path()).await;
+            return None;
+        }
+
+        if request.method != "GET"
+            || is_private_request(request)
+            || has_directive(&request.headers, &["no-cache", "no-store"])
+        {
+            return None;
+        }
+
+        let key = CacheKey::new(canister_id, request);
+        let lookup = {
+            let mut state = self.state.lock().unwrap();
+            let entry = state.entries.get(&key)?;
+
+            if entry.expires_at <= nanos(now) {
+                Err(state.remove(&key))
+            } else {
+                let cached = (
+                    entry.status_code,
+                    entry.headers.clone(),
+                    match &entry.body {
+                        StoredBody::Memory(body) => StoredBody::Memory(body.clone()),
+                        StoredBody::Disk(path) => StoredBody::Disk(path.clone()),
+                    },
+                );
+                state.touch(&key);
+                Ok(cached)
+            }
+        };
+
+        let (status_code, headers, body) = match lookup {
+            Ok(cached) => cached,
+            Err(expired) => {
+                remove_bodies(expired).await;
+                return None;
+            }
+        };
+
+        let body = match body {
+            StoredBody::Memory(body) => body.to_vec(),
+            // The file can be removed by an eviction in the meantime
+            StoredBody::Disk(path) => tokio::fs::read(&path).await.ok()?,
+        };
+
+        Some(CachedResponse {
+            status_code,
+            headers,
+            body,
+        })
+    }
+
+    /// Caches a response. This must only be called with responses that passed verification,
+    /// along with the time of the certificate they were verified with.
+    pub async fn insert(
+        &self,
+        canister_id: &Principal,
+        request: &HttpRequest,
+        response: CachedResponse,
+        certificate_time: u128,
+        now: SystemTime,
+    ) {
+        if request.method != "GET"
+            || is_private_request(request)
+            || has_directive(&request.headers, &["no-store"])
+            || response.status_code != 200
+        {
+            return;
+        }
+
+        let size = response.body.len() as u64;
+        if size > self.max_entry_size {
+            return;
+        }
+
+        let expires_at = match expiry(&response.headers, certificate_time) {
+            Some(expires_at) if expires_at > nanos(now) => expires_at,
+            _ => return,
+        };
+
+        let key = CacheKey::new(canister_id, request);
+
+        // Responses are not replaced by responses with older certificates
+        let is_newer = self
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .get(&key)
+            .map_or(true, |entry| entry.certificate_time < certificate_time);
+        if !is_newer {
+            return;
+        }
+
+        let body = match &self.dir {
+            None => StoredBody::Memory(Arc::new(response.body)),
+            Some(dir) => {
+                let id = self.next_file_id.fetch_add(1, Ordering::Relaxed);
+                let path = dir.join(format!("{id}.{BODY_FILE_EXTENSION}"));
+                if let Err(e) = tokio::fs::write(&path, &response.body).await {
+                    warn!("failed to write cached response to {}: {e}", path.display());
+                    return;
+                }
+                StoredBody::Disk(path)
+            }
+        };
+
+        let mut removed = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            removed.extend(state.remove(&key));
+            state.make_room(size, self.max_size, &mut removed);
+
+            state.tick += 1;
+            let last_used = state.tick;
+            state.lru.insert(last_used, key.clone());
+            state.size += size;
+            state.entries.insert(
+                key,
+                Entry {
+                    status_code: response.status_code,
+                    headers: response.headers,
+                    body,
+                    size,
+                    certificate_time,
+                    expires_at,
+                    last_used,
+                },
+            );
+        }
+
+        remove_bodies(removed).await;
+    }
+
+    /// Removes the cached responses of a path with any query and in all encodings
+    async fn invalidate(&self, canister_id: &Principal, path: &str) {
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            let keys = state
+                .entries
+                .keys()
+                .filter(|key| key.canister_id == *canister_id && key.path == path)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            keys.iter()
+                .filter_map(|key| state.remove(key))
+                .collect::<Vec<_>>()
+        };
+
+        remove_bodies(removed).await;
+    }
+}
+
+async fn remove_bodies(bodies: impl IntoIterator<Item = StoredBody>) {
+    for body in bodies {
+        if let StoredBody::Disk(path) = body {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("failed to remove cached response {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Returns the time a response expires at, in nanoseconds, or `None` if it must not be cached.
+/// Responses are cached for at most as long as their certificate would pass verification.
+fn expiry(headers: &[(String, String)], certificate_time: u128) -> Option<u128> {
+    if has_directive(headers, &["no-store", "no-cache", "private"])
+        || header(headers, SET_COOKIE.as_str()).is_some()
+        || headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(VARY.as_str()))
+            .flat_map(|(_, value)| value.split(','))
+            .any(|name| !name.trim().eq_ignore_ascii_case("accept-encoding"))
+    {
+        return None;
+    }
+
+    let max_age =
+        directive_value(headers, "s-maxage").or_else(|| directive_value(headers, "max-age"))?;
+    let max_age = Duration::from_secs(max_age).as_nanos();
+
+    Some(certificate_time + max_age.min(MAX_CERT_TIME_OFFSET_NS))
+}
+
+fn is_safe_method(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS")
+}
+
+/// Responses to requests with credentials may be specific to the user
+fn is_private_request(request: &HttpRequest) -> bool {
+    header(&request.headers, AUTHORIZATION.as_str()).is_some()
+        || header(&request.headers, COOKIE.as_str()).is_some()
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn cache_control(headers: &[(String, String)]) -> impl Iterator<Item = (&str, Option<&str>)> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(CACHE_CONTROL.as_str()))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        })
+}
+
+fn has_directive(headers: &[(String, String)], directives: &[&str]) -> bool {
+    cache_control(headers).any(|(name, _)| {
+        directives
+            .iter()
+            .any(|directive| name.eq_ignore_ascii_case(directive))
+    })
+}
+
+fn directive_value(headers: &[(String, String)], directive: &str) -> Option<u64> {
+    cache_control(headers)
+        .find(|(name, _)| name.eq_ignore_ascii_case(directive))
+        .and_then(|(_, value)| value?.parse().ok())
+}
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Uri;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    const CERTIFICATE_TIME: Duration = Duration::from_secs(1_700_000_000);
+
+    fn canister_id() -> Principal {
+        Principal::from_text("wwc2m-2qaaa-aaaac-qaaaa-cai").unwrap()
+    }
+
+    fn request(method: &str, path: &'static str, headers: &[(&str, &str)]) -> HttpRequest {
+        HttpRequest {
+            uri: Uri::from_static(path),
+            method: method.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    fn response(cache_control: &str, body: &[u8]) -> CachedResponse {
+        CachedResponse {
+            status_code: 200,
+            headers: vec![("Cache-Control".to_string(), cache_control.to_string())],
+            body: body.to_vec(),
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + CERTIFICATE_TIME + Duration::from_secs(secs)
+    }
+
+    fn insert(cache: &Cache, request: &HttpRequest, response: CachedResponse) {
+        aw!(cache.insert(
+            &canister_id(),
+            request,
+            response,
+            CERTIFICATE_TIME.as_nanos(),
+            at(0)
+        ));
+    }
+
+    fn get(cache: &Cache, request: &HttpRequest, now: SystemTime) -> Option<Vec<u8>> {
+        aw!(cache.get(&canister_id(), request, now)).map(|response| response.body)
+    }
+
+    #[test]
+    fn expiry_from_cache_control() {
+        let headers = |value: &str| vec![("cache-control".to_string(), value.to_string())];
+        let time = CERTIFICATE_TIME.as_nanos();
+
+        assert_eq!(
+            expiry(&headers("public, max-age=60"), time),
+            Some(time + Duration::from_secs(60).as_nanos())
+        );
+        assert_eq!(
+            expiry(&headers("max-age=60, s-maxage=30"), time),
+            Some(time + Duration::from_secs(30).as_nanos())
+        );
+        // Capped at the validity of the certificate
+        assert_eq!(
+            expiry(&headers("max-age=31536000"), time),
+            Some(time + MAX_CERT_TIME_OFFSET_NS)
+        );
+
+        for value in ["", "public", "max-age=60, no-store", "private, max-age=60"] {
+            assert_eq!(expiry(&headers(value), time), None, "{value}");
+        }
+
+        let mut vary = headers("max-age=60");
+        vary.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+        assert!(expiry(&vary, time).is_some());
+        vary[1].1 = "Accept-Encoding, Origin".to_string();
+        assert_eq!(expiry(&vary, time), None);
+    }
+
+    #[test]
+    fn cache_hits_and_expiry() {
+        let cache = Cache::new(1000, 1000, None);
+        let index = request("GET", "/index.html", &[]);
+
+        assert_eq!(get(&cache, &index, at(0)), None);
+        insert(&cache, &index, response("max-age=60", b"index"));
+        assert_eq!(get(&cache, &index, at(30)), Some(b"index".to_vec()));
+        assert_eq!(get(&cache, &index, at(60)), None);
+
+        // Responses are cached per encoding
+        insert(&cache, &index, response("max-age=60", b"index"));
+        let gzip = request("GET", "/index.html", &[("Accept-Encoding", "gzip")]);
+        assert_eq!(get(&cache, &gzip, at(0)), None);
+
+        // Requests can bypass the cache
+        let no_cache = request("GET", "/index.html", &[("Cache-Control", "no-cache")]);
+        assert_eq!(get(&cache, &no_cache, at(0)), None);
+        let authorized = request("GET", "/index.html", &[("Authorization", "Bearer x")]);
+        assert_eq!(get(&cache, &authorized, at(0)), None);
+
+        // Requests that modify the resource invalidate it
+        assert!(get(&cache, &index, at(0)).is_some());
+        assert_eq!(
+            get(&cache, &request("POST", "/index.html", &[]), at(0)),
+            None
+        );
+        assert_eq!(get(&cache, &index, at(0)), None);
+
+        // Responses are cached per query, but invalidated regardless of it
+        let versioned = request("GET", "/index.html?v=2", &[]);
+        insert(&cache, &index, response("max-age=60", b"index"));
+        insert(&cache, &versioned, response("max-age=60", b"index v2"));
+        assert_eq!(get(&cache, &index, at(0)), Some(b"index".to_vec()));
+        assert_eq!(get(&cache, &versioned, at(0)), Some(b"index v2".to_vec()));
+        assert_eq!(
+            get(&cache, &request("PUT", "/index.html?lang=en", &[]), at(0)),
+            None
+        );
+        assert_eq!(get(&cache, &index, at(0)), None);
+        assert_eq!(get(&cache, &versioned, at(0)), None);
+    }
+
+    #[test]
+    fn uncacheable_responses() {
+        let cache = Cache::new(1000, 10, None);
+        let index = request("GET", "/index.html", &[]);
+
+        insert(&cache, &index, response("no-store", b"index"));
+        insert(
+            &cache,
+            &index,
+            response("max-age=60", b"larger than an entry"),
+        );
+        let mut not_found = response("max-age=60", b"not found");
+        not_found.status_code = 404;
+        insert(&cache, &index, not_found);
+        insert(
+            &cache,
+            &request("GET", "/index.html", &[("Cache-Control", "no-store")]),
+            response("max-age=60", b"index"),
+        );
+
+        assert_eq!(get(&cache, &index, at(0)), None);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = Cache::new(10, 10, None);
+        let (a, b, c) = (
+            request("GET", "/a", &[]),
+            request("GET", "/b", &[]),
+            request("GET", "/c", &[]),
+        );
+
+        insert(&cache, &a, response("max-age=60", b"aaaa"));
+        insert(&cache, &b, response("max-age=60", b"bbbb"));
+        assert!(get(&cache, &a, at(0)).is_some());
+        insert(&cache, &c, response("max-age=60", b"cccc"));
+
+        assert!(get(&cache, &a, at(0)).is_some());
+        assert_eq!(get(&cache, &b, at(0)), None);
+        assert!(get(&cache, &c, at(0)).is_some());
+        assert_eq!(cache.state.lock().unwrap().size, 8);
+    }
+
+    #[test]
+    fn disk_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(10, 10, Some(dir.path().to_path_buf()));
+        let (a, b) = (request("GET", "/a", &[]), request("GET", "/b", &[]));
+
+        insert(&cache, &a, response("max-age=60", b"aaaaaa"));
+        assert_eq!(get(&cache, &a, at(0)), Some(b"aaaaaa".to_vec()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Evicted bodies are removed from the disk
+        insert(&cache, &b, response("max-age=60", b"bbbbbb"));
+        assert_eq!(get(&cache, &a, at(0)), None);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
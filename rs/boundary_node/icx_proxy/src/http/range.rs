@@ -0,0 +1,389 @@
+use futures::StreamExt;
+use hyper::{
+    http::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE},
+    Body, Response, StatusCode,
+};
+
+use crate::validate::{verify_streaming_body, StreamingBodyVerifier};
+
+/// A single byte range of a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=<start>-` or `bytes=<start>-<end>`, where `end` is inclusive.
+    FromTo { start: u64, end: Option<u64> },
+    /// `bytes=-<length>`, the last `length` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses the value of a `Range` header. Multiple ranges and units other than bytes are
+    /// not supported, in which case the header is ignored and the whole body is served.
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        if start.is_empty() {
+            return Some(Self::Suffix(end.parse().ok()?));
+        }
+
+        let start = start.parse().ok()?;
+        let end = match end {
+            "" => None,
+            end => Some(end.parse().ok()?),
+        };
+
+        // Invalid ranges are ignored
+        if end.map_or(false, |end| end < start) {
+            return None;
+        }
+
+        Some(Self::FromTo { start, end })
+    }
+
+    /// Resolves the range against a body of `length` bytes, returning the inclusive bounds of
+    /// the bytes to serve, or `None` if the range is not satisfiable.
+    pub fn resolve(&self, length: u64) -> Option<(u64, u64)> {
+        match *self {
+            Self::FromTo { start, end } if start < length => {
+                Some((start, end.map_or(length - 1, |end| end.min(length - 1))))
+            }
+            Self::Suffix(suffix) if suffix > 0 && length > 0 => {
+                Some((length - suffix.min(length), length - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The body of a response, either complete or streamed from the canister, along with the
+/// verifier of the streamed body if it is certified.
+pub enum ResponseBody {
+    Complete(Vec<u8>),
+    Streaming(Body, Option<StreamingBodyVerifier>),
+}
+
+/// Builds the response, serving only the requested range of a successful response.
+pub fn build_response(
+    status_code: StatusCode,
+    headers: &[(String, String)],
+    body: ResponseBody,
+    range: Option<ByteRange>,
+) -> Result<Response<Body>, hyper::http::Error> {
+    let builder = |status_code, skip_content_length| {
+        headers
+            .iter()
+            .filter(|(name, _)| {
+                !(skip_content_length && name.eq_ignore_ascii_case(CONTENT_LENGTH.as_str()))
+            })
+            .fold(
+                Response::builder().status(status_code),
+                |builder, (name, value)| builder.header(name, value),
+            )
+    };
+
+    let range = match range {
+        Some(range) if status_code == StatusCode::OK => range,
+        _ => {
+            let body = match body {
+                ResponseBody::Complete(body) => Body::from(body),
+                ResponseBody::Streaming(body, None) => body,
+                ResponseBody::Streaming(body, Some(verifier)) => {
+                    verify_streaming_body(body, verifier, None)
+                }
+            };
+
+            let has_accept_ranges = headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(ACCEPT_RANGES.as_str()));
+
+            return match status_code {
+                StatusCode::OK if !has_accept_ranges => builder(status_code, false)
+                    .header(ACCEPT_RANGES, "bytes")
+                    .body(body),
+                _ => builder(status_code, false).body(body),
+            };
+        }
+    };
+
+    match body {
+        ResponseBody::Complete(body) => {
+            let length = body.len() as u64;
+            match range.resolve(length) {
+                Some((start, end)) => builder(StatusCode::PARTIAL_CONTENT, true)
+                    .header(CONTENT_RANGE, format!("bytes {start}-{end}/{length}"))
+                    .body(Body::from(body[start as usize..=end as usize].to_vec())),
+                None => builder(StatusCode::RANGE_NOT_SATISFIABLE, true)
+                    .header(CONTENT_RANGE, format!("bytes */{length}"))
+                    .body(Body::empty()),
+            }
+        }
+        ResponseBody::Streaming(body, verifier) => {
+            let length = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_LENGTH.as_str()))
+                .and_then(|(_, value)| value.trim().parse::<u64>().ok());
+
+            // Without the length of the body, the range cannot be resolved, as it may end past
+            // the end of the body, so the whole body is served
+            let bounds = match length {
+                Some(length) => match range.resolve(length) {
+                    Some(bounds) => Some((bounds, length)),
+                    None => {
+                        return builder(StatusCode::RANGE_NOT_SATISFIABLE, true)
+                            .header(CONTENT_RANGE, format!("bytes */{length}"))
+                            .body(Body::empty())
+                    }
+                },
+                None => None,
+            };
+
+            match bounds {
+                Some(((start, end), length)) => builder(StatusCode::PARTIAL_CONTENT, true)
+                    .header(CONTENT_RANGE, format!("bytes {start}-{end}/{length}"))
+                    .body(match verifier {
+                        Some(verifier) => verify_streaming_body(body, verifier, Some((start, end))),
+                        None => slice_stream(body, start, end),
+                    }),
+                None => builder(status_code, false).body(match verifier {
+                    Some(verifier) => verify_streaming_body(body, verifier, None),
+                    None => body,
+                }),
+            }
+        }
+    }
+}
+
+/// Slices a streamed body to the bytes `start..=end`, mapping the range onto the chunks of the
+/// streaming callback. Chunks of the asset canister can only
+/// be fetched in order, so the ones before the range are still fetched, but no further chunk
+/// is fetched once the range is complete.
+fn slice_stream(body: Body, start: u64, end: u64) -> Body {
+    Body::wrap_stream(futures::stream::unfold(
+        (body, 0u64),
+        move |(mut body, offset)| async move {
+            if offset > end {
+                return None;
+            }
+
+            let chunk = match body.next().await? {
+                Ok(chunk) => chunk,
+                Err(e) => return Some((Err(e), (body, u64::MAX))),
+            };
+
+            let length = chunk.len() as u64;
+            let from = start.saturating_sub(offset).min(length);
+            let to = (end.saturating_add(1) - offset).min(length).max(from);
+            let slice = chunk.slice(from as usize..to as usize);
+
+            Some((Ok(slice), (body, offset + length)))
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn streaming_body() -> Body {
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(b"0123".to_vec()),
+            Ok(b"4567".to_vec()),
+            Ok(b"89".to_vec()),
+        ];
+
+        Body::wrap_stream(futures::stream::iter(chunks))
+    }
+
+    fn read_body(response: Response<Body>) -> Vec<u8> {
+        aw!(hyper::body::to_bytes(response.into_body()))
+            .unwrap()
+            .to_vec()
+    }
+
+    fn content_range(response: &Response<Body>) -> Option<&str> {
+        response
+            .headers()
+            .get(CONTENT_RANGE)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn parse_range() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-99"),
+            Some(ByteRange::FromTo {
+                start: 0,
+                end: Some(99)
+            })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=100-"),
+            Some(ByteRange::FromTo {
+                start: 100,
+                end: None
+            })
+        );
+        assert_eq!(ByteRange::parse("bytes=-500"), Some(ByteRange::Suffix(500)));
+
+        for value in [
+            "",
+            "bytes=",
+            "bytes=-",
+            "items=0-1",
+            "bytes=5-1",
+            "bytes=0-1,3-4",
+        ] {
+            assert_eq!(ByteRange::parse(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn resolve_range() {
+        let range = |start, end| ByteRange::FromTo { start, end };
+
+        assert_eq!(range(0, Some(3)).resolve(10), Some((0, 3)));
+        assert_eq!(range(5, Some(100)).resolve(10), Some((5, 9)));
+        assert_eq!(range(5, None).resolve(10), Some((5, 9)));
+        assert_eq!(range(10, None).resolve(10), None);
+        assert_eq!(ByteRange::Suffix(3).resolve(10), Some((7, 9)));
+        assert_eq!(ByteRange::Suffix(30).resolve(10), Some((0, 9)));
+        assert_eq!(ByteRange::Suffix(0).resolve(10), None);
+    }
+
+    #[test]
+    fn complete_body_range() {
+        let headers = vec![("content-length".to_string(), "10".to_string())];
+        let range = ByteRange::parse("bytes=2-5");
+
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Complete(b"0123456789".to_vec()),
+            range,
+        )
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&response), Some("bytes 2-5/10"));
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!(read_body(response), b"2345".to_vec());
+
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Complete(b"0123456789".to_vec()),
+            ByteRange::parse("bytes=20-"),
+        )
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(content_range(&response), Some("bytes */10"));
+
+        // Ranges of unsuccessful responses are ignored
+        let response = build_response(
+            StatusCode::NOT_FOUND,
+            &headers,
+            ResponseBody::Complete(b"not found".to_vec()),
+            range,
+        )
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(read_body(response), b"not found".to_vec());
+    }
+
+    #[test]
+    fn streaming_body_range() {
+        let headers = vec![("Content-Length".to_string(), "10".to_string())];
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Streaming(streaming_body(), None),
+            ByteRange::parse("bytes=3-20"),
+        )
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&response), Some("bytes 3-9/10"));
+        assert_eq!(read_body(response), b"3456789".to_vec());
+
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Streaming(streaming_body(), None),
+            ByteRange::parse("bytes=-3"),
+        )
+        .unwrap();
+        assert_eq!(content_range(&response), Some("bytes 7-9/10"));
+        assert_eq!(read_body(response), b"789".to_vec());
+
+        // Ranges of bodies of unknown length are ignored, even closed ones as they may end past
+        // the end of the body
+        for value in ["bytes=3-", "bytes=3-20", "bytes=3-8"] {
+            let response = build_response(
+                StatusCode::OK,
+                &[],
+                ResponseBody::Streaming(streaming_body(), None),
+                ByteRange::parse(value),
+            )
+            .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{value}");
+            assert!(content_range(&response).is_none(), "{value}");
+            assert_eq!(read_body(response), b"0123456789".to_vec(), "{value}");
+        }
+    }
+
+    #[test]
+    fn verified_streaming_body_range() {
+        use sha2::{Digest, Sha256};
+
+        let headers = vec![("Content-Length".to_string(), "10".to_string())];
+        let verifier = StreamingBodyVerifier::new(Sha256::digest(b"0123456789").into());
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Streaming(streaming_body(), Some(verifier)),
+            ByteRange::parse("bytes=3-5"),
+        )
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&response), Some("bytes 3-5/10"));
+        assert_eq!(read_body(response), b"345".to_vec());
+
+        // The chunks after the range are verified as well
+        let verifier = StreamingBodyVerifier::new(Sha256::digest(b"0123456780").into());
+        let response = build_response(
+            StatusCode::OK,
+            &headers,
+            ResponseBody::Streaming(streaming_body(), Some(verifier)),
+            ByteRange::parse("bytes=3-5"),
+        )
+        .unwrap();
+        assert!(aw!(hyper::body::to_bytes(response.into_body())).is_err());
+    }
+
+    #[test]
+    fn slice_stream_stops_after_range() {
+        let chunks = futures::stream::iter(vec![
+            Ok(b"0123".to_vec()),
+            Ok(b"4567".to_vec()),
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "chunk after the range was fetched",
+            )),
+        ]);
+        let body = slice_stream(Body::wrap_stream(chunks), 1, 5);
+
+        assert_eq!(
+            aw!(hyper::body::to_bytes(body)).unwrap().to_vec(),
+            b"12345".to_vec()
+        );
+    }
+}
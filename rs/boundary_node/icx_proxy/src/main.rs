@@ -9,6 +9,7 @@ use clap::{builder::ValueParser, crate_authors, crate_version, Parser};
 use futures::try_join;
 use tracing::{error, Instrument};
 
+mod cache;
 mod canister_alias;
 mod canister_id;
 mod config;
@@ -109,6 +110,10 @@ struct Opts {
     /// The options for metrics
     #[clap(flatten)]
     metrics: metrics::MetricsOpts,
+
+    /// The options for the response cache
+    #[clap(flatten)]
+    cache: cache::CacheOpts,
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -124,6 +129,7 @@ fn main() -> Result<(), anyhow::Error> {
         debug,
         log,
         metrics,
+        cache,
         root_key,
     } = Opts::parse();
 
@@ -150,11 +156,15 @@ fn main() -> Result<(), anyhow::Error> {
     let validator = Validator::new();
     let validator = WithMetrics(validator, MetricParams::new(&meter, "validator"));
 
+    // Setup Response Cache
+    let cache = cache::setup(cache)?;
+
     let proxy = proxy::setup(
         proxy::SetupArgs {
             resolver,
             validator,
             client,
+            cache,
         },
         proxy::ProxyOpts {
             address,
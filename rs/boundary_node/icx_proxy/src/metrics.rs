@@ -6,6 +6,7 @@ use candid::Principal;
 use clap::Args;
 use hyper::{self, Body, Request, Response, StatusCode};
 use ic_agent::Agent;
+use ic_response_verification::types::Response as CertifiedResponse;
 use opentelemetry::{
     global,
     metrics::{Counter, Meter},
@@ -17,7 +18,10 @@ use prometheus::{Encoder, TextEncoder};
 
 use crate::http::request::HttpRequest;
 use crate::http::response::HttpResponse;
-use crate::{logging::add_trace_layer, validate::Validate};
+use crate::{
+    logging::add_trace_layer,
+    validate::{StreamingBodyVerifier, Validate},
+};
 
 /// The options for metrics
 #[derive(Args)]
@@ -45,6 +49,16 @@ impl MetricParams {
                 .init(),
         }
     }
+
+    fn observe(&self, is_ok: bool) {
+        let mut status = if is_ok { "ok" } else { "fail" };
+        if cfg!(feature = "skip_body_verification") {
+            status = "skip";
+        }
+
+        let labels = &[KeyValue::new("status", status)];
+        self.counter.add(1, labels);
+    }
 }
 
 impl<T: Validate> Validate for WithMetrics<T> {
@@ -54,19 +68,23 @@ impl<T: Validate> Validate for WithMetrics<T> {
         canister_id: &Principal,
         request: &HttpRequest,
         response: &HttpResponse,
-    ) -> Result<(), Cow<'static, str>> {
+    ) -> Result<Option<CertifiedResponse>, Cow<'static, str>> {
         let out = self.0.validate(agent, canister_id, request, response);
+        self.1.observe(out.is_ok());
+        out
+    }
 
-        let mut status = if out.is_ok() { "ok" } else { "fail" };
-        if cfg!(feature = "skip_body_verification") {
-            status = "skip";
-        }
-
-        let labels = &[KeyValue::new("status", status)];
-
-        let MetricParams { counter } = &self.1;
-        counter.add(1, labels);
-
+    fn validate_streaming(
+        &self,
+        agent: &Agent,
+        canister_id: &Principal,
+        request: &HttpRequest,
+        response: &HttpResponse,
+    ) -> Result<Option<StreamingBodyVerifier>, Cow<'static, str>> {
+        let out = self
+            .0
+            .validate_streaming(agent, canister_id, request, response);
+        self.1.observe(out.is_ok());
         out
     }
 }
@@ -5,12 +5,13 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::SystemTime,
 };
 
 use anyhow::bail;
 use axum::extract::{ConnectInfo, FromRef, State};
 use hyper::{
-    http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RANGE},
     Body, Request, Response, StatusCode, Uri,
 };
 use ic_agent::{agent_error::HttpErrorPayload, export::Principal, Agent, AgentError};
@@ -23,12 +24,18 @@ use ic_utils::{
 use tracing::{enabled, info, instrument, trace, Level};
 
 use crate::error::ErrorFactory;
+use crate::http::body::read_streaming_body;
+use crate::http::range::{self, ByteRange, ResponseBody};
 use crate::http::request::HttpRequest;
 use crate::http::response::{AgentResponseAny, HttpResponse};
 use crate::{
+    cache::{Cache, CachedResponse},
     canister_id,
     proxy::{AppState, HandleError, HyperService},
-    validate::Validate,
+    validate::{
+        certificate_time, requires_complete_body, Validate,
+        MAX_CERTIFIED_HEADERS_STREAMING_BODY_SIZE,
+    },
 };
 
 // The maximum length of a body we should log as tracing.
@@ -45,6 +52,7 @@ pub struct Args<V, C> {
     validator: V,
     client: C,
     debug: bool,
+    cache: Option<Arc<Cache>>,
 }
 
 pub struct Pool {
@@ -74,6 +82,7 @@ impl<V: Clone, C: Clone> FromRef<AppState<V, C>> for Args<V, C> {
             validator: state.validator().clone(),
             client: state.client().clone(),
             debug: state.debug(),
+            cache: state.cache().cloned(),
         }
     }
 }
@@ -137,36 +146,41 @@ pub async fn handler<V: Validate, C: HyperService<Body>>(
     let uri_canister_id = uri_canister_id.map(|v| v.0);
     let host_canister_id = host_canister_id.map(|v| v.0);
     let query_param_canister_id = query_param_canister_id.map(|v| v.0);
+    let debug = args.debug;
     process_request_inner(
         request,
         addr,
-        args.agent,
-        &args.replica_uri,
-        args.validator,
-        args.client,
+        args,
         uri_canister_id
             .or(host_canister_id)
             .or(query_param_canister_id),
     )
     .await
-    .handle_error(args.debug)
+    .handle_error(debug)
 }
 
-async fn process_request_inner(
+async fn process_request_inner<V: Validate, C: HyperService<Body>>(
     request: Request<Body>,
     addr: SocketAddr,
-    agent: Agent,
-    replica_uri: &Uri,
-    validator: impl Validate,
-    mut client: impl HyperService<Body>,
+    args: Args<V, C>,
     canister_id: Option<Principal>,
 ) -> Result<Response<Body>, anyhow::Error> {
+    let Args {
+        agent,
+        replica_uri,
+        validator,
+        mut client,
+        cache,
+        ..
+    } = args;
+    let cache = cache.as_deref();
+
     let canister_id = match canister_id {
         None => {
             return if request.uri().path().starts_with("/api") {
                 info!("forwarding");
                 let proxied_request =
-                    create_proxied_request(&addr.ip(), replica_uri.clone(), request)?;
+                    create_proxied_request(&addr.ip(), (*replica_uri).clone(), request)?;
                 let response = client.call(proxied_request).await?;
                 let (parts, body) = response.into_parts();
                 Ok(Response::from_parts(parts, body.into()))
@@ -188,7 +202,7 @@ async fn process_request_inner(
     );
 
     let (parts, body) = request.into_parts();
-    let http_request = HttpRequest::from((
+    let mut http_request = HttpRequest::from((
         &parts,
         match HttpRequest::read_body(body).await {
             Ok(data) => data,
@@ -217,6 +231,34 @@ async fn process_request_inner(
         );
     }
 
+    // Ranges are served by the proxy, so that the canister always returns, and the proxy
+    // verifies, the whole body. Streamed bodies are fetched chunk by chunk: unverified ones only
+    // up to the end of the range, but verified ones are fetched in full, at the cost of fetching
+    // the chunks after the range from the canister, since verification covers the whole body.
+    let range = http_request
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(RANGE.as_str()))
+        .and_then(|(_, value)| ByteRange::parse(value));
+    http_request
+        .headers
+        .retain(|(name, _)| !name.eq_ignore_ascii_case(RANGE.as_str()));
+
+    if let Some(cache) = cache {
+        if let Some(cached) = cache
+            .get(&canister_id, &http_request, SystemTime::now())
+            .await
+        {
+            trace!(">> cached response");
+            return Ok(range::build_response(
+                StatusCode::from_u16(cached.status_code)?,
+                &cached.headers,
+                ResponseBody::Complete(cached.body),
+                range,
+            )?);
+        }
+    }
+
     let canister = HttpRequestCanister::create(&agent, canister_id);
     let header_fields = http_request
         .headers
@@ -258,32 +300,108 @@ async fn process_request_inner(
         agent_response
     };
 
-    let http_response = HttpResponse::from((&agent, agent_response));
-    let mut response_builder =
-        Response::builder().status(StatusCode::from_u16(http_response.status_code)?);
-    for (name, value) in &http_response.headers {
-        response_builder = response_builder.header(name, value);
+    let mut http_response = HttpResponse::from((&agent, agent_response));
+
+    // Responses of update calls go through consensus and need no certification.
+    let should_validate = !is_update_call;
+
+    // With response verification v2, the certification of the status code and headers is
+    // verified along with the body, so a streamed body is collected and verified as a whole.
+    if should_validate
+        && http_response.has_streaming_body
+        && requires_complete_body(&http_response.headers)
+    {
+        if let Some(body) = http_response.streaming_body.take() {
+            match read_streaming_body(body, MAX_CERTIFIED_HEADERS_STREAMING_BODY_SIZE).await {
+                Ok(body) => {
+                    http_response.body = body;
+                    http_response.has_streaming_body = false;
+                }
+                Err(e) => {
+                    return Ok(Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(e.to_string().into())
+                        .unwrap())
+                }
+            }
+        }
     }
 
-    // At the moment verification is only performed if the response is not using a streaming
-    // strategy. Performing verification for those requests would required to join all the chunks
-    // and this could cause memory issues and possibly create DOS attack vectors.
-    let should_validate = !http_response.has_streaming_body && !is_update_call;
-    if should_validate {
-        let validation = validator.validate(&agent, &canister_id, &http_request, &http_response);
-
-        if validation.is_err() {
-            return Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(validation.unwrap_err().into())
-                .unwrap());
+    // The certification of any other streamed response covers only the body, which is buffered
+    // by the proxy until it is verified.
+    let streaming_body_verifier = if should_validate && http_response.has_streaming_body {
+        match validator.validate_streaming(&agent, &canister_id, &http_request, &http_response) {
+            Ok(verifier) => verifier,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(e.into())
+                    .unwrap())
+            }
+        }
+    } else {
+        None
+    };
+
+    let certified_response = if should_validate && !http_response.has_streaming_body {
+        match validator.validate(&agent, &canister_id, &http_request, &http_response) {
+            Ok(certified_response) => certified_response,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(e.into())
+                    .unwrap())
+            }
+        }
+    } else {
+        None
+    };
+
+    // With response verification v2, only the certified status code and headers are served.
+    let (status_code, headers, is_certified) = match certified_response {
+        Some(certified_response) => (
+            certified_response.status_code,
+            certified_response.headers,
+            true,
+        ),
+        None => (
+            http_response.status_code,
+            http_response.headers.clone(),
+            false,
+        ),
+    };
+
+    // Only responses whose status code and headers are certified, i.e. with response
+    // verification v2, are cached, along with the time of the certificate they were verified
+    // with.
+    let is_cacheable = is_certified && !cfg!(feature = "skip_body_verification");
+    if let Some(cache) = cache.filter(|_| is_cacheable) {
+        if let Some(certificate_time) = certificate_time(&http_response.headers) {
+            cache
+                .insert(
+                    &canister_id,
+                    &http_request,
+                    CachedResponse {
+                        status_code,
+                        headers: headers.clone(),
+                        body: http_response.body.clone(),
+                    },
+                    certificate_time,
+                    SystemTime::now(),
+                )
+                .await;
         }
     }
 
-    let response = response_builder.body(match http_response.streaming_body {
-        Some(body) => body,
-        None => Body::from(http_response.body.clone()),
-    })?;
+    let response = range::build_response(
+        StatusCode::from_u16(status_code)?,
+        &headers,
+        match http_response.streaming_body {
+            Some(body) => ResponseBody::Streaming(body, streaming_body_verifier),
+            None => ResponseBody::Complete(http_response.body.clone()),
+        },
+        range,
+    )?;
 
     if enabled!(Level::TRACE) {
         trace!(
@@ -7,6 +7,7 @@ use ic_agent::{agent::http_transport::HyperReplicaV2Transport, Agent};
 use tracing::{error, info};
 
 use crate::{
+    cache::Cache,
     canister_id::ResolverState,
     http_client::{Body, HyperService},
     logging::add_trace_layer,
@@ -76,6 +77,7 @@ pub struct SetupArgs<V, C> {
     pub validator: V,
     pub resolver: ResolverState,
     pub client: C,
+    pub cache: Option<Arc<Cache>>,
 }
 
 pub fn setup<C: HyperService<Body> + 'static>(
@@ -127,6 +129,7 @@ pub fn setup<C: HyperService<Body> + 'static>(
         resolver: args.resolver,
         debug: opts.debug,
         client,
+        cache: args.cache,
     })));
 
     Ok(Runner {
@@ -145,6 +148,7 @@ struct AppStateInner<V, C> {
     validator: V,
     client: C,
     debug: bool,
+    cache: Option<Arc<Cache>>,
 }
 
 impl<V, C> AppState<V, C> {
@@ -163,6 +167,9 @@ impl<V, C> AppState<V, C> {
     pub fn debug(&self) -> bool {
         self.0.debug
     }
+    pub fn cache(&self) -> Option<&Arc<Cache>> {
+        self.0.cache.as_ref()
+    }
 }
 
 pub struct Runner {
@@ -1,21 +1,68 @@
+use crate::http::headers::IC_CERTIFICATE_HEADER_NAME;
 use crate::http::request::HttpRequest;
 use crate::http::response::HttpResponse;
 use candid::Principal;
-use ic_agent::Agent;
+use futures::{Stream, StreamExt};
+use hyper::{body::Bytes, Body};
+use ic_agent::{
+    hash_tree::{HashTree, Label, LookupResult},
+    lookup_value, Agent, Certificate,
+};
+use ic_response_verification::types::Response;
 use ic_response_verification::{verify_request_response_pair, MIN_VERIFICATION_VERSION};
+use serde_cbor::Value;
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::io::SeekFrom;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-const MAX_CERT_TIME_OFFSET_NS: u128 = 300_000_000_000;
+pub const MAX_CERT_TIME_OFFSET_NS: u128 = 300_000_000_000;
+
+/// Response verification v2 certifies the status code and headers as well as the body.
+const CERTIFIED_HEADERS_VERIFICATION_VERSION: u8 = 2;
+
+/// Streamed bodies certified with response verification v2 are verified as a whole, so they
+/// are read into memory, up to this size.
+pub const MAX_CERTIFIED_HEADERS_STREAMING_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+/// Streamed bodies are buffered until they pass verification, in memory up to this size and in
+/// a temporary file beyond it.
+const MAX_IN_MEMORY_STREAMING_BODY_SIZE: usize = 1024 * 1024;
+
+/// Streamed bodies of which more than this would have to be buffered are rejected.
+const MAX_BUFFERED_STREAMING_BODY_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// The size of the chunks buffered bodies are read from their temporary file in.
+const BUFFERED_CHUNK_SIZE: usize = 64 * 1024;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 pub trait Validate: Sync + Send {
+    /// Verifies the certification of the response. Returns the certified response if its
+    /// status code and headers are certified as well, in which case only those should be served.
     fn validate(
         &self,
         agent: &Agent,
         canister_id: &Principal,
         request: &HttpRequest,
         response: &HttpResponse,
-    ) -> Result<(), Cow<'static, str>>;
+    ) -> Result<Option<Response>, Cow<'static, str>>;
+
+    /// Verifies the certification of a streamed response, except for its body. Returns the
+    /// verifier of the body, if it needs to be verified, which is fed the chunks as they are
+    /// streamed. Responses certified with response verification v2 are rejected, as their status
+    /// code and headers can only be verified along with the whole body, see
+    /// [`requires_complete_body`].
+    fn validate_streaming(
+        &self,
+        agent: &Agent,
+        canister_id: &Principal,
+        request: &HttpRequest,
+        response: &HttpResponse,
+    ) -> Result<Option<StreamingBodyVerifier>, Cow<'static, str>>;
 }
 
 #[derive(Clone)]
@@ -34,9 +81,9 @@ impl Validate for Validator {
         canister_id: &Principal,
         request: &HttpRequest,
         response: &HttpResponse,
-    ) -> Result<(), Cow<'static, str>> {
+    ) -> Result<Option<Response>, Cow<'static, str>> {
         if cfg!(feature = "skip_body_verification") {
-            return Ok(());
+            return Ok(None);
         }
 
         let certification_result = match (
@@ -46,7 +93,7 @@ impl Validate for Validator {
             // TODO: Remove this (FOLLOW-483)
             // Canisters don't have to provide certified variables
             // This should change in the future, grandfathering in current implementations
-            (false, false) => return Ok(()),
+            (false, false) => return Ok(None),
             (_, _) => {
                 let ic_public_key = agent.read_root_key().map_err(|e| e.to_string())?;
                 verify_request_response_pair(
@@ -62,11 +109,373 @@ impl Validate for Validator {
             }
         };
 
-        match certification_result.passed {
-            true => Ok(()),
-            false => Err("Body does not pass verification".into()),
+        if !certification_result.passed {
+            return Err("Body does not pass verification".into());
+        }
+
+        if certification_version(&response.headers) < CERTIFIED_HEADERS_VERIFICATION_VERSION {
+            return Ok(None);
+        }
+
+        match certification_result.response {
+            Some(certified_response) => Ok(Some(certified_response)),
+            None => Err("Response verification did not return the certified response".into()),
         }
     }
+
+    fn validate_streaming(
+        &self,
+        agent: &Agent,
+        canister_id: &Principal,
+        request: &HttpRequest,
+        response: &HttpResponse,
+    ) -> Result<Option<StreamingBodyVerifier>, Cow<'static, str>> {
+        if cfg!(feature = "skip_body_verification") {
+            return Ok(None);
+        }
+
+        // TODO: Remove this (FOLLOW-483)
+        if !request.is_certification_required() && !response.has_ic_certificate() {
+            return Ok(None);
+        }
+
+        if requires_complete_body(&response.headers) {
+            return Err(
+                "Streamed responses certified with response verification v2 must be verified \
+                 with their complete body"
+                    .into(),
+            );
+        }
+
+        let decode_field = |field| {
+            certificate_header_field(&response.headers, field)
+                .and_then(|value| base64::decode(value.trim_matches(':')).ok())
+                .ok_or_else(|| format!("Missing or malformed {field} in the certificate header"))
+        };
+        let certificate = decode_field("certificate")?;
+        let tree = decode_field("tree")?;
+        let certificate: Certificate =
+            serde_cbor::from_slice(&certificate).map_err(|e| e.to_string())?;
+        let tree: HashTree = serde_cbor::from_slice(&tree).map_err(|e| e.to_string())?;
+
+        agent
+            .verify(&certificate, *canister_id)
+            .map_err(|e| e.to_string())?;
+
+        let certified_data = lookup_value(
+            &certificate,
+            vec![
+                "canister".into(),
+                canister_id.as_slice().into(),
+                "certified_data".into(),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        if certified_data != &tree.digest()[..] {
+            return Err("Tree does not match the certified data".into());
+        }
+
+        let time = lookup_value(&certificate, vec!["time".into()])
+            .ok()
+            .and_then(decode_leb128)
+            .ok_or("Missing or malformed certificate time")?;
+        if get_current_time_in_ns().abs_diff(time) > MAX_CERT_TIME_OFFSET_NS {
+            return Err("Certificate time is too far from the current time".into());
+        }
+
+        // Streamed bodies are certified by the hash of the whole body in the `http_assets`
+        // tree of response verification v1, which is keyed by the decoded path.
+        let path = percent_decode(request.uri.path()).ok_or("Malformed request path")?;
+        let path: [Label; 2] = ["http_assets".into(), path.into()];
+        let expected_sha256 = match tree.lookup_path(&path) {
+            LookupResult::Found(sha256) => <[u8; 32]>::try_from(sha256)
+                .map_err(|_| "Malformed certified hash of the streamed body")?,
+            _ => return Err("Streamed body is not certified".into()),
+        };
+
+        Ok(Some(StreamingBodyVerifier::new(expected_sha256)))
+    }
+}
+
+/// Verifies a streamed body chunk by chunk against the certified hash of the whole body.
+pub struct StreamingBodyVerifier {
+    hasher: Sha256,
+    expected_sha256: [u8; 32],
+}
+
+impl StreamingBodyVerifier {
+    pub fn new(expected_sha256: [u8; 32]) -> Self {
+        Self {
+            hasher: Sha256::new(),
+            expected_sha256,
+        }
+    }
+
+    /// Feeds the next chunk of the body to the verifier.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+    }
+
+    /// Checks the hash of all the chunks fed to the verifier against the certified hash.
+    pub fn verify(self) -> bool {
+        self.hasher.finalize()[..] == self.expected_sha256[..]
+    }
+}
+
+/// Verifies a streamed body and serves its bytes `bounds` (inclusive), or all of them if `None`.
+/// No byte is served before the whole body passed verification: the served bytes are buffered
+/// until then, and the stream fails instead if the body does not pass verification or too much
+/// of it would have to be buffered. Chunks after the range are still fetched to verify the body,
+/// but not buffered.
+pub fn verify_streaming_body(
+    body: Body,
+    verifier: StreamingBodyVerifier,
+    bounds: Option<(u64, u64)>,
+) -> Body {
+    Body::wrap_stream(verified_stream(
+        body,
+        verifier,
+        bounds,
+        MAX_BUFFERED_STREAMING_BODY_SIZE,
+    ))
+}
+
+fn verified_stream(
+    body: Body,
+    verifier: StreamingBodyVerifier,
+    bounds: Option<(u64, u64)>,
+    max_size: u64,
+) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static {
+    futures::stream::once(buffer_verified_body(body, verifier, bounds, max_size))
+        .map(|buffer| match buffer {
+            Ok(buffer) => buffer.into_stream().left_stream(),
+            Err(e) => futures::stream::once(async move { Err(e) }).right_stream(),
+        })
+        .flatten()
+}
+
+async fn buffer_verified_body(
+    mut body: Body,
+    mut verifier: StreamingBodyVerifier,
+    bounds: Option<(u64, u64)>,
+    max_size: u64,
+) -> Result<BodyBuffer, BoxError> {
+    let (start, end) = bounds.unwrap_or((0, u64::MAX));
+    let mut buffer = BodyBuffer::new(max_size);
+    let mut offset = 0u64;
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        verifier.update(&chunk);
+
+        let length = chunk.len() as u64;
+        let from = start.saturating_sub(offset).min(length);
+        let to = end
+            .saturating_add(1)
+            .saturating_sub(offset)
+            .min(length)
+            .max(from);
+        offset += length;
+
+        buffer.push(&chunk[from as usize..to as usize]).await?;
+    }
+
+    if !verifier.verify() {
+        return Err("Streamed body does not pass verification".into());
+    }
+
+    buffer.finish().await?;
+    Ok(buffer)
+}
+
+/// The bytes of a streamed body that are served once the body passed verification. They are
+/// kept in memory up to `MAX_IN_MEMORY_STREAMING_BODY_SIZE`, the rest is spilled to a temporary
+/// file, which is removed once it is dropped.
+struct BodyBuffer {
+    memory: Vec<u8>,
+    file: Option<File>,
+    size: u64,
+    max_size: u64,
+}
+
+impl BodyBuffer {
+    fn new(max_size: u64) -> Self {
+        Self {
+            memory: Vec::new(),
+            file: None,
+            size: 0,
+            max_size,
+        }
+    }
+
+    async fn push(&mut self, bytes: &[u8]) -> Result<(), BoxError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        self.size += bytes.len() as u64;
+        if self.size > self.max_size {
+            return Err("Streamed body is too large to be verified".into());
+        }
+
+        if self.file.is_none()
+            && self.memory.len() + bytes.len() <= MAX_IN_MEMORY_STREAMING_BODY_SIZE
+        {
+            self.memory.extend_from_slice(bytes);
+            return Ok(());
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(File::from_std(tempfile::tempfile()?)),
+        };
+        file.write_all(bytes).await?;
+        Ok(())
+    }
+
+    /// Prepares the temporary file, if any, to be read from the start.
+    async fn finish(&mut self) -> Result<(), BoxError> {
+        if let Some(file) = &mut self.file {
+            file.flush().await?;
+            file.seek(SeekFrom::Start(0)).await?;
+        }
+        Ok(())
+    }
+
+    fn into_stream(self) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static {
+        let memory = (!self.memory.is_empty()).then(|| Ok(Bytes::from(self.memory)));
+        futures::stream::iter(memory).chain(futures::stream::try_unfold(self.file, read_chunk))
+    }
+}
+
+/// Reads the next chunk of a buffered body from its temporary file.
+async fn read_chunk(file: Option<File>) -> Result<Option<(Bytes, Option<File>)>, BoxError> {
+    let mut file = match file {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+
+    let mut chunk = vec![0; BUFFERED_CHUNK_SIZE];
+    let length = file.read(&mut chunk).await?;
+    if length == 0 {
+        return Ok(None);
+    }
+    chunk.truncate(length);
+    Ok(Some((Bytes::from(chunk), Some(file))))
+}
+
+/// Whether the response is certified with response verification v2, whose certification of the
+/// status code and headers can only be verified along with the complete body.
+pub fn requires_complete_body(headers: &[(String, String)]) -> bool {
+    certification_version(headers) >= CERTIFIED_HEADERS_VERIFICATION_VERSION
+}
+
+/// Decodes the percent-encoded bytes of a path, as the `http_assets` tree of response
+/// verification v1 is keyed by decoded paths. Returns `None` if the decoded path is not UTF-8.
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i..i + 3) {
+            Some([b'%', high, low]) => {
+                let digit = |byte: &u8| char::from(*byte).to_digit(16);
+                digit(high)
+                    .zip(digit(low))
+                    .map(|(high, low)| (high * 16 + low) as u8)
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Returns the value of a field of the `ic-certificate` header.
+fn certificate_header_field<'a>(headers: &'a [(String, String)], field: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(IC_CERTIFICATE_HEADER_NAME))
+        .flat_map(|(_, value)| value.split(','))
+        .filter_map(|field| field.trim().split_once('='))
+        .find(|(name, _)| name.trim() == field)
+        .map(|(_, value)| value.trim())
+}
+
+/// Returns the response verification version of the `ic-certificate` header, which defaults
+/// to 1 if the header does not specify it.
+fn certification_version(headers: &[(String, String)]) -> u8 {
+    certificate_header_field(headers, "version")
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(1)
+}
+
+/// Returns the time of the certificate in the `ic-certificate` header, in nanoseconds since
+/// the UNIX epoch. The certificate is not verified, so this should only be used for
+/// responses that passed verification.
+pub fn certificate_time(headers: &[(String, String)]) -> Option<u128> {
+    let certificate = certificate_header_field(headers, "certificate")?;
+    let certificate = base64::decode(certificate.trim_matches(':')).ok()?;
+    let certificate: Value = serde_cbor::from_slice(&certificate).ok()?;
+
+    let tree = match &certificate {
+        Value::Map(fields) => fields.get(&Value::Text("tree".to_string()))?,
+        _ => return None,
+    };
+
+    decode_leb128(lookup_leaf(tree, b"time")?)
+}
+
+/// Looks up the leaf labeled `label` at the top level of a CBOR-encoded hash tree.
+fn lookup_leaf<'a>(tree: &'a Value, label: &[u8]) -> Option<&'a [u8]> {
+    let nodes = match tree {
+        Value::Array(nodes) => nodes,
+        _ => return None,
+    };
+
+    match nodes.as_slice() {
+        // Fork
+        [Value::Integer(1), left, right] => {
+            lookup_leaf(left, label).or_else(|| lookup_leaf(right, label))
+        }
+        // Labeled subtree
+        [Value::Integer(2), Value::Bytes(node_label), Value::Array(subtree)]
+            if node_label.as_slice() == label =>
+        {
+            match subtree.as_slice() {
+                // Leaf
+                [Value::Integer(3), Value::Bytes(leaf)] => Some(leaf.as_slice()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn decode_leb128(bytes: &[u8]) -> Option<u128> {
+    let mut value = 0u128;
+    for (i, byte) in bytes.iter().enumerate() {
+        let shift = 7 * i as u32;
+        if shift >= 128 {
+            return None;
+        }
+
+        value |= u128::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
 }
 
 fn get_current_time_in_ns() -> u128 {
@@ -91,7 +500,30 @@ mod tests {
         Agent,
     };
 
-    use crate::validate::{Validate, Validator};
+    use crate::http::headers::IC_CERTIFICATE_HEADER_NAME;
+    use crate::validate::{
+        certificate_time, certification_version, percent_decode, requires_complete_body,
+        verified_stream, verify_streaming_body, StreamingBodyVerifier, Validate, Validator,
+        MAX_IN_MEMORY_STREAMING_BODY_SIZE,
+    };
+    use serde_cbor::Value;
+    use sha2::{Digest, Sha256};
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn streaming_body() -> Body {
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(b"first ".to_vec()),
+            Ok(b"second ".to_vec()),
+            Ok(b"third".to_vec()),
+        ];
+
+        Body::wrap_stream(futures::stream::iter(chunks))
+    }
 
     #[test]
     fn validate_nop() {
@@ -119,6 +551,197 @@ mod tests {
             },
         );
 
-        assert_eq!(out, Ok(()));
+        assert_eq!(out, Ok(None));
+    }
+
+    #[test]
+    fn certification_version_from_header() {
+        let headers =
+            |value: &str| vec![(IC_CERTIFICATE_HEADER_NAME.to_string(), value.to_string())];
+
+        assert_eq!(certification_version(&[]), 1);
+        assert_eq!(
+            certification_version(&headers("certificate=:Y2VydA==:, tree=:dHJlZQ==:")),
+            1
+        );
+        assert_eq!(
+            certification_version(&headers(
+                "certificate=:Y2VydA==:, tree=:dHJlZQ==:, version=2, expr_path=:cGF0aA==:"
+            )),
+            2
+        );
+        assert_eq!(
+            certification_version(&[("IC-Certificate".to_string(), "version=2".to_string())]),
+            2
+        );
+    }
+
+    #[test]
+    fn certificate_time_from_header() {
+        let leaf = |label: &[u8], value: Vec<u8>| {
+            Value::Array(vec![
+                Value::Integer(2),
+                Value::Bytes(label.to_vec()),
+                Value::Array(vec![Value::Integer(3), Value::Bytes(value)]),
+            ])
+        };
+
+        // 1_700_000_000_000_000_000 nanoseconds, LEB128-encoded
+        let time = vec![0x80, 0x80, 0xa8, 0xb1, 0xe3, 0x9f, 0xe7, 0xcb, 0x17];
+        let tree = Value::Array(vec![
+            Value::Integer(1),
+            Value::Array(vec![Value::Integer(4), Value::Bytes(vec![0; 32])]),
+            leaf(b"time", time),
+        ]);
+        let certificate = Value::Map(
+            vec![
+                (Value::Text("tree".to_string()), tree),
+                (
+                    Value::Text("signature".to_string()),
+                    Value::Bytes(vec![0; 48]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let certificate = base64::encode(serde_cbor::to_vec(&certificate).unwrap());
+
+        let headers = vec![(
+            IC_CERTIFICATE_HEADER_NAME.to_string(),
+            format!("certificate=:{certificate}:, tree=:dHJlZQ==:"),
+        )];
+        assert_eq!(certificate_time(&headers), Some(1_700_000_000_000_000_000));
+
+        assert_eq!(certificate_time(&[]), None);
+        let headers = vec![(
+            IC_CERTIFICATE_HEADER_NAME.to_string(),
+            "certificate=:Y2VydA==:".to_string(),
+        )];
+        assert_eq!(certificate_time(&headers), None);
+    }
+
+    #[test]
+    fn streaming_body_passes_verification() {
+        let sha256 = Sha256::digest(b"first second third").into();
+        let body =
+            verify_streaming_body(streaming_body(), StreamingBodyVerifier::new(sha256), None);
+
+        assert_eq!(
+            aw!(hyper::body::to_bytes(body)).unwrap().to_vec(),
+            b"first second third".to_vec()
+        );
+    }
+
+    #[test]
+    fn streaming_body_fails_verification() {
+        let sha256 = Sha256::digest(b"first second fourth").into();
+        let mut body =
+            verify_streaming_body(streaming_body(), StreamingBodyVerifier::new(sha256), None);
+
+        // No chunk is served before the body fails verification.
+        match aw!(hyper::body::HttpBody::data(&mut body)) {
+            Some(Err(error)) => assert!(error.to_string().contains("verification"), "{error}"),
+            Some(Ok(chunk)) => panic!("Unverified chunk {chunk:?} was served"),
+            None => panic!("Streamed body passed verification"),
+        }
+    }
+
+    #[test]
+    fn streaming_body_is_spilled_to_disk() {
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = (0..3u8)
+            .map(|i| Ok(vec![i; MAX_IN_MEMORY_STREAMING_BODY_SIZE / 2 + 1]))
+            .collect();
+        let expected = chunks
+            .iter()
+            .flat_map(|chunk| chunk.as_ref().unwrap().clone())
+            .collect::<Vec<_>>();
+        let verifier = StreamingBodyVerifier::new(Sha256::digest(&expected).into());
+
+        let body = verify_streaming_body(
+            Body::wrap_stream(futures::stream::iter(chunks)),
+            verifier,
+            None,
+        );
+        assert_eq!(aw!(hyper::body::to_bytes(body)).unwrap().to_vec(), expected);
+    }
+
+    #[test]
+    fn streaming_body_too_large_to_buffer() {
+        let sha256 = Sha256::digest(b"first second third").into();
+        let stream = verified_stream(
+            streaming_body(),
+            StreamingBodyVerifier::new(sha256),
+            None,
+            10,
+        );
+        let body = Body::wrap_stream(stream);
+
+        let error = aw!(hyper::body::to_bytes(body)).unwrap_err();
+        assert!(error.to_string().contains("too large"), "{error}");
+
+        // Only the served range is buffered
+        let stream = verified_stream(
+            streaming_body(),
+            StreamingBodyVerifier::new(sha256),
+            Some((6, 11)),
+            10,
+        );
+        assert_eq!(
+            aw!(hyper::body::to_bytes(Body::wrap_stream(stream)))
+                .unwrap()
+                .to_vec(),
+            b"second".to_vec()
+        );
+    }
+
+    #[test]
+    fn decode_asset_path() {
+        assert_eq!(percent_decode("/index.html").unwrap(), "/index.html");
+        assert_eq!(
+            percent_decode("/my%20file%2Bv2.txt").unwrap(),
+            "/my file+v2.txt"
+        );
+        assert_eq!(percent_decode("/%C3%A4").unwrap(), "/ä");
+        // Invalid escapes are kept
+        assert_eq!(percent_decode("/100%/%+1/%4").unwrap(), "/100%/%+1/%4");
+        assert_eq!(percent_decode("/%FF"), None);
+    }
+
+    #[test]
+    fn certified_headers_require_complete_body() {
+        let headers =
+            |value: &str| vec![(IC_CERTIFICATE_HEADER_NAME.to_string(), value.to_string())];
+
+        assert!(!requires_complete_body(&[]));
+        assert!(!requires_complete_body(&headers(
+            "certificate=:Y2VydA==:, tree=:dHJlZQ==:"
+        )));
+        assert!(requires_complete_body(&headers(
+            "certificate=:Y2VydA==:, tree=:dHJlZQ==:, version=2, expr_path=:cGF0aA==:"
+        )));
+    }
+
+    #[test]
+    fn streaming_body_range_passes_verification() {
+        let sha256 = Sha256::digest(b"first second third").into();
+        let body = verify_streaming_body(
+            streaming_body(),
+            StreamingBodyVerifier::new(sha256),
+            Some((3, 8)),
+        );
+
+        assert_eq!(
+            aw!(hyper::body::to_bytes(body)).unwrap().to_vec(),
+            b"st sec".to_vec()
+        );
+
+        // The range is not served if the body fails verification.
+        let sha256 = Sha256::digest(b"first second fourth").into();
+        let body = verify_streaming_body(
+            streaming_body(),
+            StreamingBodyVerifier::new(sha256),
+            Some((3, 8)),
+        );
+        assert!(aw!(hyper::body::to_bytes(body)).is_err());
     }
 }
@@ -13,7 +13,7 @@
 use crate::CertificationVersion;
 use ic_error_types::TryFromError;
 use ic_protobuf::proxy::ProxyDecodeError;
-use ic_types::xnet::StreamIndex;
+use ic_types::{xnet::StreamIndex, CoarseTime};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
@@ -65,6 +65,8 @@ pub struct Request {
     pub method_payload: Bytes,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_payment: Option<Cycles>,
+    #[serde(skip_serializing_if = "is_zero_u32", default)]
+    pub deadline: u32,
 }
 
 /// Canonical representation of `ic_types::messages::Response`.
@@ -80,6 +82,8 @@ pub struct Response {
     pub response_payload: Payload,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_refund: Option<Cycles>,
+    #[serde(skip_serializing_if = "is_zero_u32", default)]
+    pub deadline: u32,
 }
 
 /// Canonical representation of `ic_types::funds::Cycles`.
@@ -105,6 +109,10 @@ pub fn is_zero(v: &u64) -> bool {
     *v == 0
 }
 
+pub fn is_zero_u32(v: &u32) -> bool {
+    *v == 0
+}
+
 /// Canonical representation of `ic_types::messages::Payload`.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -239,6 +247,12 @@ impl From<(&ic_types::messages::Request, CertificationVersion)> for Request {
             cycles: (&request.payment, certification_version).into(),
             icp: 0,
         };
+        let deadline = request.deadline.as_secs_since_unix_epoch();
+        assert!(
+            deadline == 0 || certification_version >= CertificationVersion::V12,
+            "Best-effort requests cannot be encoded at certification version {:?}",
+            certification_version
+        );
         Self {
             receiver: request.receiver.get().to_vec(),
             sender: request.sender.get().to_vec(),
@@ -247,6 +261,7 @@ impl From<(&ic_types::messages::Request, CertificationVersion)> for Request {
             method_name: request.method_name.clone(),
             method_payload: request.method_payload.clone(),
             cycles_payment: None,
+            deadline,
         }
     }
 }
@@ -268,6 +283,7 @@ impl TryFrom<Request> for ic_types::messages::Request {
             payment,
             method_name: request.method_name,
             method_payload: request.method_payload,
+            deadline: CoarseTime::from_secs_since_unix_epoch(request.deadline),
         })
     }
 }
@@ -280,6 +296,12 @@ impl From<(&ic_types::messages::Response, CertificationVersion)> for Response {
             cycles: (&response.refund, certification_version).into(),
             icp: 0,
         };
+        let deadline = response.deadline.as_secs_since_unix_epoch();
+        assert!(
+            deadline == 0 || certification_version >= CertificationVersion::V12,
+            "Best-effort responses cannot be encoded at certification version {:?}",
+            certification_version
+        );
         Self {
             originator: response.originator.get().to_vec(),
             respondent: response.respondent.get().to_vec(),
@@ -287,6 +309,7 @@ impl From<(&ic_types::messages::Response, CertificationVersion)> for Response {
             refund: funds,
             response_payload: (&response.response_payload, certification_version).into(),
             cycles_refund: None,
+            deadline,
         }
     }
 }
@@ -307,6 +330,7 @@ impl TryFrom<Response> for ic_types::messages::Response {
             originator_reply_callback: response.originator_reply_callback.into(),
             refund,
             response_payload: response.response_payload.try_into()?,
+            deadline: CoarseTime::from_secs_since_unix_epoch(response.deadline),
         })
     }
 }
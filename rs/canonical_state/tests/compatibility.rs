@@ -186,10 +186,31 @@ proptest! {
 /// Produces a `RequestOrResponse` valid at all certification versions in the range.
 pub(crate) fn arb_valid_versioned_message(
 ) -> impl Strategy<Value = (RequestOrResponse, RangeInclusive<CertificationVersion>)> {
-    prop_oneof![(
-        arbitrary::request_or_response(),
-        Just(CertificationVersion::V0..=MAX_SUPPORTED_CERTIFICATION_VERSION)
-    ),]
+    prop_oneof![
+        (
+            arbitrary::request_or_response(),
+            Just(CertificationVersion::V0..=MAX_SUPPORTED_CERTIFICATION_VERSION)
+        ),
+        // Best-effort messages may only be encoded starting with certification
+        // version 12.
+        (
+            arbitrary::best_effort_request_or_response(),
+            Just(CertificationVersion::V12..=MAX_SUPPORTED_CERTIFICATION_VERSION)
+        ),
+    ]
+}
+
+/// Produces a `RequestOrResponse` invalid at all certification versions in the range.
+pub(crate) fn arb_invalid_versioned_message(
+) -> impl Strategy<Value = (RequestOrResponse, RangeInclusive<CertificationVersion>)> {
+    prop_oneof![
+        // Encoding a best-effort message before certification version 12 should
+        // panic.
+        (
+            arbitrary::best_effort_request_or_response(),
+            Just(CertificationVersion::V4..=CertificationVersion::V11)
+        ),
+    ]
 }
 
 lazy_static! {
@@ -259,6 +280,24 @@ proptest! {
             }
         }
     }
+
+    /// Tests that, given a `RequestOrResponse` that is invalid for a given
+    /// certification version range (e.g. a best-effort message before
+    /// certification version 12), encoding will panic.
+    #[test]
+    fn message_encoding_panic_on_invalid((message, version_range) in arb_invalid_versioned_message()) {
+        for version in iter(version_range) {
+            for encoding in &*MESSAGE_ENCODINGS {
+                if encoding.version_range.contains(&version) {
+                    let result = std::panic::catch_unwind(|| {
+                        (encoding.encode)((&message, version))
+                    });
+
+                    assert!(result.is_err(), "Encoding of invalid {}@{:?} succeeded", encoding.name, version);
+                }
+            }
+        }
+    }
 }
 
 lazy_static! {
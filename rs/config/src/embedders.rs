@@ -68,6 +68,11 @@ pub struct FeatureFlags {
     /// Track dirty pages with a write barrier instead of the signal handler.
     pub write_barrier: FlagStatus,
     pub wasm_native_stable_memory: FlagStatus,
+    /// Allow canisters to make best-effort calls via
+    /// `ic0.call_with_best_effort_response`. Best-effort messages can only be
+    /// certified starting with `CertificationVersion::V12`, so this must not be
+    /// enabled before that is the current certification version.
+    pub best_effort_responses: FlagStatus,
 }
 
 impl FeatureFlags {
@@ -76,6 +81,7 @@ impl FeatureFlags {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             write_barrier: FlagStatus::Disabled,
             wasm_native_stable_memory: FlagStatus::Disabled,
+            best_effort_responses: FlagStatus::Disabled,
         }
     }
 }
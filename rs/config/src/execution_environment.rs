@@ -26,6 +26,10 @@ const GIB: u64 = MIB * 1024;
 /// canister's data and the deltas.
 const SUBNET_MEMORY_CAPACITY: NumBytes = NumBytes::new(450 * GIB);
 
+/// Once the subnet memory usage exceeds this threshold, canisters that
+/// allocate memory reserve cycles for its future storage fees.
+const SUBNET_MEMORY_THRESHOLD: NumBytes = NumBytes::new(300 * GIB);
+
 /// This is the upper limit on how much memory can be used by all canister
 /// messages on a given subnet.
 ///
@@ -93,6 +97,10 @@ const QUERY_SCHEDULING_TIME_SLICE_PER_CANISTER: Duration = Duration::from_millis
 /// executions and user errors.
 const QUERY_CACHE_CAPACITY: NumBytes = NumBytes::new(100 * MIB);
 
+/// The number of certified heights over which each replica collects query
+/// statistics before sending them through consensus.
+const QUERY_STATS_EPOCH_LENGTH: u64 = 600;
+
 // The ID of the Bitcoin testnet canister.
 pub const BITCOIN_TESTNET_CANISTER_ID: &str = "g4xu7-jiaaa-aaaan-aaaaq-cai";
 
@@ -109,6 +117,10 @@ const BITCOIN_MAINNET_SOFT_LAUNCH_CANISTER_ID: &str = "gsvzx-syaaa-aaaan-aaabq-c
 /// The capacity of the Wasm compilation cache.
 pub const MAX_COMPILATION_CACHE_SIZE: NumBytes = NumBytes::new(10 * GIB);
 
+/// The `canister_on_low_wasm_memory` hook runs once the free Wasm heap of a
+/// canister drops below this threshold.
+pub const LOW_WASM_MEMORY_THRESHOLD: NumBytes = NumBytes::new(100 * MIB);
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default)]
 pub struct Config {
@@ -124,6 +136,10 @@ pub struct Config {
     /// the subnet.
     pub subnet_memory_capacity: NumBytes,
 
+    /// The subnet memory usage above which canisters that allocate memory
+    /// reserve cycles for its future storage fees.
+    pub subnet_memory_threshold: NumBytes,
+
     /// The maximum amount of logical storage available to canister messages
     /// across the whole subnet.
     pub subnet_message_memory_capacity: NumBytes,
@@ -215,6 +231,9 @@ pub struct Config {
     /// Query cache capacity in bytes
     pub query_cache_capacity: NumBytes,
 
+    /// The length of a query stats epoch in certified heights.
+    pub query_stats_epoch_length: u64,
+
     /// Sandbox process eviction does not activate if the number of sandbox
     /// processes is below this threshold.
     pub min_sandbox_count: usize,
@@ -233,6 +252,10 @@ pub struct Config {
 
     /// The capacity of the Wasm compilation cache.
     pub max_compilation_cache_size: NumBytes,
+
+    /// The scheduler runs the `canister_on_low_wasm_memory` hook of a canister
+    /// once its free Wasm heap drops below this threshold.
+    pub low_wasm_memory_threshold: NumBytes,
 }
 
 impl Default for Config {
@@ -251,6 +274,7 @@ impl Default for Config {
             create_funds_whitelist: String::default(),
             max_instructions_for_message_acceptance_calls: MAX_INSTRUCTIONS_PER_MESSAGE_WITHOUT_DTS,
             subnet_memory_capacity: SUBNET_MEMORY_CAPACITY,
+            subnet_memory_threshold: SUBNET_MEMORY_THRESHOLD,
             subnet_message_memory_capacity: SUBNET_MESSAGE_MEMORY_CAPACITY,
             ingress_history_memory_capacity: INGRESS_HISTORY_MEMORY_CAPACITY,
             subnet_wasm_custom_sections_memory_capacity:
@@ -296,6 +320,7 @@ impl Default for Config {
             composite_queries: FlagStatus::Disabled,
             query_caching: FlagStatus::Disabled,
             query_cache_capacity: QUERY_CACHE_CAPACITY,
+            query_stats_epoch_length: QUERY_STATS_EPOCH_LENGTH,
             min_sandbox_count: embedders::DEFAULT_MIN_SANDBOX_COUNT,
             max_sandbox_count: embedders::DEFAULT_MAX_SANDBOX_COUNT,
             max_sandbox_idle_time: embedders::DEFAULT_MAX_SANDBOX_IDLE_TIME,
@@ -303,6 +328,7 @@ impl Default for Config {
                 embedders::STABLE_MEMORY_DIRTY_PAGE_LIMIT,
             ),
             max_compilation_cache_size: MAX_COMPILATION_CACHE_SIZE,
+            low_wasm_memory_threshold: LOW_WASM_MEMORY_THRESHOLD,
         }
     }
 }
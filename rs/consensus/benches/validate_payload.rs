@@ -21,6 +21,7 @@ use ic_ic00_types::IC_00;
 use ic_ingress_manager::IngressManager;
 use ic_interfaces::{
     artifact_pool::MutablePool,
+    batch_payload::ProposalContext,
     consensus::{PayloadBuilder, PayloadValidationError},
     consensus_pool::{ChangeAction, ChangeSet, ConsensusPool},
     time_source::TimeSource,
@@ -38,6 +39,7 @@ use ic_test_utilities::{
     consensus::{fake::*, make_genesis, MockConsensusCache},
     crypto::temp_crypto_component_with_fake_registry,
     cycles_account_manager::CyclesAccountManagerBuilder,
+    query_stats::FakeQueryStatsPayloadBuilder,
     self_validating_payload_builder::FakeSelfValidatingPayloadBuilder,
     state::ReplicatedStateBuilder,
     types::ids::{canister_test_id, node_test_id, subnet_test_id},
@@ -157,6 +159,7 @@ where
             Arc::new(FakeXNetPayloadBuilder::new()),
             Arc::new(FakeSelfValidatingPayloadBuilder::new()),
             Arc::new(FakeCanisterHttpPayloadBuilder::new()),
+            Arc::new(FakeQueryStatsPayloadBuilder::new()),
             metrics_registry,
             no_op_logger(),
         ));
@@ -307,7 +310,10 @@ fn validate_payload(
         Height::from(CERTIFIED_HEIGHT + 1),
         payload,
         &past_payloads,
-        &validation_context,
+        &ProposalContext {
+            proposer: node_test_id(0),
+            validation_context: &validation_context,
+        },
     )
 }
 
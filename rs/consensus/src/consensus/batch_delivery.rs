@@ -27,7 +27,7 @@ use ic_types::{
         canister_threshold_sig::MasterEcdsaPublicKey,
         threshold_sig::ni_dkg::{NiDkgId, NiDkgTag, NiDkgTranscript},
     },
-    messages::{CallbackId, Payload, RejectContext, Response},
+    messages::{CallbackId, Payload, RejectContext, Response, NO_DEADLINE},
     CanisterId, Cycles, Height, PrincipalId, Randomness, ReplicaVersion, SubnetId,
 };
 use std::collections::BTreeMap;
@@ -301,6 +301,7 @@ pub fn generate_execution_responses_for_canister_http_responses(
                         ic_types::messages::Payload::Reject((canister_http_reject).into())
                     }
                 },
+                deadline: NO_DEADLINE,
             }
         })
         // Deliver timeout responses
@@ -319,6 +320,7 @@ pub fn generate_execution_responses_for_canister_http_responses(
                             message: "Canister http request timed out".to_string(),
                         },
                     ),
+                    deadline: NO_DEADLINE,
                 }),
         )
         .chain(
@@ -337,6 +339,7 @@ pub fn generate_execution_responses_for_canister_http_responses(
                                 message: "Canister http responses were different across replicas, and no consensus was reached".to_string(),
                             },
                         ),
+                        deadline: NO_DEADLINE,
                     })
                 }),
         )
@@ -408,6 +411,7 @@ pub fn generate_responses_to_setup_initial_dkg_calls(
                 originator_reply_callback: callback_id,
                 refund: Cycles::zero(),
                 response_payload,
+                deadline: NO_DEADLINE,
             });
         }
     }
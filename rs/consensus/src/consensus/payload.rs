@@ -2,8 +2,11 @@ use crate::consensus::metrics::{
     PayloadBuilderMetrics, CRITICAL_ERROR_PAYLOAD_TOO_LARGE, CRITICAL_ERROR_VALIDATION_NOT_PASSED,
 };
 use ic_interfaces::{
-    canister_http::CanisterHttpPayloadBuilder, consensus::PayloadValidationError,
-    ingress_manager::IngressSelector, messaging::XNetPayloadBuilder,
+    batch_payload::{BatchPayloadBuilder, PastPayload, ProposalContext},
+    canister_http::CanisterHttpPayloadBuilder,
+    consensus::PayloadValidationError,
+    ingress_manager::IngressSelector,
+    messaging::XNetPayloadBuilder,
     self_validating_payload::SelfValidatingPayloadBuilder,
 };
 use ic_logger::{error, warn, ReplicaLogger};
@@ -41,6 +44,7 @@ pub(crate) enum BatchPayloadSectionBuilder {
     XNet(Arc<dyn XNetPayloadBuilder>),
     SelfValidating(Arc<dyn SelfValidatingPayloadBuilder>),
     CanisterHttp(Arc<dyn CanisterHttpPayloadBuilder>),
+    QueryStats(Arc<dyn BatchPayloadBuilder>),
 }
 
 impl BatchPayloadSectionBuilder {
@@ -207,6 +211,28 @@ impl BatchPayloadSectionBuilder {
                     }
                 }
             }
+            Self::QueryStats(builder) => {
+                let past_payloads =
+                    filter_past_payloads(past_payloads, |batch| batch.query_stats.as_slice());
+                let query_stats =
+                    builder.build_payload(max_size, &past_payloads, validation_context);
+                let size = NumBytes::new(query_stats.len() as u64);
+
+                if size > max_size {
+                    error!(
+                        logger,
+                        "QueryStats payload is larger than byte_limit. This is a bug, @{}",
+                        CRITICAL_ERROR_PAYLOAD_TOO_LARGE
+                    );
+
+                    metrics.critical_error_payload_too_large.inc();
+                    payload.query_stats = vec![];
+                    NumBytes::new(0)
+                } else {
+                    payload.query_stats = query_stats;
+                    size
+                }
+            }
         }
     }
 
@@ -214,7 +240,7 @@ impl BatchPayloadSectionBuilder {
     ///
     /// # Argument:
     /// - `payload`: The payload to verify.
-    /// - `validation_context`: The [`ValidationContext`], under which to validate the payload.
+    /// - `proposal_context`: The [`ProposalContext`], under which to validate the payload.
     /// - `past_payloads`: All [`Payload`]s from the certified height to the tip.
     ///
     /// # Returns:
@@ -224,9 +250,10 @@ impl BatchPayloadSectionBuilder {
         &self,
         height: Height,
         payload: &BatchPayload,
-        validation_context: &ValidationContext,
+        proposal_context: &ProposalContext,
         past_payloads: &[(Height, Time, Payload)],
     ) -> Result<NumBytes, PayloadValidationError> {
+        let validation_context = proposal_context.validation_context;
         match self {
             Self::Ingress(builder) => {
                 let past_payloads = builder.filter_past_payloads(past_payloads, validation_context);
@@ -262,6 +289,35 @@ impl BatchPayloadSectionBuilder {
                     &past_payloads,
                 )?)
             }
+            Self::QueryStats(builder) => {
+                let past_payloads =
+                    filter_past_payloads(past_payloads, |batch| batch.query_stats.as_slice());
+                builder.validate_payload(&payload.query_stats, &past_payloads, proposal_context)?;
+                Ok(NumBytes::new(payload.query_stats.len() as u64))
+            }
         }
     }
 }
+
+/// Extracts the [`PastPayload`]s of a section that is built by a generic
+/// [`BatchPayloadBuilder`] from the payloads of past blocks.
+///
+/// Summary blocks do not contain a batch payload and are skipped.
+fn filter_past_payloads<'a, F>(
+    past_payloads: &'a [(Height, Time, Payload)],
+    extractor: F,
+) -> Vec<PastPayload<'a>>
+where
+    F: Fn(&'a BatchPayload) -> &'a [u8],
+{
+    past_payloads
+        .iter()
+        .filter(|(_, _, payload)| !payload.is_summary())
+        .map(|(height, time, payload)| PastPayload {
+            height: *height,
+            time: *time,
+            block_hash: payload.get_hash().clone(),
+            payload: extractor(&payload.as_ref().as_data().batch),
+        })
+        .collect()
+}
@@ -6,6 +6,7 @@ use crate::consensus::{
 };
 use ic_consensus_utils::get_subnet_record;
 use ic_interfaces::{
+    batch_payload::{BatchPayloadBuilder, ProposalContext},
     canister_http::CanisterHttpPayloadBuilder,
     consensus::{PayloadBuilder, PayloadPermanentError, PayloadValidationError},
     ingress_manager::IngressSelector,
@@ -43,6 +44,7 @@ impl PayloadBuilderImpl {
         xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
         self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
         canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
+        query_stats_payload_builder: Arc<dyn BatchPayloadBuilder>,
         metrics: MetricsRegistry,
         logger: ReplicaLogger,
     ) -> Self {
@@ -51,6 +53,7 @@ impl PayloadBuilderImpl {
             BatchPayloadSectionBuilder::SelfValidating(self_validating_payload_builder),
             BatchPayloadSectionBuilder::XNet(xnet_payload_builder),
             BatchPayloadSectionBuilder::CanisterHttp(canister_http_payload_builder),
+            BatchPayloadSectionBuilder::QueryStats(query_stats_payload_builder),
         ];
 
         Self {
@@ -118,14 +121,14 @@ impl PayloadBuilder for PayloadBuilderImpl {
         height: Height,
         payload: &Payload,
         past_payloads: &[(Height, Time, Payload)],
-        context: &ValidationContext,
+        proposal_context: &ProposalContext,
     ) -> ValidationResult<PayloadValidationError> {
         let _timer = self.metrics.validate_payload_duration.start_timer();
         if payload.is_summary() {
             return Ok(());
         }
         let batch_payload = &payload.as_ref().as_data().batch;
-        let subnet_record = self.get_subnet_record(context)?;
+        let subnet_record = self.get_subnet_record(proposal_context.validation_context)?;
 
         // Retrieve max_block_payload_size from subnet
         let max_block_payload_size = self.get_max_block_payload_size_bytes(&subnet_record);
@@ -133,7 +136,7 @@ impl PayloadBuilder for PayloadBuilderImpl {
         let mut accumulated_size = NumBytes::new(0);
         for builder in &self.section_builder {
             accumulated_size +=
-                builder.validate_payload(height, batch_payload, context, past_payloads)?;
+                builder.validate_payload(height, batch_payload, proposal_context, past_payloads)?;
             if accumulated_size > max_block_payload_size {
                 return Err(ValidationError::Permanent(
                     PayloadPermanentError::PayloadTooBig {
@@ -199,6 +202,7 @@ pub(crate) mod test {
         consensus::fake::Fake,
         ingress_selector::FakeIngressSelector,
         mock_time,
+        query_stats::FakeQueryStatsPayloadBuilder,
         self_validating_payload_builder::FakeSelfValidatingPayloadBuilder,
         types::ids::{node_test_id, subnet_test_id},
         types::messages::SignedIngressBuilder,
@@ -206,6 +210,7 @@ pub(crate) mod test {
     };
     use ic_test_utilities_registry::SubnetRecordBuilder;
     use ic_types::{
+        batch::{CanisterQueryStats, QueryStats, QueryStatsPayload},
         canister_http::CanisterHttpResponseWithConsensus,
         consensus::certification::{Certification, CertificationContent},
         crypto::{CryptoHash, Signed},
@@ -255,6 +260,7 @@ pub(crate) mod test {
             Arc::new(xnet_payload_builder),
             Arc::new(self_validating_payload_builder),
             Arc::new(canister_http_payload_builder),
+            Arc::new(FakeQueryStatsPayloadBuilder::new()),
             MetricsRegistry::new(),
             no_op_logger(),
         )
@@ -366,4 +372,52 @@ pub(crate) mod test {
             }
         }
     }
+
+    #[test]
+    fn test_query_stats_are_delivered_with_the_batch() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let Dependencies { registry, .. } = dependencies(pool_config, 1);
+            let query_stats = QueryStatsPayload {
+                epoch: 3.into(),
+                proposer: node_test_id(0),
+                canister_stats: vec![CanisterQueryStats {
+                    canister_id: ic_test_utilities::types::ids::canister_test_id(1),
+                    stats: QueryStats {
+                        num_calls: 5,
+                        num_instructions: 1000,
+                        ingress_payload_size: 10,
+                        egress_payload_size: 20,
+                    },
+                }],
+            };
+            let payload_builder = PayloadBuilderImpl::new(
+                subnet_test_id(0),
+                registry,
+                Arc::new(FakeIngressSelector::new()),
+                Arc::new(FakeXNetPayloadBuilder::new()),
+                Arc::new(FakeSelfValidatingPayloadBuilder::new()),
+                Arc::new(FakeCanisterHttpPayloadBuilder::new()),
+                Arc::new(FakeQueryStatsPayloadBuilder::new().with_payload(query_stats.clone())),
+                MetricsRegistry::new(),
+                no_op_logger(),
+            );
+
+            let context = ValidationContext {
+                certified_height: Height::from(0),
+                registry_version: RegistryVersion::from(1),
+                time: mock_time(),
+            };
+            let subnet_record = SubnetRecordBuilder::from(&[node_test_id(0)]).build();
+            let subnet_records = SubnetRecords {
+                membership_version: subnet_record.clone(),
+                context_version: subnet_record,
+            };
+
+            let batch_messages = payload_builder
+                .get_payload(Height::from(1), &[], &context, &subnet_records)
+                .into_messages()
+                .unwrap();
+            assert_eq!(batch_messages.query_stats, Some(query_stats));
+        })
+    }
 }
@@ -15,6 +15,7 @@ use ic_consensus_utils::{
     RoundRobin,
 };
 use ic_interfaces::{
+    batch_payload::ProposalContext,
     consensus::{PayloadBuilder, PayloadPermanentError, PayloadTransientError},
     consensus_pool::*,
     dkg::DkgPool,
@@ -960,6 +961,7 @@ impl Validator {
 
         let parent = get_notarized_parent(pool_reader, proposal)?;
         self.verify_signature(pool_reader, proposal)?;
+        let proposer = proposal.signature.signer;
 
         // Ensure registry_version, certified_height and time are non-decreasing.
         let proposal = proposal.as_ref();
@@ -998,7 +1000,10 @@ impl Validator {
                 proposal.height,
                 &proposal.payload,
                 &payloads,
-                &proposal.context,
+                &ProposalContext {
+                    proposer,
+                    validation_context: &proposal.context,
+                },
             )
             .map_err(|err| {
                 err.map(
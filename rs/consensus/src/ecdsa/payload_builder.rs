@@ -38,7 +38,7 @@ use ic_types::{
         },
         AlgorithmId,
     },
-    messages::{CallbackId, RejectContext},
+    messages::{CallbackId, RejectContext, NO_DEADLINE},
     registry::RegistryClientError,
     Height, NodeId, RegistryVersion, SubnetId, Time,
 };
@@ -983,6 +983,7 @@ pub(crate) fn get_signing_requests<'a>(
                     code: RejectCode::CanisterReject,
                     message: format!("Invalid key_id in signature request: {:?}", context.key_id),
                 }),
+                deadline: NO_DEADLINE,
             };
             ecdsa_payload.signature_agreements.insert(
                 context.pseudo_random_id,
@@ -1039,6 +1040,7 @@ pub(crate) fn get_signing_requests<'a>(
                         code: RejectCode::CanisterError,
                         message: "Signature request expired".to_string(),
                     }),
+                    deadline: NO_DEADLINE,
                 };
                 ecdsa_payload.signature_agreements.insert(
                     context.pseudo_random_id,
@@ -1127,6 +1129,7 @@ pub(crate) fn update_signature_agreements(
                 }
                 .encode(),
             ),
+            deadline: NO_DEADLINE,
         };
         completed.insert(*request_id, ecdsa::CompletedSignature::Unreported(response));
     }
@@ -1594,6 +1597,7 @@ fn make_reshare_dealings_response(
                             }
                             .encode(),
                         ),
+                        deadline: NO_DEADLINE,
                     });
                 }
             }
@@ -269,6 +269,7 @@ pub(crate) mod test_utils {
             // be refunded to the canister.
             refund: ic_types::Cycles::new(0),
             response_payload: ic_types::messages::Payload::Data(vec![]),
+            deadline: ic_types::messages::NO_DEADLINE,
         }
     }
 
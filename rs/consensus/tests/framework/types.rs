@@ -7,6 +7,7 @@ use ic_config::artifact_pool::ArtifactPoolConfig;
 use ic_consensus::{consensus::ConsensusImpl, dkg};
 use ic_interfaces::{
     artifact_pool::ChangeSetProducer,
+    batch_payload::BatchPayloadBuilder,
     canister_http::CanisterHttpPayloadBuilder,
     certification::ChangeSet,
     ingress_manager::IngressSelector,
@@ -23,7 +24,7 @@ use ic_replicated_state::ReplicatedState;
 use ic_test_artifact_pool::ingress_pool::TestIngressPool;
 use ic_test_utilities::{
     canister_http::FakeCanisterHttpPayloadBuilder, ingress_selector::FakeIngressSelector,
-    message_routing::FakeMessageRouting,
+    message_routing::FakeMessageRouting, query_stats::FakeQueryStatsPayloadBuilder,
     self_validating_payload_builder::FakeSelfValidatingPayloadBuilder,
     state_manager::FakeStateManager, xnet_payload_builder::FakeXNetPayloadBuilder,
 };
@@ -141,6 +142,7 @@ pub struct ConsensusDependencies {
     pub(crate) ingress_selector: Arc<dyn IngressSelector>,
     pub(crate) self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
     pub(crate) canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
+    pub(crate) query_stats_payload_builder: Arc<dyn BatchPayloadBuilder>,
     pub consensus_pool: Arc<RwLock<ConsensusPoolImpl>>,
     pub dkg_pool: Arc<RwLock<dkg_pool::DkgPoolImpl>>,
     pub ecdsa_pool: Arc<RwLock<ecdsa_pool::EcdsaPoolImpl>>,
@@ -192,6 +194,7 @@ impl ConsensusDependencies {
             xnet_payload_builder: Arc::new(xnet_payload_builder),
             self_validating_payload_builder: Arc::new(FakeSelfValidatingPayloadBuilder::new()),
             canister_http_payload_builder: Arc::new(FakeCanisterHttpPayloadBuilder::new()),
+            query_stats_payload_builder: Arc::new(FakeQueryStatsPayloadBuilder::new()),
             state_manager,
             metrics_registry,
             replica_config,
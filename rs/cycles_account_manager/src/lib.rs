@@ -61,6 +61,46 @@ impl std::fmt::Display for CyclesAccountManagerError {
     }
 }
 
+/// Describes how saturated a subnet resource such as memory is: the current
+/// usage, the threshold above which canisters start reserving cycles, and the
+/// capacity of the resource.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceSaturation {
+    usage: u64,
+    threshold: u64,
+    capacity: u64,
+}
+
+impl ResourceSaturation {
+    pub fn new(usage: u64, threshold: u64, capacity: u64) -> Self {
+        let threshold = threshold.min(capacity);
+        Self {
+            usage,
+            threshold,
+            capacity,
+        }
+    }
+
+    /// Returns the part of the usage that is above the threshold.
+    pub fn usage_above_threshold(&self) -> u64 {
+        self.usage.saturating_sub(self.threshold)
+    }
+
+    /// Returns the saturation after allocating the given amount.
+    pub fn add(&self, allocated: u64) -> Self {
+        Self {
+            usage: self.usage.saturating_add(allocated),
+            threshold: self.threshold,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Returns the size of the range between the threshold and the capacity.
+    pub fn range(&self) -> u64 {
+        self.capacity - self.threshold
+    }
+}
+
 /// Handles any operation related to cycles accounting, such as charging (due to
 /// using system resources) or refunding unused cycles.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -518,13 +558,58 @@ impl CyclesAccountManager {
     ) -> Result<(), CanisterOutOfCyclesError> {
         let cycles_amount = self.memory_cost(bytes, duration, subnet_size);
 
+        // Storage fees are paid from the reserved balance first.
+        let from_reserved_balance = std::cmp::min(cycles_amount, system_state.reserved_balance());
+
         // Can charge all the way to the empty account (zero cycles)
         self.consume_with_threshold(
             system_state,
-            cycles_amount,
+            cycles_amount - from_reserved_balance,
             Cycles::zero(),
             CyclesUseCase::Memory,
-        )
+        )?;
+        system_state.consume_reserved_cycles(from_reserved_balance, CyclesUseCase::Memory);
+        Ok(())
+    }
+
+    /// Returns the amount of cycles that a canister needs to reserve when it
+    /// allocates `allocated_bytes` of memory on a subnet with the given memory
+    /// saturation.
+    ///
+    /// The reserved cycles pay for storing the allocated memory for a period
+    /// that grows linearly from zero at the threshold to
+    /// `max_storage_reservation_period` at the capacity. The period is averaged
+    /// between the usage before and after the allocation.
+    pub fn storage_reservation_cycles(
+        &self,
+        allocated_bytes: NumBytes,
+        subnet_memory_saturation: &ResourceSaturation,
+        subnet_size: usize,
+    ) -> Cycles {
+        let period_before = self.storage_reservation_period(subnet_memory_saturation);
+        let period_after =
+            self.storage_reservation_period(&subnet_memory_saturation.add(allocated_bytes.get()));
+        let period = (period_before + period_after) / 2;
+        self.memory_cost(allocated_bytes, period, subnet_size)
+    }
+
+    fn storage_reservation_period(
+        &self,
+        subnet_memory_saturation: &ResourceSaturation,
+    ) -> Duration {
+        let max_period = self.config.max_storage_reservation_period.as_secs() as u128;
+        let usage_above_threshold = subnet_memory_saturation.usage_above_threshold() as u128;
+        let range = subnet_memory_saturation.range() as u128;
+        let period = if range == 0 {
+            if usage_above_threshold > 0 {
+                max_period
+            } else {
+                0
+            }
+        } else {
+            max_period * usage_above_threshold.min(range) / range
+        };
+        Duration::from_secs(period as u64)
     }
 
     /// The cost of using `bytes` worth of memory.
@@ -809,6 +894,20 @@ impl CyclesAccountManager {
         )
     }
 
+    /// Returns the fee for taking or loading a canister snapshot of the given
+    /// size in [`Cycles`]. Copying the snapshot is charged like executing one
+    /// instruction per byte of the snapshot.
+    pub fn canister_snapshot_fee(&self, snapshot_size: NumBytes, subnet_size: usize) -> Cycles {
+        self.execution_cost(NumInstructions::from(snapshot_size.get()), subnet_size)
+    }
+
+    /// Returns the fee for uploading a chunk of the given size to the Wasm
+    /// chunk store of a canister in [`Cycles`]. Hashing and storing the chunk
+    /// is charged like executing one instruction per byte of the chunk.
+    pub fn upload_chunk_fee(&self, chunk_size: NumBytes, subnet_size: usize) -> Cycles {
+        self.execution_cost(NumInstructions::from(chunk_size.get()), subnet_size)
+    }
+
     /// Charges a canister for its resource allocation and usage for the
     /// duration specified. If fees were successfully charged, then returns
     /// Ok(CanisterState) else returns Err(CanisterState).
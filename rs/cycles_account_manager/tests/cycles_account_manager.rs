@@ -1,7 +1,7 @@
 use ic_base_types::NumSeconds;
 use ic_config::subnet_config::SubnetConfigs;
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
-use ic_cycles_account_manager::IngressInductionCost;
+use ic_cycles_account_manager::{IngressInductionCost, ResourceSaturation};
 use ic_ic00_types::{CanisterIdRecord, Payload, IC_00};
 use ic_interfaces::execution_environment::CanisterOutOfCyclesError;
 use ic_logger::replica_logger::no_op_logger;
@@ -329,6 +329,61 @@ fn canister_charge_for_memory_until_zero_works() {
         .is_err());
 }
 
+#[test]
+fn storage_reservation_cycles_grow_with_subnet_memory_usage() {
+    let subnet_size = SMALL_APP_SUBNET_MAX_SIZE;
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let allocated_bytes = NumBytes::from(1 << 30);
+    let reservation = |usage| {
+        cycles_account_manager.storage_reservation_cycles(
+            allocated_bytes,
+            &ResourceSaturation::new(usage, 100 << 30, 200 << 30),
+            subnet_size,
+        )
+    };
+
+    // Nothing is reserved while the usage stays below the threshold.
+    assert_eq!(reservation(0), Cycles::zero());
+    assert_eq!(reservation(99 << 30), Cycles::zero());
+
+    // Above the threshold the reservation grows with the usage.
+    assert!(reservation(100 << 30) > Cycles::zero());
+    assert!(reservation(150 << 30) > reservation(100 << 30));
+
+    // At the capacity the reservation covers the maximum period.
+    let max_period = SubnetConfigs::default()
+        .own_subnet_config(SubnetType::Application)
+        .cycles_account_manager_config
+        .max_storage_reservation_period;
+    assert_eq!(
+        reservation(200 << 30),
+        cycles_account_manager.memory_cost(allocated_bytes, max_period, subnet_size)
+    );
+}
+
+#[test]
+fn charge_for_memory_uses_reserved_balance_first() {
+    let subnet_size = SMALL_APP_SUBNET_MAX_SIZE;
+    let mut system_state = SystemStateBuilder::new().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let bytes = NumBytes::from(1 << 30);
+    let duration = Duration::from_secs(1);
+    let fee = cycles_account_manager.memory_cost(bytes, duration, subnet_size);
+
+    system_state.reserve_cycles(fee * 2_u64).unwrap();
+    let balance = system_state.balance();
+
+    cycles_account_manager
+        .charge_for_memory(&mut system_state, bytes, duration, subnet_size)
+        .unwrap();
+    assert_eq!(system_state.balance(), balance);
+    assert_eq!(system_state.reserved_balance(), fee);
+}
+
 #[test]
 fn ingress_induction_cost_valid_subnet_message() {
     let subnet_id = subnet_test_id(0);
@@ -765,3 +820,41 @@ fn consume_cycles_updates_consumed_cycles() {
         NominalCycles::from(1_000_000)
     );
 }
+
+#[test]
+fn canister_snapshot_fee_grows_with_snapshot_size() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+
+    let small = cycles_account_manager
+        .canister_snapshot_fee(NumBytes::from(1_000), SMALL_APP_SUBNET_MAX_SIZE);
+    let large = cycles_account_manager
+        .canister_snapshot_fee(NumBytes::from(1_000_000), SMALL_APP_SUBNET_MAX_SIZE);
+
+    assert_eq!(
+        small,
+        cycles_account_manager
+            .execution_cost(NumInstructions::from(1_000), SMALL_APP_SUBNET_MAX_SIZE)
+    );
+    assert!(large > small);
+}
+
+#[test]
+fn upload_chunk_fee_grows_with_chunk_size() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+
+    let small =
+        cycles_account_manager.upload_chunk_fee(NumBytes::from(1_000), SMALL_APP_SUBNET_MAX_SIZE);
+    let large = cycles_account_manager
+        .upload_chunk_fee(NumBytes::from(1_000_000), SMALL_APP_SUBNET_MAX_SIZE);
+
+    assert_eq!(
+        small,
+        cycles_account_manager
+            .execution_cost(NumInstructions::from(1_000), SMALL_APP_SUBNET_MAX_SIZE)
+    );
+    assert!(large > small);
+}
@@ -1,12 +1,16 @@
 //! Standalone interface for testing application canisters.
 
-use crate::message::{msg_stream_from_file, Message};
+use crate::message::{msg_stream_from_file, with_expiry_time, Message};
+use crate::profile::Profiler;
+use crate::script::{Interface, Outcome, Script};
 use hex::encode;
 use ic_config::{subnet_config::SubnetConfigs, Config};
+use ic_constants::MAX_INGRESS_TTL;
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
 use ic_http_endpoints_metrics::MetricsHttpEndpoint;
+use ic_ic00_types::IC_00;
 use ic_interfaces::{execution_environment::IngressHistoryReader, messaging::MessageRouting};
 use ic_interfaces_state_manager::StateReader;
 use ic_messaging::MessageRoutingImpl;
@@ -38,9 +42,11 @@ use ic_types::{
     messages::{MessageId, SignedIngress},
     replica_config::ReplicaConfig,
     time, CanisterId, NodeId, NumInstructions, PrincipalId, Randomness, RegistryVersion, SubnetId,
+    Time,
 };
 use rand::distributions::{Distribution, Uniform};
 use slog::{Drain, Logger};
+use std::cell::Cell;
 use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::path::PathBuf;
@@ -48,6 +54,8 @@ use std::sync::{Arc, Mutex};
 use std::{thread::sleep, time::Duration};
 
 mod message;
+mod profile;
+mod script;
 
 // drun will panic if it takes more than this many batches
 // until a response for a message is received
@@ -61,6 +69,58 @@ pub struct DrunOptions {
     pub extra_batches: u64,
     pub log_file: Option<PathBuf>,
     pub instruction_limit: Option<u64>,
+    /// Whether the messages file is a script; see `message::parse_message()`.
+    pub script: bool,
+    /// Candid interface used in script mode to encode payloads and decode
+    /// replies. Implies script mode.
+    pub candid_file: Option<PathBuf>,
+    /// File the per-message costs are written to; as JSON if the file name
+    /// ends in `.json`, as CSV otherwise.
+    pub profile_file: Option<PathBuf>,
+}
+
+/// The time of the delivered batches. Follows the wall clock until it is
+/// fixed by a `set-time` or `advance-time` directive.
+#[derive(Default)]
+struct Clock {
+    fixed: Cell<Option<Time>>,
+    last_batch_time: Cell<Option<Time>>,
+}
+
+impl Clock {
+    /// Returns the current drun time.
+    fn now(&self) -> Time {
+        self.fixed.get().unwrap_or_else(time::current_time)
+    }
+
+    /// Returns the time for the next batch.
+    fn batch_time(&self) -> Time {
+        let time = self.now();
+        self.last_batch_time.set(Some(time));
+        time
+    }
+
+    /// Returns the expiry time of a message delivered now.
+    fn expiry_time(&self) -> Time {
+        self.now() + MAX_INGRESS_TTL
+    }
+
+    fn set(&self, time: Time) -> Result<(), String> {
+        match self.last_batch_time.get() {
+            Some(last) if time < last => Err(format!(
+                "Cannot set time to {} before the time of the last batch {}",
+                time, last
+            )),
+            _ => {
+                self.fixed.set(Some(time));
+                Ok(())
+            }
+        }
+    }
+
+    fn advance(&self, duration: Duration) {
+        self.fixed.set(Some(self.now() + duration));
+    }
 }
 
 /// Deliver a single message to the Message Routing layer
@@ -69,14 +129,33 @@ fn deliver_message(
     message_routing: &dyn MessageRouting,
     ingress_hist_reader: &dyn IngressHistoryReader,
     extra_batches: u64,
-) {
+    clock: &Clock,
+    script: Option<&Script>,
+) -> Outcome {
+    let msg = with_expiry_time(&msg, clock.expiry_time());
     let message_id = msg.id();
-
-    let _ = execute_ingress_message(message_routing, msg, &message_id, ingress_hist_reader);
+    let method_name = (msg.canister_id() != IC_00).then(|| msg.method_name());
+
+    let result = execute_ingress_message(
+        message_routing,
+        msg,
+        &message_id,
+        ingress_hist_reader,
+        clock,
+    );
     // print result after waiting, to not interleave the result
     // with debug.print messages from subsequent calls. revise after DFN-1269.
-    wait_extra_batches(message_routing, extra_batches);
-    print_ingress_result(&message_id, ingress_hist_reader);
+    wait_extra_batches(message_routing, extra_batches, clock);
+    print_ingress_result(
+        &message_id,
+        ingress_hist_reader,
+        script,
+        method_name.as_deref(),
+    );
+    Outcome {
+        method_name,
+        result,
+    }
 }
 
 fn setup_logger(log_file: PathBuf) -> Logger {
@@ -159,6 +238,9 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         extra_batches,
         log_file,
         instruction_limit,
+        script,
+        candid_file,
+        profile_file,
     } = uo;
     // Hardcoded magic values to create a ReplicaConfig that parses.
     let subnet_type = SubnetType::System;
@@ -181,7 +263,12 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         subnet_id,
     };
 
-    let mut msg_stream = msg_stream_from_file(&msg_filename)?;
+    let script = match candid_file {
+        Some(candid_file) => Some(Script::new(Some(Interface::from_file(&candid_file)?))),
+        None if script => Some(Script::default()),
+        None => None,
+    };
+    let msg_stream = msg_stream_from_file(&msg_filename, script.as_ref())?;
     let log = match log_file {
         Some(log_file) => setup_logger(log_file),
         None => slog::Logger::root(slog::Discard, slog::o!()),
@@ -248,59 +335,113 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         MaliciousFlags::default(),
     );
 
-    msg_stream.try_for_each(|parse_result| {
-        parse_result.map(|msg| match msg {
-            Message::Install(msg) => {
-                deliver_message(
+    let clock = Clock::default();
+    let mut profiler = profile_file.map(|path| Profiler::new(path, metrics_registry.clone()));
+    // The outcome of the last ingress message or query, checked by the
+    // assertions of the script mode.
+    let mut last_outcome = None;
+    let mut failed_assertions = 0;
+    for parse_result in msg_stream {
+        match parse_result? {
+            Message::Install(msg) | Message::Ingress(msg) | Message::Create(msg) => {
+                let canister_id = msg.canister_id();
+                let method_name = msg.method_name();
+                let counters = profiler
+                    .as_ref()
+                    .map(|profiler| profiler.counters(&state_manager.get_latest_state().take()));
+                let outcome = deliver_message(
                     msg,
                     &message_routing,
                     ingress_hist_reader.as_ref(),
                     extra_batches,
+                    &clock,
+                    script.as_ref(),
                 );
+                if let (Some(profiler), Some(counters)) = (profiler.as_mut(), counters) {
+                    profiler.record(
+                        "ingress",
+                        canister_id,
+                        method_name,
+                        counters,
+                        &state_manager.get_latest_state().take(),
+                        &outcome.result,
+                    );
+                }
+                last_outcome = Some(outcome);
             }
 
-            Message::Query(q) => {
+            Message::Query(mut q) => {
+                q.ingress_expiry = clock.expiry_time().as_nanos_since_unix_epoch();
+                let canister_id = q.receiver;
+                let method_name = (q.receiver != IC_00).then(|| q.method_name.clone());
+                let state = state_manager.get_latest_state().take();
+                let counters = profiler.as_ref().map(|profiler| profiler.counters(&state));
+                let query_method_name = q.method_name.clone();
                 // NOTE: Data certificates aren't supported in drun yet.
                 // To support them, we'd need to do something similar to
                 // http_handler::get_latest_certified_state_and_data_certificate
-                print_query_result(query_handler.query(
-                    q,
-                    state_manager.get_latest_state().take(),
-                    Vec::new(),
-                ));
+                let result = query_handler.query(q, Arc::clone(&state), Vec::new());
+                print_query_result(&result, script.as_ref(), method_name.as_deref());
+                if let (Some(profiler), Some(counters)) = (profiler.as_mut(), counters) {
+                    profiler.record(
+                        "query",
+                        canister_id,
+                        query_method_name,
+                        counters,
+                        &state,
+                        &result,
+                    );
+                }
+                last_outcome = Some(Outcome {
+                    method_name,
+                    result,
+                });
             }
 
-            Message::Ingress(msg) => {
-                deliver_message(
-                    msg,
-                    &message_routing,
-                    ingress_hist_reader.as_ref(),
-                    extra_batches,
-                );
-            }
-            Message::Create(msg) => {
-                deliver_message(
-                    msg,
-                    &message_routing,
-                    ingress_hist_reader.as_ref(),
-                    extra_batches,
-                );
+            Message::Tick(batches) => wait_extra_batches(&message_routing, batches, &clock),
+            Message::SetTime(time) => clock.set(time)?,
+            Message::AdvanceTime(duration) => clock.advance(duration),
+            Message::Expect { line, assertion } => {
+                let script = script
+                    .as_ref()
+                    .expect("assertions are only parsed in script mode");
+                if let Err(err) = script.check(&assertion, last_outcome.as_ref()) {
+                    println!("Assertion failed on line {}: {}", line, err);
+                    failed_assertions += 1;
+                }
             }
-        })
-    })
+        }
+    }
+
+    if let Some(profiler) = profiler {
+        profiler.write()?;
+    }
+    if failed_assertions > 0 {
+        return Err(format!("{} assertion(s) failed", failed_assertions));
+    }
+    Ok(())
 }
 
-fn print_query_result(res: Result<WasmResult, UserError>) {
+fn print_query_result(
+    res: &Result<WasmResult, UserError>,
+    script: Option<&Script>,
+    method_name: Option<&str>,
+) {
     match res {
         Ok(payload) => {
             print!("Ok: ");
-            print_wasm_result(payload);
+            print_wasm_result(payload, script, method_name);
         }
         Err(e) => println!("Err: {}", e),
     }
 }
 
-fn print_ingress_result(message_id: &MessageId, ingress_hist_reader: &dyn IngressHistoryReader) {
+fn print_ingress_result(
+    message_id: &MessageId,
+    ingress_hist_reader: &dyn IngressHistoryReader,
+    script: Option<&Script>,
+    method_name: Option<&str>,
+) {
     let status = (ingress_hist_reader.get_latest_status())(message_id);
     print!("ingress ");
     match status {
@@ -309,7 +450,7 @@ fn print_ingress_result(message_id: &MessageId, ingress_hist_reader: &dyn Ingres
             ..
         } => {
             print!("Completed: ");
-            print_wasm_result(result)
+            print_wasm_result(&result, script, method_name)
         }
         IngressStatus::Known {
             state: IngressState::Failed(error),
@@ -319,10 +460,15 @@ fn print_ingress_result(message_id: &MessageId, ingress_hist_reader: &dyn Ingres
     };
 }
 
-fn print_wasm_result(wasm_result: WasmResult) {
-    match wasm_result {
-        WasmResult::Reply(v) => println!("Reply: 0x{}", encode(v)),
-        WasmResult::Reject(e) => println!("Reject: {}", e),
+/// Prints the result of a message; in script mode, replies are printed as
+/// Candid values.
+fn print_wasm_result(wasm_result: &WasmResult, script: Option<&Script>, method_name: Option<&str>) {
+    match (wasm_result, script) {
+        (WasmResult::Reply(v), Some(script)) => {
+            println!("Reply: {}", script.format_reply(method_name, v))
+        }
+        (WasmResult::Reply(v), None) => println!("Reply: 0x{}", encode(v)),
+        (WasmResult::Reject(e), _) => println!("Reject: {}", e),
     }
 }
 
@@ -333,7 +479,11 @@ fn get_random_seed() -> [u8; 32] {
     seed.try_into().unwrap()
 }
 
-fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -> Batch {
+fn build_batch(
+    message_routing: &dyn MessageRouting,
+    msgs: Vec<SignedIngress>,
+    clock: &Clock,
+) -> Batch {
     Batch {
         batch_number: message_routing.expected_batch_height(),
         requires_full_state_hash: !msgs.is_empty(),
@@ -344,7 +494,7 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
         randomness: Randomness::from(get_random_seed()),
         ecdsa_subnet_public_keys: BTreeMap::new(),
         registry_version: RegistryVersion::from(1),
-        time: time::current_time(),
+        time: clock.batch_time(),
         consensus_responses: vec![],
     }
 }
@@ -357,8 +507,9 @@ fn execute_ingress_message(
     msg: SignedIngress,
     msg_id: &MessageId,
     ingress_history: &dyn IngressHistoryReader,
+    clock: &Clock,
 ) -> Result<WasmResult, UserError> {
-    let mut batch = build_batch(message_routing, vec![msg]);
+    let mut batch = build_batch(message_routing, vec![msg], clock);
     for _ in 0..MAX_BATCHES_UNTIL_RESPONSE {
         // In the first batch we try to send the ingress message itself. If it fails, we
         // repeat with the same batch.
@@ -369,7 +520,7 @@ fn execute_ingress_message(
         // potential inter-canister messages that the ingress message may have
         // triggered.
         if message_routing.deliver_batch(batch.clone()).is_ok() {
-            batch = build_batch(message_routing, vec![])
+            batch = build_batch(message_routing, vec![], clock)
         }
         sleep(WAIT_PER_BATCH);
 
@@ -415,10 +566,10 @@ fn execute_ingress_message(
 ///
 /// This is a temporary measure until DFN-1269 is resolved. In that ticket, we
 /// will actually try to wait until all messages have been executed.
-fn wait_extra_batches(message_routing: &dyn MessageRouting, extra_batches: u64) {
+fn wait_extra_batches(message_routing: &dyn MessageRouting, extra_batches: u64, clock: &Clock) {
     for _ in 0..extra_batches {
         loop {
-            let batch = build_batch(message_routing, vec![]);
+            let batch = build_batch(message_routing, vec![], clock);
             let ok = message_routing.deliver_batch(batch).is_ok();
             sleep(WAIT_PER_BATCH);
             if ok {
@@ -431,6 +582,118 @@ fn wait_extra_batches(message_routing: &dyn MessageRouting, extra_batches: u64)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_config::flag_status::FlagStatus;
+
+    /// A canister whose `run` update and `run_query` query both execute a
+    /// loop of 10_000 iterations and reply.
+    const LOOP_WAT: &str = r#"
+        (module
+          (import "ic0" "msg_reply" (func $msg_reply))
+          (func $run (local $i i32)
+            (loop $loop
+              (local.set $i (i32.add (local.get $i) (i32.const 1)))
+              (br_if $loop (i32.lt_u (local.get $i) (i32.const 10000))))
+            (call $msg_reply))
+          (memory 1)
+          (export "canister_update run" (func $run))
+          (export "canister_query run_query" (func $run)))"#;
+
+    #[test]
+    fn test_profile_records_the_instructions_used_by_messages() {
+        let (mut cfg, tmpdir) = Config::temp_config();
+        // The test binary cannot act as a canister sandbox.
+        cfg.hypervisor.canister_sandboxing_flag = FlagStatus::Disabled;
+        let wasm_file = tmpdir.path().join("loop.wasm");
+        std::fs::write(&wasm_file, wat::parse_str(LOOP_WAT).unwrap()).unwrap();
+        let msg_file = tmpdir.path().join("messages.txt");
+        std::fs::write(
+            &msg_file,
+            format!(
+                "create\n\
+                install {id} {wasm} \"\"\n\
+                ingress {id} run \"\"\n\
+                query {id} run_query \"\"\n",
+                id = "rwlgt-iiaaa-aaaaa-aaaaa-cai",
+                wasm = wasm_file.display()
+            ),
+        )
+        .unwrap();
+        let profile_file = tmpdir.path().join("profile.json");
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        run_drun(DrunOptions {
+            msg_filename: msg_file.to_str().unwrap().to_string(),
+            cfg,
+            extra_batches: 0,
+            log_file: None,
+            instruction_limit: None,
+            script: false,
+            candid_file: None,
+            profile_file: Some(profile_file.clone()),
+        })
+        .unwrap();
+
+        let profile: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&profile_file).unwrap()).unwrap();
+        let messages = profile.as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        for (message, kind, method) in [
+            (&messages[2], "ingress", "run"),
+            (&messages[3], "query", "run_query"),
+        ] {
+            assert_eq!(message["kind"], kind);
+            assert_eq!(message["method"], method);
+            assert_eq!(message["status"], "reply");
+            // The loop executes a few instructions per iteration. The 2M
+            // instructions the scheduler charges the round for every message
+            // are not part of the message's instructions.
+            let instructions = message["instructions"].as_u64().unwrap();
+            assert!(
+                (10_000..1_000_000).contains(&instructions),
+                "{} used {} instructions",
+                method,
+                instructions
+            );
+        }
+    }
+
+    #[test]
+    fn test_script_ingress_expires_relative_to_the_drun_clock() {
+        let (mut cfg, tmpdir) = Config::temp_config();
+        cfg.hypervisor.canister_sandboxing_flag = FlagStatus::Disabled;
+        let wasm_file = tmpdir.path().join("loop.wasm");
+        std::fs::write(&wasm_file, wat::parse_str(LOOP_WAT).unwrap()).unwrap();
+        let msg_file = tmpdir.path().join("messages.txt");
+        std::fs::write(
+            &msg_file,
+            format!(
+                "create\n\
+                install {id} {wasm} \"\"\n\
+                advance-time 1h\n\
+                ingress {id} run \"\"\n\
+                expect-reply\n",
+                id = "rwlgt-iiaaa-aaaaa-aaaaa-cai",
+                wasm = wasm_file.display()
+            ),
+        )
+        .unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        run_drun(DrunOptions {
+            msg_filename: msg_file.to_str().unwrap().to_string(),
+            cfg,
+            extra_batches: 0,
+            log_file: None,
+            instruction_limit: None,
+            script: true,
+            candid_file: None,
+            profile_file: None,
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_get_random_seed() {
         let seed_1 = get_random_seed();
@@ -14,6 +14,9 @@ const ARG_LOG_FILE: &str = "log-file";
 const ARG_MESSAGES: &str = "messages";
 const ARG_EXTRA_BATCHES: &str = "extra-batches";
 const ARG_INSTRUCTION_LIMIT: &str = "instruction-limit";
+const ARG_SCRIPT: &str = "script";
+const ARG_CANDID: &str = "candid";
+const ARG_PROFILE: &str = "profile";
 
 fn main() -> Result<(), String> {
     // Check if `drun` is running in the canister sandbox mode where it waits
@@ -75,6 +78,9 @@ async fn drun_main() -> Result<(), String> {
             extra_batches,
             log_file,
             instruction_limit,
+            script: matches.is_present(ARG_SCRIPT),
+            candid_file: matches.value_of(ARG_CANDID).map(PathBuf::from),
+            profile_file: matches.value_of(ARG_PROFILE).map(PathBuf::from),
         };
         run_drun(uo)
     })
@@ -130,5 +136,30 @@ fn get_arg_matches() -> ArgMatches {
                 .help("Limit on the number of instructions a message is allowed to execute.")
                 .takes_value(true),
         )
+        .arg(Arg::new(ARG_SCRIPT).long(ARG_SCRIPT).help(
+            "Treat the messages file as a script: accept Candid payloads, assertions \
+            (expect-reply, expect-reject) and time and cycles directives, print replies \
+            as Candid and exit with an error if an assertion fails.",
+        ))
+        .arg(
+            Arg::new(ARG_CANDID)
+                .long(ARG_CANDID)
+                .value_name("did_file")
+                .help(
+                    "Candid interface of the canister used to encode payloads and decode \
+                    replies (implies --script).",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new(ARG_PROFILE)
+                .long(ARG_PROFILE)
+                .value_name("profile_file")
+                .help(
+                    "Write the instructions, dirty pages, cycles and response size of every \
+                    message to this file (JSON if it ends in .json, CSV otherwise).",
+                )
+                .takes_value(true),
+        )
         .get_matches()
 }
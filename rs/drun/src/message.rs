@@ -1,11 +1,14 @@
 use super::CanisterId;
+use crate::script::{Assertion, ExpectedPayload, Script};
 
+use candid::IDLArgs;
 use hex::decode;
+use ic_error_types::RejectCode;
 use ic_ic00_types::{self as ic00, CanisterInstallMode, Payload};
 use ic_types::{
     messages::{SignedIngress, UserQuery},
     time::expiry_time_from_now,
-    PrincipalId, UserId,
+    PrincipalId, Time, UserId,
 };
 
 use std::{
@@ -15,6 +18,7 @@ use std::{
     io::{self, Read},
     str::Chars,
     string::FromUtf8Error,
+    time::Duration,
 };
 
 #[derive(Debug, PartialEq)]
@@ -23,6 +27,17 @@ pub(crate) enum Message {
     Query(UserQuery),
     Install(SignedIngress),
     Create(SignedIngress),
+    /// Delivers the given number of empty batches (script mode only).
+    Tick(u64),
+    /// Fixes the time of subsequent batches (script mode only).
+    SetTime(Time),
+    /// Moves the time of subsequent batches forward (script mode only).
+    AdvanceTime(Duration),
+    /// Checks the outcome of the preceding message (script mode only).
+    Expect {
+        line: u64,
+        assertion: Assertion,
+    },
 }
 
 #[derive(Debug)]
@@ -123,9 +138,10 @@ impl<R: Read> Iterator for LineIterator<R> {
     }
 }
 
-pub(crate) fn msg_stream_from_file(
+pub(crate) fn msg_stream_from_file<'a>(
     filename: &str,
-) -> Result<impl Iterator<Item = Result<Message, String>>, String> {
+    script: Option<&'a Script>,
+) -> Result<impl Iterator<Item = Result<Message, String>> + 'a, String> {
     let f = File::open(filename).map_err(|e| e.to_string())?;
     let line_iterator = LineIterator::new(f);
 
@@ -138,14 +154,33 @@ pub(crate) fn msg_stream_from_file(
         })
         .map(|(i, line)| match line {
             Ok(line) => {
-                parse_message(&line, i as u64).map_err(|e| format!("Line {}: {}", i + 1, e))
+                parse_message(&line, i as u64, script).map_err(|e| format!("Line {}: {}", i + 1, e))
             }
             Err(e) => Err(format!("Error while reading line {}: {}", i, e)),
         }))
 }
 
-fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
+/// Parses a line of the input. The `nonce` is the index of the line.
+///
+/// In script mode, payloads may also be written as Candid text enclosed in
+/// parentheses and the following directives are supported in addition:
+///
+/// ```text
+/// create <cycles>                      create a canister with the given cycles
+/// top-up <canister_id> <cycles>        add cycles to a canister
+/// tick [<n>]                           deliver n (default 1) empty batches
+/// set-time <nanos_since_epoch>         fix the time of subsequent batches
+/// advance-time <n>(ns|ms|s|m|h)        move the time of subsequent batches
+/// expect-reply [<payload>]             the preceding message was replied to
+/// expect-reject <code> ["<message>"]   the preceding message was rejected
+/// ```
+fn parse_message(s: &str, nonce: u64, script: Option<&Script>) -> Result<Message, String> {
     let s = s.trim_end();
+    if script.is_some() {
+        if let Some(message) = parse_directive(s, nonce)? {
+            return Ok(message);
+        }
+    }
     let tokens: Vec<&str> = s.splitn(4, char::is_whitespace).collect();
 
     match &tokens[..] {
@@ -155,7 +190,7 @@ fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
 
             let canister_id = parse_canister_id(canister_id)?;
             let method_name = validate_method_name(method_name)?;
-            let method_payload = parse_octet_string(payload)?;
+            let method_payload = parse_payload(payload, Some(&method_name), script)?;
 
             let signed_ingress = SignedIngressBuilder::new()
                 // `source` should become a self-authenticating id according
@@ -167,23 +202,26 @@ fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
                 .build();
             Ok(Message::Ingress(signed_ingress))
         }
-        ["query", canister_id, method_name, payload] => Ok(Message::Query(UserQuery {
-            source: UserId::from(PrincipalId::new_anonymous()),
-            receiver: parse_canister_id(canister_id)?,
-            method_name: validate_method_name(method_name)?,
-            method_payload: parse_octet_string(payload)?,
-            ingress_expiry: expiry_time_from_now().as_nanos_since_unix_epoch(),
-            nonce: Some(nonce.to_le_bytes().to_vec()),
-        })),
-        ["create"] => parse_create(nonce),
+        ["query", canister_id, method_name, payload] => {
+            let method_name = validate_method_name(method_name)?;
+            Ok(Message::Query(UserQuery {
+                source: UserId::from(PrincipalId::new_anonymous()),
+                receiver: parse_canister_id(canister_id)?,
+                method_payload: parse_payload(payload, Some(&method_name), script)?,
+                method_name,
+                ingress_expiry: expiry_time_from_now().as_nanos_since_unix_epoch(),
+                nonce: Some(nonce.to_le_bytes().to_vec()),
+            }))
+        }
+        ["create"] => parse_create(nonce, None),
         ["install", canister_id, wasm_file, payload] => {
-            parse_install(nonce, canister_id, payload, wasm_file, "install")
+            parse_install(nonce, canister_id, payload, wasm_file, "install", script)
         }
         ["reinstall", canister_id, wasm_file, payload] => {
-            parse_install(nonce, canister_id, payload, wasm_file, "reinstall")
+            parse_install(nonce, canister_id, payload, wasm_file, "reinstall", script)
         }
         ["upgrade", canister_id, wasm_file, payload] => {
-            parse_install(nonce, canister_id, payload, wasm_file, "upgrade")
+            parse_install(nonce, canister_id, payload, wasm_file, "upgrade", script)
         }
         _ => Err(format!(
             "Failed to parse line {}, don't have a pattern to match this with",
@@ -192,6 +230,116 @@ fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
     }
 }
 
+/// Parses the script mode directives, returning `None` if the line does not
+/// contain one.
+fn parse_directive(s: &str, nonce: u64) -> Result<Option<Message>, String> {
+    let mut tokens = s.splitn(2, char::is_whitespace);
+    let directive = tokens.next().unwrap_or_default();
+    let args = tokens.next().map(str::trim).unwrap_or_default();
+
+    let message = match directive {
+        "create" if !args.is_empty() => parse_create(nonce, Some(parse_cycles(args)?))?,
+        "top-up" => match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [canister_id, cycles] => parse_top_up(nonce, canister_id, cycles)?,
+            _ => {
+                return Err(format!(
+                    "Expected: top-up <canister_id> <cycles>, got: {}",
+                    s
+                ))
+            }
+        },
+        "tick" if args.is_empty() => Message::Tick(1),
+        "tick" => Message::Tick(
+            args.parse()
+                .map_err(|e| format!("Illegal number of batches {}: {}", args, e))?,
+        ),
+        "set-time" => Message::SetTime(Time::from_nanos_since_unix_epoch(
+            args.parse()
+                .map_err(|e| format!("Illegal time {}: {}", args, e))?,
+        )),
+        "advance-time" => Message::AdvanceTime(parse_duration(args)?),
+        "expect-reply" => Message::Expect {
+            line: nonce + 1,
+            assertion: Assertion::Reply(if args.is_empty() {
+                None
+            } else {
+                Some(parse_expected_payload(args)?)
+            }),
+        },
+        "expect-reject" => {
+            let mut tokens = args.splitn(2, char::is_whitespace);
+            let code = tokens.next().unwrap_or_default();
+            let code = code
+                .parse::<u64>()
+                .ok()
+                .and_then(|code| RejectCode::try_from(code).ok())
+                .ok_or_else(|| format!("Illegal reject code {}.", code))?;
+            let message = match tokens.next().map(str::trim) {
+                Some(message) => {
+                    Some(String::from_utf8(parse_quoted(message)?).map_err(|e| e.to_string())?)
+                }
+                None => None,
+            };
+            Message::Expect {
+                line: nonce + 1,
+                assertion: Assertion::Reject { code, message },
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(message))
+}
+
+fn parse_cycles(cycles: &str) -> Result<u128, String> {
+    cycles
+        .replace('_', "")
+        .parse()
+        .map_err(|e| format!("Illegal amount of cycles {}: {}", cycles, e))
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let unit_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(unit_start);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|e| format!("Illegal duration {}: {}", s, e))?;
+    match unit {
+        "ns" => Ok(Duration::from_nanos(amount)),
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        _ => Err(format!(
+            "Illegal duration unit {}, expected one of ns, ms, s, m, h.",
+            unit
+        )),
+    }
+}
+
+fn parse_expected_payload(payload: &str) -> Result<ExpectedPayload, String> {
+    if payload.starts_with('(') {
+        payload
+            .parse::<IDLArgs>()
+            .map_err(|e| format!("Failed to parse Candid value {}: {}", payload, e))?;
+        Ok(ExpectedPayload::Candid(payload.to_string()))
+    } else {
+        parse_octet_string(payload).map(ExpectedPayload::Bytes)
+    }
+}
+
+/// Parses a payload of `method_name`, or of the canister installation if
+/// `method_name` is `None`.
+fn parse_payload(
+    payload: &str,
+    method_name: Option<&str>,
+    script: Option<&Script>,
+) -> Result<Vec<u8>, String> {
+    match script {
+        Some(script) if payload.starts_with('(') => script.encode_args(method_name, payload),
+        _ => parse_octet_string(payload),
+    }
+}
+
 fn parse_canister_id(canister_id: &str) -> Result<CanisterId, String> {
     use std::str::FromStr;
     match PrincipalId::from_str(canister_id) {
@@ -209,25 +357,42 @@ fn parse_canister_id(canister_id: &str) -> Result<CanisterId, String> {
     }
 }
 
-fn parse_create(nonce: u64) -> Result<Message, String> {
+fn parse_create(nonce: u64, cycles: Option<u128>) -> Result<Message, String> {
     use ic_test_utilities::types::messages::SignedIngressBuilder;
 
     let signed_ingress = SignedIngressBuilder::new()
         .method_name(ic00::Method::ProvisionalCreateCanisterWithCycles)
         .canister_id(ic00::IC_00)
-        .method_payload(ic00::ProvisionalCreateCanisterWithCyclesArgs::new(None, None).encode())
+        .method_payload(ic00::ProvisionalCreateCanisterWithCyclesArgs::new(cycles, None).encode())
         .nonce(nonce)
         .build();
 
     Ok(Message::Create(signed_ingress))
 }
 
+fn parse_top_up(nonce: u64, canister_id: &str, cycles: &str) -> Result<Message, String> {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+    let canister_id = parse_canister_id(canister_id)?;
+    let signed_ingress = SignedIngressBuilder::new()
+        .method_name(ic00::Method::ProvisionalTopUpCanister)
+        .canister_id(ic00::IC_00)
+        .method_payload(
+            ic00::ProvisionalTopUpCanisterArgs::new(canister_id, parse_cycles(cycles)?).encode(),
+        )
+        .nonce(nonce)
+        .build();
+
+    Ok(Message::Ingress(signed_ingress))
+}
+
 fn parse_install(
     nonce: u64,
     canister_id: &str,
     payload: &str,
     wasm_file: &str,
     mode: &str,
+    script: Option<&Script>,
 ) -> Result<Message, String> {
     use ic_test_utilities::types::messages::SignedIngressBuilder;
 
@@ -239,7 +404,7 @@ fn parse_install(
         .map_err(|e| e.to_string())?;
 
     let canister_id = parse_canister_id(canister_id)?;
-    let payload = parse_octet_string(payload)?;
+    let payload = parse_payload(payload, None, script)?;
 
     let signed_ingress = SignedIngressBuilder::new()
         // `source` should become a self-authenticating id according
@@ -263,6 +428,26 @@ fn parse_install(
     Ok(Message::Install(signed_ingress))
 }
 
+/// Returns a copy of `msg` that expires at `expiry_time`.
+///
+/// Messages are parsed before the directives preceding them move the drun
+/// clock, so their expiry time is only set when they are delivered.
+pub(crate) fn with_expiry_time(msg: &SignedIngress, expiry_time: Time) -> SignedIngress {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+    let mut builder = SignedIngressBuilder::new()
+        .sender(msg.sender())
+        .canister_id(msg.canister_id())
+        .method_name(msg.method_name())
+        .method_payload(msg.method_arg().to_vec())
+        .expiry_time(expiry_time);
+    if let Some(nonce) = msg.nonce() {
+        let nonce = nonce.try_into().expect("drun nonces are 8 bytes long");
+        builder = builder.nonce(u64::from_le_bytes(nonce));
+    }
+    builder.build()
+}
+
 fn validate_method_name(method_name: &str) -> Result<String, String> {
     fn is_ident_start(c: char) -> bool {
         c.is_ascii() && (c.is_alphabetic() || c == '_')
@@ -373,6 +558,7 @@ mod tests {
     use super::*;
     use ic_test_utilities::types::{ids::canister_test_id, messages::SignedIngressBuilder};
     use std::io::Cursor;
+    use std::str::FromStr;
 
     const APP_CANISTER_URL: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
     const APP_CANISTER_ID: u64 = 2;
@@ -383,7 +569,7 @@ mod tests {
             "ingress {} write \"payload \\x0a\\b00010001\"",
             APP_CANISTER_URL
         );
-        let parsed_message = parse_message(s, 0).unwrap();
+        let parsed_message = parse_message(s, 0, None).unwrap();
         let expiry_time = match &parsed_message {
             Message::Ingress(signed_ingress) => signed_ingress.expiry_time(),
             _ => panic!(
@@ -406,7 +592,7 @@ mod tests {
     #[test]
     fn test_parse_message_hex_payload_succeeds() {
         let s = &format!("ingress {} write 0x010203", APP_CANISTER_URL);
-        let parsed_message = parse_message(s, 0).unwrap();
+        let parsed_message = parse_message(s, 0, None).unwrap();
         let expiry_time = match &parsed_message {
             Message::Ingress(signed_ingress) => signed_ingress.expiry_time(),
             _ => panic!(
@@ -427,7 +613,7 @@ mod tests {
 
         let s = &format!("query {} read 0x010203", APP_CANISTER_URL);
         let nonce: u64 = 0;
-        let parsed_message = parse_message(s, 0).unwrap();
+        let parsed_message = parse_message(s, 0, None).unwrap();
         let ingress_expiry = match &parsed_message {
             Message::Query(query) => query.ingress_expiry,
             _ => panic!(
@@ -449,25 +635,101 @@ mod tests {
     #[test]
     fn test_parse_message_invalid_escapes_fails() {
         let s = &format!("query {} read \"\\xzz\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
 
         let s = &format!("query {} read \"\\b01\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
 
         let s = &format!("query {} read \"\\x1\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
 
         let s = &format!("query {} read \"\\b2\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
     }
 
     #[test]
     fn test_illegal_method_name_must_fail() {
         let s = &format!("query {} 0read \"\\xzz\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
 
         let s = &format!("query {} üread \"\\xzz\"", APP_CANISTER_URL);
-        assert!(parse_message(s, 0).is_err());
+        assert!(parse_message(s, 0, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_script_directives() {
+        let script = Script::default();
+        let parse = |s: &str| parse_message(s, 4, Some(&script));
+
+        assert_eq!(parse("tick").unwrap(), Message::Tick(1));
+        assert_eq!(parse("tick 10").unwrap(), Message::Tick(10));
+        assert_eq!(
+            parse("set-time 1700000000000000000").unwrap(),
+            Message::SetTime(Time::from_nanos_since_unix_epoch(1_700_000_000_000_000_000))
+        );
+        assert_eq!(
+            parse("advance-time 90s").unwrap(),
+            Message::AdvanceTime(Duration::from_secs(90))
+        );
+        assert!(parse("advance-time 90").is_err());
+        assert_eq!(
+            parse("expect-reply (42 : nat, record { a = \"b\" })").unwrap(),
+            Message::Expect {
+                line: 5,
+                assertion: Assertion::Reply(Some(ExpectedPayload::Candid(
+                    "(42 : nat, record { a = \"b\" })".to_string()
+                ))),
+            }
+        );
+        assert_eq!(
+            parse("expect-reply 0x4449").unwrap(),
+            Message::Expect {
+                line: 5,
+                assertion: Assertion::Reply(Some(ExpectedPayload::Bytes(vec![0x44, 0x49]))),
+            }
+        );
+        assert_eq!(
+            parse("expect-reject 5 \"trapped\"").unwrap(),
+            Message::Expect {
+                line: 5,
+                assertion: Assertion::Reject {
+                    code: RejectCode::CanisterError,
+                    message: Some("trapped".to_string()),
+                },
+            }
+        );
+        assert!(parse("expect-reject 9").is_err());
+        assert!(matches!(
+            parse(&format!("top-up {} 1_000_000", APP_CANISTER_URL)).unwrap(),
+            Message::Ingress(_)
+        ));
+        assert!(matches!(
+            parse("create 1_000_000").unwrap(),
+            Message::Create(_)
+        ));
+
+        // Directives are not recognized outside of script mode.
+        assert!(parse_message("tick", 4, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_message_candid_payload() {
+        let script = Script::default();
+        let s = &format!("query {} read (\"hello\", 42 : nat8)", APP_CANISTER_URL);
+        match parse_message(s, 0, Some(&script)).unwrap() {
+            Message::Query(query) => assert_eq!(
+                query.method_payload,
+                IDLArgs::from_str("(\"hello\", 42 : nat8)")
+                    .unwrap()
+                    .to_bytes()
+                    .unwrap()
+            ),
+            message => panic!(
+                "parse_message() returned an unexpected message type: {:?}",
+                message
+            ),
+        }
+        assert!(parse_message(s, 0, None).is_err());
     }
 
     #[test]
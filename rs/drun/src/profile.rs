@@ -0,0 +1,263 @@
+//! Per-message cost report of `drun`, enabled with `--profile`.
+//!
+//! The costs are computed as the difference of execution metrics and of the
+//! cycles consumed by all canisters before and after a message has been
+//! processed. They hence also include the costs of the downstream calls
+//! triggered by the message.
+//!
+//! The instructions are the ones the executions of the message and of its
+//! callbacks report as used, which the metrics below observe for every single
+//! execution. Unlike the round instructions, they exclude the overhead the
+//! scheduler charges to the round for every message.
+
+use ic_error_types::UserError;
+use ic_metrics::MetricsRegistry;
+use ic_replicated_state::ReplicatedState;
+use ic_types::{ingress::WasmResult, CanisterId};
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Instructions used by the executions of canister messages.
+const MESSAGE_INSTRUCTIONS: &str = "scheduler_instructions_consumed_per_message";
+/// Instructions used by the executions of subnet messages, e.g. `install_code`.
+const SUBNET_MESSAGE_INSTRUCTIONS: [&str; 2] = [
+    "execution_round_consensus_queue_instructions",
+    "execution_round_subnet_queue_instructions",
+];
+/// Instructions used by the executions of queries and their callbacks.
+const QUERY_INSTRUCTIONS: &str = "execution_query_instructions";
+const DIRTY_PAGES: &str = "hypervisor_dirty_pages";
+const STABLE_DIRTY_PAGES: &str = "hypervisor_stable_dirty_pages";
+
+const CSV_HEADER: &str = "message,kind,canister_id,method,instructions,heap_dirty_pages,\
+    stable_dirty_pages,cycles_charged,response_bytes,status";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// Snapshot of the counters the costs of a message are computed from.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Counters {
+    instructions: f64,
+    dirty_pages: f64,
+    stable_dirty_pages: f64,
+    consumed_cycles: u128,
+}
+
+/// The costs of a single message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MessageProfile {
+    kind: &'static str,
+    canister_id: CanisterId,
+    method_name: String,
+    instructions: u64,
+    heap_dirty_pages: u64,
+    stable_dirty_pages: u64,
+    cycles_charged: u128,
+    response_bytes: usize,
+    status: &'static str,
+}
+
+/// Records the costs of the processed messages and writes them to a CSV or
+/// JSON file, depending on the extension of the file.
+pub(crate) struct Profiler {
+    path: PathBuf,
+    format: Format,
+    metrics_registry: MetricsRegistry,
+    profiles: Vec<MessageProfile>,
+}
+
+/// Returns the sum of all observations of the histogram `name`.
+fn histogram_sum(metrics_registry: &MetricsRegistry, name: &str) -> f64 {
+    metrics_registry
+        .prometheus_registry()
+        .gather()
+        .iter()
+        .filter(|family| family.get_name() == name)
+        .flat_map(|family| family.get_metric())
+        .map(|metric| metric.get_histogram().get_sample_sum())
+        .sum()
+}
+
+impl Profiler {
+    pub(crate) fn new(path: PathBuf, metrics_registry: MetricsRegistry) -> Self {
+        let format = match path.extension() {
+            Some(extension) if extension == "json" => Format::Json,
+            _ => Format::Csv,
+        };
+        Self {
+            path,
+            format,
+            metrics_registry,
+            profiles: Vec::new(),
+        }
+    }
+
+    /// Takes a snapshot of the counters; to be called before a message is
+    /// processed.
+    pub(crate) fn counters(&self, state: &ReplicatedState) -> Counters {
+        Counters {
+            instructions: histogram_sum(&self.metrics_registry, MESSAGE_INSTRUCTIONS)
+                + SUBNET_MESSAGE_INSTRUCTIONS
+                    .iter()
+                    .map(|name| histogram_sum(&self.metrics_registry, name))
+                    .sum::<f64>()
+                + histogram_sum(&self.metrics_registry, QUERY_INSTRUCTIONS),
+            dirty_pages: histogram_sum(&self.metrics_registry, DIRTY_PAGES),
+            stable_dirty_pages: histogram_sum(&self.metrics_registry, STABLE_DIRTY_PAGES),
+            consumed_cycles: state
+                .canisters_iter()
+                .map(|canister| {
+                    canister
+                        .system_state
+                        .canister_metrics
+                        .consumed_cycles_since_replica_started
+                        .get()
+                })
+                .sum(),
+        }
+    }
+
+    /// Records the costs of a message given the counters taken `before` it
+    /// was processed and the `state` after it was processed.
+    pub(crate) fn record(
+        &mut self,
+        kind: &'static str,
+        canister_id: CanisterId,
+        method_name: String,
+        before: Counters,
+        state: &ReplicatedState,
+        result: &Result<WasmResult, UserError>,
+    ) {
+        let after = self.counters(state);
+        let dirty_pages = (after.dirty_pages - before.dirty_pages) as u64;
+        let stable_dirty_pages = (after.stable_dirty_pages - before.stable_dirty_pages) as u64;
+        let (response_bytes, status) = match result {
+            Ok(WasmResult::Reply(reply)) => (reply.len(), "reply"),
+            Ok(WasmResult::Reject(message)) => (message.len(), "reject"),
+            Err(error) => (error.description().len(), "error"),
+        };
+        self.profiles.push(MessageProfile {
+            kind,
+            canister_id,
+            method_name,
+            instructions: (after.instructions - before.instructions) as u64,
+            heap_dirty_pages: dirty_pages.saturating_sub(stable_dirty_pages),
+            stable_dirty_pages,
+            cycles_charged: after.consumed_cycles.saturating_sub(before.consumed_cycles),
+            response_bytes,
+            status,
+        });
+    }
+
+    /// Writes the report of all recorded messages.
+    pub(crate) fn write(&self) -> Result<(), String> {
+        let report = match self.format {
+            Format::Csv => to_csv(&self.profiles),
+            Format::Json => to_json(&self.profiles),
+        };
+        std::fs::write(&self.path, report)
+            .map_err(|e| format!("Failed to write profile to {}: {}", self.path.display(), e))
+    }
+}
+
+fn to_csv(profiles: &[MessageProfile]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for (i, p) in profiles.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            i + 1,
+            p.kind,
+            p.canister_id,
+            p.method_name,
+            p.instructions,
+            p.heap_dirty_pages,
+            p.stable_dirty_pages,
+            p.cycles_charged,
+            p.response_bytes,
+            p.status
+        ));
+    }
+    csv
+}
+
+fn to_json(profiles: &[MessageProfile]) -> String {
+    let profiles: Vec<_> = profiles
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            json!({
+                "message": i + 1,
+                "kind": p.kind,
+                "canister_id": p.canister_id.to_string(),
+                "method": p.method_name,
+                "instructions": p.instructions,
+                "heap_dirty_pages": p.heap_dirty_pages,
+                "stable_dirty_pages": p.stable_dirty_pages,
+                // Cycles may exceed the range of JSON numbers.
+                "cycles_charged": p.cycles_charged.to_string(),
+                "response_bytes": p.response_bytes,
+                "status": p.status,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&profiles).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_test_utilities::types::ids::canister_test_id;
+
+    fn profile() -> MessageProfile {
+        MessageProfile {
+            kind: "ingress",
+            canister_id: canister_test_id(2),
+            method_name: "write".to_string(),
+            instructions: 12_345,
+            heap_dirty_pages: 3,
+            stable_dirty_pages: 1,
+            cycles_charged: 590_000,
+            response_bytes: 6,
+            status: "reply",
+        }
+    }
+
+    #[test]
+    fn test_profile_csv() {
+        assert_eq!(
+            to_csv(&[profile()]),
+            format!(
+                "{}\n1,ingress,{},write,12345,3,1,590000,6,reply\n",
+                CSV_HEADER,
+                canister_test_id(2)
+            )
+        );
+    }
+
+    #[test]
+    fn test_profile_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&[profile()])).unwrap();
+        assert_eq!(json[0]["message"], 1);
+        assert_eq!(json[0]["method"], "write");
+        assert_eq!(json[0]["instructions"], 12_345);
+        assert_eq!(json[0]["heap_dirty_pages"], 3);
+        assert_eq!(json[0]["cycles_charged"], "590000");
+    }
+
+    #[test]
+    fn test_profile_format_from_extension() {
+        let registry = MetricsRegistry::new();
+        assert_eq!(
+            Profiler::new(PathBuf::from("profile.json"), registry.clone()).format,
+            Format::Json
+        );
+        assert_eq!(
+            Profiler::new(PathBuf::from("profile.csv"), registry).format,
+            Format::Csv
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Script mode of `drun`.
+//!
+//! In script mode, payloads can additionally be written as Candid text, replies
+//! are printed as Candid values and the input may contain assertions about the
+//! outcome of the preceding message as well as directives controlling time and
+//! cycles. See `parse_message()` for the syntax of the directives.
+
+use candid::{
+    parser::typing::{pretty_check_file, TypeEnv},
+    types::{Function, Type},
+    IDLArgs,
+};
+use ic_error_types::{RejectCode, UserError};
+use ic_types::ingress::WasmResult;
+use std::path::Path;
+
+/// The Candid interface of the canisters under test.
+pub(crate) struct Interface {
+    env: TypeEnv,
+    actor: Option<Type>,
+}
+
+impl Interface {
+    /// Loads the interface from a `.did` file.
+    pub(crate) fn from_file(path: &Path) -> Result<Self, String> {
+        let (env, actor) = pretty_check_file(path)
+            .map_err(|e| format!("Failed to load Candid file {}: {}", path.display(), e))?;
+        Ok(Self { env, actor })
+    }
+
+    fn method(&self, method_name: &str) -> Option<&Function> {
+        self.actor
+            .as_ref()
+            .and_then(|actor| self.env.get_method(actor, method_name).ok())
+    }
+
+    fn init_args(&self) -> Option<&[Type]> {
+        match &self.actor {
+            Some(Type::Class(args, _)) => Some(args),
+            _ => None,
+        }
+    }
+}
+
+/// Settings of the script mode.
+#[derive(Default)]
+pub(crate) struct Script {
+    interface: Option<Interface>,
+}
+
+/// The expected payload of an `expect-reply` assertion.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ExpectedPayload {
+    /// Raw bytes given as a hex or quoted string; compared byte by byte.
+    Bytes(Vec<u8>),
+    /// Candid text; compared with the decoded reply.
+    Candid(String),
+}
+
+/// An assertion about the outcome of the preceding message.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Assertion {
+    Reply(Option<ExpectedPayload>),
+    Reject {
+        code: RejectCode,
+        message: Option<String>,
+    },
+}
+
+/// The outcome of an ingress message or query.
+pub(crate) struct Outcome {
+    /// The method that was called, or `None` if the message was addressed to
+    /// the management canister.
+    pub(crate) method_name: Option<String>,
+    pub(crate) result: Result<WasmResult, UserError>,
+}
+
+impl Script {
+    pub(crate) fn new(interface: Option<Interface>) -> Self {
+        Self { interface }
+    }
+
+    /// Returns the type environment and the argument types of `method_name`,
+    /// or the types of the init arguments if `method_name` is `None`.
+    fn arg_types(&self, method_name: Option<&str>) -> Option<(&TypeEnv, &[Type])> {
+        let interface = self.interface.as_ref()?;
+        let types = match method_name {
+            Some(method_name) => &interface.method(method_name)?.args[..],
+            None => interface.init_args()?,
+        };
+        Some((&interface.env, types))
+    }
+
+    fn ret_types(&self, method_name: Option<&str>) -> Option<(&TypeEnv, &[Type])> {
+        let interface = self.interface.as_ref()?;
+        let function = interface.method(method_name?)?;
+        Some((&interface.env, &function.rets[..]))
+    }
+
+    /// Encodes the Candid text `args` as the arguments of `method_name`, or
+    /// as the init arguments if `method_name` is `None`. Without a Candid
+    /// interface for the method, the types are inferred from the text.
+    pub(crate) fn encode_args(
+        &self,
+        method_name: Option<&str>,
+        args: &str,
+    ) -> Result<Vec<u8>, String> {
+        let args = args
+            .parse::<IDLArgs>()
+            .map_err(|e| format!("Failed to parse Candid arguments {}: {}", args, e))?;
+        match self.arg_types(method_name) {
+            Some((env, types)) => args.to_bytes_with_types(env, types),
+            None => args.to_bytes(),
+        }
+        .map_err(|e| format!("Failed to encode Candid arguments: {}", e))
+    }
+
+    /// Decodes the reply of `method_name`.
+    fn decode_reply(&self, method_name: Option<&str>, bytes: &[u8]) -> Result<IDLArgs, String> {
+        match self.ret_types(method_name) {
+            Some((env, types)) => IDLArgs::from_bytes_with_types(bytes, env, types),
+            None => IDLArgs::from_bytes(bytes),
+        }
+        .map_err(|e| format!("Failed to decode Candid reply: {}", e))
+    }
+
+    /// Formats a reply of `method_name` as Candid text, falling back to hex
+    /// if the reply is not valid Candid.
+    pub(crate) fn format_reply(&self, method_name: Option<&str>, bytes: &[u8]) -> String {
+        match self.decode_reply(method_name, bytes) {
+            Ok(args) => args.to_string(),
+            Err(_) => format!("0x{}", hex::encode(bytes)),
+        }
+    }
+
+    /// Checks whether the Candid reply `bytes` of `method_name` is equal to
+    /// the Candid text `expected`.
+    fn candid_reply_matches(
+        &self,
+        method_name: Option<&str>,
+        expected: &str,
+        bytes: &[u8],
+    ) -> Result<bool, String> {
+        let actual = self.decode_reply(method_name, bytes)?;
+        let empty_env = TypeEnv::new();
+        let inferred_types: Vec<Type>;
+        let (env, types) = match self.ret_types(method_name) {
+            Some(ret_types) => ret_types,
+            None => {
+                inferred_types = actual.args.iter().map(|arg| arg.value_ty()).collect();
+                (&empty_env, &inferred_types[..])
+            }
+        };
+        let expected = expected
+            .parse::<IDLArgs>()
+            .and_then(|args| args.annotate_types(true, env, types))
+            .map_err(|e| format!("Expected reply does not match the reply type: {}", e))?;
+        Ok(expected.args == actual.args)
+    }
+
+    /// Checks `assertion` against the outcome of the preceding message.
+    pub(crate) fn check(
+        &self,
+        assertion: &Assertion,
+        outcome: Option<&Outcome>,
+    ) -> Result<(), String> {
+        let outcome = outcome.ok_or("there is no preceding message")?;
+        let method_name = outcome.method_name.as_deref();
+        match (assertion, &outcome.result) {
+            (Assertion::Reply(expected), Ok(WasmResult::Reply(bytes))) => {
+                let matches = match expected {
+                    None => true,
+                    Some(ExpectedPayload::Bytes(expected)) => expected == bytes,
+                    Some(ExpectedPayload::Candid(expected)) => {
+                        self.candid_reply_matches(method_name, expected, bytes)?
+                    }
+                };
+                if matches {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "unexpected reply {}",
+                        self.format_reply(method_name, bytes)
+                    ))
+                }
+            }
+            (Assertion::Reply(_), Ok(WasmResult::Reject(message))) => Err(format!(
+                "expected a reply, got reject {}: {}",
+                RejectCode::CanisterReject as u64,
+                message
+            )),
+            (Assertion::Reply(_), Err(error)) => Err(format!(
+                "expected a reply, got reject {}: {}",
+                error.reject_code() as u64,
+                error
+            )),
+            (Assertion::Reject { .. }, Ok(WasmResult::Reply(bytes))) => Err(format!(
+                "expected a reject, got reply {}",
+                self.format_reply(method_name, bytes)
+            )),
+            (Assertion::Reject { code, message }, Ok(WasmResult::Reject(actual))) => {
+                check_reject(*code, message, RejectCode::CanisterReject, actual)
+            }
+            (Assertion::Reject { code, message }, Err(error)) => {
+                check_reject(*code, message, error.reject_code(), &error.to_string())
+            }
+        }
+    }
+}
+
+fn check_reject(
+    expected_code: RejectCode,
+    expected_message: &Option<String>,
+    code: RejectCode,
+    message: &str,
+) -> Result<(), String> {
+    if expected_code != code {
+        return Err(format!(
+            "expected reject {}, got reject {}: {}",
+            expected_code as u64, code as u64, message
+        ));
+    }
+    match expected_message {
+        Some(expected) if !message.contains(expected.as_str()) => Err(format!(
+            "expected reject message containing {:?}, got {:?}",
+            expected, message
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_error_types::ErrorCode;
+
+    fn outcome(result: Result<WasmResult, UserError>) -> Outcome {
+        Outcome {
+            method_name: Some("read".to_string()),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_candid_reply_assertion() {
+        let script = Script::default();
+        let reply = script
+            .encode_args(Some("read"), "(42 : nat, \"hello\")")
+            .unwrap();
+        let outcome = outcome(Ok(WasmResult::Reply(reply)));
+
+        let expect = |text: &str| {
+            script.check(
+                &Assertion::Reply(Some(ExpectedPayload::Candid(text.to_string()))),
+                Some(&outcome),
+            )
+        };
+        assert_eq!(expect("(42, \"hello\")"), Ok(()));
+        assert!(expect("(43, \"hello\")").is_err());
+        assert!(script
+            .check(&Assertion::Reply(None), Some(&outcome))
+            .is_ok());
+        assert!(script
+            .check(
+                &Assertion::Reject {
+                    code: RejectCode::CanisterReject,
+                    message: None
+                },
+                Some(&outcome)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_assertion() {
+        let script = Script::default();
+        let outcome = outcome(Err(UserError::new(
+            ErrorCode::CanisterTrapped,
+            "Canister trapped: unreachable",
+        )));
+        let expect_reject = |code, message: Option<&str>| {
+            script.check(
+                &Assertion::Reject {
+                    code,
+                    message: message.map(str::to_string),
+                },
+                Some(&outcome),
+            )
+        };
+        assert_eq!(expect_reject(RejectCode::CanisterError, None), Ok(()));
+        assert_eq!(
+            expect_reject(RejectCode::CanisterError, Some("unreachable")),
+            Ok(())
+        );
+        assert!(expect_reject(RejectCode::CanisterError, Some("out of cycles")).is_err());
+        assert!(expect_reject(RejectCode::CanisterReject, None).is_err());
+        assert!(script.check(&Assertion::Reply(None), None).is_err());
+    }
+}
@@ -55,6 +55,7 @@ impl Config for ICWasmConfig {
     // - If it exports a function called canister_inspect_message, the function must have type () -> ().
     // - If it exports a function called canister_heartbeat, the function must have type () -> ().
     // - If it exports a function called canister_global_timer, the function must have type +() -> ()+.
+    // - If it exports a function called canister_on_low_wasm_memory, the function must have type () -> ().
     // - If it exports any functions called canister_update <name> or canister_query <name> for some name, the functions must have type () -> ().
     // - It may not export both canister_update <name> and canister_query <name> with the same name.
     // - It may not export other methods the names of which start with the prefix canister_ besides the methods allowed above.
@@ -486,6 +486,7 @@ pub fn wasm_execution_error(
             allocated_bytes: NumBytes::from(0),
             allocated_message_bytes: NumBytes::from(0),
             instance_stats: InstanceStats::default(),
+            canister_log: Default::default(),
         },
         None,
     )
@@ -647,6 +648,7 @@ pub fn process(
                     allocated_bytes: NumBytes::from(0),
                     allocated_message_bytes: NumBytes::from(0),
                     instance_stats: InstanceStats::default(),
+                    canister_log: Default::default(),
                 },
                 None,
                 Err(system_api),
@@ -696,6 +698,13 @@ pub fn process(
         wasm_result = Err(HypervisorError::WasmReservedPages);
     }
 
+    let system_api = &mut instance.store_data_mut().system_api;
+    match &wasm_result {
+        Ok(_) | Err(HypervisorError::Aborted) => {}
+        Err(err) => system_api.save_trap_message(err),
+    }
+    let canister_log = system_api.take_canister_log();
+
     let mut allocated_bytes = NumBytes::from(0);
     let mut allocated_message_bytes = NumBytes::from(0);
     let mut execution_complexity = ExecutionComplexity::default();
@@ -768,6 +777,7 @@ pub fn process(
             allocated_bytes,
             allocated_message_bytes,
             instance_stats,
+            canister_log,
         },
         wasm_state_changes,
         Ok(instance),
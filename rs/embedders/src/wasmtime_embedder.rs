@@ -29,7 +29,7 @@ use ic_replicated_state::{
 use ic_sys::PAGE_SIZE;
 use ic_types::{
     methods::{FuncRef, WasmMethod},
-    CanisterId, MAX_STABLE_MEMORY_IN_BYTES,
+    CanisterId, NumBytes, MAX_STABLE_MEMORY_IN_BYTES, MAX_WASM_MEMORY_IN_BYTES,
 };
 use ic_wasm_types::{BinaryEncodedWasm, WasmEngineError};
 use memory_tracker::{DirtyPageTracking, PageBitmap, SigsegvMemoryTracker};
@@ -319,11 +319,13 @@ impl WasmtimeEmbedder {
             Err(err) => return Err((err.clone(), system_api)),
         };
 
+        let wasm_memory_limit = system_api.wasm_memory_limit();
         let mut store = Store::new(
             module.engine(),
             StoreData {
                 system_api,
                 num_instructions_global: None,
+                limiter: WasmMemoryLimiter::default(),
             },
         );
 
@@ -430,6 +432,12 @@ impl WasmtimeEmbedder {
                 self.instantiate_memory(memory_info, &instance, store, &mut memories, canister_id)?;
         }
 
+        // The limiter is installed only after the memories have been restored
+        // so that a canister whose heap already exceeds the limit can still be
+        // instantiated. Only subsequent `memory.grow` calls are restricted.
+        store.data_mut().limiter.wasm_memory_limit = wasm_memory_limit;
+        store.limiter(|data| &mut data.limiter);
+
         let memory_trackers = sigsegv_memory_tracker(memories, &mut store, self.log.clone());
 
         let signal_stack = WasmtimeSignalStack::new();
@@ -638,6 +646,42 @@ fn sigsegv_memory_tracker<S>(
 pub struct StoreData<S> {
     pub system_api: S,
     pub num_instructions_global: Option<wasmtime::Global>,
+    pub limiter: WasmMemoryLimiter,
+}
+
+/// Makes `memory.grow` on the Wasm heap fail (return -1) if the resulting
+/// size would exceed the canister's `wasm_memory_limit`.
+///
+/// The heap is distinguished from the stable memory by its maximum size. The
+/// bytemap memories never grow because their initial and maximum sizes are
+/// the same.
+#[derive(Default)]
+pub struct WasmMemoryLimiter {
+    pub wasm_memory_limit: Option<NumBytes>,
+}
+
+impl wasmtime::ResourceLimiter for WasmMemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let is_wasm_heap = maximum.map_or(true, |max| max as u64 <= MAX_WASM_MEMORY_IN_BYTES);
+        match self.wasm_memory_limit {
+            Some(limit) if is_wasm_heap => Ok(desired as u64 <= limit.get()),
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> anyhow::Result<bool> {
+        Ok(true)
+    }
 }
 
 pub struct PageAccessResults {
@@ -846,6 +890,7 @@ impl<S: SystemApi> WasmtimeInstance<S> {
                 .collect(),
         };
         self.instance_stats.dirty_pages += stable_memory_dirty_pages.len();
+        self.instance_stats.stable_dirty_pages += stable_memory_dirty_pages.len();
 
         match result {
             Ok(_) => Ok(InstanceRunResult {
@@ -585,6 +585,15 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "msg_deadline", {
+            move |mut caller: Caller<'_, StoreData<S>>| {
+                with_system_api(&mut caller, |s| s.ic0_msg_deadline())
+                    .map_err(|e| process_err(&mut caller, e))
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "msg_reject", {
             let log = log.clone();
@@ -759,6 +768,12 @@ pub(crate) fn syscalls<S: SystemApi>(
                     NumInstructions::from(0),
                     stable_memory_dirty_page_limit,
                 )?;
+                // The message is always recorded in the canister log, even
+                // when printing it to the replica log is rate limited.
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.save_log_message(offset as u32, length as u32, memory);
+                    Ok(())
+                })?;
                 match (
                     caller.data().system_api.subnet_type(),
                     feature_flags.rate_limiting_of_debug_prints,
@@ -876,6 +891,25 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "call_with_best_effort_response", {
+            move |mut caller: Caller<'_, StoreData<S>>, timeout_seconds: i32| {
+                if feature_flags.best_effort_responses == FlagStatus::Disabled {
+                    return Err(process_err(
+                        &mut caller,
+                        HypervisorError::ContractViolation(
+                            "ic0.call_with_best_effort_response is not enabled.".to_string(),
+                        ),
+                    ));
+                }
+                with_system_api(&mut caller, |s| {
+                    s.ic0_call_with_best_effort_response(timeout_seconds as u32)
+                })
+                .map_err(|e| process_err(&mut caller, e))
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "call_cycles_add", {
             move |mut caller: Caller<'_, StoreData<S>>, amount: i64| {
@@ -1468,6 +1502,129 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "cost_call", {
+            let log = log.clone();
+            move |mut caller: Caller<'_, StoreData<S>>,
+                  method_name_size: i64,
+                  payload_size: i64,
+                  dst: u32| {
+                observe_execution_complexity(
+                    &log,
+                    canister_id,
+                    &mut caller,
+                    ExecutionComplexity {
+                        cpu: system_api_complexity::cpu::COST_CALL,
+                        ..Default::default()
+                    },
+                    stable_memory_dirty_page_limit,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_call(
+                        method_name_size as u64,
+                        payload_size as u64,
+                        dst,
+                        memory,
+                    )
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_create_canister", {
+            let log = log.clone();
+            move |mut caller: Caller<'_, StoreData<S>>, dst: u32| {
+                observe_execution_complexity(
+                    &log,
+                    canister_id,
+                    &mut caller,
+                    ExecutionComplexity {
+                        cpu: system_api_complexity::cpu::COST_CREATE_CANISTER,
+                        ..Default::default()
+                    },
+                    stable_memory_dirty_page_limit,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_create_canister(dst, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_http_request", {
+            let log = log.clone();
+            move |mut caller: Caller<'_, StoreData<S>>,
+                  request_size: i64,
+                  max_res_bytes: i64,
+                  dst: u32| {
+                observe_execution_complexity(
+                    &log,
+                    canister_id,
+                    &mut caller,
+                    ExecutionComplexity {
+                        cpu: system_api_complexity::cpu::COST_HTTP_REQUEST,
+                        ..Default::default()
+                    },
+                    stable_memory_dirty_page_limit,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_http_request(
+                        request_size as u64,
+                        max_res_bytes as u64,
+                        dst,
+                        memory,
+                    )
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_sign_with_ecdsa", {
+            let log = log.clone();
+            move |mut caller: Caller<'_, StoreData<S>>,
+                  src: u32,
+                  size: u32,
+                  curve: u32,
+                  dst: u32| {
+                observe_execution_complexity(
+                    &log,
+                    canister_id,
+                    &mut caller,
+                    ExecutionComplexity {
+                        cpu: system_api_complexity::cpu::COST_SIGN_WITH_ECDSA,
+                        ..Default::default()
+                    },
+                    stable_memory_dirty_page_limit,
+                )?;
+                let result = with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_sign_with_ecdsa(src, size, curve, dst, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled && result == 0 {
+                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)?;
+                }
+                Ok(result)
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "data_certificate_copy", {
             move |mut caller: Caller<'_, StoreData<S>>, dst: u32, offset: u32, size: u32| {
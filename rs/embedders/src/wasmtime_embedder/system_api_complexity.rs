@@ -106,4 +106,8 @@ pub mod cpu {
     pub const CERTIFIED_DATA_SET: CpuComplexity = from_nanos(70);
     pub const PERFORMANCE_COUNTER: CpuComplexity = from_nanos(50);
     pub const IS_CONTROLLER: CpuComplexity = from_nanos(200);
+    pub const COST_CALL: CpuComplexity = from_nanos(50);
+    pub const COST_CREATE_CANISTER: CpuComplexity = from_nanos(50);
+    pub const COST_HTTP_REQUEST: CpuComplexity = from_nanos(50);
+    pub const COST_SIGN_WITH_ECDSA: CpuComplexity = from_nanos(50);
 }
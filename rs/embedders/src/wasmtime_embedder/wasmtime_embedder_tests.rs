@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use super::{system_api, StoreData, INSTRUCTIONS_COUNTER_GLOBAL_NAME};
+use super::{system_api, StoreData, WasmMemoryLimiter, INSTRUCTIONS_COUNTER_GLOBAL_NAME};
 use crate::{wasm_utils::validate_and_instrument_for_testing, WasmtimeEmbedder};
 use ic_config::flag_status::FlagStatus;
 use ic_config::{embedders::Config as EmbeddersConfig, subnet_config::SchedulerConfig};
@@ -71,6 +71,7 @@ fn test_wasmtime_system_api() {
         StoreData {
             system_api,
             num_instructions_global: None,
+            limiter: WasmMemoryLimiter::default(),
         },
     );
 
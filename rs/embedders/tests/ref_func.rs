@@ -3,6 +3,7 @@
 
 use ic_test_utilities::{mock_time, wasmtime_instance::WasmtimeInstanceBuilder};
 use ic_types::{
+    messages::NO_DEADLINE,
     methods::{FuncRef, WasmMethod},
     Cycles, PrincipalId,
 };
@@ -18,6 +19,7 @@ fn run_go_export(wat: &str) {
             Cycles::from(0_u128),
             PrincipalId::new_user_test_id(0),
             0.into(),
+            NO_DEADLINE,
         ))
         .with_num_instructions(LARGE_INSTRUCTION_LIMIT.into())
         .build();
@@ -6,6 +6,7 @@ use ic_test_utilities::{
     mock_time, types::ids::user_test_id, wasmtime_instance::WasmtimeInstanceBuilder,
 };
 use ic_types::{
+    messages::NO_DEADLINE,
     methods::{FuncRef, WasmMethod},
     Cycles,
 };
@@ -286,6 +287,7 @@ mod test {
                 Cycles::zero(),
                 PrincipalId::new_user_test_id(0),
                 0.into(),
+                NO_DEADLINE,
             ))
             .build();
 
@@ -330,6 +332,7 @@ mod test {
                 Cycles::zero(),
                 PrincipalId::new_user_test_id(0),
                 0.into(),
+                NO_DEADLINE,
             ))
             .with_num_instructions((expected_cpu_complexity as u64 - 1).into())
             .with_subnet_type(subnet_type)
@@ -360,6 +363,7 @@ mod test {
                 Cycles::zero(),
                 PrincipalId::new_user_test_id(0),
                 0.into(),
+                NO_DEADLINE,
             ))
             .with_num_instructions((expected_cpu_complexity as u64 - 1).into())
             .with_subnet_type(subnet_type)
@@ -373,6 +377,59 @@ mod test {
         assert!(result.is_ok());
     }
 
+    const CALL_WITH_BEST_EFFORT_RESPONSE_WAT: &str = r#"
+    (module
+        (import "ic0" "call_new"
+            (func $ic0_call_new
+            (param $callee_src i32)         (param $callee_size i32)
+            (param $name_src i32)           (param $name_size i32)
+            (param $reply_fun i32)          (param $reply_env i32)
+            (param $reject_fun i32)         (param $reject_env i32)
+        ))
+        (import "ic0" "call_with_best_effort_response"
+            (func $ic0_call_with_best_effort_response (param $timeout_seconds i32)))
+        (memory 1)
+        (func (export "canister_update test_best_effort_call")
+            (call $ic0_call_new
+                (i32.const 0)   (i32.const 10)
+                (i32.const 100) (i32.const 18)
+                (i32.const 11)  (i32.const 0) ;; non-existent function
+                (i32.const 22)  (i32.const 0) ;; non-existent function
+            )
+            (call $ic0_call_with_best_effort_response (i32.const 10))
+        )
+    )
+    "#;
+
+    #[test]
+    fn call_with_best_effort_response_requires_feature_flag() {
+        let run = |best_effort_responses| {
+            let mut config = ic_config::embedders::Config::default();
+            config.feature_flags.best_effort_responses = best_effort_responses;
+            let mut instance = WasmtimeInstanceBuilder::new()
+                .with_config(config)
+                .with_wat(CALL_WITH_BEST_EFFORT_RESPONSE_WAT)
+                .with_api_type(ic_system_api::ApiType::update(
+                    mock_time(),
+                    vec![],
+                    Cycles::zero(),
+                    PrincipalId::new_user_test_id(0),
+                    0.into(),
+                    NO_DEADLINE,
+                ))
+                .build();
+            instance.run(FuncRef::Method(WasmMethod::Update(
+                "test_best_effort_call".to_string(),
+            )))
+        };
+
+        assert_matches!(
+            run(ic_config::flag_status::FlagStatus::Disabled),
+            Err(HypervisorError::ContractViolation(_))
+        );
+        assert!(run(ic_config::flag_status::FlagStatus::Enabled).is_ok());
+    }
+
     #[test]
     fn stack_overflow_traps() {
         use std::thread;
@@ -691,6 +748,7 @@ mod test {
                 Cycles::zero(),
                 PrincipalId::new_user_test_id(0),
                 0.into(),
+                NO_DEADLINE,
             ))
             .build();
         instance
@@ -719,6 +777,7 @@ mod test {
                 Cycles::zero(),
                 PrincipalId::new_user_test_id(0),
                 0.into(),
+                NO_DEADLINE,
             ))
             .build();
         instance
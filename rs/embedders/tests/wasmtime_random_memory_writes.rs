@@ -18,6 +18,7 @@ use ic_test_utilities::{
 };
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_types::{
+    messages::NO_DEADLINE,
     methods::{FuncRef, WasmMethod},
     ComputeAllocation, Cycles, NumBytes, NumInstructions, PrincipalId,
 };
@@ -71,6 +72,7 @@ fn test_api_for_update(
             Cycles::zero(),
             caller,
             call_context_test_id(13),
+            NO_DEADLINE,
         ),
         static_system_state,
         canister_current_memory_usage,
@@ -32,7 +32,7 @@ use ic_test_utilities::{
 };
 use ic_test_utilities_execution_environment::generate_network_topology;
 use ic_types::{
-    messages::{CallbackId, Payload, RejectContext},
+    messages::{CallbackId, Payload, RejectContext, NO_DEADLINE},
     methods::{Callback, WasmClosure},
     Cycles, MemoryAllocation, NumBytes, NumInstructions, Time,
 };
@@ -85,6 +85,7 @@ where
         instructions: as_round_instructions(MAX_NUM_INSTRUCTIONS),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: *MAX_SUBNET_AVAILABLE_MEMORY,
+        subnet_memory_saturation: exec_env.subnet_memory_saturation(&MAX_SUBNET_AVAILABLE_MEMORY),
         compute_allocation_used: 0,
     };
     let execution_state = hypervisor
@@ -102,8 +103,11 @@ where
         MemoryAllocation::try_from(NumBytes::from(0)).unwrap();
 
     // Create call context and callback
-    let call_origin =
-        CallOrigin::CanisterUpdate(canister_test_id(REMOTE_CANISTER_ID), CallbackId::new(0));
+    let call_origin = CallOrigin::CanisterUpdate(
+        canister_test_id(REMOTE_CANISTER_ID),
+        CallbackId::new(0),
+        NO_DEADLINE,
+    );
     let call_context_id = canister_state
         .system_state
         .call_context_manager_mut()
@@ -119,6 +123,7 @@ where
         WasmClosure::new(0, 1),
         WasmClosure::new(0, 1),
         None,
+        NO_DEADLINE,
     );
 
     // Create an Ingress message
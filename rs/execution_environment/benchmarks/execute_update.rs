@@ -346,6 +346,8 @@ pub fn bench_execute_update(c: &mut Criterion) {
                     execution_parameters.instruction_limits.message(),
                 ),
                 execution_complexity: ExecutionComplexity::MAX,
+                subnet_memory_saturation: exec_env
+                    .subnet_memory_saturation(&subnet_available_memory),
                 subnet_available_memory,
                 compute_allocation_used: 0,
             };
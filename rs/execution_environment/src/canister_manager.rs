@@ -1,5 +1,6 @@
 use crate::execution::install_code::{
-    validate_compute_allocation, validate_controller, validate_memory_allocation, OriginalContext,
+    canister_layout, validate_compute_allocation, validate_controller, validate_memory_allocation,
+    OriginalContext,
 };
 use crate::execution::{install::execute_install, upgrade::execute_upgrade};
 use crate::execution_environment::{CompilationCostHandling, RoundContext, RoundLimits};
@@ -11,11 +12,12 @@ use crate::{
 };
 use ic_base_types::NumSeconds;
 use ic_config::flag_status::FlagStatus;
-use ic_cycles_account_manager::CyclesAccountManager;
+use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    CanisterInstallMode, CanisterStatusResultV2, CanisterStatusType, InstallCodeArgs,
-    Method as Ic00Method,
+    CanisterChangeDetails, CanisterChangeOrigin, CanisterInstallMode, CanisterSnapshotResponse,
+    CanisterStatusResultV2, CanisterStatusType, ChunkHash, InstallChunkedCodeArgs, InstallCodeArgs,
+    LogVisibility, Method as Ic00Method, QueryStats,
 };
 use ic_interfaces::execution_environment::{
     CanisterOutOfCyclesError, HypervisorError, IngressHistoryWriter, SubnetAvailableMemory,
@@ -24,10 +26,14 @@ use ic_interfaces::messages::CanisterCall;
 use ic_logger::{error, fatal, info, ReplicaLogger};
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::canister_state::system_state::CyclesUseCase;
+use ic_replicated_state::canister_state::{
+    canister_snapshots::{CanisterSnapshot, SnapshotId, MAX_SNAPSHOTS_PER_CANISTER},
+    system_state::CyclesUseCase,
+    wasm_chunk_store::{WasmChunkHash, MAX_WASM_CHUNKS_PER_CANISTER, MAX_WASM_CHUNK_SIZE},
+};
 use ic_replicated_state::{
-    CallOrigin, CanisterState, CanisterStatus, NetworkTopology, ReplicatedState, SchedulerState,
-    SystemState,
+    CallOrigin, CanisterState, CanisterStatus, Memory, NetworkTopology, ReplicatedState,
+    ReservationError, SchedulerState, SystemState,
 };
 use ic_system_api::ExecutionParameters;
 use ic_types::messages::{MessageId, SignedIngressContent};
@@ -95,6 +101,7 @@ pub(crate) enum StopCanisterResult {
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub(crate) struct CanisterMgrConfig {
     pub(crate) subnet_memory_capacity: NumBytes,
+    pub(crate) subnet_memory_threshold: NumBytes,
     pub(crate) default_provisional_cycles_balance: Cycles,
     pub(crate) default_freeze_threshold: NumSeconds,
     pub(crate) compute_capacity: u64,
@@ -108,6 +115,7 @@ impl CanisterMgrConfig {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         subnet_memory_capacity: NumBytes,
+        subnet_memory_threshold: NumBytes,
         default_provisional_cycles_balance: Cycles,
         default_freeze_threshold: NumSeconds,
         own_subnet_id: SubnetId,
@@ -119,6 +127,7 @@ impl CanisterMgrConfig {
     ) -> Self {
         Self {
             subnet_memory_capacity,
+            subnet_memory_threshold,
             default_provisional_cycles_balance,
             default_freeze_threshold,
             own_subnet_id,
@@ -254,6 +263,25 @@ impl TryFrom<(PrincipalId, InstallCodeArgs)> for InstallCodeContext {
     }
 }
 
+/// Builds the context of an `install_chunked_code` call from its arguments and
+/// the Wasm module assembled from the chunk store.
+impl From<(PrincipalId, InstallChunkedCodeArgs, CanisterModule)> for InstallCodeContext {
+    fn from(input: (PrincipalId, InstallChunkedCodeArgs, CanisterModule)) -> Self {
+        let (sender, args, wasm_module) = input;
+        InstallCodeContext {
+            sender,
+            mode: args.mode,
+            canister_id: args.target_canister_id(),
+            wasm_module,
+            arg: args.arg,
+            compute_allocation: None,
+            memory_allocation: None,
+            // TODO(EXE-294): Query allocations are not supported and should be deleted.
+            query_allocation: QueryAllocation::default(),
+        }
+    }
+}
+
 /// The entity responsible for managing canisters (creation, installing, etc.)
 pub(crate) struct CanisterManager {
     hypervisor: Arc<Hypervisor>,
@@ -319,6 +347,12 @@ impl CanisterManager {
                 format!("Only canisters can call ic00 method {}", method_name),
             )),
 
+            // Canister logs can only be fetched via query calls.
+            Ok(Ic00Method::FetchCanisterLogs) => Err(UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                format!("ic00 method {} can only be called as a query", method_name),
+            )),
+
 
             // These methods are only valid if they are sent by the controller
             // of the canister. We assume that the canister always wants to
@@ -330,7 +364,15 @@ impl CanisterManager {
             | Ok(Ic00Method::DeleteCanister) |
             Ok(Ic00Method::UpdateSettings)|
             Ok(Ic00Method::InstallCode) |
-            Ok(Ic00Method::SetController) => {
+            Ok(Ic00Method::SetController) |
+            Ok(Ic00Method::TakeCanisterSnapshot) |
+            Ok(Ic00Method::LoadCanisterSnapshot) |
+            Ok(Ic00Method::ListCanisterSnapshots) |
+            Ok(Ic00Method::DeleteCanisterSnapshot) |
+            Ok(Ic00Method::UploadChunk) |
+            Ok(Ic00Method::StoredChunks) |
+            Ok(Ic00Method::ClearChunkStore) |
+            Ok(Ic00Method::InstallChunkedCode) => {
                 match effective_canister_id {
                     Some(canister_id) => {
                         let canister = state.canister_state(&canister_id).ok_or_else(|| UserError::new(
@@ -457,6 +499,22 @@ impl CanisterManager {
         if let Some(freezing_threshold) = settings.freezing_threshold {
             canister.system_state.freeze_threshold = freezing_threshold;
         }
+        if let Some(log_visibility) = settings.log_visibility {
+            canister.system_state.log_visibility = log_visibility;
+        }
+        if let Some(wasm_memory_limit) = settings.wasm_memory_limit {
+            // A limit of zero removes the limit.
+            canister.system_state.wasm_memory_limit = if wasm_memory_limit.get() == 0 {
+                None
+            } else {
+                Some(wasm_memory_limit)
+            };
+        }
+        if let Some(reserved_cycles_limit) = settings.reserved_cycles_limit {
+            canister
+                .system_state
+                .set_reserved_balance_limit(Some(reserved_cycles_limit));
+        }
     }
 
     /// Tries to apply the requested settings on the canister identified by
@@ -467,6 +525,7 @@ impl CanisterManager {
         settings: CanisterSettings,
         canister: &mut CanisterState,
         round_limits: &mut RoundLimits,
+        subnet_size: usize,
     ) -> Result<(), CanisterManagerError> {
         // Verify controller.
         validate_controller(canister, &sender)?;
@@ -494,6 +553,20 @@ impl CanisterManager {
             .max(old_usage);
         let old_compute_allocation = canister.scheduler_state.compute_allocation.as_percent();
 
+        if let Some(memory_allocation) = validated_settings.memory_allocation {
+            let requested_mem = memory_allocation.bytes().max(old_usage);
+            if requested_mem > old_mem {
+                self.reserve_cycles_for_memory_allocation(
+                    canister,
+                    memory_allocation,
+                    requested_mem - old_mem,
+                    validated_settings.reserved_cycles_limit,
+                    &round_limits.subnet_memory_saturation,
+                    subnet_size,
+                )?;
+            }
+        }
+
         self.do_update_settings(validated_settings, canister);
 
         let new_compute_allocation = canister.scheduler_state.compute_allocation.as_percent();
@@ -533,6 +606,72 @@ impl CanisterManager {
         Ok(())
     }
 
+    /// Returns how saturated the subnet memory is given the memory that is
+    /// still available on the whole subnet.
+    pub(crate) fn subnet_memory_saturation(
+        &self,
+        subnet_available_memory: &SubnetAvailableMemory,
+    ) -> ResourceSaturation {
+        let subnet_memory_usage = self
+            .config
+            .subnet_memory_capacity
+            .get()
+            .saturating_sub(subnet_available_memory.get_total_memory().max(0) as u64);
+        ResourceSaturation::new(
+            subnet_memory_usage,
+            self.config.subnet_memory_threshold.get(),
+            self.config.subnet_memory_capacity.get(),
+        )
+    }
+
+    /// Moves cycles from the main balance of the canister to its reserved
+    /// balance to pay for the storage of `allocated_bytes` of newly
+    /// allocated memory.
+    ///
+    /// The limit in `reserved_cycles_limit` takes precedence over the current
+    /// limit of the canister because it is about to be applied together with
+    /// the new memory allocation.
+    fn reserve_cycles_for_memory_allocation(
+        &self,
+        canister: &mut CanisterState,
+        memory_allocation: MemoryAllocation,
+        allocated_bytes: NumBytes,
+        reserved_cycles_limit: Option<Cycles>,
+        subnet_memory_saturation: &ResourceSaturation,
+        subnet_size: usize,
+    ) -> Result<(), CanisterManagerError> {
+        let reservation_cycles = self.cycles_account_manager.storage_reservation_cycles(
+            allocated_bytes,
+            subnet_memory_saturation,
+            subnet_size,
+        );
+        let old_limit = canister.system_state.reserved_balance_limit();
+        if let Some(limit) = reserved_cycles_limit {
+            canister
+                .system_state
+                .set_reserved_balance_limit(Some(limit));
+        }
+        let result = canister.system_state.reserve_cycles(reservation_cycles);
+        canister.system_state.set_reserved_balance_limit(old_limit);
+        result.map_err(|err| match err {
+            ReservationError::InsufficientCycles {
+                requested,
+                available,
+            } => CanisterManagerError::InsufficientCyclesInMemoryAllocation {
+                memory_allocation,
+                available,
+                requested,
+            },
+            ReservationError::ReservedLimitExceed { requested, limit } => {
+                CanisterManagerError::ReservedCyclesLimitExceededInMemoryAllocation {
+                    memory_allocation,
+                    requested,
+                    limit,
+                }
+            }
+        })
+    }
+
     /// Creates a new canister and inserts it into `ReplicatedState`.
     ///
     /// Returns the auto-generated id the new canister that has been created.
@@ -749,6 +888,7 @@ impl CanisterManager {
             time,
             compilation_cost_handling,
             subnet_size,
+            subnet_memory_saturation: round_limits.subnet_memory_saturation.clone(),
             requested_compute_allocation: context.compute_allocation,
             requested_memory_allocation: context.memory_allocation,
             sender: context.sender,
@@ -806,6 +946,395 @@ impl CanisterManager {
         Ok(())
     }
 
+    /// Takes a snapshot of the Wasm module, the memories, the exported globals
+    /// and the certified data of the canister.
+    ///
+    /// If `replace_snapshot` is provided, the given snapshot is deleted once
+    /// the new snapshot has been taken. Otherwise the canister must not have
+    /// reached `MAX_SNAPSHOTS_PER_CANISTER`.
+    ///
+    /// The canister is charged for copying the snapshot and the snapshot
+    /// counts against the memory usage of the canister.
+    pub(crate) fn take_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister: &mut CanisterState,
+        replace_snapshot: Option<&[u8]>,
+        time: Time,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+    ) -> Result<(CanisterSnapshotResponse, NumBytes), CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+        let canister_id = canister.canister_id();
+
+        let replace_snapshot = replace_snapshot
+            .map(|bytes| self.validate_snapshot_id(canister, bytes))
+            .transpose()?;
+        if replace_snapshot.is_none()
+            && canister.system_state.canister_snapshots().len() >= MAX_SNAPSHOTS_PER_CANISTER
+        {
+            return Err(CanisterManagerError::CanisterSnapshotLimitExceeded {
+                canister_id,
+                limit: MAX_SNAPSHOTS_PER_CANISTER,
+            });
+        }
+
+        let execution_state = canister.execution_state.as_ref().ok_or(
+            CanisterManagerError::CanisterSnapshotEmptyCanister(canister_id),
+        )?;
+        let (wasm_page_map, wasm_heap_delta) =
+            execution_state.wasm_memory.page_map.copy_to_page_delta();
+        let (stable_page_map, stable_heap_delta) =
+            execution_state.stable_memory.page_map.copy_to_page_delta();
+        let snapshot = CanisterSnapshot::new(
+            time,
+            canister.system_state.canister_version,
+            canister.system_state.certified_data.clone(),
+            execution_state.wasm_binary.binary.clone(),
+            execution_state.exported_globals.clone(),
+            Memory::new(wasm_page_map, execution_state.wasm_memory.size),
+            Memory::new(stable_page_map, execution_state.stable_memory.size),
+        );
+        let heap_delta = wasm_heap_delta + stable_heap_delta;
+        let snapshot_size = snapshot.size();
+
+        // The replaced snapshot is only removed after the new one is taken, so
+        // the canister needs enough memory to hold both for a short while.
+        self.reserve_memory(canister, snapshot_size, round_limits)?;
+
+        let fee = self
+            .cycles_account_manager
+            .canister_snapshot_fee(snapshot_size, subnet_size);
+        let memory_usage = canister.memory_usage(self.config.own_subnet_type);
+        if let Err(err) = self.cycles_account_manager.consume_cycles(
+            &mut canister.system_state,
+            memory_usage,
+            canister.scheduler_state.compute_allocation,
+            fee,
+            subnet_size,
+            CyclesUseCase::Instructions,
+        ) {
+            self.release_memory(canister, snapshot_size, round_limits);
+            return Err(CanisterManagerError::CanisterSnapshotNotEnoughCycles(err));
+        }
+
+        let snapshots = canister.system_state.canister_snapshots_mut();
+        let snapshot_id = snapshots.push(canister_id, snapshot);
+        let replaced = replace_snapshot.and_then(|snapshot_id| snapshots.remove(&snapshot_id));
+        if let Some(replaced) = replaced {
+            self.release_memory(canister, replaced.size(), round_limits);
+        }
+        canister.scheduler_state.heap_delta_debit += heap_delta;
+
+        Ok((
+            CanisterSnapshotResponse::new(
+                snapshot_id.to_vec(),
+                time.as_nanos_since_unix_epoch(),
+                snapshot_size.get(),
+            ),
+            heap_delta,
+        ))
+    }
+
+    /// Replaces the Wasm module, the memories, the exported globals and the
+    /// certified data of the canister with the ones stored in the given
+    /// snapshot. The snapshot itself is kept and can be loaded again.
+    ///
+    /// The canister must be stopped, so that no call contexts or callbacks
+    /// refer to the replaced code. The load is recorded in the canister
+    /// history.
+    ///
+    /// Returns the heap delta created by copying the memories of the snapshot.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn load_canister_snapshot(
+        &self,
+        origin: CanisterChangeOrigin,
+        canister: &mut CanisterState,
+        snapshot_id: &[u8],
+        time: Time,
+        canister_layout_path: PathBuf,
+        round_limits: &mut RoundLimits,
+        compilation_cost_handling: CompilationCostHandling,
+        subnet_size: usize,
+    ) -> Result<NumBytes, CanisterManagerError> {
+        validate_controller(canister, &origin.origin())?;
+        let canister_id = canister.canister_id();
+        if canister.status() != CanisterStatusType::Stopped {
+            return Err(CanisterManagerError::LoadCanisterSnapshotNotStopped(
+                canister_id,
+            ));
+        }
+        let snapshot_id = self.validate_snapshot_id(canister, snapshot_id)?;
+        let snapshot = canister
+            .system_state
+            .canister_snapshots()
+            .get(&snapshot_id)
+            .ok_or(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id,
+            })?;
+
+        // The new execution state keeps the canister root of the replaced one,
+        // which is only missing if the canister has no code installed.
+        let canister_root = match &canister.execution_state {
+            Some(execution_state) => execution_state.canister_root.clone(),
+            None => canister_layout(&canister_layout_path, &canister_id).raw_path(),
+        };
+        // The compilation cost is deducted from the round limits by the
+        // hypervisor.
+        let (_compilation_cost, result) = self.hypervisor.create_execution_state(
+            snapshot.wasm_binary().clone(),
+            canister_root,
+            canister_id,
+            round_limits,
+            compilation_cost_handling,
+        );
+        let mut execution_state =
+            result.map_err(|err| CanisterManagerError::Hypervisor(canister_id, err))?;
+        let (wasm_page_map, wasm_heap_delta) = snapshot.wasm_memory().page_map.copy_to_page_delta();
+        let (stable_page_map, stable_heap_delta) =
+            snapshot.stable_memory().page_map.copy_to_page_delta();
+        execution_state.wasm_memory = Memory::new(wasm_page_map, snapshot.wasm_memory().size);
+        execution_state.stable_memory = Memory::new(stable_page_map, snapshot.stable_memory().size);
+        execution_state.exported_globals = snapshot.exported_globals().clone();
+        let heap_delta = wasm_heap_delta + stable_heap_delta;
+        let certified_data = snapshot.certified_data().clone();
+        let snapshot_size = snapshot.size();
+        let change_details = CanisterChangeDetails::load_snapshot(
+            snapshot.canister_version(),
+            snapshot_id.to_vec(),
+            snapshot.taken_at_timestamp().as_nanos_since_unix_epoch(),
+        );
+
+        let old_usage = canister.memory_usage(self.config.own_subnet_type);
+        let old_execution_usage = canister
+            .execution_state
+            .as_ref()
+            .map_or(NumBytes::from(0), |es| es.memory_usage());
+        let new_usage = old_usage - old_execution_usage + execution_state.memory_usage();
+        if new_usage > old_usage {
+            self.reserve_memory(canister, new_usage - old_usage, round_limits)?;
+        }
+
+        let fee = self
+            .cycles_account_manager
+            .canister_snapshot_fee(snapshot_size, subnet_size);
+        if let Err(err) = self.cycles_account_manager.consume_cycles(
+            &mut canister.system_state,
+            new_usage,
+            canister.scheduler_state.compute_allocation,
+            fee,
+            subnet_size,
+            CyclesUseCase::Instructions,
+        ) {
+            if new_usage > old_usage {
+                self.release_memory(canister, new_usage - old_usage, round_limits);
+            }
+            return Err(CanisterManagerError::CanisterSnapshotNotEnoughCycles(err));
+        }
+        if new_usage < old_usage {
+            self.release_memory(canister, old_usage - new_usage, round_limits);
+        }
+
+        canister.execution_state = Some(execution_state);
+        canister.system_state.certified_data = certified_data;
+        canister.system_state.canister_version += 1;
+        canister
+            .system_state
+            .add_canister_change(time, origin, change_details);
+        canister.scheduler_state.heap_delta_debit += heap_delta;
+
+        Ok(heap_delta)
+    }
+
+    /// Returns the snapshots of the canister.
+    pub(crate) fn list_canister_snapshots(
+        &self,
+        sender: PrincipalId,
+        canister: &CanisterState,
+    ) -> Result<Vec<CanisterSnapshotResponse>, CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+
+        Ok(canister
+            .system_state
+            .canister_snapshots()
+            .iter()
+            .map(|(snapshot_id, snapshot)| {
+                CanisterSnapshotResponse::new(
+                    snapshot_id.to_vec(),
+                    snapshot.taken_at_timestamp().as_nanos_since_unix_epoch(),
+                    snapshot.size().get(),
+                )
+            })
+            .collect())
+    }
+
+    /// Deletes a snapshot of the canister and releases the memory it took.
+    pub(crate) fn delete_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister: &mut CanisterState,
+        snapshot_id: &[u8],
+        round_limits: &mut RoundLimits,
+    ) -> Result<(), CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+        let canister_id = canister.canister_id();
+        let snapshot_id = self.validate_snapshot_id(canister, snapshot_id)?;
+
+        let snapshot = canister
+            .system_state
+            .canister_snapshots_mut()
+            .remove(&snapshot_id)
+            .ok_or(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id,
+            })?;
+        self.release_memory(canister, snapshot.size(), round_limits);
+        Ok(())
+    }
+
+    /// Adds a chunk to the Wasm chunk store of the canister and returns the
+    /// hash of the chunk.
+    ///
+    /// The canister is charged for hashing the chunk. Uploading a chunk that
+    /// is already in the store does not take any additional memory.
+    pub(crate) fn upload_chunk(
+        &self,
+        sender: PrincipalId,
+        canister: &mut CanisterState,
+        chunk: Vec<u8>,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+    ) -> Result<WasmChunkHash, CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+        let canister_id = canister.canister_id();
+
+        if chunk.len() > MAX_WASM_CHUNK_SIZE {
+            return Err(CanisterManagerError::WasmChunkStoreError {
+                message: format!(
+                    "Chunk of {} bytes exceeds the maximum chunk size of {} bytes.",
+                    chunk.len(),
+                    MAX_WASM_CHUNK_SIZE
+                ),
+            });
+        }
+        let chunk = CanisterModule::new(chunk);
+        let hash = chunk.module_hash();
+        let chunk_size = NumBytes::from(chunk.len() as u64);
+
+        let is_new_chunk = !canister.system_state.wasm_chunk_store().contains(&hash);
+        if is_new_chunk {
+            if canister.system_state.wasm_chunk_store().len() >= MAX_WASM_CHUNKS_PER_CANISTER {
+                return Err(CanisterManagerError::WasmChunkStoreError {
+                    message: format!(
+                        "Canister {} has reached the maximum number of {} chunks.",
+                        canister_id, MAX_WASM_CHUNKS_PER_CANISTER
+                    ),
+                });
+            }
+            self.reserve_memory(canister, chunk_size, round_limits)?;
+        }
+
+        let fee = self
+            .cycles_account_manager
+            .upload_chunk_fee(chunk_size, subnet_size);
+        let memory_usage = canister.memory_usage(self.config.own_subnet_type);
+        if let Err(err) = self.cycles_account_manager.consume_cycles(
+            &mut canister.system_state,
+            memory_usage,
+            canister.scheduler_state.compute_allocation,
+            fee,
+            subnet_size,
+            CyclesUseCase::Instructions,
+        ) {
+            if is_new_chunk {
+                self.release_memory(canister, chunk_size, round_limits);
+            }
+            return Err(CanisterManagerError::WasmChunkStoreNotEnoughCycles(err));
+        }
+
+        canister.system_state.wasm_chunk_store_mut().insert(chunk);
+        Ok(hash)
+    }
+
+    /// Returns the hashes of all chunks in the Wasm chunk store of the
+    /// canister.
+    pub(crate) fn stored_chunks(
+        &self,
+        sender: PrincipalId,
+        canister: &CanisterState,
+    ) -> Result<Vec<WasmChunkHash>, CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+
+        Ok(canister
+            .system_state
+            .wasm_chunk_store()
+            .keys()
+            .cloned()
+            .collect())
+    }
+
+    /// Removes all chunks from the Wasm chunk store of the canister and
+    /// releases the memory they took.
+    pub(crate) fn clear_chunk_store(
+        &self,
+        sender: PrincipalId,
+        canister: &mut CanisterState,
+        round_limits: &mut RoundLimits,
+    ) -> Result<(), CanisterManagerError> {
+        validate_controller(canister, &sender)?;
+
+        let memory_usage = canister.system_state.wasm_chunk_store().memory_usage();
+        canister.system_state.wasm_chunk_store_mut().clear();
+        self.release_memory(canister, memory_usage, round_limits);
+        Ok(())
+    }
+
+    /// Puts together the Wasm module of an `install_chunked_code` call from
+    /// the chunk store of the store canister and checks that its hash matches
+    /// `wasm_module_hash`.
+    ///
+    /// The sender must control the store canister. Whether it also controls
+    /// the target canister is checked later by the regular `install_code`
+    /// path.
+    pub(crate) fn assemble_chunked_wasm(
+        &self,
+        sender: PrincipalId,
+        args: &InstallChunkedCodeArgs,
+        state: &ReplicatedState,
+    ) -> Result<CanisterModule, CanisterManagerError> {
+        let store_canister_id = args.store_canister_id();
+        let store_canister = state
+            .canister_state(&store_canister_id)
+            .ok_or(CanisterManagerError::CanisterNotFound(store_canister_id))?;
+        validate_controller(store_canister, &sender)?;
+
+        let store = store_canister.system_state.wasm_chunk_store();
+        let mut wasm_module = Vec::new();
+        for ChunkHash { hash } in args.chunk_hashes_list.iter() {
+            let chunk = WasmChunkHash::try_from(hash.as_slice())
+                .ok()
+                .and_then(|hash| store.get(&hash))
+                .ok_or_else(|| CanisterManagerError::WasmChunkStoreError {
+                    message: format!(
+                        "Chunk {} is not in the chunk store of canister {}.",
+                        hex::encode(hash),
+                        store_canister_id
+                    ),
+                })?;
+            wasm_module.extend_from_slice(chunk.as_slice());
+        }
+
+        let wasm_module = CanisterModule::new(wasm_module);
+        if wasm_module.module_hash()[..] != args.wasm_module_hash[..] {
+            return Err(CanisterManagerError::WasmModuleHashMismatch {
+                expected: args.wasm_module_hash.clone(),
+                actual: wasm_module.module_hash(),
+            });
+        }
+        Ok(wasm_module)
+    }
+
     /// Signals a canister to stop.
     ///
     /// If the canister is running, then the canister is marked as "stopping".
@@ -941,6 +1470,13 @@ impl CanisterManager {
         let compute_allocation = canister.scheduler_state.compute_allocation;
         let memory_allocation = canister.memory_allocation();
         let freeze_threshold = canister.system_state.freeze_threshold;
+        let total_query_stats = &canister.scheduler_state.total_query_stats;
+        let query_stats = QueryStats {
+            num_calls_total: candid::Nat::from(total_query_stats.num_calls),
+            num_instructions_total: candid::Nat::from(total_query_stats.num_instructions),
+            request_payload_bytes_total: candid::Nat::from(total_query_stats.ingress_payload_size),
+            response_payload_bytes_total: candid::Nat::from(total_query_stats.egress_payload_size),
+        };
 
         Ok(CanisterStatusResultV2::new(
             canister.status(),
@@ -963,6 +1499,16 @@ impl CanisterManager {
                     subnet_size,
                 )
                 .get(),
+            query_stats,
+            canister
+                .system_state
+                .wasm_memory_limit
+                .map(|limit| limit.get()),
+            canister.system_state.reserved_balance().get(),
+            canister
+                .system_state
+                .reserved_balance_limit()
+                .map(|limit| limit.get()),
         ))
     }
 
@@ -975,6 +1521,7 @@ impl CanisterManager {
         new_controller: PrincipalId,
         state: &mut ReplicatedState,
         round_limits: &mut RoundLimits,
+        subnet_size: usize,
     ) -> Result<(), CanisterManagerError> {
         let canister = state
             .canister_state_mut(&canister_id)
@@ -983,7 +1530,7 @@ impl CanisterManager {
         let settings = CanisterSettingsBuilder::new()
             .with_controller(new_controller)
             .build();
-        self.update_settings(sender, settings, canister, round_limits)
+        self.update_settings(sender, settings, canister, round_limits, subnet_size)
     }
 
     /// Permanently deletes a canister from `ReplicatedState`.
@@ -1024,7 +1571,7 @@ impl CanisterManager {
         }
 
         // When a canister is deleted:
-        // - its state is permanently deleted, and
+        // - its state, including its snapshots, is permanently deleted, and
         // - its cycles are discarded.
 
         // Take out the canister from `ReplicatedState`.
@@ -1281,6 +1828,92 @@ impl CanisterManager {
         Ok(canister_id)
     }
 
+    /// Parses the given snapshot id and checks that it belongs to the canister
+    /// and refers to an existing snapshot.
+    fn validate_snapshot_id(
+        &self,
+        canister: &CanisterState,
+        snapshot_id: &[u8],
+    ) -> Result<SnapshotId, CanisterManagerError> {
+        let canister_id = canister.canister_id();
+        let snapshot_id = SnapshotId::try_from(snapshot_id)
+            .map_err(|message| CanisterManagerError::InvalidSnapshotId { message })?;
+        if snapshot_id.canister_id() != canister_id
+            || !canister
+                .system_state
+                .canister_snapshots()
+                .contains(&snapshot_id)
+        {
+            return Err(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id,
+            });
+        }
+        Ok(snapshot_id)
+    }
+
+    /// Checks that the canister can grow its memory usage by `requested`
+    /// bytes and reserves them from the available memory of the subnet.
+    fn reserve_memory(
+        &self,
+        canister: &CanisterState,
+        requested: NumBytes,
+        round_limits: &mut RoundLimits,
+    ) -> Result<(), CanisterManagerError> {
+        let memory_usage_needed = canister.memory_usage(self.config.own_subnet_type) + requested;
+        match canister.memory_allocation() {
+            MemoryAllocation::Reserved(allocation) => {
+                if memory_usage_needed > allocation {
+                    return Err(CanisterManagerError::NotEnoughMemoryAllocationGiven {
+                        canister_id: canister.canister_id(),
+                        memory_allocation_given: canister.memory_allocation(),
+                        memory_usage_needed,
+                    });
+                }
+                // The memory is already reserved by the memory allocation.
+                Ok(())
+            }
+            MemoryAllocation::BestEffort => round_limits
+                .subnet_available_memory
+                .try_decrement(requested, NumBytes::from(0), NumBytes::from(0))
+                .map_err(
+                    |_| CanisterManagerError::SubnetMemoryCapacityOverSubscribed {
+                        requested_total: requested,
+                        requested_wasm_custom_sections: NumBytes::from(0),
+                        available_total: NumBytes::from(
+                            round_limits
+                                .subnet_available_memory
+                                .get_total_memory()
+                                .max(0) as u64,
+                        ),
+                        available_wasm_custom_sections: NumBytes::from(
+                            round_limits
+                                .subnet_available_memory
+                                .get_wasm_custom_sections_memory()
+                                .max(0) as u64,
+                        ),
+                    },
+                ),
+        }
+    }
+
+    /// Returns memory that is no longer used by the canister to the subnet.
+    /// It is the counterpart of `reserve_memory()`.
+    fn release_memory(
+        &self,
+        canister: &CanisterState,
+        released: NumBytes,
+        round_limits: &mut RoundLimits,
+    ) {
+        if canister.memory_allocation() == MemoryAllocation::BestEffort {
+            round_limits.subnet_available_memory.increment(
+                released,
+                NumBytes::from(0),
+                NumBytes::from(0),
+            );
+        }
+    }
+
     fn validate_canister_exists<'a>(
         &self,
         state: &'a ReplicatedState,
@@ -1341,6 +1974,38 @@ pub(crate) enum CanisterManagerError {
     CanisterNotHostedBySubnet {
         message: String,
     },
+    CanisterSnapshotNotFound {
+        canister_id: CanisterId,
+        snapshot_id: SnapshotId,
+    },
+    CanisterSnapshotLimitExceeded {
+        canister_id: CanisterId,
+        limit: usize,
+    },
+    CanisterSnapshotEmptyCanister(CanisterId),
+    CanisterSnapshotNotEnoughCycles(CanisterOutOfCyclesError),
+    LoadCanisterSnapshotNotStopped(CanisterId),
+    InvalidSnapshotId {
+        message: String,
+    },
+    WasmChunkStoreError {
+        message: String,
+    },
+    WasmChunkStoreNotEnoughCycles(CanisterOutOfCyclesError),
+    WasmModuleHashMismatch {
+        expected: Vec<u8>,
+        actual: [u8; 32],
+    },
+    InsufficientCyclesInMemoryAllocation {
+        memory_allocation: MemoryAllocation,
+        available: Cycles,
+        requested: Cycles,
+    },
+    ReservedCyclesLimitExceededInMemoryAllocation {
+        memory_allocation: MemoryAllocation,
+        requested: Cycles,
+        limit: Cycles,
+    },
 }
 
 impl From<CanisterManagerError> for UserError {
@@ -1499,6 +2164,81 @@ impl From<CanisterManagerError> for UserError {
                     format!("Unsuccessful validation of specified ID: {}", message),
                 )
             }
+            CanisterSnapshotNotFound { canister_id, snapshot_id } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!("Could not find the snapshot ID {} for canister {}.", hex::encode(snapshot_id.to_vec()), canister_id),
+                )
+            }
+            CanisterSnapshotLimitExceeded { canister_id, limit } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!("Canister {} has reached the maximum number of {} snapshots. Delete or replace an existing snapshot to take a new one.", canister_id, limit),
+                )
+            }
+            CanisterSnapshotEmptyCanister(canister_id) => {
+                Self::new(
+                    ErrorCode::CanisterWasmModuleNotFound,
+                    format!("Cannot take a snapshot of canister {} because it has no Wasm module installed.", canister_id),
+                )
+            }
+            CanisterSnapshotNotEnoughCycles(err) => {
+                Self::new(
+                    ErrorCode::CanisterOutOfCycles,
+                    format!("Canister snapshot operation failed with `{}`", err),
+                )
+            }
+            LoadCanisterSnapshotNotStopped(canister_id) => {
+                Self::new(
+                    ErrorCode::CanisterNotStopped,
+                    format!(
+                        "Canister {} must be stopped before a snapshot is loaded.",
+                        canister_id,
+                    )
+                )
+            }
+            InvalidSnapshotId { message } => {
+                Self::new(
+                    ErrorCode::InvalidManagementPayload,
+                    format!("Invalid snapshot ID: {}", message),
+                )
+            }
+            WasmChunkStoreError { message } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!("Error from Wasm chunk store: {}", message),
+                )
+            }
+            WasmChunkStoreNotEnoughCycles(err) => {
+                Self::new(
+                    ErrorCode::CanisterOutOfCycles,
+                    format!("Wasm chunk store operation failed with `{}`", err),
+                )
+            }
+            WasmModuleHashMismatch { expected, actual } => {
+                Self::new(
+                    ErrorCode::InvalidManagementPayload,
+                    format!("Wasm module hash {} does not match the hash {} of the module assembled from the given chunks.", hex::encode(expected), hex::encode(actual)),
+                )
+            }
+            InsufficientCyclesInMemoryAllocation { memory_allocation, available, requested } => {
+                Self::new(
+                    ErrorCode::CanisterOutOfCycles,
+                    format!(
+                        "Cannot increase memory allocation to {} due to insufficient cycles. At least {} additional cycles are required to reserve storage, but only {} are available.",
+                        memory_allocation, requested, available,
+                    ),
+                )
+            }
+            ReservedCyclesLimitExceededInMemoryAllocation { memory_allocation, requested, limit } => {
+                Self::new(
+                    ErrorCode::ReservedCyclesLimitExceededInMemoryAllocation,
+                    format!(
+                        "Cannot increase memory allocation to {} due to its reserved cycles limit. The current limit ({}) would be exceeded by {}.",
+                        memory_allocation, limit, requested - limit,
+                    ),
+                )
+            }
         }
     }
 }
@@ -1533,6 +2273,12 @@ pub fn uninstall_canister(
     // Drop its certified data.
     canister.system_state.certified_data = Vec::new();
 
+    // Drop its uploaded Wasm chunks.
+    canister.system_state.wasm_chunk_store_mut().clear();
+
+    // Drop its log records.
+    canister.system_state.canister_log.clear();
+
     // Deactivate global timer.
     canister.system_state.global_timer = CanisterTimer::Inactive;
     // Increment canister version.
@@ -1569,7 +2315,7 @@ pub fn uninstall_canister(
                         },
                     }));
                 }
-                CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
+                CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
                     rejects.push(Response::Canister(CanisterResponse {
                         originator: *caller_canister_id,
                         respondent: canister_id,
@@ -1579,6 +2325,7 @@ pub fn uninstall_canister(
                             code: RejectCode::CanisterReject,
                             message: String::from("Canister has been uninstalled."),
                         }),
+                        deadline: *deadline,
                     }));
                 }
                 CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => fatal!(
@@ -1604,6 +2351,9 @@ struct ValidatedCanisterSettings {
     pub compute_allocation: Option<ComputeAllocation>,
     pub memory_allocation: Option<MemoryAllocation>,
     pub freezing_threshold: Option<NumSeconds>,
+    pub log_visibility: Option<LogVisibility>,
+    pub wasm_memory_limit: Option<NumBytes>,
+    pub reserved_cycles_limit: Option<Cycles>,
 }
 
 impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
@@ -1639,6 +2389,9 @@ impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
             compute_allocation: settings.compute_allocation(),
             memory_allocation: settings.memory_allocation(),
             freezing_threshold: settings.freezing_threshold(),
+            log_visibility: settings.log_visibility(),
+            wasm_memory_limit: settings.wasm_memory_limit(),
+            reserved_cycles_limit: settings.reserved_cycles_limit(),
         })
     }
 }
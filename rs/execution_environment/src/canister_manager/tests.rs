@@ -17,11 +17,12 @@ use ic_config::{
     execution_environment::Config, flag_status::FlagStatus, subnet_config::SchedulerConfig,
 };
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
-use ic_cycles_account_manager::CyclesAccountManager;
+use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, UserError};
 use ic_ic00_types::{
-    CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgsBuilder, CanisterStatusType,
-    CreateCanisterArgs, EmptyBlob, InstallCodeArgs, Method, Payload, UpdateSettingsArgs,
+    CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgsBuilder, CanisterSnapshotResponse,
+    CanisterStatusResultV2, CanisterStatusType, CreateCanisterArgs, EmptyBlob, InstallCodeArgs,
+    LoadCanisterSnapshotArgs, Method, Payload, TakeCanisterSnapshotArgs, UpdateSettingsArgs,
 };
 use ic_interfaces::{
     execution_environment::{
@@ -95,6 +96,8 @@ const MINIMAL_WASM: [u8; 8] = [
 lazy_static! {
     static ref MAX_SUBNET_AVAILABLE_MEMORY: SubnetAvailableMemory =
         SubnetAvailableMemory::new(i64::MAX / 2, i64::MAX / 2, i64::MAX / 2);
+    static ref SUBNET_MEMORY_SATURATION: ResourceSaturation =
+        ResourceSaturation::new(0, MEMORY_CAPACITY.get(), MEMORY_CAPACITY.get());
     static ref INITIAL_CYCLES: Cycles =
         CANISTER_FREEZE_BALANCE_RESERVE + Cycles::new(5_000_000_000_000);
     static ref EXECUTION_PARAMETERS: ExecutionParameters = ExecutionParameters {
@@ -245,6 +248,7 @@ fn canister_manager_config(
     rate_limiting_of_instructions: FlagStatus,
 ) -> CanisterMgrConfig {
     CanisterMgrConfig::new(
+        MEMORY_CAPACITY,
         MEMORY_CAPACITY,
         DEFAULT_PROVISIONAL_BALANCE,
         NumSeconds::from(100_000),
@@ -350,6 +354,7 @@ fn install_canister_makes_subnet_oversubscribed() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used,
         };
         let canister_id1 = canister_manager
@@ -455,6 +460,7 @@ fn upgrade_non_existing_canister_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_test_id(0);
@@ -488,6 +494,7 @@ fn upgrade_canister_with_no_wasm_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_manager
@@ -539,6 +546,7 @@ fn can_update_compute_allocation_during_upgrade() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id1 = canister_manager
@@ -609,6 +617,7 @@ fn upgrading_canister_makes_subnet_oversubscribed() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let initial_cycles = Cycles::new(30_000_000_000_000);
@@ -813,6 +822,7 @@ fn can_update_memory_allocation_during_upgrade() {
                 MEMORY_CAPACITY.get() as i64,
                 MEMORY_CAPACITY.get() as i64,
             ),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_manager
@@ -884,6 +894,7 @@ fn install_code_preserves_messages() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = 0;
@@ -948,6 +959,7 @@ fn can_create_canister() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         assert_eq!(
@@ -995,6 +1007,7 @@ fn create_canister_fails_if_not_enough_cycles_are_sent_with_the_request() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
 
@@ -1032,6 +1045,7 @@ fn can_create_canister_with_extra_cycles() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         assert_eq!(
@@ -1063,6 +1077,7 @@ fn cannot_install_non_empty_canister() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_manager
@@ -1127,6 +1142,7 @@ fn install_code_with_wrong_controller_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         // Create a canister with canister_test_id 1 as controller.
@@ -1185,6 +1201,7 @@ fn create_canister_sets_correct_allocations() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
 
@@ -1222,6 +1239,7 @@ fn create_canister_updates_consumed_cycles_metric_correctly() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_manager
@@ -1273,6 +1291,7 @@ fn provisional_create_canister_has_no_creation_fee() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_manager
@@ -1316,6 +1335,7 @@ fn reinstall_on_empty_canister_succeeds() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(42).get();
@@ -1418,6 +1438,7 @@ fn install_puts_canister_back_after_invalid_wasm() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         // Use an invalid wasm code (import memory from an invalid module).
@@ -1479,6 +1500,7 @@ fn reinstall_clears_stable_memory() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(42).get();
@@ -1573,6 +1595,7 @@ fn stop_a_running_canister() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1);
@@ -1705,6 +1728,7 @@ fn stop_a_canister_with_incorrect_controller() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let msg_id = message_test_id(0);
@@ -1780,6 +1804,7 @@ fn start_a_canister_with_incorrect_controller() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -1819,6 +1844,7 @@ fn starting_an_already_running_canister_keeps_it_running() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(42).get();
@@ -1927,6 +1953,7 @@ fn get_canister_status_with_incorrect_controller() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -1966,6 +1993,7 @@ fn get_canister_status_of_running_canister() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2000,6 +2028,7 @@ fn get_canister_status_of_self() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2068,6 +2097,7 @@ fn set_controller_with_incorrect_controller() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_test_id(0);
@@ -2087,6 +2117,7 @@ fn set_controller_with_incorrect_controller() {
                 new_controller,
                 &mut state,
                 &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
             ),
             Err(CanisterManagerError::CanisterInvalidController {
                 canister_id,
@@ -2114,6 +2145,7 @@ fn set_controller_with_correct_controller() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let canister_id = canister_test_id(0);
@@ -2130,7 +2162,8 @@ fn set_controller_with_correct_controller() {
                 canister_id,
                 new_controller,
                 &mut state,
-                &mut round_limits
+                &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
             )
             .is_ok());
 
@@ -2326,6 +2359,7 @@ fn install_canister_with_query_allocation() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2399,6 +2433,7 @@ fn create_canister_with_cycles_sender_in_whitelist() {
         instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let sender = canister_test_id(1).get();
@@ -2434,6 +2469,7 @@ fn create_canister_with_specified_id(
         instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
 
@@ -2566,6 +2602,7 @@ fn installing_a_canister_with_not_enough_memory_allocation_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2660,6 +2697,7 @@ fn upgrading_canister_with_not_enough_memory_allocation_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2785,6 +2823,7 @@ fn installing_a_canister_with_not_enough_cycles_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -2896,6 +2935,7 @@ fn failed_upgrade_hooks_consume_instructions() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(100).get();
@@ -2936,6 +2976,7 @@ fn failed_upgrade_hooks_consume_instructions() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let compilation_cost = wasm_compilation_cost(&upgrade_wasm);
@@ -3040,6 +3081,7 @@ fn failed_install_hooks_consume_instructions() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(100).get();
@@ -3125,6 +3167,7 @@ fn install_code_respects_instruction_limit() {
         instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let sender = canister_test_id(100).get();
@@ -3174,6 +3217,7 @@ fn install_code_respects_instruction_limit() {
         instructions: as_round_instructions(NumInstructions::from(3)),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let (instructions_left, result, canister) = install_code(
@@ -3206,6 +3250,7 @@ fn install_code_respects_instruction_limit() {
         instructions: as_round_instructions(NumInstructions::from(5) + compilation_cost),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let (instructions_left, result, canister) = install_code(
@@ -3232,6 +3277,7 @@ fn install_code_respects_instruction_limit() {
         instructions: as_round_instructions(NumInstructions::from(5)),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let (instructions_left, result, canister) = install_code(
@@ -3264,6 +3310,7 @@ fn install_code_respects_instruction_limit() {
         instructions: as_round_instructions(NumInstructions::from(10) + compilation_cost),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
     let (instructions_left, result, _) = install_code(
@@ -3324,6 +3371,7 @@ fn install_code_preserves_system_state_and_scheduler_state() {
         instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
         execution_complexity: ExecutionComplexity::MAX,
         subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+        subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
         compute_allocation_used: state.total_compute_allocation(),
     };
 
@@ -3481,6 +3529,7 @@ fn lower_memory_allocation_than_usage_fails() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let wasm = r#"
@@ -3529,7 +3578,13 @@ fn lower_memory_allocation_than_usage_fails() {
         let canister = state.canister_state_mut(&canister_id).unwrap();
 
         assert_matches!(
-            canister_manager.update_settings(sender, settings, canister, &mut round_limits,),
+            canister_manager.update_settings(
+                sender,
+                settings,
+                canister,
+                &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
+            ),
             Err(CanisterManagerError::NotEnoughMemoryAllocationGiven { .. })
         );
     })
@@ -3542,6 +3597,7 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
@@ -3597,7 +3653,13 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
         let canister = state.canister_state_mut(&canister_id).unwrap();
 
         canister_manager
-            .update_settings(sender, settings, canister, &mut round_limits)
+            .update_settings(
+                sender,
+                settings,
+                canister,
+                &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
+            )
             .unwrap();
 
         install_code(
@@ -3627,6 +3689,7 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(100).get();
@@ -3713,7 +3776,13 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
         let canister = state.canister_state_mut(&canister_id).unwrap();
 
         canister_manager
-            .update_settings(sender, settings, canister, &mut round_limits)
+            .update_settings(
+                sender,
+                settings,
+                canister,
+                &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
+            )
             .unwrap();
 
         install_code(
@@ -3776,6 +3845,50 @@ fn uninstall_code_can_be_invoked_by_governance_canister() {
     );
 }
 
+#[test]
+fn uninstall_code_keeps_canister_snapshots() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+    let write = wasm()
+        .stable_grow(1)
+        .stable_write(0, b"before")
+        .reply()
+        .build();
+    test.ingress(canister_id, "update", write).unwrap();
+
+    let args = TakeCanisterSnapshotArgs::new(canister_id, None);
+    let result = test
+        .subnet_message(Method::TakeCanisterSnapshot, args.encode())
+        .unwrap();
+    let snapshot_id = Decode!(&get_reply(result), CanisterSnapshotResponse)
+        .unwrap()
+        .id;
+
+    test.uninstall_code(canister_id).unwrap();
+    assert!(test.canister_state(canister_id).execution_state.is_none());
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .canister_snapshots()
+            .len(),
+        1
+    );
+
+    // The snapshot can still be loaded to restore the uninstalled code.
+    test.stop_canister(canister_id);
+    test.process_stopping_canisters();
+    let args = LoadCanisterSnapshotArgs::new(canister_id, snapshot_id, None);
+    let result = test
+        .subnet_message(Method::LoadCanisterSnapshot, args.encode())
+        .unwrap();
+    assert_eq!(WasmResult::Reply(EmptyBlob.encode()), result);
+    test.start_canister(canister_id).unwrap();
+
+    let read = wasm().stable_read(0, 6).append_and_reply().build();
+    let result = test.ingress(canister_id, "query", read).unwrap();
+    assert_eq!(WasmResult::Reply(b"before".to_vec()), result);
+}
+
 #[test]
 fn test_install_when_setting_memory_allocation_to_zero() {
     with_setup(|canister_manager, mut state, subnet_id| {
@@ -3783,6 +3896,7 @@ fn test_install_when_setting_memory_allocation_to_zero() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
@@ -3817,6 +3931,7 @@ fn test_install_when_setting_memory_allocation_to_zero() {
                 canister,
                 //memory_allocation_used,
                 &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
             )
             .unwrap();
 
@@ -3847,6 +3962,7 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
@@ -3897,7 +4013,13 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
         let canister = state.canister_state_mut(&canister_id).unwrap();
 
         canister_manager
-            .update_settings(sender, settings, canister, &mut round_limits)
+            .update_settings(
+                sender,
+                settings,
+                canister,
+                &mut round_limits,
+                SMALL_APP_SUBNET_MAX_SIZE,
+            )
             .unwrap();
 
         install_code(
@@ -3927,6 +4049,7 @@ fn max_number_of_canisters_is_respected_when_creating_canisters() {
             instructions: as_round_instructions(EXECUTION_PARAMETERS.instruction_limits.message()),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: (*MAX_SUBNET_AVAILABLE_MEMORY),
+            subnet_memory_saturation: SUBNET_MEMORY_SATURATION.clone(),
             compute_allocation_used: state.total_compute_allocation(),
         };
         let sender = canister_test_id(1).get();
@@ -4476,6 +4599,72 @@ fn update_settings_makes_subnet_oversubscribed() {
     assert_eq!(ErrorCode::SubnetOversubscribed, err.code());
 }
 
+#[test]
+fn update_settings_reserves_cycles_for_memory_allocation_above_threshold() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_total_memory(100 * 1024 * 1024) // 100 MiB
+        .with_subnet_memory_threshold(10 * 1024 * 1024) // 10 MiB
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000_000));
+
+    // Stay below the threshold: nothing is reserved.
+    test.canister_update_allocations_settings(canister_id, None, Some(5 * 1024 * 1024))
+        .unwrap();
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .reserved_balance(),
+        Cycles::zero()
+    );
+
+    // Go above the threshold.
+    let balance_before = test.canister_state(canister_id).system_state.balance();
+    test.canister_update_allocations_settings(canister_id, None, Some(50 * 1024 * 1024))
+        .unwrap();
+    let reserved_balance = test
+        .canister_state(canister_id)
+        .system_state
+        .reserved_balance();
+    assert!(reserved_balance > Cycles::zero());
+    assert_eq!(
+        test.canister_state(canister_id).system_state.balance() + reserved_balance,
+        balance_before
+    );
+
+    let result = test.canister_status(canister_id).unwrap();
+    let status = match result {
+        WasmResult::Reply(bytes) => CanisterStatusResultV2::decode(&bytes).unwrap(),
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    };
+    assert_eq!(status.reserved_cycles(), reserved_balance.get());
+}
+
+#[test]
+fn update_settings_fails_when_reserved_cycles_limit_is_exceeded() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_total_memory(100 * 1024 * 1024) // 100 MiB
+        .with_subnet_memory_threshold(10 * 1024 * 1024) // 10 MiB
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000_000));
+    test.canister_update_reserved_cycles_limit(canister_id, Cycles::new(1))
+        .unwrap();
+
+    let err = test
+        .canister_update_allocations_settings(canister_id, None, Some(50 * 1024 * 1024))
+        .unwrap_err();
+    assert_eq!(
+        ErrorCode::ReservedCyclesLimitExceededInMemoryAllocation,
+        err.code()
+    );
+    let canister = test.canister_state(canister_id);
+    assert_eq!(canister.system_state.reserved_balance(), Cycles::zero());
+    assert_eq!(canister.memory_allocation(), MemoryAllocation::BestEffort);
+    assert_eq!(
+        canister.system_state.reserved_balance_limit(),
+        Some(Cycles::new(1))
+    );
+}
+
 #[test]
 fn create_canister_when_compute_capacity_is_oversubscribed() {
     let mut test = ExecutionTestBuilder::new()
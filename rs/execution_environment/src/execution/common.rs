@@ -16,13 +16,13 @@ use ic_interfaces::messages::{CanisterCall, CanisterCallOrTask};
 use ic_logger::{error, fatal, warn, ReplicaLogger};
 use ic_replicated_state::{
     CallContext, CallContextAction, CallOrigin, CanisterState, ExecutionState, NetworkTopology,
-    SystemState,
+    ReservationError, SystemState,
 };
 use ic_system_api::sandbox_safe_system_state::SystemStateChanges;
 use ic_types::ingress::{IngressState, IngressStatus, WasmResult};
 use ic_types::messages::{CallContextId, CallbackId, MessageId, Payload, RejectContext, Response};
 use ic_types::methods::{Callback, WasmMethod};
-use ic_types::{Cycles, MemoryAllocation, NumInstructions, Time, UserId};
+use ic_types::{CoarseTime, Cycles, MemoryAllocation, NumInstructions, Time, UserId};
 
 use crate::execution_environment::ExecutionResponse;
 use crate::{as_round_instructions, ExecuteMessageResult, RoundLimits};
@@ -68,8 +68,8 @@ pub(crate) fn action_to_response(
             time,
             log,
         ),
-        CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
-            action_to_request_response(canister, action, caller_canister_id, callback_id)
+        CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
+            action_to_request_response(canister, action, caller_canister_id, callback_id, deadline)
         }
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => fatal!(
             log,
@@ -90,6 +90,7 @@ pub(crate) fn action_to_request_response(
     action: CallContextAction,
     originator: CanisterId,
     reply_callback_id: CallbackId,
+    deadline: CoarseTime,
 ) -> ExecutionResponse {
     let response_payload_and_refund = match action {
         CallContextAction::NotYetResponded | CallContextAction::AlreadyResponded => None,
@@ -130,6 +131,7 @@ pub(crate) fn action_to_request_response(
             originator_reply_callback: reply_callback_id,
             refund,
             response_payload,
+            deadline,
         })
     } else {
         ExecutionResponse::Empty
@@ -240,13 +242,14 @@ pub(crate) fn wasm_result_to_query_response(
         CallOrigin::Ingress(user_id, message_id) => {
             wasm_result_to_ingress_response(result, canister, user_id, message_id, time)
         }
-        CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
+        CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
             let response = Response {
                 originator: caller_canister_id,
                 respondent: canister.canister_id(),
                 originator_reply_callback: callback_id,
                 refund,
                 response_payload: Payload::from(result),
+                deadline,
             };
             ExecutionResponse::Request(response)
         }
@@ -406,23 +409,49 @@ fn try_apply_canister_state_changes(
     output: &WasmExecutionOutput,
     system_state: &mut SystemState,
     subnet_available_memory: &mut SubnetAvailableMemory,
+    storage_reservation_cycles: Cycles,
     time: Time,
     network_topology: &NetworkTopology,
     subnet_id: SubnetId,
     log: &ReplicaLogger,
 ) -> HypervisorResult<()> {
-    match &system_state.memory_allocation {
-        MemoryAllocation::BestEffort => subnet_available_memory
-            .try_decrement(
-                output.allocated_bytes,
-                output.allocated_message_bytes,
-                NumBytes::from(0),
-            )
-            .map_err(|_| HypervisorError::OutOfMemory)?,
-        MemoryAllocation::Reserved(_) => (),
-    }
+    let storage_reservation_cycles = match &system_state.memory_allocation {
+        MemoryAllocation::BestEffort => {
+            subnet_available_memory
+                .try_decrement(
+                    output.allocated_bytes,
+                    output.allocated_message_bytes,
+                    NumBytes::from(0),
+                )
+                .map_err(|_| HypervisorError::OutOfMemory)?;
+            storage_reservation_cycles
+        }
+        // The storage of a reserved memory allocation is paid for when the
+        // allocation is set, so no cycles are reserved here.
+        MemoryAllocation::Reserved(_) => Cycles::zero(),
+    };
 
-    system_state_changes.apply_changes(time, system_state, network_topology, subnet_id, log)
+    system_state_changes.apply_changes(time, system_state, network_topology, subnet_id, log)?;
+
+    system_state
+        .reserve_cycles(storage_reservation_cycles)
+        .map_err(|err| match err {
+            ReservationError::InsufficientCycles {
+                requested,
+                available,
+            } => HypervisorError::InsufficientCyclesInMemoryGrow {
+                bytes: output.allocated_bytes,
+                available,
+                requested,
+            },
+            ReservationError::ReservedLimitExceed { requested, limit } => {
+                HypervisorError::ReservedCyclesLimitExceededInMemoryGrow {
+                    bytes: output.allocated_bytes,
+                    requested,
+                    limit,
+                }
+            }
+        })
 }
 
 /// Applies canister state change after Wasm execution if possible.
@@ -440,11 +469,17 @@ pub fn apply_canister_state_changes(
     system_state: &mut SystemState,
     output: &mut WasmExecutionOutput,
     round_limits: &mut RoundLimits,
+    storage_reservation_cycles: Cycles,
     time: Time,
     network_topology: &NetworkTopology,
     subnet_id: SubnetId,
     log: &ReplicaLogger,
 ) {
+    // Log records are kept even if the execution failed, so that the
+    // controllers can inspect the trap messages.
+    system_state
+        .canister_log
+        .append_delta_log(&mut output.canister_log);
     if let Some(CanisterStateChanges {
         globals,
         wasm_memory,
@@ -461,6 +496,7 @@ pub fn apply_canister_state_changes(
             output,
             system_state,
             &mut round_limits.subnet_available_memory,
+            storage_reservation_cycles,
             time,
             network_topology,
             subnet_id,
@@ -488,6 +524,11 @@ pub fn apply_canister_state_changes(
                     HypervisorError::OutOfMemory => {
                         warn!(log, "Failed to apply state changes due to DTS: {}", err)
                     }
+                    HypervisorError::InsufficientCyclesInMemoryGrow { .. }
+                    | HypervisorError::ReservedCyclesLimitExceededInMemoryGrow { .. } => {
+                        // Reserving cycles for storage is checked only here,
+                        // so these errors are expected.
+                    }
                     _ => {
                         // TODO(RUN-299): Increment a critical error counter here.
                         error!(
@@ -521,6 +562,7 @@ pub(crate) fn finish_call_with_error(
                 originator_reply_callback: request.sender_reply_callback,
                 refund: request.payment,
                 response_payload: Payload::from(Err(user_error)),
+                deadline: request.deadline,
             };
             ExecutionResponse::Request(response)
         }
@@ -572,7 +614,7 @@ mod test {
     use ic_logger::LoggerImpl;
     use ic_logger::ReplicaLogger;
     use ic_replicated_state::{CanisterState, SchedulerState, SystemState};
-    use ic_types::messages::CallbackId;
+    use ic_types::messages::{CallbackId, NO_DEADLINE};
     use ic_types::Cycles;
     use ic_types::Time;
 
@@ -599,6 +641,7 @@ mod test {
             ic_replicated_state::CallOrigin::CanisterUpdate(
                 CanisterId::from(123u64),
                 CallbackId::new(2),
+                NO_DEADLINE,
             ),
             &log,
             Cycles::from(1000u128),
@@ -1,5 +1,6 @@
 use crate::execution_environment::{as_round_instructions, RoundLimits};
 use crate::Hypervisor;
+use ic_cycles_account_manager::ResourceSaturation;
 use ic_error_types::{ErrorCode, UserError};
 use ic_interfaces::execution_environment::{ExecutionComplexity, SubnetAvailableMemory};
 use ic_logger::{fatal, ReplicaLogger};
@@ -62,6 +63,8 @@ pub fn execute_inspect_message(
         instructions: as_round_instructions(message_instruction_limit),
         execution_complexity: ExecutionComplexity::with_cpu(message_instruction_limit),
         subnet_available_memory,
+        // Ignore storage reservation
+        subnet_memory_saturation: ResourceSaturation::default(),
         // Ignore compute allocation
         compute_allocation_used: 0,
     };
@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 
 use ic_base_types::{CanisterId, NumBytes, PrincipalId};
 use ic_config::flag_status::FlagStatus;
+use ic_cycles_account_manager::ResourceSaturation;
 use ic_embedders::wasm_executor::CanisterStateChanges;
 use ic_ic00_types::CanisterInstallMode;
 use ic_interfaces::{
@@ -15,7 +16,7 @@ use ic_interfaces::{
     messages::CanisterCall,
 };
 use ic_logger::{error, fatal, info, warn};
-use ic_replicated_state::{CanisterState, ExecutionState};
+use ic_replicated_state::{CanisterState, ExecutionState, ReservationError};
 use ic_state_layout::{CanisterLayout, CheckpointLayout, ReadOnly};
 use ic_sys::PAGE_SIZE;
 use ic_system_api::ExecutionParameters;
@@ -259,6 +260,46 @@ impl InstallCodeHelper {
             round_limits.compute_allocation_used = others + new_compute_allocation.as_percent();
         }
 
+        // Cycles are reserved for the storage of the memory allocated by all
+        // steps, including a larger memory allocation.
+        let allocated_bytes = self.allocated_bytes - self.allocated_message_bytes;
+        let storage_reservation_cycles = round.cycles_account_manager.storage_reservation_cycles(
+            allocated_bytes,
+            &original.subnet_memory_saturation,
+            original.subnet_size,
+        );
+        if let Err(err) = self
+            .canister
+            .system_state
+            .reserve_cycles(storage_reservation_cycles)
+        {
+            let err = match err {
+                ReservationError::InsufficientCycles {
+                    requested,
+                    available,
+                } => HypervisorError::InsufficientCyclesInMemoryGrow {
+                    bytes: allocated_bytes,
+                    available,
+                    requested,
+                },
+                ReservationError::ReservedLimitExceed { requested, limit } => {
+                    HypervisorError::ReservedCyclesLimitExceededInMemoryGrow {
+                        bytes: allocated_bytes,
+                        requested,
+                        limit,
+                    }
+                }
+            };
+            let canister_id = clean_canister.canister_id();
+            return finish_err(
+                clean_canister,
+                self.instructions_left(),
+                original,
+                round,
+                (canister_id, err).into(),
+            );
+        }
+
         // After this point `install_code` is guaranteed to succeed.
         // Commit all the remaining state and round limit changes.
 
@@ -467,7 +508,7 @@ impl InstallCodeHelper {
     pub fn handle_wasm_execution(
         &mut self,
         canister_state_changes: Option<CanisterStateChanges>,
-        output: WasmExecutionOutput,
+        mut output: WasmExecutionOutput,
         original: &OriginalContext,
         round: &RoundContext,
     ) -> Result<(), CanisterManagerError> {
@@ -503,6 +544,11 @@ impl InstallCodeHelper {
             }
         };
 
+        self.canister
+            .system_state
+            .canister_log
+            .append_delta_log(&mut output.canister_log);
+
         if let Some(CanisterStateChanges {
             globals,
             wasm_memory,
@@ -599,6 +645,7 @@ pub(crate) struct OriginalContext {
     pub time: Time,
     pub compilation_cost_handling: CompilationCostHandling,
     pub subnet_size: usize,
+    pub subnet_memory_saturation: ResourceSaturation,
     pub requested_compute_allocation: Option<ComputeAllocation>,
     pub requested_memory_allocation: Option<MemoryAllocation>,
     pub sender: PrincipalId,
@@ -776,6 +776,46 @@ fn reserve_cycles_for_execution_fails_when_not_enough_cycles() {
     );
 }
 
+#[test]
+fn install_code_reserves_cycles_for_memory_above_threshold() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_total_memory(100 * 1024 * 1024) // 100 MiB
+        .with_subnet_memory_threshold(1024 * 1024) // 1 MiB
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000_000));
+    // 160 Wasm pages are 10 MiB.
+    let wasm = wat::parse_str("(module (memory 160))").unwrap();
+
+    let balance_before = test.canister_state(canister_id).system_state.balance();
+    test.install_canister(canister_id, wasm).unwrap();
+
+    let canister = test.canister_state(canister_id);
+    let reserved_balance = canister.system_state.reserved_balance();
+    assert!(reserved_balance > Cycles::zero());
+    assert!(canister.system_state.balance() + reserved_balance <= balance_before);
+}
+
+#[test]
+fn install_code_fails_when_reserved_cycles_limit_is_exceeded() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_total_memory(100 * 1024 * 1024) // 100 MiB
+        .with_subnet_memory_threshold(1024 * 1024) // 1 MiB
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000_000));
+    test.canister_update_reserved_cycles_limit(canister_id, Cycles::new(1))
+        .unwrap();
+    let wasm = wat::parse_str("(module (memory 160))").unwrap();
+
+    let err = test.install_canister(canister_id, wasm).unwrap_err();
+    assert_eq!(
+        ErrorCode::ReservedCyclesLimitExceededInMemoryGrow,
+        err.code()
+    );
+    let canister = test.canister_state(canister_id);
+    assert_eq!(canister.system_state.reserved_balance(), Cycles::zero());
+    assert!(canister.execution_state.is_none());
+}
+
 #[test]
 fn install_code_running_out_of_instructions() {
     let mut test = ExecutionTestBuilder::new()
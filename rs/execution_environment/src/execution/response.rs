@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use ic_base_types::CanisterId;
 use ic_constants::LOG_CANISTER_OPERATION_CYCLES_THRESHOLD;
+use ic_cycles_account_manager::ResourceSaturation;
 use ic_replicated_state::canister_state::system_state::CyclesUseCase;
 use prometheus::IntCounter;
 
@@ -353,12 +354,18 @@ impl ResponseHelper {
             }
         }
 
+        let storage_reservation_cycles = round.cycles_account_manager.storage_reservation_cycles(
+            output.allocated_bytes - output.allocated_message_bytes,
+            &original.subnet_memory_saturation,
+            original.subnet_size,
+        );
         apply_canister_state_changes(
             canister_state_changes,
             self.canister.execution_state.as_mut().unwrap(),
             &mut self.canister.system_state,
             &mut output,
             round_limits,
+            storage_reservation_cycles,
             round.time,
             round.network_topology,
             round.hypervisor.subnet_id(),
@@ -399,12 +406,18 @@ impl ResponseHelper {
             assert_eq!(requested.get(), 0);
         }
 
+        let storage_reservation_cycles = round.cycles_account_manager.storage_reservation_cycles(
+            output.allocated_bytes - output.allocated_message_bytes,
+            &original.subnet_memory_saturation,
+            original.subnet_size,
+        );
         apply_canister_state_changes(
             canister_state_changes,
             self.canister.execution_state.as_mut().unwrap(),
             &mut self.canister.system_state,
             &mut output,
             round_limits,
+            storage_reservation_cycles,
             round.time,
             round.network_topology,
             round.hypervisor.subnet_id(),
@@ -547,6 +560,7 @@ struct OriginalContext {
     subnet_size: usize,
     freezing_threshold: Cycles,
     canister_id: CanisterId,
+    subnet_memory_saturation: ResourceSaturation,
 }
 
 /// Struct used to hold necessary information for the
@@ -735,6 +749,7 @@ pub fn execute_response(
     execution_parameters: ExecutionParameters,
     error_counter: &IntCounter,
     round: RoundContext,
+    subnet_memory_saturation: ResourceSaturation,
     round_limits: &mut RoundLimits,
     subnet_size: usize,
 ) -> ExecuteMessageResult {
@@ -772,6 +787,7 @@ pub fn execute_response(
         subnet_size,
         freezing_threshold,
         canister_id: clean_canister.canister_id(),
+        subnet_memory_saturation,
     };
 
     let mut helper =
@@ -790,9 +806,9 @@ pub fn execute_response(
     };
 
     let func_ref = match original.call_origin {
-        CallOrigin::Ingress(_, _) | CallOrigin::CanisterUpdate(_, _) | CallOrigin::SystemTask => {
-            FuncRef::UpdateClosure(closure)
-        }
+        CallOrigin::Ingress(_, _)
+        | CallOrigin::CanisterUpdate(_, _, _)
+        | CallOrigin::SystemTask => FuncRef::UpdateClosure(closure),
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => FuncRef::QueryClosure(closure),
     };
 
@@ -804,6 +820,7 @@ pub fn execute_response(
             call_context_id,
             call_context.has_responded(),
             execution_parameters.execution_mode.clone(),
+            response.deadline,
         ),
         Payload::Reject(context) => ApiType::reject_callback(
             time,
@@ -812,6 +829,7 @@ pub fn execute_response(
             call_context_id,
             call_context.has_responded(),
             execution_parameters.execution_mode.clone(),
+            response.deadline,
         ),
     };
 
@@ -874,9 +892,9 @@ fn execute_response_cleanup(
         .instruction_limits
         .update(instructions_left);
     let func_ref = match original.call_origin {
-        CallOrigin::Ingress(_, _) | CallOrigin::CanisterUpdate(_, _) | CallOrigin::SystemTask => {
-            FuncRef::UpdateClosure(cleanup_closure)
-        }
+        CallOrigin::Ingress(_, _)
+        | CallOrigin::CanisterUpdate(_, _, _)
+        | CallOrigin::SystemTask => FuncRef::UpdateClosure(cleanup_closure),
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
             FuncRef::QueryClosure(cleanup_closure)
         }
@@ -9,6 +9,7 @@ use crate::execution_environment::{
     ExecuteMessageResult, PausedExecution, RoundContext, RoundLimits,
 };
 use ic_base_types::CanisterId;
+use ic_cycles_account_manager::ResourceSaturation;
 use ic_embedders::wasm_executor::{CanisterStateChanges, PausedWasmExecution, WasmExecutionResult};
 use ic_error_types::{ErrorCode, UserError};
 use ic_interfaces::execution_environment::{
@@ -17,7 +18,9 @@ use ic_interfaces::execution_environment::{
 use ic_interfaces::messages::{CanisterCall, CanisterMessageOrTask, CanisterTask};
 use ic_interfaces::messages::{CanisterCallOrTask, CanisterMessage};
 use ic_logger::{info, ReplicaLogger};
-use ic_replicated_state::{CallOrigin, CanisterState};
+use ic_replicated_state::{
+    canister_state::system_state::OnLowWasmMemoryHookStatus, CallOrigin, CanisterState,
+};
 use ic_types::messages::CallContextId;
 use ic_types::{CanisterTimer, Cycles, NumBytes, NumInstructions, Time};
 use ic_wasm_types::WasmEngineError::FailedToApplySystemChanges;
@@ -38,6 +41,7 @@ pub fn execute_update(
     execution_parameters: ExecutionParameters,
     time: Time,
     round: RoundContext,
+    subnet_memory_saturation: ResourceSaturation,
     round_limits: &mut RoundLimits,
     subnet_size: usize,
 ) -> ExecuteMessageResult {
@@ -90,6 +94,7 @@ pub fn execute_update(
         time,
         freezing_threshold,
         canister_id: clean_canister.canister_id(),
+        subnet_memory_saturation,
     };
 
     let helper = match UpdateHelper::new(&clean_canister, &original) {
@@ -112,6 +117,7 @@ pub fn execute_update(
             msg.cycles(),
             *msg.sender(),
             helper.call_context_id(),
+            msg.deadline(),
         ),
         CanisterCallOrTask::Task(CanisterTask::Heartbeat) => ApiType::system_task(
             SystemMethod::CanisterHeartbeat,
@@ -123,6 +129,11 @@ pub fn execute_update(
             time,
             helper.call_context_id(),
         ),
+        CanisterCallOrTask::Task(CanisterTask::OnLowWasmMemory) => ApiType::system_task(
+            SystemMethod::CanisterOnLowWasmMemory,
+            time,
+            helper.call_context_id(),
+        ),
     };
 
     let memory_usage = helper
@@ -239,6 +250,7 @@ struct OriginalContext {
     time: Time,
     freezing_threshold: Cycles,
     canister_id: CanisterId,
+    subnet_memory_saturation: ResourceSaturation,
 }
 
 /// Contains fields of `UpdateHelper` that are necessary for resuming an update
@@ -282,6 +294,12 @@ impl UpdateHelper {
                 // The global timer is one-off.
                 canister.system_state.global_timer = CanisterTimer::Inactive;
             }
+            CanisterCallOrTask::Task(CanisterTask::OnLowWasmMemory) => {
+                // The hook runs only once until the free Wasm memory goes
+                // above the threshold again.
+                canister.system_state.on_low_wasm_memory_hook_status =
+                    OnLowWasmMemoryHookStatus::Executed;
+            }
         }
 
         Ok(Self {
@@ -367,12 +385,18 @@ impl UpdateHelper {
             }
         }
 
+        let storage_reservation_cycles = round.cycles_account_manager.storage_reservation_cycles(
+            output.allocated_bytes - output.allocated_message_bytes,
+            &original.subnet_memory_saturation,
+            original.subnet_size,
+        );
         apply_canister_state_changes(
             canister_state_changes,
             self.canister.execution_state.as_mut().unwrap(),
             &mut self.canister.system_state,
             &mut output,
             round_limits,
+            storage_reservation_cycles,
             round.time,
             round.network_topology,
             round.hypervisor.subnet_id(),
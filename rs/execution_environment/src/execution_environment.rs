@@ -21,15 +21,16 @@ use ic_config::execution_environment::Config as ExecutionConfig;
 use ic_config::flag_status::FlagStatus;
 use ic_constants::{LOG_CANISTER_OPERATION_CYCLES_THRESHOLD, SMALL_APP_SUBNET_MAX_SIZE};
 use ic_crypto_tecdsa::derive_tecdsa_public_key;
-use ic_cycles_account_manager::{CyclesAccountManager, IngressInductionCost};
+use ic_cycles_account_manager::{CyclesAccountManager, IngressInductionCost, ResourceSaturation};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    CanisterHttpRequestArgs, CanisterIdRecord, CanisterSettingsArgs,
-    ComputeInitialEcdsaDealingsArgs, CreateCanisterArgs, ECDSAPublicKeyArgs,
-    ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, InstallCodeArgs, Method as Ic00Method,
-    Payload as Ic00Payload, ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs,
-    SetControllerArgs, SetupInitialDKGArgs, SignWithECDSAArgs, UninstallCodeArgs,
-    UpdateSettingsArgs, IC_00,
+    CanisterChangeOrigin, CanisterHttpRequestArgs, CanisterIdRecord, CanisterSettingsArgs,
+    ChunkHash, ComputeInitialEcdsaDealingsArgs, CreateCanisterArgs, DeleteCanisterSnapshotArgs,
+    ECDSAPublicKeyArgs, ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, InstallChunkedCodeArgs,
+    InstallCodeArgs, LoadCanisterSnapshotArgs, Method as Ic00Method, Payload as Ic00Payload,
+    ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs, SetControllerArgs,
+    SetupInitialDKGArgs, SignWithECDSAArgs, StoredChunksReply, TakeCanisterSnapshotArgs,
+    UninstallCodeArgs, UpdateSettingsArgs, UploadChunkArgs, IC_00,
 };
 use ic_interfaces::{
     execution_environment::{
@@ -64,7 +65,7 @@ use ic_types::{
     ingress::{IngressState, IngressStatus, WasmResult},
     messages::{
         extract_effective_canister_id, AnonymousQuery, Payload, RejectContext, Request, Response,
-        SignedIngressContent, StopCanisterContext,
+        SignedIngressContent, StopCanisterContext, NO_DEADLINE,
     },
     methods::SystemMethod,
     nominal_cycles::NominalCycles,
@@ -202,6 +203,12 @@ pub struct RoundLimits {
     /// - Wasm execution pushes a new request to the output queue.
     pub subnet_available_memory: SubnetAvailableMemory,
 
+    /// How saturated the subnet memory is, used to compute the cycles that
+    /// canisters reserve for storage. Unlike `subnet_available_memory`, it is
+    /// not divided between execution threads: it always describes the memory
+    /// usage of the whole subnet.
+    pub subnet_memory_saturation: ResourceSaturation,
+
     // TODO would be nice to change that to available, but this requires
     // a lot of changes since available allocation sits in CanisterManager config
     pub compute_allocation_used: u64,
@@ -297,6 +304,7 @@ impl ExecutionEnvironment {
         );
         let canister_manager_config: CanisterMgrConfig = CanisterMgrConfig::new(
             config.subnet_memory_capacity,
+            config.subnet_memory_threshold,
             config.default_provisional_cycles_balance,
             config.default_freeze_threshold,
             own_subnet_id,
@@ -410,6 +418,7 @@ impl ExecutionEnvironment {
                                 originator_reply_callback: request.sender_reply_callback,
                                 refund: request.payment,
                                 response_payload: response.response_payload.clone(),
+                                deadline: request.deadline,
                             }
                             .into(),
                         );
@@ -426,7 +435,7 @@ impl ExecutionEnvironment {
         let method = Ic00Method::from_str(msg.method_name());
         let payload = msg.method_payload();
         let result = match method {
-            Ok(Ic00Method::InstallCode) => {
+            Ok(Ic00Method::InstallCode) | Ok(Ic00Method::InstallChunkedCode) => {
                 // Tail call is needed for deterministic time slicing here to
                 // properly handle the case of a paused execution.
                 return self.execute_install_code(
@@ -462,6 +471,7 @@ impl ExecutionEnvironment {
                                         message: reject_message,
                                     },
                                 ),
+                                deadline: request.deadline,
                             }
                             .into(),
                         );
@@ -557,6 +567,104 @@ impl ExecutionEnvironment {
                 Some((res, msg.take_cycles()))
             }
 
+            Ok(Ic00Method::TakeCanisterSnapshot) => {
+                let res = match TakeCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.take_canister_snapshot(
+                        *msg.sender(),
+                        args,
+                        &mut state,
+                        round_limits,
+                        registry_settings.subnet_size,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::LoadCanisterSnapshot) => {
+                let res = match LoadCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.load_canister_snapshot(
+                        msg.canister_change_origin(args.get_sender_canister_version()),
+                        args,
+                        &mut state,
+                        round_limits,
+                        registry_settings.subnet_size,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::ListCanisterSnapshots) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.list_canister_snapshots(
+                        *msg.sender(),
+                        args.get_canister_id(),
+                        &mut state,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::DeleteCanisterSnapshot) => {
+                let res = match DeleteCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => {
+                        self.delete_canister_snapshot(*msg.sender(), args, &mut state, round_limits)
+                    }
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::UploadChunk) => {
+                let res = match UploadChunkArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.upload_chunk(
+                        *msg.sender(),
+                        args,
+                        &mut state,
+                        round_limits,
+                        registry_settings.subnet_size,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::StoredChunks) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => {
+                        self.stored_chunks(*msg.sender(), args.get_canister_id(), &mut state)
+                    }
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::ClearChunkStore) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.clear_chunk_store(
+                        *msg.sender(),
+                        args.get_canister_id(),
+                        &mut state,
+                        round_limits,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::FetchCanisterLogs) => Some((
+                Err(UserError::new(
+                    ErrorCode::CanisterRejectedMessage,
+                    format!(
+                        "{} API is only accessible in non-replicated mode",
+                        Ic00Method::FetchCanisterLogs
+                    ),
+                )),
+                msg.take_cycles(),
+            )),
+
             Ok(Ic00Method::UpdateSettings) => {
                 let res = match UpdateSettingsArgs::decode(payload) {
                     Err(err) => Err(err),
@@ -573,6 +681,7 @@ impl ExecutionEnvironment {
                                 canister_id,
                                 &mut state,
                                 round_limits,
+                                registry_settings.subnet_size,
                             ),
                         };
                         // The induction cost of `UpdateSettings` is charged
@@ -627,6 +736,7 @@ impl ExecutionEnvironment {
                             args.get_new_controller(),
                             &mut state,
                             round_limits,
+                            registry_settings.subnet_size,
                         )
                         .map(|()| EmptyBlob.encode())
                         .map_err(|err| err.into()),
@@ -1153,6 +1263,7 @@ impl ExecutionEnvironment {
                     instruction_limits,
                     ExecutionMode::Replicated,
                 );
+                let subnet_memory_saturation = round_limits.subnet_memory_saturation.clone();
                 execute_update(
                     canister,
                     CanisterCallOrTask::Call(req),
@@ -1161,6 +1272,7 @@ impl ExecutionEnvironment {
                     execution_parameters,
                     time,
                     round,
+                    subnet_memory_saturation,
                     round_limits,
                     subnet_size,
                 )
@@ -1184,6 +1296,7 @@ impl ExecutionEnvironment {
     ) -> ExecuteMessageResult {
         let execution_parameters =
             self.execution_parameters(&canister, instruction_limits, ExecutionMode::Replicated);
+        let subnet_memory_saturation = round_limits.subnet_memory_saturation.clone();
         execute_update(
             canister,
             CanisterCallOrTask::Task(task.clone()),
@@ -1192,6 +1305,7 @@ impl ExecutionEnvironment {
             execution_parameters,
             round.time,
             round,
+            subnet_memory_saturation,
             round_limits,
             subnet_size,
         )
@@ -1203,6 +1317,17 @@ impl ExecutionEnvironment {
         self.config.max_canister_memory_size
     }
 
+    /// Returns how saturated the subnet memory is given the memory that is
+    /// still available on the whole subnet. The available memory must not be
+    /// divided between execution threads.
+    pub fn subnet_memory_saturation(
+        &self,
+        subnet_available_memory: &SubnetAvailableMemory,
+    ) -> ResourceSaturation {
+        self.canister_manager
+            .subnet_memory_saturation(subnet_available_memory)
+    }
+
     /// Returns the subnet memory capacity.
     pub fn subnet_memory_capacity(&self) -> NumBytes {
         self.config.subnet_memory_capacity
@@ -1264,10 +1389,11 @@ impl ExecutionEnvironment {
         canister_id: CanisterId,
         state: &mut ReplicatedState,
         round_limits: &mut RoundLimits,
+        subnet_size: usize,
     ) -> Result<Vec<u8>, UserError> {
         let canister = get_canister_mut(canister_id, state)?;
         self.canister_manager
-            .update_settings(sender, settings, canister, round_limits)
+            .update_settings(sender, settings, canister, round_limits, subnet_size)
             .map(|()| EmptyBlob.encode())
             .map_err(|err| err.into())
     }
@@ -1341,6 +1467,143 @@ impl ExecutionEnvironment {
             .map_err(|err| err.into())
     }
 
+    fn take_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        args: TakeCanisterSnapshotArgs,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+    ) -> Result<Vec<u8>, UserError> {
+        let time = state.time();
+        let canister = get_canister_mut(args.get_canister_id(), state)?;
+
+        let (response, heap_delta) = self.canister_manager.take_canister_snapshot(
+            sender,
+            canister,
+            args.replace_snapshot(),
+            time,
+            round_limits,
+            subnet_size,
+        )?;
+        state.metadata.heap_delta_estimate += heap_delta;
+        Ok(response.encode())
+    }
+
+    fn load_canister_snapshot(
+        &self,
+        origin: CanisterChangeOrigin,
+        args: LoadCanisterSnapshotArgs,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+    ) -> Result<Vec<u8>, UserError> {
+        let time = state.time();
+        let canister = get_canister_mut(args.get_canister_id(), state)?;
+
+        let heap_delta = self.canister_manager.load_canister_snapshot(
+            origin,
+            canister,
+            args.snapshot_id(),
+            time,
+            "NOT_USED".into(),
+            round_limits,
+            CompilationCostHandling::CountFullAmount,
+            subnet_size,
+        )?;
+        state.metadata.heap_delta_estimate += heap_delta;
+        Ok(EmptyBlob.encode())
+    }
+
+    fn list_canister_snapshots(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &mut ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        let canister = get_canister_mut(canister_id, state)?;
+
+        self.canister_manager
+            .list_canister_snapshots(sender, canister)
+            .map(|snapshots| Encode!(&snapshots).unwrap())
+            .map_err(|err| err.into())
+    }
+
+    fn delete_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        args: DeleteCanisterSnapshotArgs,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+    ) -> Result<Vec<u8>, UserError> {
+        let canister = get_canister_mut(args.get_canister_id(), state)?;
+
+        self.canister_manager
+            .delete_canister_snapshot(sender, canister, args.snapshot_id(), round_limits)
+            .map(|()| EmptyBlob.encode())
+            .map_err(|err| err.into())
+    }
+
+    fn upload_chunk(
+        &self,
+        sender: PrincipalId,
+        args: UploadChunkArgs,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+    ) -> Result<Vec<u8>, UserError> {
+        let canister = get_canister_mut(args.get_canister_id(), state)?;
+
+        self.canister_manager
+            .upload_chunk(sender, canister, args.chunk, round_limits, subnet_size)
+            .map(|hash| {
+                ChunkHash {
+                    hash: hash.to_vec(),
+                }
+                .encode()
+            })
+            .map_err(|err| err.into())
+    }
+
+    fn stored_chunks(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &mut ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        let canister = get_canister_mut(canister_id, state)?;
+
+        self.canister_manager
+            .stored_chunks(sender, canister)
+            .map(|hashes| {
+                StoredChunksReply(
+                    hashes
+                        .into_iter()
+                        .map(|hash| ChunkHash {
+                            hash: hash.to_vec(),
+                        })
+                        .collect(),
+                )
+                .encode()
+            })
+            .map_err(|err| err.into())
+    }
+
+    fn clear_chunk_store(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+    ) -> Result<Vec<u8>, UserError> {
+        let canister = get_canister_mut(canister_id, state)?;
+
+        self.canister_manager
+            .clear_chunk_store(sender, canister, round_limits)
+            .map(|()| EmptyBlob.encode())
+            .map_err(|err| err.into())
+    }
+
     fn stop_canister(
         &self,
         canister_id: CanisterId,
@@ -1403,6 +1666,7 @@ impl ExecutionEnvironment {
             log: &self.log,
             time,
         };
+        let subnet_memory_saturation = round_limits.subnet_memory_saturation.clone();
         execute_response(
             canister,
             response,
@@ -1410,6 +1674,7 @@ impl ExecutionEnvironment {
             execution_parameters,
             self.metrics.response_cycles_refund_error_counter(),
             round,
+            subnet_memory_saturation,
             round_limits,
             subnet_size,
         )
@@ -1533,6 +1798,8 @@ impl ExecutionEnvironment {
             instructions: as_round_instructions(max_instructions_per_query),
             execution_complexity: ExecutionComplexity::with_cpu(max_instructions_per_query),
             subnet_available_memory,
+            // Ignore storage reservation
+            subnet_memory_saturation: ResourceSaturation::default(),
             // Ignore compute allocation
             compute_allocation_used: 0,
         };
@@ -1590,6 +1857,7 @@ impl ExecutionEnvironment {
                     originator_reply_callback: req.sender_reply_callback,
                     refund,
                     response_payload: payload,
+                    deadline: req.deadline,
                 };
 
                 state.push_subnet_output_response(response.into());
@@ -1671,6 +1939,7 @@ impl ExecutionEnvironment {
                             code: RejectCode::CanisterReject,
                             message: format!("Canister {}'s stop request cancelled", canister_id),
                         }),
+                        deadline: NO_DEADLINE,
                     };
                     state.push_subnet_output_response(response.into());
                 }
@@ -1865,12 +2134,23 @@ impl ExecutionEnvironment {
     ) -> (ReplicatedState, Option<NumInstructions>) {
         // A helper function to make error handling more compact using `?`.
         fn decode_input_and_take_canister(
+            canister_manager: &CanisterManager,
             msg: &CanisterCall,
             state: &mut ReplicatedState,
         ) -> Result<(InstallCodeContext, CanisterState), UserError> {
             let payload = msg.method_payload();
-            let args = InstallCodeArgs::decode(payload)?;
-            let install_context = InstallCodeContext::try_from((*msg.sender(), args))?;
+            let install_context = match Ic00Method::from_str(msg.method_name()) {
+                Ok(Ic00Method::InstallChunkedCode) => {
+                    let args = InstallChunkedCodeArgs::decode(payload)?;
+                    let wasm_module =
+                        canister_manager.assemble_chunked_wasm(*msg.sender(), &args, state)?;
+                    InstallCodeContext::from((*msg.sender(), args, wasm_module))
+                }
+                _ => {
+                    let args = InstallCodeArgs::decode(payload)?;
+                    InstallCodeContext::try_from((*msg.sender(), args))?
+                }
+            };
             let canister = state
                 .take_canister_state(&install_context.canister_id)
                 .ok_or(CanisterManagerError::CanisterNotFound(
@@ -1882,16 +2162,16 @@ impl ExecutionEnvironment {
         // Start logging execution time for `install_code`.
         let timer = Timer::start();
 
-        let (install_context, old_canister) = match decode_input_and_take_canister(&msg, &mut state)
-        {
-            Ok(result) => result,
-            Err(err) => {
-                let refund = msg.take_cycles();
-                let state =
-                    self.finish_subnet_message_execution(state, msg, Err(err), refund, timer);
-                return (state, Some(NumInstructions::from(0)));
-            }
-        };
+        let (install_context, old_canister) =
+            match decode_input_and_take_canister(&self.canister_manager, &msg, &mut state) {
+                Ok(result) => result,
+                Err(err) => {
+                    let refund = msg.take_cycles();
+                    let state =
+                        self.finish_subnet_message_execution(state, msg, Err(err), refund, timer);
+                    return (state, Some(NumInstructions::from(0)));
+                }
+            };
 
         // Check the precondition.
         match old_canister.next_execution() {
@@ -2056,6 +2336,7 @@ impl ExecutionEnvironment {
         match task {
             ExecutionTask::Heartbeat
             | ExecutionTask::GlobalTimer
+            | ExecutionTask::OnLowWasmMemory
             | ExecutionTask::PausedExecution(_)
             | ExecutionTask::AbortedExecution { .. } => {
                 panic!(
@@ -2142,7 +2423,8 @@ impl ExecutionEnvironment {
                     ExecutionTask::AbortedExecution { .. }
                     | ExecutionTask::AbortedInstallCode { .. }
                     | ExecutionTask::Heartbeat
-                    | ExecutionTask::GlobalTimer => task,
+                    | ExecutionTask::GlobalTimer
+                    | ExecutionTask::OnLowWasmMemory => task,
                     ExecutionTask::PausedExecution(id) => {
                         let paused = self.take_paused_execution(id).unwrap();
                         let (input, prepaid_execution_cycles) = paused.abort(log);
@@ -2436,6 +2718,10 @@ pub fn execute_canister(
                 let task = CanisterMessageOrTask::Task(CanisterTask::GlobalTimer);
                 (task, None)
             }
+            ExecutionTask::OnLowWasmMemory => {
+                let task = CanisterMessageOrTask::Task(CanisterTask::OnLowWasmMemory);
+                (task, None)
+            }
             ExecutionTask::AbortedExecution {
                 input,
                 prepaid_execution_cycles,
@@ -6,10 +6,11 @@ use ic_types::nominal_cycles::NominalCycles;
 use ic_base_types::{NumBytes, NumSeconds};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    self as ic00, CanisterHttpRequestArgs, CanisterIdRecord, CanisterStatusResultV2,
-    CanisterStatusType, DerivationPath, EcdsaCurve, EcdsaKeyId, EmptyBlob, HttpMethod, Method,
-    Payload as Ic00Payload, ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs,
-    TransformContext, TransformFunc, IC_00,
+    self as ic00, CanisterChange, CanisterChangeDetails, CanisterChangeOrigin,
+    CanisterHttpRequestArgs, CanisterIdRecord, CanisterStatusResultV2, CanisterStatusType,
+    DerivationPath, EcdsaCurve, EcdsaKeyId, EmptyBlob, HttpMethod, Method, Payload as Ic00Payload,
+    ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs, TransformContext,
+    TransformFunc, IC_00,
 };
 use ic_registry_routing_table::canister_id_into_u64;
 use ic_registry_routing_table::CanisterIdRange;
@@ -30,6 +31,7 @@ use ic_types::{
     ingress::{IngressState, IngressStatus, WasmResult},
     messages::{
         CallbackId, Payload, RejectContext, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
+        NO_DEADLINE,
     },
     CanisterId, Cycles, PrincipalId, RegistryVersion,
 };
@@ -1108,7 +1110,8 @@ fn setup_initial_dkg_sender_not_on_nns() {
                     ic00::Method::SetupInitialDKG,
                     other_canister,
                 )
-            })
+            }),
+            deadline: NO_DEADLINE,
         }
         .into()
     );
@@ -2538,3 +2541,286 @@ fn test_consumed_cycles_by_use_case_with_refund() {
         NominalCycles::from(test.canister_execution_cost(b_id))
     );
 }
+
+fn take_canister_snapshot(test: &mut ExecutionTest, canister_id: CanisterId) -> Vec<u8> {
+    let args = ic00::TakeCanisterSnapshotArgs::new(canister_id, None);
+    let result = test
+        .subnet_message(Method::TakeCanisterSnapshot, args.encode())
+        .unwrap();
+    Decode!(&get_reply(result), ic00::CanisterSnapshotResponse)
+        .unwrap()
+        .id
+}
+
+fn list_canister_snapshots(
+    test: &mut ExecutionTest,
+    canister_id: CanisterId,
+) -> Vec<ic00::CanisterSnapshotResponse> {
+    let result = test
+        .subnet_message(
+            Method::ListCanisterSnapshots,
+            CanisterIdRecord::from(canister_id).encode(),
+        )
+        .unwrap();
+    Decode!(&get_reply(result), Vec<ic00::CanisterSnapshotResponse>).unwrap()
+}
+
+#[test]
+fn load_canister_snapshot_restores_stable_memory() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+    let write = wasm()
+        .stable_grow(1)
+        .stable_write(0, b"before")
+        .reply()
+        .build();
+    test.ingress(canister_id, "update", write).unwrap();
+
+    let snapshot_id = take_canister_snapshot(&mut test, canister_id);
+    let snapshot_canister_version = test
+        .canister_state(canister_id)
+        .system_state
+        .canister_version;
+    let memory_usage_with_snapshot = test
+        .canister_state(canister_id)
+        .memory_usage(SubnetType::Application);
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .canister_snapshots()
+            .len(),
+        1
+    );
+
+    let overwrite = wasm().stable_write(0, b"after!").reply().build();
+    test.ingress(canister_id, "update", overwrite).unwrap();
+
+    // A running canister cannot load a snapshot.
+    let args = ic00::LoadCanisterSnapshotArgs::new(canister_id, snapshot_id.clone(), None);
+    let err = test
+        .subnet_message(Method::LoadCanisterSnapshot, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterNotStopped, err.code());
+
+    test.stop_canister(canister_id);
+    test.process_stopping_canisters();
+    let canister_version = test
+        .canister_state(canister_id)
+        .system_state
+        .canister_version;
+    let heap_delta_before_load = test.state().metadata.heap_delta_estimate;
+    let result = test
+        .subnet_message(Method::LoadCanisterSnapshot, args.encode())
+        .unwrap();
+    assert_eq!(WasmResult::Reply(EmptyBlob.encode()), result);
+    assert!(test.state().metadata.heap_delta_estimate > heap_delta_before_load);
+
+    // The load is recorded in the canister history.
+    let snapshot = list_canister_snapshots(&mut test, canister_id).remove(0);
+    let last_change = test
+        .canister_state(canister_id)
+        .system_state
+        .get_canister_history()
+        .get_changes(Some(1))
+        .next()
+        .unwrap();
+    assert_eq!(
+        **last_change,
+        CanisterChange::new(
+            test.time().as_nanos_since_unix_epoch(),
+            canister_version + 1,
+            CanisterChangeOrigin::from_user(test.user_id().get()),
+            CanisterChangeDetails::load_snapshot(
+                snapshot_canister_version,
+                snapshot_id,
+                snapshot.taken_at_timestamp,
+            ),
+        )
+    );
+    let history_entry_size = last_change.count_bytes();
+    test.start_canister(canister_id).unwrap();
+
+    let read = wasm().stable_read(0, 6).append_and_reply().build();
+    let result = test.ingress(canister_id, "query", read).unwrap();
+    assert_eq!(WasmResult::Reply(b"before".to_vec()), result);
+    assert!(
+        test.canister_state(canister_id)
+            .memory_usage(SubnetType::Application)
+            <= memory_usage_with_snapshot + history_entry_size
+    );
+}
+
+#[test]
+fn list_and_delete_canister_snapshots() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+    let snapshot_id = take_canister_snapshot(&mut test, canister_id);
+
+    let snapshots = list_canister_snapshots(&mut test, canister_id);
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].id, snapshot_id);
+    assert_eq!(
+        test.canister_state(canister_id)
+            .canister_snapshots_memory_usage()
+            .get(),
+        snapshots[0].total_size
+    );
+
+    // Only one snapshot is allowed unless an existing one is replaced.
+    let args = ic00::TakeCanisterSnapshotArgs::new(canister_id, None);
+    let err = test
+        .subnet_message(Method::TakeCanisterSnapshot, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+
+    let args = ic00::DeleteCanisterSnapshotArgs::new(canister_id, snapshot_id.clone());
+    test.subnet_message(Method::DeleteCanisterSnapshot, args.encode())
+        .unwrap();
+    assert!(list_canister_snapshots(&mut test, canister_id).is_empty());
+    assert_eq!(
+        test.canister_state(canister_id)
+            .canister_snapshots_memory_usage()
+            .get(),
+        0
+    );
+
+    // Deleting the snapshot a second time fails.
+    let args = ic00::DeleteCanisterSnapshotArgs::new(canister_id, snapshot_id);
+    let err = test
+        .subnet_message(Method::DeleteCanisterSnapshot, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+}
+
+fn upload_chunk(test: &mut ExecutionTest, canister_id: CanisterId, chunk: &[u8]) -> Vec<u8> {
+    let args = ic00::UploadChunkArgs::new(canister_id, chunk.to_vec());
+    let result = test
+        .subnet_message(Method::UploadChunk, args.encode())
+        .unwrap();
+    Decode!(&get_reply(result), ic00::ChunkHash).unwrap().hash
+}
+
+fn stored_chunks(test: &mut ExecutionTest, canister_id: CanisterId) -> Vec<Vec<u8>> {
+    let result = test
+        .subnet_message(
+            Method::StoredChunks,
+            CanisterIdRecord::from(canister_id).encode(),
+        )
+        .unwrap();
+    Decode!(&get_reply(result), ic00::StoredChunksReply)
+        .unwrap()
+        .0
+        .into_iter()
+        .map(|chunk_hash| chunk_hash.hash)
+        .collect()
+}
+
+#[test]
+fn upload_and_clear_wasm_chunks() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+    let balance_before = test.canister_state(canister_id).system_state.balance();
+
+    let hash = upload_chunk(&mut test, canister_id, b"chunk");
+    assert_eq!(hash, ic_crypto_sha::Sha256::hash(b"chunk").to_vec());
+    assert!(test.canister_state(canister_id).system_state.balance() < balance_before);
+
+    // Uploading the same chunk again does not take additional memory.
+    upload_chunk(&mut test, canister_id, b"chunk");
+    assert_eq!(stored_chunks(&mut test, canister_id), vec![hash]);
+    assert_eq!(
+        test.canister_state(canister_id)
+            .wasm_chunk_store_memory_usage()
+            .get(),
+        5
+    );
+
+    test.subnet_message(
+        Method::ClearChunkStore,
+        CanisterIdRecord::from(canister_id).encode(),
+    )
+    .unwrap();
+    assert!(stored_chunks(&mut test, canister_id).is_empty());
+    assert_eq!(
+        test.canister_state(canister_id)
+            .wasm_chunk_store_memory_usage()
+            .get(),
+        0
+    );
+}
+
+#[test]
+fn upload_chunk_fails_for_too_large_chunk() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+    let chunk =
+        vec![0; ic_replicated_state::canister_state::wasm_chunk_store::MAX_WASM_CHUNK_SIZE + 1];
+    let args = ic00::UploadChunkArgs::new(canister_id, chunk);
+    let err = test
+        .subnet_message(Method::UploadChunk, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+    assert!(stored_chunks(&mut test, canister_id).is_empty());
+}
+
+#[test]
+fn install_chunked_code_assembles_module_from_chunks() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+    let wasm_module = ic_universal_canister::UNIVERSAL_CANISTER_WASM;
+    let (first, second) = wasm_module.split_at(wasm_module.len() / 2);
+    let chunk_hashes = vec![
+        upload_chunk(&mut test, canister_id, first),
+        upload_chunk(&mut test, canister_id, second),
+    ];
+    let wasm_module_hash = ic_crypto_sha::Sha256::hash(wasm_module).to_vec();
+
+    // Chunks in the wrong order do not match the module hash.
+    let args = ic00::InstallChunkedCodeArgs::new(
+        ic00::CanisterInstallMode::Install,
+        canister_id,
+        None,
+        chunk_hashes.iter().rev().cloned().collect(),
+        wasm_module_hash.clone(),
+        vec![],
+    );
+    let err = test
+        .subnet_message(Method::InstallChunkedCode, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::InvalidManagementPayload, err.code());
+    assert!(test.canister_state(canister_id).execution_state.is_none());
+
+    let args = ic00::InstallChunkedCodeArgs::new(
+        ic00::CanisterInstallMode::Install,
+        canister_id,
+        None,
+        chunk_hashes,
+        wasm_module_hash,
+        vec![],
+    );
+    test.subnet_message(Method::InstallChunkedCode, args.encode())
+        .unwrap();
+    let result = test
+        .ingress(canister_id, "update", wasm().reply_data(b"ok").build())
+        .unwrap();
+    assert_eq!(WasmResult::Reply(b"ok".to_vec()), result);
+}
+
+#[test]
+fn install_chunked_code_fails_for_unknown_chunk() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+    let wasm_module = ic_universal_canister::UNIVERSAL_CANISTER_WASM;
+    let args = ic00::InstallChunkedCodeArgs::new(
+        ic00::CanisterInstallMode::Install,
+        canister_id,
+        None,
+        vec![ic_crypto_sha::Sha256::hash(wasm_module).to_vec()],
+        ic_crypto_sha::Sha256::hash(wasm_module).to_vec(),
+        vec![],
+    );
+    let err = test
+        .subnet_message(Method::InstallChunkedCode, args.encode())
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+}
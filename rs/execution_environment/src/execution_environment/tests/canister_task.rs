@@ -1,7 +1,9 @@
 use assert_matches::assert_matches;
+use ic_config::execution_environment::LOW_WASM_MEMORY_THRESHOLD;
+use ic_ic00_types::CanisterSettingsArgsBuilder;
 use ic_interfaces::messages::CanisterTask;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::NumWasmPages;
+use ic_replicated_state::{canister_state::WASM_PAGE_SIZE_IN_BYTES, NumWasmPages};
 use ic_replicated_state::{page_map::PAGE_SIZE, CanisterStatus};
 use ic_state_machine_tests::{Cycles, StateMachine};
 use ic_state_machine_tests::{StateMachineBuilder, WasmResult};
@@ -46,6 +48,86 @@ fn global_timer_is_executed() {
     );
 }
 
+#[test]
+fn on_low_wasm_memory_is_executed() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"(module
+            (func (export "canister_on_low_wasm_memory")
+                (drop (memory.grow (i32.const 10)))
+            )
+            (memory 1 20)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    test.canister_task(canister_id, CanisterTask::OnLowWasmMemory);
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(11)
+    );
+}
+
+#[test]
+fn memory_grow_fails_above_wasm_memory_limit() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"(module
+            (func (export "canister_heartbeat")
+                (if (i32.ne (memory.grow (i32.const 10)) (i32.const -1))
+                    (then (unreachable)))
+                (drop (memory.grow (i32.const 2)))
+            )
+            (memory 1 20)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    test.canister_state_mut(canister_id)
+        .system_state
+        .wasm_memory_limit = Some(NumBytes::new(5 * WASM_PAGE_SIZE_IN_BYTES as u64));
+    test.canister_task(canister_id, CanisterTask::Heartbeat);
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(3)
+    );
+}
+
+#[test]
+fn on_low_wasm_memory_runs_once_when_free_memory_drops_below_threshold() {
+    let env = StateMachine::new();
+    let wat = r#"(module
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32)))
+            (func (export "canister_update grow")
+                (drop (memory.grow (i32.const 2)))
+                (call $msg_reply)
+            )
+            (func (export "canister_query count")
+                (call $msg_reply_data_append (i32.const 0) (i32.const 4))
+                (call $msg_reply)
+            )
+            (func (export "canister_on_low_wasm_memory")
+                (i32.store (i32.const 0)
+                    (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+            )
+            (memory 1)
+        )"#;
+    let wasm_memory_limit = LOW_WASM_MEMORY_THRESHOLD.get() + 2 * WASM_PAGE_SIZE_IN_BYTES as u64;
+    let settings = CanisterSettingsArgsBuilder::new()
+        .with_wasm_memory_limit(wasm_memory_limit)
+        .build();
+    let canister_id = env.install_canister_wat(wat, vec![], Some(settings));
+
+    // The free Wasm memory is still above the threshold.
+    env.tick();
+    let result = env.query(canister_id, "count", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(0u32.to_le_bytes().into()));
+
+    // Growing the memory makes the hook ready, but it runs only once.
+    env.execute_ingress(canister_id, "grow", vec![]).unwrap();
+    for _ in 0..5 {
+        env.tick();
+    }
+    let result = env.query(canister_id, "count", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(1u32.to_le_bytes().into()));
+}
+
 #[test]
 fn ic0_global_timer_set_is_supported_in_pre_upgrade() {
     let env = StateMachine::new();
@@ -295,6 +295,8 @@ fn dashboard_label_value_from(code: ErrorCode) -> &'static str {
         QueryCallGraphTotalInstructionLimitExceeded => "Total instructions limit exceeded for query call graph",
         CompositeQueryCalledInReplicatedMode => "Composite query cannot be called in replicated mode",
         CanisterNotHostedBySubnet => "Canister is not hosted by subnet",
-        QueryTimeLimitExceeded => "Canister exceeded the time limit for composite query execution"
+        QueryTimeLimitExceeded => "Canister exceeded the time limit for composite query execution",
+        ReservedCyclesLimitExceededInMemoryAllocation => "Canister cannot increase memory allocation due to its reserved cycles limit",
+        ReservedCyclesLimitExceededInMemoryGrow => "Canister cannot grow memory due to its reserved cycles limit"
     }
 }
@@ -16,7 +16,7 @@ use ic_replicated_state::NetworkTopology;
 use ic_replicated_state::{page_map::allocated_pages_count, ExecutionState, SystemState};
 use ic_system_api::ExecutionParameters;
 use ic_system_api::{sandbox_safe_system_state::SandboxSafeSystemState, ApiType};
-use ic_types::{methods::FuncRef, CanisterId, NumBytes, NumInstructions, SubnetId, Time};
+use ic_types::{methods::FuncRef, CanisterId, Cycles, NumBytes, NumInstructions, SubnetId, Time};
 use ic_wasm_types::CanisterModule;
 use prometheus::{Histogram, IntGauge};
 use std::{path::PathBuf, sync::Arc};
@@ -32,6 +32,7 @@ mod tests;
 pub struct HypervisorMetrics {
     accessed_pages: Histogram,
     dirty_pages: Histogram,
+    stable_dirty_pages: Histogram,
     read_before_write_count: Histogram,
     direct_write_count: Histogram,
     allocated_pages: IntGauge,
@@ -54,6 +55,11 @@ impl HypervisorMetrics {
                 "Number of pages modified (dirtied) per execution round.",
                 exponential_buckets(1.0, 2.0, 22),
             ),
+            stable_dirty_pages: metrics_registry.histogram(
+                "hypervisor_stable_dirty_pages",
+                "Number of stable memory pages modified (dirtied) per execution round.",
+                exponential_buckets(1.0, 2.0, 22),
+            ),
             read_before_write_count: metrics_registry.histogram(
                 "hypervisor_read_before_write_count",
                 "Number of wasm heap write accesses handled where the page had already been read.",
@@ -87,6 +93,8 @@ impl HypervisorMetrics {
                 .observe(output.instance_stats.accessed_pages as f64);
             self.dirty_pages
                 .observe(output.instance_stats.dirty_pages as f64);
+            self.stable_dirty_pages
+                .observe(output.instance_stats.stable_dirty_pages as f64);
             self.read_before_write_count
                 .observe(output.instance_stats.read_before_write_count as f64);
             self.direct_write_count
@@ -312,6 +320,9 @@ impl Hypervisor {
             &mut system_state,
             &mut output,
             round_limits,
+            // Callers of this function discard the changes to the canister
+            // memory, so no cycles are reserved for storage.
+            Cycles::zero(),
             time,
             network_topology,
             self.own_subnet_id,
@@ -32,6 +32,7 @@ use ic_interfaces::execution_environment::{
 use ic_interfaces_state_manager::StateReader;
 use ic_logger::ReplicaLogger;
 use ic_metrics::MetricsRegistry;
+use ic_query_stats::QueryStatsPayloadBuilderParams;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
 use ic_replicated_state::{CallOrigin, NetworkTopology, ReplicatedState};
@@ -83,6 +84,7 @@ pub struct ExecutionServices {
     pub async_query_handler: QueryExecutionService,
     pub anonymous_query_handler: AnonymousQueryService,
     pub scheduler: Box<dyn Scheduler<State = ReplicatedState>>,
+    pub query_stats_payload_builder: QueryStatsPayloadBuilderParams,
 }
 
 impl ExecutionServices {
@@ -130,6 +132,9 @@ impl ExecutionServices {
             config.clone(),
             Arc::clone(&cycles_account_manager),
         ));
+        let (query_stats_collector, query_stats_payload_builder) =
+            ic_query_stats::init_query_stats(logger.clone(), config.query_stats_epoch_length);
+
         let sync_query_handler = Arc::new(InternalHttpQueryHandler::new(
             logger.clone(),
             hypervisor,
@@ -138,6 +143,7 @@ impl ExecutionServices {
             metrics_registry,
             scheduler_config.max_instructions_per_message_without_dts,
             Arc::clone(&cycles_account_manager),
+            query_stats_collector.clone(),
         ));
 
         let query_scheduler = QueryScheduler::new(
@@ -154,6 +160,7 @@ impl ExecutionServices {
             Arc::clone(&sync_query_handler) as Arc<_>,
             query_scheduler.clone(),
             Arc::clone(&state_reader),
+            query_stats_collector,
         );
         let ingress_filter = IngressFilter::new_service(
             query_scheduler.clone(),
@@ -178,6 +185,7 @@ impl ExecutionServices {
             config.rate_limiting_of_heap_delta,
             config.rate_limiting_of_instructions,
             config.deterministic_time_slicing,
+            config.low_wasm_memory_threshold,
         ));
 
         Self {
@@ -188,6 +196,7 @@ impl ExecutionServices {
             async_query_handler,
             anonymous_query_handler,
             scheduler,
+            query_stats_payload_builder,
         }
     }
 
@@ -18,14 +18,20 @@ use ic_config::flag_status::FlagStatus;
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_error_types::{ErrorCode, RejectCode, UserError};
+use ic_ic00_types::{
+    FetchCanisterLogsRequest, FetchCanisterLogsResponse, LogVisibility, Method as Ic00Method,
+    Payload,
+};
 use ic_interfaces::execution_environment::{QueryExecutionService, QueryHandler};
 use ic_interfaces_state_manager::StateReader;
 use ic_logger::ReplicaLogger;
 use ic_metrics::MetricsRegistry;
+use ic_query_stats::QueryStatsCollector;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::ReplicatedState;
 use ic_types::CountBytes;
 use ic_types::{
+    batch::QueryStats,
     ingress::WasmResult,
     messages::{
         Blob, Certificate, CertificateDelegation, HttpQueryResponse, HttpQueryResponseReply,
@@ -38,6 +44,7 @@ use std::{
     convert::Infallible,
     future::Future,
     pin::Pin,
+    str::FromStr,
     sync::Arc,
     task::{Context, Poll},
 };
@@ -91,6 +98,57 @@ fn label<T: Into<Label>>(t: T) -> Label {
     t.into()
 }
 
+/// Executes a query call to the management canister. Only methods that do
+/// not modify the state, like `fetch_canister_logs`, can be called this way.
+fn execute_management_query(
+    query: &UserQuery,
+    state: &ReplicatedState,
+) -> Result<WasmResult, UserError> {
+    match Ic00Method::from_str(&query.method_name) {
+        Ok(Ic00Method::FetchCanisterLogs) => {
+            let args = FetchCanisterLogsRequest::decode(&query.method_payload)?;
+            let canister_id = args.get_canister_id();
+            let canister = state.canister_state(&canister_id).ok_or_else(|| {
+                UserError::new(
+                    ErrorCode::CanisterNotFound,
+                    format!("Canister {} not found", canister_id),
+                )
+            })?;
+            match canister.system_state.log_visibility {
+                LogVisibility::Public => {}
+                LogVisibility::Controllers => {
+                    if !canister.controllers().contains(&query.source.get()) {
+                        return Err(UserError::new(
+                            ErrorCode::CanisterInvalidController,
+                            format!(
+                                "Caller {} is not allowed to query ic00 method {}",
+                                query.source, query.method_name
+                            ),
+                        ));
+                    }
+                }
+            }
+            let response = FetchCanisterLogsResponse {
+                canister_log_records: canister
+                    .system_state
+                    .canister_log
+                    .records()
+                    .iter()
+                    .cloned()
+                    .collect(),
+            };
+            Ok(WasmResult::Reply(response.encode()))
+        }
+        Ok(_) | Err(_) => Err(UserError::new(
+            ErrorCode::CanisterMethodNotFound,
+            format!(
+                "Query method {} not found in the management canister",
+                query.method_name
+            ),
+        )),
+    }
+}
+
 pub struct InternalHttpQueryHandler {
     log: ReplicaLogger,
     hypervisor: Arc<Hypervisor>,
@@ -100,6 +158,7 @@ pub struct InternalHttpQueryHandler {
     max_instructions_per_query: NumInstructions,
     cycles_account_manager: Arc<CyclesAccountManager>,
     query_cache: query_cache::QueryCache,
+    query_stats_collector: QueryStatsCollector,
 }
 
 #[derive(Clone)]
@@ -108,6 +167,7 @@ pub(crate) struct HttpQueryHandler {
     internal: Arc<dyn QueryHandler<State = ReplicatedState>>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     query_scheduler: QueryScheduler,
+    query_stats_collector: QueryStatsCollector,
 }
 
 impl InternalHttpQueryHandler {
@@ -119,6 +179,7 @@ impl InternalHttpQueryHandler {
         metrics_registry: &MetricsRegistry,
         max_instructions_per_query: NumInstructions,
         cycles_account_manager: Arc<CyclesAccountManager>,
+        query_stats_collector: QueryStatsCollector,
     ) -> Self {
         let query_cache_capacity = config.query_cache_capacity;
         Self {
@@ -130,8 +191,33 @@ impl InternalHttpQueryHandler {
             max_instructions_per_query,
             cycles_account_manager,
             query_cache: query_cache::QueryCache::new(query_cache_capacity),
+            query_stats_collector,
         }
     }
+
+    /// Registers the statistics of a query to `canister_id`, that received
+    /// `ingress_payload_size` bytes and produced `result`.
+    fn register_query_statistics(
+        &self,
+        canister_id: CanisterId,
+        num_instructions: NumInstructions,
+        ingress_payload_size: usize,
+        result: &Result<WasmResult, UserError>,
+    ) {
+        let egress_payload_size = match result {
+            Ok(wasm_result) => wasm_result.count_bytes(),
+            Err(_) => 0,
+        };
+        self.query_stats_collector.register_query_statistics(
+            canister_id,
+            &QueryStats {
+                num_calls: 1,
+                num_instructions: num_instructions.get(),
+                ingress_payload_size: ingress_payload_size as u64,
+                egress_payload_size: egress_payload_size as u64,
+            },
+        );
+    }
 }
 
 impl QueryHandler for InternalHttpQueryHandler {
@@ -145,6 +231,12 @@ impl QueryHandler for InternalHttpQueryHandler {
     ) -> Result<WasmResult, UserError> {
         let measurement_scope = MeasurementScope::root(&self.metrics.query);
 
+        if query.receiver == CanisterId::ic_00() {
+            return execute_management_query(&query, state.as_ref());
+        }
+        let canister_id = query.receiver;
+        let ingress_payload_size = query.method_payload.len();
+
         // Check the query cache first (if the query caching is enabled).
         // If a valid cache entry found, the result will be immediately returned.
         // Otherwise, the key and the env will be kept for the `insert` below.
@@ -161,6 +253,12 @@ impl QueryHandler for InternalHttpQueryHandler {
                     self.metrics.query_cache_hits.inc();
                     let count_bytes = cache.count_bytes() as f64;
                     self.metrics.query_cache_count_bytes.observe(count_bytes);
+                    self.register_query_statistics(
+                        canister_id,
+                        NumInstructions::from(0),
+                        ingress_payload_size,
+                        &res,
+                    );
                     return res;
                 } else {
                     // The cache entry is no longer valid, remove it.
@@ -200,6 +298,12 @@ impl QueryHandler for InternalHttpQueryHandler {
             Arc::clone(&self.cycles_account_manager),
             &measurement_scope,
         );
+        self.register_query_statistics(
+            canister_id,
+            context.instructions_executed(),
+            ingress_payload_size,
+            &result,
+        );
 
         // Add the query execution result to the query cache  (if the query caching is enabled).
         if self.config.query_caching == FlagStatus::Enabled {
@@ -226,11 +330,13 @@ impl HttpQueryHandler {
         internal: Arc<dyn QueryHandler<State = ReplicatedState>>,
         query_scheduler: QueryScheduler,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+        query_stats_collector: QueryStatsCollector,
     ) -> QueryExecutionService {
         BoxCloneService::new(Self {
             internal,
             state_reader,
             query_scheduler,
+            query_stats_collector,
         })
     }
 }
@@ -264,6 +370,7 @@ impl Service<(UserQuery, Option<CertificateDelegation>)> for HttpQueryHandler {
     ) -> Self::Future {
         let internal = Arc::clone(&self.internal);
         let state_reader = Arc::clone(&self.state_reader);
+        let query_stats_collector = self.query_stats_collector.clone();
         let (tx, rx) = oneshot::channel();
         let canister_id = query.receiver;
         self.query_scheduler.push(canister_id, move || {
@@ -272,6 +379,7 @@ impl Service<(UserQuery, Option<CertificateDelegation>)> for HttpQueryHandler {
                 // We managed to upgrade the weak pointer, so the query was not cancelled.
                 // Canceling the query after this point will have no effect: the query will
                 // be executed anyway. That is fine because the execution will take O(ms).
+                query_stats_collector.set_epoch_from_height(state_reader.latest_certified_height());
                 let result = match get_latest_certified_state_and_data_certificate(
                     state_reader,
                     certificate_delegation,
@@ -9,7 +9,7 @@ use crate::{
 use ic_base_types::NumBytes;
 use ic_config::flag_status::FlagStatus;
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
-use ic_cycles_account_manager::CyclesAccountManager;
+use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_interfaces::execution_environment::{
     ExecutionComplexity, ExecutionMode, HypervisorError, SubnetAvailableMemory,
@@ -22,7 +22,9 @@ use ic_replicated_state::{
 use ic_system_api::{ApiType, ExecutionParameters, InstructionLimits};
 use ic_types::{
     ingress::WasmResult,
-    messages::{Payload, RejectContext, Request, RequestOrResponse, Response, UserQuery},
+    messages::{
+        Payload, RejectContext, Request, RequestOrResponse, Response, UserQuery, NO_DEADLINE,
+    },
     methods::WasmMethod,
     CanisterId, Cycles, NumInstructions, NumMessages, Time,
 };
@@ -86,6 +88,7 @@ pub(super) struct QueryContext<'a> {
     max_instructions_per_query: NumInstructions,
     max_query_call_graph_depth: usize,
     instruction_overhead_per_query_call: RoundInstructions,
+    max_query_call_graph_instructions: NumInstructions,
     round_limits: RoundLimits,
     composite_queries: FlagStatus,
     // Walltime at which the query has started to execute.
@@ -116,6 +119,8 @@ impl<'a> QueryContext<'a> {
             instructions: as_round_instructions(max_query_call_graph_instructions),
             execution_complexity: ExecutionComplexity::with_cpu(max_query_call_graph_instructions),
             subnet_available_memory,
+            // Ignore storage reservation
+            subnet_memory_saturation: ResourceSaturation::default(),
             // Ignore compute allocation
             compute_allocation_used: 0,
         };
@@ -132,6 +137,7 @@ impl<'a> QueryContext<'a> {
             instruction_overhead_per_query_call: as_round_instructions(
                 instruction_overhead_per_query_call,
             ),
+            max_query_call_graph_instructions,
             round_limits,
             composite_queries,
             query_context_time_start: Instant::now(),
@@ -419,7 +425,7 @@ impl<'a> QueryContext<'a> {
         };
         let func_ref = match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => unreachable!("Unreachable in the QueryContext."),
             CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
                 FuncRef::QueryClosure(closure)
@@ -444,6 +450,7 @@ impl<'a> QueryContext<'a> {
                 call_context_id,
                 call_responded,
                 execution_parameters.execution_mode.clone(),
+                response.deadline,
             ),
             Payload::Reject(context) => ApiType::reject_callback(
                 time,
@@ -452,6 +459,7 @@ impl<'a> QueryContext<'a> {
                 call_context_id,
                 call_responded,
                 execution_parameters.execution_mode.clone(),
+                response.deadline,
             ),
         };
 
@@ -533,7 +541,7 @@ impl<'a> QueryContext<'a> {
     ) -> (NumInstructions, Result<Option<WasmResult>, HypervisorError>) {
         let func_ref = match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => unreachable!("Unreachable in the QueryContext."),
             CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
                 FuncRef::QueryClosure(cleanup_closure)
@@ -594,6 +602,7 @@ impl<'a> QueryContext<'a> {
                 originator_reply_callback: request.sender_reply_callback,
                 response_payload: payload,
                 refund: Cycles::zero(),
+                deadline: request.deadline,
             })
         };
 
@@ -726,7 +735,7 @@ impl<'a> QueryContext<'a> {
             self.execute_callback(canister, response, measurement_scope);
 
         match call_origin {
-            CallOrigin::CanisterUpdate(_, _)
+            CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::Ingress(_, _)
             | CallOrigin::SystemTask => fatal!(
                 self.log,
@@ -753,6 +762,7 @@ impl<'a> QueryContext<'a> {
                         originator_reply_callback: callback_id,
                         refund: Cycles::zero(),
                         response_payload: payload,
+                        deadline: NO_DEADLINE,
                     };
                     QueryResponse::CanisterResponse(response)
                 };
@@ -785,6 +795,17 @@ impl<'a> QueryContext<'a> {
         self.round_limits.reached()
     }
 
+    /// Returns the number of instructions executed by all queries and response
+    /// callbacks of this context so far, including the per-call overhead.
+    pub fn instructions_executed(&self) -> NumInstructions {
+        let instructions_left = self.round_limits.instructions.get().max(0) as u64;
+        NumInstructions::from(
+            self.max_query_call_graph_instructions
+                .get()
+                .saturating_sub(instructions_left),
+        )
+    }
+
     /// Return whether the time limit for this query context has been reached.
     pub fn time_limit_reached(&self) -> bool {
         self.query_context_time_start.elapsed() >= self.query_context_time_limit
@@ -803,7 +824,7 @@ impl<'a> QueryContext<'a> {
         );
         match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => {
                 unreachable!("Expected a query call context");
             }
@@ -815,6 +836,7 @@ impl<'a> QueryContext<'a> {
                     originator_reply_callback: callback_id,
                     refund: Cycles::zero(),
                     response_payload: Payload::Reject(RejectContext::from(error)),
+                    deadline: NO_DEADLINE,
                 };
                 QueryResponse::CanisterResponse(response)
             }
@@ -2,6 +2,10 @@ use crate::InternalHttpQueryHandler;
 use ic_base_types::NumSeconds;
 use ic_config::execution_environment::INSTRUCTION_OVERHEAD_PER_QUERY_CALL;
 use ic_error_types::{ErrorCode, UserError};
+use ic_ic00_types::{
+    CanisterSettingsArgsBuilder, FetchCanisterLogsRequest, FetchCanisterLogsResponse,
+    LogVisibility, Method, Payload, UpdateSettingsArgs,
+};
 use ic_interfaces::messages::CanisterTask;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::canister_state::system_state::CyclesUseCase;
@@ -10,7 +14,10 @@ use ic_test_utilities::{
     universal_canister::{call_args, wasm},
 };
 use ic_test_utilities_execution_environment::{ExecutionTest, ExecutionTestBuilder};
-use ic_types::{ingress::WasmResult, messages::UserQuery, CountBytes, Cycles, NumInstructions};
+use ic_types::{
+    ingress::WasmResult, messages::UserQuery, CanisterId, CountBytes, Cycles, NumInstructions,
+    UserId,
+};
 use std::{sync::Arc, time::Duration};
 
 const CYCLES_BALANCE: Cycles = Cycles::new(100_000_000_000_000);
@@ -1951,3 +1958,96 @@ fn composite_query_state_preserved_across_parallel_calls() {
         ]))
     );
 }
+
+fn fetch_canister_logs(
+    test: &ExecutionTest,
+    sender: UserId,
+    canister_id: CanisterId,
+) -> Result<WasmResult, UserError> {
+    test.query(
+        UserQuery {
+            source: sender,
+            receiver: CanisterId::ic_00(),
+            method_name: Method::FetchCanisterLogs.to_string(),
+            method_payload: FetchCanisterLogsRequest::new(canister_id).encode(),
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    )
+}
+
+#[test]
+fn fetch_canister_logs_returns_debug_prints_and_traps() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+
+    test.ingress(
+        canister_id,
+        "update",
+        wasm().debug_print(b"hello").reply().build(),
+    )
+    .unwrap();
+    test.ingress(
+        canister_id,
+        "update",
+        wasm().debug_print(b"before trap").trap().build(),
+    )
+    .unwrap_err();
+
+    let result = fetch_canister_logs(&test, test.user_id(), canister_id).unwrap();
+    let response = match result {
+        WasmResult::Reply(bytes) => FetchCanisterLogsResponse::decode(&bytes).unwrap(),
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    };
+    let records = response.canister_log_records;
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].content, b"hello".to_vec());
+    assert_eq!(records[1].content, b"before trap".to_vec());
+    assert!(records[2].content.starts_with(b"[TRAP]"));
+    let indices: Vec<_> = records.iter().map(|r| r.idx).collect();
+    assert_eq!(indices, vec![0, 1, 2]);
+}
+
+#[test]
+fn fetch_canister_logs_respects_log_visibility() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let err = fetch_canister_logs(&test, user_test_id(42), canister_id).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterInvalidController);
+
+    let payload = UpdateSettingsArgs {
+        canister_id: canister_id.into(),
+        settings: CanisterSettingsArgsBuilder::new()
+            .with_log_visibility(LogVisibility::Public)
+            .build(),
+        sender_canister_version: None,
+    }
+    .encode();
+    test.subnet_message(Method::UpdateSettings, payload)
+        .unwrap();
+
+    let result = fetch_canister_logs(&test, user_test_id(42), canister_id);
+    assert_eq!(
+        result,
+        Ok(WasmResult::Reply(
+            FetchCanisterLogsResponse::default().encode()
+        ))
+    );
+}
+
+#[test]
+fn fetch_canister_logs_is_rejected_in_replicated_mode() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let err = test
+        .subnet_message(
+            Method::FetchCanisterLogs,
+            FetchCanisterLogsRequest::new(canister_id).encode(),
+        )
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterRejectedMessage);
+}
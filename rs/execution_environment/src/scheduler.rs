@@ -24,7 +24,9 @@ use ic_logger::{debug, error, fatal, info, new_logger, warn, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_replicated_state::{
     canister_state::{
-        execution_state::NextScheduledMethod, system_state::CyclesUseCase, NextExecution,
+        execution_state::NextScheduledMethod,
+        system_state::{CyclesUseCase, OnLowWasmMemoryHookStatus},
+        NextExecution,
     },
     testing::ReplicatedStateTesting,
     CanisterState, CanisterStatus, ExecutionTask, InputQueueType, NetworkTopology, ReplicatedState,
@@ -115,6 +117,7 @@ pub(crate) struct SchedulerImpl {
     rate_limiting_of_heap_delta: FlagStatus,
     rate_limiting_of_instructions: FlagStatus,
     deterministic_time_slicing: FlagStatus,
+    low_wasm_memory_threshold: NumBytes,
 }
 
 impl SchedulerImpl {
@@ -371,6 +374,7 @@ impl SchedulerImpl {
         rate_limiting_of_heap_delta: FlagStatus,
         rate_limiting_of_instructions: FlagStatus,
         deterministic_time_slicing: FlagStatus,
+        low_wasm_memory_threshold: NumBytes,
     ) -> Self {
         let scheduler_cores = config.scheduler_cores as u32;
         Self {
@@ -385,6 +389,7 @@ impl SchedulerImpl {
             rate_limiting_of_heap_delta,
             rate_limiting_of_instructions,
             deterministic_time_slicing,
+            low_wasm_memory_threshold,
         }
     }
 
@@ -579,6 +584,10 @@ impl SchedulerImpl {
                                 break;
                             }
                         }
+                        if try_add_on_low_wasm_memory_task(canister, self.low_wasm_memory_threshold)
+                        {
+                            heartbeat_and_timer_canister_ids.insert(canister.canister_id());
+                        }
                     }
                 }
             }
@@ -592,6 +601,9 @@ impl SchedulerImpl {
 
             // Update subnet available memory before taking out the canisters.
             round_limits.subnet_available_memory = self.exec_env.subnet_available_memory(&state);
+            round_limits.subnet_memory_saturation = self
+                .exec_env
+                .subnet_memory_saturation(&round_limits.subnet_available_memory);
             let canisters = state.take_canister_states();
             // Obtain the active canisters and update the collection of heap delta rate-limited canisters.
             let (active_round_schedule, rate_limited_canister_ids) = round_schedule
@@ -682,12 +694,14 @@ impl SchedulerImpl {
                 .metrics
                 .round_inner_heartbeat_overhead_duration
                 .start_timer();
-            // Remove all remaining `Heartbeat` and `GlobalTimer` tasks
-            // because they will be added again in the next round.
+            // Remove all remaining `Heartbeat`, `GlobalTimer` and `OnLowWasmMemory`
+            // tasks because they will be added again in the next round.
             for canister_id in &heartbeat_and_timer_canister_ids {
                 let canister = state.canister_state_mut(canister_id).unwrap();
                 canister.system_state.task_queue.retain(|task| match task {
-                    ExecutionTask::Heartbeat | ExecutionTask::GlobalTimer => false,
+                    ExecutionTask::Heartbeat
+                    | ExecutionTask::GlobalTimer
+                    | ExecutionTask::OnLowWasmMemory => false,
                     ExecutionTask::PausedExecution(..)
                     | ExecutionTask::PausedInstallCode(..)
                     | ExecutionTask::AbortedExecution { .. }
@@ -789,11 +803,14 @@ impl SchedulerImpl {
             .collect();
 
         // Distribute subnet available memory equally between the threads.
+        // The subnet memory saturation is shared as is because it describes
+        // the whole subnet.
         let round_limits_per_thread = RoundLimits {
             instructions: round_limits.instructions,
             execution_complexity: round_limits.execution_complexity.clone(),
             subnet_available_memory: (round_limits.subnet_available_memory
                 / self.config.scheduler_cores as i64),
+            subnet_memory_saturation: round_limits.subnet_memory_saturation.clone(),
             compute_allocation_used: round_limits.compute_allocation_used,
         };
         // Run canisters in parallel. The results will be stored in `results_by_thread`.
@@ -816,6 +833,9 @@ impl SchedulerImpl {
                     instructions: round_limits.instructions,
                     execution_complexity: round_limits.execution_complexity.clone(),
                     subnet_available_memory: round_limits_per_thread.subnet_available_memory,
+                    subnet_memory_saturation: round_limits_per_thread
+                        .subnet_memory_saturation
+                        .clone(),
                     compute_allocation_used: round_limits.compute_allocation_used,
                 };
                 let config = &self.config;
@@ -1239,8 +1259,8 @@ impl SchedulerImpl {
             .iter()
             .filter(|(_, canister)| !canister.system_state.task_queue.is_empty());
 
-        // 1. Heartbeat and GlobalTimer tasks exist only during the round
-        //    and must not exist after the round.
+        // 1. Heartbeat, GlobalTimer and OnLowWasmMemory tasks exist only
+        //    during the round and must not exist after the round.
         // 2. Paused executions can exist only in ordinary rounds (not checkpoint rounds).
         // 3. If deterministic time slicing is disabled, then there are no paused tasks.
         //    Aborted tasks may still exist if DTS was disabled in recent checkpoints.
@@ -1261,6 +1281,12 @@ impl SchedulerImpl {
                             id
                         );
                     }
+                    ExecutionTask::OnLowWasmMemory => {
+                        panic!(
+                            "Unexpected on low wasm memory task after a round in canister {:?}",
+                            id
+                        );
+                    }
                     ExecutionTask::PausedExecution(_) | ExecutionTask::PausedInstallCode(_) => {
                         assert_eq!(
                             self.deterministic_time_slicing,
@@ -1388,10 +1414,12 @@ impl Scheduler for SchedulerImpl {
 
             for canister in state.canisters_iter_mut() {
                 cycles_in_sum += canister.system_state.balance();
+                cycles_in_sum += canister.system_state.reserved_balance();
                 cycles_in_sum += canister.system_state.queues().input_queue_cycles();
             }
         }
 
+        let subnet_available_memory = self.exec_env.subnet_available_memory(&state);
         let mut round_limits = RoundLimits {
             instructions: as_round_instructions(
                 self.config.max_instructions_per_round / SUBNET_MESSAGES_LIMIT_FRACTION,
@@ -1399,7 +1427,10 @@ impl Scheduler for SchedulerImpl {
             execution_complexity: ExecutionComplexity::with_cpu(
                 self.config.max_instructions_per_round / SUBNET_MESSAGES_LIMIT_FRACTION,
             ),
-            subnet_available_memory: self.exec_env.subnet_available_memory(&state),
+            subnet_memory_saturation: self
+                .exec_env
+                .subnet_memory_saturation(&subnet_available_memory),
+            subnet_available_memory,
             compute_allocation_used: state.total_compute_allocation(),
         };
 
@@ -1555,6 +1586,7 @@ impl Scheduler for SchedulerImpl {
                 total_canister_history_memory_usage += canister.canister_history_memory_usage();
                 total_canister_memory_usage += canister.memory_usage(own_subnet_type);
                 total_canister_balance += canister.system_state.balance();
+                cycles_out_sum += canister.system_state.reserved_balance();
                 cycles_out_sum += canister.system_state.queues().output_queue_cycles();
             }
             cycles_out_sum += total_canister_balance;
@@ -1908,7 +1940,10 @@ fn observe_replicated_state_metrics(
             Some(&ExecutionTask::AbortedInstallCode { .. }) => {
                 num_aborted_install += 1;
             }
-            Some(&ExecutionTask::Heartbeat) | Some(&ExecutionTask::GlobalTimer) | None => {}
+            Some(&ExecutionTask::Heartbeat)
+            | Some(&ExecutionTask::GlobalTimer)
+            | Some(&ExecutionTask::OnLowWasmMemory)
+            | None => {}
         }
         consumed_cycles_total += canister
             .system_state
@@ -2083,7 +2118,9 @@ fn can_execute_msg(
         };
 
         // Only one install code message allowed at a time.
-        if let Some(Ic00Method::InstallCode) = maybe_instal_code_method {
+        if let Some(Ic00Method::InstallCode) | Some(Ic00Method::InstallChunkedCode) =
+            maybe_instal_code_method
+        {
             return false;
         }
     }
@@ -2132,6 +2169,14 @@ fn get_instructions_limits_for_subnet_message(
             | StopCanister
             | UninstallCode
             | UpdateSettings
+            | TakeCanisterSnapshot
+            | LoadCanisterSnapshot
+            | ListCanisterSnapshots
+            | DeleteCanisterSnapshot
+            | FetchCanisterLogs
+            | UploadChunk
+            | StoredChunks
+            | ClearChunkStore
             | BitcoinGetBalance
             | BitcoinGetUtxos
             | BitcoinSendTransaction
@@ -2140,7 +2185,7 @@ fn get_instructions_limits_for_subnet_message(
             | BitcoinGetSuccessors
             | ProvisionalCreateCanisterWithCycles
             | ProvisionalTopUpCanister => default_limits,
-            InstallCode => InstructionLimits::new(
+            InstallCode | InstallChunkedCode => InstructionLimits::new(
                 dts,
                 config.max_instructions_per_install_code,
                 config.max_instructions_per_install_code_slice,
@@ -2183,6 +2228,36 @@ fn is_next_method_chosen(
     method_chosen
 }
 
+/// Updates the status of the `canister_on_low_wasm_memory` hook and adds the
+/// `OnLowWasmMemory` task to the front of the task queue if the hook is ready
+/// to run. The hook runs once each time the free Wasm heap drops below the
+/// threshold. Returns true if the task was added.
+fn try_add_on_low_wasm_memory_task(
+    canister: &mut CanisterState,
+    low_wasm_memory_threshold: NumBytes,
+) -> bool {
+    if !canister.exports_on_low_wasm_memory_method() {
+        return false;
+    }
+    let is_low_on_wasm_memory = canister.is_low_on_wasm_memory(low_wasm_memory_threshold);
+    let status = &mut canister.system_state.on_low_wasm_memory_hook_status;
+    *status = match (*status, is_low_on_wasm_memory) {
+        (_, false) => OnLowWasmMemoryHookStatus::ConditionNotSatisfied,
+        (OnLowWasmMemoryHookStatus::ConditionNotSatisfied, true) => {
+            OnLowWasmMemoryHookStatus::Ready
+        }
+        (status, true) => status,
+    };
+    if *status == OnLowWasmMemoryHookStatus::Ready {
+        canister
+            .system_state
+            .task_queue
+            .push_front(ExecutionTask::OnLowWasmMemory);
+        return true;
+    }
+    false
+}
+
 fn try_add_tasks(
     canister: &mut CanisterState,
     scheduled_task: ExecutionTask,
@@ -2214,7 +2289,8 @@ fn should_add_task(
         ExecutionTask::GlobalTimer => {
             global_timer_has_reached_deadline && canister.exports_global_timer_method()
         }
-        ExecutionTask::AbortedExecution { .. }
+        ExecutionTask::OnLowWasmMemory
+        | ExecutionTask::AbortedExecution { .. }
         | ExecutionTask::AbortedInstallCode { .. }
         | ExecutionTask::PausedExecution(..)
         | ExecutionTask::PausedInstallCode(..) => unreachable!("Unexpected ExecutionTask variant."),
@@ -2225,7 +2301,8 @@ fn get_other_task(task: ExecutionTask) -> ExecutionTask {
     match task {
         ExecutionTask::Heartbeat => ExecutionTask::GlobalTimer,
         ExecutionTask::GlobalTimer => ExecutionTask::Heartbeat,
-        ExecutionTask::AbortedExecution { .. }
+        ExecutionTask::OnLowWasmMemory
+        | ExecutionTask::AbortedExecution { .. }
         | ExecutionTask::AbortedInstallCode { .. }
         | ExecutionTask::PausedExecution(..)
         | ExecutionTask::PausedInstallCode(..) => unreachable!("Unexpected ExecutionTask variant."),
@@ -50,7 +50,9 @@ use ic_test_utilities_execution_environment::{generate_subnets, test_registry_se
 use ic_types::{
     crypto::{canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId},
     ingress::{IngressState, IngressStatus},
-    messages::{CallContextId, Ingress, MessageId, Request, RequestOrResponse, Response},
+    messages::{
+        CallContextId, Ingress, MessageId, Request, RequestOrResponse, Response, NO_DEADLINE,
+    },
     methods::{Callback, FuncRef, SystemMethod, WasmClosure, WasmMethod},
     CanisterTimer, ComputeAllocation, Cycles, ExecutionRound, MemoryAllocation, NumInstructions,
     Randomness, Time, UserId,
@@ -504,6 +506,7 @@ impl SchedulerTest {
             &Randomness::from([0; 32]),
             &ExecutionThread(self.scheduler.config.scheduler_cores as u32),
         );
+        let subnet_available_memory = self.scheduler.exec_env.subnet_available_memory(&state);
         let mut round_limits = RoundLimits {
             instructions: as_round_instructions(
                 self.scheduler.config.max_instructions_per_round / 16,
@@ -511,7 +514,11 @@ impl SchedulerTest {
             execution_complexity: ExecutionComplexity::with_cpu(
                 self.scheduler.config.max_instructions_per_round / 16,
             ),
-            subnet_available_memory: self.scheduler.exec_env.subnet_available_memory(&state),
+            subnet_memory_saturation: self
+                .scheduler
+                .exec_env
+                .subnet_memory_saturation(&subnet_available_memory),
+            subnet_available_memory,
             compute_allocation_used,
         };
         let measurements = MeasurementScope::root(&self.scheduler.metrics.round_subnet_queue);
@@ -830,6 +837,7 @@ impl SchedulerTestBuilder {
             IngressHistoryWriterImpl::new(config.clone(), self.log.clone(), &self.metrics_registry);
         let ingress_history_writer: Arc<dyn IngressHistoryWriter<State = ReplicatedState>> =
             Arc::new(ingress_history_writer);
+        let low_wasm_memory_threshold = config.low_wasm_memory_threshold;
         let exec_env = ExecutionEnvironment::new(
             self.log.clone(),
             hypervisor,
@@ -852,6 +860,7 @@ impl SchedulerTestBuilder {
             rate_limiting_of_heap_delta,
             rate_limiting_of_instructions,
             deterministic_time_slicing,
+            low_wasm_memory_threshold,
         );
         SchedulerTest {
             state: Some(state),
@@ -1116,6 +1125,7 @@ impl TestWasmExecutorCore {
                 allocated_bytes: NumBytes::from(0),
                 allocated_message_bytes: NumBytes::from(0),
                 instance_stats: InstanceStats::default(),
+                canister_log: Default::default(),
             };
             self.schedule
                 .push((self.round, canister_id, instructions_to_execute));
@@ -1144,6 +1154,7 @@ impl TestWasmExecutorCore {
         let instance_stats = InstanceStats {
             accessed_pages: message.dirty_pages,
             dirty_pages: message.dirty_pages,
+            stable_dirty_pages: 0,
             read_before_write_count: message.dirty_pages,
             direct_write_count: 0,
         };
@@ -1157,6 +1168,7 @@ impl TestWasmExecutorCore {
             allocated_message_bytes: NumBytes::from(0),
             num_instructions_left: instructions_left,
             instance_stats,
+            canister_log: Default::default(),
         };
         self.schedule
             .push((self.round, canister_id, instructions_to_execute));
@@ -1253,6 +1265,7 @@ impl TestWasmExecutorCore {
                 on_reply: closure.clone(),
                 on_reject: closure,
                 on_cleanup: None,
+                deadline: NO_DEADLINE,
             })
             .map_err(|err| err.to_string())?;
         let request = Request {
@@ -1262,6 +1275,7 @@ impl TestWasmExecutorCore {
             payment: Cycles::zero(),
             method_name: "update".into(),
             method_payload: encode_message_id_as_payload(call_message_id),
+            deadline: NO_DEADLINE,
         };
         if let Err(req) = system_state.push_output_request(
             canister_current_memory_usage,
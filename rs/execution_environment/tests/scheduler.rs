@@ -1,4 +1,8 @@
-use ic_state_machine_tests::{Cycles, IngressStatus, PrincipalId, StateMachine};
+use ic_config::{execution_environment::Config as HypervisorConfig, subnet_config::SubnetConfigs};
+use ic_registry_subnet_type::SubnetType;
+use ic_state_machine_tests::{
+    Cycles, IngressStatus, PrincipalId, StateMachine, StateMachineConfig,
+};
 use ic_test_utilities_metrics::fetch_int_counter_vec;
 use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
 use maplit::btreemap;
@@ -83,3 +87,46 @@ fn scheduler_observes_inducted_messages_to_others() {
     // Call and reply
     assert_eq!(2, inducted_messages[&destination_others]);
 }
+
+#[test]
+fn scheduler_does_not_reserve_cycles_for_storage_on_an_empty_subnet() {
+    let mut subnet_config = SubnetConfigs::default().own_subnet_config(SubnetType::Application);
+    subnet_config.scheduler_config.scheduler_cores = 4;
+    let hypervisor_config = HypervisorConfig::default();
+    // Each execution thread sees only its share of the available memory, which
+    // would put an empty subnet above the threshold if the share was mistaken
+    // for the memory available on the whole subnet.
+    let capacity = hypervisor_config.subnet_memory_capacity.get();
+    assert!(
+        capacity - capacity / subnet_config.scheduler_config.scheduler_cores as u64
+            > hypervisor_config.subnet_memory_threshold.get()
+    );
+    let sm =
+        StateMachine::new_with_config(StateMachineConfig::new(subnet_config, hypervisor_config));
+
+    let canister_id = sm
+        .install_canister_with_cycles(
+            UNIVERSAL_CANISTER_WASM.into(),
+            vec![],
+            None,
+            INITIAL_CYCLES_BALANCE,
+        )
+        .unwrap();
+    let memory_usage_before = sm
+        .get_latest_state()
+        .canister_state(&canister_id)
+        .unwrap()
+        .memory_usage(SubnetType::Application);
+
+    sm.execute_ingress(
+        canister_id,
+        "update",
+        wasm().stable_grow(100).reply().build(),
+    )
+    .unwrap();
+
+    let state = sm.get_latest_state();
+    let canister = state.canister_state(&canister_id).unwrap();
+    assert!(canister.memory_usage(SubnetType::Application) > memory_usage_before);
+    assert_eq!(canister.system_state.reserved_balance(), Cycles::zero());
+}
@@ -127,6 +127,8 @@ pub(crate) fn make_response(user_error: UserError) -> Response<Body> {
         C::CompositeQueryCalledInReplicatedMode => StatusCode::INTERNAL_SERVER_ERROR,
         C::CanisterNotHostedBySubnet => StatusCode::NOT_FOUND,
         C::QueryTimeLimitExceeded => StatusCode::INTERNAL_SERVER_ERROR,
+        C::ReservedCyclesLimitExceededInMemoryAllocation => StatusCode::SERVICE_UNAVAILABLE,
+        C::ReservedCyclesLimitExceededInMemoryGrow => StatusCode::SERVICE_UNAVAILABLE,
     };
     make_plaintext_response(status, user_error.description().to_string())
 }
@@ -1,9 +1,10 @@
 use crate::{
     canister_http::CanisterHttpPayloadValidationError,
     ingress_manager::IngressPayloadValidationError, messaging::XNetPayloadValidationError,
+    query_stats::QueryStatsPayloadValidationError,
     self_validating_payload::SelfValidatingPayloadValidationError, validation::ValidationResult,
 };
-use ic_base_types::NumBytes;
+use ic_base_types::{NodeId, NumBytes};
 use ic_types::{
     batch::ValidationContext, consensus::BlockPayload, crypto::CryptoHashOf, Height, Time,
 };
@@ -15,6 +16,7 @@ pub enum BatchPayloadValidationError {
     XNet(XNetPayloadValidationError),
     Bitcoin(SelfValidatingPayloadValidationError),
     CanisterHttp(CanisterHttpPayloadValidationError),
+    QueryStats(QueryStatsPayloadValidationError),
 }
 
 impl BatchPayloadValidationError {
@@ -24,6 +26,7 @@ impl BatchPayloadValidationError {
             Self::XNet(e) => e.is_transient(),
             Self::Bitcoin(e) => e.is_transient(),
             Self::CanisterHttp(e) => e.is_transient(),
+            Self::QueryStats(e) => e.is_transient(),
         }
     }
 }
@@ -51,6 +54,15 @@ pub struct PastPayload<'a> {
     pub payload: &'a [u8],
 }
 
+/// The context under which a block proposal, and therefore its payload, is
+/// validated.
+pub struct ProposalContext<'a> {
+    /// The node that made the block proposal
+    pub proposer: NodeId,
+    /// The [`ValidationContext`] of the block proposal
+    pub validation_context: &'a ValidationContext,
+}
+
 /// Indicates that this component can build batch payloads.
 ///
 /// A batch payload has the following properties:
@@ -62,7 +74,7 @@ pub struct PastPayload<'a> {
 /// # Ordering
 /// The `past_payloads` in [`BatchPayloadBuilder::build_payload`] and
 /// [`BatchPayloadBuilder::validate_payload`] MUST be in descending `height` order.
-pub trait BatchPayloadBuilder: Send {
+pub trait BatchPayloadBuilder: Send + Sync {
     /// Builds a payload and returns it in serialized form.
     ///
     /// # Arguments
@@ -87,7 +99,7 @@ pub trait BatchPayloadBuilder: Send {
     /// - `payload`: The payload to validate
     /// - `past_payloads`: A collection of past payloads. Allows the payload builder
     ///     to deduplicate messages
-    /// - `context`: [`ValidationContext`] under which to validate the payload
+    /// - `proposal_context`: [`ProposalContext`] under which to validate the payload
     ///
     /// # Returns
     ///
@@ -97,7 +109,7 @@ pub trait BatchPayloadBuilder: Send {
         &self,
         payload: &[u8],
         past_payloads: &[PastPayload],
-        context: &ValidationContext,
+        proposal_context: &ProposalContext,
     ) -> ValidationResult<BatchPayloadValidationError>;
 }
 
@@ -9,6 +9,7 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_subnet_type::SubnetType;
 use ic_sys::{PageBytes, PageIndex};
 use ic_types::{
+    canister_log::CanisterLog,
     crypto::canister_threshold_sig::MasterEcdsaPublicKey,
     ingress::{IngressStatus, WasmResult},
     messages::{
@@ -38,6 +39,10 @@ pub struct InstanceStats {
     /// hence this dirtied_pages <= accessed_pages
     pub dirty_pages: usize,
 
+    /// Number of (host) pages of stable memory modified by the instance.
+    /// These pages are also counted in `dirty_pages`.
+    pub stable_dirty_pages: usize,
+
     /// Number of times a write access is handled when the page has already been
     /// read.
     pub read_before_write_count: usize,
@@ -429,6 +434,10 @@ pub trait SystemApi {
     /// Returns the subnet type the replica runs on.
     fn subnet_type(&self) -> SubnetType;
 
+    /// Returns the upper limit on the Wasm heap memory of the canister, if
+    /// any. Growing the Wasm heap beyond the limit fails.
+    fn wasm_memory_limit(&self) -> Option<NumBytes>;
+
     /// Returns the message instruction limit, which is the total instruction
     /// limit for all slices combined.
     fn message_instruction_limit(&self) -> NumInstructions;
@@ -513,6 +522,12 @@ pub trait SystemApi {
     /// Replies to sender with an error message
     fn ic0_msg_reject(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
+    /// Returns the deadline of the current call (or of the response, when
+    /// invoked from a callback), in nanoseconds since the Unix epoch.
+    ///
+    /// It returns the special value 0 if the call has a guaranteed response.
+    fn ic0_msg_deadline(&self) -> HypervisorResult<u64>;
+
     /// Returns the length of the reject message in bytes.
     ///
     /// # Panics
@@ -565,6 +580,10 @@ pub trait SystemApi {
     /// Outputs the specified bytes on the heap as a string on STDOUT.
     fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
+    /// Appends the specified bytes on the heap to the log of the canister.
+    /// Like `ic0_debug_print`, this never fails.
+    fn save_log_message(&mut self, src: u32, size: u32, heap: &[u8]);
+
     /// Traps, with a possibly helpful message
     fn ic0_trap(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
@@ -603,6 +622,11 @@ pub trait SystemApi {
     /// See https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-call
     fn ic0_call_on_cleanup(&mut self, fun: u32, env: u32) -> HypervisorResult<()>;
 
+    /// Turns the call under construction into a best-effort call that times
+    /// out after `timeout_seconds` (capped at a system-defined maximum). Can
+    /// be called at most once between `ic0.call_new` and `ic0.call_perform`.
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()>;
+
     /// (deprecated) Please use `ic0_call_cycles_add128` instead, as this API
     /// can only add a 64-bit value.
     ///
@@ -942,6 +966,54 @@ pub trait SystemApi {
     ///
     /// This system call traps if src+size exceeds the size of the WebAssembly memory.
     fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32>;
+
+    /// Copies to `dst` the amount of cycles that the canister would be
+    /// charged for an inter-canister call with a method name of
+    /// `method_name_size` bytes and a payload of `payload_size` bytes,
+    /// including the prepayment for the response.
+    ///
+    /// The amount is represented by a 128-bit value and scaled for the size
+    /// of the current subnet.
+    fn ic0_cost_call(
+        &self,
+        method_name_size: u64,
+        payload_size: u64,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the fee for creating a canister on the current subnet
+    /// as a 128-bit value.
+    fn ic0_cost_create_canister(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the fee for an HTTP outcall with a request of
+    /// `request_size` bytes and a response limit of `max_res_bytes` bytes
+    /// as a 128-bit value.
+    fn ic0_cost_http_request(
+        &self,
+        request_size: u64,
+        max_res_bytes: u64,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the fee for a threshold ECDSA signature with the key
+    /// identified by the name at src/size and the given curve as a 128-bit
+    /// value, scaled for the size of the subnet signing with the key.
+    ///
+    /// Returns 0 on success, 1 if the curve is not supported and 2 if no
+    /// subnet signs with the key, in which case nothing is copied to `dst`.
+    ///
+    /// This system call traps if src+size or dst+16 exceeds the size of the
+    /// WebAssembly memory.
+    fn ic0_cost_sign_with_ecdsa(
+        &self,
+        src: u32,
+        size: u32,
+        curve: u32,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<u32>;
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -1029,6 +1101,9 @@ pub struct WasmExecutionOutput {
     pub allocated_bytes: NumBytes,
     pub allocated_message_bytes: NumBytes,
     pub instance_stats: InstanceStats,
+    /// Log records produced during the execution. They are appended to the
+    /// log of the canister even if the execution failed.
+    pub canister_log: CanisterLog,
 }
 
 impl fmt::Display for WasmExecutionOutput {
@@ -1,6 +1,6 @@
 use ic_base_types::{CanisterIdError, PrincipalIdBlobParseError};
 use ic_error_types::UserError;
-use ic_types::{methods::WasmMethod, CanisterId, CountBytes, Cycles, NumInstructions};
+use ic_types::{methods::WasmMethod, CanisterId, CountBytes, Cycles, NumBytes, NumInstructions};
 use ic_wasm_types::{WasmEngineError, WasmInstrumentationError, WasmValidationError};
 use serde::{Deserialize, Serialize};
 
@@ -135,6 +135,20 @@ pub enum HypervisorError {
     },
     /// A canister has written too much new data in a single message.
     MemoryAccessLimitExceeded(String),
+    /// The canister does not have enough cycles to reserve for the storage
+    /// of the memory it allocated.
+    InsufficientCyclesInMemoryGrow {
+        bytes: NumBytes,
+        available: Cycles,
+        requested: Cycles,
+    },
+    /// Reserving cycles for the memory the canister allocated would exceed
+    /// its reserved cycles limit.
+    ReservedCyclesLimitExceededInMemoryGrow {
+        bytes: NumBytes,
+        requested: Cycles,
+        limit: Cycles,
+    },
 }
 
 impl From<WasmInstrumentationError> for HypervisorError {
@@ -320,6 +334,18 @@ impl HypervisorError {
                 format!("Canister exceeded memory access limits: {}", s)
 
             ),
+            Self::InsufficientCyclesInMemoryGrow {bytes, available, requested} => UserError::new(
+                E::CanisterOutOfCycles,
+                format!("Canister {} cannot grow memory by {} bytes due to insufficient cycles. \
+                At least {} cycles are required to reserve for storage, but only {} are available.",
+                canister_id, bytes, requested, available),
+            ),
+            Self::ReservedCyclesLimitExceededInMemoryGrow {bytes, requested, limit} => UserError::new(
+                E::ReservedCyclesLimitExceededInMemoryGrow,
+                format!("Canister {} cannot grow memory by {} bytes due to its reserved cycles limit. \
+                The current limit ({}) would be exceeded by {}.",
+                canister_id, bytes, limit, requested - limit),
+            ),
         }
     }
 
@@ -350,6 +376,12 @@ impl HypervisorError {
             HypervisorError::Aborted => "Aborted",
             HypervisorError::SliceOverrun { .. } => "SliceOverrun",
             HypervisorError::MemoryAccessLimitExceeded(_) => "MemoryAccessLimitExceeded",
+            HypervisorError::InsufficientCyclesInMemoryGrow { .. } => {
+                "InsufficientCyclesInMemoryGrow"
+            }
+            HypervisorError::ReservedCyclesLimitExceededInMemoryGrow { .. } => {
+                "ReservedCyclesLimitExceededInMemoryGrow"
+            }
         }
     }
 }
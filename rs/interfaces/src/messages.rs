@@ -1,8 +1,9 @@
 //! Messages used in various components.
+use ic_ic00_types::CanisterChangeOrigin;
 use ic_types::{
-    messages::{Ingress, Request, Response, StopCanisterContext},
+    messages::{Ingress, Request, Response, StopCanisterContext, NO_DEADLINE},
     methods::SystemMethod,
-    CanisterId, Cycles, PrincipalId,
+    CanisterId, CoarseTime, Cycles, PrincipalId,
 };
 use std::{convert::TryFrom, sync::Arc};
 
@@ -78,6 +79,15 @@ impl CanisterCall {
         }
     }
 
+    /// Returns the deadline of this message, `NO_DEADLINE` unless it is the
+    /// request of a best-effort call.
+    pub fn deadline(&self) -> CoarseTime {
+        match self {
+            CanisterCall::Request(request) => request.deadline,
+            CanisterCall::Ingress(_) => NO_DEADLINE,
+        }
+    }
+
     /// Extracts the cycles received with this message.
     pub fn take_cycles(&mut self) -> Cycles {
         match self {
@@ -85,6 +95,20 @@ impl CanisterCall {
             CanisterCall::Ingress(_) => Cycles::zero(),
         }
     }
+
+    /// Returns the origin of the canister change performed by this message,
+    /// given the canister version that the sender specified in the payload.
+    pub fn canister_change_origin(
+        &self,
+        sender_canister_version: Option<u64>,
+    ) -> CanisterChangeOrigin {
+        match self {
+            CanisterCall::Request(request) => {
+                CanisterChangeOrigin::from_canister(request.sender.get(), sender_canister_version)
+            }
+            CanisterCall::Ingress(ingress) => CanisterChangeOrigin::from_user(ingress.source.get()),
+        }
+    }
 }
 
 impl From<CanisterCall> for StopCanisterContext {
@@ -117,11 +141,13 @@ impl TryFrom<CanisterMessage> for CanisterCall {
 }
 
 /// A canister task can be thought of as a special system message that the IC
-/// sends to the canister to execute its heartbeat or the global timer method.
+/// sends to the canister to execute its heartbeat, the global timer or the
+/// low Wasm memory hook method.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CanisterTask {
     Heartbeat,
     GlobalTimer,
+    OnLowWasmMemory,
 }
 
 impl From<CanisterTask> for SystemMethod {
@@ -129,6 +155,7 @@ impl From<CanisterTask> for SystemMethod {
         match task {
             CanisterTask::Heartbeat => SystemMethod::CanisterHeartbeat,
             CanisterTask::GlobalTimer => SystemMethod::CanisterGlobalTimer,
+            CanisterTask::OnLowWasmMemory => SystemMethod::CanisterOnLowWasmMemory,
         }
     }
 }
@@ -138,6 +165,7 @@ impl Display for CanisterTask {
         match self {
             Self::Heartbeat => write!(f, "Heartbeat task"),
             Self::GlobalTimer => write!(f, "Global timer task"),
+            Self::OnLowWasmMemory => write!(f, "On low Wasm memory task"),
         }
     }
 }
@@ -0,0 +1,36 @@
+//! Query statistics related public interfaces.
+use crate::validation::ValidationError;
+use ic_interfaces_state_manager::StateManagerError;
+use ic_types::{batch::QueryStatsEpoch, CanisterId, Height, NodeId};
+
+/// A QueryStatsPayload error from which it is not possible to recover.
+#[derive(Debug)]
+pub enum InvalidQueryStatsPayloadReason {
+    /// The payload could not be deserialized
+    DeserializationFailed(String),
+    /// The payload contains statistics of a node other than the block maker
+    InvalidNodeId { expected: NodeId, reported: NodeId },
+    /// The epoch of the payload has not been completed at the certified height
+    EpochNotCompleted {
+        epoch: QueryStatsEpoch,
+        certified_epoch: QueryStatsEpoch,
+    },
+    /// The epoch of the payload has already been aggregated into the state
+    EpochAlreadyAggregated {
+        epoch: QueryStatsEpoch,
+        highest_aggregated_epoch: QueryStatsEpoch,
+    },
+    /// The statistics of this canister have already been reported by the node
+    DuplicateCanisterId(CanisterId),
+}
+
+/// A QueryStatsPayload error from which it may be possible to recover.
+#[derive(Debug)]
+pub enum QueryStatsTransientValidationError {
+    /// The state was not available at the certified height
+    StateUnavailable(Height, StateManagerError),
+}
+
+/// A QueryStatsPayload error that results from payload validation.
+pub type QueryStatsPayloadValidationError =
+    ValidationError<InvalidQueryStatsPayloadReason, QueryStatsTransientValidationError>;
@@ -1,6 +1,7 @@
 use crate::{routing::stream_handler::StreamHandler, scheduling::valid_set_rule::ValidSetRule};
 use ic_interfaces_certified_stream_store::CertifiedStreamStore;
 use ic_logger::{debug, trace, ReplicaLogger};
+use ic_query_stats::deliver_query_stats;
 use ic_replicated_state::ReplicatedState;
 use ic_types::{batch::BatchMessages, messages::SignedIngressContent};
 use std::sync::Arc;
@@ -80,6 +81,10 @@ impl<'a> Demux for DemuxImpl<'a> {
             });
         }
 
+        if let Some(query_stats) = &batch_messages.query_stats {
+            deliver_query_stats(query_stats, &mut state, &self.log);
+        }
+
         state
     }
 }
@@ -46,6 +46,9 @@ struct StreamHandlerMetrics {
     pub gced_xnet_messages: IntCounter,
     /// Garbage collected XNet reject signals.
     pub gced_xnet_reject_signals: IntCounter,
+    /// Best-effort requests dropped without a reject response, because they
+    /// could not be inducted due to lack of queue slots or memory.
+    pub shed_best_effort_requests: IntCounter,
     /// Backlog of XNet messages based on end in stream header and last message
     /// in slice, per subnet.
     pub xnet_message_backlog: IntGaugeVec,
@@ -69,6 +72,7 @@ const METRIC_INDUCTED_XNET_MESSAGES: &str = "mr_inducted_xnet_message_count";
 const METRIC_INDUCTED_XNET_PAYLOAD_SIZES: &str = "mr_inducted_xnet_payload_size_bytes";
 const METRIC_GCED_XNET_MESSAGES: &str = "mr_gced_xnet_message_count";
 const METRIC_GCED_XNET_REJECT_SIGNALS: &str = "mr_gced_xnet_reject_signal_count";
+const METRIC_SHED_BEST_EFFORT_REQUESTS: &str = "mr_shed_best_effort_request_count";
 
 const METRIC_XNET_MESSAGE_BACKLOG: &str = "mr_xnet_message_backlog";
 
@@ -77,6 +81,7 @@ const LABEL_VALUE_SUCCESS: &str = "success";
 const LABEL_VALUE_SENDER_SUBNET_MISMATCH: &str = "SenderSubnetMismatch";
 const LABEL_VALUE_RECEIVER_SUBNET_MISMATCH: &str = "ReceiverSubnetMismatch";
 const LABEL_VALUE_CANISTER_MIGRATED: &str = "CanisterMigrated";
+const LABEL_VALUE_DEADLINE_EXPIRED: &str = "DeadlineExpired";
 const LABEL_TYPE: &str = "type";
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
@@ -111,6 +116,10 @@ impl StreamHandlerMetrics {
             METRIC_GCED_XNET_REJECT_SIGNALS,
             "Garbage collected XNet reject signals.",
         );
+        let shed_best_effort_requests = metrics_registry.int_counter(
+            METRIC_SHED_BEST_EFFORT_REQUESTS,
+            "Best-effort requests dropped due to lack of queue slots or memory.",
+        );
         let xnet_message_backlog = metrics_registry.int_gauge_vec(
             METRIC_XNET_MESSAGE_BACKLOG,
             "Backlog of XNet messages, by sending subnet.",
@@ -138,6 +147,7 @@ impl StreamHandlerMetrics {
                 LABEL_VALUE_SENDER_SUBNET_MISMATCH,
                 LABEL_VALUE_RECEIVER_SUBNET_MISMATCH,
                 LABEL_VALUE_CANISTER_MIGRATED,
+                LABEL_VALUE_DEADLINE_EXPIRED,
                 LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
                 LABEL_VALUE_INVALID_SUBNET_PAYLOAD,
             ] {
@@ -150,6 +160,7 @@ impl StreamHandlerMetrics {
             inducted_xnet_payload_sizes,
             gced_xnet_messages,
             gced_xnet_reject_signals,
+            shed_best_effort_requests,
             xnet_message_backlog,
             critical_error_reject_signals_for_request,
             critical_error_induct_response_failed,
@@ -567,6 +578,8 @@ impl StreamHandlerImpl {
     ///  * `Request` not inducted (queue full, out of memory, canister not
     ///    found, canister migrated): accept signal and reject response appended
     ///    to the reverse stream;
+    ///  * best-effort `Request` past its deadline: accept signal and
+    ///    `SYS_UNKNOWN` reject response appended to the reverse stream;
     ///  * `Response` not inducted (canister migrated): reject signal appended
     ///    to loopback stream (canonical versions 9+ only).
     ///  * `Request` or `Response` silently dropped and accept signal appended
@@ -576,7 +589,8 @@ impl StreamHandlerImpl {
     ///     * the receiver is not hosted by or being migrated off of this
     ///       subnet; or
     ///     * enqueuing a `Response` failed due to the canister having been
-    ///       removed.
+    ///       removed; or
+    ///     * enqueuing a best-effort `Response` failed for any reason.
     ///
     /// Updates `subnet_available_memory` to reflect any change in memory usage.
     fn induct_message(
@@ -605,6 +619,22 @@ impl StreamHandlerImpl {
 
             let payload_size = msg.payload_size_bytes().get();
             match receiver_host_subnet {
+                // Best-effort request whose deadline has already expired, reject it.
+                Some(host_subnet)
+                    if host_subnet == self.subnet_id && has_expired_deadline(&msg, state) =>
+                {
+                    self.observe_inducted_message_status(msg_type, LABEL_VALUE_DEADLINE_EXPIRED);
+                    debug!(
+                        self.log,
+                        "Deadline expired, generating reject response for {:?}", msg
+                    );
+                    stream.push(generate_reject_response(
+                        msg,
+                        RejectCode::SysUnknown,
+                        "Request deadline expired.".to_string(),
+                    ));
+                }
+
                 // Matching receiver subnet, try inducting message.
                 Some(host_subnet) if host_subnet == self.subnet_id => match state.push_input(
                     msg,
@@ -622,6 +652,26 @@ impl StreamHandlerImpl {
                         self.observe_inducted_message_status(msg_type, err.to_label_value());
 
                         match msg {
+                            // Shed load: best-effort requests that do not fit into the
+                            // receiver's queue or memory are dropped without a reject
+                            // response. The caller will time out the call with a
+                            // `SYS_UNKNOWN` reject response once its deadline expires.
+                            RequestOrResponse::Request(request)
+                                if request.is_best_effort()
+                                    && matches!(
+                                        err,
+                                        StateError::QueueFull { .. }
+                                            | StateError::OutOfMemory { .. }
+                                    ) =>
+                            {
+                                self.metrics.shed_best_effort_requests.inc();
+                                debug!(
+                                    self.log,
+                                    "Induction failed with error '{}', shedding best-effort request {:?}",
+                                    &err,
+                                    request
+                                );
+                            }
                             RequestOrResponse::Request(_) => {
                                 debug!(
                                     self.log,
@@ -632,6 +682,18 @@ impl StreamHandlerImpl {
                                 let code = reject_code_for_state_error(&err);
                                 stream.push(generate_reject_response(msg, code, err.to_string()))
                             }
+                            RequestOrResponse::Response(response) if response.is_best_effort() => {
+                                // Best-effort responses may be dropped: the callback
+                                // is timed out with a `SYS_UNKNOWN` reject response
+                                // once its deadline expires (or already was, if this
+                                // is a late response).
+                                debug!(
+                                    self.log,
+                                    "Induction failed with error '{}', dropping best-effort response {:?}",
+                                    &err,
+                                    response
+                                );
+                            }
                             RequestOrResponse::Response(response) => {
                                 // Critical error, responses should always be inducted successfully.
                                 error!(
@@ -859,6 +921,7 @@ fn generate_reject_response(
                 message,
                 MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
             )),
+            deadline: msg.deadline,
         }
         .into()
     } else {
@@ -866,6 +929,15 @@ fn generate_reject_response(
     }
 }
 
+/// Returns `true` if `msg` is a best-effort `Request` whose deadline has
+/// expired as of the current state time.
+fn has_expired_deadline(msg: &RequestOrResponse, state: &ReplicatedState) -> bool {
+    match msg {
+        RequestOrResponse::Request(request) => request.has_expired(state.time()),
+        RequestOrResponse::Response(_) => false,
+    }
+}
+
 /// Maps a `StateError` resulting from a failed induction to a `RejectCode`.
 fn reject_code_for_state_error(err: &StateError) -> RejectCode {
     match err {
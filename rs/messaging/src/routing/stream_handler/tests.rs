@@ -32,7 +32,7 @@ use ic_test_utilities_metrics::{
 use ic_types::{
     messages::{CallbackId, Payload, Request, MAX_RESPONSE_COUNT_BYTES},
     xnet::{testing::StreamSliceTesting, StreamIndex, StreamIndexedQueue},
-    CanisterId, Cycles,
+    CanisterId, CoarseTime, Cycles, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreemap;
@@ -1322,6 +1322,7 @@ fn generate_reject_response_queue_full() {
                 RejectCode::SysTransient,
                 err.to_string(),
             )),
+            deadline: msg.deadline,
         }
         .into(),
     );
@@ -1362,6 +1363,7 @@ fn generate_reject_response_canister_not_found() {
                 RejectCode::DestinationInvalid,
                 err.to_string(),
             )),
+            deadline: msg.deadline,
         }
         .into(),
     );
@@ -1594,6 +1596,137 @@ fn induct_stream_slices_response_to_missing_canister() {
     });
 }
 
+/// Tests that a best-effort response addressed to a missing canister is
+/// silently dropped, without incrementing the critical error count.
+#[test]
+fn induct_stream_slices_best_effort_response_to_missing_canister() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        let mut expected_state = initial_state.clone();
+
+        // Initial state with no canisters and one stream.
+        let outgoing_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 21,
+            message_count: 0,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => outgoing_stream]);
+
+        // Incoming slice with one best-effort response addressed to a missing canister.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 21,
+            reject_signals: None,
+        });
+        let mut response = test_response(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        response.deadline = CoarseTime::from_secs_since_unix_epoch(17);
+        stream_slice.push_message(response.into());
+
+        // The expected stream should have `signals_end` incremented for the 1 dropped message.
+        let expected_outgoing_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 21,
+            message_count: 0,
+            signals_end: 44,
+            reject_signals: None,
+        });
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_outgoing_stream]);
+
+        let inducted_state = stream_handler
+            .induct_stream_slices(initial_state, btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[(
+                &[
+                    (LABEL_TYPE, LABEL_VALUE_TYPE_RESPONSE),
+                    (LABEL_STATUS, LABEL_VALUE_CANISTER_NOT_FOUND),
+                ],
+                1,
+            )]),
+            &metrics_registry,
+        );
+        assert_eq_critical_error_induct_response_failed(0, &metrics_registry);
+    });
+}
+
+/// Tests that a best-effort request whose deadline has expired is not
+/// inducted, but rejected with `SYS_UNKNOWN` instead.
+#[test]
+fn induct_stream_slices_expired_best_effort_request() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+        initial_state.metadata.batch_time = Time::from_nanos_since_unix_epoch(100_000_000_000);
+
+        let mut expected_state = initial_state.clone();
+
+        let outgoing_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 21,
+            message_count: 0,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => outgoing_stream]);
+
+        // Incoming slice with one best-effort request that expired at 50 seconds.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 21,
+            reject_signals: None,
+        });
+        let mut request = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        request.deadline = CoarseTime::from_secs_since_unix_epoch(50);
+        stream_slice.push_message(request.clone().into());
+
+        // The expected stream should have a `SYS_UNKNOWN` reject response and
+        // `signals_end` incremented.
+        let mut expected_outgoing_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 21,
+            message_count: 0,
+            signals_end: 44,
+            reject_signals: None,
+        });
+        expected_outgoing_stream.push(
+            Response {
+                originator: request.sender,
+                respondent: request.receiver,
+                originator_reply_callback: request.sender_reply_callback,
+                refund: request.payment,
+                response_payload: Payload::Reject(RejectContext::new(
+                    RejectCode::SysUnknown,
+                    "Request deadline expired.".to_string(),
+                )),
+                deadline: request.deadline,
+            }
+            .into(),
+        );
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_outgoing_stream]);
+
+        let inducted_state = stream_handler
+            .induct_stream_slices(initial_state, btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[(
+                &[
+                    (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                    (LABEL_STATUS, LABEL_VALUE_DEADLINE_EXPIRED),
+                ],
+                1,
+            )]),
+            &metrics_registry,
+        );
+        assert_eq_critical_errors(0, 0, 0, 0, &metrics_registry);
+    });
+}
+
 /// Tests that a message from a sender that is not currently and has not
 /// recently (according to `canister_migrations`) been hosted by the remote
 /// subnet is dropped, incrementing the respective critical error count.
@@ -2459,6 +2592,79 @@ fn induct_stream_slices_with_memory_limit_setup(
     (expected_state, expected_stream, stream_slice, request1)
 }
 
+/// Tests that a best-effort request that cannot be inducted due to lack of
+/// memory is shed: dropped without generating a reject response.
+#[test]
+fn induct_stream_slices_sheds_best_effort_request() {
+    with_test_replica_logger(|log| {
+        // Subnet memory limit only allows for one in-flight request (plus epsilon).
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture_with_config(
+            &log,
+            HypervisorConfig {
+                subnet_memory_capacity: NumBytes::new(MAX_RESPONSE_COUNT_BYTES as u64 * 15 / 10),
+                ..Default::default()
+            },
+        );
+
+        // Canister with a reservation for one incoming response.
+        let mut initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        make_input_queue_reservations(&mut initial_canister_state, 1, *REMOTE_CANISTER);
+        initial_state.put_canister_state(initial_canister_state);
+        let mut expected_state = initial_state.clone();
+
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        let mut expected_stream = initial_stream.clone();
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // Incoming slice with one best-effort request.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        let mut request = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        request.deadline = CoarseTime::from_secs_since_unix_epoch(100);
+        stream_slice.push_message(request.into());
+
+        // The request is dropped: only `signals_end` is incremented, no reject response.
+        expected_stream.increment_signals_end();
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_stream]);
+
+        let inducted_state = stream_handler
+            .induct_stream_slices(initial_state, btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[(
+                &[
+                    (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                    (LABEL_STATUS, LABEL_VALUE_OUT_OF_MEMORY),
+                ],
+                1,
+            )]),
+            &metrics_registry,
+        );
+        assert_eq!(
+            1,
+            fetch_int_counter(&metrics_registry, METRIC_SHED_BEST_EFFORT_REQUESTS).unwrap()
+        );
+        assert_eq_critical_errors(0, 0, 0, 0, &metrics_registry);
+    });
+}
+
 /// Tests that messages in the loopback stream and incoming slices are inducted
 /// (with signals added appropriately); and messages present in the initial
 /// state are garbage collected or rerouted as appropriate.
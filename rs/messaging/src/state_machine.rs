@@ -17,6 +17,7 @@ const PHASE_INDUCTION: &str = "induction";
 const PHASE_EXECUTION: &str = "execution";
 const PHASE_MESSAGE_ROUTING: &str = "message_routing";
 const PHASE_TIME_OUT_REQUESTS: &str = "time_out_requests";
+const PHASE_TIME_OUT_CALLBACKS: &str = "time_out_callbacks";
 
 pub(crate) trait StateMachine: Send {
     fn execute_round(
@@ -108,6 +109,15 @@ impl StateMachine for StateMachineImpl {
 
         self.observe_phase_duration(PHASE_INDUCTION, &phase_timer);
 
+        // Time out expired best-effort callbacks, after induction so that any
+        // responses inducted in this round take precedence.
+        let phase_timer = Timer::start();
+        let timed_out_callbacks = state_with_messages.time_out_callbacks(batch.time);
+        self.metrics
+            .timed_out_callbacks_total
+            .inc_by(timed_out_callbacks);
+        self.observe_phase_duration(PHASE_TIME_OUT_CALLBACKS, &phase_timer);
+
         let execution_round_type = if batch.requires_full_state_hash {
             ExecutionRoundType::CheckpointRound
         } else {
@@ -0,0 +1,198 @@
+//! Collection and aggregation of query call statistics.
+//!
+//! Queries are executed by a single replica and do not go through consensus.
+//! To nevertheless report deterministic query statistics of a canister, every
+//! replica proceeds as follows:
+//!
+//! 1. The query handler registers the statistics of every query it executes
+//!    with the [`QueryStatsCollector`]. The statistics are collected per
+//!    [`QueryStatsEpoch`], a fixed number of certified heights.
+//! 2. Once an epoch is completed, the [`QueryStatsPayloadBuilderImpl`]
+//!    includes the statistics the replica collected during that epoch into
+//!    the blocks it makes.
+//! 3. Upon delivery of a batch, [`deliver_query_stats`] stores the statistics
+//!    of each node in the replicated state. When all nodes had the chance to
+//!    report an epoch, its statistics are aggregated into the canister states.
+use ic_logger::{info, ReplicaLogger};
+use ic_types::{
+    batch::{epoch_from_height, QueryStats, QueryStatsEpoch},
+    CanisterId, Height,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+mod payload_builder;
+mod state_machine;
+
+pub use payload_builder::{QueryStatsPayloadBuilderImpl, QueryStatsPayloadBuilderParams};
+pub use state_machine::deliver_query_stats;
+
+/// The query statistics a replica collected locally.
+///
+/// Only the statistics of the current and of the previous epoch are kept. The
+/// statistics of older epochs, that have not been included into a block yet,
+/// are dropped.
+#[derive(Default)]
+pub(crate) struct LocalQueryStats {
+    current_epoch: Option<QueryStatsEpoch>,
+    current_stats: BTreeMap<CanisterId, QueryStats>,
+    previous_epoch: Option<QueryStatsEpoch>,
+    previous_stats: BTreeMap<CanisterId, QueryStats>,
+}
+
+impl LocalQueryStats {
+    /// Advances the current epoch to `epoch`, if `epoch` is more recent.
+    pub(crate) fn set_epoch(&mut self, epoch: QueryStatsEpoch, log: &ReplicaLogger) {
+        match self.current_epoch {
+            Some(current_epoch) if current_epoch >= epoch => {}
+            _ => {
+                if let Some(current_epoch) = self.current_epoch {
+                    info!(
+                        log,
+                        "Completed query stats epoch {} with statistics of {} canisters",
+                        current_epoch,
+                        self.current_stats.len()
+                    );
+                }
+                self.previous_epoch = self.current_epoch.replace(epoch);
+                self.previous_stats = std::mem::take(&mut self.current_stats);
+            }
+        }
+    }
+
+    /// Returns the epoch and the statistics of the last completed epoch.
+    pub(crate) fn completed_epoch(
+        &self,
+    ) -> Option<(QueryStatsEpoch, &BTreeMap<CanisterId, QueryStats>)> {
+        self.previous_epoch
+            .map(|epoch| (epoch, &self.previous_stats))
+    }
+}
+
+/// Handle through which the query handler registers the statistics of the
+/// queries it executes.
+#[derive(Clone)]
+pub struct QueryStatsCollector {
+    local_stats: Arc<Mutex<LocalQueryStats>>,
+    epoch_length: u64,
+    log: ReplicaLogger,
+}
+
+impl QueryStatsCollector {
+    /// Sets the current epoch to the one the given certified height belongs
+    /// to. Statistics are registered to the current epoch.
+    pub fn set_epoch_from_height(&self, height: Height) {
+        let epoch = epoch_from_height(height, self.epoch_length);
+        self.local_stats.lock().unwrap().set_epoch(epoch, &self.log);
+    }
+
+    /// Adds the statistics of an executed query to the statistics of
+    /// `canister_id` in the current epoch.
+    pub fn register_query_statistics(&self, canister_id: CanisterId, stats: &QueryStats) {
+        self.local_stats
+            .lock()
+            .unwrap()
+            .current_stats
+            .entry(canister_id)
+            .or_default()
+            .saturating_accumulate(stats);
+    }
+}
+
+/// Sets up the collection of query statistics.
+///
+/// Returns the [`QueryStatsCollector`] to be used by the query handler, and
+/// the parameters to build the [`QueryStatsPayloadBuilderImpl`] from, which
+/// includes the collected statistics into blocks.
+pub fn init_query_stats(
+    log: ReplicaLogger,
+    epoch_length: u64,
+) -> (QueryStatsCollector, QueryStatsPayloadBuilderParams) {
+    let local_stats = Arc::new(Mutex::new(LocalQueryStats::default()));
+    (
+        QueryStatsCollector {
+            local_stats: local_stats.clone(),
+            epoch_length,
+            log,
+        },
+        QueryStatsPayloadBuilderParams {
+            local_stats,
+            epoch_length,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_test_utilities::types::ids::canister_test_id;
+    use ic_test_utilities_logger::with_test_replica_logger;
+
+    fn stats(num_calls: u32) -> QueryStats {
+        QueryStats {
+            num_calls,
+            num_instructions: 100 * num_calls as u64,
+            ingress_payload_size: 10 * num_calls as u64,
+            egress_payload_size: 20 * num_calls as u64,
+        }
+    }
+
+    #[test]
+    fn collector_moves_stats_to_completed_epoch() {
+        with_test_replica_logger(|log| {
+            let (collector, params) = init_query_stats(log, 10);
+            collector.set_epoch_from_height(Height::from(3));
+            collector.register_query_statistics(canister_test_id(1), &stats(1));
+            collector.register_query_statistics(canister_test_id(1), &stats(2));
+            collector.register_query_statistics(canister_test_id(2), &stats(1));
+            assert!(params
+                .local_stats
+                .lock()
+                .unwrap()
+                .completed_epoch()
+                .is_none());
+
+            // Heights within the same epoch do not complete it.
+            collector.set_epoch_from_height(Height::from(9));
+            assert!(params
+                .local_stats
+                .lock()
+                .unwrap()
+                .completed_epoch()
+                .is_none());
+
+            collector.set_epoch_from_height(Height::from(10));
+            collector.register_query_statistics(canister_test_id(1), &stats(5));
+            let local_stats = params.local_stats.lock().unwrap();
+            let (epoch, completed) = local_stats.completed_epoch().unwrap();
+            assert_eq!(epoch, QueryStatsEpoch::from(0));
+            assert_eq!(
+                completed,
+                &BTreeMap::from([
+                    (canister_test_id(1), stats(3)),
+                    (canister_test_id(2), stats(1))
+                ])
+            );
+            assert_eq!(
+                local_stats.current_stats,
+                BTreeMap::from([(canister_test_id(1), stats(5))])
+            );
+        })
+    }
+
+    #[test]
+    fn collector_does_not_go_back_in_epochs() {
+        with_test_replica_logger(|log| {
+            let (collector, params) = init_query_stats(log, 10);
+            collector.set_epoch_from_height(Height::from(25));
+            collector.register_query_statistics(canister_test_id(1), &stats(1));
+            collector.set_epoch_from_height(Height::from(15));
+
+            let local_stats = params.local_stats.lock().unwrap();
+            assert_eq!(local_stats.current_epoch, Some(QueryStatsEpoch::from(2)));
+            assert_eq!(local_stats.current_stats.len(), 1);
+        })
+    }
+}
@@ -0,0 +1,277 @@
+use crate::LocalQueryStats;
+use ic_interfaces::{
+    batch_payload::{
+        BatchPayloadBuilder, BatchPayloadValidationError, PastPayload, ProposalContext,
+    },
+    query_stats::{
+        InvalidQueryStatsPayloadReason, QueryStatsPayloadValidationError,
+        QueryStatsTransientValidationError,
+    },
+    validation::{ValidationError, ValidationResult},
+};
+use ic_interfaces_state_manager::StateReader;
+use ic_logger::{warn, ReplicaLogger};
+use ic_replicated_state::ReplicatedState;
+use ic_types::{
+    batch::{
+        epoch_from_height, CanisterQueryStats, QueryStatsEpoch, QueryStatsPayload,
+        ValidationContext,
+    },
+    CanisterId, NodeId, NumBytes,
+};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The parameters returned by [`crate::init_query_stats`], from which the
+/// [`QueryStatsPayloadBuilderImpl`] is built once the state manager is
+/// available.
+pub struct QueryStatsPayloadBuilderParams {
+    pub(crate) local_stats: Arc<Mutex<LocalQueryStats>>,
+    pub(crate) epoch_length: u64,
+}
+
+impl QueryStatsPayloadBuilderParams {
+    /// Creates the payload builder of the given node.
+    pub fn into_payload_builder(
+        self,
+        state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+        node_id: NodeId,
+        log: ReplicaLogger,
+    ) -> QueryStatsPayloadBuilderImpl {
+        QueryStatsPayloadBuilderImpl {
+            local_stats: self.local_stats,
+            epoch_length: self.epoch_length,
+            state_reader,
+            node_id,
+            log,
+        }
+    }
+}
+
+/// Includes the query statistics this node collected during the last
+/// completed epoch into the blocks it makes.
+pub struct QueryStatsPayloadBuilderImpl {
+    local_stats: Arc<Mutex<LocalQueryStats>>,
+    epoch_length: u64,
+    state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    node_id: NodeId,
+    log: ReplicaLogger,
+}
+
+impl BatchPayloadBuilder for QueryStatsPayloadBuilderImpl {
+    fn build_payload(
+        &self,
+        max_size: NumBytes,
+        past_payloads: &[PastPayload],
+        context: &ValidationContext,
+    ) -> Vec<u8> {
+        let certified_epoch = epoch_from_height(context.certified_height, self.epoch_length);
+
+        let mut local_stats = self.local_stats.lock().unwrap();
+        // The query handler only advances the epoch when it executes queries,
+        // so make sure that an idle node reports its statistics as well.
+        local_stats.set_epoch(certified_epoch, &self.log);
+        let (epoch, completed_stats) = match local_stats.completed_epoch() {
+            Some((epoch, stats)) if epoch < certified_epoch && !stats.is_empty() => (epoch, stats),
+            _ => return vec![],
+        };
+
+        let state = match self.state_reader.get_state_at(context.certified_height) {
+            Ok(state) => state,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Failed to get the state at height {} to build the query stats payload: {:?}",
+                    context.certified_height,
+                    err
+                );
+                return vec![];
+            }
+        };
+        if is_aggregated(state.get_ref(), epoch) {
+            return vec![];
+        }
+        let already_reported = reported_canister_ids(
+            state.get_ref(),
+            past_payloads,
+            epoch,
+            self.node_id,
+            &self.log,
+        );
+
+        let mut payload = QueryStatsPayload {
+            epoch,
+            proposer: self.node_id,
+            canister_stats: vec![],
+        };
+        let mut size = payload.serialize().len() as u64;
+        for (canister_id, stats) in completed_stats {
+            if already_reported.contains(canister_id) {
+                continue;
+            }
+            let canister_stats = CanisterQueryStats {
+                canister_id: *canister_id,
+                stats: stats.clone(),
+            };
+            let canister_stats_size = QueryStatsPayload::serialized_size_of(&canister_stats) as u64;
+            if size + canister_stats_size > max_size.get() {
+                break;
+            }
+            size += canister_stats_size;
+            payload.canister_stats.push(canister_stats);
+        }
+
+        if payload.canister_stats.is_empty() {
+            return vec![];
+        }
+        payload.serialize()
+    }
+
+    fn validate_payload(
+        &self,
+        payload: &[u8],
+        past_payloads: &[PastPayload],
+        proposal_context: &ProposalContext,
+    ) -> ValidationResult<BatchPayloadValidationError> {
+        self.validate_query_stats_payload(payload, past_payloads, proposal_context)
+            .map_err(BatchPayloadValidationError::QueryStats)
+    }
+}
+
+impl QueryStatsPayloadBuilderImpl {
+    fn validate_query_stats_payload(
+        &self,
+        payload: &[u8],
+        past_payloads: &[PastPayload],
+        proposal_context: &ProposalContext,
+    ) -> Result<(), QueryStatsPayloadValidationError> {
+        let payload = match QueryStatsPayload::deserialize(payload).map_err(|err| {
+            invalid_payload(InvalidQueryStatsPayloadReason::DeserializationFailed(
+                err.to_string(),
+            ))
+        })? {
+            Some(payload) => payload,
+            None => return Ok(()),
+        };
+
+        if payload.proposer != proposal_context.proposer {
+            return Err(invalid_payload(
+                InvalidQueryStatsPayloadReason::InvalidNodeId {
+                    expected: proposal_context.proposer,
+                    reported: payload.proposer,
+                },
+            ));
+        }
+
+        let certified_height = proposal_context.validation_context.certified_height;
+        let certified_epoch = epoch_from_height(certified_height, self.epoch_length);
+        if payload.epoch >= certified_epoch {
+            return Err(invalid_payload(
+                InvalidQueryStatsPayloadReason::EpochNotCompleted {
+                    epoch: payload.epoch,
+                    certified_epoch,
+                },
+            ));
+        }
+
+        let state =
+            self.state_reader
+                .get_state_at(certified_height)
+                .map_err(|err| {
+                    ValidationError::Transient(
+                        QueryStatsTransientValidationError::StateUnavailable(certified_height, err),
+                    )
+                })?;
+        let state = state.get_ref();
+        if let Some(highest_aggregated_epoch) = state.metadata.query_stats.highest_aggregated_epoch
+        {
+            if payload.epoch <= highest_aggregated_epoch {
+                return Err(invalid_payload(
+                    InvalidQueryStatsPayloadReason::EpochAlreadyAggregated {
+                        epoch: payload.epoch,
+                        highest_aggregated_epoch,
+                    },
+                ));
+            }
+        }
+
+        let mut reported = reported_canister_ids(
+            state,
+            past_payloads,
+            payload.epoch,
+            payload.proposer,
+            &self.log,
+        );
+        for canister_stats in &payload.canister_stats {
+            if !reported.insert(canister_stats.canister_id) {
+                return Err(invalid_payload(
+                    InvalidQueryStatsPayloadReason::DuplicateCanisterId(canister_stats.canister_id),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_payload(reason: InvalidQueryStatsPayloadReason) -> QueryStatsPayloadValidationError {
+    ValidationError::Permanent(reason)
+}
+
+/// Returns true if the statistics of `epoch` have already been aggregated
+/// into the canister states.
+fn is_aggregated(state: &ReplicatedState, epoch: QueryStatsEpoch) -> bool {
+    matches!(
+        state.metadata.query_stats.highest_aggregated_epoch,
+        Some(highest_aggregated_epoch) if epoch <= highest_aggregated_epoch
+    )
+}
+
+/// Returns the canisters whose statistics of `epoch` have already been
+/// reported by `node_id`, either in a block that has been delivered to the
+/// state or in one of the `past_payloads`.
+fn reported_canister_ids(
+    state: &ReplicatedState,
+    past_payloads: &[PastPayload],
+    epoch: QueryStatsEpoch,
+    node_id: NodeId,
+    log: &ReplicaLogger,
+) -> BTreeSet<CanisterId> {
+    let mut reported: BTreeSet<CanisterId> = state
+        .metadata
+        .query_stats
+        .stats
+        .get(&epoch)
+        .into_iter()
+        .flatten()
+        .filter(|(_, by_node)| by_node.contains_key(&node_id))
+        .map(|(canister_id, _)| *canister_id)
+        .collect();
+
+    for past_payload in past_payloads {
+        match QueryStatsPayload::deserialize(past_payload.payload) {
+            Ok(Some(payload)) if payload.epoch == epoch && payload.proposer == node_id => {
+                reported.extend(
+                    payload
+                        .canister_stats
+                        .iter()
+                        .map(|canister_stats| canister_stats.canister_id),
+                );
+            }
+            Ok(_) => {}
+            Err(err) => warn!(
+                log,
+                "Failed to deserialize the past query stats payload at height {}: {:?}",
+                past_payload.height,
+                err
+            ),
+        }
+    }
+
+    reported
+}
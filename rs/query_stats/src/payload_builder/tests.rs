@@ -0,0 +1,228 @@
+use super::*;
+use crate::init_query_stats;
+use ic_interfaces_state_manager::Labeled;
+use ic_interfaces_state_manager_mocks::MockStateManager;
+use ic_registry_subnet_type::SubnetType;
+use ic_test_utilities::{
+    mock_time,
+    types::ids::{canister_test_id, node_test_id, subnet_test_id},
+};
+use ic_test_utilities_logger::with_test_replica_logger;
+use ic_types::{
+    batch::QueryStats,
+    crypto::{CryptoHash, CryptoHashOf},
+    Height, RegistryVersion,
+};
+
+const EPOCH_LENGTH: u64 = 10;
+const MAX_SIZE: NumBytes = NumBytes::new(4 * 1024 * 1024);
+
+fn stats(num_calls: u32) -> QueryStats {
+    QueryStats {
+        num_calls,
+        num_instructions: 1_000 * num_calls as u64,
+        ingress_payload_size: 10 * num_calls as u64,
+        egress_payload_size: 100 * num_calls as u64,
+    }
+}
+
+fn validation_context(certified_height: u64) -> ValidationContext {
+    ValidationContext {
+        registry_version: RegistryVersion::new(1),
+        certified_height: Height::new(certified_height),
+        time: mock_time(),
+    }
+}
+
+fn past_payload(payload: &[u8]) -> PastPayload {
+    PastPayload {
+        height: Height::new(1),
+        time: mock_time(),
+        block_hash: CryptoHashOf::from(CryptoHash(vec![])),
+        payload,
+    }
+}
+
+/// Runs `test` with the payload builder of node 1, that collected statistics
+/// of canisters 1 to `num_canisters` during epoch 0.
+fn with_payload_builder<F>(num_canisters: u64, state: ReplicatedState, test: F)
+where
+    F: FnOnce(QueryStatsPayloadBuilderImpl),
+{
+    with_test_replica_logger(|log| {
+        let mut state_manager = MockStateManager::new();
+        state_manager
+            .expect_get_state_at()
+            .return_const(Ok(Labeled::new(Height::new(0), Arc::new(state))));
+
+        let (collector, params) = init_query_stats(log.clone(), EPOCH_LENGTH);
+        collector.set_epoch_from_height(Height::new(0));
+        for canister in 1..=num_canisters {
+            collector.register_query_statistics(canister_test_id(canister), &stats(1));
+        }
+        test(params.into_payload_builder(Arc::new(state_manager), node_test_id(1), log))
+    })
+}
+
+fn state() -> ReplicatedState {
+    ReplicatedState::new(subnet_test_id(1), SubnetType::Application)
+}
+
+fn deserialize(payload: &[u8]) -> QueryStatsPayload {
+    QueryStatsPayload::deserialize(payload).unwrap().unwrap()
+}
+
+#[test]
+fn payload_contains_stats_of_completed_epoch_only() {
+    with_payload_builder(3, state(), |payload_builder| {
+        // Epoch 0 is not completed at certified height 9.
+        assert!(payload_builder
+            .build_payload(MAX_SIZE, &[], &validation_context(9))
+            .is_empty());
+
+        let payload = payload_builder.build_payload(MAX_SIZE, &[], &validation_context(10));
+        let payload = deserialize(&payload);
+        assert_eq!(payload.epoch, QueryStatsEpoch::from(0));
+        assert_eq!(payload.proposer, node_test_id(1));
+        assert_eq!(payload.canister_stats.len(), 3);
+    })
+}
+
+#[test]
+fn payload_respects_max_size() {
+    with_payload_builder(100, state(), |payload_builder| {
+        let max_size = NumBytes::new(200);
+        let payload = payload_builder.build_payload(max_size, &[], &validation_context(10));
+        assert!(payload.len() as u64 <= max_size.get());
+
+        let canister_stats = deserialize(&payload).canister_stats;
+        assert!(!canister_stats.is_empty());
+        assert!(canister_stats.len() < 100);
+    })
+}
+
+#[test]
+fn payload_excludes_canisters_reported_in_past_payloads() {
+    with_payload_builder(3, state(), |payload_builder| {
+        let first = payload_builder.build_payload(NumBytes::new(90), &[], &validation_context(10));
+        let num_first = deserialize(&first).canister_stats.len();
+        assert!(num_first < 3);
+
+        let second = payload_builder.build_payload(
+            MAX_SIZE,
+            &[past_payload(&first)],
+            &validation_context(10),
+        );
+        assert_eq!(deserialize(&second).canister_stats.len(), 3 - num_first);
+
+        let third = payload_builder.build_payload(
+            MAX_SIZE,
+            &[past_payload(&first), past_payload(&second)],
+            &validation_context(10),
+        );
+        assert!(third.is_empty());
+    })
+}
+
+#[test]
+fn payload_is_valid() {
+    with_payload_builder(3, state(), |payload_builder| {
+        let context = validation_context(10);
+        let payload = payload_builder.build_payload(MAX_SIZE, &[], &context);
+        let proposal_context = ProposalContext {
+            proposer: node_test_id(1),
+            validation_context: &context,
+        };
+        assert!(payload_builder
+            .validate_payload(&payload, &[], &proposal_context)
+            .is_ok());
+        assert!(payload_builder
+            .validate_payload(&[], &[], &proposal_context)
+            .is_ok());
+
+        // The same statistics must not be included twice.
+        assert!(matches!(
+            payload_builder.validate_query_stats_payload(
+                &payload,
+                &[past_payload(&payload)],
+                &proposal_context
+            ),
+            Err(ValidationError::Permanent(
+                InvalidQueryStatsPayloadReason::DuplicateCanisterId(_)
+            ))
+        ));
+
+        // Only the proposer may include its statistics.
+        let proposal_context = ProposalContext {
+            proposer: node_test_id(2),
+            validation_context: &context,
+        };
+        assert!(matches!(
+            payload_builder.validate_query_stats_payload(&payload, &[], &proposal_context),
+            Err(ValidationError::Permanent(
+                InvalidQueryStatsPayloadReason::InvalidNodeId { .. }
+            ))
+        ));
+    })
+}
+
+#[test]
+fn payload_of_uncompleted_epoch_is_invalid() {
+    with_payload_builder(0, state(), |payload_builder| {
+        let payload = QueryStatsPayload {
+            epoch: QueryStatsEpoch::from(1),
+            proposer: node_test_id(1),
+            canister_stats: vec![],
+        };
+        let context = validation_context(19);
+        let proposal_context = ProposalContext {
+            proposer: node_test_id(1),
+            validation_context: &context,
+        };
+        assert!(matches!(
+            payload_builder.validate_query_stats_payload(
+                &payload.serialize(),
+                &[],
+                &proposal_context
+            ),
+            Err(ValidationError::Permanent(
+                InvalidQueryStatsPayloadReason::EpochNotCompleted { .. }
+            ))
+        ));
+    })
+}
+
+#[test]
+fn payload_of_aggregated_epoch_is_invalid() {
+    let mut state = state();
+    state.metadata.query_stats.highest_aggregated_epoch = Some(QueryStatsEpoch::from(0));
+    with_payload_builder(3, state, |payload_builder| {
+        let context = validation_context(10);
+        assert!(payload_builder
+            .build_payload(MAX_SIZE, &[], &context)
+            .is_empty());
+
+        let payload = QueryStatsPayload {
+            epoch: QueryStatsEpoch::from(0),
+            proposer: node_test_id(1),
+            canister_stats: vec![CanisterQueryStats {
+                canister_id: canister_test_id(1),
+                stats: stats(1),
+            }],
+        };
+        let proposal_context = ProposalContext {
+            proposer: node_test_id(1),
+            validation_context: &context,
+        };
+        assert!(matches!(
+            payload_builder.validate_query_stats_payload(
+                &payload.serialize(),
+                &[],
+                &proposal_context
+            ),
+            Err(ValidationError::Permanent(
+                InvalidQueryStatsPayloadReason::EpochAlreadyAggregated { .. }
+            ))
+        ));
+    })
+}
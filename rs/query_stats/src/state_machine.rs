@@ -0,0 +1,259 @@
+use ic_logger::{warn, ReplicaLogger};
+use ic_replicated_state::{
+    canister_state::TotalQueryStats, metadata_state::query_stats::QueryStatsByCanister,
+    ReplicatedState,
+};
+use ic_types::{
+    batch::{QueryStats, QueryStatsEpoch, QueryStatsPayload},
+    NodeId,
+};
+use std::collections::BTreeMap;
+
+/// The number of epochs that nodes have to report their statistics of an
+/// epoch, before it is aggregated.
+///
+/// A node reports the statistics of an epoch once the following epoch has
+/// started. The statistics of epoch `e` are therefore aggregated as soon as
+/// the first statistics of epoch `e + 2` are delivered.
+const AGGREGATION_DELAY_EPOCHS: u64 = 2;
+
+/// Stores the query statistics of a delivered payload in the state, and
+/// aggregates the statistics of all epochs that all nodes had the chance to
+/// report into the canister states.
+pub fn deliver_query_stats(
+    payload: &QueryStatsPayload,
+    state: &mut ReplicatedState,
+    logger: &ReplicaLogger,
+) {
+    let raw_stats = &mut state.metadata.query_stats;
+    if matches!(
+        raw_stats.highest_aggregated_epoch,
+        Some(highest_aggregated_epoch) if payload.epoch <= highest_aggregated_epoch
+    ) {
+        warn!(
+            logger,
+            "Received query stats of node {} for epoch {}, which has already been aggregated",
+            payload.proposer,
+            payload.epoch
+        );
+        return;
+    }
+
+    let by_canister = raw_stats.stats.entry(payload.epoch).or_default();
+    for canister_stats in &payload.canister_stats {
+        by_canister
+            .entry(canister_stats.canister_id)
+            .or_default()
+            .entry(payload.proposer)
+            .or_insert_with(|| canister_stats.stats.clone());
+    }
+
+    let aggregate_up_to = match payload.epoch.get().checked_sub(AGGREGATION_DELAY_EPOCHS) {
+        Some(epoch) => epoch,
+        None => return,
+    };
+    let remaining = raw_stats
+        .stats
+        .split_off(&QueryStatsEpoch::from(aggregate_up_to + 1));
+    let to_aggregate = std::mem::replace(&mut raw_stats.stats, remaining);
+    raw_stats.highest_aggregated_epoch = Some(QueryStatsEpoch::from(aggregate_up_to));
+
+    let num_nodes = state
+        .metadata
+        .network_topology
+        .subnets
+        .get(&state.metadata.own_subnet_id)
+        .map(|subnet| subnet.nodes.len())
+        .unwrap_or_default();
+    for by_canister in to_aggregate.into_values() {
+        aggregate_epoch(by_canister, num_nodes, state);
+    }
+}
+
+/// Adds the statistics of a single epoch to the canister states.
+fn aggregate_epoch(
+    by_canister: QueryStatsByCanister,
+    num_nodes: usize,
+    state: &mut ReplicatedState,
+) {
+    for (canister_id, by_node) in by_canister {
+        // The canister may have been deleted or migrated in the meantime.
+        if let Some(canister) = state.canister_state_mut(&canister_id) {
+            accumulate(
+                &mut canister.scheduler_state.total_query_stats,
+                &aggregate_canister_stats(&by_node, num_nodes),
+            );
+        }
+    }
+}
+
+/// Computes the statistics of a canister across the whole subnet, from the
+/// statistics reported by the individual nodes.
+///
+/// Nodes that did not report statistics count as having executed no queries.
+/// Each value is the median of the reported values multiplied by the number
+/// of nodes, such that faulty nodes, which are a minority, cannot arbitrarily
+/// distort the result.
+fn aggregate_canister_stats(
+    by_node: &BTreeMap<NodeId, QueryStats>,
+    num_nodes: usize,
+) -> TotalQueryStats {
+    let num_nodes = num_nodes.max(by_node.len());
+    let median = |field: fn(&QueryStats) -> u64| -> u64 {
+        let mut values: Vec<u64> = by_node.values().map(field).collect();
+        values.resize(num_nodes, 0);
+        values.sort_unstable();
+        values[num_nodes / 2].saturating_mul(num_nodes as u64)
+    };
+    TotalQueryStats {
+        num_calls: median(|stats| stats.num_calls as u64),
+        num_instructions: median(|stats| stats.num_instructions),
+        ingress_payload_size: median(|stats| stats.ingress_payload_size),
+        egress_payload_size: median(|stats| stats.egress_payload_size),
+    }
+}
+
+fn accumulate(total: &mut TotalQueryStats, stats: &TotalQueryStats) {
+    total.num_calls = total.num_calls.saturating_add(stats.num_calls);
+    total.num_instructions = total
+        .num_instructions
+        .saturating_add(stats.num_instructions);
+    total.ingress_payload_size = total
+        .ingress_payload_size
+        .saturating_add(stats.ingress_payload_size);
+    total.egress_payload_size = total
+        .egress_payload_size
+        .saturating_add(stats.egress_payload_size);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_replicated_state::SubnetTopology;
+    use ic_test_utilities::{
+        state::CanisterStateBuilder,
+        types::ids::{canister_test_id, node_test_id, subnet_test_id},
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_types::batch::CanisterQueryStats;
+
+    fn stats(num_calls: u32) -> QueryStats {
+        QueryStats {
+            num_calls,
+            num_instructions: 1_000 * num_calls as u64,
+            ingress_payload_size: 10 * num_calls as u64,
+            egress_payload_size: 100 * num_calls as u64,
+        }
+    }
+
+    fn payload(epoch: u64, node: u64, canister_stats: Vec<(u64, u32)>) -> QueryStatsPayload {
+        QueryStatsPayload {
+            epoch: epoch.into(),
+            proposer: node_test_id(node),
+            canister_stats: canister_stats
+                .into_iter()
+                .map(|(canister, num_calls)| CanisterQueryStats {
+                    canister_id: canister_test_id(canister),
+                    stats: stats(num_calls),
+                })
+                .collect(),
+        }
+    }
+
+    /// A state of a subnet with 4 nodes, hosting canisters 1 and 2.
+    fn state() -> ReplicatedState {
+        let subnet_id = subnet_test_id(1);
+        let mut state = ReplicatedState::new(subnet_id, SubnetType::Application);
+        state.metadata.network_topology.subnets.insert(
+            subnet_id,
+            SubnetTopology {
+                nodes: (1..=4).map(node_test_id).collect(),
+                ..Default::default()
+            },
+        );
+        for canister in [1, 2] {
+            state.put_canister_state(
+                CanisterStateBuilder::new()
+                    .with_canister_id(canister_test_id(canister))
+                    .build(),
+            );
+        }
+        state
+    }
+
+    fn total_query_stats(state: &ReplicatedState, canister: u64) -> TotalQueryStats {
+        state
+            .canister_state(&canister_test_id(canister))
+            .unwrap()
+            .scheduler_state
+            .total_query_stats
+            .clone()
+    }
+
+    #[test]
+    fn stats_are_aggregated_two_epochs_later() {
+        with_test_replica_logger(|log| {
+            let mut state = state();
+            deliver_query_stats(&payload(1, 1, vec![(1, 10), (2, 1)]), &mut state, &log);
+            deliver_query_stats(&payload(1, 2, vec![(1, 12)]), &mut state, &log);
+            deliver_query_stats(&payload(1, 3, vec![(1, 11)]), &mut state, &log);
+            deliver_query_stats(&payload(2, 1, vec![(1, 7)]), &mut state, &log);
+            assert_eq!(state.metadata.query_stats.highest_aggregated_epoch, None);
+            assert_eq!(total_query_stats(&state, 1), TotalQueryStats::default());
+
+            deliver_query_stats(&payload(3, 1, vec![]), &mut state, &log);
+            assert_eq!(
+                state.metadata.query_stats.highest_aggregated_epoch,
+                Some(1.into())
+            );
+            assert_eq!(state.metadata.query_stats.stats.len(), 2);
+
+            // Reported by 3 out of 4 nodes: [0, 10, 11, 12] has the median 11.
+            assert_eq!(
+                total_query_stats(&state, 1),
+                TotalQueryStats {
+                    num_calls: 44,
+                    num_instructions: 44_000,
+                    ingress_payload_size: 440,
+                    egress_payload_size: 4_400,
+                }
+            );
+            // Reported by a single node only: [0, 0, 0, 1] has the median 0.
+            assert_eq!(total_query_stats(&state, 2), TotalQueryStats::default());
+        })
+    }
+
+    #[test]
+    fn stats_of_aggregated_epochs_are_dropped() {
+        with_test_replica_logger(|log| {
+            let mut state = state();
+            deliver_query_stats(&payload(5, 1, vec![(1, 10)]), &mut state, &log);
+            assert_eq!(
+                state.metadata.query_stats.highest_aggregated_epoch,
+                Some(3.into())
+            );
+
+            deliver_query_stats(&payload(3, 2, vec![(1, 10)]), &mut state, &log);
+            assert!(!state
+                .metadata
+                .query_stats
+                .stats
+                .contains_key(&QueryStatsEpoch::from(3)));
+        })
+    }
+
+    #[test]
+    fn stats_of_the_same_node_are_only_counted_once() {
+        with_test_replica_logger(|log| {
+            let mut state = state();
+            for node in 1..=4 {
+                deliver_query_stats(&payload(0, node, vec![(1, 8)]), &mut state, &log);
+            }
+            deliver_query_stats(&payload(0, 1, vec![(1, 100)]), &mut state, &log);
+            deliver_query_stats(&payload(2, 1, vec![]), &mut state, &log);
+
+            assert_eq!(total_query_stats(&state, 1).num_calls, 32);
+        })
+    }
+}
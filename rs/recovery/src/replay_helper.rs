@@ -31,6 +31,7 @@ pub async fn replay(
         replay_until_height: None,
         subcmd,
         data_root: Some(data_root),
+        hash_trace: None,
     };
     // Since replay output needs to be persisted anyway in case the recovery process
     // is restarted, we avoid declaring a return value and moving out of the
@@ -39,6 +39,11 @@ pub struct ReplayToolArgs {
     #[clap(long)]
     /// The replay will stop at this height and make a checkpoint.
     pub replay_until_height: Option<u64>,
+
+    /// Write the state hash of every height certified during the replay to
+    /// this file, one `<height> <hash>` line per height.
+    #[clap(long)]
+    pub hash_trace: Option<PathBuf>,
 }
 
 #[derive(Clone, Parser)]
@@ -75,6 +80,21 @@ pub enum SubCommand {
 
     /// Verify the signature of a CUP from a subnet
     VerifySubnetCUP(VerifySubnetCUPCmd),
+
+    /// Restore from the backup with two `ic-replay` binaries (e.g. built from
+    /// two replica versions), find the first height at which their state
+    /// hashes differ and print the per-canister certified state diff at that
+    /// height. Requires `--data-root` containing the state to start from.
+    FindDivergence(FindDivergenceCmd),
+
+    /// Run a query against the state at the replay target height through the
+    /// query handler and print the decoded result.
+    Query(CanisterCallCmd),
+
+    /// Execute an update call on top of the state at the replay target height
+    /// and print the decoded result. The resulting state is not checkpointed
+    /// and is thrown away when the tool exits.
+    DryRun(CanisterCallCmd),
 }
 
 #[derive(Clone, Parser)]
@@ -176,3 +196,38 @@ pub struct VerifySubnetCUPCmd {
     /// File wih the content of the public key
     pub public_key_file: PathBuf,
 }
+
+#[derive(Clone, Parser, Debug)]
+pub struct FindDivergenceCmd {
+    /// The first `ic-replay` binary.
+    pub binary_a: PathBuf,
+    /// The second `ic-replay` binary.
+    pub binary_b: PathBuf,
+    /// Directory in which the data roots of the individual replays are created.
+    /// It needs enough space for two copies of the data root.
+    pub work_dir: PathBuf,
+    /// Registry local store path
+    pub registry_local_store_path: PathBuf,
+    /// Backup spool path
+    pub backup_spool_path: PathBuf,
+    /// The replica version to be restored
+    pub replica_version: String,
+    /// Height from which the restoration should happen
+    pub start_height: u64,
+}
+
+#[derive(Clone, Parser, Debug)]
+pub struct CanisterCallCmd {
+    /// The canister to call.
+    #[clap(long)]
+    pub canister: CanisterId,
+    /// The method to call.
+    #[clap(long)]
+    pub method: String,
+    /// The argument of the call in Candid text format.
+    #[clap(long, default_value = "()")]
+    pub arg: String,
+    /// The caller; the anonymous principal if not specified.
+    #[clap(long)]
+    pub sender: Option<PrincipalId>,
+}
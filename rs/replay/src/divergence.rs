@@ -0,0 +1,307 @@
+//! Finds the first height at which two `ic-replay` binaries restoring the
+//! same backup compute different state hashes.
+//!
+//! Both binaries first replay the whole backup, each on its own copy of the
+//! data root, writing a trace of the certified state hashes (see
+//! [`crate::hash_trace`]). A replay computing a state hash that differs from
+//! the certification in the backup stops at that height, so comparing the
+//! traces yields the first divergent height. Both binaries then replay the
+//! backup once more from fresh copies of the data root up to that height,
+//! which leaves a checkpoint at the divergent height, and the certified state
+//! trees of the two checkpoints are diffed per canister.
+
+use crate::cmd::FindDivergenceCmd;
+use crate::hash_trace::{first_divergence, read_hash_trace, Divergence};
+use ic_interfaces_registry::RegistryClient;
+use ic_logger::replica_logger::no_op_logger;
+use ic_registry_client::client::RegistryClientImpl;
+use ic_registry_local_store::LocalStoreImpl;
+use ic_registry_subnet_type::SubnetType;
+use ic_replica::setup::get_subnet_type;
+use ic_replicated_state::page_map::TestPageAllocatorFileDescriptorImpl;
+use ic_state_layout::CompleteCheckpointLayout;
+use ic_state_manager::{
+    checkpoint::load_checkpoint_parallel,
+    tree_diff::{diff, Changes},
+    tree_hash::hash_state,
+    CheckpointMetrics,
+};
+use ic_types::{Height, PrincipalId, SubnetId};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// The label of the subtree of the certified state holding the canisters.
+const CANISTER_LABEL: &[u8] = b"canister";
+
+/// The parameters shared by all replays.
+struct Replays<'a> {
+    config: &'a Path,
+    subnet_id: SubnetId,
+    data_root: &'a Path,
+    cmd: &'a FindDivergenceCmd,
+}
+
+impl Replays<'_> {
+    /// Restores the backup with `binary` on a fresh copy of the data root in
+    /// `<work_dir>/<name>`, writing a hash trace to `<work_dir>/<name>.trace`.
+    /// If `until_height` is given, the replay stops at that height and creates
+    /// a checkpoint. Returns the data root of the replay and the trace.
+    fn run(
+        &self,
+        binary: &Path,
+        name: &str,
+        until_height: Option<Height>,
+    ) -> Result<(PathBuf, PathBuf), String> {
+        let data_root = self.cmd.work_dir.join(name);
+        let trace = self.cmd.work_dir.join(format!("{}.trace", name));
+        if data_root.exists() {
+            std::fs::remove_dir_all(&data_root)
+                .map_err(|err| format!("Failed to remove {:?}: {}", data_root, err))?;
+        }
+        println!("Copying {:?} to {:?}...", self.data_root, data_root);
+        copy_dir_all(self.data_root, &data_root)
+            .map_err(|err| format!("Failed to copy the data root: {}", err))?;
+
+        let mut command = Command::new(binary);
+        command
+            .arg(self.config)
+            .arg("--subnet-id")
+            .arg(self.subnet_id.to_string())
+            .arg("--data-root")
+            .arg(&data_root)
+            .arg("--hash-trace")
+            .arg(&trace);
+        if let Some(height) = until_height {
+            command
+                .arg("--replay-until-height")
+                .arg(height.get().to_string());
+        }
+        command
+            .arg("restore-from-backup")
+            .arg(&self.cmd.registry_local_store_path)
+            .arg(&self.cmd.backup_spool_path)
+            .arg(&self.cmd.replica_version)
+            .arg(self.cmd.start_height.to_string())
+            .stdin(Stdio::piped());
+
+        println!("Running {:?}", command);
+        let mut child = command
+            .spawn()
+            .map_err(|err| format!("Failed to run {:?}: {}", binary, err))?;
+        // Confirm the question about the checkpoint at the target height.
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(b"y\n");
+        }
+        let status = child
+            .wait()
+            .map_err(|err| format!("Failed to wait for {:?}: {}", binary, err))?;
+        if !status.success() {
+            // A crashing replay shows up as a shorter hash trace.
+            println!("{:?} exited with {}", binary, status);
+        }
+        Ok((data_root, trace))
+    }
+}
+
+/// `find-divergence` sub-command entry point.
+pub fn find_divergence(
+    config: &Path,
+    subnet_id: SubnetId,
+    data_root: &Path,
+    cmd: &FindDivergenceCmd,
+) -> Result<(), String> {
+    std::fs::create_dir_all(&cmd.work_dir)
+        .map_err(|err| format!("Failed to create {:?}: {}", cmd.work_dir, err))?;
+    let replays = Replays {
+        config,
+        subnet_id,
+        data_root,
+        cmd,
+    };
+
+    let (data_root_a, trace_a) = replays.run(&cmd.binary_a, "a", None)?;
+    let (data_root_b, trace_b) = replays.run(&cmd.binary_b, "b", None)?;
+    let trace_a = read_hash_trace(&trace_a)?;
+    let trace_b = read_hash_trace(&trace_b)?;
+
+    let height = match first_divergence(&trace_a, &trace_b) {
+        None => {
+            println!(
+                "✓ No divergence: the state hashes of all {} certified heights are identical",
+                trace_a.len()
+            );
+            return Ok(());
+        }
+        Some(Divergence::Missing(height)) => {
+            let (certified, uncertified) = if trace_a.contains_key(&height) {
+                (&cmd.binary_a, &cmd.binary_b)
+            } else {
+                (&cmd.binary_b, &cmd.binary_a)
+            };
+            println!(
+                "✗ Only {:?} certified the state at height {}; {:?} computed a different \
+                 state hash or stopped early, see the output above",
+                certified, height, uncertified
+            );
+            height
+        }
+        Some(Divergence::Hash(height)) => {
+            println!(
+                "✗ State hashes diverge at height {}:\n  {:?}: {}\n  {:?}: {}",
+                height, cmd.binary_a, trace_a[&height], cmd.binary_b, trace_b[&height]
+            );
+            height
+        }
+    };
+
+    // Free the space of the full replays before creating the checkpoints.
+    for data_root in [data_root_a, data_root_b] {
+        std::fs::remove_dir_all(&data_root)
+            .map_err(|err| format!("Failed to remove {:?}: {}", data_root, err))?;
+    }
+    let (data_root_a, _) = replays.run(&cmd.binary_a, "a", Some(height))?;
+    let (data_root_b, _) = replays.run(&cmd.binary_b, "b", Some(height))?;
+
+    let changes = diff_checkpoints(
+        subnet_type(subnet_id, &cmd.registry_local_store_path)?,
+        &checkpoint_path(&data_root_a, height),
+        &checkpoint_path(&data_root_b, height),
+    )?;
+    print_changes_per_canister(&changes);
+    Ok(())
+}
+
+/// Returns the path of the checkpoint at `height` in the given data root.
+fn checkpoint_path(data_root: &Path, height: Height) -> PathBuf {
+    data_root
+        .join("ic_state")
+        .join("checkpoints")
+        .join(format!("{:016x}", height.get()))
+}
+
+/// Returns the type of the subnet according to the latest version of the
+/// registry in the backup's local store, as the replays do.
+fn subnet_type(
+    subnet_id: SubnetId,
+    registry_local_store_path: &Path,
+) -> Result<SubnetType, String> {
+    let data_provider = Arc::new(LocalStoreImpl::new(registry_local_store_path));
+    let registry = RegistryClientImpl::new(data_provider, None);
+    registry
+        .poll_once()
+        .map_err(|err| format!("Failed to poll the registry local store: {}", err))?;
+    Ok(get_subnet_type(
+        &registry,
+        subnet_id,
+        registry.get_latest_version(),
+        &no_op_logger(),
+    ))
+}
+
+/// Loads the checkpoints at `path_a` and `path_b` of a subnet of type
+/// `subnet_type` and diffs their certified state trees.
+fn diff_checkpoints(
+    subnet_type: SubnetType,
+    path_a: &Path,
+    path_b: &Path,
+) -> Result<Changes, String> {
+    let dummy_metrics_registry = ic_metrics::MetricsRegistry::new();
+    let dummy_metrics = CheckpointMetrics::new(&dummy_metrics_registry);
+    let load = |path: &Path| {
+        let layout = CompleteCheckpointLayout::new_untracked(path.to_path_buf(), Height::from(0))
+            .map_err(|err| format!("Failed to open checkpoint {:?}: {}", path, err))?;
+        load_checkpoint_parallel(
+            &layout,
+            subnet_type,
+            &dummy_metrics,
+            Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+        )
+        .map_err(|err| format!("Failed to load checkpoint {:?}: {}", path, err))
+    };
+    let state_a = load(path_a)?;
+    let state_b = load(path_b)?;
+    Ok(diff(&hash_state(&state_a), &hash_state(&state_b)))
+}
+
+/// Groups the changes by the canister they belong to. Changes outside of the
+/// canister subtree are grouped under `None`.
+fn changes_per_canister(changes: &Changes) -> BTreeMap<Option<PrincipalId>, Changes> {
+    let mut grouped: BTreeMap<_, Changes> = BTreeMap::new();
+    for (path, change) in changes {
+        let canister_id = match &path[..] {
+            [subtree, canister_id, ..] if subtree.as_bytes() == CANISTER_LABEL => {
+                PrincipalId::try_from(canister_id.as_bytes()).ok()
+            }
+            _ => None,
+        };
+        grouped
+            .entry(canister_id)
+            .or_default()
+            .insert(path.clone(), change.clone());
+    }
+    grouped
+}
+
+fn print_changes_per_canister(changes: &Changes) {
+    for (canister_id, changes) in changes_per_canister(changes) {
+        match canister_id {
+            Some(canister_id) => println!("Canister {}:", canister_id),
+            None => println!("Subnet state:"),
+        }
+        for (path, change) in changes {
+            println!("  {} {}", path, change);
+        }
+    }
+}
+
+/// Recursively copies the directory `src` to `dst`.
+fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_crypto_tree_hash::{Digest, Label, Path as LabeledPath};
+    use ic_state_manager::tree_diff::Change;
+
+    fn path(labels: &[&[u8]]) -> LabeledPath {
+        labels.iter().map(|label| Label::from(*label)).collect()
+    }
+
+    #[test]
+    fn changes_are_grouped_per_canister() {
+        let canister_1 = PrincipalId::new_user_test_id(1);
+        let canister_2 = PrincipalId::new_user_test_id(2);
+        let change = Change::InsertLeaf(Digest([0; 32]));
+        let changes: Changes = [
+            path(&[b"time"]),
+            path(&[CANISTER_LABEL, canister_1.as_slice(), b"certified_data"]),
+            path(&[CANISTER_LABEL, canister_2.as_slice(), b"module_hash"]),
+            path(&[CANISTER_LABEL, canister_2.as_slice(), b"controllers"]),
+        ]
+        .into_iter()
+        .map(|path| (path, change.clone()))
+        .collect();
+
+        let grouped = changes_per_canister(&changes);
+        assert_eq!(
+            grouped.keys().cloned().collect::<Vec<_>>(),
+            vec![None, Some(canister_1), Some(canister_2)]
+        );
+        assert_eq!(grouped[&Some(canister_2)].len(), 2);
+    }
+}
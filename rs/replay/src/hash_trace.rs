@@ -0,0 +1,150 @@
+//! A trace of the certified state hashes computed during a replay.
+//!
+//! The trace contains one line per height whose certification was delivered
+//! to the state manager, i.e. whose locally computed state hash matches the
+//! certified one. Each line consists of the height and the hex-encoded
+//! certified (partial) state hash at that height, e.g.
+//!
+//! ```text
+//! 1234 5a1c0e...
+//! 1235 0f33b2...
+//! ```
+//!
+//! Traces of two replays of the same backup can therefore be compared with
+//! plain `diff`, or with [`first_divergence`].
+
+use ic_types::{CryptoHashOfPartialState, Height};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Appends the state hashes of newly certified heights to a file.
+pub struct HashTrace {
+    path: PathBuf,
+    // The highest height written to the trace so far.
+    last_height: Cell<Option<Height>>,
+}
+
+impl HashTrace {
+    /// Creates the trace file at `path`, truncating it if it exists.
+    pub fn new(path: PathBuf) -> Self {
+        File::create(&path)
+            .unwrap_or_else(|err| panic!("Failed to create hash trace {:?}: {}", path, err));
+        Self {
+            path,
+            last_height: Cell::new(None),
+        }
+    }
+
+    /// Appends the given state hashes to the trace, skipping heights that were
+    /// already written.
+    pub fn record(&self, mut hashes: Vec<(Height, CryptoHashOfPartialState)>) {
+        hashes.sort_by_key(|(height, _)| *height);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .unwrap_or_else(|err| panic!("Failed to open hash trace {:?}: {}", self.path, err));
+        for (height, hash) in hashes {
+            if self.last_height.get().map_or(false, |last| height <= last) {
+                continue;
+            }
+            writeln!(file, "{} {}", height, hex::encode(&hash.get_ref().0)).unwrap_or_else(|err| {
+                panic!("Failed to write hash trace {:?}: {}", self.path, err)
+            });
+            self.last_height.set(Some(height));
+        }
+    }
+}
+
+/// Reads a trace written by [`HashTrace`].
+pub fn read_hash_trace(path: &Path) -> Result<BTreeMap<Height, String>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Failed to open hash trace {:?}: {}", path, err))?;
+    let mut trace = BTreeMap::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| format!("Failed to read hash trace {:?}: {}", path, err))?;
+        let (height, hash) = line
+            .split_once(' ')
+            .and_then(|(height, hash)| Some((height.parse::<u64>().ok()?, hash)))
+            .ok_or_else(|| format!("Malformed line {} in hash trace {:?}", i + 1, path))?;
+        trace.insert(Height::from(height), hash.to_string());
+    }
+    Ok(trace)
+}
+
+/// The first height at which two hash traces differ.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// Both traces contain the height, but with different hashes.
+    Hash(Height),
+    /// Only one of the traces contains the height, e.g. because the state
+    /// computed by one of the replays does not match the certification.
+    Missing(Height),
+}
+
+/// Returns the first height at which the traces `a` and `b` differ, or `None`
+/// if they are identical.
+pub fn first_divergence(
+    a: &BTreeMap<Height, String>,
+    b: &BTreeMap<Height, String>,
+) -> Option<Divergence> {
+    let heights = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+    heights
+        .into_iter()
+        .find_map(|height| match (a.get(height), b.get(height)) {
+            (Some(hash_a), Some(hash_b)) if hash_a == hash_b => None,
+            (Some(_), Some(_)) => Some(Divergence::Hash(*height)),
+            _ => Some(Divergence::Missing(*height)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::crypto::CryptoHash;
+
+    fn hash(byte: u8) -> CryptoHashOfPartialState {
+        CryptoHashOfPartialState::from(CryptoHash(vec![byte; 32]))
+    }
+
+    #[test]
+    fn hash_trace_round_trip_skips_recorded_heights() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace");
+        let trace = HashTrace::new(path.clone());
+        trace.record(vec![(Height::from(2), hash(2)), (Height::from(1), hash(1))]);
+        trace.record(vec![(Height::from(2), hash(9)), (Height::from(3), hash(3))]);
+
+        let read = read_hash_trace(&path).unwrap();
+        assert_eq!(
+            read.into_iter().collect::<Vec<_>>(),
+            vec![
+                (Height::from(1), hex::encode([1; 32])),
+                (Height::from(2), hex::encode([2; 32])),
+                (Height::from(3), hex::encode([3; 32])),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_divergence_finds_lowest_differing_height() {
+        let trace = |hashes: &[(u64, &str)]| {
+            hashes
+                .iter()
+                .map(|(height, hash)| (Height::from(*height), hash.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let a = trace(&[(1, "aa"), (2, "bb"), (3, "cc"), (4, "dd")]);
+        assert_eq!(first_divergence(&a, &a), None);
+        assert_eq!(
+            first_divergence(&a, &trace(&[(1, "aa"), (2, "bb"), (3, "xx"), (4, "yy")])),
+            Some(Divergence::Hash(Height::from(3)))
+        );
+        assert_eq!(
+            first_divergence(&a, &trace(&[(1, "aa"), (2, "bb")])),
+            Some(Divergence::Missing(Height::from(3)))
+        );
+    }
+}
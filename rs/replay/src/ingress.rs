@@ -1,8 +1,8 @@
 use crate::cmd::{
-    AddAndBlessReplicaVersionCmd, AddRegistryContentCmd, WithLedgerAccountCmd, WithNeuronCmd,
-    WithTrustedNeuronsFollowingNeuronCmd,
+    AddAndBlessReplicaVersionCmd, AddRegistryContentCmd, CanisterCallCmd, WithLedgerAccountCmd,
+    WithNeuronCmd, WithTrustedNeuronsFollowingNeuronCmd,
 };
-use candid::{decode_one, Encode};
+use candid::{decode_one, Encode, IDLArgs};
 use ic_canister_client::{prepare_update, Agent, Sender};
 use ic_nervous_system_common::ledger;
 use ic_nns_common::pb::v1::NeuronId;
@@ -27,7 +27,10 @@ use ic_registry_transport::{
     pb::v1::{registry_mutation, Precondition, RegistryMutation},
     serialize_atomic_mutate_request,
 };
-use ic_types::{messages::SignedIngress, CanisterId, PrincipalId, SubnetId, Time};
+use ic_types::{
+    messages::{SignedIngress, UserQuery},
+    CanisterId, PrincipalId, SubnetId, Time, UserId,
+};
 use icp_ledger::{AccountIdentifier, Memo, SendArgs, Tokens};
 use prost::Message;
 use std::convert::TryFrom;
@@ -79,6 +82,50 @@ fn agent_with_principal_as_sender(principal: &PrincipalId) -> Agent {
     )
 }
 
+/// Encodes the Candid text `arg` as the argument of a call.
+pub fn encode_candid_arg(arg: &str) -> Result<Vec<u8>, String> {
+    arg.parse::<IDLArgs>()
+        .and_then(|args| args.to_bytes())
+        .map_err(|err| format!("Invalid Candid argument {:?}: {}", arg, err))
+}
+
+/// Formats a reply as Candid text, falling back to hex if the reply is not
+/// valid Candid.
+pub fn format_candid_reply(bytes: &[u8]) -> String {
+    match IDLArgs::from_bytes(bytes) {
+        Ok(args) => args.to_string(),
+        Err(_) => format!("0x{}", hex::encode(bytes)),
+    }
+}
+
+fn call_sender(cmd: &CanisterCallCmd) -> PrincipalId {
+    cmd.sender.unwrap_or_else(PrincipalId::new_anonymous)
+}
+
+/// Creates the query described by `cmd`.
+pub fn cmd_make_query(cmd: &CanisterCallCmd, ingress_expiry: Time) -> Result<UserQuery, String> {
+    Ok(UserQuery {
+        source: UserId::from(call_sender(cmd)),
+        receiver: cmd.canister,
+        method_name: cmd.method.clone(),
+        method_payload: encode_candid_arg(&cmd.arg)?,
+        ingress_expiry: ingress_expiry.as_nanos_since_unix_epoch(),
+        nonce: None,
+    })
+}
+
+/// Creates the ingress message described by `cmd`.
+pub fn cmd_make_update(cmd: &CanisterCallCmd, time: Time) -> Result<SignedIngress, String> {
+    let agent = agent_with_principal_as_sender(&call_sender(cmd));
+    make_signed_ingress(
+        &agent,
+        cmd.canister,
+        &cmd.method,
+        encode_candid_arg(&cmd.arg)?,
+        time,
+    )
+}
+
 pub fn cmd_add_neuron(time: Time, cmd: &WithNeuronCmd) -> Result<Vec<IngressWithPrinter>, String> {
     let mut msgs = vec![];
 
@@ -503,3 +550,18 @@ pub fn update_subnet_record(
         context_time + Duration::from_secs(60),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candid_arg_round_trip() {
+        let arg = "(42 : nat64, \"hello\")";
+        let bytes = encode_candid_arg(arg).unwrap();
+        let reply = format_candid_reply(&bytes).parse::<IDLArgs>().unwrap();
+        assert_eq!(reply.args, arg.parse::<IDLArgs>().unwrap().args);
+        assert_eq!(format_candid_reply(&[1, 2, 3]), "0x010203");
+        assert!(encode_candid_arg("(42").is_err());
+    }
+}
@@ -14,15 +14,18 @@
 //!
 //! Use `ic-replay --help` to find out more.
 
-use crate::cmd::{ReplayToolArgs, SubCommand};
+use crate::cmd::{CanisterCallCmd, ReplayToolArgs, SubCommand};
+use crate::divergence::find_divergence;
 use crate::ingress::*;
-use crate::player::{Player, ReplayResult};
+use crate::player::{Player, ReplayError, ReplayResult};
 
 use ic_canister_client::{Agent, Sender};
 use ic_config::{Config, ConfigSource};
 use ic_nns_constants::GOVERNANCE_CANISTER_ID;
 use ic_protobuf::registry::subnet::v1::InitialNiDkgTranscriptRecord;
 use ic_types::consensus::CatchUpPackage;
+use ic_types::ingress::{IngressState, IngressStatus, WasmResult};
+use ic_types::time::current_time;
 use ic_types::ReplicaVersion;
 use prost::Message;
 use std::cell::RefCell;
@@ -32,9 +35,12 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::rc::Rc;
+use std::time::Duration;
 
 mod backup;
 pub mod cmd;
+mod divergence;
+mod hash_trace;
 pub mod ingress;
 mod mocks;
 pub mod player;
@@ -59,6 +65,7 @@ mod validator;
 ///     canister_caller_id: None,
 ///     replay_until_height: None,
 ///     data_root: None,
+///     hash_trace: None,
 ///     subcmd: Some(SubCommand::RestoreFromBackup(RestoreFromBackupCmd {
 ///         registry_local_store_path: PathBuf::from("/path/to/ic_registry_local_store"),
 ///         backup_spool_path: PathBuf::from("/path/to/spool"),
@@ -87,6 +94,20 @@ pub fn replay(args: ReplayToolArgs) -> ReplayResult {
             }
         }
 
+        if let Some(SubCommand::FindDivergence(cmd)) = subcmd {
+            let result = match (&args.config, &args.subnet_id, &args.data_root) {
+                (Some(config), Some(subnet_id), Some(data_root)) => {
+                    find_divergence(config, subnet_id.0, data_root, cmd)
+                }
+                _ => Err("Config file, subnet id and data root are required!".to_string()),
+            };
+            if let Err(err) = result {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+
         let source = ConfigSource::File(args.config.unwrap_or_else(|| {
             println!("Config file is required!");
             std::process::exit(1);
@@ -112,6 +133,15 @@ pub fn replay(args: ReplayToolArgs) -> ReplayResult {
             })
             .0;
 
+        // Reject invalid arguments before spending hours on the replay.
+        if let Some(SubCommand::Query(cmd) | SubCommand::DryRun(cmd)) = subcmd {
+            if let Err(err) = encode_candid_arg(&cmd.arg) {
+                println!("{}", err);
+                *res_clone.borrow_mut() = Err(ReplayError::CanisterCallFailed(err));
+                return;
+            }
+        }
+
         let target_height = args.replay_until_height;
         if let Some(h) = target_height {
             let question = format!("The checkpoint created at height {} ", h)
@@ -134,7 +164,8 @@ pub fn replay(args: ReplayToolArgs) -> ReplayResult {
                 subnet_id,
                 cmd.start_height,
             )
-            .with_replay_target_height(target_height);
+            .with_replay_target_height(target_height)
+            .with_hash_trace(args.hash_trace);
             *res_clone.borrow_mut() = player.restore(cmd.start_height + 1);
             return;
         }
@@ -142,14 +173,16 @@ pub fn replay(args: ReplayToolArgs) -> ReplayResult {
         {
             let _enter_guard = rt.enter();
             let player = match (subcmd.as_ref(), target_height) {
-                (Some(_), Some(_)) => {
+                (Some(cmd), Some(_))
+                    if !matches!(cmd, SubCommand::Query(_) | SubCommand::DryRun(_)) =>
+                {
                     panic!(
-                    "Target height cannot be used with any sub-command in subnet-recovery mode."
+                    "Target height can only be used with the query and dry-run sub-commands in subnet-recovery mode."
                 );
                 }
-                (_, target_height) => {
-                    Player::new(cfg, subnet_id).with_replay_target_height(target_height)
-                }
+                (_, target_height) => Player::new(cfg, subnet_id)
+                    .with_replay_target_height(target_height)
+                    .with_hash_trace(args.hash_trace),
             };
 
             if let Some(SubCommand::GetRecoveryCup(cmd)) = subcmd {
@@ -209,14 +242,19 @@ pub fn replay(args: ReplayToolArgs) -> ReplayResult {
             };
 
             *res_clone.borrow_mut() = match player.replay(extra) {
-                Ok(state_params) => {
-                    if let Some(SubCommand::UpdateRegistryLocalStore) = subcmd {
+                Ok(state_params) => match subcmd {
+                    Some(SubCommand::UpdateRegistryLocalStore) => {
                         player.update_registry_local_store();
                         Ok(player.get_latest_state_params(None, Vec::new()))
-                    } else {
-                        Ok(state_params)
                     }
-                }
+                    Some(SubCommand::Query(cmd)) => cmd_query(&player, cmd)
+                        .map(|()| state_params)
+                        .map_err(canister_call_failed),
+                    Some(SubCommand::DryRun(cmd)) => cmd_dry_run(&player, cmd)
+                        .map(|()| state_params)
+                        .map_err(canister_call_failed),
+                    _ => Ok(state_params),
+                },
                 err => err,
             }
         }
@@ -236,6 +274,52 @@ pub fn consent_given(question: &str) -> bool {
     matches!(s.as_str(), "\n" | "y\n" | "Y\n")
 }
 
+// Prints the error of the query or dry-run sub-command and returns it as a
+// `ReplayError`.
+fn canister_call_failed(err: String) -> ReplayError {
+    println!("{}", err);
+    ReplayError::CanisterCallFailed(err)
+}
+
+// Runs the query described by `cmd` against the latest state and prints the
+// result.
+fn cmd_query(player: &Player, cmd: &CanisterCallCmd) -> Result<(), String> {
+    let query = cmd_make_query(cmd, current_time() + Duration::from_secs(60))?;
+    match player.query(query) {
+        Ok(wasm_result) => print_wasm_result(&wasm_result),
+        Err(err) => println!("Query failed: {}", err),
+    }
+    Ok(())
+}
+
+// Executes the update call described by `cmd` on top of the latest state
+// without persisting the resulting state and prints the result.
+fn cmd_dry_run(player: &Player, cmd: &CanisterCallCmd) -> Result<(), String> {
+    let statuses = player.dry_run(|time| Ok(vec![cmd_make_update(cmd, time)?]))?;
+    for (message_id, status) in statuses {
+        match status {
+            IngressStatus::Known {
+                state: IngressState::Completed(wasm_result),
+                ..
+            } => print_wasm_result(&wasm_result),
+            IngressStatus::Known {
+                state: IngressState::Failed(err),
+                ..
+            } => println!("Update failed: {}", err),
+            status => println!("Update {} did not complete: {:?}", message_id, status),
+        }
+    }
+    println!("Dry run: the resulting state was discarded.");
+    Ok(())
+}
+
+fn print_wasm_result(wasm_result: &WasmResult) {
+    match wasm_result {
+        WasmResult::Reply(bytes) => println!("Reply: {}", format_candid_reply(bytes)),
+        WasmResult::Reject(msg) => println!("Reject: {}", msg),
+    }
+}
+
 // Creates a recovery CUP by using the latest CUP and overriding the height and
 // the state hash.
 fn cmd_get_recovery_cup(
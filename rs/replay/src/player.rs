@@ -1,4 +1,5 @@
 use crate::backup::{cup_file_name, rename_file};
+use crate::hash_trace::HashTrace;
 use crate::ingress::IngressWithPrinter;
 use crate::{
     backup,
@@ -14,6 +15,7 @@ use ic_consensus_utils::pool_reader::PoolReader;
 use ic_consensus_utils::{crypto_hashable_to_seed, lookup_replica_version};
 use ic_crypto_for_verification_only::CryptoComponentForVerificationOnly;
 use ic_cycles_account_manager::CyclesAccountManager;
+use ic_error_types::UserError;
 use ic_execution_environment::ExecutionServices;
 use ic_interfaces::{
     certification::CertificationPool,
@@ -55,7 +57,7 @@ use ic_types::{
     batch::Batch,
     consensus::{CatchUpPackage, HasHeight, HasVersion},
     ingress::{IngressState, IngressStatus, WasmResult},
-    messages::UserQuery,
+    messages::{MessageId, SignedIngress, UserQuery},
     time::current_time,
     CryptoHashOfState, Height, PrincipalId, Randomness, RegistryVersion, ReplicaVersion, SubnetId,
     Time, UserId,
@@ -79,6 +81,11 @@ use tempfile::TempDir;
 // Amount of time we are waiting for execution, after batches are delivered.
 const WAIT_DURATION: Duration = Duration::from_millis(500);
 
+// Maximum number of batches delivered in a dry run until the messages finish.
+const MAX_DRY_RUN_BATCHES: u64 = 100;
+// How often we check whether a dry run batch was executed.
+const DRY_RUN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Represents the height, hash and registry version of the last execution state
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct StateParams {
@@ -100,6 +107,9 @@ pub enum ReplayError {
     CUPVerificationFailed(Height),
     /// Replay was successful, but manual inspection is required to choose correct state.
     ManualInspectionRequired(StateParams),
+    /// The canister call of the query or dry-run sub-command is invalid or
+    /// could not be executed.
+    CanisterCallFailed(String),
 }
 
 pub type ReplayResult = Result<StateParams, ReplayError>;
@@ -127,6 +137,9 @@ pub struct Player {
     // The target height until which the state will be replayed.
     // None means finalized height.
     replay_target_height: Option<u64>,
+    // If set, the state hash of every certified height is written to this
+    // trace.
+    hash_trace: Option<HashTrace>,
 }
 
 impl Player {
@@ -340,6 +353,7 @@ impl Player {
             _async_log_guard,
             tmp_dir: None,
             replay_target_height: None,
+            hash_trace: None,
         }
     }
 
@@ -349,6 +363,13 @@ impl Player {
         self
     }
 
+    /// Write the state hash of every height certified during the replay to
+    /// the given file.
+    pub fn with_hash_trace(mut self, hash_trace: Option<PathBuf>) -> Self {
+        self.hash_trace = hash_trace.map(HashTrace::new);
+        self
+    }
+
     /// In case a consensus pool was supplied, replay past finalized but
     /// un-executed blocks by delivering ingress messages for execution,
     /// and make a full checkpoint of the latest state when they all finish.
@@ -500,8 +521,13 @@ impl Player {
                     )
                 })
                 .ok();
+            let hash = certification.signed.content.hash.clone();
+            // Panics if the certified hash differs from the local one.
             self.state_manager
                 .deliver_state_certification(certification);
+            if let Some(hash_trace) = &self.hash_trace {
+                hash_trace.record(vec![(h, hash)]);
+            }
             print!(" {}", h);
         }
         println!();
@@ -669,12 +695,12 @@ impl Player {
         last_batch_height
     }
 
-    fn deliver_extra_batch<F: FnMut(&Player, Time) -> Vec<IngressWithPrinter>>(
+    /// Returns an empty batch to be delivered after the last finalized block.
+    fn extra_batch(
         &self,
         message_routing: &dyn MessageRouting,
         pool: Option<&ConsensusPoolImpl>,
-        mut extra: F,
-    ) -> (Time, Option<(Height, Vec<IngressWithPrinter>)>) {
+    ) -> Batch {
         let (registry_version, time, randomness) = match pool {
             None => (
                 self.registry.get_latest_version(),
@@ -700,9 +726,8 @@ impl Player {
                 )
             }
         };
-        let batch_number = message_routing.expected_batch_height();
-        let mut extra_batch = Batch {
-            batch_number,
+        Batch {
+            batch_number: message_routing.expected_batch_height(),
             requires_full_state_hash: true,
             messages: BatchMessages::default(),
             // Use a fake randomness here since we don't have random tape for extra messages
@@ -711,7 +736,16 @@ impl Player {
             registry_version,
             time,
             consensus_responses: Vec::new(),
-        };
+        }
+    }
+
+    fn deliver_extra_batch<F: FnMut(&Player, Time) -> Vec<IngressWithPrinter>>(
+        &self,
+        message_routing: &dyn MessageRouting,
+        pool: Option<&ConsensusPoolImpl>,
+        mut extra: F,
+    ) -> (Time, Option<(Height, Vec<IngressWithPrinter>)>) {
+        let mut extra_batch = self.extra_batch(message_routing, pool);
         let context_time = extra_batch.time;
         let extra_msgs = extra(self, context_time);
         if extra_msgs.is_empty() {
@@ -740,6 +774,78 @@ impl Player {
         (context_time, Some((extra_batch.batch_number, extra_msgs)))
     }
 
+    /// Executes the ingress messages created by `make_msgs` on top of the
+    /// latest state and returns their final statuses, or their last known
+    /// statuses if they did not finish within `MAX_DRY_RUN_BATCHES` batches.
+    ///
+    /// The batches are delivered without requesting a full state hash, so no
+    /// checkpoint is created and the resulting states only live in memory
+    /// until the tool exits.
+    pub fn dry_run<F: FnOnce(Time) -> Result<Vec<SignedIngress>, String>>(
+        &self,
+        make_msgs: F,
+    ) -> Result<Vec<(MessageId, IngressStatus)>, String> {
+        let mut batch =
+            self.extra_batch(self.message_routing.as_ref(), self.consensus_pool.as_ref());
+        batch.requires_full_state_hash = false;
+        let msgs = make_msgs(batch.time)?;
+        let msg_ids: Vec<_> = msgs.iter().map(|msg| msg.id()).collect();
+        batch.messages.signed_ingress_msgs = msgs;
+
+        let get_latest_status = self.ingress_history_reader.get_latest_status();
+        let is_finished = |status: &IngressStatus| {
+            matches!(
+                status,
+                IngressStatus::Known {
+                    state: IngressState::Completed(_)
+                        | IngressState::Failed(_)
+                        | IngressState::Done,
+                    ..
+                }
+            )
+        };
+        for _ in 0..MAX_DRY_RUN_BATCHES {
+            match self.message_routing.deliver_batch(batch.clone()) {
+                Ok(()) => {
+                    while self.state_manager.latest_state_height() < batch.batch_number {
+                        std::thread::sleep(DRY_RUN_POLL_INTERVAL);
+                    }
+                    // Keep delivering empty batches to execute the downstream
+                    // calls triggered by the messages.
+                    batch.batch_number = batch.batch_number.increment();
+                    batch.time += Duration::from_nanos(1);
+                    batch.messages = BatchMessages::default();
+                }
+                Err(MessageRoutingError::QueueIsFull) => std::thread::sleep(WAIT_DURATION),
+                Err(MessageRoutingError::Ignored { .. }) => {
+                    unreachable!(
+                        "Unexpected error on a valid batch number {}",
+                        batch.batch_number
+                    );
+                }
+            }
+            if msg_ids.iter().all(|id| is_finished(&get_latest_status(id))) {
+                break;
+            }
+        }
+        Ok(msg_ids
+            .into_iter()
+            .map(|id| {
+                let status = get_latest_status(&id);
+                (id, status)
+            })
+            .collect())
+    }
+
+    /// Runs `query` against the latest state.
+    pub fn query(&self, query: UserQuery) -> Result<WasmResult, UserError> {
+        self.http_query_handler.query(
+            query,
+            self.state_manager.get_latest_state().take(),
+            Vec::new(),
+        )
+    }
+
     /// Return latest BlessedReplicaVersions record by querying the registry
     /// canister.
     pub fn get_blessed_replica_versions(
@@ -38,6 +38,7 @@ use ic_interfaces::{
     artifact_manager::{
         AdvertBroadcaster, ArtifactClient, ArtifactManager, ArtifactProcessor, JoinGuard,
     },
+    batch_payload::BatchPayloadBuilder,
     crypto::IngressSigVerifier,
     execution_environment::IngressHistoryReader,
     messaging::{MessageRouting, XNetPayloadBuilder},
@@ -126,6 +127,7 @@ pub fn create_networking_stack(
     state_sync_client: P2PStateSyncClient,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    query_stats_payload_builder: Arc<dyn BatchPayloadBuilder>,
     message_router: Arc<dyn MessageRouting>,
     consensus_crypto: Arc<dyn ConsensusCrypto + Send + Sync>,
     certifier_crypto: Arc<dyn CertificationCrypto + Send + Sync>,
@@ -164,6 +166,7 @@ pub fn create_networking_stack(
         state_sync_client,
         xnet_payload_builder,
         self_validating_payload_builder,
+        query_stats_payload_builder,
         message_router,
         ingress_history_reader,
         artifact_pools,
@@ -238,6 +241,7 @@ fn setup_artifact_manager(
     state_sync_client: P2PStateSyncClient,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    query_stats_payload_builder: Arc<dyn BatchPayloadBuilder>,
     message_router: Arc<dyn MessageRouting>,
     ingress_history_reader: Box<dyn IngressHistoryReader>,
     artifact_pools: ArtifactPools,
@@ -365,6 +369,7 @@ fn setup_artifact_manager(
                 xnet_payload_builder,
                 self_validating_payload_builder,
                 canister_http_payload_builder,
+                query_stats_payload_builder,
                 Arc::clone(&artifact_pools.dkg_pool) as Arc<_>,
                 Arc::clone(&artifact_pools.ecdsa_pool) as Arc<_>,
                 Arc::clone(&dkg_key_manager) as Arc<_>,
@@ -205,6 +205,12 @@ pub fn construct_ic_stack(
         registry.clone(),
         log.clone(),
     ));
+    // ---------- QUERY STATS DEPS FOLLOW ----------
+    let query_stats_payload_builder = Arc::new(
+        execution_services
+            .query_stats_payload_builder
+            .into_payload_builder(state_manager.clone(), node_id, log.clone()),
+    );
     // ---------- HTTPS OUTCALLS DEPS FOLLOW ----------
     let canister_http_adapter_client = setup_canister_http_client(
         rt_handle.clone(),
@@ -234,6 +240,7 @@ pub fn construct_ic_stack(
         P2PStateSyncClient::Client(state_sync),
         xnet_payload_builder,
         self_validating_payload_builder,
+        query_stats_payload_builder,
         message_router,
         // TODO(SCL-213)
         Arc::clone(&crypto) as Arc<_>,
@@ -5,7 +5,7 @@ use ic_error_types::{ErrorCode, RejectCode};
 use ic_ic00_types::{
     self as ic00, CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgsBuilder,
     CanisterStatusResultV2, CanisterStatusType, EmptyBlob, InstallCodeArgs, Method, Payload,
-    UpdateSettingsArgs, IC_00,
+    QueryStats, UpdateSettingsArgs, IC_00,
 };
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_replica_tests as utils;
@@ -710,6 +710,10 @@ fn can_get_canister_information() {
                 None,
                 2592000,
                 0u128,
+                QueryStats::default(),
+                None,
+                0u128,
+                None,
             )
         );
 
@@ -761,6 +765,10 @@ fn can_get_canister_information() {
                     None,
                     259200,
                     0u128,
+                    QueryStats::default(),
+                    None,
+                    0u128,
+                    None,
                 ),
                 CanisterStatusResultV2::decode(&res).unwrap(),
                 2 * BALANCE_EPSILON,
@@ -1,8 +1,10 @@
+pub mod canister_snapshots;
 pub mod execution_state;
 pub(crate) mod queues;
 pub mod system_state;
 #[cfg(test)]
 mod tests;
+pub mod wasm_chunk_store;
 
 use crate::canister_state::queues::CanisterOutputQueuesIterator;
 use crate::canister_state::system_state::{CanisterStatus, ExecutionTask, SystemState};
@@ -10,6 +12,7 @@ use crate::{InputQueueType, StateError};
 pub use execution_state::{EmbedderCache, ExecutionState, ExportedFunctions, Global};
 use ic_ic00_types::CanisterStatusType;
 use ic_interfaces::messages::CanisterMessage;
+use ic_protobuf::state::canister_state_bits::v1 as pb;
 use ic_registry_subnet_type::SubnetType;
 use ic_types::methods::SystemMethod;
 use ic_types::time::UNIX_EPOCH;
@@ -19,7 +22,7 @@ use ic_types::{
     AccumulatedPriority, CanisterId, ComputeAllocation, ExecutionRound, MemoryAllocation, NumBytes,
     PrincipalId, Time,
 };
-use ic_types::{LongExecutionMode, NumInstructions};
+use ic_types::{LongExecutionMode, NumInstructions, MAX_WASM_MEMORY_IN_BYTES};
 use phantom_newtype::AmountOf;
 pub use queues::{CanisterQueues, DEFAULT_QUEUE_CAPACITY};
 use std::collections::BTreeSet;
@@ -72,6 +75,43 @@ pub struct SchedulerState {
     /// needed to calculate how much time should be considered when charging
     /// occurs.
     pub time_of_last_allocation_charge: Time,
+
+    /// Query statistics of the canister, aggregated across the subnet.
+    pub total_query_stats: TotalQueryStats,
+}
+
+/// Statistics of the queries a canister has executed, aggregated over all
+/// nodes of the subnet through consensus.
+///
+/// The numbers are deterministic, i.e. the same on every replica.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TotalQueryStats {
+    pub num_calls: u64,
+    pub num_instructions: u64,
+    pub ingress_payload_size: u64,
+    pub egress_payload_size: u64,
+}
+
+impl From<&TotalQueryStats> for pb::TotalQueryStats {
+    fn from(item: &TotalQueryStats) -> Self {
+        Self {
+            num_calls: item.num_calls,
+            num_instructions: item.num_instructions,
+            ingress_payload_size: item.ingress_payload_size,
+            egress_payload_size: item.egress_payload_size,
+        }
+    }
+}
+
+impl From<pb::TotalQueryStats> for TotalQueryStats {
+    fn from(item: pb::TotalQueryStats) -> Self {
+        Self {
+            num_calls: item.num_calls,
+            num_instructions: item.num_instructions,
+            ingress_payload_size: item.ingress_payload_size,
+            egress_payload_size: item.egress_payload_size,
+        }
+    }
 }
 
 impl Default for SchedulerState {
@@ -85,6 +125,7 @@ impl Default for SchedulerState {
             heap_delta_debit: 0.into(),
             install_code_debit: 0.into(),
             time_of_last_allocation_charge: UNIX_EPOCH,
+            total_query_stats: TotalQueryStats::default(),
         }
     }
 }
@@ -234,6 +275,7 @@ impl CanisterState {
             (None, true) => NextExecution::StartNew,
             (Some(ExecutionTask::Heartbeat), _) => NextExecution::StartNew,
             (Some(ExecutionTask::GlobalTimer), _) => NextExecution::StartNew,
+            (Some(ExecutionTask::OnLowWasmMemory), _) => NextExecution::StartNew,
             (Some(ExecutionTask::AbortedExecution { .. }), _)
             | (Some(ExecutionTask::PausedExecution(..)), _) => NextExecution::ContinueLong,
             (Some(ExecutionTask::AbortedInstallCode { .. }), _)
@@ -253,6 +295,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -266,6 +309,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedExecution { .. })
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -279,6 +323,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::AbortedExecution { .. })
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -292,6 +337,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedExecution { .. }) => false,
@@ -427,12 +473,16 @@ impl CanisterState {
 
     /// The amount of memory currently being used by the canister.
     ///
-    /// This only includes execution memory (heap, stable, globals, Wasm)
-    /// and canister history memory for system subnets; and execution memory
-    /// plus system state memory (canister messages and canister history)
-    /// for application subnets.
+    /// This only includes execution memory (heap, stable, globals, Wasm),
+    /// canister history memory, canister snapshots memory and Wasm chunk
+    /// store memory for system subnets; and execution memory plus system
+    /// state memory (canister messages, canister history, canister snapshots
+    /// and Wasm chunk store) for application subnets.
     pub fn memory_usage(&self, own_subnet_type: SubnetType) -> NumBytes {
-        let mut result = self.raw_memory_usage() + self.canister_history_memory_usage();
+        let mut result = self.raw_memory_usage()
+            + self.canister_history_memory_usage()
+            + self.canister_snapshots_memory_usage()
+            + self.wasm_chunk_store_memory_usage();
         if own_subnet_type != SubnetType::System {
             result += self.message_memory_usage();
         }
@@ -466,6 +516,16 @@ impl CanisterState {
         self.system_state.canister_history_memory_usage()
     }
 
+    /// Returns the amount of memory used by canister snapshots in bytes.
+    pub fn canister_snapshots_memory_usage(&self) -> NumBytes {
+        self.system_state.canister_snapshots_memory_usage()
+    }
+
+    /// Returns the amount of memory used by the Wasm chunk store in bytes.
+    pub fn wasm_chunk_store_memory_usage(&self) -> NumBytes {
+        self.system_state.wasm_chunk_store_memory_usage()
+    }
+
     /// Hack to get the dashboard templating working.
     pub fn memory_usage_ref(&self, own_subnet_type: &SubnetType) -> NumBytes {
         self.memory_usage(*own_subnet_type)
@@ -509,6 +569,31 @@ impl CanisterState {
         self.exports_method(&WasmMethod::System(SystemMethod::CanisterGlobalTimer))
     }
 
+    /// Returns true if the canister exports the `canister_on_low_wasm_memory`
+    /// system method.
+    pub fn exports_on_low_wasm_memory_method(&self) -> bool {
+        self.exports_method(&WasmMethod::System(SystemMethod::CanisterOnLowWasmMemory))
+    }
+
+    /// Returns true if the free Wasm heap of the canister is below the given
+    /// threshold. The free heap is measured against the `wasm_memory_limit`
+    /// of the canister or the maximum Wasm memory size if there is no limit.
+    pub fn is_low_on_wasm_memory(&self, threshold: NumBytes) -> bool {
+        let wasm_memory_usage = match &self.execution_state {
+            Some(execution_state) => num_bytes_try_from(execution_state.wasm_memory.size)
+                .expect("could not convert from wasm memory number of pages to bytes"),
+            None => return false,
+        };
+        let wasm_memory_limit = self
+            .system_state
+            .wasm_memory_limit
+            .unwrap_or_else(|| NumBytes::new(MAX_WASM_MEMORY_IN_BYTES));
+        wasm_memory_limit
+            .get()
+            .saturating_sub(wasm_memory_usage.get())
+            < threshold.get()
+    }
+
     /// Returns true if the canister exports the given Wasm method.
     pub fn exports_method(&self, method: &WasmMethod) -> bool {
         match &self.execution_state {
@@ -0,0 +1,248 @@
+use crate::{canister_state::execution_state::Memory, num_bytes_try_from, Global};
+use ic_types::{CanisterId, NumBytes, PrincipalId, Time};
+use ic_wasm_types::CanisterModule;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Maximum number of snapshots that a canister can hold at any point in time.
+pub const MAX_SNAPSHOTS_PER_CANISTER: usize = 1;
+
+/// Size of the local part of a `SnapshotId` in its byte representation.
+pub(crate) const LOCAL_ID_LENGTH: usize = std::mem::size_of::<u64>();
+
+/// A globally unique identifier of a canister snapshot.
+///
+/// It consists of the id of the canister the snapshot belongs to and of an
+/// identifier that is unique among the snapshots ever taken of that canister.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotId {
+    canister_id: CanisterId,
+    local_id: u64,
+}
+
+impl SnapshotId {
+    pub fn new(canister_id: CanisterId, local_id: u64) -> Self {
+        Self {
+            canister_id,
+            local_id,
+        }
+    }
+
+    pub fn canister_id(&self) -> CanisterId {
+        self.canister_id
+    }
+
+    pub fn local_id(&self) -> u64 {
+        self.local_id
+    }
+
+    /// Returns the byte representation of the snapshot id as exposed in the
+    /// management canister interface: the big-endian encoding of the local id
+    /// followed by the bytes of the canister id.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = self.local_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.canister_id.get_ref().as_slice());
+        bytes
+    }
+}
+
+impl fmt::Display for SnapshotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.canister_id, self.local_id)
+    }
+}
+
+impl TryFrom<&[u8]> for SnapshotId {
+    type Error = String;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() <= LOCAL_ID_LENGTH {
+            return Err(format!("Invalid snapshot id length {}", bytes.len()));
+        }
+        let (local_id, canister_id) = bytes.split_at(LOCAL_ID_LENGTH);
+        let local_id = u64::from_be_bytes(local_id.try_into().unwrap());
+        let canister_id = PrincipalId::try_from(canister_id)
+            .map_err(|err| format!("Invalid canister id in snapshot id: {}", err))
+            .and_then(|principal| {
+                CanisterId::new(principal)
+                    .map_err(|err| format!("Invalid canister id in snapshot id: {}", err))
+            })?;
+        Ok(Self::new(canister_id, local_id))
+    }
+}
+
+/// A snapshot of the state of a canister that can later be loaded back
+/// into the canister.
+///
+/// The snapshot captures the Wasm module, the Wasm and stable memories, the
+/// exported globals and the certified data of the canister.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanisterSnapshot {
+    /// The time at which the snapshot was taken.
+    taken_at_timestamp: Time,
+    /// The canister version at the time the snapshot was taken.
+    canister_version: u64,
+    certified_data: Vec<u8>,
+    wasm_binary: CanisterModule,
+    exported_globals: Vec<Global>,
+    wasm_memory: Memory,
+    stable_memory: Memory,
+}
+
+// `Global` does not implement `Eq` because of its floating point variants,
+// but its `PartialEq` implementation is reflexive for all values a canister
+// can export, so it is safe to mark snapshots as `Eq`.
+impl Eq for CanisterSnapshot {}
+
+impl CanisterSnapshot {
+    pub fn new(
+        taken_at_timestamp: Time,
+        canister_version: u64,
+        certified_data: Vec<u8>,
+        wasm_binary: CanisterModule,
+        exported_globals: Vec<Global>,
+        wasm_memory: Memory,
+        stable_memory: Memory,
+    ) -> Self {
+        Self {
+            taken_at_timestamp,
+            canister_version,
+            certified_data,
+            wasm_binary,
+            exported_globals,
+            wasm_memory,
+            stable_memory,
+        }
+    }
+
+    pub fn taken_at_timestamp(&self) -> Time {
+        self.taken_at_timestamp
+    }
+
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version
+    }
+
+    pub fn certified_data(&self) -> &Vec<u8> {
+        &self.certified_data
+    }
+
+    pub fn wasm_binary(&self) -> &CanisterModule {
+        &self.wasm_binary
+    }
+
+    pub fn exported_globals(&self) -> &Vec<Global> {
+        &self.exported_globals
+    }
+
+    pub fn wasm_memory(&self) -> &Memory {
+        &self.wasm_memory
+    }
+
+    pub fn wasm_memory_mut(&mut self) -> &mut Memory {
+        &mut self.wasm_memory
+    }
+
+    pub fn stable_memory(&self) -> &Memory {
+        &self.stable_memory
+    }
+
+    pub fn stable_memory_mut(&mut self) -> &mut Memory {
+        &mut self.stable_memory
+    }
+
+    /// Returns the memory taken by the snapshot. It is computed the same way
+    /// as the memory usage of an `ExecutionState`.
+    pub fn size(&self) -> NumBytes {
+        // We use 8 bytes per global.
+        let globals_size_bytes = 8 * self.exported_globals.len() as u64;
+        let wasm_binary_size_bytes = self.wasm_binary.len() as u64;
+        let certified_data_size_bytes = self.certified_data.len() as u64;
+        num_bytes_try_from(self.wasm_memory.size)
+            .expect("could not convert from wasm memory number of pages to bytes")
+            + num_bytes_try_from(self.stable_memory.size)
+                .expect("could not convert from stable memory number of pages to bytes")
+            + NumBytes::from(globals_size_bytes)
+            + NumBytes::from(wasm_binary_size_bytes)
+            + NumBytes::from(certified_data_size_bytes)
+    }
+}
+
+/// The snapshots of a single canister, ordered by their ids.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanisterSnapshots {
+    snapshots: BTreeMap<SnapshotId, CanisterSnapshot>,
+    /// The local id to be assigned to the next snapshot. It is never
+    /// decremented so that snapshot ids are not reused.
+    next_local_id: u64,
+    /// Sum of the sizes of all snapshots. We pre-compute and store the sum
+    /// because the memory usage of a canister is requested frequently.
+    memory_usage: NumBytes,
+}
+
+impl CanisterSnapshots {
+    pub fn new(snapshots: BTreeMap<SnapshotId, CanisterSnapshot>, next_local_id: u64) -> Self {
+        let memory_usage = snapshots.values().map(|s| s.size()).sum();
+        Self {
+            snapshots,
+            next_local_id,
+            memory_usage,
+        }
+    }
+
+    /// Adds a new snapshot of the given canister and returns its id.
+    pub fn push(&mut self, canister_id: CanisterId, snapshot: CanisterSnapshot) -> SnapshotId {
+        let snapshot_id = SnapshotId::new(canister_id, self.next_local_id);
+        self.next_local_id += 1;
+        self.memory_usage += snapshot.size();
+        self.snapshots.insert(snapshot_id, snapshot);
+        snapshot_id
+    }
+
+    /// Removes the snapshot with the given id and returns it, if it exists.
+    pub fn remove(&mut self, snapshot_id: &SnapshotId) -> Option<CanisterSnapshot> {
+        let snapshot = self.snapshots.remove(snapshot_id)?;
+        self.memory_usage -= snapshot.size();
+        Some(snapshot)
+    }
+
+    /// Removes all snapshots, but keeps the next local id so that the ids of
+    /// the removed snapshots are never reused.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.memory_usage = NumBytes::from(0);
+    }
+
+    pub fn get(&self, snapshot_id: &SnapshotId) -> Option<&CanisterSnapshot> {
+        self.snapshots.get(snapshot_id)
+    }
+
+    pub fn get_mut(&mut self, snapshot_id: &SnapshotId) -> Option<&mut CanisterSnapshot> {
+        self.snapshots.get_mut(snapshot_id)
+    }
+
+    pub fn contains(&self, snapshot_id: &SnapshotId) -> bool {
+        self.snapshots.contains_key(snapshot_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SnapshotId, &CanisterSnapshot)> {
+        self.snapshots.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn next_local_id(&self) -> u64 {
+        self.next_local_id
+    }
+
+    pub fn memory_usage(&self) -> NumBytes {
+        self.memory_usage
+    }
+}
@@ -15,11 +15,11 @@ use ic_protobuf::{
 };
 use ic_types::{
     messages::{
-        Ingress, Payload, RejectContext, Request, RequestOrResponse, Response,
+        CallbackId, Ingress, Payload, RejectContext, Request, RequestOrResponse, Response,
         MAX_RESPONSE_COUNT_BYTES,
     },
     xnet::{QueueId, SessionId},
-    CanisterId, CountBytes, Cycles, Time,
+    CanisterId, CoarseTime, CountBytes, Cycles, Time,
 };
 use queue::{IngressQueue, InputQueue, OutputQueue};
 use std::{
@@ -583,8 +583,13 @@ impl CanisterQueues {
         let oq_stats_delta =
             OutputQueuesStats::stats_delta(&RequestOrResponse::Request(msg.clone()));
 
+        // Best-effort requests time out at their own deadline, if earlier.
+        let mut deadline = time + REQUEST_LIFETIME;
+        if msg.is_best_effort() {
+            deadline = deadline.min(msg.deadline.into());
+        }
         output_queue
-            .push_request(msg, time + REQUEST_LIFETIME)
+            .push_request(msg, deadline)
             .expect("cannot fail due to the checks above");
 
         self.input_queues_stats.reserved_slots += 1;
@@ -628,6 +633,7 @@ impl CanisterQueues {
             originator_reply_callback: request.sender_reply_callback,
             refund: request.payment,
             response_payload: Payload::Reject(reject_context),
+            deadline: request.deadline,
         }));
         self.push_input(response, InputQueueType::LocalSubnet)
             .map_err(|(e, _msg)| e)
@@ -1054,6 +1060,86 @@ impl CanisterQueues {
         timed_out_requests_count
     }
 
+    /// Returns `true` if the input queue from `respondent` holds a response for
+    /// `callback_id`.
+    pub(crate) fn has_response_for_callback(
+        &self,
+        respondent: &CanisterId,
+        callback_id: CallbackId,
+    ) -> bool {
+        self.canister_queues
+            .get(respondent)
+            .map_or(false, |(input_queue, _)| {
+                input_queue.has_response_for_callback(callback_id)
+            })
+    }
+
+    /// Enqueues a `SYS_UNKNOWN` reject response for the best-effort call with
+    /// the given callback, whose deadline has expired, into the input queue from
+    /// `respondent`.
+    ///
+    /// Nothing is enqueued if the input queue already holds a response for the
+    /// callback; or if the request is still in the output queue, as it will be
+    /// timed out (producing a `SYS_UNKNOWN` reject response of its own) by
+    /// `time_out_requests()`.
+    ///
+    /// Returns `true` if a reject response was enqueued.
+    pub fn try_push_deadline_expired_input(
+        &mut self,
+        callback_id: CallbackId,
+        respondent: &CanisterId,
+        deadline: CoarseTime,
+        own_canister_id: &CanisterId,
+        local_canisters: &BTreeMap<CanisterId, CanisterState>,
+    ) -> bool {
+        let (input_queue, output_queue) = match self.canister_queues.get_mut(respondent) {
+            Some(queues) => queues,
+            // No reserved response slot, the callback cannot be outstanding.
+            None => return false,
+        };
+        if input_queue.has_response_for_callback(callback_id)
+            || output_queue.has_request_for_callback(callback_id)
+        {
+            return false;
+        }
+
+        let response = RequestOrResponse::Response(Arc::new(Response {
+            originator: *own_canister_id,
+            respondent: *respondent,
+            originator_reply_callback: callback_id,
+            refund: Cycles::zero(),
+            response_payload: Payload::Reject(RejectContext::new_with_message_length_limit(
+                RejectCode::SysUnknown,
+                "Call deadline has expired.".to_string(),
+                MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
+            )),
+            deadline,
+        }));
+
+        let iq_stats_delta = InputQueuesStats::stats_delta(QueueOp::Push, &response);
+        let mu_stats_delta = MemoryUsageStats::stats_delta(QueueOp::Push, &response);
+        if input_queue.push(response).is_err() {
+            // No reserved response slot, the callback cannot be outstanding.
+            return false;
+        }
+        self.input_queues_stats += iq_stats_delta;
+        self.memory_usage_stats += mu_stats_delta;
+
+        // If this was a previously empty input queue, add it to input queue schedule.
+        if input_queue.num_messages() == 1 {
+            if respondent == own_canister_id || local_canisters.contains_key(respondent) {
+                self.local_subnet_input_schedule.push_back(*respondent);
+            } else {
+                self.remote_subnet_input_schedule.push_back(*respondent);
+            }
+        }
+
+        debug_assert!(self.stats_ok());
+        debug_assert!(self.schedules_ok(own_canister_id, local_canisters));
+
+        true
+    }
+
     /// Re-partitions `self.local_subnet_input_schedule` and
     /// `self.remote_subnet_input_schedule` based on the set of all local canisters
     /// plus `own_canister_id` (since Rust's ownership rules would prevent us from
@@ -1099,17 +1185,26 @@ impl CanisterQueues {
 }
 
 /// Generates a timeout reject response from a request, refunding its payment.
+///
+/// Best-effort requests are rejected with `SYS_UNKNOWN`, as the caller cannot
+/// tell whether a best-effort request was delivered or not.
 fn generate_timeout_response(request: &Arc<Request>) -> RequestOrResponse {
+    let reject_code = if request.is_best_effort() {
+        RejectCode::SysUnknown
+    } else {
+        RejectCode::SysTransient
+    };
     RequestOrResponse::Response(Arc::new(Response {
         originator: request.sender,
         respondent: request.receiver,
         originator_reply_callback: request.sender_reply_callback,
         refund: request.payment,
         response_payload: Payload::Reject(RejectContext::new_with_message_length_limit(
-            RejectCode::SysTransient,
+            reject_code,
             "Request timed out.".to_string(),
             MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
         )),
+        deadline: request.deadline,
     }))
 }
 
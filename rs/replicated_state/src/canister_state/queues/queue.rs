@@ -4,7 +4,7 @@ mod tests;
 
 use ic_protobuf::proxy::ProxyDecodeError;
 use ic_protobuf::state::{ingress::v1 as pb_ingress, queues::v1 as pb_queues};
-use ic_types::messages::{Ingress, Request, RequestOrResponse, Response};
+use ic_types::messages::{CallbackId, Ingress, Request, RequestOrResponse, Response};
 use ic_types::{CountBytes, Cycles, Time};
 use std::{
     collections::VecDeque,
@@ -388,6 +388,18 @@ impl InputQueue {
         self.queue.has_used_slots()
     }
 
+    /// Returns `true` if the queue holds a response for the given callback.
+    ///
+    /// Time complexity: O(num_messages).
+    pub(super) fn has_response_for_callback(&self, callback_id: CallbackId) -> bool {
+        self.queue.queue.iter().any(|msg| match msg {
+            RequestOrResponse::Response(response) => {
+                response.originator_reply_callback == callback_id
+            }
+            RequestOrResponse::Request(_) => false,
+        })
+    }
+
     /// Returns the amount of cycles contained in the queue.
     pub(super) fn cycles_in_queue(&self) -> Cycles {
         let mut total_cycles = Cycles::zero();
@@ -677,6 +689,18 @@ impl OutputQueue {
         self.queue.calculate_stat_sum(stat)
     }
 
+    /// Returns `true` if the queue holds the request for the given callback.
+    ///
+    /// Time complexity: O(num_messages).
+    pub(super) fn has_request_for_callback(&self, callback_id: CallbackId) -> bool {
+        self.queue.queue.iter().any(|msg| match msg {
+            Some(RequestOrResponse::Request(request)) => {
+                request.sender_reply_callback == callback_id
+            }
+            _ => false,
+        })
+    }
+
     /// Returns true if there are any expired deadlines at `current_time`, false otherwise.
     pub(super) fn has_expired_deadlines(&self, current_time: Time) -> bool {
         match self.deadline_range_ends.front() {
@@ -16,7 +16,11 @@ use ic_test_utilities::{
         messages::{IngressBuilder, RequestBuilder, ResponseBuilder},
     },
 };
-use ic_types::{messages::CallbackId, time::expiry_time_from_now};
+use ic_types::{
+    messages::{CallbackId, NO_DEADLINE},
+    time::expiry_time_from_now,
+    CoarseTime,
+};
 use maplit::btreemap;
 use proptest::prelude::*;
 use std::convert::TryInto;
@@ -1809,6 +1813,7 @@ fn time_out_requests_pushes_correct_reject_responses() {
                     payment: Cycles::from(cycles as u64),
                     method_name: "No-Op".to_string(),
                     method_payload: vec![],
+                    deadline: NO_DEADLINE,
                 }),
                 deadline,
             )
@@ -1864,7 +1869,8 @@ fn time_out_requests_pushes_correct_reject_responses() {
                     RejectCode::SysTransient,
                     "Request timed out.".to_string(),
                     MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN
-                ))
+                )),
+                deadline: NO_DEADLINE,
             }),
             *reject_response,
         );
@@ -1898,3 +1904,132 @@ fn time_out_requests_pushes_correct_reject_responses() {
         VecDeque::from(vec![remote_canister_id]),
     );
 }
+
+/// Tests that a best-effort output request times out at its own deadline (if
+/// earlier than the default request lifetime) and is rejected with `SYS_UNKNOWN`.
+#[test]
+fn time_out_best_effort_request_at_own_deadline() {
+    let mut canister_queues = CanisterQueues::default();
+
+    let own_canister_id = canister_test_id(67);
+    let remote_canister_id = canister_test_id(97);
+
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+    canister_queues
+        .push_output_request(
+            Arc::new(
+                RequestBuilder::default()
+                    .sender(own_canister_id)
+                    .receiver(remote_canister_id)
+                    .sender_reply_callback(CallbackId::from(1))
+                    .payment(Cycles::from(7_u64))
+                    .deadline(deadline)
+                    .build(),
+            ),
+            Time::from_nanos_since_unix_epoch(0),
+        )
+        .unwrap();
+
+    // Not yet expired one nanosecond before the deadline.
+    let deadline_time = Time::from(deadline);
+    assert!(!canister_queues.has_expired_deadlines(deadline_time - Duration::from_nanos(1)));
+    assert!(canister_queues.has_expired_deadlines(deadline_time));
+
+    assert_eq!(
+        1,
+        canister_queues.time_out_requests(deadline_time, &own_canister_id, &BTreeMap::new()),
+    );
+
+    let (input_queue, output_queue) = canister_queues
+        .canister_queues
+        .get(&remote_canister_id)
+        .unwrap();
+    assert_eq!(0, output_queue.num_messages());
+    assert_eq!(
+        Some(&RequestOrResponse::Response(Arc::new(Response {
+            originator: own_canister_id,
+            respondent: remote_canister_id,
+            originator_reply_callback: CallbackId::from(1),
+            refund: Cycles::from(7_u64),
+            response_payload: Payload::Reject(RejectContext::new_with_message_length_limit(
+                RejectCode::SysUnknown,
+                "Request timed out.".to_string(),
+                MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN
+            )),
+            deadline,
+        }))),
+        input_queue.peek()
+    );
+}
+
+/// Tests that a `SYS_UNKNOWN` reject response is enqueued for an expired
+/// best-effort callback only once the request has left the output queue; and
+/// only once per callback.
+#[test]
+fn try_push_deadline_expired_input() {
+    let mut canister_queues = CanisterQueues::default();
+
+    let own_canister_id = canister_test_id(67);
+    let remote_canister_id = canister_test_id(97);
+    let callback_id = CallbackId::from(1);
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+
+    canister_queues
+        .push_output_request(
+            Arc::new(
+                RequestBuilder::default()
+                    .sender(own_canister_id)
+                    .receiver(remote_canister_id)
+                    .sender_reply_callback(callback_id)
+                    .deadline(deadline)
+                    .build(),
+            ),
+            Time::from_nanos_since_unix_epoch(0),
+        )
+        .unwrap();
+    let try_push_deadline_expired_input = |canister_queues: &mut CanisterQueues| {
+        canister_queues.try_push_deadline_expired_input(
+            callback_id,
+            &remote_canister_id,
+            deadline,
+            &own_canister_id,
+            &BTreeMap::new(),
+        )
+    };
+
+    // The request is still in the output queue, it will be timed out instead.
+    assert!(!try_push_deadline_expired_input(&mut canister_queues));
+
+    // Request is routed.
+    canister_queues
+        .output_into_iter(own_canister_id)
+        .pop()
+        .unwrap();
+
+    // A reject response is enqueued...
+    assert!(try_push_deadline_expired_input(&mut canister_queues));
+    assert!(canister_queues.has_response_for_callback(&remote_canister_id, callback_id));
+    // ...but only once.
+    assert!(!try_push_deadline_expired_input(&mut canister_queues));
+    assert_eq!(
+        VecDeque::from(vec![remote_canister_id]),
+        canister_queues.remote_subnet_input_schedule
+    );
+
+    assert_eq!(
+        Some(CanisterMessage::Response(Arc::new(Response {
+            originator: own_canister_id,
+            respondent: remote_canister_id,
+            originator_reply_callback: callback_id,
+            refund: Cycles::zero(),
+            response_payload: Payload::Reject(RejectContext::new_with_message_length_limit(
+                RejectCode::SysUnknown,
+                "Call deadline has expired.".to_string(),
+                MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN
+            )),
+            deadline,
+        }))),
+        canister_queues.pop_input()
+    );
+    assert!(!canister_queues.has_response_for_callback(&remote_canister_id, callback_id));
+}
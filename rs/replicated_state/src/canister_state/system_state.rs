@@ -1,12 +1,14 @@
 mod call_context_manager;
 
+use super::canister_snapshots::CanisterSnapshots;
 use super::queues::can_push;
 pub use super::queues::memory_required_to_push_request;
+use super::wasm_chunk_store::WasmChunkStore;
 pub use crate::canister_state::queues::CanisterOutputQueuesIterator;
 use crate::{CanisterQueues, CanisterState, InputQueueType, StateError};
 pub use call_context_manager::{CallContext, CallContextAction, CallContextManager, CallOrigin};
 use ic_base_types::NumSeconds;
-use ic_ic00_types::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin};
+use ic_ic00_types::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin, LogVisibility};
 use ic_interfaces::messages::{CanisterCall, CanisterMessage, CanisterMessageOrTask, CanisterTask};
 use ic_logger::{error, ReplicaLogger};
 use ic_protobuf::{
@@ -15,9 +17,10 @@ use ic_protobuf::{
 };
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
+    canister_log::CanisterLog,
     messages::{Ingress, RejectContext, Request, RequestOrResponse, Response, StopCanisterContext},
     nominal_cycles::NominalCycles,
-    CanisterId, CanisterTimer, Cycles, MemoryAllocation, NumBytes, PrincipalId, Time,
+    CanisterId, CanisterTimer, CoarseTime, Cycles, MemoryAllocation, NumBytes, PrincipalId, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreeset;
@@ -297,6 +300,15 @@ pub struct SystemState {
     /// completes, it will apply `ingress_induction_cycles_debit` to `cycles_balance`.
     ingress_induction_cycles_debit: Cycles,
 
+    /// Cycles reserved for paying future storage fees. They are moved from
+    /// `cycles_balance` when the canister allocates memory while the subnet
+    /// memory usage is above the threshold and cannot be spent otherwise.
+    reserved_balance: Cycles,
+
+    /// Upper limit on `reserved_balance`. Allocating memory that would require
+    /// reserving more cycles fails. `None` means no limit.
+    reserved_balance_limit: Option<Cycles>,
+
     /// Tasks to execute before processing input messages.
     /// Currently the task queue is empty outside of execution rounds.
     pub task_queue: VecDeque<ExecutionTask>,
@@ -309,6 +321,80 @@ pub struct SystemState {
 
     /// Canister history.
     canister_history: CanisterHistory,
+
+    /// Snapshots of the canister taken via `take_canister_snapshot`.
+    canister_snapshots: CanisterSnapshots,
+
+    /// Log records produced by the canister via `ic0.debug_print` and traps.
+    pub canister_log: CanisterLog,
+
+    /// Who is allowed to fetch the log records of the canister.
+    pub log_visibility: LogVisibility,
+
+    /// Chunks of a Wasm module uploaded via `upload_chunk`.
+    wasm_chunk_store: WasmChunkStore,
+
+    /// Upper limit on the Wasm heap memory of the canister. Growing the Wasm
+    /// heap beyond this limit fails. `None` means no limit.
+    pub wasm_memory_limit: Option<NumBytes>,
+
+    /// Tracks whether the `canister_on_low_wasm_memory` hook needs to run.
+    pub on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
+}
+
+/// The status of the `canister_on_low_wasm_memory` hook of a canister.
+///
+/// The hook runs at most once each time the free Wasm memory of the canister
+/// drops below the threshold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnLowWasmMemoryHookStatus {
+    /// The free Wasm memory of the canister is above the threshold.
+    #[default]
+    ConditionNotSatisfied,
+    /// The free Wasm memory dropped below the threshold and the hook is
+    /// waiting to be executed.
+    Ready,
+    /// The hook was executed and will not run again until the free Wasm
+    /// memory goes back above the threshold.
+    Executed,
+}
+
+impl From<&OnLowWasmMemoryHookStatus> for pb::OnLowWasmMemoryHookStatus {
+    fn from(item: &OnLowWasmMemoryHookStatus) -> Self {
+        match item {
+            OnLowWasmMemoryHookStatus::ConditionNotSatisfied => Self::ConditionNotSatisfied,
+            OnLowWasmMemoryHookStatus::Ready => Self::Ready,
+            OnLowWasmMemoryHookStatus::Executed => Self::Executed,
+        }
+    }
+}
+
+impl TryFrom<pb::OnLowWasmMemoryHookStatus> for OnLowWasmMemoryHookStatus {
+    type Error = ProxyDecodeError;
+
+    fn try_from(value: pb::OnLowWasmMemoryHookStatus) -> Result<Self, Self::Error> {
+        match value {
+            // Checkpoints written before the hook existed do not set the field.
+            pb::OnLowWasmMemoryHookStatus::Unspecified
+            | pb::OnLowWasmMemoryHookStatus::ConditionNotSatisfied => {
+                Ok(OnLowWasmMemoryHookStatus::ConditionNotSatisfied)
+            }
+            pb::OnLowWasmMemoryHookStatus::Ready => Ok(OnLowWasmMemoryHookStatus::Ready),
+            pb::OnLowWasmMemoryHookStatus::Executed => Ok(OnLowWasmMemoryHookStatus::Executed),
+        }
+    }
+}
+
+/// Errors that can occur when reserving cycles for future storage fees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReservationError {
+    /// The main balance does not have enough cycles to reserve.
+    InsufficientCycles {
+        requested: Cycles,
+        available: Cycles,
+    },
+    /// The reserved balance would exceed the reserved cycles limit.
+    ReservedLimitExceed { requested: Cycles, limit: Cycles },
 }
 
 /// A wrapper around the different canister statuses.
@@ -408,6 +494,10 @@ pub enum ExecutionTask {
     /// The task exists only within an execution round, it never gets serialized.
     GlobalTimer,
 
+    /// Canister low Wasm memory hook task.
+    /// The task exists only within an execution round, it never gets serialized.
+    OnLowWasmMemory,
+
     // A paused execution task exists only within an epoch (between
     // checkpoints). It is never serialized, and it turns into `AbortedExecution`
     // before the checkpoint or when there are too many long-running executions.
@@ -446,6 +536,7 @@ impl From<&ExecutionTask> for pb::ExecutionTask {
         match item {
             ExecutionTask::Heartbeat
             | ExecutionTask::GlobalTimer
+            | ExecutionTask::OnLowWasmMemory
             | ExecutionTask::PausedExecution(_)
             | ExecutionTask::PausedInstallCode(_) => {
                 panic!("Attempt to serialize ephemeral task: {:?}.", item);
@@ -473,6 +564,9 @@ impl From<&ExecutionTask> for pb::ExecutionTask {
                     CanisterMessageOrTask::Task(CanisterTask::GlobalTimer) => {
                         PbInput::Task(PbCanisterTask::Timer as i32)
                     }
+                    CanisterMessageOrTask::Task(CanisterTask::OnLowWasmMemory) => {
+                        PbInput::Task(PbCanisterTask::OnLowWasmMemory as i32)
+                    }
                 };
                 Self {
                     task: Some(pb::execution_task::Task::AbortedExecution(
@@ -546,6 +640,7 @@ impl TryFrom<pb::ExecutionTask> for ExecutionTask {
                             }
                             PbCanisterTask::Heartbeat => CanisterTask::Heartbeat,
                             PbCanisterTask::Timer => CanisterTask::GlobalTimer,
+                            PbCanisterTask::OnLowWasmMemory => CanisterTask::OnLowWasmMemory,
                         };
                         CanisterMessageOrTask::Task(task)
                     }
@@ -677,6 +772,8 @@ impl SystemState {
             queues: CanisterQueues::default(),
             cycles_balance: initial_cycles,
             ingress_induction_cycles_debit: Cycles::zero(),
+            reserved_balance: Cycles::zero(),
+            reserved_balance_limit: None,
             memory_allocation: MemoryAllocation::BestEffort,
             freeze_threshold,
             status,
@@ -686,6 +783,12 @@ impl SystemState {
             global_timer: CanisterTimer::Inactive,
             canister_version: 0,
             canister_history: CanisterHistory::default(),
+            canister_snapshots: CanisterSnapshots::default(),
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            wasm_chunk_store: WasmChunkStore::default(),
+            wasm_memory_limit: None,
+            on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus::default(),
         }
     }
 
@@ -717,10 +820,18 @@ impl SystemState {
         canister_metrics: CanisterMetrics,
         cycles_balance: Cycles,
         ingress_induction_cycles_debit: Cycles,
+        reserved_balance: Cycles,
+        reserved_balance_limit: Option<Cycles>,
         task_queue: VecDeque<ExecutionTask>,
         global_timer: CanisterTimer,
         canister_version: u64,
         canister_history: CanisterHistory,
+        canister_snapshots: CanisterSnapshots,
+        canister_log: CanisterLog,
+        log_visibility: LogVisibility,
+        wasm_chunk_store: WasmChunkStore,
+        wasm_memory_limit: Option<NumBytes>,
+        on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
     ) -> Self {
         Self {
             controllers,
@@ -733,10 +844,18 @@ impl SystemState {
             canister_metrics,
             cycles_balance,
             ingress_induction_cycles_debit,
+            reserved_balance,
+            reserved_balance_limit,
             task_queue,
             global_timer,
             canister_version,
             canister_history,
+            canister_snapshots,
+            canister_log,
+            log_visibility,
+            wasm_chunk_store,
+            wasm_memory_limit,
+            on_low_wasm_memory_hook_status,
         }
     }
 
@@ -761,6 +880,58 @@ impl SystemState {
         self.ingress_induction_cycles_debit
     }
 
+    /// Returns the amount of cycles reserved for paying future storage fees.
+    pub fn reserved_balance(&self) -> Cycles {
+        self.reserved_balance
+    }
+
+    /// Returns the upper limit on the reserved balance.
+    pub fn reserved_balance_limit(&self) -> Option<Cycles> {
+        self.reserved_balance_limit
+    }
+
+    /// Sets the upper limit on the reserved balance. The limit does not
+    /// affect cycles that have already been reserved.
+    pub fn set_reserved_balance_limit(&mut self, limit: Option<Cycles>) {
+        self.reserved_balance_limit = limit;
+    }
+
+    /// Moves the given amount of cycles from the main balance to the reserved
+    /// balance.
+    ///
+    /// Returns an error if the main balance is too low or if the reserved
+    /// balance would exceed its limit. The state is unchanged in that case.
+    pub fn reserve_cycles(&mut self, amount: Cycles) -> Result<(), ReservationError> {
+        if amount.get() == 0 {
+            return Ok(());
+        }
+        if let Some(limit) = self.reserved_balance_limit {
+            let requested = self.reserved_balance + amount;
+            if requested > limit {
+                return Err(ReservationError::ReservedLimitExceed { requested, limit });
+            }
+        }
+        if amount > self.debited_balance() {
+            return Err(ReservationError::InsufficientCycles {
+                requested: amount,
+                available: self.debited_balance(),
+            });
+        }
+        self.cycles_balance -= amount;
+        self.reserved_balance += amount;
+        Ok(())
+    }
+
+    /// Consumes up to `amount` cycles from the reserved balance and returns
+    /// the remaining amount that the reserved balance could not cover.
+    pub fn consume_reserved_cycles(&mut self, amount: Cycles, use_case: CyclesUseCase) -> Cycles {
+        let consumed = std::cmp::min(amount, self.reserved_balance);
+        self.reserved_balance -= consumed;
+        self.observe_consumed_cycles(consumed);
+        self.observe_consumed_cycles_with_use_case(consumed, use_case, ConsumingCycles::Yes);
+        amount - consumed
+    }
+
     /// Records the given amount as debit that will be charged from the balance
     /// at some point in the future.
     ///
@@ -1000,6 +1171,25 @@ impl SystemState {
                     call_context_manager
                         .validate_response(response)
                         .map_err(|err| (err, msg.clone()))?;
+
+                    // A `SYS_UNKNOWN` reject response may already have been
+                    // enqueued for an expired best-effort call.
+                    if response.is_best_effort()
+                        && self.queues.has_response_for_callback(
+                            &response.respondent,
+                            response.originator_reply_callback,
+                        )
+                    {
+                        return Err((
+                            StateError::NonMatchingResponse {
+                                err_str: "Duplicate response".to_string(),
+                                originator: response.originator,
+                                callback_id: response.originator_reply_callback,
+                                respondent: response.respondent,
+                            },
+                            msg,
+                        ));
+                    }
                 }
                 push_input(
                     &mut self.queues,
@@ -1084,6 +1274,18 @@ impl SystemState {
         self.canister_history.get_memory_usage()
     }
 
+    /// Returns the memory currently in use by the `SystemState`
+    /// for canister snapshots.
+    pub fn canister_snapshots_memory_usage(&self) -> NumBytes {
+        self.canister_snapshots.memory_usage()
+    }
+
+    /// Returns the memory currently in use by the `SystemState`
+    /// for the Wasm chunk store.
+    pub fn wasm_chunk_store_memory_usage(&self) -> NumBytes {
+        self.wasm_chunk_store.memory_usage()
+    }
+
     /// Sets the (transient) size in bytes of responses from this canister
     /// routed into streams and not yet garbage collected.
     pub(super) fn set_stream_responses_size_bytes(&mut self, size_bytes: usize) {
@@ -1184,6 +1386,51 @@ impl SystemState {
             .time_out_requests(current_time, own_canister_id, local_canisters)
     }
 
+    /// Queries whether any best-effort call has an expired deadline.
+    pub fn has_expired_callbacks(&self, current_time: Time) -> bool {
+        self.call_context_manager().map_or(false, |ccm| {
+            ccm.has_expired_callbacks(CoarseTime::floor(current_time))
+        })
+    }
+
+    /// Enqueues a `SYS_UNKNOWN` reject response for every best-effort call whose
+    /// deadline has expired and that has neither a response nor a timed out
+    /// request pending. Returns the number of reject responses enqueued.
+    ///
+    /// See [`CanisterQueues::try_push_deadline_expired_input`] for further details.
+    pub fn time_out_callbacks(
+        &mut self,
+        current_time: Time,
+        own_canister_id: &CanisterId,
+        local_canisters: &BTreeMap<CanisterId, CanisterState>,
+    ) -> u64 {
+        let expired_callbacks: Vec<_> = match self.call_context_manager() {
+            Some(ccm) => ccm
+                .expired_callbacks(CoarseTime::floor(current_time))
+                .filter_map(|(callback_id, callback)| {
+                    callback
+                        .respondent
+                        .map(|respondent| (callback_id, respondent, callback.deadline))
+                })
+                .collect(),
+            None => return 0,
+        };
+
+        let mut timed_out_callbacks_count = 0;
+        for (callback_id, respondent, deadline) in expired_callbacks {
+            if self.queues.try_push_deadline_expired_input(
+                callback_id,
+                &respondent,
+                deadline,
+                own_canister_id,
+                local_canisters,
+            ) {
+                timed_out_callbacks_count += 1;
+            }
+        }
+        timed_out_callbacks_count
+    }
+
     /// Re-partitions the local and remote input schedules of `self.queues`
     /// following a canister migration, based on the updated set of local canisters.
     ///
@@ -1280,6 +1527,22 @@ impl SystemState {
     pub fn get_canister_history(&self) -> &CanisterHistory {
         &self.canister_history
     }
+
+    pub fn canister_snapshots(&self) -> &CanisterSnapshots {
+        &self.canister_snapshots
+    }
+
+    pub fn canister_snapshots_mut(&mut self) -> &mut CanisterSnapshots {
+        &mut self.canister_snapshots
+    }
+
+    pub fn wasm_chunk_store(&self) -> &WasmChunkStore {
+        &self.wasm_chunk_store
+    }
+
+    pub fn wasm_chunk_store_mut(&mut self) -> &mut WasmChunkStore {
+        &mut self.wasm_chunk_store
+    }
 }
 
 /// Implements memory limits verification for pushing a canister-to-canister
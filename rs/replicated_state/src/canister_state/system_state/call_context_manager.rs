@@ -11,12 +11,13 @@ use ic_types::messages::Response;
 use ic_types::Time;
 use ic_types::{
     ingress::WasmResult,
-    messages::{CallContextId, CallbackId, MessageId},
+    messages::{CallContextId, CallbackId, MessageId, NO_DEADLINE},
     methods::Callback,
-    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, UserId,
+    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, CoarseTime, Cycles, Funds,
+    UserId,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{From, TryFrom, TryInto};
 use std::time::Duration;
 
@@ -211,12 +212,16 @@ pub struct CallContextManager {
     // maps call context to its responded status
     call_contexts: BTreeMap<CallContextId, CallContext>,
     callbacks: BTreeMap<CallbackId, Callback>,
+    /// Deadlines of the callbacks of best-effort calls, derived from `callbacks`.
+    callback_deadlines: BTreeSet<(CoarseTime, CallbackId)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CallOrigin {
     Ingress(UserId, MessageId),
-    CanisterUpdate(CanisterId, CallbackId),
+    /// Canister update call, along with the call's deadline (`NO_DEADLINE`
+    /// for guaranteed response calls).
+    CanisterUpdate(CanisterId, CallbackId, CoarseTime),
     Query(UserId),
     CanisterQuery(CanisterId, CallbackId),
     /// System task is either a Heartbeat or a GlobalTimer.
@@ -230,10 +235,11 @@ impl From<&CallOrigin> for pb::call_context::CallOrigin {
                 user_id: Some(user_id_into_protobuf(*user_id)),
                 message_id: message_id.as_bytes().to_vec(),
             }),
-            CallOrigin::CanisterUpdate(canister_id, callback_id) => {
+            CallOrigin::CanisterUpdate(canister_id, callback_id, deadline) => {
                 Self::CanisterUpdate(pb::call_context::CanisterUpdateOrQuery {
                     canister_id: Some(pb_types::CanisterId::from(*canister_id)),
                     callback_id: callback_id.get(),
+                    deadline_seconds: deadline.as_secs_since_unix_epoch(),
                 })
             }
             CallOrigin::Query(user_id) => Self::Query(user_id_into_protobuf(*user_id)),
@@ -241,6 +247,7 @@ impl From<&CallOrigin> for pb::call_context::CallOrigin {
                 Self::CanisterQuery(pb::call_context::CanisterUpdateOrQuery {
                     canister_id: Some(pb_types::CanisterId::from(*canister_id)),
                     callback_id: callback_id.get(),
+                    deadline_seconds: NO_DEADLINE.as_secs_since_unix_epoch(),
                 })
             }
             CallOrigin::SystemTask => Self::SystemTask(pb::call_context::SystemTask {}),
@@ -266,10 +273,12 @@ impl TryFrom<pb::call_context::CallOrigin> for CallOrigin {
                 pb::call_context::CanisterUpdateOrQuery {
                     canister_id,
                     callback_id,
+                    deadline_seconds,
                 },
             ) => Self::CanisterUpdate(
                 try_from_option_field(canister_id, "CallOrigin::CanisterUpdate::canister_id")?,
                 callback_id.into(),
+                CoarseTime::from_secs_since_unix_epoch(deadline_seconds),
             ),
             pb::call_context::CallOrigin::Query(user_id) => {
                 Self::Query(user_id_try_from_protobuf(user_id)?)
@@ -278,6 +287,7 @@ impl TryFrom<pb::call_context::CallOrigin> for CallOrigin {
                 pb::call_context::CanisterUpdateOrQuery {
                     canister_id,
                     callback_id,
+                    ..
                 },
             ) => Self::CanisterQuery(
                 try_from_option_field(canister_id, "CallOrigin::CanisterQuery::canister_id")?,
@@ -489,6 +499,10 @@ impl CallContextManager {
     pub fn register_callback(&mut self, callback: Callback) -> CallbackId {
         self.next_callback_id += 1;
         let callback_id = CallbackId::from(self.next_callback_id);
+        if callback.is_best_effort() {
+            self.callback_deadlines
+                .insert((callback.deadline, callback_id));
+        }
         self.callbacks.insert(callback_id, callback);
         callback_id
     }
@@ -501,7 +515,35 @@ impl CallContextManager {
     /// If we get a response for one of the outstanding calls, we unregister
     /// the callback and return it.
     pub fn unregister_callback(&mut self, callback_id: CallbackId) -> Option<Callback> {
-        self.callbacks.remove(&callback_id)
+        let callback = self.callbacks.remove(&callback_id)?;
+        self.callback_deadlines
+            .remove(&(callback.deadline, callback_id));
+        Some(callback)
+    }
+
+    /// Returns the IDs and callbacks of the best-effort calls whose deadline
+    /// has expired at `current_time`, in order of increasing deadline.
+    ///
+    /// Expired callbacks are only unregistered once their (synthetic
+    /// `SYS_UNKNOWN` reject or actual) response is executed, so they are
+    /// returned until then.
+    pub fn expired_callbacks(
+        &self,
+        current_time: CoarseTime,
+    ) -> impl Iterator<Item = (CallbackId, &Callback)> {
+        self.callback_deadlines
+            .iter()
+            .take_while(move |(deadline, _)| *deadline <= current_time)
+            .map(|(_, callback_id)| (*callback_id, &self.callbacks[callback_id]))
+    }
+
+    /// Returns true if any best-effort call has an expired deadline at
+    /// `current_time`.
+    pub fn has_expired_callbacks(&self, current_time: CoarseTime) -> bool {
+        self.callback_deadlines
+            .iter()
+            .next()
+            .map_or(false, |(deadline, _)| *deadline <= current_time)
     }
 
     /// Returns the call origin, which is either the message id of the ingress
@@ -576,9 +618,11 @@ impl CallContextManager {
 impl From<&CanisterCall> for CallOrigin {
     fn from(msg: &CanisterCall) -> Self {
         match msg {
-            CanisterCall::Request(request) => {
-                CallOrigin::CanisterUpdate(request.sender, request.sender_reply_callback)
-            }
+            CanisterCall::Request(request) => CallOrigin::CanisterUpdate(
+                request.sender,
+                request.sender_reply_callback,
+                request.deadline,
+            ),
             CanisterCall::Ingress(ingress) => {
                 CallOrigin::Ingress(ingress.source, ingress.message_id.clone())
             }
@@ -646,11 +690,18 @@ impl TryFrom<pb::CallContextManager> for CallContextManager {
             );
         }
 
+        let callback_deadlines = callbacks
+            .iter()
+            .filter(|(_, callback)| callback.is_best_effort())
+            .map(|(callback_id, callback)| (callback.deadline, *callback_id))
+            .collect();
+
         Ok(Self {
             next_call_context_id: value.next_call_context_id,
             next_callback_id: value.next_callback_id,
             call_contexts,
             callbacks,
+            callback_deadlines,
         })
     }
 }
@@ -8,13 +8,13 @@ fn call_context_origin() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(10),
         Time::from_nanos_since_unix_epoch(0),
     );
     assert_eq!(
         ccm.call_contexts().get(&cc_id).unwrap().call_origin,
-        CallOrigin::CanisterUpdate(id, cb_id)
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE)
     );
 }
 
@@ -26,18 +26,18 @@ fn call_context_handling() {
 
     // On two incoming calls
     let call_context_id1 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
     let call_context_id2 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(2)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(2), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
 
     let call_context_id3 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(3)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(3), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -78,6 +78,7 @@ fn call_context_handling() {
         WasmClosure::new(0, 1),
         WasmClosure::new(2, 3),
         None,
+        NO_DEADLINE,
     ));
     let callback_id2 = call_context_manager.register_callback(Callback::new(
         call_context_id1,
@@ -89,6 +90,7 @@ fn call_context_handling() {
         WasmClosure::new(4, 5),
         WasmClosure::new(6, 7),
         None,
+        NO_DEADLINE,
     ));
 
     // There are 2 ougoing calls
@@ -105,6 +107,7 @@ fn call_context_handling() {
         WasmClosure::new(8, 9),
         WasmClosure::new(10, 11),
         None,
+        NO_DEADLINE,
     ));
     // There is 1 outgoing call
     assert_eq!(call_context_manager.outstanding_calls(call_context_id2), 1);
@@ -254,7 +257,7 @@ fn withdraw_cycles_fails_when_not_enough_available_cycles() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(30),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -276,7 +279,7 @@ fn withdraw_cycles_succeeds_when_enough_available_cycles() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(30),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -288,3 +291,65 @@ fn withdraw_cycles_succeeds_when_enough_available_cycles() {
         Ok(())
     );
 }
+
+#[test]
+fn expired_callbacks() {
+    let mut ccm = CallContextManager::default();
+    let call_context_id = ccm.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
+        Cycles::zero(),
+        Time::from_nanos_since_unix_epoch(0),
+    );
+    let callback_with_deadline = |deadline| {
+        Callback::new(
+            call_context_id,
+            Some(canister_test_id(1)),
+            Some(canister_test_id(2)),
+            Cycles::zero(),
+            None,
+            None,
+            WasmClosure::new(0, 1),
+            WasmClosure::new(2, 3),
+            None,
+            deadline,
+        )
+    };
+
+    // One guaranteed response call and two best-effort calls.
+    ccm.register_callback(callback_with_deadline(NO_DEADLINE));
+    let callback_id2 = ccm.register_callback(callback_with_deadline(
+        CoarseTime::from_secs_since_unix_epoch(20),
+    ));
+    let callback_id3 = ccm.register_callback(callback_with_deadline(
+        CoarseTime::from_secs_since_unix_epoch(10),
+    ));
+
+    let expired_ids = |ccm: &CallContextManager, secs| {
+        ccm.expired_callbacks(CoarseTime::from_secs_since_unix_epoch(secs))
+            .map(|(callback_id, _)| callback_id)
+            .collect::<Vec<_>>()
+    };
+
+    // Nothing expired before the earliest deadline.
+    assert!(!ccm.has_expired_callbacks(CoarseTime::from_secs_since_unix_epoch(9)));
+    assert_eq!(Vec::<CallbackId>::new(), expired_ids(&ccm, 9));
+
+    // Callbacks expire at their deadline, in deadline order.
+    assert!(ccm.has_expired_callbacks(CoarseTime::from_secs_since_unix_epoch(10)));
+    assert_eq!(vec![callback_id3], expired_ids(&ccm, 10));
+    assert_eq!(vec![callback_id3, callback_id2], expired_ids(&ccm, 20));
+    // Guaranteed response calls never expire.
+    assert_eq!(
+        vec![callback_id3, callback_id2],
+        expired_ids(&ccm, u32::MAX)
+    );
+
+    // Unregistered callbacks are no longer returned.
+    ccm.unregister_callback(callback_id3).unwrap();
+    assert_eq!(vec![callback_id2], expired_ids(&ccm, 20));
+
+    // The index of deadlines survives a protobuf roundtrip.
+    let pb: pb::CallContextManager = (&ccm).into();
+    let ccm: CallContextManager = pb.try_into().unwrap();
+    assert_eq!(vec![callback_id2], expired_ids(&ccm, 20));
+}
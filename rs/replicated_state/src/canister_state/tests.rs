@@ -2,14 +2,18 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use super::*;
+use crate::canister_state::canister_snapshots::{
+    CanisterSnapshot, CanisterSnapshots, SnapshotId, LOCAL_ID_LENGTH,
+};
 use crate::canister_state::execution_state::CustomSection;
 use crate::canister_state::execution_state::CustomSectionType;
 use crate::canister_state::execution_state::WasmMetadata;
 use crate::canister_state::system_state::{
     CanisterHistory, CyclesUseCase, MAX_CANISTER_HISTORY_CHANGES,
 };
+use crate::canister_state::wasm_chunk_store::WasmChunkStore;
 use crate::CallOrigin;
-use crate::Memory;
+use crate::{Memory, PageMap};
 use ic_base_types::NumSeconds;
 use ic_ic00_types::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin};
 use ic_logger::replica_logger::no_op_logger;
@@ -20,15 +24,15 @@ use ic_test_utilities::types::{
     messages::{RequestBuilder, ResponseBuilder},
 };
 use ic_types::messages::CallContextId;
-use ic_types::{
-    messages::CallbackId,
-    methods::{Callback, WasmClosure},
-    Time,
-};
 use ic_types::{
     messages::MAX_RESPONSE_COUNT_BYTES, nominal_cycles::NominalCycles, xnet::QueueId, CountBytes,
     Cycles,
 };
+use ic_types::{
+    messages::{CallbackId, NO_DEADLINE},
+    methods::{Callback, WasmClosure},
+    Time,
+};
 use ic_wasm_types::CanisterModule;
 
 const CANISTER_ID: CanisterId = CanisterId::from_u64(42);
@@ -87,7 +91,7 @@ impl CanisterStateFixture {
             .call_context_manager_mut()
             .unwrap()
             .new_call_context(
-                CallOrigin::CanisterUpdate(CANISTER_ID, CallbackId::from(1)),
+                CallOrigin::CanisterUpdate(CANISTER_ID, CallbackId::from(1), NO_DEADLINE),
                 Cycles::zero(),
                 Time::from_nanos_since_unix_epoch(0),
             );
@@ -105,6 +109,7 @@ impl CanisterStateFixture {
                 WasmClosure::new(0, 2),
                 WasmClosure::new(0, 2),
                 None,
+                NO_DEADLINE,
             ))
     }
 
@@ -609,6 +614,7 @@ fn canister_state_callback_round_trip() {
         WasmClosure::new(0, 2),
         WasmClosure::new(0, 2),
         None,
+        NO_DEADLINE,
     );
 
     let pb_callback = pb::Callback::from(&callback);
@@ -763,3 +769,63 @@ fn canister_history_operations() {
         ));
     }
 }
+
+fn canister_snapshot(wasm_pages: usize) -> CanisterSnapshot {
+    CanisterSnapshot::new(
+        mock_time(),
+        1,
+        vec![1, 2, 3],
+        CanisterModule::new(vec![0; 10]),
+        vec![Global::I32(1)],
+        Memory::new(PageMap::new_for_testing(), NumWasmPages::new(wasm_pages)),
+        Memory::new_for_testing(),
+    )
+}
+
+#[test]
+fn snapshot_id_round_trips_through_bytes() {
+    let snapshot_id = SnapshotId::new(canister_test_id(42), 7);
+    let bytes = snapshot_id.to_vec();
+    assert_eq!(SnapshotId::try_from(bytes.as_slice()), Ok(snapshot_id));
+    assert!(SnapshotId::try_from(&bytes[..LOCAL_ID_LENGTH]).is_err());
+}
+
+#[test]
+fn canister_snapshots_track_memory_usage_and_do_not_reuse_ids() {
+    let canister_id = canister_test_id(1);
+    let mut snapshots = CanisterSnapshots::default();
+    let first = snapshots.push(canister_id, canister_snapshot(1));
+    let second = snapshots.push(canister_id, canister_snapshot(2));
+    assert_ne!(first, second);
+    assert_eq!(
+        snapshots.memory_usage(),
+        canister_snapshot(1).size() + canister_snapshot(2).size()
+    );
+
+    snapshots.remove(&first).unwrap();
+    assert_eq!(snapshots.memory_usage(), canister_snapshot(2).size());
+
+    snapshots.clear();
+    assert_eq!(snapshots.memory_usage(), NumBytes::from(0));
+    let third = snapshots.push(canister_id, canister_snapshot(1));
+    assert_eq!(third.local_id(), 2);
+}
+
+#[test]
+fn wasm_chunk_store_deduplicates_chunks_and_tracks_memory_usage() {
+    let mut store = WasmChunkStore::default();
+    let first = store.insert(CanisterModule::new(vec![1; 10]));
+    let second = store.insert(CanisterModule::new(vec![2; 20]));
+    assert_ne!(first, second);
+    assert_eq!(store.memory_usage(), NumBytes::from(30));
+
+    // Uploading the same chunk again does not take more memory.
+    assert_eq!(store.insert(CanisterModule::new(vec![1; 10])), first);
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.memory_usage(), NumBytes::from(30));
+    assert_eq!(store.get(&second).unwrap().as_slice(), &[2; 20][..]);
+
+    store.clear();
+    assert!(store.is_empty());
+    assert_eq!(store.memory_usage(), NumBytes::from(0));
+}
@@ -0,0 +1,81 @@
+use ic_types::NumBytes;
+use ic_wasm_types::CanisterModule;
+use std::collections::BTreeMap;
+
+/// Maximum size of a single chunk that can be uploaded via `upload_chunk`.
+pub const MAX_WASM_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of chunks that the chunk store of a canister can hold.
+pub const MAX_WASM_CHUNKS_PER_CANISTER: usize = 100;
+
+/// The SHA-256 hash of a chunk. Chunks are addressed by their hash.
+pub type WasmChunkHash = [u8; 32];
+
+/// A content-addressed store of chunks of a Wasm module that is too large to
+/// be sent in a single `install_code` message.
+///
+/// Chunks are stored as `CanisterModule`s, so cloning the store is cheap and
+/// the hash of a chunk is computed once, when the chunk is uploaded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WasmChunkStore {
+    chunks: BTreeMap<WasmChunkHash, CanisterModule>,
+    /// Sum of the sizes of all chunks. We pre-compute and store the sum
+    /// because the memory usage of a canister is requested frequently.
+    memory_usage: NumBytes,
+}
+
+impl WasmChunkStore {
+    pub fn new(chunks: Vec<CanisterModule>) -> Self {
+        let mut store = Self::default();
+        for chunk in chunks {
+            store.insert(chunk);
+        }
+        store
+    }
+
+    /// Adds the given chunk to the store and returns its hash. Adding a chunk
+    /// that is already in the store is a no-op.
+    pub fn insert(&mut self, chunk: CanisterModule) -> WasmChunkHash {
+        let hash = chunk.module_hash();
+        if !self.chunks.contains_key(&hash) {
+            self.memory_usage += NumBytes::from(chunk.len() as u64);
+            self.chunks.insert(hash, chunk);
+        }
+        hash
+    }
+
+    pub fn get(&self, hash: &WasmChunkHash) -> Option<&CanisterModule> {
+        self.chunks.get(hash)
+    }
+
+    pub fn contains(&self, hash: &WasmChunkHash) -> bool {
+        self.chunks.contains_key(hash)
+    }
+
+    /// Returns the hashes of all chunks in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = &WasmChunkHash> {
+        self.chunks.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&WasmChunkHash, &CanisterModule)> {
+        self.chunks.iter()
+    }
+
+    /// Removes all chunks from the store.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.memory_usage = NumBytes::from(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn memory_usage(&self) -> NumBytes {
+        self.memory_usage
+    }
+}
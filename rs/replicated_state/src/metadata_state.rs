@@ -1,10 +1,13 @@
+pub mod query_stats;
 pub mod subnet_call_context_manager;
 #[cfg(test)]
 mod tests;
 
 use crate::{
     canister_state::system_state::CyclesUseCase,
-    metadata_state::subnet_call_context_manager::SubnetCallContextManager,
+    metadata_state::{
+        query_stats::RawQueryStats, subnet_call_context_manager::SubnetCallContextManager,
+    },
 };
 use ic_base_types::CanisterId;
 use ic_btc_types_internal::BlockBlob;
@@ -144,6 +147,10 @@ pub struct SystemMetadata {
     /// response limit. To work around this limitation, large responses are paginated
     /// and are stored here temporarily until they're fetched by the calling canister.
     pub bitcoin_get_successors_follow_up_responses: BTreeMap<CanisterId, Vec<BlockBlob>>,
+
+    /// Query statistics received through consensus that still need to be
+    /// aggregated into the canister states.
+    pub query_stats: RawQueryStats,
 }
 
 /// Full description of the IC network toplogy.
@@ -516,6 +523,7 @@ impl From<&SystemMetadata> for pb_metadata::SystemMetadata {
                     },
                 )
                 .collect(),
+            query_stats: Some((&item.query_stats).into()),
         }
     }
 }
@@ -612,6 +620,10 @@ impl TryFrom<pb_metadata::SystemMetadata> for SystemMetadata {
             },
             expected_compiled_wasms: BTreeSet::new(),
             bitcoin_get_successors_follow_up_responses,
+            query_stats: match item.query_stats {
+                Some(query_stats) => query_stats.try_into()?,
+                None => RawQueryStats::default(),
+            },
         })
     }
 }
@@ -645,6 +657,7 @@ impl SystemMetadata {
             subnet_metrics: Default::default(),
             expected_compiled_wasms: BTreeSet::new(),
             bitcoin_get_successors_follow_up_responses: BTreeMap::default(),
+            query_stats: RawQueryStats::default(),
         }
     }
 
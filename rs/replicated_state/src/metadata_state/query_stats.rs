@@ -0,0 +1,85 @@
+use ic_base_types::{CanisterId, NodeId};
+use ic_protobuf::{
+    proxy::{try_from_option_field, ProxyDecodeError},
+    state::system_metadata::v1 as pb_metadata,
+};
+use ic_types::{
+    batch::{QueryStats, QueryStatsEpoch},
+    node_id_into_protobuf, node_id_try_from_option,
+};
+use std::collections::BTreeMap;
+
+/// Query statistics of a single epoch, by canister and by reporting node.
+pub type QueryStatsByCanister = BTreeMap<CanisterId, BTreeMap<NodeId, QueryStats>>;
+
+/// Query statistics that have been delivered through consensus but not yet
+/// aggregated into the canister states.
+///
+/// Every node reports the statistics of each epoch at most once per canister,
+/// in a block it made. Once enough time has passed for all nodes to have
+/// reported an epoch, its statistics are aggregated and dropped from here.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawQueryStats {
+    /// The highest epoch whose statistics have been aggregated, if any.
+    pub highest_aggregated_epoch: Option<QueryStatsEpoch>,
+    /// The statistics of the epochs that have not been aggregated yet.
+    pub stats: BTreeMap<QueryStatsEpoch, QueryStatsByCanister>,
+}
+
+impl From<&RawQueryStats> for pb_metadata::RawQueryStats {
+    fn from(item: &RawQueryStats) -> Self {
+        let mut stats = vec![];
+        for (epoch, by_canister) in &item.stats {
+            for (canister_id, by_node) in by_canister {
+                for (node_id, node_stats) in by_node {
+                    stats.push(pb_metadata::QueryStatsInner {
+                        proposer: Some(node_id_into_protobuf(*node_id)),
+                        epoch: epoch.get(),
+                        canister_id: Some((*canister_id).into()),
+                        num_calls: node_stats.num_calls,
+                        num_instructions: node_stats.num_instructions,
+                        ingress_payload_size: node_stats.ingress_payload_size,
+                        egress_payload_size: node_stats.egress_payload_size,
+                    });
+                }
+            }
+        }
+        Self {
+            highest_aggregated_epoch: item.highest_aggregated_epoch.map(|epoch| epoch.get()),
+            stats,
+        }
+    }
+}
+
+impl TryFrom<pb_metadata::RawQueryStats> for RawQueryStats {
+    type Error = ProxyDecodeError;
+
+    fn try_from(item: pb_metadata::RawQueryStats) -> Result<Self, Self::Error> {
+        let mut stats = BTreeMap::<QueryStatsEpoch, QueryStatsByCanister>::new();
+        for entry in item.stats {
+            let node_id = node_id_try_from_option(entry.proposer)?;
+            let canister_id = CanisterId::try_from(try_from_option_field(
+                entry.canister_id,
+                "QueryStatsInner::canister_id",
+            )?)?;
+            stats
+                .entry(QueryStatsEpoch::from(entry.epoch))
+                .or_default()
+                .entry(canister_id)
+                .or_default()
+                .insert(
+                    node_id,
+                    QueryStats {
+                        num_calls: entry.num_calls,
+                        num_instructions: entry.num_instructions,
+                        ingress_payload_size: entry.ingress_payload_size,
+                        egress_payload_size: entry.egress_payload_size,
+                    },
+                );
+        }
+        Ok(Self {
+            highest_aggregated_epoch: item.highest_aggregated_epoch.map(QueryStatsEpoch::from),
+            stats,
+        })
+    }
+}
@@ -8,8 +8,8 @@ use ic_test_utilities::{
     mock_time,
     types::{
         ids::{
-            canister_test_id, message_test_id, subnet_test_id, user_test_id, SUBNET_0, SUBNET_1,
-            SUBNET_2,
+            canister_test_id, message_test_id, node_test_id, subnet_test_id, user_test_id,
+            SUBNET_0, SUBNET_1, SUBNET_2,
         },
         messages::{RequestBuilder, ResponseBuilder},
         xnet::{StreamHeaderBuilder, StreamSliceBuilder},
@@ -17,6 +17,7 @@ use ic_test_utilities::{
 };
 use ic_types::canister_http::Transform;
 use ic_types::{
+    batch::{QueryStats, QueryStatsEpoch},
     canister_http::{CanisterHttpMethod, CanisterHttpRequestContext},
     ingress::WasmResult,
     messages::{CallbackId, Payload},
@@ -447,6 +448,25 @@ fn roundtrip_encoding() {
     // Set `last_generated_canister_id` to valid, but migrated canister ID.
     system_metadata.last_generated_canister_id = Some(15.into());
     validate_roundtrip_encoding(&system_metadata);
+
+    // Add raw query stats of two nodes, one epoch having been aggregated.
+    system_metadata.query_stats = RawQueryStats {
+        highest_aggregated_epoch: Some(2.into()),
+        stats: btreemap! {
+            QueryStatsEpoch::from(3) => btreemap! {
+                canister_test_id(1) => btreemap! {
+                    node_test_id(1) => QueryStats {
+                        num_calls: 1,
+                        num_instructions: 2,
+                        ingress_payload_size: 3,
+                        egress_payload_size: 4,
+                    },
+                    node_test_id(2) => QueryStats::default(),
+                },
+            },
+        },
+    };
+    validate_roundtrip_encoding(&system_metadata);
 }
 
 #[test]
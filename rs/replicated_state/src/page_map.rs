@@ -15,7 +15,7 @@ pub use page_allocator::{
 // NOTE: We use a persistent map to make snapshotting of a PageMap a cheap
 // operation. This allows us to simplify canister state management: we can
 // simply have a copy of the whole PageMap in every canister snapshot.
-use ic_types::{Height, NumPages, MAX_STABLE_MEMORY_IN_BYTES};
+use ic_types::{Height, NumBytes, NumPages, MAX_STABLE_MEMORY_IN_BYTES};
 use int_map::IntMap;
 use libc::off_t;
 use page_allocator::Page;
@@ -428,6 +428,30 @@ impl PageMap {
         pages.iter().map(|(index, _)| *index).collect()
     }
 
+    /// Returns a page map with the same contents as this one that is not
+    /// backed by any checkpoint file. All pages of the modified prefix are
+    /// copied into the page delta of the new page map, so that the copy does
+    /// not depend on the checkpoint files of this page map and flushing its
+    /// deltas writes out its full contents.
+    ///
+    /// Returns the copy together with the size of its page delta, which is
+    /// new heap delta that the caller must charge against the heap delta
+    /// limits.
+    pub fn copy_to_page_delta(&self) -> (PageMap, NumBytes) {
+        let mut page_map = Self {
+            checkpoint: Default::default(),
+            base_height: None,
+            page_delta: Default::default(),
+            unflushed_delta: Default::default(),
+            has_stripped_unflushed_deltas: false,
+            page_allocator: self.page_allocator.clone(),
+        };
+        let pages: Vec<_> = self.host_pages_iter().collect();
+        page_map.update(&pages);
+        let heap_delta = NumBytes::from((pages.len() * PAGE_SIZE) as u64);
+        (page_map, heap_delta)
+    }
+
     /// Persists the heap delta contained in this page map to the specified
     /// destination.
     pub fn persist_delta(&self, dst: &Path) -> Result<(), PersistenceError> {
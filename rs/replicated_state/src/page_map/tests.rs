@@ -159,6 +159,48 @@ fn persisted_map_is_equivalent_to_the_original() {
     assert_eq!(persisted_map, original_map);
 }
 
+#[test]
+fn copy_to_page_delta_does_not_depend_on_checkpoint() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("heap");
+    let copy_file = tmp.path().join("copy");
+
+    let base_page = [42u8; PAGE_SIZE];
+    let mut base_map = PageMap::new_for_testing();
+    base_map.update(&[
+        (PageIndex::new(0), &base_page),
+        (PageIndex::new(5), &base_page),
+    ]);
+    base_map.persist_delta(&heap_file).unwrap();
+
+    let mut original_map = PageMap::open(
+        &heap_file,
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .unwrap();
+    let page_7 = [7u8; PAGE_SIZE];
+    original_map.update(&[(PageIndex::new(7), &page_7)]);
+
+    let (copy, heap_delta) = original_map.copy_to_page_delta();
+    assert_eq!(copy.base_height, None);
+    assert_eq!(heap_delta.get(), 8 * PAGE_SIZE as u64);
+    assert_equal_page_maps(&copy, &original_map);
+
+    // Persisting the delta of the copy must write out all of its pages.
+    copy.persist_delta(&copy_file).unwrap();
+    let persisted_copy = PageMap::open(
+        &copy_file,
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .unwrap();
+    assert_equal_page_maps(&persisted_copy, &original_map);
+}
+
 #[test]
 fn can_persist_and_load_an_empty_page_map() {
     let tmp = tempfile::Builder::new()
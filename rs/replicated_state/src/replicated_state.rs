@@ -323,10 +323,15 @@ pub struct MemoryTaken {
     wasm_custom_sections: NumBytes,
     /// Memory taken by canister history.
     canister_history: NumBytes,
+    /// Memory taken by canister snapshots.
+    canister_snapshots: NumBytes,
+    /// Memory taken by Wasm chunk stores.
+    wasm_chunk_store: NumBytes,
     /// Total memory taken. This is the sum of `execution`, `messages`,
-    /// and `canister_history` on application subnets; and excludes
-    /// canister message memory (i.e. sum of `execution` and
-    /// `canister_history`) on system subnets.
+    /// `canister_history`, `canister_snapshots` and `wasm_chunk_store` on
+    /// application subnets; and excludes canister message memory (i.e. sum
+    /// of `execution`, `canister_history`, `canister_snapshots` and
+    /// `wasm_chunk_store`) on system subnets.
     total: NumBytes,
 }
 
@@ -351,6 +356,16 @@ impl MemoryTaken {
         self.canister_history
     }
 
+    /// Returns the amount of memory taken by canister snapshots.
+    pub fn canister_snapshots(&self) -> NumBytes {
+        self.canister_snapshots
+    }
+
+    /// Returns the amount of memory taken by Wasm chunk stores.
+    pub fn wasm_chunk_store(&self) -> NumBytes {
+        self.wasm_chunk_store
+    }
+
     /// Returns the total amount of memory taken.
     pub fn total(&self) -> NumBytes {
         self.total
@@ -561,6 +576,8 @@ impl ReplicatedState {
             mut message_memory_taken,
             wasm_custom_sections_memory_taken,
             canister_history_memory_taken,
+            canister_snapshots_memory_taken,
+            wasm_chunk_store_memory_taken,
         ) = self
             .canisters_iter()
             .map(|canister| {
@@ -572,6 +589,8 @@ impl ReplicatedState {
                     canister.system_state.message_memory_usage(),
                     canister.wasm_custom_sections_memory_usage(),
                     canister.canister_history_memory_usage(),
+                    canister.canister_snapshots_memory_usage(),
+                    canister.wasm_chunk_store_memory_usage(),
                 )
             })
             .reduce(|accum, val| {
@@ -580,6 +599,8 @@ impl ReplicatedState {
                     accum.1 + val.1,
                     accum.2 + val.2,
                     accum.3 + val.3,
+                    accum.4 + val.4,
+                    accum.5 + val.5,
                 )
             })
             .unwrap_or_default();
@@ -588,7 +609,10 @@ impl ReplicatedState {
 
         // Raw memory taken includes `wasm_custom_sections_memory_taken` so we
         // don't have to add it to the total memory taken separately.
-        let mut total_memory_taken = raw_memory_taken + canister_history_memory_taken;
+        let mut total_memory_taken = raw_memory_taken
+            + canister_history_memory_taken
+            + canister_snapshots_memory_taken
+            + wasm_chunk_store_memory_taken;
 
         // Add message memory taken to total for non-system subnets only.
         if self.metadata.own_subnet_type != SubnetType::System {
@@ -600,6 +624,8 @@ impl ReplicatedState {
             messages: message_memory_taken,
             wasm_custom_sections: wasm_custom_sections_memory_taken,
             canister_history: canister_history_memory_taken,
+            canister_snapshots: canister_snapshots_memory_taken,
+            wasm_chunk_store: wasm_chunk_store_memory_taken,
             total: total_memory_taken,
         }
     }
@@ -834,6 +860,40 @@ impl ReplicatedState {
 
         timed_out_requests_count
     }
+
+    /// Enqueues `SYS_UNKNOWN` reject responses for all best-effort calls whose
+    /// deadlines have expired at `current_time`. Returns the number of reject
+    /// responses enqueued.
+    ///
+    /// See `CanisterQueues::try_push_deadline_expired_input` for further details.
+    #[allow(clippy::needless_collect)]
+    pub fn time_out_callbacks(&mut self, current_time: Time) -> u64 {
+        // Same as for `time_out_requests()`, only remove-call-replace the
+        // canisters with expired callbacks.
+        let canister_ids_with_expired_callbacks = self
+            .canister_states
+            .iter()
+            .filter(|(_, canister_state)| {
+                canister_state
+                    .system_state
+                    .has_expired_callbacks(current_time)
+            })
+            .map(|(canister_id, _)| *canister_id)
+            .collect::<Vec<_>>();
+
+        let mut timed_out_callbacks_count = 0;
+        for canister_id in canister_ids_with_expired_callbacks {
+            let mut canister = self.canister_states.remove(&canister_id).unwrap();
+            timed_out_callbacks_count += canister.system_state.time_out_callbacks(
+                current_time,
+                &canister_id,
+                &self.canister_states,
+            );
+            self.canister_states.insert(canister_id, canister);
+        }
+
+        timed_out_callbacks_count
+    }
 }
 
 /// A trait exposing `ReplicatedState` functionality for the exclusive use of
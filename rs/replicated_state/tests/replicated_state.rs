@@ -1,9 +1,11 @@
+use assert_matches::assert_matches;
 use ic_base_types::{CanisterId, NumBytes, NumSeconds, PrincipalId, SubnetId};
 use ic_btc_interface::NetworkSnakeCase;
 use ic_btc_types_internal::{
     BitcoinAdapterResponse, BitcoinAdapterResponseWrapper, GetSuccessorsRequestInitial,
     GetSuccessorsResponseComplete,
 };
+use ic_error_types::RejectCode;
 use ic_ic00_types::{
     BitcoinGetSuccessorsResponse, CanisterChange, CanisterChangeDetails, CanisterChangeOrigin,
     Payload as _,
@@ -16,7 +18,7 @@ use ic_replicated_state::{
     canister_state::execution_state::{CustomSection, CustomSectionType, WasmMetadata},
     metadata_state::subnet_call_context_manager::BitcoinGetSuccessorsContext,
     replicated_state::{MemoryTaken, PeekableOutputIterator, ReplicatedStateMessageRouting},
-    CanisterState, ReplicatedState, SchedulerState, StateError, SystemState,
+    CallOrigin, CanisterState, ReplicatedState, SchedulerState, StateError, SystemState,
 };
 use ic_test_utilities::mock_time;
 use ic_test_utilities::state::{arb_replicated_state_with_queues, ExecutionStateBuilder};
@@ -26,13 +28,18 @@ use ic_test_utilities::types::{
     messages::{RequestBuilder, ResponseBuilder},
 };
 use ic_types::{
-    messages::{Payload, Request, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES},
-    CountBytes, Cycles, MemoryAllocation, Time,
+    messages::{
+        CallbackId, Payload, Request, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
+        NO_DEADLINE,
+    },
+    methods::{Callback, WasmClosure},
+    CoarseTime, CountBytes, Cycles, MemoryAllocation, Time,
 };
 use proptest::prelude::*;
 use std::collections::{BTreeMap, VecDeque};
 use std::mem::size_of;
 use std::sync::Arc;
+use std::time::Duration;
 
 const SUBNET_ID: SubnetId = SubnetId::new(PrincipalId::new(29, [0xfc; 29]));
 const CANISTER_ID: CanisterId = CanisterId::from_u64(42);
@@ -757,6 +764,81 @@ fn time_out_requests_updates_subnet_input_schedules_correctly() {
     );
 }
 
+#[test]
+fn time_out_callbacks_enqueues_reject_responses_for_expired_callbacks() {
+    let mut fixture = ReplicatedStateFixture::new();
+    let remote_canister_id = CanisterId::from_u64(123);
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+    let deadline_time = Time::from(deadline);
+
+    // Register a best-effort callback and route the matching request.
+    let call_context_manager = fixture
+        .state
+        .canister_state_mut(&CANISTER_ID)
+        .unwrap()
+        .system_state
+        .call_context_manager_mut()
+        .unwrap();
+    let call_context_id = call_context_manager.new_call_context(
+        CallOrigin::CanisterUpdate(remote_canister_id, CallbackId::from(1), NO_DEADLINE),
+        Cycles::zero(),
+        mock_time(),
+    );
+    let callback_id = call_context_manager.register_callback(Callback::new(
+        call_context_id,
+        Some(CANISTER_ID),
+        Some(remote_canister_id),
+        Cycles::zero(),
+        None,
+        None,
+        WasmClosure::new(0, 2),
+        WasmClosure::new(0, 2),
+        None,
+        deadline,
+    ));
+    let mut request = request_to(remote_canister_id);
+    request.sender_reply_callback = callback_id;
+    request.deadline = deadline;
+    fixture.push_output_request(request, mock_time()).unwrap();
+    fixture.state.output_into_iter().next().unwrap();
+
+    // Nothing to do before the deadline.
+    assert_eq!(
+        0,
+        fixture
+            .state
+            .time_out_callbacks(deadline_time - Duration::from_nanos(1))
+    );
+
+    // A reject response is enqueued at the deadline, only once.
+    assert_eq!(1, fixture.state.time_out_callbacks(deadline_time));
+    assert_eq!(0, fixture.state.time_out_callbacks(deadline_time));
+    assert_eq!(
+        fixture.remote_subnet_input_schedule(),
+        &VecDeque::from(vec![remote_canister_id])
+    );
+
+    // A late response is rejected as a duplicate.
+    let mut response = response_from(remote_canister_id);
+    response.originator_reply_callback = callback_id;
+    response.deadline = deadline;
+    assert_matches!(
+        fixture.push_input(response.into()),
+        Err((StateError::NonMatchingResponse { .. }, _))
+    );
+
+    match fixture.pop_input() {
+        Some(CanisterMessage::Response(response)) => {
+            assert_eq!(callback_id, response.originator_reply_callback);
+            assert_matches!(
+                &response.response_payload,
+                Payload::Reject(context) if context.code() == RejectCode::SysUnknown
+            );
+        }
+        msg => panic!("Expected a reject response, got {:?}", msg),
+    }
+}
+
 proptest! {
     #[test]
     fn peek_and_next_consistent(
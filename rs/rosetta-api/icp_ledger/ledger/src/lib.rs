@@ -289,6 +289,10 @@ impl Ledger {
                 CTE::TxDuplicate { duplicate_of } => PTE(TE::TxDuplicate { duplicate_of }),
                 CTE::InsufficientAllowance { .. } => todo!(),
                 CTE::ExpiredApproval { .. } => todo!(),
+                CTE::AllowanceChanged { current_allowance } => PaymentError::Reject(format!(
+                    "The allowance changed, current allowance: {}",
+                    current_allowance
+                )),
                 CTE::TxThrottled => PaymentError::Reject(
                     concat!(
                         "Too many transactions in replay prevention window, ",
@@ -1,4 +1,4 @@
-use candid::{candid_method, Principal};
+use candid::{candid_method, Nat, Principal};
 use ic_canisters_http_types::{HttpRequest, HttpResponse, HttpResponseBuilder};
 use ic_cdk_macros::{init, post_upgrade, query, update};
 use ic_icrc1::{blocks::encoded_block_to_generic_block, Block};
@@ -10,6 +10,7 @@ use ic_stable_structures::{
 };
 use icrc_ledger_types::icrc3::blocks::BlockRange;
 use icrc_ledger_types::icrc3::blocks::GenericBlock as IcrcBlock;
+use icrc_ledger_types::icrc3::blocks::{BlockWithId, GetBlocksArgs, GetBlocksResult};
 
 use icrc_ledger_types::icrc3::transactions::Transaction;
 use icrc_ledger_types::icrc3::transactions::{GetTransactionsRequest, TransactionRange};
@@ -265,6 +266,42 @@ fn get_blocks(req: GetTransactionsRequest) -> BlockRange {
     BlockRange { blocks }
 }
 
+/// Returns the requested blocks as ICRC-3 generic values.
+/// Requested ranges are clamped to the blocks this archive holds, and the total
+/// number of blocks in the response is capped by the maximum number of
+/// transactions per response.
+#[query]
+#[candid_method(query)]
+fn icrc3_get_blocks(args: GetBlocksArgs) -> GetBlocksResult {
+    let (block_index_offset, max_blocks) =
+        with_archive_opts(|opts| (opts.block_index_offset, opts.max_transactions_per_response));
+    let mut blocks = vec![];
+    for arg in args {
+        let (start, length) = arg
+            .as_start_and_length()
+            .unwrap_or_else(|msg| ic_cdk::api::trap(&msg));
+        let end = start.saturating_add(length);
+        let start = start.max(block_index_offset);
+        let length = end
+            .saturating_sub(start)
+            .min(max_blocks.saturating_sub(blocks.len() as u64));
+        if length == 0 {
+            continue;
+        }
+        let decoded = decode_block_range(start, length, decode_icrc1_block);
+        blocks.extend((start..).zip(decoded).map(|(id, block)| BlockWithId {
+            id: Nat::from(id),
+            block,
+        }));
+    }
+    let log_length = block_index_offset + with_blocks(|log| log.len());
+    GetBlocksResult {
+        log_length: Nat::from(log_length),
+        blocks,
+        archived_blocks: vec![],
+    }
+}
+
 #[query]
 fn __get_candid_interface_tmp_hack() -> &'static str {
     include_str!(env!("ARCHIVE_DID_PATH"))
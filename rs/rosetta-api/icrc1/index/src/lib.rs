@@ -4,7 +4,7 @@ use ic_canister_profiler::{measure_span, SpanStats};
 use ic_cdk::api::stable::{StableReader, StableWriter};
 use icrc_ledger_types::icrc3::archive::QueryTxArchiveFn;
 use icrc_ledger_types::icrc3::transactions::{
-    GetTransactionsResponse, Transaction, TransactionRange, Transfer,
+    Approve, GetTransactionsResponse, Transaction, TransactionRange, Transfer, TransferFrom,
 };
 use icrc_ledger_types::{
     icrc1::account::Account, icrc1::account::Subaccount, icrc3::archive::ArchivedRange,
@@ -296,6 +296,25 @@ fn index_transaction(txid: u64, transaction: Transaction) -> Result<(), String>
             add_tx(txid, to);
             Ok(())
         }
+        "transfer_from" => {
+            let TransferFrom {
+                from, to, spender, ..
+            } = transaction.transfer_from.ok_or(
+                "Got a transaction with kind 'transfer_from' but the transfer_from field was None",
+            )?;
+            add_tx(txid, from);
+            add_tx(txid, to);
+            add_tx(txid, spender);
+            Ok(())
+        }
+        "approve" => {
+            let Approve { from, spender, .. } = transaction
+                .approve
+                .ok_or("Got a transaction with kind 'approve' but the approve field was None")?;
+            add_tx(txid, from);
+            add_tx(txid, spender);
+            Ok(())
+        }
         kind => Err(format!("Found transaction of unknown kind {}", kind)),
     }
 }
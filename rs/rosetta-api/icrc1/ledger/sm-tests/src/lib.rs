@@ -1,5 +1,6 @@
 use candid::{CandidType, Decode, Encode, Nat, Principal};
 use ic_base_types::PrincipalId;
+use ic_crypto_tree_hash::{LookupStatus, MixedHashTree};
 use ic_error_types::UserError;
 use ic_icrc1::{endpoints::StandardRecord, hash::Hash, Block, Operation, Transaction};
 use ic_ledger_canister_core::archive::ArchiveOptions;
@@ -8,10 +9,18 @@ use ic_state_machine_tests::{CanisterId, ErrorCode, StateMachine, WasmResult};
 use icrc_ledger_types::icrc::generic_metadata_value::MetadataValue as Value;
 use icrc_ledger_types::icrc1::account::{Account, Subaccount};
 use icrc_ledger_types::icrc1::transfer::{Memo, TransferArg, TransferError};
-use icrc_ledger_types::icrc3::archive::ArchiveInfo;
+use icrc_ledger_types::icrc2::allowance::{Allowance, AllowanceArgs};
+use icrc_ledger_types::icrc2::approve::{ApproveArgs, ApproveError};
+use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+use icrc_ledger_types::icrc3::archive::{
+    ArchiveInfo, GetArchivesArgs, GetArchivesResult, ICRC3ArchiveInfo,
+};
 use icrc_ledger_types::icrc3::blocks::BlockRange;
 use icrc_ledger_types::icrc3::blocks::GenericBlock as IcrcBlock;
 use icrc_ledger_types::icrc3::blocks::GetBlocksResponse;
+use icrc_ledger_types::icrc3::blocks::{
+    GetBlocksArgs, GetBlocksRequest, GetBlocksResult, ICRC3DataCertificate,
+};
 use icrc_ledger_types::icrc3::transactions::GetTransactionsRequest;
 use icrc_ledger_types::icrc3::transactions::GetTransactionsResponse;
 use icrc_ledger_types::icrc3::transactions::Transaction as Tx;
@@ -202,6 +211,91 @@ fn transfer(
     )
 }
 
+fn send_approval(
+    env: &StateMachine,
+    ledger: CanisterId,
+    from: Principal,
+    arg: &ApproveArgs,
+) -> Result<BlockIndex, ApproveError> {
+    Decode!(
+        &env.execute_ingress_as(PrincipalId(from), ledger, "icrc2_approve", Encode!(arg).unwrap())
+            .expect("failed to apply approval")
+            .bytes(),
+        Result<Nat, ApproveError>
+    )
+    .expect("failed to decode approve response")
+    .map(|n| n.0.to_u64().unwrap())
+}
+
+fn send_transfer_from(
+    env: &StateMachine,
+    ledger: CanisterId,
+    spender: Principal,
+    arg: &TransferFromArgs,
+) -> Result<BlockIndex, TransferFromError> {
+    Decode!(
+        &env.execute_ingress_as(
+            PrincipalId(spender),
+            ledger,
+            "icrc2_transfer_from",
+            Encode!(arg).unwrap()
+        )
+        .expect("failed to apply transfer_from")
+        .bytes(),
+        Result<Nat, TransferFromError>
+    )
+    .expect("failed to decode transfer_from response")
+    .map(|n| n.0.to_u64().unwrap())
+}
+
+fn get_allowance(
+    env: &StateMachine,
+    ledger: CanisterId,
+    account: impl Into<Account>,
+    spender: impl Into<Account>,
+) -> Allowance {
+    let arg = AllowanceArgs {
+        account: account.into(),
+        spender: spender.into(),
+    };
+    Decode!(
+        &env.query(ledger, "icrc2_allowance", Encode!(&arg).unwrap())
+            .expect("failed to query allowance")
+            .bytes(),
+        Allowance
+    )
+    .expect("failed to decode allowance response")
+}
+
+fn default_approve_args(spender: impl Into<Account>, amount: u64) -> ApproveArgs {
+    ApproveArgs {
+        from_subaccount: None,
+        spender: spender.into(),
+        amount: Nat::from(amount),
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    }
+}
+
+fn default_transfer_from_args(
+    from: impl Into<Account>,
+    to: impl Into<Account>,
+    amount: u64,
+) -> TransferFromArgs {
+    TransferFromArgs {
+        spender_subaccount: None,
+        from: from.into(),
+        to: to.into(),
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    }
+}
+
 fn list_archives(env: &StateMachine, ledger: CanisterId) -> Vec<ArchiveInfo> {
     Decode!(
         &env.query(ledger, "archives", Encode!().unwrap())
@@ -290,6 +384,58 @@ fn get_archive_blocks(
     get_transactions_as(env, archive, start, length, "get_blocks".to_string())
 }
 
+fn icrc3_get_blocks(
+    env: &StateMachine,
+    canister: Principal,
+    start: u64,
+    length: usize,
+) -> GetBlocksResult {
+    let canister_id =
+        CanisterId::new(canister.into()).expect("failed to convert Principal to CanisterId");
+    let args: GetBlocksArgs = vec![GetBlocksRequest {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    }];
+    Decode!(
+        &env.query(canister_id, "icrc3_get_blocks", Encode!(&args).unwrap())
+            .expect("failed to query icrc3_get_blocks")
+            .bytes(),
+        GetBlocksResult
+    )
+    .expect("failed to decode icrc3_get_blocks response")
+}
+
+fn icrc3_get_archives(
+    env: &StateMachine,
+    ledger: CanisterId,
+    from: Option<Principal>,
+) -> GetArchivesResult {
+    Decode!(
+        &env.query(
+            ledger,
+            "icrc3_get_archives",
+            Encode!(&GetArchivesArgs { from }).unwrap()
+        )
+        .expect("failed to query icrc3_get_archives")
+        .bytes(),
+        GetArchivesResult
+    )
+    .expect("failed to decode icrc3_get_archives response")
+}
+
+fn icrc3_get_tip_certificate(
+    env: &StateMachine,
+    ledger: CanisterId,
+) -> Option<ICRC3DataCertificate> {
+    Decode!(
+        &env.query(ledger, "icrc3_get_tip_certificate", Encode!().unwrap())
+            .expect("failed to query icrc3_get_tip_certificate")
+            .bytes(),
+        Option<ICRC3DataCertificate>
+    )
+    .expect("failed to decode icrc3_get_tip_certificate response")
+}
+
 fn get_phash(block: &IcrcBlock) -> Result<Option<Hash>, String> {
     match block {
         IcrcBlock::Map(map) => {
@@ -410,8 +556,52 @@ fn arb_burn() -> impl Strategy<Value = Operation> {
     (arb_account(), arb_amount()).prop_map(|(from, amount)| Operation::Burn { from, amount })
 }
 
+fn arb_approve() -> impl Strategy<Value = Operation> {
+    (
+        arb_account(),
+        arb_account(),
+        arb_amount(),
+        proptest::option::of(arb_amount()),
+        proptest::option::of(any::<u64>()),
+        proptest::option::of(arb_amount()),
+    )
+        .prop_map(
+            |(from, spender, amount, expected_allowance, expires_at, fee)| Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+                fee,
+            },
+        )
+}
+
+fn arb_transfer_from() -> impl Strategy<Value = Operation> {
+    (
+        arb_account(),
+        arb_account(),
+        arb_account(),
+        arb_amount(),
+        proptest::option::of(arb_amount()),
+    )
+        .prop_map(|(from, to, spender, amount, fee)| Operation::TransferFrom {
+            from,
+            to,
+            spender,
+            amount,
+            fee,
+        })
+}
+
 fn arb_operation() -> impl Strategy<Value = Operation> {
-    prop_oneof![arb_transfer(), arb_mint(), arb_burn()]
+    prop_oneof![
+        arb_transfer(),
+        arb_mint(),
+        arb_burn(),
+        arb_approve(),
+        arb_transfer_from()
+    ]
 }
 
 fn arb_transaction() -> impl Strategy<Value = Transaction> {
@@ -647,10 +837,20 @@ where
     let standards = supported_standards(&env, canister_id);
     assert_eq!(
         standards,
-        vec![StandardRecord {
-            name: "ICRC-1".to_string(),
-            url: "https://github.com/dfinity/ICRC-1".to_string(),
-        }]
+        vec![
+            StandardRecord {
+                name: "ICRC-1".to_string(),
+                url: "https://github.com/dfinity/ICRC-1".to_string(),
+            },
+            StandardRecord {
+                name: "ICRC-2".to_string(),
+                url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2".to_string(),
+            },
+            StandardRecord {
+                name: "ICRC-3".to_string(),
+                url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3".to_string(),
+            },
+        ]
     );
 }
 
@@ -1197,6 +1397,116 @@ where
     assert_eq!(0, missing_blocks_reply.archived_blocks.len());
 }
 
+pub fn test_icrc3_get_blocks<T>(ledger_wasm: Vec<u8>, encode_init_args: fn(InitArgs) -> T)
+where
+    T: CandidType,
+{
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+
+    let (env, canister_id) = setup(
+        ledger_wasm,
+        encode_init_args,
+        vec![(Account::from(p1.0), 10_000_000)],
+    );
+
+    for i in 0..ARCHIVE_TRIGGER_THRESHOLD {
+        transfer(&env, canister_id, p1.0, p2.0, 10_000 + i * 10_000).expect("transfer failed");
+    }
+
+    env.run_until_completion(/*max_ticks=*/ 10);
+
+    let chain_length = ARCHIVE_TRIGGER_THRESHOLD + 1;
+    let resp = icrc3_get_blocks(&env, canister_id.get().0, 0, 1_000_000);
+    assert_eq!(resp.log_length, Nat::from(chain_length));
+    assert_eq!(
+        resp.blocks.len(),
+        (chain_length - NUM_BLOCKS_TO_ARCHIVE) as usize
+    );
+    assert_eq!(resp.blocks[0].id, Nat::from(NUM_BLOCKS_TO_ARCHIVE));
+    assert_eq!(resp.archived_blocks.len(), 1);
+    assert_eq!(
+        resp.archived_blocks[0].args,
+        vec![GetBlocksRequest {
+            start: Nat::from(0),
+            length: Nat::from(NUM_BLOCKS_TO_ARCHIVE),
+        }]
+    );
+
+    let archive_canister_id = list_archives(&env, canister_id)[0].canister_id;
+    assert_eq!(
+        resp.archived_blocks[0].callback.canister_id,
+        archive_canister_id
+    );
+    assert_eq!(resp.archived_blocks[0].callback.method, "icrc3_get_blocks");
+    assert_eq!(
+        icrc3_get_archives(&env, canister_id, None),
+        vec![ICRC3ArchiveInfo {
+            canister_id: archive_canister_id,
+            start: Nat::from(0),
+            end: Nat::from(NUM_BLOCKS_TO_ARCHIVE - 1),
+        }]
+    );
+    assert_eq!(
+        icrc3_get_archives(&env, canister_id, Some(archive_canister_id)),
+        vec![]
+    );
+
+    let archived = icrc3_get_blocks(&env, archive_canister_id, 0, NUM_BLOCKS_TO_ARCHIVE as usize);
+    assert!(archived.archived_blocks.is_empty());
+    assert_eq!(archived.blocks.len(), NUM_BLOCKS_TO_ARCHIVE as usize);
+
+    // Ranges that extend past the archive's window return the blocks it holds.
+    let tail = icrc3_get_blocks(&env, archive_canister_id, NUM_BLOCKS_TO_ARCHIVE - 2, 10);
+    assert_eq!(
+        tail.blocks.iter().map(|b| b.id.clone()).collect::<Vec<_>>(),
+        vec![
+            Nat::from(NUM_BLOCKS_TO_ARCHIVE - 2),
+            Nat::from(NUM_BLOCKS_TO_ARCHIVE - 1)
+        ]
+    );
+
+    // The ICRC-3 blocks must be the same values that get_blocks returns, so
+    // the hash chain computed on them agrees with the ledger's block hashes.
+    let legacy_blocks = get_blocks(&env, canister_id.get().0, 0, 1_000_000).blocks;
+    let mut prev_hash = None;
+    for (expected_id, block) in archived.blocks.into_iter().chain(resp.blocks).enumerate() {
+        assert_eq!(block.id, Nat::from(expected_id));
+        assert_eq!(
+            prev_hash,
+            get_phash(&block.block).expect("cannot get the hash of the previous block")
+        );
+        prev_hash = Some(block.block.hash());
+    }
+    assert_eq!(
+        prev_hash,
+        legacy_blocks.last().map(|block| block.hash()),
+        "the ICRC-3 tip hash must match the hash of the last block"
+    );
+
+    let tip_certificate = icrc3_get_tip_certificate(&env, canister_id)
+        .expect("the ledger must return a tip certificate");
+    let hash_tree: MixedHashTree = ciborium::de::from_reader(tip_certificate.hash_tree.as_slice())
+        .expect("failed to decode the tip certificate hash tree");
+    let mut last_block_index = vec![];
+    leb128::write::unsigned(&mut last_block_index, chain_length - 1).unwrap();
+    assert_eq!(
+        hash_tree.lookup(&[b"last_block_index"]),
+        LookupStatus::Found(&MixedHashTree::Leaf(last_block_index))
+    );
+    assert_eq!(
+        hash_tree.lookup(&[b"last_block_hash"]),
+        LookupStatus::Found(&MixedHashTree::Leaf(prev_hash.unwrap().to_vec()))
+    );
+    ic_certification::verify_certified_data(
+        &tip_certificate.certificate,
+        &canister_id,
+        &env.root_key(),
+        &hash_tree.digest().0,
+    )
+    .expect("the tip certificate must certify the hash tree");
+}
+
 // Generate random blocks and check that their CBOR encoding complies with the CDDL spec.
 pub fn block_encoding_agrees_with_the_schema() {
     use std::path::PathBuf;
@@ -1714,3 +2024,182 @@ where
         ),
     }
 }
+
+pub fn test_approve_smoke<T>(ledger_wasm: Vec<u8>, encode_init_args: fn(InitArgs) -> T)
+where
+    T: CandidType,
+{
+    let from = PrincipalId::new_user_test_id(1);
+    let spender = PrincipalId::new_user_test_id(2);
+    let (env, canister_id) = setup(
+        ledger_wasm,
+        encode_init_args,
+        vec![(Account::from(from.0), 100_000)],
+    );
+
+    let block_index = send_approval(
+        &env,
+        canister_id,
+        from.0,
+        &default_approve_args(spender.0, 10_000),
+    )
+    .expect("approval failed");
+    assert_eq!(block_index, 1);
+    let allowance = get_allowance(&env, canister_id, from.0, spender.0);
+    assert_eq!(allowance.allowance, Nat::from(10_000u64));
+    assert_eq!(allowance.expires_at, None);
+    assert_eq!(balance_of(&env, canister_id, from.0), 100_000 - FEE);
+    assert_eq!(balance_of(&env, canister_id, spender.0), 0);
+
+    // The approval replaces the allowance instead of adding to it.
+    send_approval(
+        &env,
+        canister_id,
+        from.0,
+        &default_approve_args(spender.0, 5_000),
+    )
+    .expect("approval failed");
+    let allowance = get_allowance(&env, canister_id, from.0, spender.0);
+    assert_eq!(allowance.allowance, Nat::from(5_000u64));
+
+    // The expected allowance must match the current one.
+    assert_eq!(
+        send_approval(
+            &env,
+            canister_id,
+            from.0,
+            &ApproveArgs {
+                expected_allowance: Some(Nat::from(10_000u64)),
+                ..default_approve_args(spender.0, 1_000)
+            },
+        ),
+        Err(ApproveError::AllowanceChanged {
+            current_allowance: Nat::from(5_000u64)
+        })
+    );
+
+    // Approvals that expire in the past are rejected.
+    let now = system_time_to_nanos(env.time());
+    assert_eq!(
+        send_approval(
+            &env,
+            canister_id,
+            from.0,
+            &ApproveArgs {
+                expires_at: Some(now - 1),
+                ..default_approve_args(spender.0, 1_000)
+            },
+        ),
+        Err(ApproveError::Expired { ledger_time: now })
+    );
+
+    // Approvals with an explicit wrong fee are rejected.
+    assert_eq!(
+        send_approval(
+            &env,
+            canister_id,
+            from.0,
+            &ApproveArgs {
+                fee: Some(Nat::from(FEE + 1)),
+                ..default_approve_args(spender.0, 1_000)
+            },
+        ),
+        Err(ApproveError::BadFee {
+            expected_fee: Nat::from(FEE)
+        })
+    );
+
+    // Allowances expire once the ledger time passes their expiration date.
+    let expires_at = now + Duration::from_secs(3600).as_nanos() as u64;
+    send_approval(
+        &env,
+        canister_id,
+        from.0,
+        &ApproveArgs {
+            expires_at: Some(expires_at),
+            ..default_approve_args(spender.0, 1_000)
+        },
+    )
+    .expect("approval failed");
+    let allowance = get_allowance(&env, canister_id, from.0, spender.0);
+    assert_eq!(allowance.allowance, Nat::from(1_000u64));
+    assert_eq!(allowance.expires_at, Some(expires_at));
+
+    env.advance_time(Duration::from_secs(3601));
+    let allowance = get_allowance(&env, canister_id, from.0, spender.0);
+    assert_eq!(allowance.allowance, Nat::from(0u64));
+    assert_eq!(allowance.expires_at, None);
+}
+
+pub fn test_transfer_from_smoke<T>(ledger_wasm: Vec<u8>, encode_init_args: fn(InitArgs) -> T)
+where
+    T: CandidType,
+{
+    let from = PrincipalId::new_user_test_id(1);
+    let spender = PrincipalId::new_user_test_id(2);
+    let to = PrincipalId::new_user_test_id(3);
+    let (env, canister_id) = setup(
+        ledger_wasm,
+        encode_init_args,
+        vec![(Account::from(from.0), 100_000)],
+    );
+
+    // Spending without an allowance fails.
+    assert_eq!(
+        send_transfer_from(
+            &env,
+            canister_id,
+            spender.0,
+            &default_transfer_from_args(from.0, to.0, 30_000),
+        ),
+        Err(TransferFromError::InsufficientAllowance {
+            allowance: Nat::from(0u64)
+        })
+    );
+
+    send_approval(
+        &env,
+        canister_id,
+        from.0,
+        &default_approve_args(spender.0, 50_000),
+    )
+    .expect("approval failed");
+
+    send_transfer_from(
+        &env,
+        canister_id,
+        spender.0,
+        &default_transfer_from_args(from.0, to.0, 30_000),
+    )
+    .expect("transfer_from failed");
+
+    // The fee is charged to the source account and counts towards the allowance.
+    assert_eq!(
+        balance_of(&env, canister_id, from.0),
+        100_000 - 2 * FEE - 30_000
+    );
+    assert_eq!(balance_of(&env, canister_id, to.0), 30_000);
+    assert_eq!(balance_of(&env, canister_id, spender.0), 0);
+    let allowance = get_allowance(&env, canister_id, from.0, spender.0);
+    assert_eq!(allowance.allowance, Nat::from(50_000 - 30_000 - FEE));
+
+    assert_eq!(
+        send_transfer_from(
+            &env,
+            canister_id,
+            spender.0,
+            &default_transfer_from_args(from.0, to.0, 10_000),
+        ),
+        Err(TransferFromError::InsufficientAllowance {
+            allowance: Nat::from(50_000 - 30_000 - FEE)
+        })
+    );
+
+    // The ledger records the spender in the transaction log.
+    let tx = get_transactions(&env, canister_id.get().0, 2, 1)
+        .transactions
+        .pop()
+        .unwrap();
+    assert_eq!(tx.kind, "transfer_from");
+    assert_eq!(tx.transfer_from.unwrap().spender, Account::from(spender.0));
+}
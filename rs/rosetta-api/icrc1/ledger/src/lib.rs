@@ -5,7 +5,6 @@ use candid::{
     types::number::{Int, Nat},
     CandidType, Principal,
 };
-use ic_base_types::PrincipalId;
 use ic_crypto_tree_hash::{Label, MixedHashTree};
 use ic_icrc1::blocks::encoded_block_to_generic_block;
 use ic_icrc1::{Block, LedgerBalances, Transaction};
@@ -24,10 +23,16 @@ use ic_ledger_core::{
 };
 use icrc_ledger_types::icrc1::account::Account;
 use icrc_ledger_types::icrc3::transactions::Transaction as Tx;
-use icrc_ledger_types::icrc3::{blocks::GetBlocksResponse, transactions::GetTransactionsResponse};
+use icrc_ledger_types::icrc3::{
+    blocks::{
+        ArchivedBlocks, BlockWithId, GetBlocksArgs, GetBlocksRequest, GetBlocksResponse,
+        GetBlocksResult,
+    },
+    transactions::GetTransactionsResponse,
+};
 use icrc_ledger_types::{
     icrc::generic_metadata_value::MetadataValue as Value,
-    icrc3::archive::{ArchivedRange, QueryBlockArchiveFn, QueryTxArchiveFn},
+    icrc3::archive::{ArchivedRange, QueryArchiveFn, QueryBlockArchiveFn, QueryTxArchiveFn},
 };
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
@@ -166,6 +171,9 @@ pub struct Ledger {
     token_name: String,
     metadata: Vec<(String, StoredValue)>,
     max_memo_length: u16,
+
+    #[serde(default)]
+    approvals: AllowanceTable<ApprovalKey, Account, Account>,
 }
 
 impl Ledger {
@@ -198,6 +206,7 @@ impl Ledger {
                 .map(|(k, v)| (k, StoredValue::from(v)))
                 .collect(),
             max_memo_length: max_memo_length.unwrap_or(DEFAULT_MAX_MEMO_LENGTH),
+            approvals: Default::default(),
         };
 
         for (account, balance) in initial_balances.into_iter() {
@@ -216,19 +225,19 @@ impl Ledger {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
-pub struct ApprovalKey(Account, PrincipalId);
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct ApprovalKey(Account, Account);
 
-impl From<(&Account, &PrincipalId)> for ApprovalKey {
-    fn from((account, principal): (&Account, &PrincipalId)) -> Self {
-        Self(*account, *principal)
+impl From<(&Account, &Account)> for ApprovalKey {
+    fn from((account, spender): (&Account, &Account)) -> Self {
+        Self(*account, *spender)
     }
 }
 
 impl LedgerContext for Ledger {
     type AccountId = Account;
-    type SpenderId = PrincipalId;
-    type Approvals = AllowanceTable<ApprovalKey, Account, PrincipalId>;
+    type SpenderId = Account;
+    type Approvals = AllowanceTable<ApprovalKey, Account, Account>;
     type BalancesStore = HashMap<Self::AccountId, Tokens>;
 
     fn balances(&self) -> &Balances<Self::BalancesStore> {
@@ -240,11 +249,11 @@ impl LedgerContext for Ledger {
     }
 
     fn approvals(&self) -> &Self::Approvals {
-        unimplemented!()
+        &self.approvals
     }
 
     fn approvals_mut(&mut self) -> &mut Self::Approvals {
-        unimplemented!()
+        &mut self.approvals
     }
 
     fn fee_collector(&self) -> Option<&FeeCollector<Self::AccountId>> {
@@ -393,14 +402,17 @@ impl Ledger {
         match self.blockchain().last_hash {
             Some(hash) => {
                 let last_block_index = self.blockchain().chain_length().checked_sub(1).unwrap();
+                let mut last_block_index_buf = Vec::with_capacity(10);
+                leb128::write::unsigned(&mut last_block_index_buf, last_block_index).unwrap();
+                // The labels follow the ICRC-3 specification of the tip certificate.
                 MixedHashTree::Fork(Box::new((
                     MixedHashTree::Labeled(
-                        Label::from("last_block_index"),
-                        Box::new(MixedHashTree::Leaf(last_block_index.to_be_bytes().to_vec())),
+                        Label::from("last_block_hash"),
+                        Box::new(MixedHashTree::Leaf(hash.as_slice().to_vec())),
                     ),
                     MixedHashTree::Labeled(
-                        Label::from("tip_hash"),
-                        Box::new(MixedHashTree::Leaf(hash.as_slice().to_vec())),
+                        Label::from("last_block_index"),
+                        Box::new(MixedHashTree::Leaf(last_block_index_buf)),
                     ),
                 )))
             }
@@ -478,4 +490,61 @@ impl Ledger {
             archived_blocks,
         }
     }
+
+    /// Returns blocks in the specified ranges as ICRC-3 generic values.
+    /// Ranges that live in archives are grouped by archive canister.
+    pub fn icrc3_get_blocks(&self, args: GetBlocksArgs) -> GetBlocksResult {
+        let mut blocks = vec![];
+        let mut archived_ranges: BTreeMap<Principal, GetBlocksArgs> = BTreeMap::new();
+
+        for arg in args {
+            let (start, length) = arg
+                .as_start_and_length()
+                .unwrap_or_else(|msg| ic_cdk::api::trap(&msg));
+            let max_length = MAX_TRANSACTIONS_PER_REQUEST.saturating_sub(blocks.len());
+            if max_length == 0 {
+                break;
+            }
+            let length = length.min(max_length as u64) as usize;
+
+            let (first_index, local_blocks, archived_blocks) = self.query_blocks(
+                start,
+                length,
+                encoded_block_to_generic_block,
+                |canister_id| canister_id,
+            );
+
+            blocks.extend(
+                (first_index..)
+                    .zip(local_blocks)
+                    .map(|(id, block)| BlockWithId {
+                        id: Nat::from(id),
+                        block,
+                    }),
+            );
+            for ArchivedRange {
+                start,
+                length,
+                callback,
+            } in archived_blocks
+            {
+                archived_ranges
+                    .entry(callback)
+                    .or_default()
+                    .push(GetBlocksRequest { start, length });
+            }
+        }
+
+        GetBlocksResult {
+            log_length: Nat::from(self.blockchain.chain_length()),
+            blocks,
+            archived_blocks: archived_ranges
+                .into_iter()
+                .map(|(canister_id, args)| ArchivedBlocks {
+                    args,
+                    callback: QueryArchiveFn::new(canister_id, "icrc3_get_blocks"),
+                })
+                .collect(),
+        }
+    }
 }
@@ -104,6 +104,11 @@ fn test_get_blocks() {
     ic_icrc1_ledger_sm_tests::test_get_blocks(ledger_wasm(), encode_init_args);
 }
 
+#[test]
+fn test_icrc3_get_blocks() {
+    ic_icrc1_ledger_sm_tests::test_icrc3_get_blocks(ledger_wasm(), encode_init_args);
+}
+
 // Generate random blocks and check that their CBOR encoding complies with the CDDL spec.
 #[test]
 fn block_encoding_agrees_with_the_schema() {
@@ -147,3 +152,13 @@ fn check_fee_collector_blocks() {
 fn check_memo_max_len() {
     ic_icrc1_ledger_sm_tests::test_memo_max_len(ledger_wasm(), encode_init_args);
 }
+
+#[test]
+fn test_approve_smoke() {
+    ic_icrc1_ledger_sm_tests::test_approve_smoke(ledger_wasm(), encode_init_args);
+}
+
+#[test]
+fn test_transfer_from_smoke() {
+    ic_icrc1_ledger_sm_tests::test_transfer_from_smoke(ledger_wasm(), encode_init_args);
+}
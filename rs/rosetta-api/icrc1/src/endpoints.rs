@@ -3,7 +3,11 @@ use candid::types::number::Nat;
 use candid::CandidType;
 use ic_ledger_canister_core::ledger::TransferError as CoreTransferError;
 use icrc_ledger_types::icrc1::transfer::TransferError;
-use icrc_ledger_types::icrc3::transactions::{Burn, Mint, Transaction, Transfer};
+use icrc_ledger_types::icrc2::approve::ApproveError;
+use icrc_ledger_types::icrc2::transfer_from::TransferFromError;
+use icrc_ledger_types::icrc3::transactions::{
+    Approve, Burn, Mint, Transaction, Transfer, TransferFrom,
+};
 use serde::Deserialize;
 
 pub fn convert_transfer_error(err: CoreTransferError) -> TransferError {
@@ -25,8 +29,70 @@ pub fn convert_transfer_error(err: CoreTransferError) -> TransferError {
         LTE::TxDuplicate { duplicate_of } => TE::Duplicate {
             duplicate_of: Nat::from(duplicate_of),
         },
-        LTE::InsufficientAllowance { .. } => todo!(),
-        LTE::ExpiredApproval { .. } => todo!(),
+        LTE::InsufficientAllowance { .. }
+        | LTE::ExpiredApproval { .. }
+        | LTE::AllowanceChanged { .. } => {
+            unreachable!("bug: icrc1_transfer cannot fail with {:?}", err)
+        }
+    }
+}
+
+pub fn convert_approve_error(err: CoreTransferError) -> ApproveError {
+    use ic_ledger_canister_core::ledger::TransferError as LTE;
+    use ApproveError as AE;
+
+    match err {
+        LTE::BadFee { expected_fee } => AE::BadFee {
+            expected_fee: Nat::from(expected_fee.get_e8s()),
+        },
+        LTE::InsufficientFunds { balance } => AE::InsufficientFunds {
+            balance: Nat::from(balance.get_e8s()),
+        },
+        LTE::ExpiredApproval { ledger_time } => AE::Expired {
+            ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+        },
+        LTE::AllowanceChanged { current_allowance } => AE::AllowanceChanged {
+            current_allowance: Nat::from(current_allowance.get_e8s()),
+        },
+        LTE::TxTooOld { .. } => AE::TooOld,
+        LTE::TxCreatedInFuture { ledger_time } => AE::CreatedInFuture {
+            ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+        },
+        LTE::TxThrottled => AE::TemporarilyUnavailable,
+        LTE::TxDuplicate { duplicate_of } => AE::Duplicate {
+            duplicate_of: Nat::from(duplicate_of),
+        },
+        LTE::InsufficientAllowance { .. } => {
+            unreachable!("bug: icrc2_approve cannot fail with {:?}", err)
+        }
+    }
+}
+
+pub fn convert_transfer_from_error(err: CoreTransferError) -> TransferFromError {
+    use ic_ledger_canister_core::ledger::TransferError as LTE;
+    use TransferFromError as TFE;
+
+    match err {
+        LTE::BadFee { expected_fee } => TFE::BadFee {
+            expected_fee: Nat::from(expected_fee.get_e8s()),
+        },
+        LTE::InsufficientFunds { balance } => TFE::InsufficientFunds {
+            balance: Nat::from(balance.get_e8s()),
+        },
+        LTE::InsufficientAllowance { allowance } => TFE::InsufficientAllowance {
+            allowance: Nat::from(allowance.get_e8s()),
+        },
+        LTE::TxTooOld { .. } => TFE::TooOld,
+        LTE::TxCreatedInFuture { ledger_time } => TFE::CreatedInFuture {
+            ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+        },
+        LTE::TxThrottled => TFE::TemporarilyUnavailable,
+        LTE::TxDuplicate { duplicate_of } => TFE::Duplicate {
+            duplicate_of: Nat::from(duplicate_of),
+        },
+        LTE::ExpiredApproval { .. } | LTE::AllowanceChanged { .. } => {
+            unreachable!("bug: icrc2_transfer_from cannot fail with {:?}", err)
+        }
     }
 }
 
@@ -47,6 +113,8 @@ impl From<Block> for Transaction {
             mint: None,
             burn: None,
             transfer: None,
+            transfer_from: None,
+            approve: None,
             timestamp: b.timestamp,
         };
         let created_at_time = b.transaction.created_at_time;
@@ -89,6 +157,48 @@ impl From<Block> for Transaction {
                     memo,
                 });
             }
+            Operation::TransferFrom {
+                from,
+                to,
+                spender,
+                amount,
+                fee,
+            } => {
+                tx.kind = "transfer_from".to_string();
+                tx.transfer_from = Some(TransferFrom {
+                    from,
+                    to,
+                    spender,
+                    amount: Nat::from(amount),
+                    fee: fee
+                        .map(Nat::from)
+                        .or_else(|| b.effective_fee.map(Nat::from)),
+                    created_at_time,
+                    memo,
+                });
+            }
+            Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+                fee,
+            } => {
+                tx.kind = "approve".to_string();
+                tx.approve = Some(Approve {
+                    from,
+                    spender,
+                    amount: Nat::from(amount),
+                    expected_allowance: expected_allowance.map(Nat::from),
+                    expires_at,
+                    fee: fee
+                        .map(Nat::from)
+                        .or_else(|| b.effective_fee.map(Nat::from)),
+                    created_at_time,
+                    memo,
+                });
+            }
         }
 
         tx
@@ -4,7 +4,6 @@ pub mod endpoints;
 pub mod hash;
 
 use ciborium::tag::Required;
-use ic_base_types::PrincipalId;
 use ic_ledger_canister_core::ledger::{LedgerContext, LedgerTransaction, TxApplyError};
 pub use ic_ledger_core::tokens::Tokens;
 use ic_ledger_core::{
@@ -47,6 +46,34 @@ pub enum Operation {
         #[serde(rename = "amt")]
         amount: u64,
     },
+    #[serde(rename = "approve")]
+    Approve {
+        #[serde(with = "compact_account")]
+        from: Account,
+        #[serde(with = "compact_account")]
+        spender: Account,
+        #[serde(rename = "amt")]
+        amount: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_allowance: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fee: Option<u64>,
+    },
+    #[serde(rename = "xfer_from")]
+    TransferFrom {
+        #[serde(with = "compact_account")]
+        from: Account,
+        #[serde(with = "compact_account")]
+        to: Account,
+        #[serde(with = "compact_account")]
+        spender: Account,
+        #[serde(rename = "amt")]
+        amount: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fee: Option<u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -66,7 +93,7 @@ pub struct Transaction {
 
 impl LedgerTransaction for Transaction {
     type AccountId = Account;
-    type SpenderId = PrincipalId;
+    type SpenderId = Account;
 
     fn burn(
         from: Account,
@@ -107,11 +134,11 @@ impl LedgerTransaction for Transaction {
     fn apply<C>(
         &self,
         context: &mut C,
-        _now: TimeStamp,
+        now: TimeStamp,
         effective_fee: Tokens,
     ) -> Result<(), TxApplyError>
     where
-        C: LedgerContext<AccountId = Self::AccountId>,
+        C: LedgerContext<AccountId = Self::AccountId, SpenderId = Self::SpenderId>,
     {
         let fee_collector = context.fee_collector().map(|fc| fc.fee_collector);
         let fee_collector = fee_collector.as_ref();
@@ -134,6 +161,74 @@ impl LedgerTransaction for Transaction {
             Operation::Mint { to, amount } => {
                 context.balances_mut().mint(to, Tokens::from_e8s(*amount))?
             }
+            Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+                fee,
+            } => {
+                let fee = fee.map(Tokens::from_e8s).unwrap_or(effective_fee);
+                // Check the balance first so that a failed approval leaves
+                // both the balances and the allowances untouched.
+                let balance = context.balances().account_balance(from);
+                if balance < fee {
+                    return Err(TxApplyError::InsufficientFunds { balance });
+                }
+                context.approvals_mut().set_allowance(
+                    from,
+                    spender,
+                    Tokens::from_e8s(*amount),
+                    expected_allowance.map(Tokens::from_e8s),
+                    expires_at.map(TimeStamp::from_nanos_since_unix_epoch),
+                    now,
+                )?;
+                match fee_collector {
+                    Some(fee_collector) => context.balances_mut().transfer(
+                        from,
+                        fee_collector,
+                        fee,
+                        Tokens::ZERO,
+                        None,
+                    )?,
+                    None => context.balances_mut().burn(from, fee)?,
+                }
+            }
+            Operation::TransferFrom {
+                from,
+                to,
+                spender,
+                amount,
+                fee,
+            } => {
+                let amount = Tokens::from_e8s(*amount);
+                let fee = fee.map(Tokens::from_e8s).unwrap_or(effective_fee);
+                if from == spender {
+                    // NB. The account owner does not need an allowance to
+                    // spend their own tokens.
+                    context
+                        .balances_mut()
+                        .transfer(from, to, amount, fee, fee_collector)?;
+                    return Ok(());
+                }
+
+                // The allowance covers both the amount and the fee.
+                let spent = amount.saturating_add(fee);
+                let allowance = context.approvals().allowance(from, spender, now);
+                if allowance.amount < spent {
+                    return Err(TxApplyError::InsufficientAllowance {
+                        allowance: allowance.amount,
+                    });
+                }
+                context
+                    .balances_mut()
+                    .transfer(from, to, amount, fee, fee_collector)?;
+                context
+                    .approvals_mut()
+                    .use_allowance(from, spender, spent, now)
+                    .expect("bug: cannot use allowance");
+            }
         }
         Ok(())
     }
@@ -175,6 +270,53 @@ impl Transaction {
             memo,
         }
     }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn approve(
+        from: Account,
+        spender: Account,
+        amount: Tokens,
+        expected_allowance: Option<Tokens>,
+        expires_at: Option<TimeStamp>,
+        fee: Option<Tokens>,
+        created_at_time: Option<TimeStamp>,
+        memo: Option<Memo>,
+    ) -> Self {
+        Self {
+            operation: Operation::Approve {
+                from,
+                spender,
+                amount: amount.get_e8s(),
+                expected_allowance: expected_allowance.map(Tokens::get_e8s),
+                expires_at: expires_at.map(|t| t.as_nanos_since_unix_epoch()),
+                fee: fee.map(Tokens::get_e8s),
+            },
+            created_at_time: created_at_time.map(|t| t.as_nanos_since_unix_epoch()),
+            memo,
+        }
+    }
+
+    pub fn transfer_from(
+        from: Account,
+        to: Account,
+        spender: Account,
+        amount: Tokens,
+        fee: Option<Tokens>,
+        created_at_time: Option<TimeStamp>,
+        memo: Option<Memo>,
+    ) -> Self {
+        Self {
+            operation: Operation::TransferFrom {
+                from,
+                to,
+                spender,
+                amount: amount.get_e8s(),
+                fee: fee.map(Tokens::get_e8s),
+            },
+            created_at_time: created_at_time.map(|t| t.as_nanos_since_unix_epoch()),
+            memo,
+        }
+    }
 }
 
 impl TryFrom<icrc_ledger_types::icrc3::transactions::Transaction> for Transaction {
@@ -220,41 +362,55 @@ impl TryFrom<icrc_ledger_types::icrc3::transactions::Transaction> for Transactio
                 .0
                 .to_u64()
                 .ok_or_else(|| "Could not convert Nat to u64".to_owned())?;
-            match transfer.fee {
-                Some(fee) => {
-                    let fee = fee
-                        .0
+            let fee = transfer
+                .fee
+                .map(|fee| {
+                    fee.0
                         .to_u64()
-                        .ok_or_else(|| "Could not convert Nat to u64".to_owned())?;
-
-                    let operation = Operation::Transfer {
-                        to: transfer.to,
-                        amount,
-                        from: transfer.from,
-                        fee: Some(fee),
-                    };
-                    return Ok(Self {
-                        operation,
-                        created_at_time: transfer.created_at_time,
-                        memo: transfer.memo,
-                    });
-                }
-                None => {
-                    let operation = Operation::Transfer {
-                        to: transfer.to,
-                        amount,
-                        from: transfer.from,
-                        fee: None,
-                    };
-                    return Ok(Self {
-                        operation,
-                        created_at_time: transfer.created_at_time,
-                        memo: transfer.memo,
-                    });
-                }
-            }
+                        .ok_or_else(|| "Could not convert Nat to u64".to_owned())
+                })
+                .transpose()?;
+            let operation = match transfer.spender {
+                Some(spender) => Operation::TransferFrom {
+                    from: transfer.from,
+                    to: transfer.to,
+                    spender,
+                    amount,
+                    fee,
+                },
+                None => Operation::Transfer {
+                    from: transfer.from,
+                    to: transfer.to,
+                    amount,
+                    fee,
+                },
+            };
+            return Ok(Self {
+                operation,
+                created_at_time: transfer.created_at_time,
+                memo: transfer.memo,
+            });
+        }
+        if let Some(approve) = value.approve {
+            let to_u64 = |n: candid::Nat| {
+                n.0.to_u64()
+                    .ok_or_else(|| "Could not convert Nat to u64".to_owned())
+            };
+            let operation = Operation::Approve {
+                from: approve.from,
+                spender: approve.spender,
+                amount: to_u64(approve.amount)?,
+                expected_allowance: approve.expected_allowance.map(to_u64).transpose()?,
+                expires_at: approve.expires_at,
+                fee: approve.fee.map(to_u64).transpose()?,
+            };
+            return Ok(Self {
+                operation,
+                created_at_time: approve.created_at_time,
+                memo: approve.memo,
+            });
         }
-        Err("Transaction has neither mint, burn nor transfer operation".to_owned())
+        Err("Transaction has neither mint, burn, transfer nor approve operation".to_owned())
     }
 }
 
@@ -331,10 +487,13 @@ impl BlockType for Block {
         effective_fee: Tokens,
         fee_collector: Option<FeeCollector<Self::AccountId>>,
     ) -> Self {
-        let effective_fee = if let Operation::Transfer { fee, .. } = &transaction.operation {
-            fee.is_none().then_some(effective_fee.get_e8s())
-        } else {
-            None
+        let effective_fee = match &transaction.operation {
+            Operation::Transfer { fee, .. }
+            | Operation::Approve { fee, .. }
+            | Operation::TransferFrom { fee, .. } => {
+                fee.is_none().then_some(effective_fee.get_e8s())
+            }
+            Operation::Mint { .. } | Operation::Burn { .. } => None,
         };
         let (fee_collector, fee_collector_block_index) = match fee_collector {
             Some(FeeCollector {
@@ -174,6 +174,9 @@ pub fn transfer_args_with_sender(
                     memo: block.transaction.memo,
                     amount: amount.into(),
                 },
+                Operation::Approve { .. } | Operation::TransferFrom { .. } => {
+                    unreachable!("blocks_strategy does not generate ICRC-2 operations")
+                }
             })
             .collect()
     })
@@ -1,7 +1,9 @@
 use crate::{archive::ArchiveCanisterWasm, blockchain::Blockchain, range_utils, runtime::Runtime};
 use ic_base_types::CanisterId;
 use ic_canister_log::{log, Sink};
-use ic_ledger_core::approvals::{Approvals, ExpiredApproval, InsufficientAllowance};
+use ic_ledger_core::approvals::{
+    Approvals, ExpiredApproval, InsufficientAllowance, SetAllowanceError,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 use std::ops::Range;
@@ -26,6 +28,7 @@ pub enum TxApplyError {
     InsufficientFunds { balance: Tokens },
     InsufficientAllowance { allowance: Tokens },
     ExpiredApproval { now: TimeStamp },
+    AllowanceChanged { current_allowance: Tokens },
 }
 
 impl From<BalanceError> for TxApplyError {
@@ -48,6 +51,17 @@ impl From<ExpiredApproval> for TxApplyError {
     }
 }
 
+impl From<SetAllowanceError> for TxApplyError {
+    fn from(e: SetAllowanceError) -> Self {
+        match e {
+            SetAllowanceError::ExpiredApproval { now } => Self::ExpiredApproval { now },
+            SetAllowanceError::AllowanceChanged { current_allowance } => {
+                Self::AllowanceChanged { current_allowance }
+            }
+        }
+    }
+}
+
 pub trait LedgerContext {
     type AccountId: std::hash::Hash + Ord + Eq + Clone;
     type SpenderId;
@@ -169,6 +183,7 @@ pub enum TransferError {
     InsufficientFunds { balance: Tokens },
     InsufficientAllowance { allowance: Tokens },
     ExpiredApproval { ledger_time: TimeStamp },
+    AllowanceChanged { current_allowance: Tokens },
     TxTooOld { allowed_window_nanos: u64 },
     TxCreatedInFuture { ledger_time: TimeStamp },
     TxThrottled,
@@ -229,6 +244,9 @@ where
             TxApplyError::ExpiredApproval { now } => {
                 TransferError::ExpiredApproval { ledger_time: now }
             }
+            TxApplyError::AllowanceChanged { current_allowance } => {
+                TransferError::AllowanceChanged { current_allowance }
+            }
         })?;
 
     let fee_collector = ledger.fee_collector().cloned();
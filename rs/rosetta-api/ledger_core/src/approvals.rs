@@ -17,6 +17,18 @@ pub struct ExpiredApproval {
     pub now: TimeStamp,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetAllowanceError {
+    ExpiredApproval { now: TimeStamp },
+    AllowanceChanged { current_allowance: Tokens },
+}
+
+impl From<ExpiredApproval> for SetAllowanceError {
+    fn from(e: ExpiredApproval) -> Self {
+        Self::ExpiredApproval { now: e.now }
+    }
+}
+
 pub trait Approvals {
     type AccountId;
     type SpenderId;
@@ -39,6 +51,20 @@ pub trait Approvals {
         now: TimeStamp,
     ) -> Result<Tokens, ExpiredApproval>;
 
+    /// Sets the spender's allowance for the account to the specified amount.
+    ///
+    /// If `expected_allowance` is specified, the allowance changes only if
+    /// the current allowance is equal to the expected one.
+    fn set_allowance(
+        &mut self,
+        account: &Self::AccountId,
+        spender: &Self::SpenderId,
+        amount: Tokens,
+        expected_allowance: Option<Tokens>,
+        expires_at: Option<TimeStamp>,
+        now: TimeStamp,
+    ) -> Result<Tokens, SetAllowanceError>;
+
     /// Decreases the spender's allowance for the account by the specified amount.
     ///
     /// If the total allowance goes negative, the table resets it to zero.
@@ -169,6 +195,45 @@ where
         }
     }
 
+    fn set_allowance(
+        &mut self,
+        account: &AccountId,
+        spender: &SpenderId,
+        amount: Tokens,
+        expected_allowance: Option<Tokens>,
+        expires_at: Option<TimeStamp>,
+        now: TimeStamp,
+    ) -> Result<Tokens, SetAllowanceError> {
+        if expires_at.unwrap_or_else(remote_future) <= now {
+            return Err(ExpiredApproval { now }.into());
+        }
+
+        if let Some(expected_allowance) = expected_allowance {
+            let current_allowance = self.allowance(account, spender, now).amount;
+            if current_allowance != expected_allowance {
+                return Err(SetAllowanceError::AllowanceChanged { current_allowance });
+            }
+        }
+
+        let key = K::from((account, spender));
+
+        if amount == Tokens::ZERO {
+            self.allowances.remove(&key);
+            return Ok(Tokens::ZERO);
+        }
+
+        let old_expiration = self
+            .allowances
+            .insert(key.clone(), Allowance { amount, expires_at })
+            .and_then(|allowance| allowance.expires_at);
+        if expires_at != old_expiration {
+            if let Some(expires_at) = expires_at {
+                self.expiration_queue.push(Reverse((expires_at, key)));
+            }
+        }
+        Ok(amount)
+    }
+
     fn decrease_allowance(
         &mut self,
         account: &AccountId,
@@ -252,7 +317,6 @@ where
         for _ in 0..limit {
             match self.expiration_queue.peek() {
                 Some(Reverse((ts, _key))) => {
-                    println!("{:?}", ts);
                     if *ts > now {
                         return pruned;
                     }
@@ -220,3 +220,93 @@ fn allowance_table_pruning_obsolete_expirations() {
         }
     );
 }
+
+#[test]
+fn set_allowance_replaces_previous_allowance() {
+    let mut table = TestAllowanceTable::default();
+
+    table
+        .set_allowance(&Account(1), &Spender(1), tokens(100), None, None, ts(1))
+        .unwrap();
+    table
+        .set_allowance(
+            &Account(1),
+            &Spender(1),
+            tokens(30),
+            None,
+            Some(ts(10)),
+            ts(1),
+        )
+        .unwrap();
+
+    assert_eq!(
+        table.allowance(&Account(1), &Spender(1), ts(1)),
+        Allowance {
+            amount: tokens(30),
+            expires_at: Some(ts(10))
+        }
+    );
+
+    table
+        .set_allowance(&Account(1), &Spender(1), tokens(0), None, None, ts(1))
+        .unwrap();
+    assert_eq!(table.len(), 0);
+}
+
+#[test]
+fn set_allowance_checks_expected_allowance_and_expiration() {
+    let mut table = TestAllowanceTable::default();
+
+    table
+        .set_allowance(
+            &Account(1),
+            &Spender(1),
+            tokens(100),
+            None,
+            Some(ts(10)),
+            ts(1),
+        )
+        .unwrap();
+
+    assert_eq!(
+        table.set_allowance(
+            &Account(1),
+            &Spender(1),
+            tokens(50),
+            Some(tokens(90)),
+            None,
+            ts(1)
+        ),
+        Err(SetAllowanceError::AllowanceChanged {
+            current_allowance: tokens(100)
+        })
+    );
+
+    // The allowance has expired, so the current allowance is zero.
+    table
+        .set_allowance(
+            &Account(1),
+            &Spender(1),
+            tokens(50),
+            Some(tokens(0)),
+            None,
+            ts(20),
+        )
+        .unwrap();
+
+    assert_eq!(
+        table.set_allowance(
+            &Account(1),
+            &Spender(1),
+            tokens(50),
+            None,
+            Some(ts(5)),
+            ts(20)
+        ),
+        Err(SetAllowanceError::ExpiredApproval { now: ts(20) })
+    );
+    assert_eq!(
+        table.allowance(&Account(1), &Spender(1), ts(20)).amount,
+        tokens(50)
+    );
+}
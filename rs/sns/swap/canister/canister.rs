@@ -684,6 +684,10 @@ mod tests {
             None,
             0,
             0,
+            Default::default(),
+            None,
+            0,
+            None,
         )
     }
 
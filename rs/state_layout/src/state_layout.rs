@@ -2,6 +2,7 @@ use crate::error::LayoutError;
 use crate::utils::do_copy;
 
 use ic_base_types::{NumBytes, NumSeconds};
+use ic_ic00_types::LogVisibility;
 use ic_logger::{error, info, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
 use ic_protobuf::{
@@ -19,14 +20,17 @@ use ic_protobuf::{
 use ic_replicated_state::{
     canister_state::{
         execution_state::{NextScheduledMethod, WasmMetadata},
-        system_state::{CanisterHistory, CyclesUseCase},
+        system_state::{CanisterHistory, CyclesUseCase, OnLowWasmMemoryHookStatus},
+        wasm_chunk_store::WasmChunkHash,
+        TotalQueryStats,
     },
     CallContextManager, CanisterStatus, ExecutionTask, ExportedFunctions, Global, NumWasmPages,
 };
 use ic_sys::mmap::ScopedMmap;
 use ic_types::{
-    nominal_cycles::NominalCycles, AccumulatedPriority, CanisterId, ComputeAllocation, Cycles,
-    ExecutionRound, Height, MemoryAllocation, NumInstructions, PrincipalId,
+    canister_log::CanisterLog, nominal_cycles::NominalCycles, AccumulatedPriority, CanisterId,
+    ComputeAllocation, Cycles, ExecutionRound, Height, MemoryAllocation, NumInstructions,
+    PrincipalId, Time,
 };
 use ic_utils::fs::sync_path;
 use ic_utils::thread::parallel_map;
@@ -146,6 +150,36 @@ pub struct CanisterStateBits {
     pub canister_version: u64,
     pub consumed_cycles_since_replica_started_by_use_cases: BTreeMap<CyclesUseCase, NominalCycles>,
     pub canister_history: CanisterHistory,
+    pub canister_snapshots: CanisterSnapshotsBits,
+    pub canister_log: CanisterLog,
+    pub log_visibility: LogVisibility,
+    pub wasm_chunk_hashes: Vec<WasmChunkHash>,
+    pub total_query_stats: TotalQueryStats,
+    pub wasm_memory_limit: Option<NumBytes>,
+    pub on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
+    pub reserved_balance: Cycles,
+    pub reserved_balance_limit: Option<Cycles>,
+}
+
+/// This struct contains the bits of a canister snapshot that are not stored
+/// in separate files, i.e. everything except the Wasm module and the memories.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanisterSnapshotBits {
+    pub local_id: u64,
+    pub taken_at_timestamp: Time,
+    pub canister_version: u64,
+    pub certified_data: Vec<u8>,
+    pub binary_hash: WasmHash,
+    pub exported_globals: Vec<Global>,
+    pub wasm_memory_size: NumWasmPages,
+    pub stable_memory_size: NumWasmPages,
+}
+
+/// The bits of all snapshots of a canister.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CanisterSnapshotsBits {
+    pub snapshots: Vec<CanisterSnapshotBits>,
+    pub next_local_id: u64,
 }
 
 #[derive(Clone)]
@@ -191,9 +225,16 @@ struct CheckpointRefData {
 /// │   │   └── <hex(canister_id)>
 /// │   │       ├── canister.pbuf
 /// │   │       ├── queues.pbuf
+/// │   │       ├── snapshots
+/// │   │       │   └── <local_snapshot_id>
+/// │   │       │       ├── software.wasm
+/// │   │       │       ├── stable_memory.bin
+/// │   │       │       └── vmemory_0.bin
 /// │   │       ├── software.wasm
 /// │   │       ├── stable_memory.bin
-/// │   │       └── vmemory_0.bin
+/// │   │       ├── vmemory_0.bin
+/// │   │       └── wasm_chunk_store
+/// │   │           └── <hex(chunk_hash)>.wasm
 /// │   ├── ingress_history.pbuf
 /// │   ├── subnet_queues.pbuf
 /// │   └── system_metadata.pbuf
@@ -204,9 +245,16 @@ struct CheckpointRefData {
 /// │      │   └── <hex(canister_id)>
 /// │      │       ├── canister.pbuf
 /// │      │       ├── queues.pbuf
+/// │      │       ├── snapshots
+/// │      │       │   └── <local_snapshot_id>
+/// │      │       │       ├── software.wasm
+/// │      │       │       ├── stable_memory.bin
+/// │      │       │       └── vmemory_0.bin
 /// │      │       ├── software.wasm
 /// │      │       ├── stable_memory.bin
-/// │      │       └── vmemory_0.bin
+/// │      │       ├── vmemory_0.bin
+/// │      │       └── wasm_chunk_store
+/// │      │           └── <hex(chunk_hash)>.wasm
 /// │      ├── ingress_history.pbuf
 /// │      ├── subnet_queues.pbuf
 /// │      └── system_metadata.pbuf
@@ -1297,6 +1345,93 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
     pub fn stable_memory_blob(&self) -> PathBuf {
         self.canister_root.join("stable_memory.bin")
     }
+
+    /// Returns the local ids of all snapshots stored in this canister layout.
+    pub fn snapshot_ids(&self) -> Result<Vec<u64>, LayoutError> {
+        let snapshots_dir = self.canister_root.join("snapshots");
+        collect_subdirs(snapshots_dir.as_path(), |p| {
+            p.parse::<u64>().unwrap_or_else(|err| {
+                panic!(
+                    "Failed to convert directory name {} into a snapshot id: {}",
+                    p, err
+                )
+            })
+        })
+    }
+
+    /// Returns the hashes of all chunks stored in the Wasm chunk store
+    /// directory of this canister layout.
+    pub fn wasm_chunk_hashes(&self) -> Result<Vec<WasmChunkHash>, LayoutError> {
+        let chunk_store_dir = self.canister_root.join("wasm_chunk_store");
+        collect_subdirs(chunk_store_dir.as_path(), |p| {
+            p.strip_suffix(".wasm")
+                .and_then(|hash| hex::decode(hash).ok())
+                .and_then(|hash| WasmChunkHash::try_from(hash).ok())
+                .unwrap_or_else(|| panic!("Failed to convert file name {} into a chunk hash", p))
+        })
+    }
+
+    /// Returns the file a chunk of the Wasm chunk store is stored in.
+    pub fn wasm_chunk(&self, hash: &WasmChunkHash) -> Result<WasmFile<Permissions>, LayoutError> {
+        let chunk_store_dir = self.canister_root.join("wasm_chunk_store");
+        Permissions::check_dir(&chunk_store_dir)?;
+        Ok(chunk_store_dir
+            .join(format!("{}.wasm", hex::encode(hash)))
+            .into())
+    }
+
+    pub fn snapshot(&self, local_id: u64) -> Result<SnapshotLayout<Permissions>, LayoutError> {
+        SnapshotLayout::new(
+            self.canister_root
+                .join("snapshots")
+                .join(local_id.to_string()),
+        )
+    }
+}
+
+pub struct SnapshotLayout<Permissions: AccessPolicy> {
+    snapshot_root: PathBuf,
+    permissions_tag: PhantomData<Permissions>,
+}
+
+impl<Permissions: AccessPolicy> SnapshotLayout<Permissions> {
+    pub fn new(snapshot_root: PathBuf) -> Result<Self, LayoutError> {
+        Permissions::check_dir(&snapshot_root)?;
+        Ok(Self {
+            snapshot_root,
+            permissions_tag: PhantomData,
+        })
+    }
+
+    pub fn raw_path(&self) -> PathBuf {
+        self.snapshot_root.clone()
+    }
+
+    pub fn wasm(&self) -> WasmFile<Permissions> {
+        self.snapshot_root.join("software.wasm").into()
+    }
+
+    pub fn vmemory_0(&self) -> PathBuf {
+        self.snapshot_root.join("vmemory_0.bin")
+    }
+
+    pub fn stable_memory_blob(&self) -> PathBuf {
+        self.snapshot_root.join("stable_memory.bin")
+    }
+}
+
+impl<Permissions> SnapshotLayout<Permissions>
+where
+    Permissions: WritePolicy,
+{
+    /// Removes the snapshot directory and all files in it.
+    pub fn delete_dir(&self) -> Result<(), LayoutError> {
+        std::fs::remove_dir_all(&self.snapshot_root).map_err(|err| LayoutError::IoError {
+            path: self.snapshot_root.clone(),
+            message: "Failed to remove snapshot directory".to_string(),
+            io_err: err,
+        })
+    }
 }
 
 fn open_for_write(path: &Path) -> Result<std::fs::File, LayoutError> {
@@ -1558,6 +1693,29 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
                 })
                 .collect(),
             canister_history: Some((&item.canister_history).into()),
+            canister_snapshots: Some((&item.canister_snapshots).into()),
+            canister_log_records: item
+                .canister_log
+                .records()
+                .iter()
+                .map(|record| record.into())
+                .collect(),
+            next_canister_log_record_idx: item.canister_log.next_idx(),
+            log_visibility: pb_canister_state_bits::LogVisibility::from(item.log_visibility).into(),
+            wasm_chunk_hashes: item
+                .wasm_chunk_hashes
+                .iter()
+                .map(|hash| hash.to_vec())
+                .collect(),
+            total_query_stats: Some((&item.total_query_stats).into()),
+            wasm_memory_limit: item.wasm_memory_limit.map(|limit| limit.get()),
+            on_low_wasm_memory_hook_status:
+                pb_canister_state_bits::OnLowWasmMemoryHookStatus::from(
+                    &item.on_low_wasm_memory_hook_status,
+                )
+                .into(),
+            reserved_balance: Some(item.reserved_balance.into()),
+            reserved_balance_limit: item.reserved_balance_limit.map(|limit| limit.into()),
         }
     }
 }
@@ -1597,6 +1755,17 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             .transpose()?
             .unwrap_or_else(Cycles::zero);
 
+        let reserved_balance = value
+            .reserved_balance
+            .map(|c| c.try_into())
+            .transpose()?
+            .unwrap_or_else(Cycles::zero);
+
+        let reserved_balance_limit = value
+            .reserved_balance_limit
+            .map(|c| c.try_into())
+            .transpose()?;
+
         let task_queue = value
             .task_queue
             .into_iter()
@@ -1659,6 +1828,116 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
                 "CanisterStateBits::canister_history",
             )
             .unwrap_or_default(),
+            canister_snapshots: value
+                .canister_snapshots
+                .map(|s| s.try_into())
+                .transpose()?
+                .unwrap_or_default(),
+            canister_log: CanisterLog::new(
+                value.next_canister_log_record_idx,
+                value
+                    .canister_log_records
+                    .into_iter()
+                    .map(|record| record.into())
+                    .collect(),
+            ),
+            log_visibility: pb_canister_state_bits::LogVisibility::from_i32(value.log_visibility)
+                .map(LogVisibility::from)
+                .unwrap_or_default(),
+            wasm_chunk_hashes: value
+                .wasm_chunk_hashes
+                .into_iter()
+                .map(|hash| {
+                    WasmChunkHash::try_from(hash).map_err(|e| ProxyDecodeError::ValueOutOfRange {
+                        typ: "WasmChunkHash",
+                        err: format!("Expected a 32-byte long chunk hash, got {:?}", e),
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            total_query_stats: value
+                .total_query_stats
+                .map(TotalQueryStats::from)
+                .unwrap_or_default(),
+            wasm_memory_limit: value.wasm_memory_limit.map(NumBytes::from),
+            on_low_wasm_memory_hook_status:
+                pb_canister_state_bits::OnLowWasmMemoryHookStatus::from_i32(
+                    value.on_low_wasm_memory_hook_status,
+                )
+                .unwrap_or_default()
+                .try_into()?,
+            reserved_balance,
+            reserved_balance_limit,
+        })
+    }
+}
+
+impl From<&CanisterSnapshotBits> for pb_canister_state_bits::CanisterSnapshotBits {
+    fn from(item: &CanisterSnapshotBits) -> Self {
+        Self {
+            local_id: item.local_id,
+            taken_at_timestamp_nanos: item.taken_at_timestamp.as_nanos_since_unix_epoch(),
+            canister_version: item.canister_version,
+            certified_data: item.certified_data.clone(),
+            binary_hash: item.binary_hash.to_vec(),
+            exported_globals: item
+                .exported_globals
+                .iter()
+                .map(|global| global.into())
+                .collect(),
+            wasm_memory_size: item.wasm_memory_size.get() as u64,
+            stable_memory_size: item.stable_memory_size.get() as u64,
+        }
+    }
+}
+
+impl TryFrom<pb_canister_state_bits::CanisterSnapshotBits> for CanisterSnapshotBits {
+    type Error = ProxyDecodeError;
+    fn try_from(value: pb_canister_state_bits::CanisterSnapshotBits) -> Result<Self, Self::Error> {
+        let binary_hash: [u8; 32] =
+            value
+                .binary_hash
+                .try_into()
+                .map_err(|e| ProxyDecodeError::ValueOutOfRange {
+                    typ: "BinaryHash",
+                    err: format!("Expected a 32-byte long module hash, got {:?}", e),
+                })?;
+        let exported_globals = value
+            .exported_globals
+            .into_iter()
+            .map(|g| g.try_into())
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            local_id: value.local_id,
+            taken_at_timestamp: Time::from_nanos_since_unix_epoch(value.taken_at_timestamp_nanos),
+            canister_version: value.canister_version,
+            certified_data: value.certified_data,
+            binary_hash: binary_hash.into(),
+            exported_globals,
+            wasm_memory_size: NumWasmPages::from(value.wasm_memory_size as usize),
+            stable_memory_size: NumWasmPages::from(value.stable_memory_size as usize),
+        })
+    }
+}
+
+impl From<&CanisterSnapshotsBits> for pb_canister_state_bits::CanisterSnapshots {
+    fn from(item: &CanisterSnapshotsBits) -> Self {
+        Self {
+            snapshots: item.snapshots.iter().map(|s| s.into()).collect(),
+            next_local_id: item.next_local_id,
+        }
+    }
+}
+
+impl TryFrom<pb_canister_state_bits::CanisterSnapshots> for CanisterSnapshotsBits {
+    type Error = ProxyDecodeError;
+    fn try_from(value: pb_canister_state_bits::CanisterSnapshots) -> Result<Self, Self::Error> {
+        Ok(Self {
+            snapshots: value
+                .snapshots
+                .into_iter()
+                .map(|s| s.try_into())
+                .collect::<Result<_, _>>()?,
+            next_local_id: value.next_local_id,
         })
     }
 }
@@ -2019,6 +2298,15 @@ mod test {
             canister_version: 0,
             consumed_cycles_since_replica_started_by_use_cases: BTreeMap::new(),
             canister_history: CanisterHistory::default(),
+            canister_snapshots: CanisterSnapshotsBits::default(),
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            wasm_chunk_hashes: Vec::new(),
+            total_query_stats: TotalQueryStats::default(),
+            wasm_memory_limit: None,
+            on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus::default(),
+            reserved_balance: Cycles::zero(),
+            reserved_balance_limit: None,
         }
     }
 
@@ -2147,6 +2435,12 @@ mod test {
             CanisterChangeOrigin::from_canister(canister_test_id(123).get(), None),
             CanisterChangeDetails::controllers_change(vec![]),
         ));
+        canister_history.add_canister_change(CanisterChange::new(
+            555,
+            7,
+            CanisterChangeOrigin::from_user(user_test_id(42).get()),
+            CanisterChangeDetails::load_snapshot(3, vec![1, 2, 3], 222),
+        ));
 
         // A canister state with non-empty history.
         let canister_state_bits = CanisterStateBits {
@@ -2160,6 +2454,125 @@ mod test {
         assert_eq!(canister_state_bits.canister_history, canister_history);
     }
 
+    #[test]
+    fn test_encode_decode_canister_snapshots() {
+        let canister_snapshots = CanisterSnapshotsBits {
+            snapshots: vec![CanisterSnapshotBits {
+                local_id: 3,
+                taken_at_timestamp: mock_time(),
+                canister_version: 7,
+                certified_data: vec![1, 2, 3],
+                binary_hash: [4; 32].into(),
+                exported_globals: vec![Global::I32(1), Global::F64(2.0)],
+                wasm_memory_size: NumWasmPages::from(10),
+                stable_memory_size: NumWasmPages::from(20),
+            }],
+            next_local_id: 4,
+        };
+
+        // A canister state with a snapshot.
+        let canister_state_bits = CanisterStateBits {
+            canister_snapshots: canister_snapshots.clone(),
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.canister_snapshots, canister_snapshots);
+    }
+
+    #[test]
+    fn test_encode_decode_canister_log() {
+        let mut canister_log = CanisterLog::new(10, vec![]);
+        canister_log.add_record(100, b"hello".to_vec());
+        canister_log.add_record(200, b"world".to_vec());
+
+        let canister_state_bits = CanisterStateBits {
+            canister_log: canister_log.clone(),
+            log_visibility: LogVisibility::Public,
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.canister_log, canister_log);
+        assert_eq!(canister_state_bits.log_visibility, LogVisibility::Public);
+    }
+
+    #[test]
+    fn test_encode_decode_wasm_chunk_hashes() {
+        let wasm_chunk_hashes = vec![[1; 32], [2; 32]];
+        let canister_state_bits = CanisterStateBits {
+            wasm_chunk_hashes: wasm_chunk_hashes.clone(),
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.wasm_chunk_hashes, wasm_chunk_hashes);
+    }
+
+    #[test]
+    fn test_encode_decode_total_query_stats() {
+        let total_query_stats = TotalQueryStats {
+            num_calls: 13,
+            num_instructions: 5_000_000,
+            ingress_payload_size: 1_024,
+            egress_payload_size: 4_096,
+        };
+        let canister_state_bits = CanisterStateBits {
+            total_query_stats: total_query_stats.clone(),
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.total_query_stats, total_query_stats);
+    }
+
+    #[test]
+    fn test_encode_decode_wasm_memory_limit() {
+        let canister_state_bits = CanisterStateBits {
+            wasm_memory_limit: Some(NumBytes::from(1 << 30)),
+            on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus::Executed,
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(
+            canister_state_bits.wasm_memory_limit,
+            Some(NumBytes::from(1 << 30))
+        );
+        assert_eq!(
+            canister_state_bits.on_low_wasm_memory_hook_status,
+            OnLowWasmMemoryHookStatus::Executed
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_reserved_balance() {
+        let canister_state_bits = CanisterStateBits {
+            reserved_balance: Cycles::new(1_000_000),
+            reserved_balance_limit: Some(Cycles::new(5_000_000)),
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.reserved_balance, Cycles::new(1_000_000));
+        assert_eq!(
+            canister_state_bits.reserved_balance_limit,
+            Some(Cycles::new(5_000_000))
+        );
+    }
+
     #[test]
     fn test_encode_decode_task_queue() {
         let ingress = Arc::new(IngressBuilder::new().method_name("test_ingress").build());
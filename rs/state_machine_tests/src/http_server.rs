@@ -0,0 +1,642 @@
+//! An HTTP front end for a [`StateMachine`] that serves the public
+//! `/api/v2` endpoints of the replica, so that standard agents can talk to
+//! an in-process replica.
+//!
+//! Responses are certified with the subnet key of the state machine, which
+//! is also the root key reported by `/api/v2/status`. The state machine does
+//! not execute rounds on its own: a call is executed when it is submitted,
+//! and the `/control` endpoints let the client tick the state machine and
+//! control its time. Note that agents compute the expiry of a call from
+//! their local clock, so the client needs to set the time of the state
+//! machine close to its local clock before making calls.
+//!
+//! Signatures and the authorization of `read_state` paths are not checked.
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use ic_crypto::threshold_sig_public_key_to_der;
+use ic_error_types::{ErrorCode, RejectCode};
+use ic_state_machine_tests::{
+    CanisterId, IngressState, IngressStatus, PayloadBuilder, StateMachine, WasmResult,
+};
+use ic_types::messages::{
+    Blob, HttpCallContent, HttpQueryContent, HttpQueryResponse, HttpQueryResponseReply,
+    HttpReadStateContent, HttpReadStateResponse, HttpRequest, HttpRequestEnvelope,
+    HttpStatusResponse, ReadState, ReplicaHealthStatus, SignedIngress, SignedRequestBytes,
+    UserQuery, IC_API_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, TcpListener};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, oneshot};
+
+/// The maximum number of rounds executed to complete a submitted call.
+const MAX_TICKS_PER_CALL: usize = 100;
+
+const CONTENT_TYPE_CBOR: &str = "application/cbor";
+
+type Job = Box<dyn FnOnce(&StateMachine) + Send>;
+
+/// Forwards requests to the thread that owns the state machine. The state
+/// machine is not thread-safe, so all requests are executed sequentially on
+/// that thread.
+#[derive(Clone)]
+struct AppState {
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl AppState {
+    /// Runs `f` on the state machine thread and returns its result, or an
+    /// internal server error if the state machine failed to run it.
+    async fn run<R, F>(&self, f: F) -> Result<R, Response>
+    where
+        R: Send + 'static,
+        F: FnOnce(&StateMachine) -> R + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |env| {
+                let _ = tx.send(f(env));
+            }))
+            .map_err(|_| {
+                plaintext_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "The state machine is not running.".to_string(),
+                )
+            })?;
+        rx.await.map_err(|_| {
+            plaintext_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "The state machine failed to process the request.".to_string(),
+            )
+        })
+    }
+}
+
+/// The time of the state machine in nanoseconds since the Unix epoch.
+#[derive(Deserialize, Serialize)]
+struct RawTime {
+    nanos_since_epoch: u64,
+}
+
+/// The amount of time to advance the state machine by.
+#[derive(Deserialize)]
+struct RawDuration {
+    nanos: u64,
+}
+
+/// The number of rounds to execute.
+#[derive(Deserialize)]
+struct RawTicks {
+    ticks: usize,
+}
+
+/// Starts a state machine built by `make_env` and serves it on the given
+/// port until the process is terminated. The port that the server listens on
+/// is written to `port_file` if it is specified, which is useful when `port`
+/// is 0.
+pub fn run<F>(port: u16, port_file: Option<PathBuf>, make_env: F)
+where
+    F: FnOnce() -> StateMachine + Send + 'static,
+{
+    let (jobs_tx, mut jobs_rx) = mpsc::unbounded_channel::<Job>();
+    std::thread::spawn(move || {
+        let env = make_env();
+        while let Some(job) = jobs_rx.blocking_recv() {
+            // A request that panics is answered with an error, and must not
+            // stop the state machine from serving the following requests.
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(|| job(&env)));
+        }
+    });
+
+    let app = Router::new()
+        .route("/api/v2/status", get(status))
+        .route("/api/v2/canister/:effective_canister_id/call", post(call))
+        .route("/api/v2/canister/:effective_canister_id/query", post(query))
+        .route(
+            "/api/v2/canister/:effective_canister_id/read_state",
+            post(read_state),
+        )
+        .route("/control/tick", post(tick))
+        .route("/control/time", get(time))
+        .route("/control/set_time", post(set_time))
+        .route("/control/advance_time", post(advance_time))
+        .with_state(AppState { jobs: jobs_tx });
+
+    let tcp_listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))
+        .expect("failed to bind the HTTP port");
+    if let Some(port_file) = port_file {
+        let port = tcp_listener.local_addr().unwrap().port();
+        std::fs::write(port_file, port.to_string()).expect("failed to write the port file");
+    }
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to create a tokio runtime");
+    runtime.block_on(async move {
+        axum::Server::from_tcp(tcp_listener)
+            .expect("failed to start the HTTP server")
+            .serve(app.into_make_service())
+            .await
+            .expect("the HTTP server failed")
+    });
+}
+
+async fn status(State(state): State<AppState>) -> Response {
+    let root_key = match state.run(|env| env.root_key()).await {
+        Ok(root_key) => root_key,
+        Err(res) => return res,
+    };
+    cbor_response(&HttpStatusResponse {
+        ic_api_version: IC_API_VERSION.to_string(),
+        root_key: Some(Blob(threshold_sig_public_key_to_der(root_key).unwrap())),
+        impl_version: None,
+        impl_hash: None,
+        replica_health_status: Some(ReplicaHealthStatus::Healthy),
+        certified_height: None,
+    })
+}
+
+/// Submits the call and executes rounds until the call completes, so that
+/// the result is available to the first `read_state` request of the agent.
+async fn call(
+    State(state): State<AppState>,
+    Path(effective_canister_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    let effective_canister_id = match parse_canister_id(&effective_canister_id) {
+        Ok(canister_id) => canister_id,
+        Err(res) => return res,
+    };
+    let envelope = match <HttpRequestEnvelope<HttpCallContent>>::try_from(
+        &SignedRequestBytes::from(body.to_vec()),
+    ) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Could not parse body as call request: {}", e),
+            )
+        }
+    };
+    let msg = match SignedIngress::try_from(envelope) {
+        Ok(msg) => msg,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Malformed request: {:?}", e),
+            )
+        }
+    };
+    let canister_id = msg.canister_id();
+    if canister_id != CanisterId::ic_00() && canister_id != effective_canister_id {
+        return plaintext_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Specified CanisterId {} does not match effective canister id in URL {}",
+                canister_id, effective_canister_id
+            ),
+        );
+    }
+
+    let result = state
+        .run(move |env| {
+            let msg_id = msg.id();
+            env.execute_payload(PayloadBuilder::new().signed_ingress(msg));
+            for _ in 0..MAX_TICKS_PER_CALL {
+                match env.ingress_status(&msg_id) {
+                    IngressStatus::Known {
+                        state: IngressState::Received | IngressState::Processing,
+                        ..
+                    } => env.tick(),
+                    // The call completed, or it was not inducted, e.g.,
+                    // because it expired.
+                    _ => break,
+                }
+            }
+        })
+        .await;
+    match result {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(res) => res,
+    }
+}
+
+async fn query(
+    State(state): State<AppState>,
+    Path(effective_canister_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    let effective_canister_id = match parse_canister_id(&effective_canister_id) {
+        Ok(canister_id) => canister_id,
+        Err(res) => return res,
+    };
+    let envelope = match <HttpRequestEnvelope<HttpQueryContent>>::try_from(
+        &SignedRequestBytes::from(body.to_vec()),
+    ) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Could not parse body as read request: {}", e),
+            )
+        }
+    };
+    let request = match HttpRequest::<UserQuery>::try_from(envelope) {
+        Ok(request) => request,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Malformed request: {:?}", e),
+            )
+        }
+    };
+    let query = request.content().clone();
+    if query.receiver != effective_canister_id {
+        return plaintext_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Specified CanisterId {} does not match effective canister id in URL {}",
+                query.receiver, effective_canister_id
+            ),
+        );
+    }
+
+    let result = match state
+        .run(move |env| {
+            env.query_as(
+                query.source.get(),
+                query.receiver,
+                query.method_name,
+                query.method_payload,
+            )
+        })
+        .await
+    {
+        Ok(result) => result,
+        Err(res) => return res,
+    };
+    let response = match result {
+        Ok(WasmResult::Reply(arg)) => HttpQueryResponse::Replied {
+            reply: HttpQueryResponseReply { arg: Blob(arg) },
+        },
+        Ok(WasmResult::Reject(message)) => HttpQueryResponse::Rejected {
+            error_code: ErrorCode::CanisterRejectedMessage.to_string(),
+            reject_code: RejectCode::CanisterReject as u64,
+            reject_message: message,
+        },
+        Err(user_error) => HttpQueryResponse::Rejected {
+            error_code: user_error.code().to_string(),
+            reject_code: user_error.reject_code() as u64,
+            reject_message: user_error.to_string(),
+        },
+    };
+    cbor_response(&response)
+}
+
+async fn read_state(
+    State(state): State<AppState>,
+    Path(effective_canister_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    if let Err(res) = parse_canister_id(&effective_canister_id) {
+        return res;
+    }
+    let envelope = match <HttpRequestEnvelope<HttpReadStateContent>>::try_from(
+        &SignedRequestBytes::from(body.to_vec()),
+    ) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Could not parse body as read request: {}", e),
+            )
+        }
+    };
+    let request = match HttpRequest::<ReadState>::try_from(envelope) {
+        Ok(request) => request,
+        Err(e) => {
+            return plaintext_response(
+                StatusCode::BAD_REQUEST,
+                format!("Malformed request: {:?}", e),
+            )
+        }
+    };
+    let paths = request.content().paths.clone();
+
+    match state.run(move |env| env.read_state(paths)).await {
+        Ok(Some(certificate)) => cbor_response(&HttpReadStateResponse {
+            certificate: Blob(into_cbor(&certificate)),
+        }),
+        Ok(None) => plaintext_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Certified state is not available yet. Please try again...".to_string(),
+        ),
+        Err(res) => res,
+    }
+}
+
+/// Executes the given number of rounds, one if the body is empty.
+async fn tick(State(state): State<AppState>, body: Bytes) -> Response {
+    let ticks = if body.is_empty() {
+        1
+    } else {
+        match serde_json::from_slice::<RawTicks>(&body) {
+            Ok(raw) => raw.ticks,
+            Err(e) => {
+                return plaintext_response(
+                    StatusCode::BAD_REQUEST,
+                    format!("Could not parse body as ticks: {}", e),
+                )
+            }
+        }
+    };
+    match state
+        .run(move |env| {
+            for _ in 0..ticks {
+                env.tick();
+            }
+        })
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(res) => res,
+    }
+}
+
+async fn time(State(state): State<AppState>) -> Response {
+    match state.run(|env| env.time()).await {
+        Ok(time) => Json(RawTime {
+            nanos_since_epoch: time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+        })
+        .into_response(),
+        Err(res) => res,
+    }
+}
+
+async fn set_time(State(state): State<AppState>, Json(raw): Json<RawTime>) -> Response {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_nanos(raw.nanos_since_epoch);
+    match state.run(move |env| env.set_time(time)).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(res) => res,
+    }
+}
+
+async fn advance_time(State(state): State<AppState>, Json(raw): Json<RawDuration>) -> Response {
+    match state
+        .run(move |env| env.advance_time(Duration::from_nanos(raw.nanos)))
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(res) => res,
+    }
+}
+
+fn parse_canister_id(canister_id: &str) -> Result<CanisterId, Response> {
+    CanisterId::from_str(canister_id).map_err(|e| {
+        plaintext_response(
+            StatusCode::BAD_REQUEST,
+            format!("Malformed effective canister id {}: {}", canister_id, e),
+        )
+    })
+}
+
+fn plaintext_response(status: StatusCode, message: String) -> Response {
+    (status, message).into_response()
+}
+
+fn cbor_response<R: Serialize>(r: &R) -> Response {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE_CBOR)], into_cbor(r)).into_response()
+}
+
+/// Converts an object into self-describing CBOR, as the replica does.
+fn into_cbor<R: Serialize>(r: &R) -> Vec<u8> {
+    let mut ser = serde_cbor::Serializer::new(Vec::new());
+    ser.self_describe().expect("Could not write magic tag.");
+    r.serialize(&mut ser).expect("Serialization failed.");
+    ser.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_crypto_tree_hash::{Label, LookupStatus, MixedHashTree};
+    use ic_types::messages::{HttpCanisterUpdate, HttpReadState, HttpUserQuery};
+    use ic_types::PrincipalId;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    const HELLO_WAT: &str = r#"
+        (module
+          (import "ic0" "msg_reply_data_append"
+            (func $msg_reply_data_append (param i32 i32)))
+          (import "ic0" "msg_reply" (func $msg_reply))
+          (func $hello
+            (call $msg_reply_data_append (i32.const 0) (i32.const 5))
+            (call $msg_reply))
+          (memory 1)
+          (data (i32.const 0) "hello")
+          (export "canister_update hello" (func $hello))
+          (export "canister_query hello_query" (func $hello)))"#;
+
+    /// Starts the HTTP server on a fresh state machine with a canister that
+    /// replies "hello" and returns the port, the canister ID, the root key
+    /// and the time of the state machine.
+    fn start_server() -> (
+        u16,
+        CanisterId,
+        ic_state_machine_tests::ThresholdSigPublicKey,
+        SystemTime,
+    ) {
+        let port_file = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let server_port_file = port_file.to_path_buf();
+        std::thread::spawn(move || {
+            run(0, Some(server_port_file), move || {
+                let env = StateMachine::new();
+                let canister_id = env.install_canister_wat(HELLO_WAT, vec![], None);
+                tx.send((canister_id, env.root_key(), env.time())).unwrap();
+                env
+            })
+        });
+        let (canister_id, root_key, time) = rx.recv().expect("the state machine did not start");
+
+        for _ in 0..600 {
+            if let Ok(port) = std::fs::read_to_string(&port_file)
+                .unwrap_or_default()
+                .trim()
+                .parse::<u16>()
+            {
+                return (port, canister_id, root_key, time);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("the HTTP server did not start");
+    }
+
+    /// Sends a minimal HTTP/1.1 request and returns the status code and the
+    /// body of the response.
+    fn http_request(port: u16, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            path,
+            CONTENT_TYPE_CBOR,
+            body.len(),
+        )
+        .expect("failed to send request");
+        stream.write_all(body).expect("failed to send request");
+        let mut response = vec![];
+        stream
+            .read_to_end(&mut response)
+            .expect("failed to read response");
+        let head_end = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("malformed response");
+        let head = String::from_utf8_lossy(&response[..head_end]).to_string();
+        let status = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .expect("malformed status line");
+        (status, response[head_end + 4..].to_vec())
+    }
+
+    fn envelope<C: Serialize>(content: C) -> Vec<u8> {
+        into_cbor(&HttpRequestEnvelope {
+            content,
+            sender_pubkey: None,
+            sender_sig: None,
+            sender_delegation: None,
+        })
+    }
+
+    fn expiry(time: SystemTime) -> u64 {
+        (time + Duration::from_secs(60))
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    }
+
+    #[test]
+    fn http_server_serves_calls_queries_and_certified_state() {
+        let (port, canister_id, root_key, time) = start_server();
+        let sender = Blob(PrincipalId::new_anonymous().to_vec());
+
+        let (status, body) = http_request(port, "GET", "/api/v2/status", &[]);
+        assert_eq!(status, 200);
+        let status_response: HttpStatusResponse = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(
+            status_response.root_key,
+            Some(Blob(threshold_sig_public_key_to_der(root_key).unwrap()))
+        );
+
+        let update = HttpCanisterUpdate {
+            canister_id: Blob(canister_id.get().to_vec()),
+            method_name: "hello".to_string(),
+            arg: Blob(vec![]),
+            sender: sender.clone(),
+            ingress_expiry: expiry(time),
+            nonce: None,
+        };
+        let message_id = update.id();
+        let (status, _) = http_request(
+            port,
+            "POST",
+            &format!("/api/v2/canister/{}/call", canister_id),
+            &envelope(HttpCallContent::Call { update }),
+        );
+        assert_eq!(status, 202);
+
+        let (status, body) = http_request(
+            port,
+            "POST",
+            &format!("/api/v2/canister/{}/query", canister_id),
+            &envelope(HttpQueryContent::Query {
+                query: HttpUserQuery {
+                    canister_id: Blob(canister_id.get().to_vec()),
+                    method_name: "hello_query".to_string(),
+                    arg: Blob(vec![]),
+                    sender: sender.clone(),
+                    ingress_expiry: expiry(time),
+                    nonce: None,
+                },
+            }),
+        );
+        assert_eq!(status, 200);
+        let query_response: HttpQueryResponse = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(
+            query_response,
+            HttpQueryResponse::Replied {
+                reply: HttpQueryResponseReply {
+                    arg: Blob(b"hello".to_vec())
+                }
+            }
+        );
+
+        let reply_path = vec![
+            Label::from("request_status"),
+            Label::from(message_id.as_bytes()),
+            Label::from("reply"),
+        ];
+        let (status, body) = http_request(
+            port,
+            "POST",
+            &format!("/api/v2/canister/{}/read_state", canister_id),
+            &envelope(HttpReadStateContent::ReadState {
+                read_state: HttpReadState {
+                    sender,
+                    paths: vec![reply_path.clone().into()],
+                    nonce: None,
+                    ingress_expiry: expiry(time),
+                },
+            }),
+        );
+        assert_eq!(status, 200);
+        let read_state_response: HttpReadStateResponse = serde_cbor::from_slice(&body).unwrap();
+        let certificate = ic_certification::verify_certificate(
+            &read_state_response.certificate,
+            &canister_id,
+            &root_key,
+        )
+        .expect("the certificate must be signed with the root key");
+        assert_eq!(
+            certificate
+                .tree
+                .lookup(&reply_path.iter().map(Label::as_bytes).collect::<Vec<_>>()),
+            LookupStatus::Found(&MixedHashTree::Leaf(b"hello".to_vec()))
+        );
+    }
+
+    #[test]
+    fn http_server_rejects_malformed_requests() {
+        let (port, canister_id, _, _) = start_server();
+
+        let (status, _) = http_request(
+            port,
+            "POST",
+            &format!("/api/v2/canister/{}/read_state", canister_id),
+            b"not cbor",
+        );
+        assert_eq!(status, 400);
+
+        let (status, _) = http_request(port, "POST", "/api/v2/canister/not-a-canister/call", &[]);
+        assert_eq!(status, 400);
+
+        // The server keeps serving requests after rejecting malformed ones.
+        let (status, _) = http_request(port, "GET", "/api/v2/status", &[]);
+        assert_eq!(status, 200);
+    }
+}
@@ -9,12 +9,18 @@ use ic_crypto_internal_threshold_sig_bls12381::api::{
     combine_signatures, combined_public_key, generate_threshold_key, sign_message,
 };
 use ic_crypto_internal_threshold_sig_bls12381::types::SecretKeyBytes;
+use ic_crypto_internal_threshold_sig_ecdsa::{DerivationPath, EccCurveType, EccPoint, EccScalar};
 use ic_crypto_internal_types::sign::threshold_sig::public_key::CspThresholdSigPublicKey;
-use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
+use ic_crypto_tree_hash::{
+    flatmap, sparse_labeled_tree_from_paths, Label, LabeledTree, LabeledTree::SubTree,
+};
 use ic_cycles_account_manager::CyclesAccountManager;
-pub use ic_error_types::{ErrorCode, UserError};
+pub use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_execution_environment::ExecutionServices;
-use ic_ic00_types::{self as ic00, CanisterIdRecord, InstallCodeArgs, Method, Payload};
+use ic_ic00_types::{
+    self as ic00, CanisterIdRecord, InstallCodeArgs, Method, Payload, SignWithECDSAReply,
+    TransformArgs,
+};
 pub use ic_ic00_types::{
     CanisterHttpResponsePayload, CanisterInstallMode, CanisterSettingsArgs, EcdsaKeyId, HttpHeader,
     HttpMethod, UpdateSettingsArgs,
@@ -37,6 +43,7 @@ use ic_protobuf::registry::{
     provisional_whitelist::v1::ProvisionalWhitelist as PbProvisionalWhitelist,
     routing_table::v1::CanisterMigrations as PbCanisterMigrations,
     routing_table::v1::RoutingTable as PbRoutingTable,
+    subnet::v1::SubnetListRecord,
 };
 use ic_protobuf::types::v1::PrincipalId as PrincipalIdIdProto;
 use ic_protobuf::types::v1::SubnetId as SubnetIdProto;
@@ -44,7 +51,8 @@ use ic_registry_client_fake::FakeRegistryClient;
 use ic_registry_client_helpers::subnet::SubnetListRegistry;
 use ic_registry_keys::{
     make_canister_migrations_record_key, make_ecdsa_signing_subnet_list_key, make_node_record_key,
-    make_provisional_whitelist_record_key, make_routing_table_record_key, ROOT_SUBNET_ID_KEY,
+    make_provisional_whitelist_record_key, make_routing_table_record_key,
+    make_subnet_list_record_key, make_subnet_record_key, ROOT_SUBNET_ID_KEY,
 };
 use ic_registry_proto_data_provider::ProtoRegistryDataProvider;
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
@@ -63,19 +71,18 @@ use ic_replicated_state::{
 use ic_state_layout::{CheckpointLayout, RwPolicy};
 use ic_state_manager::StateManagerImpl;
 use ic_test_utilities_metrics::{fetch_histogram_stats, fetch_int_counter};
-use ic_test_utilities_registry::{
-    add_subnet_record, insert_initial_dkg_transcript, SubnetRecordBuilder,
-};
+use ic_test_utilities_registry::{insert_initial_dkg_transcript, SubnetRecordBuilder};
 pub use ic_types::canister_http::CanisterHttpRequestContext;
+use ic_types::canister_http::MAX_CANISTER_HTTP_RESPONSE_BYTES;
 use ic_types::consensus::certification::CertificationContent;
 use ic_types::crypto::threshold_sig::ni_dkg::{NiDkgId, NiDkgTag, NiDkgTargetSubnet};
 pub use ic_types::crypto::threshold_sig::ThresholdSigPublicKey;
 use ic_types::crypto::{
-    canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId, CombinedThresholdSig,
-    CombinedThresholdSigOf, Signable, Signed,
+    canister_threshold_sig::{ExtendedDerivationPath, MasterEcdsaPublicKey},
+    AlgorithmId, CombinedThresholdSig, CombinedThresholdSigOf, Signable, Signed,
 };
 use ic_types::malicious_flags::MaliciousFlags;
-use ic_types::messages::{CallbackId, Certificate, Response};
+use ic_types::messages::{CallbackId, Certificate, RejectContext, Response, NO_DEADLINE};
 use ic_types::signature::ThresholdSignature;
 use ic_types::time::GENESIS;
 use ic_types::{
@@ -103,7 +110,10 @@ use std::str::FromStr;
 use std::string::ToString;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 use std::{fmt, io};
 use tempfile::TempDir;
 use tokio::runtime::Runtime;
@@ -121,16 +131,31 @@ impl Verifier for FakeVerifier {
     }
 }
 
-/// Constructs the initial version of the registry containing a subnet with the
-/// specified SUBNET_ID, with the nodes with the specified NODE_IDs.
-fn make_nodes_registry(
-    nns_subnet_id: SubnetId,
+/// Describes a subnet in the initial version of the registry.
+struct SubnetRegistrySpec {
     subnet_id: SubnetId,
     subnet_type: SubnetType,
-    mut routing_table: RoutingTable,
-    node_ids: &[NodeId],
-    ecdsa_keys: &[EcdsaKeyId],
+    node_ids: Vec<NodeId>,
+    ecdsa_keys: Vec<EcdsaKeyId>,
     features: SubnetFeatures,
+}
+
+/// Returns the node ids of a subnet with the given size. The ids are offset by
+/// the index of the subnet, so that subnets sharing a registry have distinct
+/// nodes.
+fn make_node_ids(subnet_index: u64, subnet_size: usize) -> Vec<NodeId> {
+    (0..subnet_size as u64)
+        .map(|id| NodeId::from(PrincipalId::new_node_test_id((subnet_index << 32) + id)))
+        .collect()
+}
+
+/// Constructs the initial version of the registry containing the specified
+/// subnets. If the routing table is empty, each subnet is assigned a range of
+/// canister ids in the order in which the subnets are specified.
+fn make_nodes_registry(
+    nns_subnet_id: SubnetId,
+    mut routing_table: RoutingTable,
+    subnets: &[SubnetRegistrySpec],
 ) -> (Arc<ProtoRegistryDataProvider>, Arc<FakeRegistryClient>) {
     let registry_version = RegistryVersion::from(1);
     let data_provider = Arc::new(ProtoRegistryDataProvider::new());
@@ -150,26 +175,33 @@ fn make_nodes_registry(
 
     // ECDSA subnet_id must be different from nns_subnet_id, otherwise
     // `sign_with_ecdsa` won't be charged.
-    let subnet_id_proto = SubnetIdProto {
-        principal_id: Some(PrincipalIdIdProto {
-            raw: subnet_id.get_ref().to_vec(),
-        }),
-    };
-    for key_id in ecdsa_keys {
-        let id = make_ecdsa_signing_subnet_list_key(key_id);
+    let mut ecdsa_signing_subnets: BTreeMap<&EcdsaKeyId, Vec<SubnetIdProto>> = BTreeMap::new();
+    for subnet in subnets {
+        for key_id in &subnet.ecdsa_keys {
+            ecdsa_signing_subnets
+                .entry(key_id)
+                .or_default()
+                .push(SubnetIdProto {
+                    principal_id: Some(PrincipalIdIdProto {
+                        raw: subnet.subnet_id.get_ref().to_vec(),
+                    }),
+                });
+        }
+    }
+    for (key_id, subnets) in ecdsa_signing_subnets {
         data_provider
             .add(
-                &id.clone(),
+                &make_ecdsa_signing_subnet_list_key(key_id),
                 registry_version,
-                Some(EcdsaSigningSubnetList {
-                    subnets: vec![subnet_id_proto.clone()],
-                }),
+                Some(EcdsaSigningSubnetList { subnets }),
             )
             .unwrap();
     }
 
     if routing_table.is_empty() {
-        routing_table_insert_subnet(&mut routing_table, subnet_id).unwrap();
+        for subnet in subnets {
+            routing_table_insert_subnet(&mut routing_table, subnet.subnet_id).unwrap();
+        }
     }
     let pb_routing_table = PbRoutingTable::from(routing_table);
     data_provider
@@ -188,42 +220,69 @@ fn make_nodes_registry(
         )
         .unwrap();
 
-    for node_id in node_ids {
-        let node_record = NodeRecord {
-            node_operator_id: vec![0],
-            xnet: None,
-            http: Some(ConnectionEndpoint {
-                ip_addr: "2a00:fb01:400:42:5000:22ff:fe5e:e3c4".into(),
-                port: 1234,
-                protocol: 0,
-            }),
-            p2p_flow_endpoints: vec![],
-            chip_id: vec![],
-        };
+    for subnet in subnets {
+        for node_id in &subnet.node_ids {
+            let node_record = NodeRecord {
+                node_operator_id: vec![0],
+                xnet: None,
+                http: Some(ConnectionEndpoint {
+                    ip_addr: "2a00:fb01:400:42:5000:22ff:fe5e:e3c4".into(),
+                    port: 1234,
+                    protocol: 0,
+                }),
+                p2p_flow_endpoints: vec![],
+                chip_id: vec![],
+            };
+            data_provider
+                .add(
+                    &make_node_record_key(*node_id),
+                    registry_version,
+                    Some(node_record),
+                )
+                .unwrap();
+        }
+
+        let record = SubnetRecordBuilder::from(&subnet.node_ids[..])
+            .with_subnet_type(subnet.subnet_type)
+            .with_ecdsa_config(EcdsaConfig {
+                quadruples_to_create_in_advance: 1,
+                key_ids: subnet.ecdsa_keys.clone(),
+                max_queue_size: Some(DEFAULT_ECDSA_MAX_QUEUE_SIZE),
+                signature_request_timeout_ns: None,
+                idkg_key_rotation_period_ms: None,
+            })
+            .with_features(subnet.features.into())
+            .build();
+
+        insert_initial_dkg_transcript(
+            registry_version.get(),
+            subnet.subnet_id,
+            &record,
+            &data_provider,
+        );
         data_provider
             .add(
-                &make_node_record_key(*node_id),
+                &make_subnet_record_key(subnet.subnet_id),
                 registry_version,
-                Some(node_record),
+                Some(record),
             )
             .unwrap();
     }
 
     // Set subnetwork list(needed for filling network_topology.nns_subnet_id)
-    let record = SubnetRecordBuilder::from(node_ids)
-        .with_subnet_type(subnet_type)
-        .with_ecdsa_config(EcdsaConfig {
-            quadruples_to_create_in_advance: 1,
-            key_ids: ecdsa_keys.to_vec(),
-            max_queue_size: Some(DEFAULT_ECDSA_MAX_QUEUE_SIZE),
-            signature_request_timeout_ns: None,
-            idkg_key_rotation_period_ms: None,
-        })
-        .with_features(features.into())
-        .build();
-
-    insert_initial_dkg_transcript(registry_version.get(), subnet_id, &record, &data_provider);
-    add_subnet_record(&data_provider, registry_version.get(), subnet_id, record);
+    let subnet_list_record = SubnetListRecord {
+        subnets: subnets
+            .iter()
+            .map(|subnet| subnet.subnet_id.get().into_vec())
+            .collect(),
+    };
+    data_provider
+        .add(
+            &make_subnet_list_record_key(),
+            registry_version,
+            Some(subnet_list_record),
+        )
+        .unwrap();
 
     let registry_client = Arc::new(FakeRegistryClient::new(Arc::clone(&data_provider) as _));
     registry_client.update_to_latest_version();
@@ -274,6 +333,14 @@ impl StateMachineConfig {
     }
 }
 
+/// Produces the response to an HTTP outcall of a canister. The transform
+/// function of the request, if any, is applied to the returned response before
+/// it is delivered to the canister. Returning an error rejects the outcall.
+pub type HttpOutcallHandler = Box<
+    dyn Fn(&CanisterHttpRequestContext) -> Result<CanisterHttpResponsePayload, (RejectCode, String)>
+        + Send,
+>;
+
 /// Represents a replicated state machine detached from the network layer that
 /// can be used to test this part of the stack in isolation.
 pub struct StateMachine {
@@ -293,6 +360,9 @@ pub struct StateMachine {
     nonce: std::cell::Cell<u64>,
     time: std::cell::Cell<Time>,
     ecdsa_subnet_public_keys: BTreeMap<EcdsaKeyId, MasterEcdsaPublicKey>,
+    ecdsa_subnet_secret_keys: BTreeMap<EcdsaKeyId, EccScalar>,
+    ecdsa_signing_enabled: std::cell::Cell<bool>,
+    http_outcall_handler: std::cell::RefCell<Option<HttpOutcallHandler>>,
 }
 
 impl Default for StateMachine {
@@ -324,6 +394,8 @@ pub struct StateMachineBuilder {
     use_cost_scaling_flag: bool,
     ecdsa_keys: Vec<EcdsaKeyId>,
     features: SubnetFeatures,
+    ecdsa_signing_enabled: bool,
+    registry: Option<(Arc<ProtoRegistryDataProvider>, Arc<FakeRegistryClient>)>,
 }
 
 impl StateMachineBuilder {
@@ -346,6 +418,8 @@ impl StateMachineBuilder {
                 http_requests: true,
                 ..SubnetFeatures::default()
             },
+            ecdsa_signing_enabled: false,
+            registry: None,
         }
     }
 
@@ -421,6 +495,28 @@ impl StateMachineBuilder {
         Self { features, ..self }
     }
 
+    /// If the argument is true, the state machine signs the pending
+    /// `sign_with_ecdsa` requests in every round, see
+    /// [`StateMachine::set_ecdsa_signing_enabled`].
+    pub fn with_ecdsa_signing_enabled(self, ecdsa_signing_enabled: bool) -> Self {
+        Self {
+            ecdsa_signing_enabled,
+            ..self
+        }
+    }
+
+    /// Uses the specified registry instead of creating one for this subnet.
+    fn with_registry(
+        self,
+        registry_data_provider: Arc<ProtoRegistryDataProvider>,
+        registry_client: Arc<FakeRegistryClient>,
+    ) -> Self {
+        Self {
+            registry: Some((registry_data_provider, registry_client)),
+            ..self
+        }
+    }
+
     pub fn build(self) -> StateMachine {
         StateMachine::setup_from_dir(
             self.state_dir,
@@ -436,6 +532,8 @@ impl StateMachineBuilder {
             self.use_cost_scaling_flag,
             self.ecdsa_keys,
             self.features,
+            self.ecdsa_signing_enabled,
+            self.registry,
         )
     }
 }
@@ -476,14 +574,11 @@ impl StateMachine {
         use_cost_scaling_flag: bool,
         ecdsa_keys: Vec<EcdsaKeyId>,
         features: SubnetFeatures,
+        ecdsa_signing_enabled: bool,
+        registry: Option<(Arc<ProtoRegistryDataProvider>, Arc<FakeRegistryClient>)>,
     ) -> Self {
         let replica_logger = replica_logger();
 
-        let mut node_ids = vec![];
-        for id in 0..subnet_size {
-            let node_id = NodeId::from(PrincipalId::new_node_test_id(id as u64));
-            node_ids.push(node_id);
-        }
         let metrics_registry = MetricsRegistry::new();
 
         let (subnet_config, mut hypervisor_config) = match config {
@@ -494,15 +589,19 @@ impl StateMachine {
             ),
         };
 
-        let (registry_data_provider, registry_client) = make_nodes_registry(
-            nns_subnet_id,
-            subnet_id,
-            subnet_type,
-            routing_table,
-            &node_ids,
-            &ecdsa_keys,
-            features,
-        );
+        let (registry_data_provider, registry_client) = registry.unwrap_or_else(|| {
+            make_nodes_registry(
+                nns_subnet_id,
+                routing_table,
+                &[SubnetRegistrySpec {
+                    subnet_id,
+                    subnet_type,
+                    node_ids: make_node_ids(0, subnet_size),
+                    ecdsa_keys: ecdsa_keys.clone(),
+                    features,
+                }],
+            )
+        });
 
         let sm_config = ic_config::state_manager::Config::new(state_dir.path().to_path_buf());
 
@@ -586,14 +685,22 @@ impl StateMachine {
         ));
 
         let mut ecdsa_subnet_public_keys = BTreeMap::new();
+        let mut ecdsa_subnet_secret_keys = BTreeMap::new();
         for ecdsa_key in ecdsa_keys {
+            // The master key is derived from the key id, so that the keys of
+            // canisters are stable across test runs.
+            let secret_key = EccScalar::from_seed(
+                EccCurveType::K256,
+                Seed::from_bytes(ecdsa_key.to_string().as_bytes()),
+            );
             ecdsa_subnet_public_keys.insert(
-                ecdsa_key,
+                ecdsa_key.clone(),
                 MasterEcdsaPublicKey {
                     algorithm_id: AlgorithmId::EcdsaSecp256k1,
-                    public_key: b"master_ecdsa_public_key".to_vec(),
+                    public_key: EccPoint::mul_by_g(&secret_key).unwrap().serialize(),
                 },
             );
+            ecdsa_subnet_secret_keys.insert(ecdsa_key, secret_key);
         }
 
         Self {
@@ -615,6 +722,9 @@ impl StateMachine {
             nonce: std::cell::Cell::new(nonce),
             time: std::cell::Cell::new(time),
             ecdsa_subnet_public_keys,
+            ecdsa_subnet_secret_keys,
+            ecdsa_signing_enabled: std::cell::Cell::new(ecdsa_signing_enabled),
+            http_outcall_handler: std::cell::RefCell::new(None),
         }
     }
 
@@ -738,6 +848,7 @@ impl StateMachine {
 
     /// Triggers a single round of execution with block payload as an input.
     pub fn execute_payload(&self, payload: PayloadBuilder) {
+        let payload = self.add_mock_consensus_responses(payload);
         let batch_number = self.message_routing.expected_batch_height();
 
         let mut seed = [0u8; 32];
@@ -751,6 +862,7 @@ impl StateMachine {
                 signed_ingress_msgs: payload.ingress_messages,
                 certified_stream_slices: payload.xnet_payload.stream_slices,
                 bitcoin_adapter_responses: vec![],
+                query_stats: None,
             },
             randomness: Randomness::from(seed),
             ecdsa_subnet_public_keys: self.ecdsa_subnet_public_keys.clone(),
@@ -1288,12 +1400,7 @@ impl StateMachine {
         method: impl ToString,
         method_payload: Vec<u8>,
     ) -> Result<WasmResult, UserError> {
-        if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
-            let state_hashes = self.state_manager.list_state_hashes_to_certify();
-            let (height, hash) = state_hashes.last().unwrap();
-            self.state_manager
-                .deliver_state_certification(self.certify_hash(height, hash));
-        }
+        self.certify_latest_state();
 
         let path = SubTree(flatmap! {
             Label::from("canister") => SubTree(
@@ -1324,6 +1431,35 @@ impl StateMachine {
         )
     }
 
+    /// Reads the given paths from the latest state and returns a certificate
+    /// for them, signed with the subnet key of the state machine. The `time`
+    /// path is always included, as in the replica.
+    ///
+    /// Returns `None` if the paths cannot be read from the certified state.
+    pub fn read_state(&self, mut paths: Vec<ic_crypto_tree_hash::Path>) -> Option<Certificate> {
+        self.certify_latest_state();
+
+        paths.push(ic_crypto_tree_hash::Path::from(Label::from("time")));
+        let labeled_tree = sparse_labeled_tree_from_paths(&mut paths);
+        let (_state, tree, certification) =
+            self.state_manager.read_certified_state(&labeled_tree)?;
+        Some(Certificate {
+            tree,
+            signature: Blob(certification.signed.signature.signature.get().0),
+            delegation: None,
+        })
+    }
+
+    /// Certifies the latest state if it is not certified yet.
+    fn certify_latest_state(&self) {
+        if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
+            let state_hashes = self.state_manager.list_state_hashes_to_certify();
+            let (height, hash) = state_hashes.last().unwrap();
+            self.state_manager
+                .deliver_state_certification(self.certify_hash(height, hash));
+        }
+    }
+
     fn certify_hash(&self, height: &Height, hash: &CryptoHashOfPartialState) -> Certification {
         let signature_bytes = Some(
             sign_message(
@@ -1710,6 +1846,504 @@ impl StateMachine {
             .canister_http_request_contexts
             .clone()
     }
+
+    /// If the argument is true, the state machine signs the pending
+    /// `sign_with_ecdsa` requests at the start of every round, as the threshold
+    /// ECDSA protocol would. See [`StateMachine::sign_with_ecdsa`].
+    pub fn set_ecdsa_signing_enabled(&self, enabled: bool) {
+        self.ecdsa_signing_enabled.set(enabled)
+    }
+
+    /// Sets the handler that responds to the pending HTTP outcalls at the start
+    /// of every round. See [`HttpOutcallHandler`].
+    pub fn set_http_outcall_handler<F>(&self, handler: F)
+    where
+        F: Fn(
+                &CanisterHttpRequestContext,
+            ) -> Result<CanisterHttpResponsePayload, (RejectCode, String)>
+            + Send
+            + 'static,
+    {
+        *self.http_outcall_handler.borrow_mut() = Some(Box::new(handler));
+    }
+
+    /// Removes the HTTP outcall handler, so that the test responds to the
+    /// pending HTTP outcalls.
+    pub fn clear_http_outcall_handler(&self) {
+        *self.http_outcall_handler.borrow_mut() = None;
+    }
+
+    /// Signs the message hash of the specified context.
+    ///
+    /// The signing key is derived from the master key of the requested key id
+    /// with the derivation path of the caller, as in the threshold ECDSA
+    /// protocol, so the signature verifies against the public key that the
+    /// `ecdsa_public_key` method returns to the caller.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the state machine has no such key id.
+    pub fn sign_with_ecdsa(&self, context: &SignWithEcdsaContext) -> SignWithECDSAReply {
+        let master_secret_key = self
+            .ecdsa_subnet_secret_keys
+            .get(&context.key_id)
+            .unwrap_or_else(|| panic!("unknown ECDSA key id {}", context.key_id));
+        let derivation_path = DerivationPath::from(&ExtendedDerivationPath {
+            caller: context.request.sender.get(),
+            derivation_path: context.derivation_path.clone(),
+        });
+        let (key_tweak, _chain_code) = derivation_path
+            .derive_tweak(&EccPoint::mul_by_g(master_secret_key).unwrap())
+            .expect("failed to derive the key");
+        let secret_key = master_secret_key.add(&key_tweak).unwrap();
+
+        // The nonce is derived from the key and the request, so that the
+        // signatures are deterministic.
+        let mut nonce_seed = secret_key.serialize();
+        nonce_seed.extend_from_slice(&context.message_hash);
+        nonce_seed.extend_from_slice(&context.pseudo_random_id);
+        let nonce = EccScalar::from_seed(EccCurveType::K256, Seed::from_bytes(&nonce_seed));
+
+        let r = EccScalar::from_bytes_wide(
+            EccCurveType::K256,
+            &EccPoint::mul_by_g(&nonce)
+                .and_then(|point| point.affine_x())
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+        let e = EccScalar::from_bytes_wide(EccCurveType::K256, &context.message_hash).unwrap();
+        let s = nonce
+            .invert()
+            .expect("the nonce is zero")
+            .mul(&r.mul(&secret_key).and_then(|rd| rd.add(&e)).unwrap())
+            .unwrap();
+        // The signatures of the IC have a normalized `s`.
+        let s = if s.is_high() { s.negate() } else { s };
+
+        let mut signature = r.serialize();
+        signature.extend(s.serialize());
+        SignWithECDSAReply { signature }
+    }
+
+    /// Adds responses to the pending `sign_with_ecdsa` requests and HTTP
+    /// outcalls that the payload does not respond to yet, if the respective
+    /// responders are enabled.
+    fn add_mock_consensus_responses(&self, mut payload: PayloadBuilder) -> PayloadBuilder {
+        let http_outcall_handler = self.http_outcall_handler.borrow();
+        if !self.ecdsa_signing_enabled.get() && http_outcall_handler.is_none() {
+            return payload;
+        }
+
+        let state = self.get_latest_state();
+        let contexts = &state.metadata.subnet_call_context_manager;
+        let responded: BTreeSet<CallbackId> = payload
+            .consensus_responses
+            .iter()
+            .map(|response| response.originator_reply_callback)
+            .collect();
+
+        if self.ecdsa_signing_enabled.get() {
+            for (callback_id, context) in contexts.sign_with_ecdsa_contexts.iter() {
+                if !responded.contains(callback_id) {
+                    payload = payload.consensus_response(
+                        *callback_id,
+                        MsgPayload::Data(self.sign_with_ecdsa(context).encode()),
+                    );
+                }
+            }
+        }
+
+        if let Some(handler) = http_outcall_handler.as_ref() {
+            for (callback_id, context) in contexts.canister_http_request_contexts.iter() {
+                if responded.contains(callback_id) {
+                    continue;
+                }
+                let response_payload = match handler(context)
+                    .and_then(|response| self.transform_http_response(context, response))
+                {
+                    Ok(response) => MsgPayload::Data(response),
+                    Err((code, message)) => MsgPayload::Reject(RejectContext::new(code, message)),
+                };
+                payload = payload.consensus_response(*callback_id, response_payload);
+            }
+        }
+        payload
+    }
+
+    /// Applies the transform function of the request, if any, to the response
+    /// and returns the encoded result, enforcing the same response size limits
+    /// as the HTTP outcalls adapter and client.
+    fn transform_http_response(
+        &self,
+        context: &CanisterHttpRequestContext,
+        response: CanisterHttpResponsePayload,
+    ) -> Result<Vec<u8>, (RejectCode, String)> {
+        let max_response_bytes = context
+            .max_response_bytes
+            .map(|max_response_bytes| max_response_bytes.get())
+            .unwrap_or(MAX_CANISTER_HTTP_RESPONSE_BYTES);
+        let response_bytes = response.body.len()
+            + response
+                .headers
+                .iter()
+                .map(|header| header.name.len() + header.value.len())
+                .sum::<usize>();
+        if response_bytes as u64 > max_response_bytes {
+            return Err((
+                RejectCode::SysFatal,
+                format!(
+                    "Http response exceeds the specified response size limit {}",
+                    max_response_bytes
+                ),
+            ));
+        }
+
+        let transformed_response = match &context.transform {
+            Some(transform) => {
+                let args = TransformArgs {
+                    response,
+                    context: transform.context.clone(),
+                };
+                match self.query_as(
+                    CanisterId::ic_00().get(),
+                    context.request.sender,
+                    &transform.method_name,
+                    args.encode(),
+                ) {
+                    Ok(WasmResult::Reply(reply)) => reply,
+                    Ok(WasmResult::Reject(message)) => {
+                        return Err((RejectCode::CanisterReject, message))
+                    }
+                    Err(err) => return Err((err.reject_code(), err.description().to_string())),
+                }
+            }
+            None => response.encode(),
+        };
+
+        if transformed_response.len() as u64 > MAX_CANISTER_HTTP_RESPONSE_BYTES {
+            let message = match context.transform {
+                Some(_) => format!(
+                    "Transformed http response exceeds limit: {}",
+                    MAX_CANISTER_HTTP_RESPONSE_BYTES
+                ),
+                None => format!(
+                    "Http response exceeds limit: {}. Apply a transform function to the http response.",
+                    MAX_CANISTER_HTTP_RESPONSE_BYTES
+                ),
+            };
+            return Err((RejectCode::SysFatal, message));
+        }
+        Ok(transformed_response)
+    }
+}
+
+/// Builds a [`StateMachineEnv`] with one state machine per subnet.
+pub struct StateMachineEnvBuilder {
+    nns_subnet_id: Option<SubnetId>,
+    subnets: Vec<StateMachineBuilder>,
+}
+
+impl StateMachineEnvBuilder {
+    pub fn new() -> Self {
+        Self {
+            nns_subnet_id: None,
+            subnets: Vec::new(),
+        }
+    }
+
+    /// Sets the id of the NNS subnet, which defaults to the id of the first
+    /// subnet.
+    pub fn with_nns_subnet_id(self, nns_subnet_id: SubnetId) -> Self {
+        Self {
+            nns_subnet_id: Some(nns_subnet_id),
+            ..self
+        }
+    }
+
+    /// Adds a subnet configured by the specified builder.
+    ///
+    /// The NNS subnet id and the routing table of the builder are ignored:
+    /// all subnets share the registry of the environment, in which each subnet
+    /// is assigned a range of canister ids in the order the subnets are added.
+    pub fn with_subnet(mut self, subnet: StateMachineBuilder) -> Self {
+        self.subnets.push(subnet);
+        self
+    }
+
+    /// # Panics
+    ///
+    /// This function panics if no subnet was added or if two subnets have the
+    /// same id.
+    pub fn build(self) -> StateMachineEnv {
+        assert!(
+            !self.subnets.is_empty(),
+            "a state machine environment needs at least one subnet"
+        );
+        let mut subnet_ids = BTreeSet::new();
+        for subnet in &self.subnets {
+            assert!(
+                subnet_ids.insert(subnet.subnet_id),
+                "duplicate subnet id {}",
+                subnet.subnet_id
+            );
+        }
+
+        let nns_subnet_id = self.nns_subnet_id.unwrap_or(self.subnets[0].subnet_id);
+        let specs: Vec<_> = self
+            .subnets
+            .iter()
+            .enumerate()
+            .map(|(index, subnet)| SubnetRegistrySpec {
+                subnet_id: subnet.subnet_id,
+                subnet_type: subnet.subnet_type,
+                node_ids: make_node_ids(index as u64, subnet.subnet_size),
+                ecdsa_keys: subnet.ecdsa_keys.clone(),
+                features: subnet.features,
+            })
+            .collect();
+        let (registry_data_provider, registry_client) =
+            make_nodes_registry(nns_subnet_id, RoutingTable::new(), &specs);
+
+        let subnets = self
+            .subnets
+            .into_iter()
+            .map(|subnet| {
+                let env = subnet
+                    .with_nns_subnet_id(nns_subnet_id)
+                    .with_registry(
+                        Arc::clone(&registry_data_provider),
+                        Arc::clone(&registry_client),
+                    )
+                    .build();
+                (env.get_subnet_id(), env)
+            })
+            .collect();
+        StateMachineEnv {
+            registry_client,
+            subnets,
+        }
+    }
+}
+
+impl Default for StateMachineEnvBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Represents several subnets that share a registry and exchange XNet
+/// messages, so that multi-subnet flows can be tested in a single process.
+///
+/// Each round, every subnet first inducts the messages and signals that the
+/// other subnets have for it, just like the XNet payload builder does on a
+/// real subnet. Canisters are migrated between subnets by updating the shared
+/// registry with [`StateMachineEnv::prepare_canister_migrations`] and
+/// [`StateMachineEnv::reroute_canister_range`], moving the canister with
+/// [`StateMachineEnv::move_canister_state`], and finally calling
+/// [`StateMachineEnv::complete_canister_migrations`].
+pub struct StateMachineEnv {
+    registry_client: Arc<FakeRegistryClient>,
+    subnets: BTreeMap<SubnetId, StateMachine>,
+}
+
+impl fmt::Debug for StateMachineEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateMachineEnv")
+            .field("subnets", &self.subnets)
+            .finish()
+    }
+}
+
+impl StateMachineEnv {
+    /// Returns the state machine of the specified subnet.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the environment does not contain the subnet.
+    pub fn subnet(&self, subnet_id: SubnetId) -> &StateMachine {
+        self.subnets
+            .get(&subnet_id)
+            .unwrap_or_else(|| panic!("unknown subnet {}", subnet_id))
+    }
+
+    /// Returns the ids of all subnets of the environment.
+    pub fn subnet_ids(&self) -> Vec<SubnetId> {
+        self.subnets.keys().cloned().collect()
+    }
+
+    /// Returns the state machine of the subnet that the routing table assigns
+    /// the specified canister to, if that subnet is part of the environment.
+    pub fn route(&self, canister_id: CanisterId) -> Option<&StateMachine> {
+        use ic_registry_client_helpers::routing_table::RoutingTableRegistry;
+
+        let routing_table = self
+            .registry_client
+            .get_routing_table(self.registry_client.get_latest_version())
+            .expect("malformed routing table")
+            .expect("missing routing table");
+        routing_table
+            .route(canister_id.get())
+            .and_then(|subnet_id| self.subnets.get(&subnet_id))
+    }
+
+    /// Executes a round on every subnet, inducting the XNet messages destined
+    /// to each subnet.
+    pub fn tick(&self) {
+        for (subnet_id, env) in self.subnets.iter() {
+            env.execute_block_with_xnet_payload(self.xnet_payload_for(*subnet_id));
+        }
+    }
+
+    /// Makes all subnets tick until there are no more messages in the system,
+    /// including messages in streams between subnets of the environment.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the environment did not process all messages
+    /// within the `max_ticks` iterations.
+    pub fn run_until_completion(&self, max_ticks: usize) {
+        for _tick in 0..max_ticks {
+            if self.reached_completion() {
+                return;
+            }
+            self.tick();
+        }
+        if !self.reached_completion() {
+            panic!(
+                "The state machine environment did not reach completion after {} ticks",
+                max_ticks
+            );
+        }
+    }
+
+    /// Sets the time of all subnets.
+    pub fn set_time(&self, time: SystemTime) {
+        for env in self.subnets.values() {
+            env.set_time(time);
+        }
+    }
+
+    /// Advances the time of all subnets by the given amount.
+    pub fn advance_time(&self, amount: Duration) {
+        for env in self.subnets.values() {
+            env.advance_time(amount);
+        }
+    }
+
+    /// Marks canisters in the specified range as being migrated from the
+    /// source subnet to the destination subnet.
+    pub fn prepare_canister_migrations(
+        &self,
+        canister_range: std::ops::RangeInclusive<CanisterId>,
+        source: SubnetId,
+        destination: SubnetId,
+    ) {
+        // All subnets share the registry, so updating it through any subnet
+        // updates it for all of them.
+        self.any_subnet()
+            .prepare_canister_migrations(canister_range, source, destination);
+    }
+
+    /// Updates the routing table so that a range of canisters is assigned to
+    /// the specified destination subnet.
+    pub fn reroute_canister_range(
+        &self,
+        canister_range: std::ops::RangeInclusive<CanisterId>,
+        destination: SubnetId,
+    ) {
+        self.any_subnet()
+            .reroute_canister_range(canister_range, destination);
+    }
+
+    /// Marks canisters in the specified range as successfully migrated along
+    /// the specified trace of subnets.
+    pub fn complete_canister_migrations(
+        &self,
+        canister_range: std::ops::RangeInclusive<CanisterId>,
+        migration_trace: Vec<SubnetId>,
+    ) {
+        self.any_subnet()
+            .complete_canister_migrations(canister_range, migration_trace);
+    }
+
+    /// Moves the state of the specified canister from the source subnet to
+    /// the destination subnet.
+    pub fn move_canister_state(
+        &self,
+        canister_id: CanisterId,
+        source: SubnetId,
+        destination: SubnetId,
+    ) -> Result<(), String> {
+        self.subnet(source)
+            .move_canister_state_to(self.subnet(destination), canister_id)
+    }
+
+    fn any_subnet(&self) -> &StateMachine {
+        self.subnets.values().next().unwrap()
+    }
+
+    /// Builds an XNet payload with the certified stream slices that the other
+    /// subnets have for the specified subnet. Each slice starts at the first
+    /// message that the subnet has not inducted yet.
+    fn xnet_payload_for(&self, subnet_id: SubnetId) -> XNetPayload {
+        let state = self.subnet(subnet_id).get_latest_state();
+        let mut stream_slices = BTreeMap::new();
+        for (remote_subnet_id, remote_env) in self.subnets.iter() {
+            if *remote_subnet_id == subnet_id
+                || remote_env
+                    .get_latest_state()
+                    .get_stream(&subnet_id)
+                    .is_none()
+            {
+                continue;
+            }
+            let begin = state
+                .get_stream(remote_subnet_id)
+                .map(|reverse_stream| reverse_stream.signals_end());
+            let payload = remote_env
+                .generate_xnet_payload(subnet_id, begin, begin, None, None)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "failed to encode the stream from {} to {}: {:?}",
+                        remote_subnet_id, subnet_id, e
+                    )
+                });
+            stream_slices.extend(payload.stream_slices);
+        }
+        XNetPayload { stream_slices }
+    }
+
+    /// Returns true if no canister has messages to process and all messages
+    /// in streams between subnets of the environment have been inducted.
+    fn reached_completion(&self) -> bool {
+        self.subnets.iter().all(|(subnet_id, env)| {
+            let state = env.get_latest_state();
+            let streams_inducted = state
+                .subnets_with_available_streams()
+                .into_iter()
+                .filter_map(|remote_subnet_id| {
+                    self.subnets
+                        .get(&remote_subnet_id)
+                        .map(|remote_env| (remote_subnet_id, remote_env))
+                })
+                .all(|(remote_subnet_id, remote_env)| {
+                    let messages_end = state.get_stream(&remote_subnet_id).unwrap().messages_end();
+                    let signals_end = remote_env
+                        .get_latest_state()
+                        .get_stream(subnet_id)
+                        .map(|reverse_stream| reverse_stream.signals_end())
+                        .unwrap_or_default();
+                    signals_end >= messages_end
+                });
+            streams_inducted
+                && !state
+                    .canisters_iter()
+                    .any(|canister| canister.has_input() || canister.has_output())
+                && !state.subnet_queues().has_input()
+                && !state.subnet_queues().has_output()
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -1787,18 +2421,29 @@ impl PayloadBuilder {
         self
     }
 
+    /// Adds an ingress message that was signed elsewhere, e.g., by an agent.
+    pub fn signed_ingress(mut self, msg: SignedIngress) -> Self {
+        self.ingress_messages.push(msg);
+        self
+    }
+
     pub fn xnet_payload(mut self, xnet_payload: XNetPayload) -> Self {
         self.xnet_payload = xnet_payload;
         self
     }
 
-    pub fn http_response(mut self, id: CallbackId, payload: &CanisterHttpResponsePayload) -> Self {
+    pub fn http_response(self, id: CallbackId, payload: &CanisterHttpResponsePayload) -> Self {
+        self.consensus_response(id, MsgPayload::Data(payload.encode()))
+    }
+
+    fn consensus_response(mut self, id: CallbackId, response_payload: MsgPayload) -> Self {
         self.consensus_responses.push(Response {
             originator: CanisterId::ic_00(),
             respondent: CanisterId::ic_00(),
             originator_reply_callback: id,
             refund: Cycles::zero(),
-            response_payload: MsgPayload::Data(payload.encode()),
+            response_payload,
+            deadline: NO_DEADLINE,
         });
         self
     }
@@ -7,6 +7,9 @@ use ic_test_state_machine_client::{CanisterCall, RawCanisterId, Request, Request
 use ic_types::{CanisterId, Cycles, PrincipalId};
 use serde::Serialize;
 use std::io::{stdin, stdout, Read, Write};
+use std::path::PathBuf;
+
+mod http_server;
 
 macro_rules! debug_print {
     ($opts:expr, $msg:expr $(,$args:expr)* $(,)*) => {
@@ -53,6 +56,15 @@ struct Opts {
     /// Prints additional debug information to stderr (to not interfere with data sent over stdin/stdout).
     #[clap(short, long)]
     debug: bool,
+
+    /// Serves the public HTTP API of the replica on the given port instead of
+    /// reading requests from stdin. Use 0 to pick a free port.
+    #[clap(long)]
+    http_port: Option<u16>,
+
+    /// Writes the port that the HTTP server listens on to the given file.
+    #[clap(long, requires = "http-port")]
+    port_file: Option<PathBuf>,
 }
 
 fn main() {
@@ -62,6 +74,12 @@ fn main() {
         ..Default::default()
     };
     let config = StateMachineConfig::new(SubnetConfig::default_system_subnet(), hypervisor_config);
+    if let Some(http_port) = opts.http_port {
+        http_server::run(http_port, opts.port_file, move || {
+            StateMachineBuilder::new().with_config(Some(config)).build()
+        });
+        return;
+    }
     let env = StateMachineBuilder::new().with_config(Some(config)).build();
     loop {
         debug_print!(&opts, "enter request loop");
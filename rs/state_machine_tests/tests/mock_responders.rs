@@ -0,0 +1,251 @@
+use ic_crypto_ecdsa_secp256k1::PublicKey;
+use ic_crypto_sha::Sha256;
+use ic_ic00_types::{
+    CanisterHttpRequestArgs, CanisterHttpResponsePayload, DerivationPath, ECDSAPublicKeyArgs,
+    ECDSAPublicKeyResponse, EcdsaCurve, EcdsaKeyId, HttpMethod, Method, Payload, SignWithECDSAArgs,
+    SignWithECDSAReply, TransformContext, TransformFunc, IC_00,
+};
+use ic_state_machine_tests::{
+    CanisterId, RejectCode, StateMachine, StateMachineBuilder, WasmResult,
+};
+use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
+
+/// A canister whose `fetch` method forwards its argument to the
+/// `http_request` method of the management canister and replies with the
+/// response, and whose `transform` method replies with `TRANSFORMED` followed
+/// by the principal of its caller.
+const HTTP_OUTCALL_WAT: &str = r#"
+(module
+  (import "ic0" "msg_arg_data_size" (func $msg_arg_data_size (result i32)))
+  (import "ic0" "msg_arg_data_copy" (func $msg_arg_data_copy (param i32 i32 i32)))
+  (import "ic0" "msg_reply" (func $msg_reply))
+  (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "msg_reject" (func $msg_reject (param i32 i32)))
+  (import "ic0" "msg_reject_msg_size" (func $msg_reject_msg_size (result i32)))
+  (import "ic0" "msg_reject_msg_copy" (func $msg_reject_msg_copy (param i32 i32 i32)))
+  (import "ic0" "msg_caller_size" (func $msg_caller_size (result i32)))
+  (import "ic0" "msg_caller_copy" (func $msg_caller_copy (param i32 i32 i32)))
+  (import "ic0" "call_new"
+    (func $call_new (param i32 i32 i32 i32 i32 i32 i32 i32)))
+  (import "ic0" "call_data_append" (func $call_data_append (param i32 i32)))
+  (import "ic0" "call_perform" (func $call_perform (result i32)))
+
+  (func $fetch
+    (call $msg_arg_data_copy (i32.const 1000) (i32.const 0) (call $msg_arg_data_size))
+    (call $call_new
+      (i32.const 0) (i32.const 0)
+      (i32.const 100) (i32.const 12)
+      (i32.const 0) (i32.const 0)
+      (i32.const 1) (i32.const 0))
+    (call $call_data_append (i32.const 1000) (call $msg_arg_data_size))
+    (drop (call $call_perform)))
+
+  (func $on_reply
+    (call $msg_arg_data_copy (i32.const 1000) (i32.const 0) (call $msg_arg_data_size))
+    (call $msg_reply_data_append (i32.const 1000) (call $msg_arg_data_size))
+    (call $msg_reply))
+
+  (func $on_reject
+    (call $msg_reject_msg_copy (i32.const 1000) (i32.const 0) (call $msg_reject_msg_size))
+    (call $msg_reject (i32.const 1000) (call $msg_reject_msg_size)))
+
+  (func $transform
+    (call $msg_reply_data_append (i32.const 200) (i32.const 11))
+    (call $msg_caller_copy (i32.const 300) (i32.const 0) (call $msg_caller_size))
+    (call $msg_reply_data_append (i32.const 300) (call $msg_caller_size))
+    (call $msg_reply))
+
+  (table funcref (elem $on_reply $on_reject))
+  (memory $memory 1)
+  (data (i32.const 100) "http_request")
+  (data (i32.const 200) "TRANSFORMED")
+  (export "memory" (memory $memory))
+  (export "canister_update fetch" (func $fetch))
+  (export "canister_query transform" (func $transform)))
+"#;
+
+const URL: &str = "https://example.com/";
+
+fn call_ic00(
+    env: &StateMachine,
+    canister_id: CanisterId,
+    method: Method,
+    args: Vec<u8>,
+) -> Vec<u8> {
+    let payload = wasm()
+        .call_simple(IC_00, method, call_args().other_side(args))
+        .build();
+    match env.execute_ingress(canister_id, "update", payload).unwrap() {
+        WasmResult::Reply(bytes) => bytes,
+        WasmResult::Reject(message) => panic!("call to {} was rejected: {}", method, message),
+    }
+}
+
+fn http_request_args(canister_id: CanisterId, transform: bool) -> CanisterHttpRequestArgs {
+    CanisterHttpRequestArgs {
+        url: URL.to_string(),
+        max_response_bytes: None,
+        headers: vec![],
+        body: None,
+        method: HttpMethod::GET,
+        transform: transform.then(|| TransformContext {
+            function: TransformFunc(candid::Func {
+                principal: canister_id.get().0,
+                method: "transform".to_string(),
+            }),
+            context: vec![],
+        }),
+    }
+}
+
+#[test]
+fn sign_with_ecdsa_replies_with_signature_of_derived_key() {
+    let key_id = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256k1,
+        name: "test_key".to_string(),
+    };
+    let env = StateMachineBuilder::new()
+        .with_ecdsa_key(key_id.clone())
+        .with_ecdsa_signing_enabled(true)
+        .build();
+    let canister_id = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.to_vec(), vec![], None)
+        .unwrap();
+    let derivation_path = DerivationPath::new(vec![b"account".to_vec()]);
+
+    let public_key = ECDSAPublicKeyResponse::decode(&call_ic00(
+        &env,
+        canister_id,
+        Method::ECDSAPublicKey,
+        ECDSAPublicKeyArgs {
+            canister_id: None,
+            derivation_path: derivation_path.clone(),
+            key_id: key_id.clone(),
+        }
+        .encode(),
+    ))
+    .unwrap();
+
+    let message = b"transfer 1 BTC";
+    let reply = SignWithECDSAReply::decode(&call_ic00(
+        &env,
+        canister_id,
+        Method::SignWithECDSA,
+        SignWithECDSAArgs {
+            message_hash: Sha256::hash(message),
+            derivation_path,
+            key_id,
+        }
+        .encode(),
+    ))
+    .unwrap();
+
+    let public_key = PublicKey::deserialize_sec1(&public_key.public_key).unwrap();
+    assert!(public_key.verify_signature(message, &reply.signature));
+    assert!(env.sign_with_ecdsa_contexts().is_empty());
+}
+
+#[test]
+fn http_outcall_handler_responds_to_outcalls() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(HTTP_OUTCALL_WAT, vec![], None);
+    env.set_http_outcall_handler(|context| {
+        Ok(CanisterHttpResponsePayload {
+            status: 200,
+            headers: vec![],
+            body: context.url.as_bytes().to_vec(),
+        })
+    });
+
+    let reply = env
+        .execute_ingress(
+            canister_id,
+            "fetch",
+            http_request_args(canister_id, false).encode(),
+        )
+        .unwrap();
+    assert_eq!(
+        reply,
+        WasmResult::Reply(
+            CanisterHttpResponsePayload {
+                status: 200,
+                headers: vec![],
+                body: URL.as_bytes().to_vec(),
+            }
+            .encode()
+        )
+    );
+    assert!(env.canister_http_request_contexts().is_empty());
+}
+
+#[test]
+fn http_outcall_handler_response_is_transformed() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(HTTP_OUTCALL_WAT, vec![], None);
+    env.set_http_outcall_handler(|_context| {
+        Ok(CanisterHttpResponsePayload {
+            status: 200,
+            headers: vec![],
+            body: b"volatile".to_vec(),
+        })
+    });
+
+    let reply = env
+        .execute_ingress(
+            canister_id,
+            "fetch",
+            http_request_args(canister_id, true).encode(),
+        )
+        .unwrap();
+    // The transform function is called by the management canister.
+    let mut expected = b"TRANSFORMED".to_vec();
+    expected.extend(CanisterId::ic_00().get().as_slice());
+    assert_eq!(reply, WasmResult::Reply(expected));
+}
+
+#[test]
+fn http_outcall_response_must_fit_max_response_bytes() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(HTTP_OUTCALL_WAT, vec![], None);
+    env.set_http_outcall_handler(|_context| {
+        Ok(CanisterHttpResponsePayload {
+            status: 200,
+            headers: vec![],
+            body: b"volatile".to_vec(),
+        })
+    });
+
+    let reply = env
+        .execute_ingress(
+            canister_id,
+            "fetch",
+            CanisterHttpRequestArgs {
+                max_response_bytes: Some(4),
+                ..http_request_args(canister_id, true)
+            }
+            .encode(),
+        )
+        .unwrap();
+    assert_eq!(
+        reply,
+        WasmResult::Reject("Http response exceeds the specified response size limit 4".to_string())
+    );
+}
+
+#[test]
+fn http_outcall_handler_can_reject_outcalls() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(HTTP_OUTCALL_WAT, vec![], None);
+    env.set_http_outcall_handler(|_context| {
+        Err((RejectCode::SysTransient, "connection refused".to_string()))
+    });
+
+    let reply = env
+        .execute_ingress(
+            canister_id,
+            "fetch",
+            http_request_args(canister_id, false).encode(),
+        )
+        .unwrap();
+    assert_eq!(reply, WasmResult::Reject("connection refused".to_string()));
+}
@@ -0,0 +1,125 @@
+use ic_state_machine_tests::{
+    CanisterId, IngressState, IngressStatus, PrincipalId, StateMachineBuilder, StateMachineEnv,
+    StateMachineEnvBuilder, SubnetId, WasmResult,
+};
+
+/// A canister whose `ping` method calls `pong` on the canister whose id is
+/// passed as the argument, and replies with the reply of `pong`.
+const PING_PONG_WAT: &str = r#"
+(module
+  (import "ic0" "msg_arg_data_size" (func $msg_arg_data_size (result i32)))
+  (import "ic0" "msg_arg_data_copy" (func $msg_arg_data_copy (param i32 i32 i32)))
+  (import "ic0" "msg_reply" (func $msg_reply))
+  (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "call_new"
+    (func $call_new (param i32 i32 i32 i32 i32 i32 i32 i32)))
+  (import "ic0" "call_perform" (func $call_perform (result i32)))
+
+  (func $ping
+    (call $msg_arg_data_copy (i32.const 0) (i32.const 0) (call $msg_arg_data_size))
+    (call $call_new
+      (i32.const 0) (call $msg_arg_data_size)
+      (i32.const 100) (i32.const 4)
+      (i32.const 0) (i32.const 0)
+      (i32.const 1) (i32.const 0))
+    (drop (call $call_perform)))
+
+  (func $pong
+    (call $msg_reply_data_append (i32.const 200) (i32.const 4))
+    (call $msg_reply))
+
+  (func $on_reply
+    (call $msg_arg_data_copy (i32.const 300) (i32.const 0) (call $msg_arg_data_size))
+    (call $msg_reply_data_append (i32.const 300) (call $msg_arg_data_size))
+    (call $msg_reply))
+
+  (func $on_reject
+    (call $msg_reply))
+
+  (table funcref (elem $on_reply $on_reject))
+  (memory $memory 1)
+  (data (i32.const 100) "pong")
+  (data (i32.const 200) "PONG")
+  (export "memory" (memory $memory))
+  (export "canister_update ping" (func $ping))
+  (export "canister_update pong" (func $pong)))
+"#;
+
+fn subnet_id(id: u64) -> SubnetId {
+    SubnetId::from(PrincipalId::new_subnet_test_id(id))
+}
+
+fn make_env(num_subnets: u64) -> StateMachineEnv {
+    (1..=num_subnets)
+        .fold(StateMachineEnvBuilder::new(), |builder, id| {
+            builder.with_subnet(StateMachineBuilder::new().with_subnet_id(subnet_id(id)))
+        })
+        .build()
+}
+
+/// Calls `ping` on the `caller` canister hosted by the specified subnet and
+/// returns the reply once the call completed.
+fn ping(
+    env: &StateMachineEnv,
+    subnet: SubnetId,
+    caller: CanisterId,
+    callee: CanisterId,
+) -> Vec<u8> {
+    let msg_id = env.subnet(subnet).send_ingress(
+        PrincipalId::new_anonymous(),
+        caller,
+        "ping",
+        callee.get().into_vec(),
+    );
+    env.run_until_completion(100);
+    match env.subnet(subnet).ingress_status(&msg_id) {
+        IngressStatus::Known {
+            state: IngressState::Completed(WasmResult::Reply(bytes)),
+            ..
+        } => bytes,
+        status => panic!("unexpected ingress status {:?}", status),
+    }
+}
+
+#[test]
+fn xnet_messages_are_delivered_between_subnets() {
+    let env = make_env(2);
+    let local = env
+        .subnet(subnet_id(1))
+        .install_canister_wat(PING_PONG_WAT, vec![], None);
+    let remote = env
+        .subnet(subnet_id(2))
+        .install_canister_wat(PING_PONG_WAT, vec![], None);
+    assert_ne!(local, remote);
+    assert_eq!(
+        env.route(remote).unwrap().get_subnet_id(),
+        subnet_id(2),
+        "the canister must be routed to the subnet that created it"
+    );
+
+    assert_eq!(ping(&env, subnet_id(1), local, remote), b"PONG".to_vec());
+    assert_eq!(ping(&env, subnet_id(2), remote, local), b"PONG".to_vec());
+}
+
+#[test]
+fn canister_can_be_migrated_between_subnets() {
+    let env = make_env(3);
+    let local = env
+        .subnet(subnet_id(1))
+        .install_canister_wat(PING_PONG_WAT, vec![], None);
+    let remote = env
+        .subnet(subnet_id(2))
+        .install_canister_wat(PING_PONG_WAT, vec![], None);
+    assert_eq!(ping(&env, subnet_id(1), local, remote), b"PONG".to_vec());
+
+    env.prepare_canister_migrations(remote..=remote, subnet_id(2), subnet_id(3));
+    env.reroute_canister_range(remote..=remote, subnet_id(3));
+    env.move_canister_state(remote, subnet_id(2), subnet_id(3))
+        .unwrap();
+    env.complete_canister_migrations(remote..=remote, vec![subnet_id(2), subnet_id(3)]);
+
+    assert_eq!(env.route(remote).unwrap().get_subnet_id(), subnet_id(3));
+    assert!(!env.subnet(subnet_id(2)).canister_exists(remote));
+    assert!(env.subnet(subnet_id(3)).canister_exists(remote));
+    assert_eq!(ping(&env, subnet_id(1), local, remote), b"PONG".to_vec());
+}
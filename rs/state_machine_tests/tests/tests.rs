@@ -1,6 +1,7 @@
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
+use std::net::TcpStream;
 use std::process::{ChildStdin, ChildStdout, Command, Stdio};
 use std::time::{Duration, SystemTime};
 
@@ -42,6 +43,75 @@ fn test() {
     );
 }
 
+#[test]
+fn http_server_controls_time() {
+    let state_machine_binary =
+        std::env::var_os("STATE_MACHINE_BIN").expect("missing state machine binary binary");
+    let port_file = std::env::temp_dir().join(format!("state_machine_port_{}", std::process::id()));
+    let mut child = Command::new(state_machine_binary)
+        .arg("--http-port")
+        .arg("0")
+        .arg("--port-file")
+        .arg(&port_file)
+        .spawn()
+        .expect("failed to start test state machine");
+
+    let mut port = None;
+    for _ in 0..600 {
+        if let Ok(contents) = std::fs::read_to_string(&port_file) {
+            if let Ok(p) = contents.trim().parse::<u16>() {
+                port = Some(p);
+                break;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let port = port.expect("the HTTP server did not start");
+
+    let (status, _) = http_request(
+        port,
+        "POST",
+        "/control/set_time",
+        r#"{"nanos_since_epoch":1700000000000000000}"#,
+    );
+    assert!(status.contains("200"), "{}", status);
+    let (status, _) = http_request(port, "POST", "/control/advance_time", r#"{"nanos":1000}"#);
+    assert!(status.contains("200"), "{}", status);
+    let (status, _) = http_request(port, "POST", "/control/tick", "");
+    assert!(status.contains("200"), "{}", status);
+
+    let (status, body) = http_request(port, "GET", "/control/time", "");
+    assert!(status.contains("200"), "{}", status);
+    assert_eq!(body, r#"{"nanos_since_epoch":1700000000000001000}"#);
+
+    let (status, _) = http_request(port, "GET", "/api/v2/status", "");
+    assert!(status.contains("200"), "{}", status);
+
+    child.kill().unwrap();
+    let _ = std::fs::remove_file(&port_file);
+}
+
+/// Sends a minimal HTTP/1.1 request and returns the status line and the body.
+fn http_request(port: u16, method: &str, path: &str, body: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .expect("failed to send request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .expect("failed to read response");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head.lines().next().unwrap_or_default().to_string();
+    (status, body.to_string())
+}
+
 fn call_state_machine<T: DeserializeOwned>(
     request: Request,
     stdin: &mut ChildStdin,
@@ -7,10 +7,18 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
 use ic_replicated_state::Memory;
 use ic_replicated_state::{
-    canister_state::execution_state::WasmBinary, page_map::PageMap, CanisterMetrics, CanisterState,
-    ExecutionState, ReplicatedState, SchedulerState, SystemState,
+    canister_state::{
+        canister_snapshots::{CanisterSnapshot, CanisterSnapshots, SnapshotId},
+        execution_state::WasmBinary,
+        wasm_chunk_store::WasmChunkStore,
+    },
+    page_map::PageMap,
+    CanisterMetrics, CanisterState, ExecutionState, ReplicatedState, SchedulerState, SystemState,
+};
+use ic_state_layout::{
+    CanisterLayout, CanisterSnapshotsBits, CanisterStateBits, CheckpointLayout, ReadOnly,
+    ReadPolicy,
 };
-use ic_state_layout::{CanisterLayout, CanisterStateBits, CheckpointLayout, ReadOnly, ReadPolicy};
 use ic_types::{CanisterTimer, Height, LongExecutionMode, Time};
 use ic_utils::thread::parallel_map;
 use std::collections::BTreeMap;
@@ -324,6 +332,28 @@ pub fn load_canister_state<P: ReadPolicy>(
             })?;
     durations.insert("canister_queues", starting_time.elapsed());
 
+    let starting_time = Instant::now();
+    let canister_snapshots = load_canister_snapshots(
+        canister_layout,
+        canister_id,
+        canister_state_bits.canister_snapshots,
+        height,
+        Arc::clone(&fd_factory),
+    )?;
+    durations.insert("canister_snapshots", starting_time.elapsed());
+
+    let starting_time = Instant::now();
+    let mut wasm_chunks = Vec::with_capacity(canister_state_bits.wasm_chunk_hashes.len());
+    for hash in &canister_state_bits.wasm_chunk_hashes {
+        wasm_chunks.push(
+            canister_layout
+                .wasm_chunk(hash)?
+                .deserialize(Some((*hash).into()))?,
+        );
+    }
+    let wasm_chunk_store = WasmChunkStore::new(wasm_chunks);
+    durations.insert("wasm_chunk_store", starting_time.elapsed());
+
     let canister_metrics = CanisterMetrics::new(
         canister_state_bits.scheduled_as_first,
         canister_state_bits.skipped_round_due_to_no_messages,
@@ -343,10 +373,18 @@ pub fn load_canister_state<P: ReadPolicy>(
         canister_metrics,
         canister_state_bits.cycles_balance,
         canister_state_bits.cycles_debit,
+        canister_state_bits.reserved_balance,
+        canister_state_bits.reserved_balance_limit,
         canister_state_bits.task_queue.into_iter().collect(),
         CanisterTimer::from_nanos_since_unix_epoch(canister_state_bits.global_timer_nanos),
         canister_state_bits.canister_version,
         canister_state_bits.canister_history,
+        canister_snapshots,
+        canister_state_bits.canister_log,
+        canister_state_bits.log_visibility,
+        wasm_chunk_store,
+        canister_state_bits.wasm_memory_limit,
+        canister_state_bits.on_low_wasm_memory_hook_status,
     );
 
     let canister_state = CanisterState {
@@ -365,6 +403,7 @@ pub fn load_canister_state<P: ReadPolicy>(
             time_of_last_allocation_charge: Time::from_nanos_since_unix_epoch(
                 canister_state_bits.time_of_last_allocation_charge_nanos,
             ),
+            total_query_stats: canister_state_bits.total_query_stats,
         },
     };
 
@@ -373,6 +412,56 @@ pub fn load_canister_state<P: ReadPolicy>(
     Ok((canister_state, metrics))
 }
 
+/// Loads the snapshots of a canister whose metadata is given by
+/// `canister_snapshots_bits` from the snapshot files in `canister_layout`.
+fn load_canister_snapshots<P: ReadPolicy>(
+    canister_layout: &CanisterLayout<P>,
+    canister_id: &CanisterId,
+    canister_snapshots_bits: CanisterSnapshotsBits,
+    height: Height,
+    fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+) -> Result<CanisterSnapshots, CheckpointError> {
+    let mut snapshots = BTreeMap::new();
+    for snapshot_bits in canister_snapshots_bits.snapshots {
+        let snapshot_layout = canister_layout.snapshot(snapshot_bits.local_id)?;
+        let wasm_memory = Memory::new(
+            PageMap::open(
+                &snapshot_layout.vmemory_0(),
+                height,
+                Arc::clone(&fd_factory),
+            )?,
+            snapshot_bits.wasm_memory_size,
+        );
+        let stable_memory = Memory::new(
+            PageMap::open(
+                &snapshot_layout.stable_memory_blob(),
+                height,
+                Arc::clone(&fd_factory),
+            )?,
+            snapshot_bits.stable_memory_size,
+        );
+        let wasm_binary = snapshot_layout
+            .wasm()
+            .deserialize(Some(snapshot_bits.binary_hash))?;
+        snapshots.insert(
+            SnapshotId::new(*canister_id, snapshot_bits.local_id),
+            CanisterSnapshot::new(
+                snapshot_bits.taken_at_timestamp,
+                snapshot_bits.canister_version,
+                snapshot_bits.certified_data,
+                wasm_binary,
+                snapshot_bits.exported_globals,
+                wasm_memory,
+                stable_memory,
+            ),
+        );
+    }
+    Ok(CanisterSnapshots::new(
+        snapshots,
+        canister_snapshots_bits.next_local_id,
+    ))
+}
+
 fn load_canister_state_from_checkpoint<P: ReadPolicy>(
     checkpoint_layout: &CheckpointLayout<P>,
     canister_id: &CanisterId,
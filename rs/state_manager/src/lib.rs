@@ -39,8 +39,9 @@ use ic_protobuf::proxy::{ProtoProxy, ProxyDecodeError};
 use ic_protobuf::{messaging::xnet::v1, state::v1 as pb};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    canister_state::execution_state::SandboxMemory, page_map::PersistenceError, PageIndex, PageMap,
-    ReplicatedState,
+    canister_state::{canister_snapshots::SnapshotId, execution_state::SandboxMemory},
+    page_map::PersistenceError,
+    PageIndex, PageMap, ReplicatedState,
 };
 use ic_state_layout::{error::LayoutError, AccessPolicy, CheckpointLayout, ReadOnly, StateLayout};
 use ic_types::{
@@ -961,6 +962,8 @@ struct PopulatedMetadata {
 pub enum PageMapType {
     WasmMemory(CanisterId),
     StableMemory(CanisterId),
+    SnapshotWasmMemory(SnapshotId),
+    SnapshotStableMemory(SnapshotId),
 }
 
 impl PageMapType {
@@ -972,6 +975,10 @@ impl PageMapType {
                 result.push(Self::WasmMemory(id.to_owned()));
                 result.push(Self::StableMemory(id.to_owned()));
             }
+            for (snapshot_id, _) in canister.system_state.canister_snapshots().iter() {
+                result.push(Self::SnapshotWasmMemory(snapshot_id.to_owned()));
+                result.push(Self::SnapshotStableMemory(snapshot_id.to_owned()));
+            }
         }
 
         result
@@ -985,6 +992,14 @@ impl PageMapType {
         match &self {
             PageMapType::WasmMemory(id) => Ok(layout.canister(id)?.vmemory_0()),
             PageMapType::StableMemory(id) => Ok(layout.canister(id)?.stable_memory_blob()),
+            PageMapType::SnapshotWasmMemory(id) => Ok(layout
+                .canister(&id.canister_id())?
+                .snapshot(id.local_id())?
+                .vmemory_0()),
+            PageMapType::SnapshotStableMemory(id) => Ok(layout
+                .canister(&id.canister_id())?
+                .snapshot(id.local_id())?
+                .stable_memory_blob()),
         }
     }
 
@@ -1001,6 +1016,22 @@ impl PageMapType {
                     .as_ref()
                     .map(|ex| &ex.stable_memory.page_map)
             }),
+            PageMapType::SnapshotWasmMemory(id) => {
+                state.canister_state(&id.canister_id()).and_then(|can| {
+                    can.system_state
+                        .canister_snapshots()
+                        .get(id)
+                        .map(|snapshot| &snapshot.wasm_memory().page_map)
+                })
+            }
+            PageMapType::SnapshotStableMemory(id) => {
+                state.canister_state(&id.canister_id()).and_then(|can| {
+                    can.system_state
+                        .canister_snapshots()
+                        .get(id)
+                        .map(|snapshot| &snapshot.stable_memory().page_map)
+                })
+            }
         }
     }
 
@@ -1017,6 +1048,22 @@ impl PageMapType {
                     .as_mut()
                     .map(|ex| &mut ex.stable_memory.page_map)
             }),
+            PageMapType::SnapshotWasmMemory(id) => {
+                state.canister_state_mut(&id.canister_id()).and_then(|can| {
+                    can.system_state
+                        .canister_snapshots_mut()
+                        .get_mut(id)
+                        .map(|snapshot| &mut snapshot.wasm_memory_mut().page_map)
+                })
+            }
+            PageMapType::SnapshotStableMemory(id) => {
+                state.canister_state_mut(&id.canister_id()).and_then(|can| {
+                    can.system_state
+                        .canister_snapshots_mut()
+                        .get_mut(id)
+                        .map(|snapshot| &mut snapshot.stable_memory_mut().page_map)
+                })
+            }
         }
     }
 }
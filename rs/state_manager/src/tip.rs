@@ -8,12 +8,13 @@ use ic_logger::{error, fatal, info, ReplicaLogger};
 use ic_protobuf::state::system_metadata::v1::SystemMetadata;
 #[allow(unused)]
 use ic_replicated_state::{
-    canister_state::execution_state::SandboxMemory, CanisterState, NumWasmPages, PageMap,
-    ReplicatedState,
+    canister_state::{execution_state::SandboxMemory, wasm_chunk_store::WasmChunkHash},
+    CanisterState, NumWasmPages, PageMap, ReplicatedState,
 };
 use ic_state_layout::{
-    error::LayoutError, CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadOnly,
-    RwPolicy, StateLayout, TipHandler,
+    error::LayoutError, CanisterLayout, CanisterSnapshotBits, CanisterSnapshotsBits,
+    CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadOnly, RwPolicy, StateLayout,
+    TipHandler,
 };
 use ic_types::state_sync::{FILE_GROUP_CHUNK_ID_OFFSET, MANIFEST_CHUNK_ID_OFFSET};
 use ic_types::{malicious_flags::MaliciousFlags, CanisterId, Height};
@@ -447,6 +448,9 @@ fn serialize_canister_to_tip(
             None
         }
     };
+    let canister_snapshots =
+        serialize_canister_snapshots_to_tip(log, canister_state, &canister_layout)?;
+    let wasm_chunk_hashes = serialize_wasm_chunk_store_to_tip(canister_state, &canister_layout)?;
     // Priority credit must be zero at this point
     assert_eq!(canister_state.scheduler_state.priority_credit.get(), 0);
     canister_layout.canister().serialize(
@@ -460,6 +464,8 @@ fn serialize_canister_to_tip(
             freeze_threshold: canister_state.system_state.freeze_threshold,
             cycles_balance: canister_state.system_state.balance(),
             cycles_debit: canister_state.system_state.ingress_induction_cycles_debit(),
+            reserved_balance: canister_state.system_state.reserved_balance(),
+            reserved_balance_limit: canister_state.system_state.reserved_balance_limit(),
             execution_state_bits,
             status: canister_state.system_state.status.clone(),
             scheduled_as_first: canister_state
@@ -508,12 +514,109 @@ fn serialize_canister_to_tip(
                 .get_consumed_cycles_since_replica_started_by_use_cases()
                 .clone(),
             canister_history: canister_state.system_state.get_canister_history().clone(),
+            canister_snapshots,
+            canister_log: canister_state.system_state.canister_log.clone(),
+            log_visibility: canister_state.system_state.log_visibility,
+            wasm_chunk_hashes,
+            total_query_stats: canister_state.scheduler_state.total_query_stats.clone(),
+            wasm_memory_limit: canister_state.system_state.wasm_memory_limit,
+            on_low_wasm_memory_hook_status: canister_state
+                .system_state
+                .on_low_wasm_memory_hook_status,
         }
         .into(),
     )?;
     Ok(())
 }
 
+/// Serializes the Wasm modules and the memories of the snapshots of the given
+/// canister to the tip and removes the files of snapshots that were deleted
+/// since the last checkpoint.
+fn serialize_canister_snapshots_to_tip(
+    log: &ReplicaLogger,
+    canister_state: &CanisterState,
+    canister_layout: &CanisterLayout<RwPolicy<TipHandler>>,
+) -> Result<CanisterSnapshotsBits, CheckpointError> {
+    let canister_snapshots = canister_state.system_state.canister_snapshots();
+    let mut snapshots = Vec::with_capacity(canister_snapshots.len());
+    for (snapshot_id, snapshot) in canister_snapshots.iter() {
+        let snapshot_layout = canister_layout.snapshot(snapshot_id.local_id())?;
+        let wasm_binary = snapshot.wasm_binary();
+        match wasm_binary.file() {
+            Some(path) => {
+                let wasm = snapshot_layout.wasm();
+                if !wasm.raw_path().exists() {
+                    ic_state_layout::utils::do_copy(log, path, wasm.raw_path()).map_err(
+                        |io_err| CheckpointError::IoError {
+                            path: path.to_path_buf(),
+                            message: "failed to copy snapshot Wasm file".to_string(),
+                            io_err: io_err.to_string(),
+                        },
+                    )?;
+                }
+            }
+            None => {
+                snapshot_layout.wasm().serialize(wasm_binary)?;
+            }
+        }
+        snapshot
+            .wasm_memory()
+            .page_map
+            .persist_delta(&snapshot_layout.vmemory_0())?;
+        snapshot
+            .stable_memory()
+            .page_map
+            .persist_delta(&snapshot_layout.stable_memory_blob())?;
+
+        snapshots.push(CanisterSnapshotBits {
+            local_id: snapshot_id.local_id(),
+            taken_at_timestamp: snapshot.taken_at_timestamp(),
+            canister_version: snapshot.canister_version(),
+            certified_data: snapshot.certified_data().clone(),
+            binary_hash: wasm_binary.module_hash().into(),
+            exported_globals: snapshot.exported_globals().clone(),
+            wasm_memory_size: snapshot.wasm_memory().size,
+            stable_memory_size: snapshot.stable_memory().size,
+        });
+    }
+
+    // Remove the files of deleted snapshots that were copied over from the
+    // previous checkpoint.
+    for local_id in canister_layout.snapshot_ids()? {
+        if !snapshots.iter().any(|s| s.local_id == local_id) {
+            canister_layout.snapshot(local_id)?.delete_dir()?;
+        }
+    }
+
+    Ok(CanisterSnapshotsBits {
+        snapshots,
+        next_local_id: canister_snapshots.next_local_id(),
+    })
+}
+
+/// Writes the chunks of the Wasm chunk store of the given canister that are
+/// not yet in the tip and removes the files of chunks that were deleted since
+/// the last checkpoint. Chunk files are content-addressed, so existing files
+/// never need to be rewritten.
+fn serialize_wasm_chunk_store_to_tip(
+    canister_state: &CanisterState,
+    canister_layout: &CanisterLayout<RwPolicy<TipHandler>>,
+) -> Result<Vec<WasmChunkHash>, CheckpointError> {
+    let wasm_chunk_store = canister_state.system_state.wasm_chunk_store();
+    for (hash, chunk) in wasm_chunk_store.iter() {
+        let chunk_file = canister_layout.wasm_chunk(hash)?;
+        if !chunk_file.raw_path().exists() {
+            chunk_file.serialize(chunk)?;
+        }
+    }
+    for hash in canister_layout.wasm_chunk_hashes()? {
+        if !wasm_chunk_store.contains(&hash) {
+            canister_layout.wasm_chunk(&hash)?.try_delete_file()?;
+        }
+    }
+    Ok(wasm_chunk_store.keys().cloned().collect())
+}
+
 /// Defragments part of the tip directory.
 ///
 /// The way we use PageMap files in the tip, namely by having a
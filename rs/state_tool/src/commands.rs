@@ -1,4 +1,6 @@
 //! Command implementations.
+pub mod audit;
+pub mod canister;
 pub mod cdiff;
 pub mod chash;
 pub mod convert_ids;
@@ -0,0 +1,558 @@
+//! Audits the internal consistency of a checkpoint stored on disk.
+//!
+//! The audit decodes all protobuf files of the checkpoint, loads every
+//! canister, checks that the page map files agree with the memory sizes
+//! recorded in the canister metadata, reports files that are not part of the
+//! checkpoint layout and compares the recomputed manifest with the one stored
+//! in `states_metadata.pbuf`. The result is printed as a JSON report.
+
+use ic_protobuf::state::v1 as pb;
+use ic_replicated_state::{
+    canister_state::WASM_PAGE_SIZE_IN_BYTES, page_map::TestPageAllocatorFileDescriptorImpl,
+};
+use ic_state_layout::{
+    CanisterLayout, CanisterStateBits, CompleteCheckpointLayout, LayoutError, ReadOnly,
+};
+use ic_state_manager::{
+    checkpoint::load_canister_state,
+    manifest::{manifest_from_path, manifest_hash},
+};
+use ic_sys::PAGE_SIZE;
+use ic_types::{state_sync::Manifest, CanisterId, Height, NumWasmPages};
+use prost::Message;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Severity {
+    /// The checkpoint is corrupted or cannot be loaded.
+    Error,
+    /// The checkpoint can be loaded, but something looks suspicious.
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A single problem found by the audit.
+#[derive(Debug)]
+struct Issue {
+    severity: Severity,
+    kind: &'static str,
+    /// Path of the offending file, relative to the checkpoint root.
+    path: PathBuf,
+    message: String,
+}
+
+/// Collects the issues found while auditing the checkpoint at `root`.
+struct Audit {
+    root: PathBuf,
+    issues: Vec<Issue>,
+    /// Files (relative to `root`) that belong to the checkpoint layout.
+    expected_files: BTreeSet<PathBuf>,
+    canisters_checked: usize,
+    root_hash: Option<[u8; 32]>,
+    stored_root_hash: Option<[u8; 32]>,
+}
+
+impl Audit {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            issues: Vec::new(),
+            expected_files: BTreeSet::new(),
+            canisters_checked: 0,
+            root_hash: None,
+            stored_root_hash: None,
+        }
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    fn report(&mut self, severity: Severity, kind: &'static str, path: &Path, message: String) {
+        let path = self.relative(path).to_path_buf();
+        self.issues.push(Issue {
+            severity,
+            kind,
+            path,
+            message,
+        });
+    }
+
+    fn expect(&mut self, path: PathBuf) {
+        let path = self.relative(&path).to_path_buf();
+        self.expected_files.insert(path);
+    }
+
+    fn num_errors(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count()
+    }
+
+    /// Decodes the subnet-level protobuf files of the checkpoint.
+    fn check_checkpoint_files(&mut self, layout: &CompleteCheckpointLayout) {
+        self.expect(layout.system_metadata().raw_path().to_path_buf());
+        self.expect(layout.ingress_history().raw_path().to_path_buf());
+        self.expect(layout.subnet_queues().raw_path().to_path_buf());
+        if let Err(err) = layout.system_metadata().deserialize() {
+            let path = layout.system_metadata().raw_path().to_path_buf();
+            self.report(Severity::Error, "protobuf_decode", &path, err.to_string());
+        }
+        if let Err(err) = layout.ingress_history().deserialize_opt() {
+            let path = layout.ingress_history().raw_path().to_path_buf();
+            self.report(Severity::Error, "protobuf_decode", &path, err.to_string());
+        }
+        if let Err(err) = layout.subnet_queues().deserialize() {
+            let path = layout.subnet_queues().raw_path().to_path_buf();
+            self.report(Severity::Error, "protobuf_decode", &path, err.to_string());
+        }
+    }
+
+    /// Checks every canister listed by the checkpoint layout.
+    fn check_canisters(&mut self, layout: &CompleteCheckpointLayout) {
+        let canister_ids = match list_layout_entries(|| layout.canister_ids()) {
+            Ok(canister_ids) => canister_ids,
+            Err(err) => {
+                self.report(Severity::Error, "layout", layout.raw_path(), err);
+                return;
+            }
+        };
+        for canister_id in canister_ids {
+            match layout.canister(&canister_id) {
+                Ok(canister_layout) => self.check_canister(&canister_id, &canister_layout),
+                Err(err) => self.report(Severity::Error, "io", layout.raw_path(), err.to_string()),
+            }
+        }
+    }
+
+    fn check_canister(&mut self, canister_id: &CanisterId, layout: &CanisterLayout<ReadOnly>) {
+        self.canisters_checked += 1;
+        let canister_root = layout.raw_path();
+        self.expect(layout.canister().raw_path().to_path_buf());
+        self.expect(layout.queues().raw_path().to_path_buf());
+        self.expect(layout.wasm().raw_path().to_path_buf());
+        self.expect(layout.vmemory_0());
+        self.expect(layout.stable_memory_blob());
+
+        let bits = match layout
+            .canister()
+            .deserialize()
+            .map_err(|err| err.to_string())
+            .and_then(|proto| {
+                CanisterStateBits::try_from(proto)
+                    .map_err(|err| format!("failed to convert canister state bits: {}", err))
+            }) {
+            Ok(bits) => bits,
+            Err(err) => {
+                let path = layout.canister().raw_path().to_path_buf();
+                self.report(Severity::Error, "protobuf_decode", &path, err);
+                return;
+            }
+        };
+
+        if let Err(err) = load_canister_state(
+            layout,
+            canister_id,
+            Height::from(0),
+            Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+        ) {
+            self.report(
+                Severity::Error,
+                "canister_load",
+                &canister_root,
+                err.to_string(),
+            );
+        }
+
+        let heap_size = bits
+            .execution_state_bits
+            .as_ref()
+            .map(|execution_state_bits| execution_state_bits.heap_size);
+        self.check_page_map(&layout.vmemory_0(), heap_size);
+        self.check_page_map(
+            &layout.stable_memory_blob(),
+            heap_size.map(|_| bits.stable_memory_size),
+        );
+
+        let snapshots: BTreeMap<u64, _> = bits
+            .canister_snapshots
+            .snapshots
+            .iter()
+            .map(|snapshot| (snapshot.local_id, snapshot))
+            .collect();
+        let snapshot_ids = match list_layout_entries(|| layout.snapshot_ids()) {
+            Ok(snapshot_ids) => snapshot_ids,
+            Err(err) => {
+                self.report(Severity::Error, "layout", &canister_root, err);
+                Vec::new()
+            }
+        };
+        for local_id in snapshot_ids {
+            let snapshot_layout = match layout.snapshot(local_id) {
+                Ok(snapshot_layout) => snapshot_layout,
+                Err(err) => {
+                    self.report(Severity::Error, "io", &canister_root, err.to_string());
+                    continue;
+                }
+            };
+            match snapshots.get(&local_id) {
+                Some(snapshot) => {
+                    self.expect(snapshot_layout.wasm().raw_path().to_path_buf());
+                    self.expect(snapshot_layout.vmemory_0());
+                    self.expect(snapshot_layout.stable_memory_blob());
+                    self.check_page_map(
+                        &snapshot_layout.vmemory_0(),
+                        Some(snapshot.wasm_memory_size),
+                    );
+                    self.check_page_map(
+                        &snapshot_layout.stable_memory_blob(),
+                        Some(snapshot.stable_memory_size),
+                    );
+                }
+                None => self.report(
+                    Severity::Warning,
+                    "orphaned_file",
+                    &snapshot_layout.raw_path(),
+                    format!("snapshot {} is not referenced by canister.pbuf", local_id),
+                ),
+            }
+        }
+
+        let chunk_hashes = match list_layout_entries(|| layout.wasm_chunk_hashes()) {
+            Ok(chunk_hashes) => chunk_hashes,
+            Err(err) => {
+                self.report(Severity::Error, "layout", &canister_root, err);
+                Vec::new()
+            }
+        };
+        for hash in chunk_hashes {
+            let path = match layout.wasm_chunk(&hash) {
+                Ok(chunk) => chunk.raw_path().to_path_buf(),
+                Err(err) => {
+                    self.report(Severity::Error, "io", &canister_root, err.to_string());
+                    break;
+                }
+            };
+            // Chunks are reported here, so they must not show up as
+            // unexpected files as well.
+            self.expect(path.clone());
+            if !bits.wasm_chunk_hashes.contains(&hash) {
+                self.report(
+                    Severity::Warning,
+                    "orphaned_file",
+                    &path,
+                    "wasm chunk is not referenced by canister.pbuf".to_string(),
+                );
+            }
+        }
+    }
+
+    /// Checks that the page map file at `path` is a whole number of pages and
+    /// does not extend beyond `memory_size`. A canister without execution
+    /// state (`memory_size == None`) must have an empty page map.
+    fn check_page_map(&mut self, path: &Path, memory_size: Option<NumWasmPages>) {
+        let len = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => {
+                self.report(Severity::Error, "io", path, err.to_string());
+                return;
+            }
+        };
+        if len % PAGE_SIZE as u64 != 0 {
+            self.report(
+                Severity::Error,
+                "page_map",
+                path,
+                format!(
+                    "file size {} is not a multiple of the page size {}",
+                    len, PAGE_SIZE
+                ),
+            );
+        }
+        let max_len = memory_size
+            .map(|size| (size.get() * WASM_PAGE_SIZE_IN_BYTES) as u64)
+            .unwrap_or(0);
+        if len > max_len {
+            self.report(
+                Severity::Warning,
+                "page_map",
+                path,
+                format!(
+                    "file size {} exceeds the memory size of {} bytes",
+                    len, max_len
+                ),
+            );
+        }
+    }
+
+    /// Reports all files under the checkpoint root that are not part of the
+    /// checkpoint layout.
+    fn check_unexpected_files(&mut self) {
+        let mut files = Vec::new();
+        if let Err(err) = collect_files(&self.root, &mut files) {
+            let root = self.root.clone();
+            self.report(Severity::Error, "io", &root, err);
+        }
+        for file in files {
+            let relative = self.relative(&file).to_path_buf();
+            if self.expected_files.contains(&relative) {
+                continue;
+            }
+            self.report(
+                Severity::Warning,
+                "unexpected_file",
+                &file,
+                "file is not part of the checkpoint layout".to_string(),
+            );
+        }
+    }
+
+    /// Recomputes the manifest of the checkpoint and compares it with the
+    /// manifest stored in the states metadata file, if any.
+    fn check_manifest(&mut self, metadata_path: &Path) {
+        let root = self.root.clone();
+        let manifest = match manifest_from_path(&root) {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                self.report(Severity::Error, "manifest", &root, err.to_string());
+                return;
+            }
+        };
+        self.root_hash = Some(manifest_hash(&manifest));
+
+        let height = match checkpoint_height(&root) {
+            Some(height) => height,
+            None => {
+                self.report(
+                    Severity::Warning,
+                    "manifest",
+                    &root,
+                    "cannot determine the checkpoint height from the directory name".to_string(),
+                );
+                return;
+            }
+        };
+        let stored = match load_stored_manifest(metadata_path, height) {
+            Ok(Some(stored)) => stored,
+            Ok(None) => {
+                self.report(
+                    Severity::Warning,
+                    "manifest",
+                    metadata_path,
+                    format!("no manifest stored for height {}", height),
+                );
+                return;
+            }
+            Err(err) => {
+                self.report(Severity::Error, "manifest", metadata_path, err);
+                return;
+            }
+        };
+        self.stored_root_hash = Some(manifest_hash(&stored));
+        if self.root_hash == self.stored_root_hash {
+            return;
+        }
+
+        let stored_files: BTreeMap<_, _> = stored
+            .file_table
+            .iter()
+            .map(|file| (&file.relative_path, file.hash))
+            .collect();
+        let computed_files: BTreeMap<_, _> = manifest
+            .file_table
+            .iter()
+            .map(|file| (&file.relative_path, file.hash))
+            .collect();
+        let paths: BTreeSet<_> = stored_files.keys().chain(computed_files.keys()).collect();
+        let mut mismatches = Vec::new();
+        for path in paths {
+            let message = match (stored_files.get(path), computed_files.get(path)) {
+                (Some(stored), Some(computed)) if stored != computed => format!(
+                    "file hash {} does not match stored hash {}",
+                    hex::encode(computed),
+                    hex::encode(stored)
+                ),
+                (Some(_), None) => "file is in the stored manifest but missing on disk".to_string(),
+                (None, Some(_)) => "file is on disk but not in the stored manifest".to_string(),
+                _ => continue,
+            };
+            mismatches.push((path.to_path_buf(), message));
+        }
+        if mismatches.is_empty() {
+            self.report(
+                Severity::Error,
+                "manifest_mismatch",
+                &root,
+                "root hash does not match the stored manifest".to_string(),
+            );
+        }
+        for (path, message) in mismatches {
+            self.report(Severity::Error, "manifest_mismatch", &path, message);
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "checkpoint": self.root.display().to_string(),
+            "ok": self.num_errors() == 0,
+            "canisters_checked": self.canisters_checked,
+            "root_hash": self.root_hash.map(hex::encode),
+            "stored_root_hash": self.stored_root_hash.map(hex::encode),
+            "issues": self
+                .issues
+                .iter()
+                .map(|issue| json!({
+                    "severity": issue.severity.as_str(),
+                    "kind": issue.kind,
+                    "path": issue.path.display().to_string(),
+                    "message": issue.message,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Calls one of the layout accessors that list the entries of a directory.
+///
+/// These accessors panic on entries whose names they cannot parse, which the
+/// audit reports as an error instead of aborting.
+fn list_layout_entries<T>(
+    list: impl FnOnce() -> Result<Vec<T>, LayoutError>,
+) -> Result<Vec<T>, String> {
+    match std::panic::catch_unwind(AssertUnwindSafe(list)) {
+        Ok(result) => result.map_err(|err| err.to_string()),
+        Err(payload) => Err(payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "failed to list directory entries".to_string())),
+    }
+}
+
+/// Parses the height of a checkpoint from the name of its directory.
+fn checkpoint_height(path: &Path) -> Option<Height> {
+    let name = path.file_name()?.to_str()?;
+    u64::from_str_radix(name, 16).ok().map(Height::new)
+}
+
+/// Recursively collects all files under `dir`.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| format!("Failed to read directory {}: {}", dir.display(), err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("Failed to read directory {}: {}", dir.display(), err))?
+            .path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(())
+}
+
+/// Loads the manifest stored for `height` in the states metadata file.
+fn load_stored_manifest(path: &Path, height: Height) -> Result<Option<Manifest>, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+    let metadata = pb::StatesMetadata::decode(&bytes[..])
+        .map_err(|err| format!("Failed to decode {}: {}", path.display(), err))?;
+    metadata
+        .by_height
+        .get(&height.get())
+        .and_then(|state_metadata| state_metadata.manifest.clone())
+        .map(|manifest| {
+            Manifest::try_from(manifest)
+                .map_err(|err| format!("Failed to decode stored manifest: {}", err))
+        })
+        .transpose()
+}
+
+/// Audits the checkpoint at `path`.
+fn audit(path: PathBuf, metadata_path: &Path) -> Result<Audit, String> {
+    let layout = CompleteCheckpointLayout::new_untracked(path.clone(), Height::from(0))
+        .map_err(|err| format!("Failed to open checkpoint {}: {}", path.display(), err))?;
+    let mut audit = Audit::new(path);
+    audit.check_checkpoint_files(&layout);
+    audit.check_canisters(&layout);
+    audit.check_unexpected_files();
+    audit.check_manifest(metadata_path);
+    Ok(audit)
+}
+
+/// `audit` command entry point.
+///
+/// Prints the JSON report to stdout and fails if any errors were found.
+/// Unless `metadata` is specified, the stored manifest is read from the
+/// `states_metadata.pbuf` file of the state root the checkpoint belongs to.
+pub fn do_audit(path: PathBuf, metadata: Option<PathBuf>) -> Result<(), String> {
+    let metadata_path = metadata.unwrap_or_else(|| {
+        path.parent()
+            .and_then(Path::parent)
+            .unwrap_or_else(|| Path::new("."))
+            .join("states_metadata.pbuf")
+    });
+    let audit = audit(path, &metadata_path)?;
+    let report = serde_json::to_string_pretty(&audit.to_json())
+        .map_err(|err| format!("Failed to serialize audit report: {}", err))?;
+    println!("{}", report);
+
+    match audit.num_errors() {
+        0 => Ok(()),
+        errors => Err(format!(
+            "✗ Audit of {} found {} errors",
+            audit.root.display(),
+            errors
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_files_outside_of_checkpoint_layout() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let root = tmp.path().join("0000000000000064");
+        std::fs::create_dir_all(root.join("canister_states").join("not_a_canister_id")).unwrap();
+        std::fs::write(root.join("stray.bin"), b"stray").unwrap();
+
+        let audit = audit(root, &tmp.path().join("states_metadata.pbuf")).unwrap();
+        let issues: Vec<_> = audit
+            .issues
+            .iter()
+            .filter(|issue| issue.kind == "unexpected_file")
+            .map(|issue| issue.path.clone())
+            .collect();
+        assert_eq!(issues, vec![PathBuf::from("stray.bin")]);
+        assert!(audit.issues.iter().any(|issue| issue.kind == "layout"
+            && issue.severity == Severity::Error
+            && issue.message.contains("not_a_canister_id")));
+        assert!(audit
+            .issues
+            .iter()
+            .any(|issue| issue.kind == "protobuf_decode"
+                && issue.path == Path::new("system_metadata.pbuf")));
+        assert_eq!(checkpoint_height(&audit.root), Some(Height::new(100)));
+    }
+}
@@ -0,0 +1,422 @@
+//! Exports and diffs the state of a single canister in a checkpoint.
+
+use ic_interfaces::messages::CanisterMessage;
+use ic_replicated_state::{
+    canister_state::{execution_state::Memory, WASM_PAGE_SIZE_IN_BYTES},
+    page_map::TestPageAllocatorFileDescriptorImpl,
+    CanisterState, PageIndex,
+};
+use ic_state_layout::CompleteCheckpointLayout;
+use ic_state_manager::checkpoint::load_canister_state;
+use ic_sys::PAGE_SIZE;
+use ic_types::{
+    messages::{Ingress, Payload, Request, RequestOrResponse, Response},
+    CanisterId, Height,
+};
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Name of the file holding the canister metadata in the export directory.
+const METADATA_FILE: &str = "canister.json";
+/// Name of the file holding the raw heap contents in the export directory.
+const HEAP_FILE: &str = "heap.bin";
+/// Name of the file holding the raw stable memory contents in the export
+/// directory.
+const STABLE_MEMORY_FILE: &str = "stable_memory.bin";
+
+fn parse_canister_id(canister: &str) -> Result<CanisterId, String> {
+    CanisterId::from_str(canister)
+        .map_err(|err| format!("Invalid canister id {}: {:?}", canister, err))
+}
+
+/// Loads the state of `canister_id` from the checkpoint at `path`.
+fn load_canister(path: &Path, canister_id: &CanisterId) -> Result<CanisterState, String> {
+    let layout = CompleteCheckpointLayout::new_untracked(path.to_path_buf(), Height::from(0))
+        .map_err(|err| format!("Failed to open checkpoint {}: {}", path.display(), err))?;
+    let canister_layout = layout
+        .canister(canister_id)
+        .map_err(|err| format!("Failed to open canister {}: {}", canister_id, err))?;
+    if !canister_layout.raw_path().exists() {
+        return Err(format!(
+            "Canister {} does not exist in checkpoint {}",
+            canister_id,
+            path.display()
+        ));
+    }
+    let (canister_state, _metrics) = load_canister_state(
+        &canister_layout,
+        canister_id,
+        Height::from(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .map_err(|err| format!("Failed to load canister {}: {}", canister_id, err))?;
+    Ok(canister_state)
+}
+
+/// Returns the number of OS pages covered by the given wasm memory.
+fn num_os_pages(memory: &Memory) -> u64 {
+    (memory.size.get() * WASM_PAGE_SIZE_IN_BYTES / PAGE_SIZE) as u64
+}
+
+fn ingress_json(ingress: &Ingress) -> Value {
+    json!({
+        "type": "ingress",
+        "message_id": ingress.message_id.to_string(),
+        "source": ingress.source.to_string(),
+        "method_name": ingress.method_name,
+        "payload": hex::encode(&ingress.method_payload),
+        "expiry_time_nanos": ingress.expiry_time.as_nanos_since_unix_epoch(),
+    })
+}
+
+fn request_json(request: &Request) -> Value {
+    json!({
+        "type": "request",
+        "sender": request.sender.to_string(),
+        "receiver": request.receiver.to_string(),
+        "callback_id": request.sender_reply_callback.get(),
+        "method_name": request.method_name,
+        "payload": hex::encode(&request.method_payload),
+        "payment": request.payment.get().to_string(),
+        "deadline": request.deadline.as_secs_since_unix_epoch(),
+    })
+}
+
+fn response_json(response: &Response) -> Value {
+    let payload = match &response.response_payload {
+        Payload::Data(data) => json!({ "reply": hex::encode(data) }),
+        Payload::Reject(context) => json!({
+            "reject_code": format!("{:?}", context.code()),
+            "reject_message": context.message(),
+        }),
+    };
+    json!({
+        "type": "response",
+        "originator": response.originator.to_string(),
+        "respondent": response.respondent.to_string(),
+        "callback_id": response.originator_reply_callback.get(),
+        "refund": response.refund.get().to_string(),
+        "payload": payload,
+        "deadline": response.deadline.as_secs_since_unix_epoch(),
+    })
+}
+
+/// Lists the messages in the input queues in the order in which the canister
+/// would execute them and the messages in the output queues in the order in
+/// which they would be routed.
+fn queue_entries(canister: &CanisterState) -> (Vec<Value>, Vec<Value>) {
+    let mut canister = canister.clone();
+    let mut input = vec![];
+    while let Some(message) = canister.pop_input() {
+        input.push(match message {
+            CanisterMessage::Ingress(ingress) => ingress_json(&ingress),
+            CanisterMessage::Request(request) => request_json(&request),
+            CanisterMessage::Response(response) => response_json(&response),
+        });
+    }
+    let output = canister
+        .output_into_iter()
+        .map(|(_, message)| match message {
+            RequestOrResponse::Request(request) => request_json(&request),
+            RequestOrResponse::Response(response) => response_json(&response),
+        })
+        .collect();
+    (input, output)
+}
+
+/// Produces a JSON description of the canister metadata, queue entries, call
+/// contexts, callbacks and memory sizes.
+fn canister_metadata(canister: &CanisterState) -> Value {
+    let system_state = &canister.system_state;
+    let (call_contexts, callbacks) = system_state
+        .call_context_manager()
+        .map(|manager| {
+            let call_contexts: Map<String, Value> = manager
+                .call_contexts()
+                .iter()
+                .map(|(id, context)| {
+                    let callback_ids: Vec<_> = manager
+                        .callbacks()
+                        .iter()
+                        .filter(|(_, callback)| callback.call_context_id == *id)
+                        .map(|(callback_id, _)| callback_id.get())
+                        .collect();
+                    let context = json!({
+                        "origin": format!("{:?}", context.call_origin()),
+                        "responded": context.has_responded(),
+                        "deleted": context.is_deleted(),
+                        "available_cycles": context.available_cycles().get().to_string(),
+                        "callback_ids": callback_ids,
+                    });
+                    (id.get().to_string(), context)
+                })
+                .collect();
+            let callbacks: Map<String, Value> = manager
+                .callbacks()
+                .iter()
+                .map(|(id, callback)| {
+                    let callback = json!({
+                        "call_context_id": callback.call_context_id.get(),
+                        "originator": callback.originator.map(|canister_id| canister_id.to_string()),
+                        "respondent": callback.respondent.map(|canister_id| canister_id.to_string()),
+                        "cycles_sent": callback.cycles_sent.get().to_string(),
+                        "on_reply": format!("{:?}", callback.on_reply),
+                        "on_reject": format!("{:?}", callback.on_reject),
+                        "on_cleanup": callback.on_cleanup.as_ref().map(|c| format!("{:?}", c)),
+                        "deadline": callback.deadline.as_secs_since_unix_epoch(),
+                    });
+                    (id.get().to_string(), callback)
+                })
+                .collect();
+            (call_contexts, callbacks)
+        })
+        .unwrap_or_default();
+    let (input_queue, output_queue) = queue_entries(canister);
+    let execution_state = canister.execution_state.as_ref().map(|execution_state| {
+        json!({
+            "module_hash": hex::encode(execution_state.wasm_binary.binary.module_hash()),
+            "heap_size_bytes": execution_state.wasm_memory.size.get() * WASM_PAGE_SIZE_IN_BYTES,
+            "stable_memory_size_bytes":
+                execution_state.stable_memory.size.get() * WASM_PAGE_SIZE_IN_BYTES,
+            "exported_globals": execution_state.exported_globals.len(),
+            "last_executed_round": execution_state.last_executed_round.get(),
+        })
+    });
+
+    json!({
+        "canister_id": canister.canister_id().to_string(),
+        "controllers": system_state
+            .controllers
+            .iter()
+            .map(|controller| controller.to_string())
+            .collect::<Vec<_>>(),
+        "status": system_state.status_string(),
+        "canister_version": system_state.canister_version,
+        "cycles_balance": system_state.balance().get().to_string(),
+        "reserved_cycles_balance": system_state.reserved_balance().get().to_string(),
+        "freeze_threshold_seconds": system_state.freeze_threshold.get(),
+        "memory_allocation": format!("{:?}", system_state.memory_allocation),
+        "compute_allocation": canister.scheduler_state.compute_allocation.as_percent(),
+        "certified_data": hex::encode(&system_state.certified_data),
+        "queues": {
+            "input": input_queue,
+            "output": output_queue,
+            "task_queue_length": system_state.task_queue.len(),
+        },
+        "call_contexts": call_contexts,
+        "callbacks": callbacks,
+        "message_memory_usage_bytes": system_state.message_memory_usage().get(),
+        "execution_state": execution_state,
+    })
+}
+
+/// Writes the first `num_pages` OS pages of `memory` to `path`.
+fn write_memory(memory: &Memory, path: &Path) -> Result<(), String> {
+    let file = File::create(path)
+        .map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+    let mut writer = BufWriter::new(file);
+    for page in 0..num_os_pages(memory) {
+        writer
+            .write_all(memory.page_map.get_page(PageIndex::new(page)))
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+/// `canister export` command entry point.
+pub fn do_export(state: PathBuf, canister: String, output: PathBuf) -> Result<(), String> {
+    let canister_id = parse_canister_id(&canister)?;
+    let canister_state = load_canister(&state, &canister_id)?;
+
+    std::fs::create_dir_all(&output)
+        .map_err(|err| format!("Failed to create {}: {}", output.display(), err))?;
+    let metadata_path = output.join(METADATA_FILE);
+    let metadata = serde_json::to_string_pretty(&canister_metadata(&canister_state))
+        .map_err(|err| format!("Failed to serialize canister metadata: {}", err))?;
+    std::fs::write(&metadata_path, metadata)
+        .map_err(|err| format!("Failed to write {}: {}", metadata_path.display(), err))?;
+    println!("Wrote {}", metadata_path.display());
+
+    if let Some(execution_state) = &canister_state.execution_state {
+        for (memory, file_name) in [
+            (&execution_state.wasm_memory, HEAP_FILE),
+            (&execution_state.stable_memory, STABLE_MEMORY_FILE),
+        ] {
+            let path = output.join(file_name);
+            write_memory(memory, &path)?;
+            println!("Wrote {}", path.display());
+        }
+    } else {
+        println!("Canister {} is empty, no memory to export", canister_id);
+    }
+
+    Ok(())
+}
+
+/// Returns the ranges of OS pages whose contents differ between `a` and `b`.
+/// Pages beyond the size of one of the memories are compared against zeroes.
+fn changed_pages(a: &Memory, b: &Memory) -> Vec<RangeInclusive<u64>> {
+    let num_pages = num_os_pages(a).max(num_os_pages(b));
+    let mut ranges: Vec<RangeInclusive<u64>> = Vec::new();
+    for page in 0..num_pages {
+        let index = PageIndex::new(page);
+        if a.page_map.get_page(index) == b.page_map.get_page(index) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == page => *range = *range.start()..=page,
+            _ => ranges.push(page..=page),
+        }
+    }
+    ranges
+}
+
+fn print_memory_diff(name: &str, a: &Memory, b: &Memory) {
+    if a.size != b.size {
+        println!(
+            "{}: size changed from {} to {} wasm pages",
+            name,
+            a.size.get(),
+            b.size.get()
+        );
+    }
+    let ranges = changed_pages(a, b);
+    if ranges.is_empty() {
+        println!("{}: no pages changed", name);
+        return;
+    }
+    let num_changed: u64 = ranges.iter().map(|r| r.end() - r.start() + 1).sum();
+    println!(
+        "{}: {} of {} pages ({} bytes each) changed:",
+        name,
+        num_changed,
+        num_os_pages(a).max(num_os_pages(b)),
+        PAGE_SIZE
+    );
+    for range in ranges {
+        if range.start() == range.end() {
+            println!("  {}", range.start());
+        } else {
+            println!("  {}..={}", range.start(), range.end());
+        }
+    }
+}
+
+/// Describes the differences between two JSON values, one line per changed
+/// leaf. Objects are compared key by key and arrays index by index, so that
+/// the path of each line names the changed queue entry, call context or
+/// callback.
+fn value_diff(path: &str, a: &Value, b: &Value) -> Vec<String> {
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match (a, b) {
+        _ if a == b => vec![],
+        (Value::Object(fields_a), Value::Object(fields_b)) => {
+            let keys: std::collections::BTreeSet<_> =
+                fields_a.keys().chain(fields_b.keys()).collect();
+            keys.into_iter()
+                .flat_map(|key| {
+                    let value_a = fields_a.get(key).unwrap_or(&Value::Null);
+                    let value_b = fields_b.get(key).unwrap_or(&Value::Null);
+                    value_diff(&child(key), value_a, value_b)
+                })
+                .collect()
+        }
+        (Value::Array(items_a), Value::Array(items_b)) => (0..items_a.len().max(items_b.len()))
+            .flat_map(|index| {
+                let value_a = items_a.get(index).unwrap_or(&Value::Null);
+                let value_b = items_b.get(index).unwrap_or(&Value::Null);
+                value_diff(&format!("{}[{}]", path, index), value_a, value_b)
+            })
+            .collect(),
+        _ => vec![format!("{}: {} -> {}", path, a, b)],
+    }
+}
+
+/// `canister diff` command entry point.
+pub fn do_diff(path_a: PathBuf, path_b: PathBuf, canister: String) -> Result<(), String> {
+    let canister_id = parse_canister_id(&canister)?;
+    let canister_a = load_canister(&path_a, &canister_id)?;
+    let canister_b = load_canister(&path_b, &canister_id)?;
+
+    let metadata_a = canister_metadata(&canister_a);
+    let metadata_b = canister_metadata(&canister_b);
+    for line in value_diff("", &metadata_a, &metadata_b) {
+        println!("{}", line);
+    }
+
+    match (&canister_a.execution_state, &canister_b.execution_state) {
+        (Some(a), Some(b)) => {
+            print_memory_diff("heap", &a.wasm_memory, &b.wasm_memory);
+            print_memory_diff("stable memory", &a.stable_memory, &b.stable_memory);
+        }
+        (None, None) => println!("Canister {} is empty in both checkpoints", canister_id),
+        (Some(_), None) => println!("Canister {} was uninstalled", canister_id),
+        (None, Some(_)) => println!("Canister {} was installed", canister_id),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_replicated_state::PageMap;
+    use ic_types::NumWasmPages;
+
+    fn memory_with_pages(pages: &[(u64, u8)]) -> Memory {
+        let mut page_map = PageMap::new_for_testing();
+        let contents: Vec<_> = pages
+            .iter()
+            .map(|(index, byte)| (PageIndex::new(*index), [*byte; PAGE_SIZE]))
+            .collect();
+        let updates: Vec<_> = contents
+            .iter()
+            .map(|(index, bytes)| (*index, bytes))
+            .collect();
+        page_map.update(&updates);
+        Memory::new(page_map, NumWasmPages::new(2))
+    }
+
+    #[test]
+    fn changed_pages_are_grouped_into_ranges() {
+        let a = memory_with_pages(&[(0, 1), (1, 1), (5, 1)]);
+        let b = memory_with_pages(&[(0, 2), (1, 2), (2, 2), (5, 1), (7, 3)]);
+        assert_eq!(changed_pages(&a, &b), vec![0..=2, 7..=7]);
+        assert_eq!(changed_pages(&a, &a), vec![]);
+    }
+
+    #[test]
+    fn value_diff_names_the_changed_entries() {
+        let a = json!({
+            "status": "Running",
+            "queues": { "input": [{ "method_name": "a" }, { "method_name": "b" }] },
+            "callbacks": { "1": { "respondent": "x" } },
+        });
+        let b = json!({
+            "status": "Running",
+            "queues": { "input": [{ "method_name": "a" }, { "method_name": "c" }] },
+            "callbacks": { "1": { "respondent": "x" }, "2": { "respondent": "y" } },
+        });
+        assert_eq!(
+            value_diff("", &a, &b),
+            vec![
+                r#"callbacks.2: null -> {"respondent":"y"}"#.to_string(),
+                r#"queues.input[1].method_name: "b" -> "c""#.to_string(),
+            ]
+        );
+        assert!(value_diff("", &a, &a).is_empty());
+    }
+}
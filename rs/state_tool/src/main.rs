@@ -2,9 +2,10 @@
 //!
 //! A command-line tool to manage Internet Computer replicated states (decode
 //! persisted state files, diff checkpoints, compute partial state hashes and
-//! checkpoint manifests, import state trees).
+//! checkpoint manifests, import state trees, export and diff single canisters,
+//! audit checkpoints).
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 mod commands;
@@ -13,6 +14,25 @@ mod commands;
 #[derive(Parser, Debug)]
 #[clap(about = "IC state tool", version)]
 enum Opt {
+    /// Checks the internal consistency of a checkpoint and prints a JSON
+    /// report of the problems found.
+    #[clap(name = "audit")]
+    Audit {
+        /// Path to a checkpoint.
+        #[clap(long = "state")]
+        path: PathBuf,
+
+        /// Path to the states metadata file holding the stored manifest;
+        /// defaults to `states_metadata.pbuf` in the state root of the
+        /// checkpoint.
+        #[clap(long = "metadata")]
+        metadata: Option<PathBuf>,
+    },
+
+    /// Exports or diffs the state of a single canister.
+    #[clap(name = "canister", subcommand)]
+    Canister(CanisterCommand),
+
     /// Computes diff of canonical trees between checkpoints.
     #[clap(name = "cdiff")]
     CDiff { path_a: PathBuf, path_b: PathBuf },
@@ -144,9 +164,55 @@ enum Opt {
     },
 }
 
+/// Supported `state_tool canister` subcommands and their arguments.
+#[derive(Subcommand, Debug)]
+enum CanisterCommand {
+    /// Writes the canister metadata, queue entries, call contexts and
+    /// callbacks as JSON together with raw heap and stable memory blobs into
+    /// the output directory.
+    #[clap(name = "export")]
+    Export {
+        /// Path to a checkpoint.
+        #[clap(long = "state")]
+        state: PathBuf,
+
+        /// The canister to export, in textual representation.
+        #[clap(long = "canister")]
+        canister: String,
+
+        /// Directory to write the exported files to.
+        #[clap(long = "output", default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Shows the metadata, queue entries, call contexts and callbacks as well
+    /// as the heap and stable memory pages that differ for a canister between
+    /// two checkpoints.
+    #[clap(name = "diff")]
+    Diff {
+        path_a: PathBuf,
+        path_b: PathBuf,
+
+        /// The canister to diff, in textual representation.
+        #[clap(long = "canister")]
+        canister: String,
+    },
+}
+
 fn main() {
     let opt = Parser::parse();
     let result = match opt {
+        Opt::Audit { path, metadata } => commands::audit::do_audit(path, metadata),
+        Opt::Canister(CanisterCommand::Export {
+            state,
+            canister,
+            output,
+        }) => commands::canister::do_export(state, canister, output),
+        Opt::Canister(CanisterCommand::Diff {
+            path_a,
+            path_b,
+            canister,
+        }) => commands::canister::do_diff(path_a, path_b, canister),
         Opt::CDiff { path_a, path_b } => commands::cdiff::do_diff(path_a, path_b),
         Opt::CHash { path } => commands::chash::do_hash(path),
         Opt::ImportState {
@@ -8,6 +8,7 @@ pub mod system_api_empty;
 use ic_base_types::PrincipalIdBlobParseError;
 use ic_config::flag_status::FlagStatus;
 use ic_error_types::RejectCode;
+use ic_ic00_types::{EcdsaCurve, EcdsaKeyId};
 use ic_interfaces::execution_environment::{
     ExecutionComplexity, ExecutionMode,
     HypervisorError::{self, *},
@@ -23,11 +24,12 @@ use ic_replicated_state::{
 };
 use ic_sys::PageBytes;
 use ic_types::{
+    canister_log::{CanisterLog, MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE},
     ingress::WasmResult,
     messages::{CallContextId, RejectContext, Request, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
     methods::{SystemMethod, WasmClosure},
-    CanisterId, CanisterTimer, ComputeAllocation, Cycles, NumBytes, NumInstructions, NumPages,
-    PrincipalId, SubnetId, Time, MAX_STABLE_MEMORY_IN_BYTES,
+    CanisterId, CanisterTimer, CoarseTime, ComputeAllocation, Cycles, NumBytes, NumInstructions,
+    NumPages, PrincipalId, SubnetId, Time, MAX_STABLE_MEMORY_IN_BYTES,
 };
 use ic_utils::deterministic_operations::deterministic_copy_from_slice;
 use request_in_prep::{into_request, RequestInPrep};
@@ -42,6 +44,8 @@ use std::{
 const MULTIPLIER_MAX_SIZE_LOCAL_SUBNET: u64 = 5;
 const MAX_NON_REPLICATED_QUERY_REPLY_SIZE: NumBytes = NumBytes::new(3 << 20);
 const CERTIFIED_DATA_MAX_LENGTH: u32 = 32;
+/// The maximum timeout of a best-effort call, in seconds.
+pub const MAX_CALL_TIMEOUT_SECONDS: u32 = 300;
 
 // Enables tracing of system calls for local debugging.
 const TRACE_SYSCALLS: bool = false;
@@ -52,6 +56,13 @@ const WASM_NATIVE_STABLE_MEMORY_ERROR: &str = "Stable memory cannot be accessed
 
 const MAX_32_BIT_STABLE_MEMORY_IN_PAGES: u64 = 64 * 1024; // 4GiB
 
+/// The encoding of the secp256k1 curve in `ic0.cost_sign_with_ecdsa`.
+const ECDSA_CURVE_SECP256K1: u32 = 0;
+
+/// The results of `ic0.cost_sign_with_ecdsa` other than success.
+const ECDSA_UNKNOWN_CURVE: u32 = 1;
+const ECDSA_UNKNOWN_KEY: u32 = 2;
+
 // This macro is used in system calls for tracing.
 macro_rules! trace_syscall {
     ($self:ident, $name:ident, $result:expr $( , $args:expr )*) => {{
@@ -232,6 +243,9 @@ pub enum ApiType {
         /// request is currently under construction.
         outgoing_request: Option<RequestInPrep>,
         max_reply_size: NumBytes,
+        /// Deadline of the incoming call (`NO_DEADLINE` for ingress messages
+        /// and guaranteed response calls).
+        deadline: CoarseTime,
     },
 
     // For executing canister methods marked as `query`
@@ -278,6 +292,8 @@ pub enum ApiType {
         outgoing_request: Option<RequestInPrep>,
         max_reply_size: NumBytes,
         execution_mode: ExecutionMode,
+        /// Deadline of the response (`NO_DEADLINE` for guaranteed responses).
+        deadline: CoarseTime,
     },
 
     // For executing closures when a `Reject` is received
@@ -295,6 +311,8 @@ pub enum ApiType {
         outgoing_request: Option<RequestInPrep>,
         max_reply_size: NumBytes,
         execution_mode: ExecutionMode,
+        /// Deadline of the response (`NO_DEADLINE` for guaranteed responses).
+        deadline: CoarseTime,
     },
 
     PreUpgrade {
@@ -314,10 +332,12 @@ pub enum ApiType {
         message_accepted: bool,
     },
 
-    // For executing the `canister_heartbeat` or `canister_global_timer` methods
+    // For executing the `canister_heartbeat`, `canister_global_timer` or
+    // `canister_on_low_wasm_memory` methods
     SystemTask {
         /// System task to execute.
-        /// Only `canister_heartbeat` and `canister_global_timer` are allowed.
+        /// Only `canister_heartbeat`, `canister_global_timer` and
+        /// `canister_on_low_wasm_memory` are allowed.
         system_task: SystemMethod,
         time: Time,
         call_context_id: CallContextId,
@@ -370,6 +390,7 @@ impl ApiType {
         incoming_cycles: Cycles,
         caller: PrincipalId,
         call_context_id: CallContextId,
+        deadline: CoarseTime,
     ) -> Self {
         Self::Update {
             time,
@@ -381,6 +402,7 @@ impl ApiType {
             response_status: ResponseStatus::NotRepliedYet,
             outgoing_request: None,
             max_reply_size: MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+            deadline,
         }
     }
 
@@ -431,6 +453,7 @@ impl ApiType {
         call_context_id: CallContextId,
         replied: bool,
         execution_mode: ExecutionMode,
+        deadline: CoarseTime,
     ) -> Self {
         Self::ReplyCallback {
             time,
@@ -446,6 +469,7 @@ impl ApiType {
             outgoing_request: None,
             max_reply_size: MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
             execution_mode,
+            deadline,
         }
     }
 
@@ -457,6 +481,7 @@ impl ApiType {
         call_context_id: CallContextId,
         replied: bool,
         execution_mode: ExecutionMode,
+        deadline: CoarseTime,
     ) -> Self {
         Self::RejectCallback {
             time,
@@ -472,6 +497,7 @@ impl ApiType {
             outgoing_request: None,
             max_reply_size: MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
             execution_mode,
+            deadline,
         }
     }
 
@@ -528,7 +554,11 @@ impl ApiType {
             ApiType::SystemTask { system_task, .. } => match system_task {
                 SystemMethod::CanisterHeartbeat => "heartbeat",
                 SystemMethod::CanisterGlobalTimer => "global timer",
-                _ => panic!("Only `canister_heartbeat` and `canister_global_timer` are allowed."),
+                SystemMethod::CanisterOnLowWasmMemory => "on low wasm memory",
+                _ => panic!(
+                    "Only `canister_heartbeat`, `canister_global_timer` and \
+                     `canister_on_low_wasm_memory` are allowed."
+                ),
             },
             ApiType::Update { .. } => "update",
             ApiType::ReplicatedQuery { .. } => "replicated query",
@@ -546,6 +576,23 @@ impl ApiType {
             ApiType::Cleanup { .. } => "cleanup",
         }
     }
+
+    /// Returns the time at which the message is executed.
+    pub fn time(&self) -> Time {
+        match self {
+            ApiType::Start { time }
+            | ApiType::Init { time, .. }
+            | ApiType::SystemTask { time, .. }
+            | ApiType::Update { time, .. }
+            | ApiType::Cleanup { time, .. }
+            | ApiType::NonReplicatedQuery { time, .. }
+            | ApiType::ReplicatedQuery { time, .. }
+            | ApiType::PreUpgrade { time, .. }
+            | ApiType::ReplyCallback { time, .. }
+            | ApiType::RejectCallback { time, .. }
+            | ApiType::InspectMessage { time, .. } => *time,
+        }
+    }
 }
 
 // This type is potentially serialized and exposed to the external world.  We
@@ -749,6 +796,9 @@ pub struct SystemApiImpl {
 
     /// Tracks the complexity accumulated during the message execution.
     execution_complexity: ExecutionComplexity,
+
+    /// Log records produced during the message execution.
+    canister_log: CanisterLog,
 }
 
 impl SystemApiImpl {
@@ -786,6 +836,7 @@ impl SystemApiImpl {
             current_slice_instruction_limit: i64::try_from(slice_limit).unwrap_or(i64::MAX),
             instructions_executed_before_current_slice: 0,
             execution_complexity: ExecutionComplexity::default(),
+            canister_log: CanisterLog::default(),
         }
     }
 
@@ -966,6 +1017,22 @@ impl SystemApiImpl {
         }
     }
 
+    fn get_deadline(&self) -> Option<CoarseTime> {
+        match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => None,
+            ApiType::Update { deadline, .. }
+            | ApiType::ReplyCallback { deadline, .. }
+            | ApiType::RejectCallback { deadline, .. } => Some(*deadline),
+        }
+    }
+
     fn get_reject_context(&self) -> Option<&RejectContext> {
         match &self.api_type {
             ApiType::Start { .. }
@@ -1168,6 +1235,21 @@ impl SystemApiImpl {
         self.stable_memory().stable_memory_size
     }
 
+    /// Appends a record describing the error the execution failed with to
+    /// the log of the canister.
+    pub fn save_trap_message(&mut self, err: &HypervisorError) {
+        self.canister_log.add_record(
+            self.api_type.time().as_nanos_since_unix_epoch(),
+            format!("[TRAP]: {}", err).into_bytes(),
+        );
+    }
+
+    /// Returns the log records produced during the execution and leaves an
+    /// empty log in their place.
+    pub fn take_canister_log(&mut self) -> CanisterLog {
+        std::mem::take(&mut self.canister_log)
+    }
+
     /// Wrapper around `self.sandbox_safe_system_state.push_output_request()` that
     /// tries to allocate memory for the `Request` before pushing it.
     ///
@@ -1276,6 +1358,10 @@ impl SystemApi for SystemApiImpl {
         self.execution_parameters.subnet_type
     }
 
+    fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.sandbox_safe_system_state.wasm_memory_limit()
+    }
+
     fn message_instruction_limit(&self) -> NumInstructions {
         self.execution_parameters.instruction_limits.message()
     }
@@ -1618,6 +1704,15 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_msg_deadline(&self) -> HypervisorResult<u64> {
+        let result = self
+            .get_deadline()
+            .map(|deadline| Time::from(deadline).as_nanos_since_unix_epoch())
+            .ok_or_else(|| self.error_for("ic0_msg_deadline"));
+        trace_syscall!(self, ic0_msg_deadline, result);
+        result
+    }
+
     fn ic0_msg_reject_msg_size(&self) -> HypervisorResult<u32> {
         let reject_context = self
             .get_reject_context()
@@ -1943,6 +2038,46 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()> {
+        let time = self.api_type.time();
+        let result = match &mut self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => {
+                Err(self.error_for("ic0_call_with_best_effort_response"))
+            }
+            ApiType::Update {
+                outgoing_request, ..
+            }
+            | ApiType::SystemTask {
+                outgoing_request, ..
+            }
+            | ApiType::ReplyCallback {
+                outgoing_request, ..
+            }
+            | ApiType::RejectCallback {
+                outgoing_request, ..
+            } => match outgoing_request {
+                None => Err(HypervisorError::ContractViolation(
+                    "ic0.call_with_best_effort_response called when no call is under construction."
+                        .to_string(),
+                )),
+                Some(request) => request.set_timeout(timeout_seconds, time),
+            },
+        };
+        trace_syscall!(
+            self,
+            ic0_call_with_best_effort_response,
+            result,
+            timeout_seconds
+        );
+        result
+    }
+
     fn ic0_call_cycles_add(&mut self, amount: u64) -> HypervisorResult<()> {
         let result = self.ic0_call_cycles_add_helper("ic0_call_cycles_add", Cycles::from(amount));
         trace_syscall!(self, ic0_call_cycles_add, result, amount);
@@ -2814,6 +2949,18 @@ impl SystemApi for SystemApiImpl {
         Ok(())
     }
 
+    fn save_log_message(&mut self, src: u32, size: u32, heap: &[u8]) {
+        // Larger messages would be truncated by the log anyway.
+        let size = size.min(MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE as u32);
+        let content = match valid_subslice("save_log_message", src, size, heap) {
+            Ok(bytes) => bytes.to_vec(),
+            // Like `ic0.debug_print`, logging never fails.
+            Err(_) => b"(debug message out of memory bounds)".to_vec(),
+        };
+        self.canister_log
+            .add_record(self.api_type.time().as_nanos_since_unix_epoch(), content);
+    }
+
     fn ic0_trap(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
         const MAX_ERROR_MESSAGE_SIZE: u32 = 16 * 1024;
         let size = size.min(MAX_ERROR_MESSAGE_SIZE);
@@ -2863,6 +3010,116 @@ impl SystemApi for SystemApiImpl {
         );
         result
     }
+
+    fn ic0_cost_call(
+        &self,
+        method_name_size: u64,
+        payload_size: u64,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        let result = {
+            let cost = self
+                .sandbox_safe_system_state
+                .cost_call(method_name_size, payload_size);
+            copy_cycles_to_heap(cost, dst, heap, "ic0_cost_call")
+        };
+        trace_syscall!(
+            self,
+            ic0_cost_call,
+            result,
+            method_name_size,
+            payload_size,
+            dst,
+            summarize(heap, dst, 16)
+        );
+        result
+    }
+
+    fn ic0_cost_create_canister(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()> {
+        let result = {
+            let cost = self.sandbox_safe_system_state.cost_create_canister();
+            copy_cycles_to_heap(cost, dst, heap, "ic0_cost_create_canister")
+        };
+        trace_syscall!(
+            self,
+            ic0_cost_create_canister,
+            result,
+            dst,
+            summarize(heap, dst, 16)
+        );
+        result
+    }
+
+    fn ic0_cost_http_request(
+        &self,
+        request_size: u64,
+        max_res_bytes: u64,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        let result = {
+            let cost = self
+                .sandbox_safe_system_state
+                .cost_http_request(request_size, max_res_bytes);
+            copy_cycles_to_heap(cost, dst, heap, "ic0_cost_http_request")
+        };
+        trace_syscall!(
+            self,
+            ic0_cost_http_request,
+            result,
+            request_size,
+            max_res_bytes,
+            dst,
+            summarize(heap, dst, 16)
+        );
+        result
+    }
+
+    fn ic0_cost_sign_with_ecdsa(
+        &self,
+        src: u32,
+        size: u32,
+        curve: u32,
+        dst: u32,
+        heap: &mut [u8],
+    ) -> HypervisorResult<u32> {
+        let result = {
+            // The result must fit into the heap even if nothing is copied.
+            valid_subslice("ic0.cost_sign_with_ecdsa", dst, 16, heap)?;
+            let name = valid_subslice("ic0.cost_sign_with_ecdsa", src, size, heap)?;
+            match curve {
+                ECDSA_CURVE_SECP256K1 => {
+                    let cost = String::from_utf8(name.to_vec()).ok().and_then(|name| {
+                        self.sandbox_safe_system_state
+                            .cost_sign_with_ecdsa(&EcdsaKeyId {
+                                curve: EcdsaCurve::Secp256k1,
+                                name,
+                            })
+                    });
+                    match cost {
+                        Some(cost) => {
+                            copy_cycles_to_heap(cost, dst, heap, "ic0_cost_sign_with_ecdsa")?;
+                            Ok(0)
+                        }
+                        None => Ok(ECDSA_UNKNOWN_KEY),
+                    }
+                }
+                _ => Ok(ECDSA_UNKNOWN_CURVE),
+            }
+        };
+        trace_syscall!(
+            self,
+            ic0_cost_sign_with_ecdsa,
+            result,
+            src,
+            size,
+            curve,
+            dst,
+            summarize(heap, dst, 16)
+        );
+        result
+    }
 }
 
 /// The default implementation of the `OutOfInstructionHandler` trait.
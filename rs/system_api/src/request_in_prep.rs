@@ -1,10 +1,12 @@
-use crate::{sandbox_safe_system_state::SandboxSafeSystemState, valid_subslice};
+use crate::{
+    sandbox_safe_system_state::SandboxSafeSystemState, valid_subslice, MAX_CALL_TIMEOUT_SECONDS,
+};
 use ic_interfaces::execution_environment::{HypervisorError, HypervisorResult};
 use ic_logger::ReplicaLogger;
 use ic_types::{
-    messages::{CallContextId, Request},
+    messages::{CallContextId, Request, NO_DEADLINE},
     methods::{Callback, WasmClosure},
-    CanisterId, Cycles, NumBytes, PrincipalId,
+    CanisterId, CoarseTime, Cycles, NumBytes, PrincipalId, Time,
 };
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -37,6 +39,9 @@ pub struct RequestInPrep {
     cycles: Cycles,
     method_name: String,
     method_payload: Vec<u8>,
+    /// Deadline of the call, set by `ic0.call_with_best_effort_response`.
+    /// `None` for guaranteed response calls.
+    deadline: Option<CoarseTime>,
     /// The maximum size of a message that will go to a canister on another
     /// subnet.
     max_size_remote_subnet: NumBytes,
@@ -94,6 +99,7 @@ impl RequestInPrep {
             cycles: Cycles::zero(),
             method_name,
             method_payload: Vec::new(),
+            deadline: None,
             max_size_remote_subnet,
             multiplier_max_size_local_subnet,
         })
@@ -111,6 +117,25 @@ impl RequestInPrep {
         }
     }
 
+    /// Turns the call into a best-effort call, with a deadline of `time` plus
+    /// `timeout_seconds` (capped at `MAX_CALL_TIMEOUT_SECONDS`).
+    pub(crate) fn set_timeout(&mut self, timeout_seconds: u32, time: Time) -> HypervisorResult<()> {
+        if self.deadline.is_some() {
+            return Err(HypervisorError::ContractViolation(
+                "ic0.call_with_best_effort_response can be called at most once between `ic0.call_new` and `ic0.call_perform`"
+                    .to_string(),
+            ));
+        }
+
+        let timeout_seconds = timeout_seconds.min(MAX_CALL_TIMEOUT_SECONDS);
+        self.deadline = Some(CoarseTime::from_secs_since_unix_epoch(
+            CoarseTime::floor(time)
+                .as_secs_since_unix_epoch()
+                .saturating_add(timeout_seconds),
+        ));
+        Ok(())
+    }
+
     pub(crate) fn take_cycles(self) -> Cycles {
         self.cycles
     }
@@ -159,6 +184,7 @@ pub(crate) fn into_request(
         cycles,
         method_name,
         method_payload,
+        deadline,
         max_size_remote_subnet,
         multiplier_max_size_local_subnet,
     }: RequestInPrep,
@@ -169,6 +195,13 @@ pub(crate) fn into_request(
     let destination_canister =
         CanisterId::new(callee).map_err(HypervisorError::InvalidCanisterId)?;
 
+    let deadline = deadline.unwrap_or(NO_DEADLINE);
+    if deadline != NO_DEADLINE && sandbox_safe_system_state.is_ic00_alias(&destination_canister) {
+        return Err(HypervisorError::ContractViolation(
+            "Best-effort calls to the management canister are not supported.".to_string(),
+        ));
+    }
+
     let payload_size = (method_name.len() + method_payload.len()) as u64;
     {
         let max_size_local_subnet = max_size_remote_subnet * multiplier_max_size_local_subnet;
@@ -195,6 +228,7 @@ pub(crate) fn into_request(
         on_reply,
         on_reject,
         on_cleanup,
+        deadline,
     ))?;
 
     let req = Request {
@@ -204,6 +238,7 @@ pub(crate) fn into_request(
         method_payload,
         sender_reply_callback: callback_id,
         payment: cycles,
+        deadline,
     };
     // We cannot call `Request::payload_size_bytes()` before constructing the
     // request, so ensure our separate calculation matches the actual size.
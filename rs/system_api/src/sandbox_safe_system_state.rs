@@ -6,7 +6,8 @@ use ic_constants::{LOG_CANISTER_OPERATION_CYCLES_THRESHOLD, SMALL_APP_SUBNET_MAX
 use ic_cycles_account_manager::{CyclesAccountManager, CyclesAccountManagerError};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    CreateCanisterArgs, InstallCodeArgs, Method as Ic00Method, Payload,
+    CreateCanisterArgs, EcdsaKeyId, InstallChunkedCodeArgs, InstallCodeArgs,
+    LoadCanisterSnapshotArgs, Method as Ic00Method, Payload,
     ProvisionalCreateCanisterWithCyclesArgs, SetControllerArgs, UninstallCodeArgs,
     UpdateSettingsArgs, IC_00,
 };
@@ -207,6 +208,10 @@ impl SystemStateChanges {
                 .map(|record| record.get_sender_canister_version()),
             Ok(Ic00Method::UninstallCode) => UninstallCodeArgs::decode(payload)
                 .map(|record| record.get_sender_canister_version()),
+            Ok(Ic00Method::LoadCanisterSnapshot) => LoadCanisterSnapshotArgs::decode(payload)
+                .map(|record| record.get_sender_canister_version()),
+            Ok(Ic00Method::InstallChunkedCode) => InstallChunkedCodeArgs::decode(payload)
+                .map(|record| record.get_sender_canister_version()),
             Ok(Ic00Method::ProvisionalCreateCanisterWithCycles) => {
                 ProvisionalCreateCanisterWithCyclesArgs::decode(payload)
                     .map(|record| record.get_sender_canister_version())
@@ -223,6 +228,13 @@ impl SystemStateChanges {
             | Ok(Ic00Method::ECDSAPublicKey)
             | Ok(Ic00Method::ComputeInitialEcdsaDealings)
             | Ok(Ic00Method::ProvisionalTopUpCanister)
+            | Ok(Ic00Method::TakeCanisterSnapshot)
+            | Ok(Ic00Method::ListCanisterSnapshots)
+            | Ok(Ic00Method::DeleteCanisterSnapshot)
+            | Ok(Ic00Method::FetchCanisterLogs)
+            | Ok(Ic00Method::UploadChunk)
+            | Ok(Ic00Method::StoredChunks)
+            | Ok(Ic00Method::ClearChunkStore)
             | Ok(Ic00Method::BitcoinSendTransactionInternal)
             | Ok(Ic00Method::BitcoinGetSuccessors)
             | Ok(Ic00Method::BitcoinGetBalance)
@@ -290,7 +302,7 @@ impl SystemStateChanges {
                 })?;
                 if (*amount_taken).get() > LOG_CANISTER_OPERATION_CYCLES_THRESHOLD {
                     match call_context.call_origin() {
-                        CallOrigin::CanisterUpdate(origin_canister_id, _)
+                        CallOrigin::CanisterUpdate(origin_canister_id, _, _)
                         | CallOrigin::CanisterQuery(origin_canister_id, _) => info!(
                             logger,
                             "Canister {} accepted {} cycles from canister {}.",
@@ -498,6 +510,8 @@ pub struct SandboxSafeSystemState {
     pub(super) status: CanisterStatusView,
     pub(super) subnet_type: SubnetType,
     pub(super) subnet_size: usize,
+    /// The sizes of the subnets signing with each threshold ECDSA key.
+    ecdsa_signing_subnet_sizes: BTreeMap<EcdsaKeyId, usize>,
     dirty_page_overhead: NumInstructions,
     freeze_threshold: NumSeconds,
     memory_allocation: MemoryAllocation,
@@ -514,6 +528,7 @@ pub struct SandboxSafeSystemState {
     global_timer: CanisterTimer,
     canister_version: u64,
     controllers: BTreeSet<PrincipalId>,
+    wasm_memory_limit: Option<NumBytes>,
 }
 
 impl SandboxSafeSystemState {
@@ -534,10 +549,12 @@ impl SandboxSafeSystemState {
         ic00_available_request_slots: usize,
         ic00_aliases: BTreeSet<CanisterId>,
         subnet_size: usize,
+        ecdsa_signing_subnet_sizes: BTreeMap<EcdsaKeyId, usize>,
         dirty_page_overhead: NumInstructions,
         global_timer: CanisterTimer,
         canister_version: u64,
         controllers: BTreeSet<PrincipalId>,
+        wasm_memory_limit: Option<NumBytes>,
     ) -> Self {
         Self {
             canister_id,
@@ -545,6 +562,7 @@ impl SandboxSafeSystemState {
             status,
             subnet_type: cycles_account_manager.subnet_type(),
             subnet_size,
+            ecdsa_signing_subnet_sizes,
             dirty_page_overhead,
             freeze_threshold,
             memory_allocation,
@@ -559,6 +577,7 @@ impl SandboxSafeSystemState {
             global_timer,
             canister_version,
             controllers,
+            wasm_memory_limit,
         }
     }
 
@@ -599,6 +618,18 @@ impl SandboxSafeSystemState {
         let subnet_size = network_topology
             .get_subnet_size(&cycles_account_manager.get_subnet_id())
             .unwrap_or(SMALL_APP_SUBNET_MAX_SIZE);
+        // Signatures are requested from the first signing subnet of the key,
+        // which charges the fee for its own size.
+        let ecdsa_signing_subnet_sizes = network_topology
+            .ecdsa_signing_subnets
+            .iter()
+            .filter_map(|(key_id, subnet_ids)| {
+                let subnet_size = network_topology
+                    .get_subnet_size(subnet_ids.first()?)
+                    .unwrap_or(SMALL_APP_SUBNET_MAX_SIZE);
+                Some((key_id.clone(), subnet_size))
+            })
+            .collect();
 
         Self::new_internal(
             system_state.canister_id,
@@ -616,10 +647,12 @@ impl SandboxSafeSystemState {
             ic00_available_request_slots,
             ic00_aliases,
             subnet_size,
+            ecdsa_signing_subnet_sizes,
             dirty_page_overhead,
             system_state.global_timer,
             system_state.canister_version,
             system_state.controllers.clone(),
+            system_state.wasm_memory_limit,
         )
     }
 
@@ -627,6 +660,11 @@ impl SandboxSafeSystemState {
         self.canister_id
     }
 
+    /// Returns the upper limit on the Wasm heap memory of the canister, if any.
+    pub fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.wasm_memory_limit
+    }
+
     pub fn global_timer(&self) -> CanisterTimer {
         self.global_timer
     }
@@ -785,6 +823,44 @@ impl SandboxSafeSystemState {
             .prepayment_for_response_transmission(self.subnet_size)
     }
 
+    /// Returns the amount of cycles withdrawn from the canister balance when
+    /// it performs an inter-canister call with the given sizes, including
+    /// the prepayment for the response.
+    pub(super) fn cost_call(&self, method_name_size: u64, payload_size: u64) -> Cycles {
+        let bytes = NumBytes::from(method_name_size.saturating_add(payload_size));
+        self.cycles_account_manager
+            .xnet_call_performed_fee(self.subnet_size)
+            + self
+                .cycles_account_manager
+                .xnet_call_bytes_transmitted_fee(bytes, self.subnet_size)
+            + self.prepayment_for_response_transmission()
+            + self.prepayment_for_response_execution()
+    }
+
+    /// Returns the fee for creating a canister on this subnet.
+    pub(super) fn cost_create_canister(&self) -> Cycles {
+        self.cycles_account_manager
+            .canister_creation_fee(self.subnet_size)
+    }
+
+    /// Returns the fee for an HTTP outcall with the given request size and
+    /// maximum response size.
+    pub(super) fn cost_http_request(&self, request_size: u64, max_res_bytes: u64) -> Cycles {
+        self.cycles_account_manager.http_request_fee(
+            NumBytes::from(request_size),
+            Some(NumBytes::from(max_res_bytes)),
+            self.subnet_size,
+        )
+    }
+
+    /// Returns the fee for a threshold ECDSA signature with the given key,
+    /// scaled for the size of the subnet signing with it, or `None` if no
+    /// subnet signs with the key.
+    pub(super) fn cost_sign_with_ecdsa(&self, key_id: &EcdsaKeyId) -> Option<Cycles> {
+        let subnet_size = *self.ecdsa_signing_subnet_sizes.get(key_id)?;
+        Some(self.cycles_account_manager.ecdsa_signature_fee(subnet_size))
+    }
+
     pub(super) fn withdraw_cycles_for_transfer(
         &mut self,
         canister_current_memory_usage: NumBytes,
@@ -863,6 +939,12 @@ impl SandboxSafeSystemState {
     }
 
     /// Calculate the cost for newly created dirty pages.
+    /// Returns `true` if `canister_id` is the management canister or one of
+    /// its aliases (i.e. a subnet ID).
+    pub(super) fn is_ic00_alias(&self, canister_id: &CanisterId) -> bool {
+        self.ic00_aliases.contains(canister_id)
+    }
+
     pub fn dirty_page_cost(&self, dirty_pages: NumPages) -> HypervisorResult<NumInstructions> {
         let (inst, overflow) = dirty_pages
             .get()
@@ -21,7 +21,7 @@ use ic_test_utilities::{
     types::ids::{call_context_test_id, canister_test_id, subnet_test_id, user_test_id},
 };
 use ic_types::{
-    messages::{CallContextId, CallbackId, RejectContext},
+    messages::{CallContextId, CallbackId, RejectContext, NO_DEADLINE},
     methods::SystemMethod,
     ComputeAllocation, Cycles, NumInstructions, Time,
 };
@@ -78,6 +78,7 @@ impl ApiTypeBuilder {
             Cycles::zero(),
             user_test_id(1).get(),
             CallContextId::from(1),
+            NO_DEADLINE,
         )
     }
 
@@ -97,6 +98,7 @@ impl ApiTypeBuilder {
             CallContextId::new(1),
             false,
             ExecutionMode::Replicated,
+            NO_DEADLINE,
         )
     }
 
@@ -108,6 +110,7 @@ impl ApiTypeBuilder {
             call_context_test_id(1),
             false,
             ExecutionMode::Replicated,
+            NO_DEADLINE,
         )
     }
 }
@@ -144,7 +147,7 @@ pub fn get_system_state() -> SystemState {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
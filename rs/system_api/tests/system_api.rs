@@ -1,35 +1,41 @@
+use assert_matches::assert_matches;
 use ic_base_types::{NumSeconds, PrincipalIdBlobParseError};
 use ic_config::{
     embedders::Config as EmbeddersConfig, flag_status::FlagStatus, subnet_config::SchedulerConfig,
 };
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
 use ic_error_types::RejectCode;
+use ic_ic00_types::{EcdsaCurve, EcdsaKeyId};
 use ic_interfaces::execution_environment::{
-    CanisterOutOfCyclesError, HypervisorError, HypervisorResult, PerformanceCounterType,
-    SubnetAvailableMemory, SystemApi, TrapCode,
+    CanisterOutOfCyclesError, ExecutionMode, HypervisorError, HypervisorResult,
+    PerformanceCounterType, SubnetAvailableMemory, SystemApi, TrapCode,
 };
 use ic_logger::replica_logger::no_op_logger;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    testing::CanisterQueuesTesting, CallOrigin, Memory, NetworkTopology, SystemState,
+    testing::CanisterQueuesTesting, CallOrigin, Memory, NetworkTopology, SubnetTopology,
+    SystemState,
 };
 use ic_system_api::{
     sandbox_safe_system_state::SandboxSafeSystemState, ApiType, DefaultOutOfInstructionsHandler,
-    NonReplicatedQueryKind, SystemApiImpl,
+    NonReplicatedQueryKind, SystemApiImpl, MAX_CALL_TIMEOUT_SECONDS,
 };
 use ic_test_utilities::{
     cycles_account_manager::CyclesAccountManagerBuilder,
     mock_time,
     state::SystemStateBuilder,
     types::{
-        ids::{call_context_test_id, canister_test_id, subnet_test_id, user_test_id},
+        ids::{call_context_test_id, canister_test_id, node_test_id, subnet_test_id, user_test_id},
         messages::RequestBuilder,
     },
 };
 use ic_types::{
-    messages::{CallContextId, CallbackId, RejectContext, MAX_RESPONSE_COUNT_BYTES},
+    messages::{
+        CallContextId, CallbackId, RejectContext, RequestOrResponse, MAX_RESPONSE_COUNT_BYTES,
+        NO_DEADLINE,
+    },
     methods::{Callback, WasmClosure},
-    time, CanisterTimer, CountBytes, Cycles, NumBytes, NumInstructions, Time,
+    time, CanisterTimer, CoarseTime, CountBytes, Cycles, NumBytes, NumInstructions, Time,
 };
 use std::{
     collections::BTreeSet,
@@ -147,6 +153,7 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -158,6 +165,7 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -205,6 +213,7 @@ fn test_canister_update_support() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -216,6 +225,7 @@ fn test_canister_update_support() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -263,6 +273,7 @@ fn test_canister_replicated_query_support() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -274,6 +285,7 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -321,6 +333,7 @@ fn test_canister_pure_query_support() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -332,6 +345,7 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -389,6 +403,7 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -400,6 +415,7 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -447,6 +463,7 @@ fn test_reply_api_support_on_nns() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject_code());
+    assert_api_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -458,6 +475,7 @@ fn test_reply_api_support_on_nns() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -505,6 +523,7 @@ fn test_reply_api_support_non_nns() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject_code());
+    assert_api_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -516,6 +535,7 @@ fn test_reply_api_support_non_nns() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -566,6 +586,7 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject_code());
+    assert_api_supported(api.ic0_msg_deadline());
     assert_api_supported(api.ic0_msg_reject_msg_size());
     assert_api_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -577,6 +598,7 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -627,6 +649,7 @@ fn test_reject_api_support_non_nns() {
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject_code());
+    assert_api_supported(api.ic0_msg_deadline());
     assert_api_supported(api.ic0_msg_reject_msg_size());
     assert_api_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -638,6 +661,7 @@ fn test_reject_api_support_non_nns() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -685,6 +709,7 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -696,6 +721,7 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -743,6 +769,7 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_canister_self_size());
@@ -754,6 +781,7 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -801,6 +829,7 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -812,6 +841,7 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -864,6 +894,7 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -875,6 +906,7 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
@@ -923,6 +955,7 @@ fn test_canister_heartbeat_support() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -934,6 +967,7 @@ fn test_canister_heartbeat_support() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -981,6 +1015,7 @@ fn test_canister_heartbeat_support_nns() {
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
+    assert_api_not_supported(api.ic0_msg_deadline());
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
@@ -992,6 +1027,7 @@ fn test_canister_heartbeat_support_nns() {
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
@@ -1166,7 +1202,7 @@ fn test_canister_balance() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1194,7 +1230,7 @@ fn test_canister_cycle_balance() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1228,7 +1264,7 @@ fn test_msg_cycles_available_traps() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             available_cycles,
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1389,7 +1425,7 @@ fn msg_cycles_accept_all_cycles_in_call_context() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::from(amount),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1412,7 +1448,7 @@ fn msg_cycles_accept_all_cycles_in_call_context_when_more_asked() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(40),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1443,7 +1479,7 @@ fn call_perform_not_enough_cycles_resets_state() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(40),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1472,6 +1508,98 @@ fn call_perform_not_enough_cycles_resets_state() {
     assert_eq!(call_context_manager.callbacks().len(), 0);
 }
 
+#[test]
+fn call_with_best_effort_response_sets_deadline() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut system_state = SystemStateBuilder::default().build();
+    let own_canister_id = system_state.canister_id;
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+    assert_eq!(api.ic0_msg_deadline(), Ok(0));
+
+    api.ic0_call_new(0, 10, 0, 10, 0, 0, 0, 0, &[0; 1024])
+        .unwrap();
+    // The timeout is capped at `MAX_CALL_TIMEOUT_SECONDS`.
+    api.ic0_call_with_best_effort_response(MAX_CALL_TIMEOUT_SECONDS + 1)
+        .unwrap();
+    // Can only be called once per call.
+    api.ic0_call_with_best_effort_response(10).unwrap_err();
+    assert_eq!(api.ic0_call_perform().unwrap(), 0);
+
+    let system_state_changes = api.into_system_state_changes();
+    system_state_changes
+        .apply_changes(
+            mock_time(),
+            &mut system_state,
+            &default_network_topology(),
+            subnet_test_id(1),
+            &no_op_logger(),
+        )
+        .unwrap();
+
+    let expected_deadline = CoarseTime::from_secs_since_unix_epoch(
+        CoarseTime::floor(mock_time()).as_secs_since_unix_epoch() + MAX_CALL_TIMEOUT_SECONDS,
+    );
+    match system_state.output_into_iter(own_canister_id).next() {
+        Some((_, RequestOrResponse::Request(request))) => {
+            assert_eq!(expected_deadline, request.deadline)
+        }
+        other => panic!("Expected a request, got {:?}", other),
+    }
+    let callbacks = system_state.call_context_manager().unwrap().callbacks();
+    assert_eq!(1, callbacks.len());
+    assert!(callbacks
+        .values()
+        .all(|callback| callback.deadline == expected_deadline));
+}
+
+#[test]
+fn best_effort_call_to_management_canister_fails() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let system_state = SystemStateBuilder::default().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+
+    // An empty callee is the management canister.
+    api.ic0_call_new(0, 0, 0, 10, 0, 0, 0, 0, &[0; 1024])
+        .unwrap();
+    api.ic0_call_with_best_effort_response(10).unwrap();
+    assert_matches!(
+        api.ic0_call_perform(),
+        Err(HypervisorError::ContractViolation(_))
+    );
+}
+
+#[test]
+fn msg_deadline_in_reply_callback() {
+    let deadline = CoarseTime::from_secs_since_unix_epoch(13);
+    let api_type = ApiType::reply_callback(
+        mock_time(),
+        vec![],
+        Cycles::zero(),
+        CallContextId::new(1),
+        false,
+        ExecutionMode::Replicated,
+        deadline,
+    );
+    let api = get_system_api(
+        api_type,
+        &SystemStateBuilder::default().build(),
+        CyclesAccountManagerBuilder::new().build(),
+    );
+
+    assert_eq!(
+        Ok(Time::from(deadline).as_nanos_since_unix_epoch()),
+        api.ic0_msg_deadline()
+    );
+}
+
 #[test]
 fn update_available_memory_updates_subnet_available_memory() {
     let wasm_page_size = 64 << 10;
@@ -1545,6 +1673,7 @@ fn take_execution_result_properly_frees_memory() {
             WasmClosure::new(0, 0),
             WasmClosure::new(0, 0),
             None,
+            NO_DEADLINE,
         ))
         .unwrap();
     let mut api = SystemApiImpl::new(
@@ -1621,6 +1750,7 @@ fn push_output_request_respects_memory_limits() {
                 WasmClosure::new(0, 0),
                 WasmClosure::new(0, 0),
                 None,
+                NO_DEADLINE,
             ))
             .unwrap();
         let mut api = SystemApiImpl::new(
@@ -1727,6 +1857,7 @@ fn push_output_request_oversized_request_memory_limits() {
             WasmClosure::new(0, 0),
             WasmClosure::new(0, 0),
             None,
+            NO_DEADLINE,
         ))
         .unwrap();
     let mut api = SystemApiImpl::new(
@@ -1876,3 +2007,116 @@ fn ic0_is_controller_invalid_principal_id() {
         ))
     ));
 }
+
+#[test]
+fn ic0_cost_apis_return_fees_scaled_for_subnet_size() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &SystemStateBuilder::default().build(),
+        cycles_account_manager,
+    );
+    let subnet_size = SMALL_APP_SUBNET_MAX_SIZE;
+    let mut heap = vec![0; 16];
+
+    api.ic0_cost_call(10, 100, 0, &mut heap).unwrap();
+    let expected = cycles_account_manager.xnet_call_performed_fee(subnet_size)
+        + cycles_account_manager.xnet_call_bytes_transmitted_fee(NumBytes::from(110), subnet_size)
+        + cycles_account_manager.prepayment_for_response_transmission(subnet_size)
+        + cycles_account_manager.prepayment_for_response_execution(subnet_size);
+    assert_eq!(heap, expected.get().to_le_bytes());
+
+    api.ic0_cost_create_canister(0, &mut heap).unwrap();
+    assert_eq!(
+        heap,
+        cycles_account_manager
+            .canister_creation_fee(subnet_size)
+            .get()
+            .to_le_bytes()
+    );
+
+    api.ic0_cost_http_request(100, 2_000, 0, &mut heap).unwrap();
+    let expected = cycles_account_manager.http_request_fee(
+        NumBytes::from(100),
+        Some(NumBytes::from(2_000)),
+        subnet_size,
+    );
+    assert_eq!(heap, expected.get().to_le_bytes());
+}
+
+#[test]
+fn ic0_cost_sign_with_ecdsa_is_scaled_for_signing_subnet_size() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let key_id = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256k1,
+        name: "key_1".to_string(),
+    };
+    let signing_subnet_id = subnet_test_id(2);
+    let signing_subnet_size = 34;
+    let mut network_topology = default_network_topology();
+    network_topology.subnets.insert(
+        signing_subnet_id,
+        SubnetTopology {
+            nodes: (0..signing_subnet_size as u64).map(node_test_id).collect(),
+            ..SubnetTopology::default()
+        },
+    );
+    network_topology
+        .ecdsa_signing_subnets
+        .insert(key_id, vec![signing_subnet_id]);
+    let sandbox_safe_system_state = SandboxSafeSystemState::new(
+        &SystemStateBuilder::default().build(),
+        cycles_account_manager,
+        &network_topology,
+        SchedulerConfig::application_subnet().dirty_page_overhead,
+    );
+    let api = SystemApiImpl::new(
+        ApiTypeBuilder::build_update_api(),
+        sandbox_safe_system_state,
+        CANISTER_CURRENT_MEMORY_USAGE,
+        execution_parameters(),
+        SubnetAvailableMemory::new(i64::MAX / 2, i64::MAX / 2, i64::MAX / 2),
+        EmbeddersConfig::default()
+            .feature_flags
+            .wasm_native_stable_memory,
+        Memory::new_for_testing(),
+        Arc::new(DefaultOutOfInstructionsHandler {}),
+        no_op_logger(),
+    );
+
+    // The key name is placed right after the 16 bytes for the result.
+    let mut heap = vec![0; 16];
+    heap.extend_from_slice(b"key_1");
+    assert_eq!(api.ic0_cost_sign_with_ecdsa(16, 5, 0, 0, &mut heap), Ok(0));
+    assert_eq!(
+        heap[0..16],
+        cycles_account_manager
+            .ecdsa_signature_fee(signing_subnet_size)
+            .get()
+            .to_le_bytes()
+    );
+
+    // No subnet signs with the key.
+    let mut heap = vec![0; 16];
+    heap.extend_from_slice(b"key_2");
+    assert_eq!(api.ic0_cost_sign_with_ecdsa(16, 5, 0, 0, &mut heap), Ok(2));
+    assert_eq!(heap[0..16], [0; 16]);
+}
+
+#[test]
+fn ic0_cost_sign_with_ecdsa_rejects_unknown_curve() {
+    let api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &SystemStateBuilder::default().build(),
+        CyclesAccountManagerBuilder::new().build(),
+    );
+    let mut heap = vec![0; 16];
+    assert_eq!(api.ic0_cost_sign_with_ecdsa(0, 0, 1, 0, &mut heap), Ok(1));
+    assert_eq!(heap, vec![0; 16]);
+
+    // The result does not fit into the heap.
+    assert!(matches!(
+        api.ic0_cost_sign_with_ecdsa(0, 0, 0, 8, &mut heap),
+        Err(HypervisorError::ContractViolation(..))
+    ));
+}
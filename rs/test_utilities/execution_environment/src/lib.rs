@@ -568,6 +568,23 @@ impl ExecutionTest {
         self.subnet_message(Method::UpdateSettings, payload)
     }
 
+    /// Updates the reserved cycles limit of the given canister.
+    pub fn canister_update_reserved_cycles_limit(
+        &mut self,
+        canister_id: CanisterId,
+        reserved_cycles_limit: Cycles,
+    ) -> Result<WasmResult, UserError> {
+        let payload = UpdateSettingsArgs {
+            canister_id: canister_id.into(),
+            settings: CanisterSettingsArgsBuilder::new()
+                .with_reserved_cycles_limit(reserved_cycles_limit.get())
+                .build(),
+            sender_canister_version: None,
+        }
+        .encode();
+        self.subnet_message(Method::UpdateSettings, payload)
+    }
+
     /// Sets the controller of the canister to the given principal.
     pub fn set_controller(
         &mut self,
@@ -815,6 +832,9 @@ impl ExecutionTest {
             instructions: RoundInstructions::from(i64::MAX),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: self.subnet_available_memory,
+            subnet_memory_saturation: self
+                .exec_env
+                .subnet_memory_saturation(&self.subnet_available_memory),
             compute_allocation_used,
         };
         let instruction_limits = InstructionLimits::new(
@@ -835,6 +855,12 @@ impl ExecutionTest {
                     .task_queue
                     .push_front(ExecutionTask::GlobalTimer);
             }
+            CanisterTask::OnLowWasmMemory => {
+                canister
+                    .system_state
+                    .task_queue
+                    .push_front(ExecutionTask::OnLowWasmMemory);
+            }
         }
         let result = execute_canister(
             &self.exec_env,
@@ -917,6 +943,9 @@ impl ExecutionTest {
             instructions: RoundInstructions::from(i64::MAX),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: self.subnet_available_memory,
+            subnet_memory_saturation: self
+                .exec_env
+                .subnet_memory_saturation(&self.subnet_available_memory),
             compute_allocation_used,
         };
         let result = self.exec_env.execute_canister_response(
@@ -1014,6 +1043,9 @@ impl ExecutionTest {
             instructions: RoundInstructions::from(i64::MAX),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: self.subnet_available_memory,
+            subnet_memory_saturation: self
+                .exec_env
+                .subnet_memory_saturation(&self.subnet_available_memory),
             compute_allocation_used,
         };
         let (new_state, instructions_used) = self.exec_env.execute_subnet_message(
@@ -1065,6 +1097,9 @@ impl ExecutionTest {
             instructions: RoundInstructions::from(i64::MAX),
             execution_complexity: ExecutionComplexity::MAX,
             subnet_available_memory: self.subnet_available_memory,
+            subnet_memory_saturation: self
+                .exec_env
+                .subnet_memory_saturation(&self.subnet_available_memory),
             compute_allocation_used,
         };
         for canister_id in canister_ids {
@@ -1140,6 +1175,9 @@ impl ExecutionTest {
                     instructions: RoundInstructions::from(i64::MAX),
                     execution_complexity: ExecutionComplexity::MAX,
                     subnet_available_memory: self.subnet_available_memory,
+                    subnet_memory_saturation: self
+                        .exec_env
+                        .subnet_memory_saturation(&self.subnet_available_memory),
                     compute_allocation_used,
                 };
                 let (new_state, instructions_used) = self.exec_env.resume_install_code(
@@ -1164,6 +1202,9 @@ impl ExecutionTest {
                     instructions: RoundInstructions::from(i64::MAX),
                     execution_complexity: ExecutionComplexity::MAX,
                     subnet_available_memory: self.subnet_available_memory,
+                    subnet_memory_saturation: self
+                        .exec_env
+                        .subnet_memory_saturation(&self.subnet_available_memory),
                     compute_allocation_used,
                 };
                 let result = execute_canister(
@@ -1416,6 +1457,7 @@ pub struct ExecutionTestBuilder {
     instruction_limit_without_dts: NumInstructions,
     initial_canister_cycles: Cycles,
     subnet_total_memory: i64,
+    subnet_memory_threshold: i64,
     subnet_message_memory: i64,
     subnet_wasm_custom_sections_memory: i64,
     registry_settings: RegistryExecutionSettings,
@@ -1444,6 +1486,9 @@ impl Default for ExecutionTestBuilder {
         let subnet_total_memory = ic_config::execution_environment::Config::default()
             .subnet_memory_capacity
             .get() as i64;
+        let subnet_memory_threshold = ic_config::execution_environment::Config::default()
+            .subnet_memory_threshold
+            .get() as i64;
         let subnet_message_memory = ic_config::execution_environment::Config::default()
             .subnet_message_memory_capacity
             .get() as i64;
@@ -1470,6 +1515,7 @@ impl Default for ExecutionTestBuilder {
                 .max_instructions_per_message_without_dts,
             initial_canister_cycles: INITIAL_CANISTER_CYCLES,
             subnet_total_memory,
+            subnet_memory_threshold,
             subnet_message_memory,
             subnet_wasm_custom_sections_memory,
             registry_settings: test_registry_settings(),
@@ -1607,6 +1653,13 @@ impl ExecutionTestBuilder {
         }
     }
 
+    pub fn with_subnet_memory_threshold(self, subnet_memory_threshold: i64) -> Self {
+        Self {
+            subnet_memory_threshold,
+            ..self
+        }
+    }
+
     pub fn with_subnet_message_memory(self, subnet_message_memory: i64) -> Self {
         Self {
             subnet_message_memory,
@@ -1858,6 +1911,7 @@ impl ExecutionTestBuilder {
             query_cache_capacity: self.query_cache_capacity.into(),
             allocatable_compute_capacity_in_percent: self.allocatable_compute_capacity_in_percent,
             subnet_memory_capacity: NumBytes::from(self.subnet_total_memory as u64),
+            subnet_memory_threshold: NumBytes::from(self.subnet_memory_threshold as u64),
             subnet_message_memory_capacity: NumBytes::from(self.subnet_message_memory as u64),
             bitcoin: BitcoinConfig {
                 privileged_access: self.bitcoin_privileged_access,
@@ -1905,6 +1959,8 @@ impl ExecutionTestBuilder {
             config.clone(),
             Arc::clone(&cycles_account_manager),
         );
+        let (query_stats_collector, _) =
+            ic_query_stats::init_query_stats(self.log.clone(), config.query_stats_epoch_length);
         let query_handler = InternalHttpQueryHandler::new(
             self.log.clone(),
             hypervisor,
@@ -1913,6 +1969,7 @@ impl ExecutionTestBuilder {
             &metrics_registry,
             self.instruction_limit_without_dts,
             Arc::clone(&cycles_account_manager),
+            query_stats_collector,
         );
         ExecutionTest {
             state: Some(state),
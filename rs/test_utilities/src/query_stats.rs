@@ -0,0 +1,45 @@
+use ic_base_types::NumBytes;
+use ic_interfaces::{
+    batch_payload::{
+        BatchPayloadBuilder, BatchPayloadValidationError, PastPayload, ProposalContext,
+    },
+    validation::ValidationResult,
+};
+use ic_types::batch::{QueryStatsPayload, ValidationContext};
+
+#[derive(Default)]
+pub struct FakeQueryStatsPayloadBuilder(Option<QueryStatsPayload>);
+
+impl FakeQueryStatsPayloadBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_payload(mut self, payload: QueryStatsPayload) -> Self {
+        self.0 = Some(payload);
+        self
+    }
+}
+
+impl BatchPayloadBuilder for FakeQueryStatsPayloadBuilder {
+    fn build_payload(
+        &self,
+        _max_size: NumBytes,
+        _past_payloads: &[PastPayload],
+        _context: &ValidationContext,
+    ) -> Vec<u8> {
+        self.0
+            .as_ref()
+            .map(|payload| payload.serialize())
+            .unwrap_or_default()
+    }
+
+    fn validate_payload(
+        &self,
+        _payload: &[u8],
+        _past_payloads: &[PastPayload],
+        _proposal_context: &ProposalContext,
+    ) -> ValidationResult<BatchPayloadValidationError> {
+        Ok(())
+    }
+}
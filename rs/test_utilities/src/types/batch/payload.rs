@@ -15,6 +15,7 @@ impl Default for PayloadBuilder {
                 // TODO(MR-70): use payload builder
                 self_validating: SelfValidatingPayload::default(),
                 canister_http: CanisterHttpPayload::default(),
+                query_stats: vec![],
             },
         }
     }
@@ -22,6 +22,7 @@ pub enum RejectCode {
     DestinationInvalid = 3,
     CanisterReject = 4,
     CanisterError = 5,
+    SysUnknown = 6,
 }
 
 impl ToString for RejectCode {
@@ -38,6 +39,7 @@ impl RejectCode {
             RejectCode::DestinationInvalid => "DESTINATION_INVALID",
             RejectCode::CanisterReject => "CANISTER_REJECT",
             RejectCode::CanisterError => "CANISTER_ERROR",
+            RejectCode::SysUnknown => "SYS_UNKNOWN",
         }
     }
 }
@@ -51,6 +53,7 @@ impl TryFrom<u64> for RejectCode {
             3 => Ok(RejectCode::DestinationInvalid),
             4 => Ok(RejectCode::CanisterReject),
             5 => Ok(RejectCode::CanisterError),
+            6 => Ok(RejectCode::SysUnknown),
             _ => Err(TryFromError::ValueOutOfRange(code)),
         }
     }
@@ -103,6 +106,8 @@ impl From<ErrorCode> for RejectCode {
             CompositeQueryCalledInReplicatedMode => CanisterError,
             CanisterNotHostedBySubnet => CanisterReject,
             QueryTimeLimitExceeded => CanisterError,
+            ReservedCyclesLimitExceededInMemoryAllocation => CanisterError,
+            ReservedCyclesLimitExceededInMemoryGrow => CanisterError,
         }
     }
 }
@@ -157,6 +162,8 @@ pub enum ErrorCode {
     QueryCallGraphTotalInstructionLimitExceeded = 526,
     CompositeQueryCalledInReplicatedMode = 527,
     QueryTimeLimitExceeded = 528,
+    ReservedCyclesLimitExceededInMemoryAllocation = 529,
+    ReservedCyclesLimitExceededInMemoryGrow = 530,
 }
 
 impl TryFrom<u64> for ErrorCode {
@@ -205,6 +212,8 @@ impl TryFrom<u64> for ErrorCode {
             526 => Ok(ErrorCode::QueryCallGraphTotalInstructionLimitExceeded),
             527 => Ok(ErrorCode::CompositeQueryCalledInReplicatedMode),
             528 => Ok(ErrorCode::QueryTimeLimitExceeded),
+            529 => Ok(ErrorCode::ReservedCyclesLimitExceededInMemoryAllocation),
+            530 => Ok(ErrorCode::ReservedCyclesLimitExceededInMemoryGrow),
             _ => Err(TryFromError::ValueOutOfRange(err)),
         }
     }
@@ -296,7 +305,9 @@ impl UserError {
             | ErrorCode::QueryCallGraphTooDeep
             | ErrorCode::QueryCallGraphTotalInstructionLimitExceeded
             | ErrorCode::CompositeQueryCalledInReplicatedMode
-            | ErrorCode::QueryTimeLimitExceeded => false,
+            | ErrorCode::QueryTimeLimitExceeded
+            | ErrorCode::ReservedCyclesLimitExceededInMemoryAllocation
+            | ErrorCode::ReservedCyclesLimitExceededInMemoryGrow => false,
         }
     }
 
@@ -59,6 +59,21 @@ pub enum Method {
     UpdateSettings,
     ComputeInitialEcdsaDealings,
 
+    // Canister snapshots.
+    TakeCanisterSnapshot,
+    LoadCanisterSnapshot,
+    ListCanisterSnapshots,
+    DeleteCanisterSnapshot,
+
+    // Canister logging.
+    FetchCanisterLogs,
+
+    // Chunked Wasm upload.
+    UploadChunk,
+    StoredChunks,
+    ClearChunkStore,
+    InstallChunkedCode,
+
     // Bitcoin Interface.
     BitcoinGetBalance,
     BitcoinGetUtxos,
@@ -238,6 +253,36 @@ impl CanisterControllersChangeRecord {
     }
 }
 
+/// `CandidType` for `CanisterLoadSnapshotRecord`
+/// ```text
+/// record {
+///   canister_version : nat64;
+///   snapshot_id : blob;
+///   taken_at_timestamp : nat64;
+/// }
+/// ```
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CanisterLoadSnapshotRecord {
+    canister_version: u64,
+    #[serde(with = "serde_bytes")]
+    snapshot_id: Vec<u8>,
+    taken_at_timestamp: u64,
+}
+
+impl CanisterLoadSnapshotRecord {
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+
+    pub fn taken_at_timestamp(&self) -> u64 {
+        self.taken_at_timestamp
+    }
+}
+
 /// `CandidType` for `CanisterChangeDetails`
 /// ```text
 /// variant {
@@ -252,6 +297,11 @@ impl CanisterControllersChangeRecord {
 ///   canister_controllers_change : record {
 ///     controllers : vec principal;
 ///   };
+///   load_snapshot : record {
+///     canister_version : nat64;
+///     snapshot_id : blob;
+///     taken_at_timestamp : nat64;
+///   };
 /// }
 /// ```
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -264,6 +314,8 @@ pub enum CanisterChangeDetails {
     CanisterCodeDeployment(CanisterCodeDeploymentRecord),
     #[serde(rename = "canister_controllers_change")]
     CanisterControllersChange(CanisterControllersChangeRecord),
+    #[serde(rename = "load_snapshot")]
+    CanisterLoadSnapshot(CanisterLoadSnapshotRecord),
 }
 
 impl CanisterChangeDetails {
@@ -286,9 +338,21 @@ impl CanisterChangeDetails {
             controllers,
         })
     }
+
+    pub fn load_snapshot(
+        canister_version: u64,
+        snapshot_id: Vec<u8>,
+        taken_at_timestamp: u64,
+    ) -> CanisterChangeDetails {
+        CanisterChangeDetails::CanisterLoadSnapshot(CanisterLoadSnapshotRecord {
+            canister_version,
+            snapshot_id,
+            taken_at_timestamp,
+        })
+    }
 }
 
-/// Every canister change (canister creation, code uninstallation, code deployment, or controllers change) consists of
+/// Every canister change (canister creation, code uninstallation, code deployment, controllers change, or snapshot load) consists of
 ///
 /// 1. the system timestamp (in nanoseconds since Unix Epoch) at which the change was performed,
 /// 2. the canister version after performing the change,
@@ -304,6 +368,9 @@ impl CanisterChangeDetails {
 ///
 /// Controllers changes are described by the full new set of the canister controllers after the change.
 ///
+/// Snapshot loads are described by the id of the loaded snapshot, the time at which it was taken
+/// and the canister version at that time.
+///
 /// `CandidType` for `CanisterChange`
 /// ```text
 /// record {
@@ -338,20 +405,23 @@ impl CanisterChange {
 
     /// Returns the number of bytes to represent a canister change in memory.
     /// The vector of controllers in `CanisterCreation` and `CanisterControllersChange`
-    /// is counted separately because the controllers are stored on heap
-    /// and thus not accounted for in `size_of::<CanisterChange>()`.
+    /// and the snapshot id in `CanisterLoadSnapshot` are counted separately because
+    /// they are stored on heap and thus not accounted for in `size_of::<CanisterChange>()`.
     pub fn count_bytes(&self) -> NumBytes {
-        let controllers_memory_size = match &self.change_details {
+        let heap_memory_size = match &self.change_details {
             CanisterChangeDetails::CanisterCreation(canister_creation) => {
                 canister_creation.controllers().len() * size_of::<PrincipalId>()
             }
             CanisterChangeDetails::CanisterControllersChange(canister_controllers_change) => {
                 canister_controllers_change.controllers().len() * size_of::<PrincipalId>()
             }
+            CanisterChangeDetails::CanisterLoadSnapshot(canister_load_snapshot) => {
+                canister_load_snapshot.snapshot_id().len()
+            }
             CanisterChangeDetails::CanisterCodeDeployment(_)
             | CanisterChangeDetails::CanisterCodeUninstall => 0,
         };
-        NumBytes::from((size_of::<CanisterChange>() + controllers_memory_size) as u64)
+        NumBytes::from((size_of::<CanisterChange>() + heap_memory_size) as u64)
     }
 }
 
@@ -438,6 +508,15 @@ impl From<&CanisterChangeDetails> for pb_canister_state_bits::canister_change::C
                     },
                 )
             }
+            CanisterChangeDetails::CanisterLoadSnapshot(canister_load_snapshot) => {
+                pb_canister_state_bits::canister_change::ChangeDetails::CanisterLoadSnapshot(
+                    pb_canister_state_bits::CanisterLoadSnapshot {
+                        canister_version: canister_load_snapshot.canister_version,
+                        snapshot_id: canister_load_snapshot.snapshot_id.clone(),
+                        taken_at_timestamp: canister_load_snapshot.taken_at_timestamp,
+                    },
+                )
+            }
         }
     }
 }
@@ -481,6 +560,13 @@ impl TryFrom<pb_canister_state_bits::canister_change::ChangeDetails> for Caniste
                     .map(TryInto::try_into)
                     .collect::<Result<Vec<PrincipalId>, _>>()?,
             )),
+            pb_canister_state_bits::canister_change::ChangeDetails::CanisterLoadSnapshot(
+                canister_load_snapshot,
+            ) => Ok(CanisterChangeDetails::load_snapshot(
+                canister_load_snapshot.canister_version,
+                canister_load_snapshot.snapshot_id,
+                canister_load_snapshot.taken_at_timestamp,
+            )),
         }
     }
 }
@@ -538,11 +624,374 @@ impl UninstallCodeArgs {
 
 impl Payload<'_> for UninstallCodeArgs {}
 
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     replace_snapshot: opt blob;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct TakeCanisterSnapshotArgs {
+    canister_id: PrincipalId,
+    #[serde(default)]
+    replace_snapshot: Option<serde_bytes::ByteBuf>,
+}
+
+impl TakeCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, replace_snapshot: Option<Vec<u8>>) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            replace_snapshot: replace_snapshot.map(serde_bytes::ByteBuf::from),
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn replace_snapshot(&self) -> Option<&[u8]> {
+        self.replace_snapshot.as_ref().map(|id| id.as_slice())
+    }
+}
+
+impl Payload<'_> for TakeCanisterSnapshotArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     snapshot_id: blob;
+///     sender_canister_version: opt nat64;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct LoadCanisterSnapshotArgs {
+    canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    snapshot_id: Vec<u8>,
+    sender_canister_version: Option<u64>,
+}
+
+impl LoadCanisterSnapshotArgs {
+    pub fn new(
+        canister_id: CanisterId,
+        snapshot_id: Vec<u8>,
+        sender_canister_version: Option<u64>,
+    ) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            snapshot_id,
+            sender_canister_version,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+
+    pub fn get_sender_canister_version(&self) -> Option<u64> {
+        self.sender_canister_version
+    }
+}
+
+impl Payload<'_> for LoadCanisterSnapshotArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     snapshot_id: blob;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct DeleteCanisterSnapshotArgs {
+    canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    snapshot_id: Vec<u8>,
+}
+
+impl DeleteCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, snapshot_id: Vec<u8>) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            snapshot_id,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+}
+
+impl Payload<'_> for DeleteCanisterSnapshotArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     id: blob;
+///     taken_at_timestamp: nat64;
+///     total_size: nat64;
+/// })`
+///
+/// `take_canister_snapshot` returns a single record of this type and
+/// `list_canister_snapshots` returns a `vec` of them.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct CanisterSnapshotResponse {
+    #[serde(with = "serde_bytes")]
+    pub id: Vec<u8>,
+    pub taken_at_timestamp: u64,
+    pub total_size: u64,
+}
+
+impl CanisterSnapshotResponse {
+    pub fn new(id: Vec<u8>, taken_at_timestamp: u64, total_size: u64) -> Self {
+        Self {
+            id,
+            taken_at_timestamp,
+            total_size,
+        }
+    }
+}
+
+impl Payload<'_> for CanisterSnapshotResponse {}
+
+/// Who is allowed to fetch the logs of a canister via `fetch_canister_logs`.
+///
+/// `(variant { controllers; public })`
+#[derive(CandidType, Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum LogVisibility {
+    /// Only the controllers of the canister can fetch its logs.
+    #[default]
+    #[serde(rename = "controllers")]
+    Controllers,
+    /// Anyone can fetch the logs of the canister.
+    #[serde(rename = "public")]
+    Public,
+}
+
+impl From<LogVisibility> for pb_canister_state_bits::LogVisibility {
+    fn from(item: LogVisibility) -> Self {
+        match item {
+            LogVisibility::Controllers => pb_canister_state_bits::LogVisibility::Controllers,
+            LogVisibility::Public => pb_canister_state_bits::LogVisibility::Public,
+        }
+    }
+}
+
+impl From<pb_canister_state_bits::LogVisibility> for LogVisibility {
+    fn from(item: pb_canister_state_bits::LogVisibility) -> Self {
+        match item {
+            // Checkpoints written before log visibility existed default to
+            // the most restrictive setting.
+            pb_canister_state_bits::LogVisibility::Unspecified
+            | pb_canister_state_bits::LogVisibility::Controllers => LogVisibility::Controllers,
+            pb_canister_state_bits::LogVisibility::Public => LogVisibility::Public,
+        }
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     idx: nat64;
+///     timestamp_nanos: nat64;
+///     content: blob;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CanisterLogRecord {
+    pub idx: u64,
+    pub timestamp_nanos: u64,
+    #[serde(with = "serde_bytes")]
+    pub content: Vec<u8>,
+}
+
+impl CanisterLogRecord {
+    /// Returns the number of bytes the record takes in the log of a canister.
+    pub fn data_size(&self) -> usize {
+        size_of::<u64>() * 2 + self.content.len()
+    }
+}
+
+impl From<&CanisterLogRecord> for pb_canister_state_bits::CanisterLogRecord {
+    fn from(item: &CanisterLogRecord) -> Self {
+        Self {
+            idx: item.idx,
+            timestamp_nanos: item.timestamp_nanos,
+            content: item.content.clone(),
+        }
+    }
+}
+
+impl From<pb_canister_state_bits::CanisterLogRecord> for CanisterLogRecord {
+    fn from(item: pb_canister_state_bits::CanisterLogRecord) -> Self {
+        Self {
+            idx: item.idx,
+            timestamp_nanos: item.timestamp_nanos,
+            content: item.content,
+        }
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+/// })`
+#[derive(CandidType, Deserialize, Debug)]
+pub struct FetchCanisterLogsRequest {
+    pub canister_id: PrincipalId,
+}
+
+impl FetchCanisterLogsRequest {
+    pub fn new(canister_id: CanisterId) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::new(self.canister_id).unwrap()
+    }
+}
+
+impl Payload<'_> for FetchCanisterLogsRequest {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_log_records: vec canister_log_record;
+/// })`
+#[derive(CandidType, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct FetchCanisterLogsResponse {
+    pub canister_log_records: Vec<CanisterLogRecord>,
+}
+
+impl Payload<'_> for FetchCanisterLogsResponse {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     chunk: blob;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+pub struct UploadChunkArgs {
+    pub canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    pub chunk: Vec<u8>,
+}
+
+impl UploadChunkArgs {
+    pub fn new(canister_id: CanisterId, chunk: Vec<u8>) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            chunk,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::new(self.canister_id).unwrap()
+    }
+}
+
+impl Payload<'_> for UploadChunkArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     hash: blob;
+/// })`
+///
+/// `upload_chunk` returns the hash of the uploaded chunk in this form.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ChunkHash {
+    #[serde(with = "serde_bytes")]
+    pub hash: Vec<u8>,
+}
+
+impl Payload<'_> for ChunkHash {}
+
+/// Struct used for encoding/decoding
+/// `(vec record {
+///     hash: blob;
+/// })`
+#[derive(CandidType, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct StoredChunksReply(pub Vec<ChunkHash>);
+
+impl Payload<'_> for StoredChunksReply {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     mode : variant { install; reinstall; upgrade };
+///     target_canister: principal;
+///     store_canister: opt principal;
+///     chunk_hashes_list: vec record { hash: blob };
+///     wasm_module_hash: blob;
+///     arg: blob;
+///     sender_canister_version : opt nat64;
+/// })`
+///
+/// The Wasm module is the concatenation of the chunks listed in
+/// `chunk_hashes_list`, taken from the chunk store of `store_canister`, or of
+/// `target_canister` if no store canister is given.
+#[derive(Clone, CandidType, Deserialize, Debug)]
+pub struct InstallChunkedCodeArgs {
+    pub mode: CanisterInstallMode,
+    pub target_canister: PrincipalId,
+    pub store_canister: Option<PrincipalId>,
+    pub chunk_hashes_list: Vec<ChunkHash>,
+    #[serde(with = "serde_bytes")]
+    pub wasm_module_hash: Vec<u8>,
+    pub arg: Vec<u8>,
+    pub sender_canister_version: Option<u64>,
+}
+
+impl InstallChunkedCodeArgs {
+    pub fn new(
+        mode: CanisterInstallMode,
+        target_canister: CanisterId,
+        store_canister: Option<CanisterId>,
+        chunk_hashes_list: Vec<Vec<u8>>,
+        wasm_module_hash: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Self {
+        Self {
+            mode,
+            target_canister: target_canister.into(),
+            store_canister: store_canister.map(|canister_id| canister_id.into()),
+            chunk_hashes_list: chunk_hashes_list
+                .into_iter()
+                .map(|hash| ChunkHash { hash })
+                .collect(),
+            wasm_module_hash,
+            arg,
+            sender_canister_version: None,
+        }
+    }
+
+    pub fn target_canister_id(&self) -> CanisterId {
+        CanisterId::new(self.target_canister).unwrap()
+    }
+
+    /// Returns the canister whose chunk store holds the chunks of the module.
+    pub fn store_canister_id(&self) -> CanisterId {
+        CanisterId::new(self.store_canister.unwrap_or(self.target_canister)).unwrap()
+    }
+
+    pub fn get_sender_canister_version(&self) -> Option<u64> {
+        self.sender_canister_version
+    }
+}
+
+impl Payload<'_> for InstallChunkedCodeArgs {}
+
 /// Struct used for encoding/decoding
 /// `(record {
 ///     controller : principal;
 ///     compute_allocation: nat;
 ///     memory_allocation: opt nat;
+///     wasm_memory_limit: opt nat;
+///     reserved_cycles_limit: opt nat;
 /// })`
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
 pub struct DefiniteCanisterSettingsArgs {
@@ -551,6 +1000,8 @@ pub struct DefiniteCanisterSettingsArgs {
     compute_allocation: candid::Nat,
     memory_allocation: candid::Nat,
     freezing_threshold: candid::Nat,
+    wasm_memory_limit: Option<candid::Nat>,
+    reserved_cycles_limit: Option<candid::Nat>,
 }
 
 impl DefiniteCanisterSettingsArgs {
@@ -560,6 +1011,8 @@ impl DefiniteCanisterSettingsArgs {
         compute_allocation: u64,
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
+        wasm_memory_limit: Option<u64>,
+        reserved_cycles_limit: Option<u128>,
     ) -> Self {
         let memory_allocation = match memory_allocation {
             None => candid::Nat::from(0),
@@ -571,12 +1024,26 @@ impl DefiniteCanisterSettingsArgs {
             compute_allocation: candid::Nat::from(compute_allocation),
             memory_allocation,
             freezing_threshold: candid::Nat::from(freezing_threshold),
+            wasm_memory_limit: wasm_memory_limit.map(candid::Nat::from),
+            reserved_cycles_limit: reserved_cycles_limit.map(candid::Nat::from),
         }
     }
 
     pub fn controllers(&self) -> Vec<PrincipalId> {
         self.controllers.clone()
     }
+
+    pub fn wasm_memory_limit(&self) -> Option<u64> {
+        self.wasm_memory_limit
+            .as_ref()
+            .map(|limit| limit.0.to_u64().unwrap())
+    }
+
+    pub fn reserved_cycles_limit(&self) -> Option<u128> {
+        self.reserved_cycles_limit
+            .as_ref()
+            .map(|limit| limit.0.to_u128().unwrap())
+    }
 }
 
 impl Payload<'_> for DefiniteCanisterSettingsArgs {}
@@ -646,6 +1113,8 @@ impl Payload<'_> for CanisterStatusResult {}
 ///     memory_size: nat;
 ///     cycles: nat;
 ///     idle_cycles_burned_per_day: nat;
+///     query_stats: query_stats;
+///     reserved_cycles: nat;
 /// })`
 #[derive(CandidType, Debug, Deserialize, Eq, PartialEq)]
 pub struct CanisterStatusResultV2 {
@@ -659,6 +1128,25 @@ pub struct CanisterStatusResultV2 {
     balance: Vec<(Vec<u8>, candid::Nat)>,
     freezing_threshold: candid::Nat,
     idle_cycles_burned_per_day: candid::Nat,
+    query_stats: QueryStats,
+    reserved_cycles: candid::Nat,
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     num_calls_total: nat;
+///     num_instructions_total: nat;
+///     request_payload_bytes_total: nat;
+///     response_payload_bytes_total: nat;
+/// })`
+///
+/// The query statistics of a canister, aggregated across the subnet.
+#[derive(CandidType, Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct QueryStats {
+    pub num_calls_total: candid::Nat,
+    pub num_instructions_total: candid::Nat,
+    pub request_payload_bytes_total: candid::Nat,
+    pub response_payload_bytes_total: candid::Nat,
 }
 
 impl CanisterStatusResultV2 {
@@ -674,6 +1162,10 @@ impl CanisterStatusResultV2 {
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
         idle_cycles_burned_per_day: u128,
+        query_stats: QueryStats,
+        wasm_memory_limit: Option<u64>,
+        reserved_cycles: u128,
+        reserved_cycles_limit: Option<u128>,
     ) -> Self {
         Self {
             status,
@@ -690,9 +1182,13 @@ impl CanisterStatusResultV2 {
                 compute_allocation,
                 memory_allocation,
                 freezing_threshold,
+                wasm_memory_limit,
+                reserved_cycles_limit,
             ),
             freezing_threshold: candid::Nat::from(freezing_threshold),
             idle_cycles_burned_per_day: candid::Nat::from(idle_cycles_burned_per_day),
+            query_stats,
+            reserved_cycles: candid::Nat::from(reserved_cycles),
         }
     }
 
@@ -727,6 +1223,22 @@ impl CanisterStatusResultV2 {
     pub fn idle_cycles_burned_per_day(&self) -> u128 {
         self.idle_cycles_burned_per_day.0.to_u128().unwrap()
     }
+
+    pub fn query_stats(&self) -> QueryStats {
+        self.query_stats.clone()
+    }
+
+    pub fn wasm_memory_limit(&self) -> Option<u64> {
+        self.settings.wasm_memory_limit()
+    }
+
+    pub fn reserved_cycles(&self) -> u128 {
+        self.reserved_cycles.0.to_u128().unwrap()
+    }
+
+    pub fn reserved_cycles_limit(&self) -> Option<u128> {
+        self.settings.reserved_cycles_limit()
+    }
 }
 
 /// Indicates whether the canister is running, stopping, or stopped.
@@ -1036,6 +1548,10 @@ impl Payload<'_> for UpdateSettingsArgs {}
 ///     controllers: opt vec principal;
 ///     compute_allocation: opt nat;
 ///     memory_allocation: opt nat;
+///     freezing_threshold: opt nat;
+///     log_visibility: opt log_visibility;
+///     wasm_memory_limit: opt nat;
+///     reserved_cycles_limit: opt nat;
 /// })`
 #[derive(Default, Clone, CandidType, Deserialize, Debug)]
 pub struct CanisterSettingsArgs {
@@ -1045,6 +1561,9 @@ pub struct CanisterSettingsArgs {
     pub compute_allocation: Option<candid::Nat>,
     pub memory_allocation: Option<candid::Nat>,
     pub freezing_threshold: Option<candid::Nat>,
+    pub log_visibility: Option<LogVisibility>,
+    pub wasm_memory_limit: Option<candid::Nat>,
+    pub reserved_cycles_limit: Option<candid::Nat>,
 }
 
 impl Payload<'_> for CanisterSettingsArgs {}
@@ -1062,6 +1581,9 @@ impl CanisterSettingsArgs {
             compute_allocation: compute_allocation.map(candid::Nat::from),
             memory_allocation: memory_allocation.map(candid::Nat::from),
             freezing_threshold: freezing_threshold.map(candid::Nat::from),
+            log_visibility: None,
+            wasm_memory_limit: None,
+            reserved_cycles_limit: None,
         }
     }
 
@@ -1077,6 +1599,9 @@ pub struct CanisterSettingsArgsBuilder {
     compute_allocation: Option<candid::Nat>,
     memory_allocation: Option<candid::Nat>,
     freezing_threshold: Option<candid::Nat>,
+    log_visibility: Option<LogVisibility>,
+    wasm_memory_limit: Option<candid::Nat>,
+    reserved_cycles_limit: Option<candid::Nat>,
 }
 
 #[allow(dead_code)]
@@ -1092,6 +1617,9 @@ impl CanisterSettingsArgsBuilder {
             compute_allocation: self.compute_allocation,
             memory_allocation: self.memory_allocation,
             freezing_threshold: self.freezing_threshold,
+            log_visibility: self.log_visibility,
+            wasm_memory_limit: self.wasm_memory_limit,
+            reserved_cycles_limit: self.reserved_cycles_limit,
         }
     }
 
@@ -1135,6 +1663,32 @@ impl CanisterSettingsArgsBuilder {
             ..self
         }
     }
+
+    /// Sets who is allowed to fetch the logs of the canister.
+    pub fn with_log_visibility(self, log_visibility: LogVisibility) -> Self {
+        Self {
+            log_visibility: Some(log_visibility),
+            ..self
+        }
+    }
+
+    /// Sets the Wasm memory limit in bytes. Growing the Wasm heap of the
+    /// canister beyond this limit fails. A limit of zero removes the limit.
+    pub fn with_wasm_memory_limit(self, wasm_memory_limit: u64) -> Self {
+        Self {
+            wasm_memory_limit: Some(candid::Nat::from(wasm_memory_limit)),
+            ..self
+        }
+    }
+
+    /// Sets the upper bound of the reserved cycles balance. Memory
+    /// allocations that would require reserving cycles above this limit fail.
+    pub fn with_reserved_cycles_limit(self, reserved_cycles_limit: u128) -> Self {
+        Self {
+            reserved_cycles_limit: Some(candid::Nat::from(reserved_cycles_limit)),
+            ..self
+        }
+    }
 }
 
 /// Struct used for encoding/decoding
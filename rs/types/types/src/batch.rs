@@ -3,11 +3,15 @@
 
 mod canister_http;
 mod ingress;
+mod query_stats;
 mod self_validating;
 mod xnet;
 
 pub use self::canister_http::{CanisterHttpPayload, MAX_CANISTER_HTTP_PAYLOAD_SIZE};
 pub use self::ingress::{IngressPayload, IngressPayloadError};
+pub use self::query_stats::{
+    epoch_from_height, CanisterQueryStats, QueryStats, QueryStatsEpoch, QueryStatsPayload,
+};
 pub use self::self_validating::{SelfValidatingPayload, MAX_BITCOIN_PAYLOAD_IN_BYTES};
 pub use self::xnet::XNetPayload;
 
@@ -77,6 +81,9 @@ pub struct BatchPayload {
     pub xnet: XNetPayload,
     pub self_validating: SelfValidatingPayload,
     pub canister_http: CanisterHttpPayload,
+    /// A serialized [`QueryStatsPayload`], empty if there is none.
+    #[serde(default)]
+    pub query_stats: Vec<u8>,
 }
 
 /// Return ingress messages, xnet messages, and responses from the bitcoin adapter.
@@ -85,6 +92,7 @@ pub struct BatchMessages {
     pub signed_ingress_msgs: Vec<SignedIngress>,
     pub certified_stream_slices: BTreeMap<SubnetId, CertifiedStreamSlice>,
     pub bitcoin_adapter_responses: Vec<BitcoinAdapterResponse>,
+    pub query_stats: Option<QueryStatsPayload>,
 }
 
 impl BatchPayload {
@@ -97,6 +105,10 @@ impl BatchPayload {
             signed_ingress_msgs: self.ingress.try_into()?,
             certified_stream_slices: self.xnet.stream_slices,
             bitcoin_adapter_responses: self.self_validating.0,
+            // The payload has passed validation, so it can be deserialized.
+            query_stats: QueryStatsPayload::deserialize(&self.query_stats)
+                .ok()
+                .flatten(),
         })
     }
 
@@ -105,6 +117,7 @@ impl BatchPayload {
             && self.xnet.stream_slices.is_empty()
             && self.self_validating.is_empty()
             && self.canister_http.is_empty()
+            && self.query_stats.is_empty()
     }
 }
 #[cfg(test)]
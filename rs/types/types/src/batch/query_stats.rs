@@ -0,0 +1,208 @@
+use crate::{node_id_into_protobuf, node_id_try_from_option, CanisterId, Height, NodeId};
+use ic_protobuf::{
+    proxy::{try_from_option_field, ProxyDecodeError},
+    types::v1 as pb,
+};
+use phantom_newtype::AmountOf;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+pub struct QueryStatsEpochTag;
+/// An epoch of query statistics.
+///
+/// Every replica aggregates the statistics of the queries it executes over
+/// one epoch, a fixed number of consecutive certified heights. The statistics
+/// of a completed epoch are then sent through consensus.
+pub type QueryStatsEpoch = AmountOf<QueryStatsEpochTag, u64>;
+
+/// Returns the query stats epoch that the given certified height belongs to.
+pub fn epoch_from_height(height: Height, epoch_length: u64) -> QueryStatsEpoch {
+    QueryStatsEpoch::from(height.get() / epoch_length.max(1))
+}
+
+/// Statistics of the queries executed by a single replica on a single
+/// canister.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QueryStats {
+    pub num_calls: u32,
+    pub num_instructions: u64,
+    pub ingress_payload_size: u64,
+    pub egress_payload_size: u64,
+}
+
+impl QueryStats {
+    /// Adds the statistics of `other` to `self`, saturating on overflow.
+    pub fn saturating_accumulate(&mut self, other: &QueryStats) {
+        self.num_calls = self.num_calls.saturating_add(other.num_calls);
+        self.num_instructions = self.num_instructions.saturating_add(other.num_instructions);
+        self.ingress_payload_size = self
+            .ingress_payload_size
+            .saturating_add(other.ingress_payload_size);
+        self.egress_payload_size = self
+            .egress_payload_size
+            .saturating_add(other.egress_payload_size);
+    }
+}
+
+/// The [`QueryStats`] a replica collected for one canister.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterQueryStats {
+    pub canister_id: CanisterId,
+    pub stats: QueryStats,
+}
+
+/// Payload that contains the query statistics a replica collected during a
+/// completed epoch.
+///
+/// A replica only ever includes its own statistics, i.e. `proposer` must be
+/// the maker of the block the payload is included in.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QueryStatsPayload {
+    pub epoch: QueryStatsEpoch,
+    pub proposer: NodeId,
+    pub canister_stats: Vec<CanisterQueryStats>,
+}
+
+impl QueryStatsPayload {
+    /// Serializes the payload into the format used in the block.
+    pub fn serialize(&self) -> Vec<u8> {
+        pb::QueryStatsPayload::from(self).encode_to_vec()
+    }
+
+    /// Deserializes a payload from its block format.
+    ///
+    /// An empty byte slice corresponds to no payload and yields `Ok(None)`.
+    pub fn deserialize(data: &[u8]) -> Result<Option<Self>, ProxyDecodeError> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let payload = pb::QueryStatsPayload::decode(data).map_err(ProxyDecodeError::DecodeError)?;
+        Self::try_from(payload).map(Some)
+    }
+
+    /// Returns the number of bytes the given [`CanisterQueryStats`] adds to
+    /// the serialized payload.
+    pub fn serialized_size_of(stats: &CanisterQueryStats) -> usize {
+        let len = pb::CanisterQueryStats::from(stats).encoded_len();
+        // One byte for the field key, plus the length delimiter.
+        1 + prost::length_delimiter_len(len) + len
+    }
+}
+
+impl From<&CanisterQueryStats> for pb::CanisterQueryStats {
+    fn from(stats: &CanisterQueryStats) -> Self {
+        Self {
+            canister_id: Some(stats.canister_id.into()),
+            num_calls: stats.stats.num_calls,
+            num_instructions: stats.stats.num_instructions,
+            ingress_payload_size: stats.stats.ingress_payload_size,
+            egress_payload_size: stats.stats.egress_payload_size,
+        }
+    }
+}
+
+impl TryFrom<pb::CanisterQueryStats> for CanisterQueryStats {
+    type Error = ProxyDecodeError;
+
+    fn try_from(stats: pb::CanisterQueryStats) -> Result<Self, Self::Error> {
+        Ok(Self {
+            canister_id: CanisterId::try_from(try_from_option_field(
+                stats.canister_id,
+                "CanisterQueryStats::canister_id",
+            )?)?,
+            stats: QueryStats {
+                num_calls: stats.num_calls,
+                num_instructions: stats.num_instructions,
+                ingress_payload_size: stats.ingress_payload_size,
+                egress_payload_size: stats.egress_payload_size,
+            },
+        })
+    }
+}
+
+impl From<&QueryStatsPayload> for pb::QueryStatsPayload {
+    fn from(payload: &QueryStatsPayload) -> Self {
+        Self {
+            epoch: payload.epoch.get(),
+            proposer: Some(node_id_into_protobuf(payload.proposer)),
+            canister_stats: payload
+                .canister_stats
+                .iter()
+                .map(pb::CanisterQueryStats::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::QueryStatsPayload> for QueryStatsPayload {
+    type Error = ProxyDecodeError;
+
+    fn try_from(payload: pb::QueryStatsPayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            epoch: QueryStatsEpoch::from(payload.epoch),
+            proposer: node_id_try_from_option(payload.proposer)?,
+            canister_stats: payload
+                .canister_stats
+                .into_iter()
+                .map(CanisterQueryStats::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_base_types::PrincipalId;
+
+    fn canister_stats(id: u64, num_calls: u32) -> CanisterQueryStats {
+        CanisterQueryStats {
+            canister_id: CanisterId::from_u64(id),
+            stats: QueryStats {
+                num_calls,
+                num_instructions: 1_000_000,
+                ingress_payload_size: 100,
+                egress_payload_size: 2_000,
+            },
+        }
+    }
+
+    #[test]
+    fn query_stats_payload_serialization_roundtrip() {
+        let payload = QueryStatsPayload {
+            epoch: QueryStatsEpoch::from(7),
+            proposer: NodeId::from(PrincipalId::new_node_test_id(3)),
+            canister_stats: vec![canister_stats(1, 10), canister_stats(2, 20)],
+        };
+        let bytes = payload.serialize();
+        assert_eq!(
+            QueryStatsPayload::deserialize(&bytes).unwrap(),
+            Some(payload.clone())
+        );
+
+        let sizes: usize = payload
+            .canister_stats
+            .iter()
+            .map(QueryStatsPayload::serialized_size_of)
+            .sum();
+        let empty = QueryStatsPayload {
+            canister_stats: vec![],
+            ..payload
+        };
+        assert_eq!(empty.serialize().len() + sizes, bytes.len());
+    }
+
+    #[test]
+    fn empty_bytes_deserialize_to_no_payload() {
+        assert_eq!(QueryStatsPayload::deserialize(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn epoch_from_height_rounds_down() {
+        assert_eq!(epoch_from_height(Height::from(0), 10), 0.into());
+        assert_eq!(epoch_from_height(Height::from(9), 10), 0.into());
+        assert_eq!(epoch_from_height(Height::from(10), 10), 1.into());
+        assert_eq!(epoch_from_height(Height::from(25), 10), 2.into());
+    }
+}
@@ -0,0 +1,150 @@
+//! A bounded buffer of the log records of a canister.
+use ic_ic00_types::CanisterLogRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The maximum number of bytes the log records of a single canister can take.
+/// When the limit is exceeded, the oldest records are dropped.
+pub const MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE: usize = 4 * 1024;
+
+/// The log records of a canister, oldest first.
+///
+/// The buffer is used both for the log of a canister kept in the replicated
+/// state and for the records produced during a single message execution. In
+/// the latter case the indices of the records are only assigned once the
+/// records are appended to the log of the canister.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanisterLog {
+    /// The index to be assigned to the next record.
+    next_idx: u64,
+    records: VecDeque<CanisterLogRecord>,
+    /// The sum of the sizes of all records in the buffer.
+    bytes_used: usize,
+}
+
+impl CanisterLog {
+    pub fn new(next_idx: u64, records: Vec<CanisterLogRecord>) -> Self {
+        let bytes_used = records.iter().map(|r| r.data_size()).sum();
+        Self {
+            next_idx,
+            records: records.into(),
+            bytes_used,
+        }
+    }
+
+    /// Returns the index to be assigned to the next record.
+    pub fn next_idx(&self) -> u64 {
+        self.next_idx
+    }
+
+    pub fn records(&self) -> &VecDeque<CanisterLogRecord> {
+        &self.records
+    }
+
+    /// Returns the number of bytes taken by the records in the buffer.
+    pub fn bytes_used(&self) -> usize {
+        self.bytes_used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Adds a new record with the given timestamp and content. The content is
+    /// truncated to fit into the buffer and the oldest records are dropped
+    /// until the new record fits.
+    pub fn add_record(&mut self, timestamp_nanos: u64, mut content: Vec<u8>) {
+        let mut record = CanisterLogRecord {
+            idx: self.next_idx,
+            timestamp_nanos,
+            content: vec![],
+        };
+        content.truncate(MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE - record.data_size());
+        record.content = content;
+        self.next_idx += 1;
+        self.push_back(record);
+    }
+
+    /// Moves all records of `delta` to the end of this log, assigning them
+    /// new consecutive indices.
+    pub fn append_delta_log(&mut self, delta: &mut CanisterLog) {
+        for record in delta.records.drain(..) {
+            self.add_record(record.timestamp_nanos, record.content);
+        }
+        delta.bytes_used = 0;
+    }
+
+    /// Drops all records but keeps the next index, so that indices are never
+    /// reused.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.bytes_used = 0;
+    }
+
+    fn push_back(&mut self, record: CanisterLogRecord) {
+        let record_size = record.data_size();
+        while self.bytes_used + record_size > MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE {
+            match self.records.pop_front() {
+                Some(oldest) => self.bytes_used -= oldest.data_size(),
+                None => break,
+            }
+        }
+        self.bytes_used += record_size;
+        self.records.push_back(record);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_get_consecutive_indices() {
+        let mut log = CanisterLog::default();
+        log.add_record(10, b"first".to_vec());
+        log.add_record(20, b"second".to_vec());
+
+        let indices: Vec<_> = log.records().iter().map(|r| r.idx).collect();
+        assert_eq!(indices, vec![0, 1]);
+        assert_eq!(log.next_idx(), 2);
+        assert_eq!(log.bytes_used(), 2 * 16 + 5 + 6);
+    }
+
+    #[test]
+    fn oldest_records_are_dropped_when_buffer_is_full() {
+        let mut log = CanisterLog::default();
+        let content = vec![b'x'; MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE / 4];
+        for i in 0..10 {
+            log.add_record(i, content.clone());
+        }
+
+        assert!(log.bytes_used() <= MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE);
+        assert_eq!(log.records().back().unwrap().idx, 9);
+        assert_eq!(log.records().front().unwrap().idx, 7);
+    }
+
+    #[test]
+    fn oversized_record_is_truncated() {
+        let mut log = CanisterLog::default();
+        log.add_record(0, b"old".to_vec());
+        log.add_record(1, vec![b'x'; 2 * MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE]);
+
+        assert_eq!(log.records().len(), 1);
+        assert_eq!(log.bytes_used(), MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn appending_delta_reassigns_indices_and_clears_delta() {
+        let mut log = CanisterLog::new(5, vec![]);
+        let mut delta = CanisterLog::default();
+        delta.add_record(1, b"a".to_vec());
+        delta.add_record(2, b"b".to_vec());
+
+        log.append_delta_log(&mut delta);
+
+        let indices: Vec<_> = log.records().iter().map(|r| r.idx).collect();
+        assert_eq!(indices, vec![5, 6]);
+        assert!(delta.is_empty());
+        assert_eq!(delta.bytes_used(), 0);
+    }
+}
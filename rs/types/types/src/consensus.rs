@@ -1337,6 +1337,7 @@ impl From<&Block> for pb::Block {
             ingress_payload,
             self_validating_payload,
             canister_http_payload,
+            query_stats_payload,
             ecdsa_payload,
         ) = if payload.is_summary() {
             (
@@ -1345,6 +1346,7 @@ impl From<&Block> for pb::Block {
                 None,
                 None,
                 None,
+                vec![],
                 payload
                     .as_summary()
                     .ecdsa
@@ -1359,6 +1361,7 @@ impl From<&Block> for pb::Block {
                 Some(pb::IngressPayload::from(&batch.ingress)),
                 Some(pb::SelfValidatingPayload::from(&batch.self_validating)),
                 Some(pb::CanisterHttpPayload::from(&batch.canister_http)),
+                batch.query_stats.clone(),
                 payload.as_data().ecdsa.as_ref().map(|ecdsa| ecdsa.into()),
             )
         };
@@ -1375,6 +1378,7 @@ impl From<&Block> for pb::Block {
             ingress_payload,
             self_validating_payload,
             canister_http_payload,
+            query_stats_payload,
             ecdsa_payload,
             payload_hash: block.payload.get_hash().clone().get().0,
         }
@@ -1410,6 +1414,7 @@ impl TryFrom<pb::Block> for Block {
                 .map(crate::batch::CanisterHttpPayload::try_from)
                 .transpose()?
                 .unwrap_or_default(),
+            query_stats: block.query_stats_payload,
         };
         let payload = match dkg_payload {
             dkg::Payload::Summary(summary) => {
@@ -668,6 +668,10 @@ pub enum ReplicaHealthStatus {
     Healthy,
 }
 
+// TODO(NET-776)
+/// The IC API version reported on status requests.
+pub const IC_API_VERSION: &str = "0.18.0";
+
 /// The response to `/api/v2/status`.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -139,6 +139,56 @@ impl Time {
     }
 }
 
+/// Time since UNIX_EPOCH, in seconds.
+///
+/// A coarser, more compact alternative to [`Time`], e.g. for deadlines of
+/// inter-canister messages.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct CoarseTime(u32);
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+impl CoarseTime {
+    pub const fn from_secs_since_unix_epoch(secs: u32) -> Self {
+        CoarseTime(secs)
+    }
+
+    /// Number of seconds since UNIX EPOCH.
+    pub fn as_secs_since_unix_epoch(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the largest `CoarseTime` that is not later than `time`,
+    /// saturating at `u32::MAX` seconds.
+    pub fn floor(time: Time) -> CoarseTime {
+        let secs = time.as_nanos_since_unix_epoch() / NANOS_PER_SEC;
+        CoarseTime(secs.min(u32::MAX as u64) as u32)
+    }
+
+    /// Returns the smallest `CoarseTime` that is not earlier than `time`,
+    /// saturating at `u32::MAX` seconds.
+    pub fn ceil(time: Time) -> CoarseTime {
+        let nanos = time.as_nanos_since_unix_epoch();
+        let secs = nanos / NANOS_PER_SEC + u64::from(nanos % NANOS_PER_SEC != 0);
+        CoarseTime(secs.min(u32::MAX as u64) as u32)
+    }
+}
+
+impl From<CoarseTime> for Time {
+    fn from(coarse_time: CoarseTime) -> Time {
+        Time::from_nanos_since_unix_epoch(coarse_time.0 as u64 * NANOS_PER_SEC)
+    }
+}
+
+impl fmt::Display for CoarseTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&Time::from(*self), f)
+    }
+}
+
 #[derive(Error, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TimeInstantiationError {
     #[error("Time cannot be instantiated as it would overflow: {0}")]
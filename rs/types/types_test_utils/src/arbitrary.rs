@@ -1,11 +1,14 @@
 use crate::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id};
 use ic_types::{
     crypto::{AlgorithmId, KeyPurpose, UserPublicKey},
-    messages::{CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response},
+    messages::{
+        CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response, NO_DEADLINE,
+    },
     state_sync::{ChunkInfo, FileInfo},
     time::UNIX_EPOCH,
     xnet::StreamIndex,
-    CanisterId, Cycles, Height, IDkgId, NodeId, RegistryVersion, SubnetId, Time, UserId,
+    CanisterId, CoarseTime, Cycles, Height, IDkgId, NodeId, RegistryVersion, SubnetId, Time,
+    UserId,
 };
 use proptest::prelude::*;
 use std::{convert::TryInto, time::Duration};
@@ -123,6 +126,20 @@ prop_compose! {
             payment: Cycles::from(cycles_payment),
             method_name,
             method_payload,
+            deadline: NO_DEADLINE,
+        }
+    }
+}
+
+prop_compose! {
+    /// Returns an arbitrary best-effort [`Request`].
+    pub fn best_effort_request()(
+        request in request(),
+        deadline in 1..=u32::MAX,
+    ) -> Request {
+        Request {
+            deadline: CoarseTime::from_secs_since_unix_epoch(deadline),
+            ..request
         }
     }
 }
@@ -156,7 +173,21 @@ prop_compose! {
             respondent,
             originator_reply_callback: CallbackId::from(callback),
             refund: Cycles::from(cycles_refund),
-            response_payload
+            response_payload,
+            deadline: NO_DEADLINE,
+        }
+    }
+}
+
+prop_compose! {
+    /// Returns an arbitrary best-effort [`Response`].
+    pub fn best_effort_response()(
+        response in response(),
+        deadline in 1..=u32::MAX,
+    ) -> Response {
+        Response {
+            deadline: CoarseTime::from_secs_since_unix_epoch(deadline),
+            ..response
         }
     }
 }
@@ -169,6 +200,14 @@ pub fn request_or_response() -> impl Strategy<Value = RequestOrResponse> {
     ]
 }
 
+/// Produces an arbitrary best-effort [`RequestOrResponse`].
+pub fn best_effort_request_or_response() -> impl Strategy<Value = RequestOrResponse> {
+    prop_oneof![
+        best_effort_request().prop_flat_map(|req| Just(req.into())),
+        best_effort_response().prop_flat_map(|rep| Just(rep.into())),
+    ]
+}
+
 prop_compose! {
     /// Returns an arbitrary [`StreamIndex`] in the `[0, max)` range.
     pub fn stream_index(max: u64) (
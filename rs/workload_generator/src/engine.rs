@@ -3,8 +3,8 @@ use crate::{
     content_length::ContentLength,
     message::Message,
     metrics::{FUTURE_STARTED, REQUEST_STARTING},
-    plan::{EngineCall, Plan},
-    stats::Fact,
+    plan::{Arrivals, EngineCall, Plan, ScenarioPlan},
+    stats::{Fact, StepFact},
     RequestType,
 };
 use backoff::backoff::Backoff;
@@ -159,12 +159,14 @@ impl Engine {
     ///   Currently, we
     /// use a single runtime. Not specifying this yields better throughput.
     /// - `rpms` - Request rate (per milliseconds) to issue against the IC
+    /// - `arrivals` - How the send times of the requests are determined
     /// - `time_secs` - The time in seconds that the workload should be kept up
     /// - `nonce` - Nonce to use for update calls
     #[allow(clippy::too_many_arguments)]
     pub async fn execute_rps(
         &self,
         rpms: usize,
+        arrivals: &Arrivals,
         request_type: RequestType,
         canister_method_name: String,
         time_secs: usize,
@@ -175,7 +177,10 @@ impl Engine {
         periodic_output: bool,
         random_query_payload: bool,
     ) -> Vec<Fact> {
-        let requests: usize = ((time_secs * rpms) as f64 / 1000f64).ceil() as usize;
+        let requests: usize = match arrivals {
+            Arrivals::Trace(times) => times.len(),
+            _ => ((time_secs * rpms) as f64 / 1000f64).ceil() as usize,
+        };
         if requests == 0 {
             debug!("Not executing any requests");
             return vec![];
@@ -195,7 +200,7 @@ impl Engine {
             request_type,
             canister_method_name,
         );
-        let (collector, rec_handle) = collector::start::<Fact>(plan.clone(), periodic_output);
+        let (collector, rec_handle) = collector::start::<Fact>(plan.requests, periodic_output);
 
         let (tx, rx) = channel(requests);
         let time_origin = Instant::now();
@@ -207,14 +212,13 @@ impl Engine {
             time_origin,
         ));
 
-        // Time between each two consecutive requests
-        let inter_arrival_time = 1000. / rpms as f64;
+        let send_times =
+            arrivals.send_times(requests, rpms as f64 / 1000f64, &mut rand::thread_rng());
         let mut tx_handles = vec![];
-        for n in 0..requests {
-            // Calculate the time at which the request should be running from start time
-            // and inter arrival time.
-            let target_instant =
-                time_origin + START_OFFSET + Duration::from_secs_f64(inter_arrival_time * n as f64);
+        for (n, send_time) in send_times.into_iter().enumerate() {
+            // The time at which the request should be sent. Latencies are
+            // measured from this time, even if the request is sent later.
+            let target_instant = time_origin + START_OFFSET + send_time;
             sleep_until(tokio::time::Instant::from_std(target_instant)).await;
             let tx = tx.clone();
             let plan = plan.clone();
@@ -222,8 +226,103 @@ impl Engine {
             FUTURE_STARTED.inc();
             tx_handles.push(tokio::task::spawn(async move {
                 REQUEST_STARTING.inc();
-                Engine::execute_request(agent, tx, time_origin, &plan, n, random_query_payload)
-                    .await;
+                Engine::execute_request(
+                    agent,
+                    tx,
+                    time_origin,
+                    target_instant,
+                    &plan,
+                    n,
+                    random_query_payload,
+                )
+                .await;
+            }));
+        }
+        for tx_handle in tx_handles {
+            tx_handle.await.unwrap_or_else(|_| {
+                panic!("Await the tx failed.");
+            });
+        }
+        std::mem::drop(tx);
+        rx_handle.await.unwrap_or_else(|_| {
+            panic!("Await the rx failed.");
+        });
+
+        rec_handle.join().unwrap()
+    }
+
+    /// Execute the requests of a scenario at the scheduled times. Returns the
+    /// facts of all requests, tagged with the steps they executed.
+    pub async fn execute_scenario(
+        &self,
+        plan: &ScenarioPlan,
+        periodic_output: bool,
+    ) -> Vec<StepFact> {
+        let requests = plan.schedule.len();
+        if requests == 0 {
+            debug!("Not executing any requests");
+            return vec![];
+        }
+        debug!(
+            "⏱️  Executing {} requests in {} steps",
+            requests,
+            plan.steps.len()
+        );
+
+        let (collector, rec_handle) = collector::start::<StepFact>(requests, periodic_output);
+
+        let (tx, rx) = channel(requests);
+        let time_origin = Instant::now();
+
+        let rx_handle = tokio::task::spawn(Engine::evaluate_scenario_requests(rx, collector));
+
+        let mut tx_handles = vec![];
+        for (n, (offset, step)) in plan.schedule.iter().enumerate() {
+            let target_instant = time_origin + START_OFFSET + *offset;
+            sleep_until(tokio::time::Instant::from_std(target_instant)).await;
+            let tx = tx.clone();
+            let step = *step;
+            let step_plan = plan.steps[step].clone();
+            let agent = self.agents[n % self.agents.len()].clone();
+            FUTURE_STARTED.inc();
+            tx_handles.push(tokio::task::spawn(async move {
+                REQUEST_STARTING.inc();
+                // Every call sends exactly one result, which is tagged with
+                // the step before it is passed on to the evaluation.
+                let (call_tx, mut call_rx) = channel(1);
+                match step_plan.generate_call() {
+                    EngineCall::Read { method, arg } => {
+                        Engine::execute_query(
+                            &agent,
+                            call_tx,
+                            time_origin,
+                            target_instant,
+                            &step_plan.plan,
+                            method,
+                            arg,
+                            n,
+                        )
+                        .await;
+                    }
+                    EngineCall::Write { method, arg } => {
+                        Engine::execute_update(
+                            &agent,
+                            call_tx,
+                            time_origin,
+                            target_instant,
+                            &step_plan.plan,
+                            method,
+                            arg,
+                            n,
+                        )
+                        .await;
+                    }
+                }
+                if let Some(result) = call_rx.recv().await {
+                    tx.send((step, result)).await.unwrap_or_else(|_| {
+                        panic!("Sending a fact failed.");
+                    });
+                }
             }));
         }
         for tx_handle in tx_handles {
@@ -244,18 +343,36 @@ impl Engine {
         agent: Agent,
         tx: Sender<CallResult>,
         time_origin: Instant,
+        time_scheduled: Instant,
         plan: &Plan,
         n: usize,
         random_query_payload: bool,
     ) -> bool {
         match plan.generate_call(n, random_query_payload) {
-            EngineCall::Read { method, arg } => {
-                Engine::execute_query(&agent, tx, time_origin, plan, method, arg, n)
-                    .await
-                    .is_some()
-            }
+            EngineCall::Read { method, arg } => Engine::execute_query(
+                &agent,
+                tx,
+                time_origin,
+                time_scheduled,
+                plan,
+                method,
+                arg,
+                n,
+            )
+            .await
+            .is_some(),
             EngineCall::Write { method, arg } => {
-                Engine::execute_update(&agent, tx, time_origin, plan, method, arg, n).await
+                Engine::execute_update(
+                    &agent,
+                    tx,
+                    time_origin,
+                    time_scheduled,
+                    plan,
+                    method,
+                    arg,
+                    n,
+                )
+                .await
             }
         }
     }
@@ -265,6 +382,7 @@ impl Engine {
         agent: &Agent,
         tx: Sender<CallResult>,
         _time_origin: Instant,
+        time_scheduled: Instant,
         plan: &Plan,
         method: String,
         arg: Vec<u8>,
@@ -285,7 +403,7 @@ impl Engine {
                     fs::write(f, bytes).unwrap();
                 }
 
-                Engine::check_query(r, tx, time_query_start, time_query_end).await
+                Engine::check_query(r, tx, time_scheduled, time_query_start, time_query_end).await
             }
             Err(e) => {
                 let err = format!("{:?}", e).to_string();
@@ -315,7 +433,8 @@ impl Engine {
                         time_query_start,
                         time_query_end,
                         false,
-                    ),
+                    )
+                    .scheduled_at(time_scheduled),
                     counter: None,
                     call_failure: CallFailure::OnWait,
                     err_msg: Some(err),
@@ -336,6 +455,7 @@ impl Engine {
         agent: &Agent,
         tx: Sender<CallResult>,
         time_origin: Instant,
+        time_scheduled: Instant,
         plan: &Plan,
         method: String,
         arg: Vec<u8>,
@@ -389,7 +509,9 @@ impl Engine {
                         time_start,
                         Instant::now(),
                         false,
-                    ),
+                    )
+                    .scheduled_at(time_scheduled)
+                    .update(None),
                     counter: None,
                     call_failure: CallFailure::OnSubmit,
                     err_msg: Some(err_msg),
@@ -428,7 +550,9 @@ impl Engine {
                             time_start,
                             Instant::now(),
                             false,
-                        ),
+                        )
+                        .scheduled_at(time_scheduled)
+                        .update(None),
                         counter: None,
                         call_failure: CallFailure::OnSubmit,
                         err_msg: Some(err_msg),
@@ -440,6 +564,7 @@ impl Engine {
                     return false;
                 }
 
+                let time_submitted = Instant::now();
                 let mut finished = false;
 
                 // https://docs.rs/backoff/latest/backoff/exponential/struct.ExponentialBackoff.html#structfield.initial_interval
@@ -498,7 +623,9 @@ impl Engine {
                                             time_start,
                                             Instant::now(),
                                             true,
-                                        ),
+                                        )
+                                        .scheduled_at(time_scheduled)
+                                        .update(Some(time_submitted)),
                                         counter: Some(counter),
                                         call_failure: CallFailure::None,
                                         err_msg: None,
@@ -532,7 +659,9 @@ impl Engine {
                                             time_start,
                                             Instant::now(),
                                             false,
-                                        ),
+                                        )
+                                        .scheduled_at(time_scheduled)
+                                        .update(Some(time_submitted)),
                                         counter: None,
                                         call_failure: CallFailure::OnWait,
                                         err_msg: Some(err_msg),
@@ -564,7 +693,9 @@ impl Engine {
                             time_start,
                             Instant::now(),
                             false,
-                        ),
+                        )
+                        .scheduled_at(time_scheduled)
+                        .update(Some(time_submitted)),
                         counter: None,
                         call_failure: CallFailure::OnWait,
                         err_msg: Some(err_msg),
@@ -582,6 +713,7 @@ impl Engine {
     async fn check_query(
         resp: Option<Vec<u8>>,
         tx: Sender<CallResult>,
+        time_scheduled: Instant,
         time_query_start: Instant,
         time_query_end: Instant,
     ) -> Option<u32> {
@@ -603,7 +735,8 @@ impl Engine {
                 time_query_start,
                 time_query_end,
                 true,
-            ),
+            )
+            .scheduled_at(time_scheduled),
             counter,
             call_failure: CallFailure::None,
             err_msg: None,
@@ -656,6 +789,38 @@ impl Engine {
         collector.send(Message::Eof).unwrap();
     }
 
+    async fn evaluate_scenario_requests(
+        mut rx: Receiver<(usize, CallResult)>,
+        collector: std::sync::mpsc::Sender<Message<StepFact>>,
+    ) {
+        let mut failures = HashMap::new();
+
+        while let Some((step, result)) = rx.recv().await {
+            collector
+                .send(Message::Body(StepFact {
+                    step,
+                    fact: result.fact,
+                }))
+                .expect("Failed to collect facts for scenario calls");
+
+            if let Some(err_msg) = result.err_msg {
+                eprintln!("{}", err_msg);
+            }
+
+            let stat = failures.entry(result.call_failure).or_insert(0);
+            *stat += 1;
+        }
+
+        collector
+            .send(Message::Log(format!(
+                "submit failures: {} - wait failures: {}",
+                failures.get(&CallFailure::OnSubmit).unwrap_or(&0),
+                failures.get(&CallFailure::OnWait).unwrap_or(&0),
+            )))
+            .unwrap();
+        collector.send(Message::Eof).unwrap();
+    }
+
     /// Given the raw bytes of the "arg" counter canister response (NOT the
     /// top-level response), returns the corresponding counter value.
     fn interpret_counter_canister_response(bytes: &[u8]) -> u32 {
@@ -27,6 +27,7 @@ mod engine;
 mod message;
 mod metrics;
 mod plan;
+mod scenario;
 mod stats;
 
 use ic_canister_client::{HttpClient, HttpClientConfig, Sender as AgentSender};
@@ -34,6 +35,8 @@ use ic_canister_client_sender::ed25519_public_key_to_der;
 use ic_config::metrics::{Config as MetricsConfig, Exporter};
 use ic_test_identity::{get_pair, TEST_IDENTITY_KEYPAIR, TEST_IDENTITY_KEYPAIR_HARD_CODED};
 use ic_types::{messages::Blob, CanisterId, PrincipalId, UserId};
+use plan::{Arrivals, ScenarioPlan};
+use scenario::Scenario;
 use stats::Summary;
 
 #[cfg(build = "debug")]
@@ -85,6 +88,15 @@ pub enum RequestType {
     Query,
 }
 
+#[derive(ArgEnum, Debug, Eq, PartialEq, Clone, Copy)]
+#[clap(rename_all = "camel")]
+pub enum ArrivalProcess {
+    // Requests are sent at a fixed interval
+    Uniform,
+    // Requests are sent as a Poisson process
+    Poisson,
+}
+
 #[derive(ArgEnum, Debug, Eq, PartialEq, Clone, Copy)]
 #[clap(rename_all = "camel")]
 pub enum ChartSize {
@@ -120,10 +132,33 @@ async fn main() {
         .arg(
             Arg::new("rps")
                 .short('r')
-                .required(true)
+                .required_unless_present_any(&["scenario", "arrival-trace"])
                 .takes_value(true)
                 .help("Requests per second to generate. Accepts fractional values, e.g. 1.5 rps."),
         )
+        .arg(
+            Arg::new("scenario")
+                .long("scenario")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Scenario file, in TOML or YAML format, describing phases with (ramping) rates and weighted query and update calls to execute. Replaces -r, -n, --method and the canister options."),
+        )
+        .arg(
+            Arg::new("arrivals")
+                .long("arrivals")
+                .takes_value(true)
+                .ignore_case(true)
+                .default_value("Uniform")
+                .possible_values(ArrivalProcess::value_variants().iter().filter_map(|a| a.to_possible_value()))
+                .help("How the intervals between requests are chosen: fixed (Uniform) or exponentially distributed (Poisson). Requests are sent at these times whether or not earlier requests have completed, and latencies are measured from them."),
+        )
+        .arg(
+            Arg::new("arrival-trace")
+                .long("arrival-trace")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("File with the send times of the requests, in seconds since the start of the run, one per line. Replaces -r, -n and --arrivals."),
+        )
         .arg(
             Arg::new("evaluate-max-rps")
                 .long("evaluate-max-rps")
@@ -317,9 +352,36 @@ async fn main() {
         .unwrap()
         .parse::<usize>()
         .unwrap();
-    let rps = matches.value_of("rps").unwrap().parse::<f64>().unwrap();
+    let rps = matches
+        .value_of("rps")
+        .map(|rps| rps.parse::<f64>().unwrap())
+        .unwrap_or_default();
     let rpms = (rps * 1000f64).floor() as usize;
 
+    let scenario = matches.value_of("scenario").map(|path| {
+        Scenario::load(Path::new(path)).unwrap_or_else(|err| {
+            panic!("{}", err);
+        })
+    });
+
+    let arrivals = match matches.value_of("arrival-trace") {
+        Some(path) => Arrivals::from_trace_file(Path::new(path)).unwrap_or_else(|err| {
+            panic!("{}", err);
+        }),
+        // case insensitive
+        None => match ArrivalProcess::from_str(
+            matches
+                .value_of("arrivals")
+                .expect("arrivals option not specified"),
+            true,
+        )
+        .expect("Failed to parse arrivals option.")
+        {
+            ArrivalProcess::Uniform => Arrivals::Uniform,
+            ArrivalProcess::Poisson => Arrivals::Poisson,
+        },
+    };
+
     let principal_id = matches
         .value_of("principal-id")
         .map(|x| PrincipalId::from_str(x).unwrap());
@@ -473,50 +535,6 @@ async fn main() {
                 eng.wait_for_all_agents_to_be_healthy().await;
             }
 
-            // use id of install canister if no id specified
-            let canister_id = if let Some(s) = matches.value_of("canister-id") {
-                let canister_id =
-                    CanisterId::try_from(PrincipalId::from_str(s).unwrap_or_else(|_| {
-                        panic!("Illegal value for option --canister-id: '{}'", s);
-                    }))
-                    .unwrap();
-                if let Some(wasm_file_path) = matches.value_of_os("canister").map(Path::new) {
-                    let mut install_succeeded = false;
-                    for url in install_endpoint {
-                        match canister::install_canister(
-                            http_client.clone(),
-                            sender.clone(),
-                            url,
-                            canister_id,
-                            Some(wasm_file_path),
-                        )
-                        .await
-                        {
-                            Ok(()) => {
-                                install_succeeded = true;
-                                break;
-                            }
-                            Err(err) => println!(
-                                "⚠️  Could not install canister at replica url {}. {}",
-                                url, err
-                            ),
-                        }
-                    }
-
-                    if !install_succeeded {
-                        panic!("Failed to install wasm to existing canister");
-                    }
-                }
-                canister_id
-            } else {
-                let wasm_file_path = matches.value_of_os("canister").map(Path::new);
-                canister::setup_canister(http_client, sender, install_endpoint, wasm_file_path)
-                    .await
-                    .unwrap_or_else(|err| {
-                        panic!("Failed to create canister: {}", err);
-                    })
-            };
-
             // case insensitive
             let chart_size = ChartSize::from_str(
                 matches
@@ -529,36 +547,103 @@ async fn main() {
             // Hold all summaries so we can serialize them later if needed
             let mut summaries: Vec<Summary> = Vec::new();
 
-            // Make sure to save the guard, see documentation for more information
-            println!(
-                "Running {:?} rps for {} seconds, req_type = {:?}",
-                rps, duration, request_type
-            );
+            if let Some(scenario) = scenario {
+                let plan =
+                    ScenarioPlan::new(&scenario, nonce.clone(), &arrivals).unwrap_or_else(|err| {
+                        panic!("{}", err);
+                    });
+                println!(
+                    "Running scenario with {} phases and {} steps, {} requests in total",
+                    scenario.phases.len(),
+                    plan.steps.len(),
+                    plan.schedule.len()
+                );
 
-            let facts = eng
-                .execute_rps(
-                    rpms,
-                    request_type,
-                    canister_method_name,
-                    duration,
-                    nonce.clone(),
-                    call_payload_size,
-                    call_payload,
-                    &canister_id,
-                    periodic_output,
-                    random_query_payload,
-                )
-                .await;
-
-            // Drop the engine with the hope that all client connections will be closed.
-            // Sometimes we may end up in situation where all file decriptors
-            // are consumed by the number of connections. We need a more
-            // sustainable solution where the file decriptors
-            // are not a bottleneck.
-            std::mem::drop(eng);
-            let summary = Summary::from_facts(&facts);
-            summaries.push(summary.clone());
-            println!("{}", summary.with_chart_size(chart_size));
+                let facts = eng.execute_scenario(&plan, periodic_output).await;
+                std::mem::drop(eng);
+                let step_names: Vec<String> =
+                    plan.steps.iter().map(|step| step.name.clone()).collect();
+                summaries = Summary::from_step_facts(&step_names, facts);
+                for summary in &summaries {
+                    println!("{}", summary.clone().with_chart_size(chart_size));
+                }
+            } else {
+                // use id of install canister if no id specified
+                let canister_id = if let Some(s) = matches.value_of("canister-id") {
+                    let canister_id =
+                        CanisterId::try_from(PrincipalId::from_str(s).unwrap_or_else(|_| {
+                            panic!("Illegal value for option --canister-id: '{}'", s);
+                        }))
+                        .unwrap();
+                    if let Some(wasm_file_path) = matches.value_of_os("canister").map(Path::new) {
+                        let mut install_succeeded = false;
+                        for url in install_endpoint {
+                            match canister::install_canister(
+                                http_client.clone(),
+                                sender.clone(),
+                                url,
+                                canister_id,
+                                Some(wasm_file_path),
+                            )
+                            .await
+                            {
+                                Ok(()) => {
+                                    install_succeeded = true;
+                                    break;
+                                }
+                                Err(err) => println!(
+                                    "⚠️  Could not install canister at replica url {}. {}",
+                                    url, err
+                                ),
+                            }
+                        }
+
+                        if !install_succeeded {
+                            panic!("Failed to install wasm to existing canister");
+                        }
+                    }
+                    canister_id
+                } else {
+                    let wasm_file_path = matches.value_of_os("canister").map(Path::new);
+                    canister::setup_canister(http_client, sender, install_endpoint, wasm_file_path)
+                        .await
+                        .unwrap_or_else(|err| {
+                            panic!("Failed to create canister: {}", err);
+                        })
+                };
+
+                // Make sure to save the guard, see documentation for more information
+                println!(
+                    "Running {:?} rps for {} seconds, req_type = {:?}",
+                    rps, duration, request_type
+                );
+
+                let facts = eng
+                    .execute_rps(
+                        rpms,
+                        &arrivals,
+                        request_type,
+                        canister_method_name,
+                        duration,
+                        nonce.clone(),
+                        call_payload_size,
+                        call_payload,
+                        &canister_id,
+                        periodic_output,
+                        random_query_payload,
+                    )
+                    .await;
+
+                // Drop the engine with the hope that all client connections will be closed.
+                // Sometimes we may end up in situation where all file decriptors
+                // are consumed by the number of connections. We need a more
+                // sustainable solution where the file decriptors
+                // are not a bottleneck.
+                std::mem::drop(eng);
+                let summary = Summary::from_facts(&facts);
+                summaries.push(summary.clone());
+                println!("{}", summary.with_chart_size(chart_size));
+            }
 
             if let Some(metrics) = metrics_runtime.take() {
                 std::mem::drop(metrics);
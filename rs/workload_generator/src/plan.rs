@@ -1,8 +1,13 @@
-use crate::RequestType;
+use crate::{
+    scenario::{CallKind, Payload, Phase, Scenario},
+    RequestType,
+};
 use byte_unit::Byte;
 use candid::Encode;
 use ic_types::CanisterId;
-use rand::RngCore;
+use rand::{Rng, RngCore};
+use rand_distr::Exp1;
+use std::{path::Path, time::Duration};
 
 #[derive(Clone)]
 pub struct Plan {
@@ -98,3 +103,367 @@ impl Plan {
         }
     }
 }
+
+/// Determines the times at which requests are sent. Requests are sent at
+/// these times regardless of whether earlier requests have completed, i.e.
+/// the load is open-loop.
+#[derive(Clone, Debug)]
+pub enum Arrivals {
+    /// Requests are sent at a fixed interval.
+    Uniform,
+    /// The intervals between requests are exponentially distributed, i.e.
+    /// requests arrive as a Poisson process.
+    Poisson,
+    /// Requests are sent at the given times, relative to the start of the run.
+    Trace(Vec<Duration>),
+}
+
+impl Arrivals {
+    /// Reads a trace of send times, in seconds since the start of the run,
+    /// one per line. Empty lines and lines starting with `#` are ignored.
+    pub fn from_trace_file(path: &Path) -> Result<Arrivals, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read arrival trace {}: {}", path.display(), err))?;
+        let mut times = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let secs = line
+                .parse::<f64>()
+                .ok()
+                .filter(|secs| secs.is_finite() && *secs >= 0.)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid send time {:?} on line {} of arrival trace {}",
+                        line,
+                        i + 1,
+                        path.display()
+                    )
+                })?;
+            times.push(Duration::from_secs_f64(secs));
+        }
+        times.sort();
+        Ok(Arrivals::Trace(times))
+    }
+
+    /// Returns the send times of `requests` requests issued at `rps` requests
+    /// per second, or the times of the trace. Poisson arrivals are sampled
+    /// from `rng`.
+    pub fn send_times<R: Rng>(&self, requests: usize, rps: f64, rng: &mut R) -> Vec<Duration> {
+        match self {
+            Arrivals::Uniform => (0..requests)
+                .map(|n| Duration::from_secs_f64(n as f64 / rps))
+                .collect(),
+            Arrivals::Poisson => {
+                let mut time = 0.;
+                (0..requests)
+                    .map(|_| {
+                        let send_time = Duration::from_secs_f64(time);
+                        let gap: f64 = rng.sample(Exp1);
+                        time += gap / rps;
+                        send_time
+                    })
+                    .collect()
+            }
+            Arrivals::Trace(times) => times.clone(),
+        }
+    }
+}
+
+/// The plan of a single step of a scenario.
+#[derive(Clone)]
+pub struct StepPlan {
+    pub name: String,
+    pub plan: Plan,
+    payload: Payload,
+}
+
+impl StepPlan {
+    pub fn generate_call(&self) -> EngineCall {
+        let method = self.plan.canister_method_name.clone();
+        let arg = self.payload.generate();
+        match self.plan.request_type {
+            RequestType::Update => EngineCall::Write { method, arg },
+            _ => EngineCall::Read { method, arg },
+        }
+    }
+}
+
+/// The plan of a scenario: the times, relative to the start of the run, at
+/// which requests are issued together with the step each of them executes.
+pub struct ScenarioPlan {
+    pub steps: Vec<StepPlan>,
+    pub schedule: Vec<(Duration, usize)>,
+}
+
+impl ScenarioPlan {
+    pub fn new(scenario: &Scenario, nonce: String, arrivals: &Arrivals) -> Result<Self, String> {
+        let weights: Vec<_> = scenario.steps.iter().map(|step| step.weight).collect();
+        let times = match arrivals {
+            Arrivals::Trace(times) => times.clone(),
+            _ => request_times(&scenario.phases, arrivals, &mut rand::thread_rng()),
+        };
+        let schedule: Vec<_> = times
+            .into_iter()
+            .zip(WeightedRoundRobin::new(weights))
+            .collect();
+
+        let mut steps = Vec::with_capacity(scenario.steps.len());
+        for (i, step) in scenario.steps.iter().enumerate() {
+            let request_type = match step.kind {
+                CallKind::Query => RequestType::Query,
+                CallKind::Update => RequestType::Update,
+            };
+            let plan = Plan::new(
+                schedule.iter().filter(|(_, s)| *s == i).count(),
+                nonce.clone(),
+                Byte::from_bytes(0),
+                vec![],
+                step.canister_id()?,
+                request_type,
+                step.method.clone(),
+            );
+            steps.push(StepPlan {
+                name: step.name.clone(),
+                plan,
+                payload: step.payload.to_payload()?,
+            });
+        }
+        Ok(Self { steps, schedule })
+    }
+}
+
+/// Returns the times at which requests are issued during the given phases.
+///
+/// The rate of a phase changes linearly from `r0` to `r1` over its duration
+/// `d`, so the expected number of requests issued until time `t` is
+/// `r0 * t + (r1 - r0) * t^2 / (2 * d)`. A request is issued whenever this
+/// number reaches the next arrival `k` of a process with rate 1, i.e. the
+/// next integer for uniform arrivals, or the next arrival of a Poisson
+/// process, which yields a Poisson process with the rate of the phase.
+fn request_times<R: Rng>(phases: &[Phase], arrivals: &Arrivals, rng: &mut R) -> Vec<Duration> {
+    let mut times = vec![];
+    let mut phase_start = 0.;
+    for phase in phases {
+        let duration = phase.duration_secs as f64;
+        let r0 = phase.rps;
+        let r1 = phase.end_rps.unwrap_or(phase.rps);
+        let expected_requests = (r0 + r1) / 2. * duration;
+        let arrival_counts: Vec<f64> = match arrivals {
+            Arrivals::Poisson => {
+                let mut counts = vec![];
+                let mut k: f64 = rng.sample(Exp1);
+                while k < expected_requests {
+                    counts.push(k);
+                    let gap: f64 = rng.sample(Exp1);
+                    k += gap;
+                }
+                counts
+            }
+            _ => (0..expected_requests.floor() as usize)
+                .map(|k| k as f64)
+                .collect(),
+        };
+        let a = (r1 - r0) / (2. * duration);
+        for k in arrival_counts {
+            let t = if a == 0. {
+                k / r0
+            } else {
+                // Clamp rounding errors of the discriminant around zero.
+                (-r0 + (r0 * r0 + 4. * a * k).max(0.).sqrt()) / (2. * a)
+            };
+            times.push(Duration::from_secs_f64(phase_start + t.clamp(0., duration)));
+        }
+        phase_start += duration;
+    }
+    times
+}
+
+/// Smooth weighted round-robin: yields step indices in proportion to their
+/// weights, interleaving the steps as evenly as possible.
+struct WeightedRoundRobin {
+    weights: Vec<i64>,
+    current: Vec<i64>,
+    total: i64,
+}
+
+impl WeightedRoundRobin {
+    fn new(weights: Vec<u32>) -> Self {
+        let weights: Vec<i64> = weights.into_iter().map(i64::from).collect();
+        Self {
+            current: vec![0; weights.len()],
+            total: weights.iter().sum(),
+            weights,
+        }
+    }
+}
+
+impl Iterator for WeightedRoundRobin {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        for (current, weight) in self.current.iter_mut().zip(&self.weights) {
+            *current += weight;
+        }
+        let (best, _) = self
+            .current
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, current)| **current)?;
+        self.current[best] -= self.total;
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn poisson_send_times_have_the_requested_rate() {
+        let requests = 10_000;
+        let rps = 200.;
+        let times = Arrivals::Poisson.send_times(requests, rps, &mut StdRng::seed_from_u64(42));
+
+        assert_eq!(times.len(), requests);
+        assert_eq!(times[0], Duration::ZERO);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        let mean_gap = times.last().unwrap().as_secs_f64() / (requests - 1) as f64;
+        assert!(
+            (mean_gap * rps - 1.).abs() < 0.05,
+            "mean gap {} does not match the rate {}",
+            mean_gap,
+            rps
+        );
+        // The gaps are exponentially distributed, so their standard deviation
+        // equals their mean, unlike the constant gaps of uniform arrivals.
+        let gaps: Vec<f64> = times
+            .windows(2)
+            .map(|w| (w[1] - w[0]).as_secs_f64())
+            .collect();
+        let variance = gaps.iter().map(|g| (g - mean_gap).powi(2)).sum::<f64>() / gaps.len() as f64;
+        assert!(
+            (variance.sqrt() / mean_gap - 1.).abs() < 0.05,
+            "gaps are not exponentially distributed"
+        );
+    }
+
+    #[test]
+    fn poisson_send_times_are_deterministic_for_a_seed() {
+        let sample =
+            |seed| Arrivals::Poisson.send_times(100, 10., &mut StdRng::seed_from_u64(seed));
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+    }
+
+    #[test]
+    fn trace_send_times_are_read_from_the_trace_file() {
+        let path = std::env::temp_dir().join(format!("arrival_trace_{}", std::process::id()));
+        std::fs::write(&path, "# seconds\n1.5\n\n0\n  0.25 \n").unwrap();
+        let arrivals = Arrivals::from_trace_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The trace determines the times, regardless of the requested count and rate.
+        assert_eq!(
+            arrivals.send_times(10, 1000., &mut StdRng::seed_from_u64(0)),
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(250),
+                Duration::from_millis(1500)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_trace_files_are_rejected() {
+        for (i, content) in ["1\nabc\n", "-1\n", "inf\n"].iter().enumerate() {
+            let path =
+                std::env::temp_dir().join(format!("arrival_trace_{}_{}", std::process::id(), i));
+            std::fs::write(&path, content).unwrap();
+            let result = Arrivals::from_trace_file(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{:?} was accepted", content);
+        }
+        assert!(Arrivals::from_trace_file(Path::new("/nonexistent/arrival_trace")).is_err());
+    }
+
+    fn phase(duration_secs: u64, rps: f64, end_rps: Option<f64>) -> Phase {
+        Phase {
+            name: "phase".to_string(),
+            duration_secs,
+            rps,
+            end_rps,
+        }
+    }
+
+    #[test]
+    fn uniform_request_times_follow_the_ramp() {
+        // The rate ramps up from 0 to 100 requests per second over 10
+        // seconds, so a quarter of the 500 requests are issued in the first
+        // half of the phase.
+        let times = request_times(
+            &[phase(10, 0., Some(100.))],
+            &Arrivals::Uniform,
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(times.len(), 500);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        assert!(*times.last().unwrap() <= Duration::from_secs(10));
+        let first_half = times
+            .iter()
+            .filter(|t| **t < Duration::from_secs(5))
+            .count();
+        assert_eq!(first_half, 125);
+    }
+
+    #[test]
+    fn request_times_of_later_phases_start_after_earlier_phases() {
+        let times = request_times(
+            &[phase(2, 10., None), phase(1, 20., Some(0.))],
+            &Arrivals::Uniform,
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(times.len(), 30);
+        for (k, t) in times[..20].iter().enumerate() {
+            assert!((t.as_secs_f64() - k as f64 / 10.).abs() < 1e-6);
+        }
+        assert!(times[20..]
+            .iter()
+            .all(|t| Duration::from_secs(2) <= *t && *t <= Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn poisson_request_times_follow_the_ramp() {
+        // 0 to 200 requests per second over 100 seconds: 10000 requests are
+        // expected, a quarter of them in the first half of the phase.
+        let times = request_times(
+            &[phase(100, 0., Some(200.))],
+            &Arrivals::Poisson,
+            &mut StdRng::seed_from_u64(42),
+        );
+        assert!((times.len() as f64 / 10_000. - 1.).abs() < 0.05);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        assert!(*times.last().unwrap() <= Duration::from_secs(100));
+        let first_half = times
+            .iter()
+            .filter(|t| **t < Duration::from_secs(50))
+            .count();
+        assert!((first_half as f64 / 2_500. - 1.).abs() < 0.1);
+    }
+
+    #[test]
+    fn weighted_round_robin_interleaves_steps_in_proportion_to_their_weights() {
+        let order: Vec<_> = WeightedRoundRobin::new(vec![3, 1, 0]).take(8).collect();
+        assert_eq!(order, vec![0, 0, 1, 0, 0, 0, 1, 0]);
+
+        let mut counts = [0; 3];
+        for step in WeightedRoundRobin::new(vec![5, 3, 2]).take(1000) {
+            counts[step] += 1;
+        }
+        assert_eq!(counts, [500, 300, 200]);
+    }
+}
@@ -0,0 +1,289 @@
+//! Scenario files describing mixed workloads.
+//!
+//! A scenario consists of a sequence of phases, each issuing requests at a
+//! constant rate or at a rate that changes linearly from `rps` to `end_rps`
+//! (e.g. to ramp up or down), and of a set of weighted steps. Every request
+//! executes one of the steps, chosen in proportion to the weights of the
+//! steps. Scenarios are written in TOML or, if the file ends in `.yaml` or
+//! `.yml`, in YAML:
+//!
+//! ```toml
+//! [[phases]]
+//! name = "ramp-up"
+//! duration_secs = 60
+//! rps = 10
+//! end_rps = 100
+//!
+//! [[phases]]
+//! name = "steady"
+//! duration_secs = 300
+//! rps = 100
+//!
+//! [[steps]]
+//! name = "read"
+//! canister_id = "rwlgt-iiaaa-aaaaa-aaaaa-cai"
+//! method = "read"
+//! kind = "query"
+//! weight = 3
+//! payload = { type = "random", size = "1KiB" }
+//!
+//! [[steps]]
+//! name = "greet"
+//! canister_id = "rrkah-fqaaa-aaaaa-aaaaq-cai"
+//! method = "greet"
+//! kind = "update"
+//! payload = { type = "candid", value = '("world")' }
+//! ```
+
+use byte_unit::Byte;
+use candid::IDLArgs;
+use ic_types::{CanisterId, PrincipalId};
+use rand::RngCore;
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path, str::FromStr};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub phases: Vec<Phase>,
+    pub steps: Vec<Step>,
+}
+
+/// A period of time during which requests are issued at a rate changing
+/// linearly from `rps` to `end_rps`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    pub name: String,
+    pub duration_secs: u64,
+    pub rps: f64,
+    /// The rate at the end of the phase; defaults to `rps`.
+    pub end_rps: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CallKind {
+    Query,
+    Update,
+}
+
+/// A canister call issued by a fraction of the requests of a scenario.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub name: String,
+    /// Canister ID in text format (xxxxx-xxx).
+    pub canister_id: String,
+    pub method: String,
+    pub kind: CallKind,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub payload: PayloadGenerator,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Generates the argument of the calls of a step.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PayloadGenerator {
+    /// An empty argument.
+    #[default]
+    Empty,
+    /// The bytes of the given hex string.
+    Hex { value: String },
+    /// The given Candid arguments in text format, e.g. `("hello", 42)`.
+    Candid { value: String },
+    /// `size` zero bytes, e.g. `size = "1KiB"`.
+    Zeros { size: String },
+    /// `size` random bytes, different for every call.
+    Random { size: String },
+}
+
+/// A [`PayloadGenerator`] with parsed and encoded values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Payload {
+    Fixed(Vec<u8>),
+    Random(usize),
+}
+
+impl Payload {
+    pub fn generate(&self) -> Vec<u8> {
+        match self {
+            Payload::Fixed(bytes) => bytes.clone(),
+            Payload::Random(size) => {
+                let mut payload = vec![0u8; *size];
+                rand::thread_rng().fill_bytes(&mut payload);
+                payload
+            }
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<usize, String> {
+    Byte::from_str(size.trim())
+        .map(|size| size.get_bytes() as usize)
+        .map_err(|err| format!("Invalid payload size {:?}: {}", size, err))
+}
+
+impl PayloadGenerator {
+    pub fn to_payload(&self) -> Result<Payload, String> {
+        match self {
+            PayloadGenerator::Empty => Ok(Payload::Fixed(vec![])),
+            PayloadGenerator::Hex { value } => hex::decode(value)
+                .map(Payload::Fixed)
+                .map_err(|err| format!("Invalid hex payload {:?}: {}", value, err)),
+            PayloadGenerator::Candid { value } => value
+                .parse::<IDLArgs>()
+                .and_then(|args| args.to_bytes())
+                .map(Payload::Fixed)
+                .map_err(|err| format!("Invalid Candid payload {:?}: {}", value, err)),
+            PayloadGenerator::Zeros { size } => Ok(Payload::Fixed(vec![0; parse_size(size)?])),
+            PayloadGenerator::Random { size } => Ok(Payload::Random(parse_size(size)?)),
+        }
+    }
+}
+
+impl Step {
+    pub fn canister_id(&self) -> Result<CanisterId, String> {
+        PrincipalId::from_str(&self.canister_id)
+            .ok()
+            .and_then(|principal_id| CanisterId::try_from(principal_id).ok())
+            .ok_or_else(|| {
+                format!(
+                    "Illegal canister ID '{}' in step '{}'",
+                    self.canister_id, self.name
+                )
+            })
+    }
+}
+
+impl Scenario {
+    /// Loads a scenario from a TOML or YAML file.
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read scenario {}: {}", path.display(), err))?;
+        let scenario: Scenario = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .map_err(|err| format!("Failed to parse scenario {}: {}", path.display(), err))?,
+            _ => toml::from_str(&content)
+                .map_err(|err| format!("Failed to parse scenario {}: {}", path.display(), err))?,
+        };
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.phases.is_empty() {
+            return Err("The scenario has no phases".to_string());
+        }
+        for phase in &self.phases {
+            let end_rps = phase.end_rps.unwrap_or(phase.rps);
+            if [phase.rps, end_rps]
+                .iter()
+                .any(|rps| !rps.is_finite() || *rps < 0.)
+            {
+                return Err(format!("Invalid rate in phase '{}'", phase.name));
+            }
+        }
+        if self.steps.iter().all(|step| step.weight == 0) {
+            return Err("The scenario has no steps with a positive weight".to_string());
+        }
+        let mut names = BTreeSet::new();
+        for step in &self.steps {
+            if !names.insert(&step.name) {
+                return Err(format!("Duplicate step name '{}'", step.name));
+            }
+            step.canister_id()?;
+            step.payload.to_payload()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: &str = r#"
+        [[steps]]
+        name = "read"
+        canister_id = "rwlgt-iiaaa-aaaaa-aaaaa-cai"
+        method = "read"
+        kind = "query"
+    "#;
+
+    const PHASE: &str = r#"
+        [[phases]]
+        name = "steady"
+        duration_secs = 10
+        rps = 100
+    "#;
+
+    fn validate(toml: &str) -> Result<(), String> {
+        toml::from_str::<Scenario>(toml).unwrap().validate()
+    }
+
+    #[test]
+    fn valid_scenario_is_accepted() {
+        assert_eq!(validate(&format!("{}{}", PHASE, STEP)), Ok(()));
+    }
+
+    #[test]
+    fn scenario_without_phases_is_rejected() {
+        assert!(validate(&format!("phases = []\n{}", STEP)).is_err());
+    }
+
+    #[test]
+    fn phases_with_invalid_rates_are_rejected() {
+        for rates in [
+            "rps = -1",
+            "rps = nan",
+            "rps = 10\nend_rps = inf",
+            "rps = 10\nend_rps = -5",
+        ] {
+            let phase = format!("[[phases]]\nname = \"p\"\nduration_secs = 10\n{}\n", rates);
+            assert!(
+                validate(&format!("{}{}", phase, STEP)).is_err(),
+                "{} was accepted",
+                rates
+            );
+        }
+    }
+
+    #[test]
+    fn scenario_without_positive_weights_is_rejected() {
+        assert!(validate(&format!("steps = []\n{}", PHASE)).is_err());
+        assert!(validate(&format!("{}{}weight = 0\n", PHASE, STEP)).is_err());
+    }
+
+    #[test]
+    fn duplicate_step_names_are_rejected() {
+        assert_eq!(
+            validate(&format!("{}{}{}", PHASE, STEP, STEP)),
+            Err("Duplicate step name 'read'".to_string())
+        );
+    }
+
+    #[test]
+    fn steps_with_invalid_canister_ids_or_payloads_are_rejected() {
+        let step = STEP.replace("rwlgt-iiaaa-aaaaa-aaaaa-cai", "not-a-canister");
+        assert!(validate(&format!("{}{}", PHASE, step)).is_err());
+        for payload in [
+            r#"{ type = "hex", value = "xyz" }"#,
+            r#"{ type = "candid", value = "(" }"#,
+            r#"{ type = "random", size = "lots" }"#,
+        ] {
+            let step = format!("{}payload = {}\n", STEP, payload);
+            assert!(
+                validate(&format!("{}{}", PHASE, step)).is_err(),
+                "{} was accepted",
+                payload
+            );
+        }
+    }
+}
@@ -1,12 +1,24 @@
 use crate::{chart::Chart, collector::RequestInfo, content_length::ContentLength, ChartSize};
+use hdrhistogram::Histogram;
 use std::time::Instant;
-use std::{cmp, collections::HashMap, fmt, time::Duration};
+use std::{
+    cmp,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    time::Duration,
+};
 
 use serde::Serialize;
 
 // Interval in seconds of rate end times that are grouped in the same bucket.
 const RATE_BUCKET_SIZE: usize = 5;
 
+// Number of significant decimal digits of the latency histograms.
+const HISTOGRAM_SIGNIFICANT_DIGITS: u8 = 3;
+
+// Percentiles reported for the latency histograms.
+const REPORTED_PERCENTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 99.99];
+
 trait ToMilliseconds {
     fn to_ms(&self) -> f64;
 }
@@ -39,10 +51,18 @@ impl From<MS> for Duration {
 #[derive(Debug)]
 pub struct Fact {
     status: u16,
+    // The time at which the request was scheduled to be sent. Latencies
+    // measured from this time include the delays of requests that could not
+    // be sent on time, i.e. they do not suffer from coordinated omission.
+    time_request_scheduled: Instant,
     time_request_start: Instant,
+    // For update calls only: the time at which the ingress message was
+    // accepted, if it was.
+    time_request_submitted: Option<Instant>,
     time_request_end: Instant,
     content_length: ContentLength,
     success: bool,
+    is_update: bool,
 }
 
 impl Fact {
@@ -60,12 +80,33 @@ impl Fact {
     ) -> Fact {
         Fact {
             status,
+            time_request_scheduled: time_request_start,
             time_request_start,
+            time_request_submitted: None,
             time_request_end,
             content_length,
             success,
+            is_update: false,
         }
     }
+
+    /// Sets the time at which the request was scheduled to be sent.
+    pub fn scheduled_at(mut self, time_request_scheduled: Instant) -> Fact {
+        self.time_request_scheduled = time_request_scheduled;
+        self
+    }
+
+    /// Marks the fact as one about an update call whose ingress message was
+    /// accepted at `time_request_submitted`, if it was.
+    pub fn update(mut self, time_request_submitted: Option<Instant>) -> Fact {
+        self.time_request_submitted = time_request_submitted;
+        self.is_update = true;
+        self
+    }
+
+    fn latency_since_scheduled(&self) -> Duration {
+        self.time_request_end - self.time_request_scheduled
+    }
 }
 impl RequestInfo for Fact {
     fn is_succ(&self) -> bool {
@@ -73,6 +114,19 @@ impl RequestInfo for Fact {
     }
 }
 
+/// A fact about a request executing the given step of a scenario.
+#[derive(Debug)]
+pub struct StepFact {
+    pub step: usize,
+    pub fact: Fact,
+}
+
+impl RequestInfo for StepFact {
+    fn is_succ(&self) -> bool {
+        self.fact.success
+    }
+}
+
 struct DurationStats {
     sorted: Vec<Duration>,
 }
@@ -167,9 +221,66 @@ impl DurationStats {
     }
 }
 
+/// Latency statistics computed with an HDR histogram.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencySummary {
+    count: u64,
+    min: Duration,
+    mean: Duration,
+    max: Duration,
+    /// The latencies at the percentiles in `REPORTED_PERCENTILES`, keyed by
+    /// the percentile, e.g. "99.9".
+    percentiles: BTreeMap<String, Duration>,
+}
+
+impl LatencySummary {
+    fn from_latencies(latencies: impl Iterator<Item = Duration>) -> LatencySummary {
+        // Latencies are recorded in microseconds; the histogram grows as needed.
+        let mut histogram = Histogram::<u64>::new(HISTOGRAM_SIGNIFICANT_DIGITS)
+            .expect("Failed to create latency histogram");
+        for latency in latencies {
+            histogram.saturating_record(latency.as_micros() as u64);
+        }
+        if histogram.is_empty() {
+            return LatencySummary::default();
+        }
+        LatencySummary {
+            count: histogram.len(),
+            min: Duration::from_micros(histogram.min()),
+            mean: Duration::from_micros(histogram.mean() as u64),
+            max: Duration::from_micros(histogram.max()),
+            percentiles: REPORTED_PERCENTILES
+                .iter()
+                .map(|p| {
+                    (
+                        p.to_string(),
+                        Duration::from_micros(histogram.value_at_percentile(*p)),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn fmt_row(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        write!(f, "  {:20} {:8}", name, self.count)?;
+        for p in REPORTED_PERCENTILES {
+            let latency = self
+                .percentiles
+                .get(&p.to_string())
+                .copied()
+                .unwrap_or_default();
+            write!(f, " {:>10.1}", latency.to_ms())?;
+        }
+        writeln!(f, " {:>10.1}", self.max.to_ms())
+    }
+}
+
 /// Represents the statistics around a given set of facts.
 #[derive(Debug, Clone, Serialize)]
 pub struct Summary {
+    /// The name of the scenario step the summary is about, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<String>,
     average: Duration,
     median: Duration,
     max: Duration,
@@ -181,6 +292,17 @@ pub struct Summary {
     latency_histogram: Vec<u32>,
     succ_rate_histogram: HashMap<usize, u32>,
     status_counts: HashMap<u16, u32>,
+    /// Latency of all successful requests, measured from the time at which
+    /// they were scheduled to be sent.
+    scheduled_latency: LatencySummary,
+    /// Latency of successful queries, measured from their scheduled send time.
+    query_latency: LatencySummary,
+    /// Time until the ingress messages of update calls were accepted,
+    /// measured from their scheduled send time.
+    ingress_submission_latency: LatencySummary,
+    /// Time until successful update calls completed, measured from their
+    /// scheduled send time.
+    ingress_completion_latency: LatencySummary,
     #[serde(skip_serializing)]
     chart_size: ChartSize,
 }
@@ -206,11 +328,31 @@ impl Summary {
             },
         );
 
+        let successful = || facts.iter().filter(|f| f.success);
         Summary {
             count,
             content_length,
             status_counts,
             succ_rate_histogram: Summary::get_succ_rate_histogram(facts),
+            scheduled_latency: LatencySummary::from_latencies(
+                successful().map(Fact::latency_since_scheduled),
+            ),
+            query_latency: LatencySummary::from_latencies(
+                successful()
+                    .filter(|f| !f.is_update)
+                    .map(Fact::latency_since_scheduled),
+            ),
+            ingress_submission_latency: LatencySummary::from_latencies(facts.iter().filter_map(
+                |f| {
+                    f.time_request_submitted
+                        .map(|submitted| submitted - f.time_request_scheduled)
+                },
+            )),
+            ingress_completion_latency: LatencySummary::from_latencies(
+                successful()
+                    .filter(|f| f.is_update)
+                    .map(Fact::latency_since_scheduled),
+            ),
             ..Summary::from_durations(&DurationStats::from_facts(facts))
         }
     }
@@ -225,6 +367,27 @@ impl Summary {
         self
     }
 
+    /// Returns the summary of all facts of a scenario followed by the
+    /// summaries of the facts of each step.
+    pub fn from_step_facts(step_names: &[String], facts: Vec<StepFact>) -> Vec<Summary> {
+        let mut facts_per_step: Vec<Vec<Fact>> = step_names.iter().map(|_| vec![]).collect();
+        for StepFact { step, fact } in facts {
+            facts_per_step[step].push(fact);
+        }
+        let mut step_summaries: Vec<Summary> = step_names
+            .iter()
+            .zip(&facts_per_step)
+            .map(|(name, facts)| Summary {
+                step: Some(name.clone()),
+                ..Summary::from_facts(facts)
+            })
+            .collect();
+        let all_facts: Vec<Fact> = facts_per_step.into_iter().flatten().collect();
+        let mut summaries = vec![Summary::from_facts(&all_facts)];
+        summaries.append(&mut step_summaries);
+        summaries
+    }
+
     fn get_succ_rate_histogram(facts: &[Fact]) -> HashMap<usize, u32> {
         let mut buckets = HashMap::new();
         let end_times = facts.iter().map(|f| (f.time_request_end, f.is_succ()));
@@ -275,6 +438,7 @@ impl Summary {
 
     fn zero() -> Summary {
         Summary {
+            step: None,
             average: Duration::new(0, 0),
             stddev: Duration::new(0, 0),
             median: Duration::new(0, 0),
@@ -286,6 +450,10 @@ impl Summary {
             latency_histogram: vec![0; 0],
             succ_rate_histogram: HashMap::new(),
             status_counts: HashMap::new(),
+            scheduled_latency: LatencySummary::default(),
+            query_latency: LatencySummary::default(),
+            ingress_submission_latency: LatencySummary::default(),
+            ingress_completion_latency: LatencySummary::default(),
             chart_size: ChartSize::Medium,
         }
     }
@@ -323,7 +491,10 @@ where
 
 impl fmt::Display for Summary {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Summary")?;
+        match &self.step {
+            Some(step) => writeln!(f, "Summary of step {}", step)?,
+            None => writeln!(f, "Summary")?,
+        }
         writeln!(
             f,
             "  Average:   {} ms (std: {} ms)",
@@ -352,6 +523,19 @@ impl fmt::Display for Summary {
             };
             writeln!(f, "  {:4}: {:10}   {}", k, v, desc)?;
         }
+        writeln!(f)?;
+        writeln!(f, "Latency since scheduled send time (ms):")?;
+        write!(f, "  {:20} {:>8}", "", "count")?;
+        for p in REPORTED_PERCENTILES {
+            write!(f, " {:>10}", format!("p{}", p))?;
+        }
+        writeln!(f, " {:>10}", "max")?;
+        self.scheduled_latency.fmt_row(f, "All")?;
+        self.query_latency.fmt_row(f, "Query")?;
+        self.ingress_submission_latency
+            .fmt_row(f, "Ingress submission")?;
+        self.ingress_completion_latency
+            .fmt_row(f, "Ingress completion")?;
         if self.chart_size != ChartSize::None {
             writeln!(f)?;
             writeln!(f, "Latency Percentiles (2% of requests per bar):")?;
@@ -364,3 +548,61 @@ impl fmt::Display for Summary {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rand_distr::Exp1;
+
+    // HDR histograms with 3 significant digits are accurate to 0.1%; allow
+    // for the microsecond rounding of the recorded values too.
+    fn assert_within(actual: Duration, low: Duration, high: Duration) {
+        let low = low.as_secs_f64() * (1. - 1e-3) - 1e-6;
+        let high = high.as_secs_f64() * (1. + 1e-3) + 1e-6;
+        assert!(
+            low <= actual.as_secs_f64() && actual.as_secs_f64() <= high,
+            "{:?} is not within [{}s, {}s]",
+            actual,
+            low,
+            high
+        );
+    }
+
+    #[test]
+    fn latency_summary_reports_the_percentiles_of_the_latencies() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut latencies: Vec<Duration> = (0..100_000)
+            .map(|_| {
+                let sample: f64 = rng.sample(Exp1);
+                Duration::from_micros((sample * 50_000.) as u64 + 1)
+            })
+            .collect();
+        let summary = LatencySummary::from_latencies(latencies.iter().copied());
+        latencies.sort();
+
+        assert_eq!(summary.count, latencies.len() as u64);
+        let (min, max) = (latencies[0], *latencies.last().unwrap());
+        assert_within(summary.min, min, min);
+        assert_within(summary.max, max, max);
+        let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        assert_within(summary.mean, mean, mean);
+        assert_eq!(summary.percentiles.len(), REPORTED_PERCENTILES.len());
+        for p in REPORTED_PERCENTILES {
+            // The rank of the percentile, up to rounding.
+            let rank = (p / 100. * latencies.len() as f64).round() as usize;
+            assert_within(
+                summary.percentiles[&p.to_string()],
+                latencies[rank.saturating_sub(2)],
+                latencies[rank.min(latencies.len() - 1)],
+            );
+        }
+    }
+
+    #[test]
+    fn latency_summary_of_no_latencies_is_empty() {
+        let summary = LatencySummary::from_latencies(std::iter::empty());
+        assert_eq!(summary.count, 0);
+        assert!(summary.percentiles.is_empty());
+    }
+}